@@ -0,0 +1,36 @@
+#!/bin/bash
+
+# Regenerate assets/oui from the IEEE MA-L (OUI) registry.
+# Run from the repository root; needs curl and python3.
+
+set -euo pipefail
+
+OUI_URL="https://standards-oui.ieee.org/oui/oui.csv"
+OUTPUT="assets/oui"
+
+tmp=$(mktemp)
+trap 'rm -f "$tmp"' EXIT
+
+curl -fsSL "$OUI_URL" -o "$tmp"
+
+{
+    sed -n '1,/^[^#]/{/^#/p}' "$OUTPUT"
+    python3 - "$tmp" <<'PY'
+import csv
+import sys
+
+with open(sys.argv[1], newline="", encoding="utf-8") as f:
+    rows = list(csv.reader(f))[1:]
+
+vendors = {}
+for row in rows:
+    if len(row) >= 3 and len(row[1]) == 6:
+        vendors[row[1].upper()] = " ".join(row[2].split())
+
+for prefix in sorted(vendors):
+    print(prefix, vendors[prefix])
+PY
+} > "$OUTPUT.new"
+
+mv "$OUTPUT.new" "$OUTPUT"
+echo "Wrote $(grep -vc '^#' "$OUTPUT") prefixes to $OUTPUT"
@@ -1,37 +1,162 @@
 // app.rs - Main application orchestration (with debug logging)
 use anyhow::Result;
-use crossbeam::channel::{self, Receiver, Sender};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
 use dashmap::DashMap;
 use log::{debug, error, info, warn};
+use pcap::{Activated, Error as PcapError};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use std::thread;
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant, SystemTime};
 
-use crate::filter::ConnectionFilter;
+use crate::firewall::{FirewallBackend, FirewallRule};
+use crate::ignore::{IgnoreList, PassiveDns};
+use crate::signatures::SignatureRegistry;
 
 use crate::network::{
-    capture::{CaptureConfig, PacketReader, setup_packet_capture},
-    merge::{create_connection_from_packet, merge_packet_into_connection},
-    parser::{PacketParser, ParsedPacket, ParserConfig},
-    platform::create_process_lookup_with_pktap_status,
+    capture::{
+        CaptureConfig, CapturedPacket, PacketReader, open_savefile, setup_packet_capture,
+        validate_bpf_filter,
+    },
+    coalesce::{DEFAULT_SERVER_PORTS, coalesce_flows, group_key},
+    dpi::{Classified, DpiJob, DpiWorkerPool},
+    fingerprint::SignatureTable,
+    geoip::GeoDatabase,
+    health::{HealthMetrics, HealthMonitor},
+    interfaces::{self, InterfaceChange, InterfaceStats, InterfaceStatsTracker},
+    mac::NeighborTable,
+    merge::{
+        MergeOptions, create_connection_from_packet, merge_dpi_result, merge_packet_into_connection,
+    },
+    parser::{OffloadCounting, PacketParser, ParsedPacket, ParserConfig, RouterMode},
+    platform::{ProcessLookup, ProcessUsage, create_process_lookup_with_pktap_status},
+    recorder::FlowRecorder,
+    resolver::HostnameResolver,
+    scan::{ScanAlert, ScanDetector, ScanThresholds},
     services::ServiceLookup,
-    types::{ApplicationProtocol, Connection, Protocol},
+    talkers,
+    types::{
+        ApplicationProtocol, CloseReason, Connection, GeoInfo, Protocol, ProtocolState, QuicInfo,
+        QuicPacketType, TcpState, quic_reassembly_bytes, quic_reassembly_rejected,
+    },
 };
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::{LazyLock, Mutex};
 
 /// Global QUIC connection ID to connection key mapping
-/// This allows tracking QUIC connections across connection ID changes
-static QUIC_CONNECTION_MAPPING: LazyLock<Mutex<HashMap<String, String>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
+/// This allows tracking QUIC connections across address and port changes
+static QUIC_CONNECTION_MAPPING: LazyLock<Mutex<QuicCidMap>> =
+    LazyLock::new(|| Mutex::new(QuicCidMap::default()));
+
+/// Shortest connection ID looked for in short-header packets. Their header
+/// doesn't give the ID's length, so shorter ones would match by chance.
+const MIN_SHORT_HEADER_CID_LEN: usize = 4;
+
+/// Connection IDs named in QUIC long headers, by the key of the connection
+/// that uses them.
+///
+/// The handshake shows both endpoints' IDs in the clear: the client's
+/// Initial is addressed to an ID it made up, and each side's long headers
+/// carry the ID it wants to be addressed by. Short-header packets that later
+/// arrive from a new address or port (NAT rebinding, a client moving between
+/// networks) still start with one of those IDs, so they're folded into the
+/// connection that announced it. IDs handed out later in NEW_CONNECTION_ID
+/// frames are encrypted, so a move that also switches to one of them still
+/// shows up as a new connection.
+#[derive(Debug, Default)]
+struct QuicCidMap {
+    keys: HashMap<Vec<u8>, String>,
+    /// Lengths of the IDs in `keys`, tried in turn on short headers
+    lengths: BTreeSet<usize>,
+}
+
+impl QuicCidMap {
+    /// Key of the connection a QUIC packet on the flow `key` belongs to,
+    /// remembering the IDs a long header names for that connection
+    fn resolve(&mut self, quic_info: &QuicInfo, key: &str) -> String {
+        if quic_info.packet_type == QuicPacketType::OneRtt {
+            let dcid = &quic_info.connection_id;
+            return self
+                .lengths
+                .iter()
+                .filter(|&&len| (MIN_SHORT_HEADER_CID_LEN..=dcid.len()).contains(&len))
+                .find_map(|&len| self.keys.get(&dcid[..len]))
+                .map_or_else(|| key.to_string(), String::clone);
+        }
+
+        let ids: Vec<&Vec<u8>> = [&quic_info.connection_id, &quic_info.source_connection_id]
+            .into_iter()
+            .filter(|id| !id.is_empty())
+            .collect();
+        let canonical = ids
+            .iter()
+            .find_map(|id| self.keys.get(*id))
+            .map_or_else(|| key.to_string(), String::clone);
+        for id in ids {
+            self.keys
+                .entry(id.clone())
+                .or_insert_with(|| canonical.clone());
+            self.lengths.insert(id.len());
+        }
+        canonical
+    }
+
+    /// Forget the IDs of connections `keep` returns false for
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.keys.retain(|_, key| keep(key));
+        self.lengths = self.keys.keys().map(Vec::len).collect();
+    }
+}
+
+/// Pending jobs per DPI worker before new jobs are dropped
+const DPI_QUEUE_CAPACITY: usize = 1024;
+
+/// Captured packets waiting for a processor before live capture drops new ones
+const PACKET_QUEUE_CAPACITY: usize = 16384;
+
+/// How long `start` lets the capture pipeline run before the first snapshot
+const WARM_UP_DURATION: Duration = Duration::from_millis(500);
+
+/// Idle time after which buffered QUIC CRYPTO fragments are discarded
+const QUIC_REASSEMBLY_STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// How often packet processors read this host's addresses again
+const LOCAL_ADDRESS_REFRESH: Duration = Duration::from_secs(30);
+
+/// Closed connections kept for the Closed tab
+const CLOSED_HISTORY_LEN: usize = 1000;
+
+/// Remote addresses whose locations are remembered before starting over
+const MAX_CACHED_LOCATIONS: usize = 16 * 1024;
+
+/// How often the interface list is checked for interfaces coming and going
+const INTERFACE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long `stop` waits for background threads to finish
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Requests from the UI to the process enrichment thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnrichmentCommand {
+    /// Rebuild the process lookup tables and enrich right away
+    RefreshNow,
+    /// Resolve the process of one connection (by key) right away
+    LookupProcess(String),
+}
 
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct Config {
     /// Network interface to capture from (None for default)
     pub interface: Option<String>,
+    /// Savefile to read packets from instead of capturing on an interface
+    pub read_file: Option<PathBuf>,
+    /// Put the interface in promiscuous mode to see other hosts' traffic
+    pub promiscuous: bool,
     /// Filter localhost connections
     pub filter_localhost: bool,
     /// UI refresh interval in milliseconds
@@ -40,16 +165,72 @@ pub struct Config {
     pub enable_dpi: bool,
     /// BPF filter for packet capture
     pub bpf_filter: Option<String>,
+    /// Maximum number of tracked connections before the oldest are evicted
+    pub max_connections: usize,
+    /// Longest a flow may sit idle before cleanup, capping the per-protocol timeouts
+    pub max_idle: Option<Duration>,
+    /// Count link-layer headers in byte totals, as seen on the wire
+    pub count_link_layer: bool,
+    /// Which flows count as forwarded between two other hosts
+    pub router_mode: RouterMode,
+    /// Whether TCP super-packets from segmentation offload count as one packet
+    pub offload_counting: OffloadCounting,
+    /// Parse the packets carried inside GRE and VXLAN tunnels
+    pub decapsulate_tunnels: bool,
+    /// Look up the names of remote hosts in the background
+    pub show_hostnames: bool,
+    /// Show where remote hosts are, from a GeoIP database
+    pub show_locations: bool,
+    /// MaxMind DB to locate remote hosts with (None to look in the usual places)
+    pub geoip_db: Option<PathBuf>,
+    /// Directory that recordings of single connections are saved to
+    pub record_dir: PathBuf,
+    /// UDP server ports whose per-exchange client flows are shown as one row
+    pub coalesce_ports: Vec<u16>,
+    /// Signature file replacing the built-in OS fingerprint table
+    pub os_signatures: Option<PathBuf>,
+    /// Rules for connections to hide from the view, totals and alerts
+    pub ignore_file: Option<PathBuf>,
+    /// End the run after this long
+    pub duration: Option<Duration>,
+    /// End the run once this many connections have been seen
+    pub stop_after_connections: Option<u64>,
+    /// When bursts of SYNs are reported as scans or floods
+    pub scan_thresholds: ScanThresholds,
+    /// Cipher suites flagged as weak on top of the built-in list
+    pub weak_cipher_suites: Vec<u16>,
+    /// Keep TCP keepalive probes out of rates and dim keepalive-only flows
+    pub keepalive_detection: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             interface: None,
+            read_file: None,
+            promiscuous: true,
             filter_localhost: true,
             refresh_interval: 1000,
             enable_dpi: true,
             bpf_filter: None, // No filter by default to see all packets
+            max_connections: 50_000,
+            max_idle: None,
+            count_link_layer: false,
+            router_mode: RouterMode::default(),
+            offload_counting: OffloadCounting::default(),
+            decapsulate_tunnels: false,
+            show_hostnames: false,
+            show_locations: true,
+            geoip_db: None,
+            record_dir: PathBuf::from("."),
+            coalesce_ports: DEFAULT_SERVER_PORTS.to_vec(),
+            os_signatures: None,
+            ignore_file: None,
+            duration: None,
+            stop_after_connections: None,
+            scan_thresholds: ScanThresholds::default(),
+            weak_cipher_suites: Vec::new(),
+            keepalive_detection: true,
         }
     }
 }
@@ -58,8 +239,23 @@ impl Default for Config {
 #[derive(Debug)]
 pub struct AppStats {
     pub packets_processed: AtomicU64,
+    /// Packets the kernel dropped because its capture buffer was full
     pub packets_dropped: AtomicU64,
+    /// Packets the kernel handed to the capture, as counted by libpcap
+    pub capture_received: AtomicU64,
+    /// Packets the interface or its driver dropped before the kernel saw them
+    pub interface_dropped: AtomicU64,
+    /// `packets_dropped` over the last second of capture
+    pub drops_per_sec: AtomicU64,
+    /// Packets captured but dropped because the processors fell behind
+    pub queue_dropped: AtomicU64,
     pub connections_tracked: AtomicU64,
+    /// Connections removed because the table hit `max_connections`
+    pub connections_evicted: AtomicU64,
+    /// Connections removed by the regular idle timeout cleanup
+    pub connections_expired: AtomicU64,
+    /// Connections added to the table since the start
+    pub connections_created: AtomicU64,
     pub last_update: RwLock<Instant>,
 }
 
@@ -68,12 +264,284 @@ impl Default for AppStats {
         Self {
             packets_processed: AtomicU64::new(0),
             packets_dropped: AtomicU64::new(0),
+            capture_received: AtomicU64::new(0),
+            interface_dropped: AtomicU64::new(0),
+            drops_per_sec: AtomicU64::new(0),
+            queue_dropped: AtomicU64::new(0),
             connections_tracked: AtomicU64::new(0),
+            connections_evicted: AtomicU64::new(0),
+            connections_expired: AtomicU64::new(0),
+            connections_created: AtomicU64::new(0),
             last_update: RwLock::new(Instant::now()),
         }
     }
 }
 
+/// Bytes transferred per hour of the week (Monday first), for the heatmap view
+#[derive(Debug)]
+pub struct TrafficHeatmap {
+    buckets: [[AtomicU64; 24]; 7],
+}
+
+impl Default for TrafficHeatmap {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU64::new(0))),
+        }
+    }
+}
+
+impl TrafficHeatmap {
+    /// Add bytes to the bucket for the given local time
+    pub fn record(&self, bytes: u64, at: DateTime<Local>) {
+        let day = at.weekday().num_days_from_monday() as usize;
+        let hour = at.hour() as usize;
+        self.buckets[day][hour].fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Copy the current bucket values (day-of-week x hour-of-day)
+    pub fn snapshot(&self) -> [[u64; 24]; 7] {
+        std::array::from_fn(|day| {
+            std::array::from_fn(|hour| self.buckets[day][hour].load(Ordering::Relaxed))
+        })
+    }
+}
+
+/// A connection that left the table, and why
+#[derive(Debug, Clone)]
+pub struct ClosedConnection {
+    pub connection: Connection,
+    pub reason: CloseReason,
+    pub closed_at: SystemTime,
+}
+
+/// Connections and bytes of one protocol over a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolTotals {
+    pub connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl ProtocolTotals {
+    fn add(&mut self, conn: &Connection) {
+        self.connections += 1;
+        self.bytes_sent += conn.bytes_sent;
+        self.bytes_received += conn.bytes_received;
+    }
+}
+
+/// What a run saw, printed when it ends after `--duration` or `--count`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    pub duration: Duration,
+    pub packets_processed: u64,
+    pub packets_dropped: u64,
+    /// Totals by protocol name, over closed and still open connections
+    pub protocols: BTreeMap<String, ProtocolTotals>,
+    /// Scan and attack alerts raised during the run
+    pub alerts_raised: usize,
+    /// The most recent of those alerts, oldest first
+    pub alerts: Vec<ScanAlert>,
+}
+
+impl RunSummary {
+    pub fn total(&self) -> ProtocolTotals {
+        self.protocols
+            .values()
+            .fold(ProtocolTotals::default(), |total, protocol| {
+                ProtocolTotals {
+                    connections: total.connections + protocol.connections,
+                    bytes_sent: total.bytes_sent + protocol.bytes_sent,
+                    bytes_received: total.bytes_received + protocol.bytes_received,
+                }
+            })
+    }
+
+    /// Plain-text report with raw byte counts, for scripts to parse
+    pub fn render(&self) -> String {
+        let total = self.total();
+        let mut out = format!(
+            "Duration: {:.1}s\n\
+             Packets: {} processed, {} dropped\n\
+             Connections: {}\n\
+             Bytes: {} sent, {} received\n\
+             Alerts: {}\n\n",
+            self.duration.as_secs_f64(),
+            self.packets_processed,
+            self.packets_dropped,
+            total.connections,
+            total.bytes_sent,
+            total.bytes_received,
+            self.alerts_raised
+        );
+        out.push_str(&format!(
+            "{:<10}{:>12}{:>16}{:>16}\n",
+            "Protocol", "Connections", "Bytes Sent", "Bytes Received"
+        ));
+        for (protocol, totals) in &self.protocols {
+            out.push_str(&format!(
+                "{:<10}{:>12}{:>16}{:>16}\n",
+                protocol, totals.connections, totals.bytes_sent, totals.bytes_received
+            ));
+        }
+        if !self.alerts.is_empty() {
+            out.push('\n');
+            for alert in &self.alerts {
+                out.push_str(&format!("Alert: {}\n", alert.describe()));
+            }
+        }
+        out
+    }
+}
+
+/// The most recently closed connections, oldest first
+#[derive(Debug, Default)]
+pub struct ClosedHistory {
+    entries: Mutex<VecDeque<ClosedConnection>>,
+    /// Every closed connection, including those that fell out of `entries`
+    totals: Mutex<BTreeMap<String, ProtocolTotals>>,
+}
+
+impl ClosedHistory {
+    /// Remember a connection removed from the table
+    pub fn record(&self, mut connection: Connection, reason: CloseReason) {
+        // Handshake buffers are of no use once the flow is gone
+        if let Some(dpi_info) = &mut connection.dpi_info {
+            match &mut dpi_info.application {
+                ApplicationProtocol::Quic(quic_info) => quic_info.crypto_reassembler = None,
+                ApplicationProtocol::Https(https_info) => https_info.handshake = None,
+                _ => {}
+            }
+        }
+
+        self.totals
+            .lock()
+            .unwrap()
+            .entry(connection.protocol.to_string())
+            .or_default()
+            .add(&connection);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == CLOSED_HISTORY_LEN {
+            entries.pop_front();
+        }
+        entries.push_back(ClosedConnection {
+            connection,
+            reason,
+            closed_at: SystemTime::now(),
+        });
+    }
+
+    /// Copy the history, most recently closed first
+    pub fn recent(&self) -> Vec<ClosedConnection> {
+        self.entries.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// Totals by protocol of every connection recorded so far
+    pub fn totals(&self) -> BTreeMap<String, ProtocolTotals> {
+        self.totals.lock().unwrap().clone()
+    }
+}
+
+/// Immutable view of the connection table, produced once per refresh.
+///
+/// The snapshot provider publishes a new `Arc<ConnectionSnapshot>` every
+/// refresh interval, so the UI and exporters can hold on to it with a cheap
+/// `Arc` clone instead of deep-copying every connection on each frame.
+#[derive(Debug)]
+pub struct ConnectionSnapshot {
+    /// Active connections, sorted by creation time
+    pub connections: Vec<Connection>,
+    /// Flows folded into a coalesced row, by the row's key
+    pub subflows: HashMap<String, Vec<Connection>>,
+    /// Number of connections tracked before filtering
+    pub total_connections: usize,
+    /// Packets processed when the snapshot was taken
+    pub packets_processed: u64,
+    /// Packets dropped when the snapshot was taken
+    pub packets_dropped: u64,
+    /// Packets the kernel handed to the capture when the snapshot was taken
+    pub capture_received: u64,
+    /// Packets the interface dropped when the snapshot was taken
+    pub interface_dropped: u64,
+    /// Packets the kernel dropped over the last second
+    pub drops_per_sec: u64,
+    /// Packets dropped because the processors fell behind the capture
+    pub queue_dropped: u64,
+    /// Connections evicted because the table was full
+    pub connections_evicted: u64,
+    /// Connections removed by the cleanup thread after going idle or closing
+    pub connections_expired: u64,
+    /// DPI jobs dropped because the worker pool was saturated
+    pub dpi_jobs_dropped: u64,
+    /// Bytes buffered for QUIC CRYPTO frame reassembly
+    pub quic_reassembly_bytes: usize,
+    /// QUIC CRYPTO fragments rejected by the global reassembly budget
+    pub quic_reassembly_rejected: u64,
+    /// New-connection rate and TCP state counts
+    pub health: HealthMetrics,
+    /// Scans and floods detected so far, newest last
+    pub scan_alerts: Vec<ScanAlert>,
+    /// When the snapshot was taken
+    pub taken_at: Instant,
+}
+
+impl Default for ConnectionSnapshot {
+    fn default() -> Self {
+        Self {
+            connections: Vec::new(),
+            subflows: HashMap::new(),
+            total_connections: 0,
+            packets_processed: 0,
+            packets_dropped: 0,
+            capture_received: 0,
+            interface_dropped: 0,
+            drops_per_sec: 0,
+            queue_dropped: 0,
+            connections_evicted: 0,
+            connections_expired: 0,
+            dpi_jobs_dropped: 0,
+            quic_reassembly_bytes: 0,
+            quic_reassembly_rejected: 0,
+            health: HealthMetrics::default(),
+            scan_alerts: Vec::new(),
+            taken_at: Instant::now(),
+        }
+    }
+}
+
+impl ConnectionSnapshot {
+    /// Share of TCP packets that were retransmissions, as an estimate of
+    /// packet loss. Ignored connections are left out.
+    pub fn packet_loss_rate(&self) -> f64 {
+        let (retransmits, packets) = self
+            .connections
+            .iter()
+            .filter(|conn| conn.protocol == Protocol::TCP && !conn.ignored)
+            .fold((0, 0), |(retransmits, packets), conn| {
+                (
+                    retransmits + conn.retransmit_count,
+                    packets + conn.packets_sent + conn.packets_received,
+                )
+            });
+        if packets == 0 {
+            0.0
+        } else {
+            retransmits as f64 / packets as f64
+        }
+    }
+
+    /// Whether `conn` is one of the flows folded into a coalesced row
+    pub fn is_subflow(&self, conn: &Connection) -> bool {
+        conn.subflow_count == 0
+            && self
+                .subflows
+                .get(&group_key(conn))
+                .is_some_and(|flows| flows.iter().any(|flow| flow.key() == conn.key()))
+    }
+}
+
 /// Main application state
 pub struct App {
     /// Configuration
@@ -83,7 +551,7 @@ pub struct App {
     should_stop: Arc<AtomicBool>,
 
     /// Current connections snapshot for UI
-    connections_snapshot: Arc<RwLock<Vec<Connection>>>,
+    connections_snapshot: Arc<RwLock<Arc<ConnectionSnapshot>>>,
 
     /// Service name lookup
     service_lookup: Arc<ServiceLookup>,
@@ -94,6 +562,12 @@ pub struct App {
     /// Loading state
     is_loading: Arc<AtomicBool>,
 
+    /// Wakes the snapshot provider before its refresh interval elapses, to
+    /// publish early or to stop. A request carrying a sender is answered on
+    /// it once the new snapshot is published.
+    snapshot_tx: Sender<Option<Sender<()>>>,
+    snapshot_rx: Receiver<Option<Sender<()>>>,
+
     /// Current network interface name
     current_interface: Arc<RwLock<Option<String>>>,
 
@@ -102,6 +576,67 @@ pub struct App {
 
     /// Whether PKTAP is active (macOS only) - used to disable process enrichment
     pktap_active: Arc<AtomicBool>,
+
+    /// Commands for the process enrichment thread. Bounded and only used with
+    /// `try_send`, so the UI never waits on a slow process lookup.
+    enrichment_tx: Sender<EnrichmentCommand>,
+    enrichment_rx: Receiver<EnrichmentCommand>,
+
+    /// BPF filters for the capture thread to switch to
+    bpf_filter_tx: Sender<String>,
+    bpf_filter_rx: Receiver<String>,
+
+    /// Messages from the background threads for the status bar
+    notice_tx: Sender<String>,
+    notice_rx: Receiver<String>,
+
+    /// Hourly traffic totals for the heatmap view
+    heatmap: Arc<TrafficHeatmap>,
+
+    /// Connections removed from the table, with the reason
+    closed: Arc<ClosedHistory>,
+
+    /// MAC addresses of LAN hosts seen in Ethernet headers and ARP
+    neighbors: Arc<NeighborTable>,
+
+    /// Sliding-window SYN counters for scan and flood detection
+    scans: Arc<ScanDetector>,
+
+    /// Connections hidden from the view, the totals and scan detection
+    ignore: Arc<IgnoreList>,
+
+    /// Custom protocol signatures, shared with the packet processors
+    signatures: Arc<SignatureRegistry>,
+
+    /// TCP stack signatures for guessing remote operating systems
+    os_signatures: Arc<SignatureTable>,
+
+    /// Database remote hosts are located with, when one was found
+    geoip: Option<Arc<GeoDatabase>>,
+
+    /// Workers running DPI off the packet path (None when DPI is disabled)
+    dpi_pool: Option<Arc<DpiWorkerPool>>,
+
+    /// Connections whose packets are being saved to pcap files
+    recorder: Arc<FlowRecorder>,
+
+    /// How packets and DPI results are folded into connections
+    merge_options: Arc<MergeOptions>,
+
+    /// Firewall rules generated from the UI, in creation order
+    generated_rules: RwLock<Vec<FirewallRule>>,
+
+    /// Interface counters last read for the Interfaces tab
+    interface_stats: Mutex<InterfaceStatsTracker>,
+
+    /// The connection table shared by the background threads
+    connections: Arc<DashMap<String, Connection>>,
+
+    /// When `start` was called
+    started_at: Option<Instant>,
+
+    /// Background threads, joined by `stop`
+    threads: Mutex<Vec<JoinHandle<()>>>,
 }
 
 impl App {
@@ -113,25 +648,121 @@ impl App {
             ServiceLookup::with_defaults()
         });
 
+        let signatures = if config.enable_dpi {
+            SignatureRegistry::load_default()
+        } else {
+            SignatureRegistry::new()
+        };
+        if !signatures.is_empty() {
+            info!("Loaded {} custom signature(s)", signatures.len());
+        }
+        let signatures = Arc::new(signatures);
+
+        let os_signatures = match &config.os_signatures {
+            Some(path) => {
+                let table = SignatureTable::from_file(path)?;
+                if table.is_empty() {
+                    warn!(
+                        "{} has no OS signatures, OS guessing is off",
+                        path.display()
+                    );
+                }
+                info!(
+                    "Loaded {} OS signatures from {}",
+                    table.len(),
+                    path.display()
+                );
+                table
+            }
+            None => SignatureTable::builtin(),
+        };
+
+        let geoip = match &config.geoip_db {
+            _ if !config.show_locations => None,
+            Some(path) => GeoDatabase::open(path)
+                .inspect_err(|e| warn!("Continuing without GeoIP: {:#}", e))
+                .ok(),
+            None => GeoDatabase::find_default().map(|(path, db)| {
+                info!("Using GeoIP database {}", path.display());
+                db
+            }),
+        };
+
+        let ignore = match &config.ignore_file {
+            Some(path) => {
+                let list = IgnoreList::from_file(path)?;
+                info!("Loaded {} ignore rules from {}", list.len(), path.display());
+                list
+            }
+            None => IgnoreList::default(),
+        };
+
+        let dpi_pool = config.enable_dpi.then(|| {
+            let workers = thread::available_parallelism()
+                .map(|n| n.get() / 2)
+                .unwrap_or(2)
+                .clamp(1, 4);
+            info!("Starting {} DPI workers", workers);
+            Arc::new(DpiWorkerPool::new(
+                workers,
+                DPI_QUEUE_CAPACITY,
+                Arc::clone(&signatures),
+            ))
+        });
+
+        let (enrichment_tx, enrichment_rx) = channel::bounded(16);
+        let (snapshot_tx, snapshot_rx) = channel::bounded(1);
+        let (bpf_filter_tx, bpf_filter_rx) = channel::unbounded();
+        let (notice_tx, notice_rx) = channel::unbounded();
+        let scans = Arc::new(ScanDetector::new(config.scan_thresholds.clone()));
+        let recorder = Arc::new(FlowRecorder::new(config.record_dir.clone()));
+        let merge_options = Arc::new(MergeOptions {
+            weak_cipher_suites: config.weak_cipher_suites.iter().copied().collect(),
+            keepalive_detection: config.keepalive_detection,
+        });
+
         Ok(Self {
             config,
             should_stop: Arc::new(AtomicBool::new(false)),
-            connections_snapshot: Arc::new(RwLock::new(Vec::new())),
+            connections_snapshot: Arc::new(RwLock::new(Arc::new(ConnectionSnapshot::default()))),
             service_lookup: Arc::new(service_lookup),
             stats: Arc::new(AppStats::default()),
             is_loading: Arc::new(AtomicBool::new(true)),
+            snapshot_tx,
+            snapshot_rx,
             current_interface: Arc::new(RwLock::new(None)),
             linktype: Arc::new(RwLock::new(None)),
             pktap_active: Arc::new(AtomicBool::new(false)),
+            enrichment_tx,
+            enrichment_rx,
+            bpf_filter_tx,
+            bpf_filter_rx,
+            notice_tx,
+            notice_rx,
+            heatmap: Arc::new(TrafficHeatmap::default()),
+            closed: Arc::new(ClosedHistory::default()),
+            neighbors: Arc::new(NeighborTable::default()),
+            scans,
+            ignore: Arc::new(ignore),
+            signatures,
+            os_signatures: Arc::new(os_signatures),
+            geoip: geoip.map(Arc::new),
+            dpi_pool,
+            recorder,
+            merge_options,
+            generated_rules: RwLock::new(Vec::new()),
+            interface_stats: Mutex::new(InterfaceStatsTracker::default()),
+            connections: Arc::new(DashMap::new()),
+            started_at: None,
+            threads: Mutex::new(Vec::new()),
         })
     }
 
     /// Start all background threads
     pub fn start(&mut self) -> Result<()> {
         info!("Starting network monitor application");
-
-        // Create shared connection map
-        let connections: Arc<DashMap<String, Connection>> = Arc::new(DashMap::new());
+        self.started_at = Some(Instant::now());
+        let connections = Arc::clone(&self.connections);
 
         // Start packet capture pipeline
         self.start_packet_capture_pipeline(connections.clone())?;
@@ -148,13 +779,35 @@ impl App {
         // Start rate refresh thread
         self.start_rate_refresh_thread(connections)?;
 
-        // Mark loading as complete after a short delay
-        let is_loading = Arc::clone(&self.is_loading);
-        thread::spawn(move || {
-            thread::sleep(Duration::from_millis(500));
-            is_loading.store(false, Ordering::Relaxed);
-        });
+        // A savefile doesn't care about interfaces coming and going
+        if self.config.read_file.is_none() {
+            self.start_interface_watcher();
+        }
 
+        // Capture the initial burst of traffic so the first frame isn't empty
+        self.warm_up(WARM_UP_DURATION)?;
+
+        Ok(())
+    }
+
+    /// Let the capture pipeline run for `duration`, then publish a snapshot right away
+    /// instead of waiting for the next refresh interval. Marks loading as complete.
+    pub fn warm_up(&self, duration: Duration) -> Result<()> {
+        thread::sleep(duration);
+
+        let (published_tx, published_rx) = channel::bounded(1);
+        if self.snapshot_tx.try_send(Some(published_tx)).is_ok() {
+            // Don't hold up startup if the snapshot provider is busy
+            let _ = published_rx.recv_timeout(Duration::from_millis(500));
+        }
+
+        self.is_loading.store(false, Ordering::Relaxed);
+        info!(
+            "Warm-up finished after {:?}: {} connections, {} packets",
+            duration,
+            self.get_snapshot().total_connections,
+            self.stats.packets_processed.load(Ordering::Relaxed)
+        );
         Ok(())
     }
 
@@ -164,11 +817,14 @@ impl App {
         connections: Arc<DashMap<String, Connection>>,
     ) -> Result<()> {
         // Create packet channel
-        let (packet_tx, packet_rx) = channel::unbounded();
+        let (packet_tx, packet_rx) = channel::bounded(PACKET_QUEUE_CAPACITY);
 
         // Start capture thread
         self.start_capture_thread(packet_tx)?;
 
+        // Merge results coming back from the DPI workers
+        self.start_dpi_merger(connections.clone());
+
         // Start multiple packet processing threads
         let num_processors = thread::available_parallelism()
             .map(|n| n.get())
@@ -183,9 +839,10 @@ impl App {
     }
 
     /// Start packet capture thread
-    fn start_capture_thread(&self, packet_tx: Sender<Vec<u8>>) -> Result<()> {
+    fn start_capture_thread(&self, packet_tx: Sender<CapturedPacket>) -> Result<()> {
         let capture_config = CaptureConfig {
             interface: self.config.interface.clone(),
+            promiscuous: self.config.promiscuous,
             filter: self.config.bpf_filter.clone(),
             ..Default::default()
         };
@@ -195,8 +852,46 @@ impl App {
         let current_interface = Arc::clone(&self.current_interface);
         let linktype_storage = Arc::clone(&self.linktype);
         let _pktap_active = Arc::clone(&self.pktap_active);
+        let bpf_filters = self.bpf_filter_rx.clone();
+        let notices = self.notice_tx.clone();
+        let read_file = self.config.read_file.clone();
+
+        self.track(thread::spawn(move || {
+            if let Some(path) = read_file {
+                match open_savefile(&path, capture_config.filter.as_deref()) {
+                    Ok((capture, linktype)) => {
+                        *current_interface.write().unwrap() = Some(path.display().to_string());
+                        *linktype_storage.write().unwrap() = Some(linktype);
+                        info!(
+                            "Reading packets from {} (linktype: {})",
+                            path.display(),
+                            linktype
+                        );
+                        // Nothing is lost by waiting for the processors here
+                        match read_packets(
+                            PacketReader::replay(capture),
+                            &packet_tx,
+                            true,
+                            &should_stop,
+                            &stats,
+                            &bpf_filters,
+                        ) {
+                            Ok(packets_read) => {
+                                info!("Read {} packets from {}", packets_read, path.display())
+                            }
+                            Err(e) => {
+                                let message =
+                                    format!("Stopped reading {}: {:#}", path.display(), e);
+                                error!("{}", message);
+                                let _ = notices.send(message);
+                            }
+                        }
+                    }
+                    Err(e) => error!("Failed to read {}: {}", path.display(), e),
+                }
+                return;
+            }
 
-        thread::spawn(move || {
             match setup_packet_capture(capture_config) {
                 Ok((capture, device_name, linktype)) => {
                     // Store the actual interface name and linktype being used
@@ -217,67 +912,24 @@ impl App {
                         "Packet capture started successfully on interface: {} (linktype: {})",
                         device_name, linktype
                     );
-                    let mut reader = PacketReader::new(capture);
-                    let mut packets_read = 0u64;
-                    let mut last_log = Instant::now();
-                    let mut last_stats_check = Instant::now();
-
-                    loop {
-                        if should_stop.load(Ordering::Relaxed) {
-                            info!("Capture thread stopping");
-                            break;
-                        }
-
-                        match reader.next_packet() {
-                            Ok(Some(packet)) => {
-                                packets_read += 1;
-
-                                // Log first packet immediately
-                                if packets_read == 1 {
-                                    info!("First packet captured! Size: {} bytes", packet.len());
-                                }
-
-                                // Log every 10000 packets or every 5 seconds
-                                if packets_read.is_multiple_of(10000)
-                                    || last_log.elapsed() > Duration::from_secs(5)
-                                {
-                                    info!("Read {} packets so far", packets_read);
-                                    last_log = Instant::now();
-                                }
-
-                                if packet_tx.send(packet).is_err() {
-                                    warn!("Packet channel closed");
-                                    break;
-                                }
-                            }
-                            Ok(None) => {
-                                // Timeout - check stats every second
-                                if last_stats_check.elapsed() > Duration::from_secs(1) {
-                                    if let Ok(capture_stats) = reader.stats() {
-                                        if capture_stats.received > 0 {
-                                            debug!(
-                                                "Capture stats - Received: {}, Dropped: {}",
-                                                capture_stats.received, capture_stats.dropped
-                                            );
-                                        }
-                                        stats
-                                            .packets_dropped
-                                            .store(capture_stats.dropped as u64, Ordering::Relaxed);
-                                    }
-                                    last_stats_check = Instant::now();
-                                }
-                            }
-                            Err(e) => {
-                                error!("Capture error: {}", e);
-                                break;
-                            }
+                    match read_packets(
+                        PacketReader::new(capture),
+                        &packet_tx,
+                        false,
+                        &should_stop,
+                        &stats,
+                        &bpf_filters,
+                    ) {
+                        Ok(packets_read) => info!(
+                            "Capture thread exiting, total packets read: {}",
+                            packets_read
+                        ),
+                        Err(e) => {
+                            let message = format!("Capture on {} stopped: {:#}", device_name, e);
+                            error!("{}", message);
+                            let _ = notices.send(message);
                         }
                     }
-
-                    info!(
-                        "Capture thread exiting, total packets read: {}",
-                        packets_read
-                    );
                 }
                 Err(e) => {
                     error!("Failed to start packet capture: {}", e);
@@ -287,7 +939,7 @@ impl App {
                     warn!("Application will run in process-only mode");
                 }
             }
-        });
+        }));
 
         Ok(())
     }
@@ -296,30 +948,72 @@ impl App {
     fn start_packet_processor(
         &self,
         id: usize,
-        packet_rx: Receiver<Vec<u8>>,
+        packet_rx: Receiver<CapturedPacket>,
         connections: Arc<DashMap<String, Connection>>,
     ) {
         let should_stop = Arc::clone(&self.should_stop);
         let stats = Arc::clone(&self.stats);
         let linktype_storage = Arc::clone(&self.linktype);
-        let parser_config = ParserConfig {
+        let current_interface = Arc::clone(&self.current_interface);
+        let max_connections = self.config.max_connections;
+        let heatmap = Arc::clone(&self.heatmap);
+        let closed = Arc::clone(&self.closed);
+        let neighbors = Arc::clone(&self.neighbors);
+        let scans = Arc::clone(&self.scans);
+        let ignore = Arc::clone(&self.ignore);
+        let dpi_pool = self.dpi_pool.clone();
+        let recorder = Arc::clone(&self.recorder);
+        let merge_options = Arc::clone(&self.merge_options);
+        let mut parser_config = ParserConfig {
             enable_dpi: self.config.enable_dpi,
+            signatures: Arc::clone(&self.signatures),
+            defer_dpi: dpi_pool.is_some(),
+            count_link_layer: self.config.count_link_layer,
+            router_mode: self.config.router_mode,
+            offload_counting: self.config.offload_counting,
+            decapsulate_tunnels: self.config.decapsulate_tunnels,
+            os_signatures: Arc::clone(&self.os_signatures),
             ..Default::default()
         };
-
-        thread::spawn(move || {
+        // Classified flows stop costing a payload copy after their first
+        // few packets
+        let dpi_packet_limit = parser_config.dpi_packet_limit as u64;
+        let table = Arc::clone(&connections);
+        parser_config.wants_dpi = Some(Arc::new(move |key: &str| {
+            table.get(key).is_none_or(|conn| {
+                conn.dpi_info.is_none()
+                    || conn.packets_sent + conn.packets_received < dpi_packet_limit
+            })
+        }));
+
+        self.track(thread::spawn(move || {
             info!("Packet processor {} started", id);
 
             // Wait for linktype to be available
-            let parser = loop {
+            let mut parser = loop {
                 if let Some(linktype) = *linktype_storage.read().unwrap() {
-                    break PacketParser::with_config(parser_config.clone()).with_linktype(linktype);
+                    // Set alongside the linktype; a savefile's path has no MTU
+                    let mut parser_config = parser_config.clone();
+                    if let Some(mtu) = current_interface
+                        .read()
+                        .unwrap()
+                        .as_deref()
+                        .and_then(interfaces::mtu)
+                    {
+                        parser_config.mtu = mtu as usize;
+                    }
+                    break PacketParser::with_config(parser_config).with_linktype(linktype);
+                }
+                // Capture setup failed, so no linktype is coming
+                if should_stop.load(Ordering::Relaxed) {
+                    return;
                 }
                 thread::sleep(Duration::from_millis(10));
             };
             let mut batch = Vec::new();
             let mut total_processed = 0u64;
             let mut last_log = Instant::now();
+            let mut addresses_read = Instant::now();
 
             loop {
                 if should_stop.load(Ordering::Relaxed) {
@@ -327,6 +1021,12 @@ impl App {
                     break;
                 }
 
+                // Which end of a flow is this host follows address changes
+                if addresses_read.elapsed() > LOCAL_ADDRESS_REFRESH {
+                    parser.refresh_local_addresses();
+                    addresses_read = Instant::now();
+                }
+
                 // Collect packets in batches
                 batch.clear();
                 let deadline = Instant::now() + Duration::from_millis(10);
@@ -340,12 +1040,64 @@ impl App {
 
                 // Process batch
                 let mut parsed_count = 0;
-                for packet_data in &batch {
-                    if let Some(parsed) = parser.parse_packet(packet_data) {
-                        update_connection(&connections, parsed, &stats);
+                let mut batch_bytes = 0u64;
+                let mut batch_time = None;
+                for packet in &batch {
+                    if let Some(mut parsed) = parser.parse_packet(
+                        &packet.data,
+                        packet.wire_len,
+                        packet.timestamp,
+                        packet.instant,
+                    ) {
+                        batch_bytes += parsed.packet_len as u64;
+                        batch_time = batch_time.max(Some(parsed.timestamp));
+                        let dpi_job = parsed.dpi_payload.take().map(|payload| DpiJob {
+                            connection_key: parsed.connection_key.clone(),
+                            protocol: parsed.protocol,
+                            local_port: parsed.local_addr.port(),
+                            remote_port: parsed.remote_addr.port(),
+                            is_outgoing: parsed.is_outgoing,
+                            tcp_seq: parsed.tcp_seq,
+                            payload,
+                            instant: parsed.instant,
+                        });
+                        resolve_remote_mac(&neighbors, &mut parsed);
+                        let alerts_muted = !ignore.is_empty()
+                            && (ignore.mutes_alerts(&parsed)
+                                || connections
+                                    .get(&parsed.connection_key)
+                                    .is_some_and(|conn| conn.alerts_muted));
+                        let (scan, alerts) = if alerts_muted {
+                            (None, Vec::new())
+                        } else {
+                            scans.observe(&parsed)
+                        };
+                        parsed.scan = scan;
+                        recorder.write(&parsed.connection_key, packet);
+                        update_connection(
+                            &connections,
+                            parsed,
+                            &stats,
+                            &closed,
+                            max_connections,
+                            &merge_options,
+                        );
+                        // The flows that led up to an alert were let through untagged
+                        for alert in &alerts {
+                            tag_scan_flows(&connections, alert);
+                        }
+                        // Queue DPI after the connection exists so the result has a target
+                        if let (Some(pool), Some(job)) = (&dpi_pool, dpi_job) {
+                            pool.submit(job);
+                        }
                         parsed_count += 1;
                     }
                 }
+                if let Some(batch_time) = batch_time
+                    && batch_bytes > 0
+                {
+                    heatmap.record(batch_bytes, DateTime::<Local>::from(batch_time));
+                }
 
                 if !batch.is_empty() {
                     total_processed += batch.len() as u64;
@@ -370,7 +1122,32 @@ impl App {
                 "Packet processor {} exiting, total processed: {}",
                 id, total_processed
             );
-        });
+        }));
+    }
+
+    /// Start the thread merging DPI worker results into the connection table
+    fn start_dpi_merger(&self, connections: Arc<DashMap<String, Connection>>) {
+        let Some(dpi_pool) = self.dpi_pool.clone() else {
+            return;
+        };
+        let should_stop = Arc::clone(&self.should_stop);
+        let merge_options = Arc::clone(&self.merge_options);
+
+        self.track(thread::spawn(move || {
+            info!("DPI merge thread started");
+
+            while !should_stop.load(Ordering::Relaxed) {
+                let Ok(first) = dpi_pool.results().recv_timeout(Duration::from_millis(100)) else {
+                    continue;
+                };
+                merge_classified(&connections, first, &merge_options);
+                for classified in dpi_pool.results().try_iter() {
+                    merge_classified(&connections, classified, &merge_options);
+                }
+            }
+
+            info!("DPI merge thread stopping");
+        }));
     }
 
     /// Start process enrichment thread conditionally based on PKTAP status
@@ -380,8 +1157,9 @@ impl App {
     ) -> Result<()> {
         let pktap_active = Arc::clone(&self.pktap_active);
         let should_stop = Arc::clone(&self.should_stop);
+        let commands = self.enrichment_rx.clone();
 
-        thread::spawn(move || {
+        self.track(thread::spawn(move || {
             // On macOS, wait for PKTAP detection to avoid unnecessary lsof calls
             #[cfg(target_os = "macos")]
             {
@@ -417,10 +1195,12 @@ impl App {
             }
 
             // Start the actual process enrichment
-            if let Err(e) = Self::run_process_enrichment(connections, should_stop, pktap_active) {
+            if let Err(e) =
+                Self::run_process_enrichment(connections, should_stop, pktap_active, commands)
+            {
                 error!("Process enrichment thread failed: {}", e);
             }
-        });
+        }));
 
         Ok(())
     }
@@ -430,6 +1210,7 @@ impl App {
         connections: Arc<DashMap<String, Connection>>,
         should_stop: Arc<AtomicBool>,
         pktap_active: Arc<AtomicBool>,
+        commands: Receiver<EnrichmentCommand>,
     ) -> Result<()> {
         let process_lookup =
             create_process_lookup_with_pktap_status(pktap_active.load(Ordering::Relaxed))?;
@@ -437,6 +1218,8 @@ impl App {
 
         info!("Process enrichment thread started");
         let mut last_refresh = Instant::now();
+        let mut refresh_now = false;
+        let mut sockets_seen = HashSet::new();
 
         loop {
             if should_stop.load(Ordering::Relaxed) {
@@ -453,74 +1236,39 @@ impl App {
                 break;
             }
 
-            // Refresh process lookup periodically
-            if last_refresh.elapsed() > Duration::from_secs(5) {
-                if let Err(e) = process_lookup.refresh() {
-                    debug!("Process lookup refresh failed: {}", e);
-                }
-                last_refresh = Instant::now();
-            }
-
-            // Enrich connections without process info
-            let mut enriched = 0;
-            for mut entry in connections.iter_mut() {
-                // Allow partial enrichment - fill in missing pieces without overwriting existing data
-                if let Some((pid, name)) = process_lookup.get_process_for_connection(&entry) {
-                    let mut did_enrich = false;
-
-                    // Only set process name if it's missing
-                    if entry.process_name.is_none() {
-                        entry.process_name = Some(name.clone());
-                        did_enrich = true;
-                        debug!(
-                            "✓ Set process name for connection {}: {}",
-                            entry.key(),
-                            name
+            // Refresh process lookup periodically, or right away when the UI asks for it
+            if refresh_now || last_refresh.elapsed() > Duration::from_secs(5) {
+                match process_lookup.refresh() {
+                    Ok(()) => {
+                        let gone = mark_gone_sockets(
+                            &connections,
+                            process_lookup.as_ref(),
+                            &mut sockets_seen,
                         );
-                    } else {
-                        // Check if the existing name differs significantly (for debugging)
-                        let existing_name = entry.process_name.as_ref().unwrap();
-                        let existing_normalized = existing_name
-                            .split_whitespace()
-                            .collect::<Vec<&str>>()
-                            .join(" ");
-                        let new_normalized =
-                            name.split_whitespace().collect::<Vec<&str>>().join(" ");
-
-                        if existing_normalized != new_normalized {
-                            debug!(
-                                "⚠️  Process name differs: existing='{}' vs lsof='{}'",
-                                existing_name, name
-                            );
+                        if gone > 0 {
+                            debug!("{} connections lost their socket", gone);
                         }
                     }
-
-                    // Only set PID if it's missing
-                    if entry.pid.is_none() {
-                        entry.pid = Some(pid);
-                        did_enrich = true;
-                        debug!("✓ Set PID for connection {}: {}", entry.key(), pid);
-                    } else if entry.pid != Some(pid) {
-                        // PID differs - log for debugging
-                        debug!(
-                            "⚠️  PID differs for {}: existing={:?} vs lsof={}",
-                            entry.key(),
-                            entry.pid,
-                            pid
-                        );
-                    }
-
-                    if did_enrich {
-                        enriched += 1;
-                    }
+                    Err(e) => debug!("Process lookup refresh failed: {}", e),
                 }
+                last_refresh = Instant::now();
             }
 
+            let enriched = enrich_connections(&connections, process_lookup.as_ref());
             if enriched > 0 {
                 debug!("Enriched {} connections with process info", enriched);
             }
-
-            thread::sleep(interval);
+            update_process_usage(&connections, process_lookup.as_ref());
+
+            // Wait for the next pass, waking up early for UI commands
+            refresh_now = match commands.recv_timeout(interval) {
+                Ok(EnrichmentCommand::RefreshNow) => true,
+                // Only rescan if the cached tables didn't know the connection
+                Ok(EnrichmentCommand::LookupProcess(key)) => connections
+                    .get(&key)
+                    .is_some_and(|conn| conn.process_name.is_none() || conn.pid.is_none()),
+                Err(_) => false,
+            };
         }
 
         Ok(())
@@ -529,14 +1277,31 @@ impl App {
     /// Start snapshot provider thread for UI updates
     fn start_snapshot_provider(&self, connections: Arc<DashMap<String, Connection>>) -> Result<()> {
         let snapshot = Arc::clone(&self.connections_snapshot);
+        let snapshot_requests = self.snapshot_rx.clone();
         let should_stop = Arc::clone(&self.should_stop);
         let stats = Arc::clone(&self.stats);
+        let dpi_pool = self.dpi_pool.clone();
         let service_lookup = Arc::clone(&self.service_lookup);
         let filter_localhost = self.config.filter_localhost;
+        let coalesce_ports = self.config.coalesce_ports.clone();
         let refresh_interval = Duration::from_millis(self.config.refresh_interval);
-
-        thread::spawn(move || {
+        let scans = Arc::clone(&self.scans);
+        let ignore = Arc::clone(&self.ignore);
+        let recorder = Arc::clone(&self.recorder);
+        // Dropped with the thread, which ends the lookup threads too
+        let resolver = self.config.show_hostnames.then(HostnameResolver::new);
+        let geoip = self.geoip.clone();
+
+        self.track(thread::spawn(move || {
             info!("Snapshot provider thread started");
+            let mut health_monitor = HealthMonitor::default();
+            // Kept across refreshes: DNS flows expire long before the
+            // connections to the names they resolved
+            let mut passive_dns = PassiveDns::default();
+            // Looked up the first time an address is seen
+            let mut locations: HashMap<IpAddr, Option<GeoInfo>> = HashMap::new();
+            // Answered once the snapshot it asked for is published
+            let mut published_tx: Option<Sender<()>> = None;
 
             loop {
                 if should_stop.load(Ordering::Relaxed) {
@@ -548,7 +1313,24 @@ impl App {
                 let start = Instant::now();
                 let total_connections = connections.len();
 
-                let mut snapshot_data: Vec<Connection> = connections
+                if let Some(resolver) = &resolver {
+                    resolver.collect_results();
+                }
+
+                if locations.len() > MAX_CACHED_LOCATIONS {
+                    locations.clear();
+                }
+
+                if !ignore.is_empty() {
+                    for entry in connections.iter() {
+                        passive_dns.learn(entry.value());
+                    }
+                    for mut entry in connections.iter_mut() {
+                        ignore.apply(entry.value_mut(), &passive_dns);
+                    }
+                }
+
+                let snapshot_data: Vec<Connection> = connections
                     .iter()
                     .map(|entry| {
                         let mut conn = entry.value().clone();
@@ -566,6 +1348,17 @@ impl App {
                             }
                         }
 
+                        if let Some(resolver) = &resolver {
+                            conn.hostname = resolver.hostname(conn.remote_addr.ip());
+                        }
+                        if let Some(geoip) = &geoip {
+                            conn.geo_info = locations
+                                .entry(conn.remote_addr.ip())
+                                .or_insert_with_key(|&ip| geoip.lookup(ip))
+                                .clone();
+                        }
+                        conn.recording = recorder.is_recording(entry.key());
+
                         conn
                     })
                     .filter(|conn| {
@@ -579,20 +1372,46 @@ impl App {
                     })
                     .filter(|conn| conn.is_active())
                     .collect();
+                let health = health_monitor.observe(
+                    snapshot_data.iter().filter(|conn| !conn.ignored),
+                    Instant::now(),
+                );
+                let (mut snapshot_data, subflows) = coalesce_flows(snapshot_data, &coalesce_ports);
 
                 // Sort by creation time (oldest first, newest last for maximum stability)
-                snapshot_data.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+                snapshot_data.sort_by_key(|conn| conn.created_instant);
 
                 let filtered_count = snapshot_data.len();
 
-                // Update snapshot
-                *snapshot.write().unwrap() = snapshot_data;
+                // Publish snapshot - readers keep their own Arc until they fetch the next one
+                *snapshot.write().unwrap() = Arc::new(ConnectionSnapshot {
+                    connections: snapshot_data,
+                    subflows,
+                    total_connections,
+                    packets_processed: stats.packets_processed.load(Ordering::Relaxed),
+                    packets_dropped: stats.packets_dropped.load(Ordering::Relaxed),
+                    capture_received: stats.capture_received.load(Ordering::Relaxed),
+                    interface_dropped: stats.interface_dropped.load(Ordering::Relaxed),
+                    drops_per_sec: stats.drops_per_sec.load(Ordering::Relaxed),
+                    queue_dropped: stats.queue_dropped.load(Ordering::Relaxed),
+                    connections_evicted: stats.connections_evicted.load(Ordering::Relaxed),
+                    connections_expired: stats.connections_expired.load(Ordering::Relaxed),
+                    dpi_jobs_dropped: dpi_pool.as_ref().map_or(0, |pool| pool.dropped_jobs()),
+                    quic_reassembly_bytes: quic_reassembly_bytes(),
+                    quic_reassembly_rejected: quic_reassembly_rejected(),
+                    health,
+                    scan_alerts: scans.alerts(),
+                    taken_at: Instant::now(),
+                });
 
                 // Update stats
                 stats
                     .connections_tracked
                     .store(total_connections as u64, Ordering::Relaxed);
                 *stats.last_update.write().unwrap() = Instant::now();
+                if let Some(published_tx) = published_tx.take() {
+                    let _ = published_tx.send(());
+                }
 
                 debug!(
                     "Snapshot updated in {:?} - Total: {}, Filtered: {}",
@@ -601,9 +1420,14 @@ impl App {
                     filtered_count
                 );
 
-                thread::sleep(refresh_interval);
+                // Wait for the next refresh, waking early if a snapshot is
+                // requested or the app is stopping
+                published_tx = snapshot_requests
+                    .recv_timeout(refresh_interval)
+                    .ok()
+                    .flatten();
             }
-        });
+        }));
 
         Ok(())
     }
@@ -615,7 +1439,7 @@ impl App {
     ) -> Result<()> {
         let should_stop = Arc::clone(&self.should_stop);
 
-        thread::spawn(move || {
+        self.track(thread::spawn(move || {
             info!("Rate refresh thread started");
 
             loop {
@@ -631,9 +1455,9 @@ impl App {
                 }
 
                 // Run every 1 second to balance responsiveness with performance
-                thread::sleep(Duration::from_secs(1));
+                sleep_unless_stopped(&should_stop, Duration::from_secs(1));
             }
-        });
+        }));
 
         Ok(())
     }
@@ -641,8 +1465,13 @@ impl App {
     /// Start cleanup thread to remove old connections
     fn start_cleanup_thread(&self, connections: Arc<DashMap<String, Connection>>) -> Result<()> {
         let should_stop = Arc::clone(&self.should_stop);
+        let stats = Arc::clone(&self.stats);
+        let closed = Arc::clone(&self.closed);
+        let scans = Arc::clone(&self.scans);
+        let recorder = Arc::clone(&self.recorder);
+        let max_idle = self.config.max_idle;
 
-        thread::spawn(move || {
+        self.track(thread::spawn(move || {
             info!("Cleanup thread started");
 
             loop {
@@ -652,40 +1481,71 @@ impl App {
                 }
 
                 // Remove inactive connections
-                let now = SystemTime::now();
+                let now = Instant::now();
                 let mut removed = 0;
+                let mut reclaimed_bytes = 0;
 
                 // Collect keys of connections to be removed
                 let mut removed_keys = Vec::new();
+                let mut brute_force_alerts = Vec::new();
 
                 connections.retain(|key, conn| {
                     // Use dynamic timeout based on connection type and state
-                    let should_keep = !conn.should_cleanup(now);
+                    let should_keep = !conn.should_cleanup(now, max_idle);
 
                     if !should_keep {
                         removed += 1;
                         removed_keys.push(key.clone());
                         // Log cleanup reason for debugging
-                        let conn_timeout = conn.get_timeout();
-                        let idle_time = now.duration_since(conn.last_activity).unwrap_or_default();
+                        let conn_timeout = conn.get_timeout(max_idle);
+                        let idle_time = conn.idle_time_at(now);
+                        let reason = conn.close_reason();
                         debug!(
-                            "Cleanup: Removing {} connection {} (idle: {:?}, timeout: {:?}, state: {})",
+                            "Cleanup: Removing {} connection {} (idle: {:?}, timeout: {:?}, state: {}, reason: {})",
                             conn.protocol,
                             key,
                             idle_time,
                             conn_timeout,
-                            conn.state()
+                            conn.state(),
+                            reason
                         );
+                        if !conn.alerts_muted {
+                            brute_force_alerts.extend(scans.observe_close(conn));
+                        }
+                        closed.record(conn.clone(), reason);
+                    } else if let Some(dpi_info) = &mut conn.dpi_info
+                        && let ApplicationProtocol::Quic(quic_info) = &mut dpi_info.application
+                    {
+                        // Release reassembly buffers that finished or went stale
+                        reclaimed_bytes +=
+                            quic_info.reclaim_reassembler(QUIC_REASSEMBLY_STALE_AFTER);
                     }
 
                     should_keep
                 });
 
+                // Tagging needs the table, which `retain` holds
+                for alert in &brute_force_alerts {
+                    tag_scan_flows(&connections, alert);
+                }
+
+                // Recordings end with their connection, whether it expired
+                // here or was evicted to make room
+                recorder.retain(|key| connections.contains_key(key));
+
+                if reclaimed_bytes > 0 {
+                    debug!(
+                        "Reclaimed {} bytes of QUIC reassembly buffers ({} still buffered)",
+                        reclaimed_bytes,
+                        quic_reassembly_bytes()
+                    );
+                }
+
                 // Clean up QUIC connection ID mappings for removed connections
                 if !removed_keys.is_empty()
                     && let Ok(mut mapping) = QUIC_CONNECTION_MAPPING.lock()
                 {
-                    mapping.retain(|_, conn_key| !removed_keys.contains(conn_key));
+                    mapping.retain(|conn_key| !removed_keys.iter().any(|key| key == conn_key));
                     debug!(
                         "Cleaned up QUIC mappings for {} removed connections",
                         removed_keys.len()
@@ -693,49 +1553,80 @@ impl App {
                 }
 
                 if removed > 0 {
+                    stats
+                        .connections_expired
+                        .fetch_add(removed, Ordering::Relaxed);
                     debug!(
                         "Removed {} inactive connections and cleaned up QUIC mappings",
                         removed
                     );
                 }
 
-                thread::sleep(Duration::from_secs(10));
+                sleep_unless_stopped(&should_stop, Duration::from_secs(10));
             }
-        });
+        }));
 
         Ok(())
     }
 
-    /// Get current connections for UI display
-    pub fn get_connections(&self) -> Vec<Connection> {
-        self.connections_snapshot.read().unwrap().clone()
-    }
+    /// Watch for interfaces appearing and disappearing, telling the status
+    /// bar about each and whether it was the one being captured on
+    fn start_interface_watcher(&self) {
+        let mut known = match interfaces::interface_names() {
+            Ok(names) => names,
+            Err(e) => {
+                warn!("Not watching for interface changes: {}", e);
+                return;
+            }
+        };
+        let should_stop = Arc::clone(&self.should_stop);
+        let current_interface = Arc::clone(&self.current_interface);
+        let notices = self.notice_tx.clone();
 
-    /// Get filtered connections for UI display
-    pub fn get_filtered_connections(&self, filter_query: &str) -> Vec<Connection> {
-        let connections = self.connections_snapshot.read().unwrap().clone();
+        self.track(thread::spawn(move || {
+            info!("Interface watcher started");
 
-        if filter_query.trim().is_empty() {
-            return connections;
-        }
+            loop {
+                sleep_unless_stopped(&should_stop, INTERFACE_POLL_INTERVAL);
+                if should_stop.load(Ordering::Relaxed) {
+                    info!("Interface watcher stopping");
+                    break;
+                }
 
-        let filter = ConnectionFilter::parse(filter_query);
-        connections
-            .into_iter()
-            .filter(|conn| filter.matches(conn))
-            .collect()
+                let names = match interfaces::interface_names() {
+                    Ok(names) => names,
+                    Err(e) => {
+                        debug!("Failed to list interfaces: {}", e);
+                        continue;
+                    }
+                };
+                let capturing = current_interface.read().unwrap().clone();
+                for change in interfaces::interface_changes(&known, &names) {
+                    let notice = match &change {
+                        InterfaceChange::Removed(name) if capturing.as_ref() == Some(name) => {
+                            warn!("Capture interface {} disappeared", name);
+                            format!("Capture interface {} disappeared, no more packets", name)
+                        }
+                        _ => {
+                            info!("{}", change);
+                            change.to_string()
+                        }
+                    };
+                    let _ = notices.send(notice);
+                }
+                known = names;
+            }
+        }));
     }
 
-    /// Get application statistics
-    pub fn get_stats(&self) -> AppStats {
-        AppStats {
-            packets_processed: AtomicU64::new(self.stats.packets_processed.load(Ordering::Relaxed)),
-            packets_dropped: AtomicU64::new(self.stats.packets_dropped.load(Ordering::Relaxed)),
-            connections_tracked: AtomicU64::new(
-                self.stats.connections_tracked.load(Ordering::Relaxed),
-            ),
-            last_update: RwLock::new(*self.stats.last_update.read().unwrap()),
-        }
+    /// Messages for the status bar sent since the last call, oldest first
+    pub fn take_notices(&self) -> Vec<String> {
+        self.notice_rx.try_iter().collect()
+    }
+
+    /// Get the latest connection snapshot (cheap `Arc` clone)
+    pub fn get_snapshot(&self) -> Arc<ConnectionSnapshot> {
+        Arc::clone(&self.connections_snapshot.read().unwrap())
     }
 
     /// Check if application is still loading
@@ -743,59 +1634,666 @@ impl App {
         self.is_loading.load(Ordering::Relaxed)
     }
 
+    /// Longest a flow may sit idle before cleanup, if capped
+    pub fn max_idle(&self) -> Option<Duration> {
+        self.config.max_idle
+    }
+
     /// Get the current network interface name
     pub fn get_current_interface(&self) -> Option<String> {
         self.current_interface.read().unwrap().clone()
     }
 
-    /// Stop all threads gracefully
-    pub fn stop(&self) {
-        info!("Stopping application");
-        self.should_stop.store(true, Ordering::Relaxed);
+    /// Generate a rule blocking the connection's remote endpoint with the native firewall.
+    /// Returns the index of the rule in the generated list (existing rules are reused).
+    pub fn generate_firewall_rule(&self, conn: &Connection) -> Option<usize> {
+        let rule = FirewallRule::for_connection(conn, FirewallBackend::native())?;
+        let mut rules = self.generated_rules.write().unwrap();
+
+        if let Some(index) = rules.iter().position(|r| r.command == rule.command) {
+            return Some(index);
+        }
+
+        info!("Generated firewall rule: {}", rule.command);
+        rules.push(rule);
+        Some(rules.len() - 1)
     }
-}
 
-/// Update or create a connection from a parsed packet
-fn update_connection(
+    /// Ask the enrichment thread to rebuild its process lookup tables immediately
+    pub fn request_process_refresh(&self) {
+        // A full queue means a refresh is already pending
+        let _ = self.enrichment_tx.try_send(EnrichmentCommand::RefreshNow);
+    }
+
+    /// Ask the enrichment thread to resolve a connection's process right away
+    pub fn request_process_lookup(&self, connection_key: String) {
+        let _ = self
+            .enrichment_tx
+            .try_send(EnrichmentCommand::LookupProcess(connection_key));
+    }
+
+    /// Replace the BPF filter of the running capture. An empty filter captures
+    /// every packet again. Fails if no capture is running or the filter
+    /// doesn't compile.
+    #[allow(dead_code)] // Library API; the TUI only sets a filter at startup
+    pub fn set_bpf_filter(&self, filter: &str) -> Result<()> {
+        let Some(linktype) = *self.linktype.read().unwrap() else {
+            return Err(anyhow::anyhow!("Packet capture is not running"));
+        };
+        validate_bpf_filter(filter, linktype)?;
+        self.bpf_filter_tx.send(filter.to_string())?;
+        Ok(())
+    }
+
+    /// Get the recently closed connections, most recent first
+    pub fn closed_connections(&self) -> Vec<ClosedConnection> {
+        self.closed.recent()
+    }
+
+    /// Kernel counters of every interface, with their throughput since the
+    /// previous reading. Counters are read at most once a second.
+    pub fn interface_stats(&self) -> Result<Vec<InterfaceStats>> {
+        self.interface_stats
+            .lock()
+            .unwrap()
+            .update(Instant::now(), interfaces::interface_counters)
+    }
+
+    /// Get hourly traffic totals indexed by [day-of-week][hour-of-day], Monday first
+    pub fn heatmap(&self) -> [[u64; 24]; 7] {
+        self.heatmap.snapshot()
+    }
+
+    /// Get all generated firewall rules
+    pub fn get_generated_rules(&self) -> Vec<FirewallRule> {
+        self.generated_rules.read().unwrap().clone()
+    }
+
+    /// Execute a previously generated firewall rule
+    pub fn apply_firewall_rule(&self, index: usize) -> Result<()> {
+        let mut rules = self.generated_rules.write().unwrap();
+        let rule = rules
+            .get_mut(index)
+            .ok_or_else(|| anyhow::anyhow!("No firewall rule at index {}", index))?;
+        rule.apply()
+    }
+
+    /// Start saving the connection's packets to a pcap file in the record
+    /// directory, returning the file's path
+    pub fn start_recording(&self, conn: &Connection) -> Result<PathBuf> {
+        let linktype = self
+            .linktype
+            .read()
+            .unwrap()
+            .ok_or_else(|| anyhow::anyhow!("no packet capture is running"))?;
+        self.recorder.start(&conn.key(), linktype)
+    }
+
+    /// Stop saving the connection's packets, returning the finished file
+    pub fn stop_recording(&self, conn: &Connection) -> Option<PathBuf> {
+        self.recorder.stop(&conn.key())
+    }
+
+    /// Stop all threads gracefully
+    pub fn stop(&self) {
+        info!("Stopping application");
+        self.should_stop.store(true, Ordering::Relaxed);
+        let _ = self.snapshot_tx.try_send(None);
+
+        // Threads check the flag at least every second; a capture or process
+        // lookup stuck in a system call is left behind rather than waited on
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        let mut threads = std::mem::take(&mut *self.threads.lock().unwrap());
+        while !threads.is_empty() && Instant::now() < deadline {
+            let (finished, running): (Vec<_>, Vec<_>) =
+                threads.into_iter().partition(|handle| handle.is_finished());
+            for handle in finished {
+                if handle.join().is_err() {
+                    error!("A background thread panicked");
+                }
+            }
+            threads = running;
+            thread::sleep(Duration::from_millis(10));
+        }
+        if !threads.is_empty() {
+            warn!(
+                "{} background threads still running after {:?}",
+                threads.len(),
+                SHUTDOWN_TIMEOUT
+            );
+        }
+
+        // Finish the files of recordings still running
+        self.recorder.stop_all();
+    }
+
+    /// Remember a background thread so `stop` can wait for it
+    fn track(&self, handle: JoinHandle<()>) {
+        self.threads.lock().unwrap().push(handle);
+    }
+
+    /// Whether `--duration` has elapsed or `--count` connections were seen
+    pub fn run_finished(&self) -> bool {
+        let timed_out = self
+            .config
+            .duration
+            .zip(self.started_at)
+            .is_some_and(|(duration, started_at)| started_at.elapsed() >= duration);
+        let counted = self
+            .config
+            .stop_after_connections
+            .is_some_and(|count| self.stats.connections_created.load(Ordering::Relaxed) >= count);
+        timed_out || counted
+    }
+
+    /// Totals over the run so far: connections that closed plus those still open
+    pub fn run_summary(&self) -> RunSummary {
+        let mut protocols = self.closed.totals();
+        for entry in self.connections.iter() {
+            protocols
+                .entry(entry.protocol.to_string())
+                .or_default()
+                .add(entry.value());
+        }
+        RunSummary {
+            duration: self.started_at.map_or(Duration::ZERO, |at| at.elapsed()),
+            packets_processed: self.stats.packets_processed.load(Ordering::Relaxed),
+            packets_dropped: self.stats.packets_dropped.load(Ordering::Relaxed),
+            protocols,
+            alerts_raised: self.scans.alerts_raised(),
+            alerts: self.scans.alerts(),
+        }
+    }
+
+    /// Clone the tracked connections `predicate` accepts, in no particular
+    /// order. Unlike the snapshot, flows are not coalesced and the others are
+    /// never copied.
+    #[allow(dead_code)] // Library API; the TUI reads snapshots
+    pub fn get_connections_filtered(
+        &self,
+        predicate: impl Fn(&Connection) -> bool,
+    ) -> Vec<Connection> {
+        self.connections
+            .iter()
+            .filter(|entry| predicate(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Bytes sent and received per process over the current connections, as
+    /// (process, sent, received), most traffic first
+    pub fn process_bandwidth(&self) -> Vec<(String, u64, u64)> {
+        talkers::process_bandwidth(&self.get_snapshot().connections)
+    }
+
+    /// Write every tracked connection to `path` as CSV, oldest first. Flows
+    /// are listed one by one, not coalesced as in the UI.
+    pub fn export_connections_to_csv(&self, path: &Path) -> Result<()> {
+        let mut rows: Vec<(SystemTime, String)> = self
+            .connections
+            .iter()
+            .map(|entry| (entry.created_at, csv_row(entry.value())))
+            .collect();
+        rows.sort();
+
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for (_, row) in rows {
+            csv.push_str(&row);
+            csv.push('\n');
+        }
+        std::fs::write(path, csv)?;
+        Ok(())
+    }
+}
+
+/// Columns written by `App::export_connections_to_csv`
+const CSV_HEADER: &str = "protocol,local_addr,remote_addr,state,pid,process,bytes_sent,\
+bytes_received,packets_sent,packets_received,created_at,last_activity,service,app_protocol";
+
+/// A connection as a CSV row, with RFC 3339 timestamps. Unknown values are
+/// left empty.
+fn csv_row(conn: &Connection) -> String {
+    let fields = [
+        conn.protocol.to_string(),
+        conn.local_addr.to_string(),
+        conn.remote_addr.to_string(),
+        conn.state(),
+        conn.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+        conn.process_name.clone().unwrap_or_default(),
+        conn.bytes_sent.to_string(),
+        conn.bytes_received.to_string(),
+        conn.packets_sent.to_string(),
+        conn.packets_received.to_string(),
+        DateTime::<Local>::from(conn.created_at).to_rfc3339(),
+        DateTime::<Local>::from(conn.last_activity).to_rfc3339(),
+        conn.service_name.clone().unwrap_or_default(),
+        conn.dpi_info
+            .as_ref()
+            .map(|dpi| dpi.application.to_string())
+            .unwrap_or_default(),
+    ];
+    fields.map(csv_field).join(",")
+}
+
+/// Quote a CSV field holding a comma, quote or line break
+fn csv_field(field: String) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Sleep for `duration`, returning early once `should_stop` is set
+fn sleep_unless_stopped(should_stop: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline && !should_stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(100).min(deadline - Instant::now()));
+    }
+}
+
+/// Send the packets `reader` reads down `packet_tx` until told to stop or a
+/// savefile runs out, returning how many were read. When the channel is full,
+/// either wait for room or drop and count the packet. Fails when the capture
+/// does, e.g. because its interface went away.
+fn read_packets<T: Activated + ?Sized>(
+    mut reader: PacketReader<T>,
+    packet_tx: &Sender<CapturedPacket>,
+    wait_when_full: bool,
+    should_stop: &AtomicBool,
+    stats: &AppStats,
+    bpf_filters: &Receiver<String>,
+) -> Result<u64> {
+    let mut packets_read = 0u64;
+    let mut last_log = Instant::now();
+    let mut last_stats_check = Instant::now();
+    let mut last_dropped = 0u32;
+
+    loop {
+        if should_stop.load(Ordering::Relaxed) {
+            info!("Capture thread stopping");
+            break;
+        }
+
+        // Read the kernel's counters every second, busy or not
+        let since_stats_check = last_stats_check.elapsed();
+        if since_stats_check > Duration::from_secs(1) {
+            if let Ok(capture_stats) = reader.stats() {
+                if capture_stats.dropped > last_dropped {
+                    debug!(
+                        "Capture stats - Received: {}, Dropped: {}",
+                        capture_stats.received, capture_stats.dropped
+                    );
+                }
+                // The counters are 32 bits and wrap on busy links
+                let dropped = capture_stats.dropped.wrapping_sub(last_dropped);
+                last_dropped = capture_stats.dropped;
+                stats.drops_per_sec.store(
+                    (dropped as f64 / since_stats_check.as_secs_f64()).round() as u64,
+                    Ordering::Relaxed,
+                );
+                stats
+                    .packets_dropped
+                    .store(capture_stats.dropped as u64, Ordering::Relaxed);
+                stats
+                    .capture_received
+                    .store(capture_stats.received as u64, Ordering::Relaxed);
+                stats
+                    .interface_dropped
+                    .store(capture_stats.if_dropped as u64, Ordering::Relaxed);
+            }
+            last_stats_check = Instant::now();
+        }
+
+        // Only the latest filter set since the last packet matters
+        if let Some(filter) = bpf_filters.try_iter().last() {
+            match reader.set_filter(&filter) {
+                Ok(()) => info!("Applied BPF filter: {}", filter),
+                Err(e) => error!("Failed to apply BPF filter '{}': {}", filter, e),
+            }
+        }
+
+        match reader.next_packet() {
+            Ok(Some(packet)) => {
+                packets_read += 1;
+
+                // Log first packet immediately
+                if packets_read == 1 {
+                    info!("First packet captured! Size: {} bytes", packet.data.len());
+                }
+
+                // Log every 10000 packets or every 5 seconds
+                if packets_read.is_multiple_of(10000) || last_log.elapsed() > Duration::from_secs(5)
+                {
+                    info!("Read {} packets so far", packets_read);
+                    last_log = Instant::now();
+                }
+
+                let sent = if wait_when_full {
+                    packet_tx.send(packet).is_ok()
+                } else {
+                    match packet_tx.try_send(packet) {
+                        // Keep reading, or the kernel's buffer overflows as well
+                        Err(TrySendError::Full(_)) => {
+                            stats.queue_dropped.fetch_add(1, Ordering::Relaxed);
+                            true
+                        }
+                        sent => sent.is_ok(),
+                    }
+                };
+                if !sent {
+                    warn!("Packet channel closed");
+                    break;
+                }
+            }
+            Ok(None) => {
+                // Timeout, nothing to read
+            }
+            Err(e) => {
+                if matches!(
+                    e.downcast_ref::<PcapError>(),
+                    Some(PcapError::NoMorePackets)
+                ) {
+                    info!("Reached the end of the savefile");
+                    break;
+                }
+                return Err(e.context(format!("capture failed after {} packets", packets_read)));
+            }
+        }
+    }
+
+    Ok(packets_read)
+}
+
+/// Update or create a connection from a parsed packet
+fn update_connection(
     connections: &DashMap<String, Connection>,
     parsed: ParsedPacket,
-    _stats: &AppStats,
+    stats: &AppStats,
+    closed: &ClosedHistory,
+    max_connections: usize,
+    options: &MergeOptions,
 ) {
     let mut key = parsed.connection_key.clone();
-    let now = SystemTime::now();
 
-    // For QUIC packets, check if we have a connection ID mapping
+    // For QUIC packets, check whether a connection ID places them on a
+    // connection seen from another address
     if parsed.protocol == Protocol::UDP
         && let Some(dpi_result) = &parsed.dpi_result
         && let ApplicationProtocol::Quic(quic_info) = &dpi_result.application
-        && let Some(conn_id_hex) = &quic_info.connection_id_hex
         && let Ok(mut mapping) = QUIC_CONNECTION_MAPPING.lock()
     {
-        if let Some(existing_key) = mapping.get(conn_id_hex) {
-            key = existing_key.clone();
-            debug!(
-                "QUIC: Using existing connection key {} for Connection ID {}",
-                key, conn_id_hex
-            );
-        } else {
-            // New QUIC connection ID, create mapping
-            mapping.insert(conn_id_hex.clone(), key.clone());
+        let existing_key = mapping.resolve(quic_info, &key);
+        if existing_key != key && connections.contains_key(&existing_key) {
             debug!(
-                "QUIC: Created new mapping {} -> {} for Connection ID {}",
-                conn_id_hex, key, conn_id_hex
+                "QUIC: Using existing connection key {} for packets on {}",
+                existing_key, key
             );
+            key = existing_key;
         }
     }
 
+    // Make room before inserting a new flow into a full table
+    if connections.len() >= max_connections && !connections.contains_key(&key) {
+        evict_for_capacity(connections, max_connections, stats, closed);
+    }
+
     connections
         .entry(key.clone())
         .and_modify(|conn| {
-            *conn = merge_packet_into_connection(conn.clone(), &parsed, now);
+            *conn = merge_packet_into_connection(conn.clone(), &parsed, options);
         })
         .or_insert_with(|| {
             debug!("New connection detected: {}", key);
-            create_connection_from_packet(&parsed, now)
+            stats.connections_created.fetch_add(1, Ordering::Relaxed);
+            create_connection_from_packet(&parsed, options)
+        });
+}
+
+/// Fill in missing process names and PIDs, returning how many connections changed.
+///
+/// Lookups run on cloned connections without holding any table lock: a cache
+/// miss can shell out to lsof or netstat and take hundreds of milliseconds,
+/// which would otherwise stall the packet processors and snapshot provider.
+fn enrich_connections(
+    connections: &DashMap<String, Connection>,
+    process_lookup: &dyn ProcessLookup,
+) -> usize {
+    let pending: Vec<(String, Connection)> = connections
+        .iter()
+        // Forwarded flows belong to other hosts, so no local process has them
+        .filter(|entry| !entry.forwarded)
+        .filter(|entry| entry.process_name.is_none() || entry.pid.is_none())
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+
+    let mut enriched = 0;
+    for (key, conn) in pending {
+        let Some((pid, name)) = process_lookup.get_process_for_connection(&conn) else {
+            continue;
+        };
+        // The connection may have been removed while we were looking it up
+        let Some(mut entry) = connections.get_mut(&key) else {
+            continue;
+        };
+
+        // Allow partial enrichment - fill in missing pieces without overwriting existing data
+        let mut did_enrich = false;
+        if entry.process_name.is_none() {
+            debug!("✓ Set process name for connection {}: {}", key, name);
+            entry.process_name = Some(name);
+            did_enrich = true;
+        }
+        if entry.pid.is_none() {
+            debug!("✓ Set PID for connection {}: {}", key, pid);
+            entry.pid = Some(pid);
+            did_enrich = true;
+        } else if entry.pid != Some(pid) {
+            // PID differs - log for debugging
+            debug!(
+                "⚠️  PID differs for {}: existing={:?} vs lookup={}",
+                key, entry.pid, pid
+            );
+        }
+
+        if did_enrich {
+            enriched += 1;
+        }
+    }
+    enriched
+}
+
+/// Sample the CPU and memory use of each process owning connections, and
+/// record it on those connections
+fn update_process_usage(
+    connections: &DashMap<String, Connection>,
+    process_lookup: &dyn ProcessLookup,
+) {
+    let pids: HashSet<u32> = connections.iter().filter_map(|entry| entry.pid).collect();
+    let usage: HashMap<u32, ProcessUsage> = pids
+        .into_iter()
+        .filter_map(|pid| Some((pid, process_lookup.process_usage(pid)?)))
+        .collect();
+    // Platforms without usage sampling leave every connection untouched
+    if usage.is_empty() {
+        return;
+    }
+
+    for mut conn in connections.iter_mut() {
+        if let Some(pid) = conn.pid {
+            let usage = usage.get(&pid);
+            conn.cpu_usage = usage.and_then(|usage| usage.cpu_usage);
+            conn.memory_usage = usage.and_then(|usage| usage.memory_usage);
+        }
+    }
+}
+
+/// Tag the table's flows that `alert` is about
+fn tag_scan_flows(connections: &DashMap<String, Connection>, alert: &ScanAlert) {
+    for mut conn in connections.iter_mut() {
+        if conn.scan.is_none() && alert.matches(&conn) {
+            conn.scan = Some(alert.kind);
+        }
+    }
+}
+
+/// Remember the MAC a LAN peer was seen at, or fill it in on packets
+/// captured without link-layer headers from what ARP and earlier frames showed
+fn resolve_remote_mac(neighbors: &NeighborTable, parsed: &mut ParsedPacket) {
+    let remote_ip = parsed.remote_addr.ip();
+    match parsed.remote_mac {
+        Some(mac) => neighbors.learn(remote_ip, mac),
+        None if !parsed.forwarded => parsed.remote_mac = neighbors.get(&remote_ip),
+        None => {}
+    }
+}
+
+/// Flag connections whose socket was in the process lookup tables before the
+/// last refresh but no longer is, returning how many were flagged. Only
+/// sockets seen at least once count, since many flows never show up there.
+fn mark_gone_sockets(
+    connections: &DashMap<String, Connection>,
+    process_lookup: &dyn ProcessLookup,
+    sockets_seen: &mut HashSet<String>,
+) -> usize {
+    // Look up bare copies of the flows so no table lock is held meanwhile
+    let flows: Vec<(String, Connection)> = connections
+        .iter()
+        .filter(|entry| !entry.forwarded && !entry.source_gone)
+        .map(|entry| {
+            let flow = Connection::new(
+                entry.protocol,
+                entry.local_addr,
+                entry.remote_addr,
+                entry.protocol_state,
+            );
+            (entry.key().clone(), flow)
+        })
+        .collect();
+
+    let mut gone = 0;
+    for (key, flow) in flows {
+        if process_lookup.get_process_for_connection(&flow).is_some() {
+            sockets_seen.insert(key);
+        } else if sockets_seen.remove(&key)
+            && let Some(mut conn) = connections.get_mut(&key)
+        {
+            conn.source_gone = true;
+            gone += 1;
+        }
+    }
+    sockets_seen.retain(|key| connections.contains_key(key));
+    gone
+}
+
+/// Merge a DPI worker result into its connection.
+///
+/// QUIC connections can move to a new 5-tuple; when a result carries a connection ID
+/// that already belongs to another tracked flow, the new tuple is folded into it.
+fn merge_classified(
+    connections: &DashMap<String, Connection>,
+    classified: Classified,
+    options: &MergeOptions,
+) {
+    let Classified {
+        mut connection_key,
+        result,
+        instant,
+    } = classified;
+
+    if let ApplicationProtocol::Quic(quic_info) = &result.application {
+        let existing_key = QUIC_CONNECTION_MAPPING.lock().ok().and_then(|mut mapping| {
+            let existing = mapping.resolve(quic_info, &connection_key);
+            (existing != connection_key).then_some(existing)
         });
+
+        if let Some(existing_key) = existing_key
+            && connections.contains_key(&existing_key)
+        {
+            if let Some((_, migrated)) = connections.remove(&connection_key)
+                && let Some(mut existing) = connections.get_mut(&existing_key)
+            {
+                debug!(
+                    "QUIC: Folding {} into {} by connection ID",
+                    connection_key, existing_key
+                );
+                existing.packets_sent += migrated.packets_sent;
+                existing.packets_received += migrated.packets_received;
+                existing.bytes_sent += migrated.bytes_sent;
+                existing.bytes_received += migrated.bytes_received;
+                existing.last_activity = existing.last_activity.max(migrated.last_activity);
+                existing.last_activity_instant = existing
+                    .last_activity_instant
+                    .max(migrated.last_activity_instant);
+            }
+            connection_key = existing_key;
+        }
+    }
+
+    // The flow may have been cleaned up or evicted while the job was queued
+    if let Some(mut conn) = connections.get_mut(&connection_key) {
+        merge_dpi_result(&mut conn, &result, instant, options);
+    }
+}
+
+/// Evict connections when the table is full, preferring half-open flows and then the
+/// least recently active ones. Each pass frees about 10% of the capacity so a flood
+/// doesn't trigger a full scan for every new packet.
+fn evict_for_capacity(
+    connections: &DashMap<String, Connection>,
+    max_connections: usize,
+    stats: &AppStats,
+    closed: &ClosedHistory,
+) {
+    let target = max_connections.saturating_sub((max_connections / 10).max(1));
+    let excess = connections.len().saturating_sub(target);
+    if excess == 0 {
+        return;
+    }
+
+    let mut candidates: Vec<(bool, Instant, String)> = connections
+        .iter()
+        .map(|entry| {
+            let conn = entry.value();
+            (
+                is_established(conn),
+                conn.last_activity_instant,
+                entry.key().clone(),
+            )
+        })
+        .collect();
+    candidates
+        .sort_unstable_by_key(|(established, last_activity, _)| (*established, *last_activity));
+
+    let mut evicted_keys = HashSet::with_capacity(excess);
+    for (_, _, key) in candidates.into_iter().take(excess) {
+        if let Some((key, conn)) = connections.remove(&key) {
+            closed.record(conn, CloseReason::Evicted);
+            evicted_keys.insert(key);
+        }
+    }
+
+    if let Ok(mut mapping) = QUIC_CONNECTION_MAPPING.lock() {
+        mapping.retain(|conn_key| !evicted_keys.contains(conn_key));
+    }
+
+    stats
+        .connections_evicted
+        .fetch_add(evicted_keys.len() as u64, Ordering::Relaxed);
+    warn!(
+        "Connection table full ({} entries), evicted {} oldest flows",
+        max_connections,
+        evicted_keys.len()
+    );
+}
+
+/// Whether a flow has completed a handshake (TCP) or seen traffic both ways (others)
+fn is_established(conn: &Connection) -> bool {
+    match conn.protocol_state {
+        ProtocolState::Tcp(state) => state == TcpState::Established,
+        _ => conn.packets_sent > 0 && conn.packets_received > 0,
+    }
 }
 
 impl Drop for App {
@@ -805,3 +2303,845 @@ impl Drop for App {
         thread::sleep(Duration::from_millis(100));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::dpi::DpiResult;
+    use crate::network::parser::TcpFlags;
+    use crate::network::platform::{ConnectionKey, lookup_socket};
+    use crate::network::scan::ScanKind;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    fn syn_packet(local_port: u16, remote: Ipv4Addr) -> ParsedPacket {
+        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), local_port);
+        let remote_addr = SocketAddr::new(IpAddr::V4(remote), 80);
+        ParsedPacket {
+            connection_key: format!("TCP:{}-TCP:{}", local_addr, remote_addr),
+            protocol: Protocol::TCP,
+            local_addr,
+            remote_addr,
+            protocol_state: ProtocolState::Tcp(TcpState::Unknown),
+            tcp_flags: Some(TcpFlags {
+                fin: false,
+                syn: true,
+                rst: false,
+                psh: false,
+                ack: false,
+                urg: false,
+            }),
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
+            is_outgoing: false,
+            forwarded: false,
+            packet_len: 60,
+            payload_len: 0,
+            timestamp: SystemTime::now(),
+            instant: Instant::now(),
+            dpi_result: None,
+            dpi_payload: None,
+            process_name: None,
+            process_id: None,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
+        }
+    }
+
+    #[test]
+    fn test_heatmap_buckets_by_weekday_and_hour() {
+        use chrono::TimeZone;
+
+        let heatmap = TrafficHeatmap::default();
+        // 2024-01-03 was a Wednesday
+        let at = Local.with_ymd_and_hms(2024, 1, 3, 14, 30, 0).unwrap();
+        heatmap.record(1500, at);
+        heatmap.record(500, at);
+
+        let buckets = heatmap.snapshot();
+        assert_eq!(buckets[2][14], 2000);
+        assert_eq!(buckets.iter().flatten().sum::<u64>(), 2000);
+    }
+
+    #[test]
+    fn test_connection_table_cap_under_flood() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let max_connections = 100;
+
+        // A handful of established flows that must survive the flood
+        for port in 0..5u16 {
+            let packet = syn_packet(40000 + port, Ipv4Addr::new(10, 0, 0, 1));
+            let mut conn = create_connection_from_packet(&packet, &MergeOptions::default());
+            conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
+            connections.insert(packet.connection_key.clone(), conn);
+        }
+
+        // SYN flood from unique sources
+        for i in 0..2000u32 {
+            let remote = Ipv4Addr::from(0x0b00_0000 + i);
+            update_connection(
+                &connections,
+                syn_packet(80, remote),
+                &stats,
+                &closed,
+                max_connections,
+                &MergeOptions::default(),
+            );
+            assert!(connections.len() <= max_connections);
+        }
+
+        let established = connections
+            .iter()
+            .filter(|entry| is_established(entry.value()))
+            .count();
+        assert_eq!(established, 5, "established flows should survive eviction");
+        assert!(stats.connections_evicted.load(Ordering::Relaxed) >= 1900);
+        assert_eq!(stats.connections_expired.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_eviction_prefers_least_recently_active() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let now = Instant::now();
+
+        for i in 0..10u32 {
+            let packet = syn_packet(1000 + i as u16, Ipv4Addr::new(10, 0, 1, 1));
+            let mut conn = create_connection_from_packet(&packet, &MergeOptions::default());
+            conn.last_activity_instant = now - Duration::from_secs(100 - i as u64);
+            connections.insert(packet.connection_key.clone(), conn);
+        }
+
+        evict_for_capacity(&connections, 10, &stats, &closed);
+
+        assert_eq!(connections.len(), 9);
+        assert!(
+            !connections
+                .iter()
+                .any(|entry| entry.value().local_addr.port() == 1000),
+            "the oldest flow should be evicted first"
+        );
+        let recent = closed.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].reason, CloseReason::Evicted);
+        assert_eq!(recent[0].connection.local_addr.port(), 1000);
+    }
+
+    fn quic_result(conn_id_hex: &str) -> DpiResult {
+        let mut quic_info = QuicInfo::new(1);
+        quic_info.packet_type = QuicPacketType::Initial;
+        quic_info.connection_id = (0..conn_id_hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&conn_id_hex[i..i + 2], 16).unwrap())
+            .collect();
+        quic_info.connection_id_hex = Some(conn_id_hex.to_string());
+        DpiResult {
+            application: ApplicationProtocol::Quic(Box::new(quic_info)),
+        }
+    }
+
+    /// DPI result for a short-header packet starting with `dcid`
+    fn quic_short_header_result(dcid: &[u8]) -> DpiResult {
+        let mut quic_info = QuicInfo::new(0);
+        quic_info.packet_type = QuicPacketType::OneRtt;
+        quic_info.connection_id = dcid.to_vec();
+        DpiResult {
+            application: ApplicationProtocol::Quic(Box::new(quic_info)),
+        }
+    }
+
+    #[test]
+    fn test_classified_result_is_merged_into_connection() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let packet = syn_packet(5000, Ipv4Addr::new(10, 0, 2, 1));
+        let key = packet.connection_key.clone();
+        update_connection(
+            &connections,
+            packet,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        merge_classified(
+            &connections,
+            Classified {
+                connection_key: key.clone(),
+                result: quic_result("a1b2c3d4e5f60001"),
+                instant: Instant::now(),
+            },
+            &MergeOptions::default(),
+        );
+        assert!(connections.get(&key).unwrap().dpi_info.is_some());
+
+        // Results for flows that are already gone are ignored
+        merge_classified(
+            &connections,
+            Classified {
+                connection_key: "TCP:1.1.1.1:1-TCP:2.2.2.2:2".to_string(),
+                result: quic_result("a1b2c3d4e5f60002"),
+                instant: Instant::now(),
+            },
+            &MergeOptions::default(),
+        );
+        assert_eq!(connections.len(), 1);
+    }
+
+    #[test]
+    fn test_migrated_quic_flow_is_folded_by_connection_id() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let original = syn_packet(6000, Ipv4Addr::new(10, 0, 3, 1));
+        let migrated = syn_packet(6001, Ipv4Addr::new(10, 0, 3, 1));
+        let (original_key, migrated_key) = (
+            original.connection_key.clone(),
+            migrated.connection_key.clone(),
+        );
+        update_connection(
+            &connections,
+            original,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        update_connection(
+            &connections,
+            migrated,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        for key in [&original_key, &migrated_key] {
+            merge_classified(
+                &connections,
+                Classified {
+                    connection_key: key.clone(),
+                    result: quic_result("a1b2c3d4e5f60003"),
+                    instant: Instant::now(),
+                },
+                &MergeOptions::default(),
+            );
+        }
+
+        assert_eq!(connections.len(), 1);
+        let conn = connections.get(&original_key).unwrap();
+        assert_eq!(conn.packets_received, 2);
+        assert!(conn.dpi_info.is_some());
+    }
+
+    #[test]
+    fn test_short_header_packets_follow_the_server_connection_id() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let server_cid = [0x5e, 0x77, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        let quic_packet = |local_port, result| {
+            let mut packet = syn_packet(local_port, Ipv4Addr::new(10, 0, 4, 1));
+            packet.protocol = Protocol::UDP;
+            packet.dpi_result = Some(result);
+            packet
+        };
+
+        // The server's Initial names the ID it wants to be addressed by
+        let mut initial = quic_result("c11e0700c11e0700");
+        if let ApplicationProtocol::Quic(info) = &mut initial.application {
+            info.source_connection_id = server_cid.to_vec();
+        }
+        let handshake = quic_packet(6100, initial);
+        let original_key = handshake.connection_key.clone();
+        update_connection(
+            &connections,
+            handshake,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        // After a NAT rebinding, the client's 1-RTT packets come from a new
+        // port. Their header holds the ID and then the encrypted packet
+        // number and payload.
+        let mut dcid = server_cid.to_vec();
+        dcid.extend_from_slice(&[0x9f; 12]);
+        let rebound = quic_packet(6101, quic_short_header_result(&dcid));
+        update_connection(
+            &connections,
+            rebound,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections.get(&original_key).unwrap().packets_received, 2);
+
+        // A 1-RTT packet for an ID never seen in a long header is its own flow
+        let unknown = quic_packet(6102, quic_short_header_result(&[0x42; 20]));
+        update_connection(
+            &connections,
+            unknown,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        assert_eq!(connections.len(), 2);
+
+        // Deferred DPI folds the flow created before its result came back
+        let late = syn_packet(6103, Ipv4Addr::new(10, 0, 4, 1));
+        let late_key = late.connection_key.clone();
+        update_connection(
+            &connections,
+            late,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        merge_classified(
+            &connections,
+            Classified {
+                connection_key: late_key.clone(),
+                result: quic_short_header_result(&dcid),
+                instant: Instant::now(),
+            },
+            &MergeOptions::default(),
+        );
+        assert!(!connections.contains_key(&late_key));
+        assert_eq!(connections.get(&original_key).unwrap().packets_received, 3);
+    }
+
+    /// Process lookup that takes as long as an lsof call on a busy machine
+    struct SlowLookup;
+
+    impl ProcessLookup for SlowLookup {
+        fn get_process_for_connection(&self, _conn: &Connection) -> Option<(u32, String)> {
+            thread::sleep(Duration::from_millis(300));
+            Some((42, "slow".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_slow_process_lookup_does_not_lock_the_table() {
+        let connections = Arc::new(DashMap::new());
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        for port in [7000, 7001] {
+            update_connection(
+                &connections,
+                syn_packet(port, Ipv4Addr::new(10, 0, 4, 1)),
+                &stats,
+                &closed,
+                100,
+                &MergeOptions::default(),
+            );
+        }
+
+        let enricher = {
+            let connections = Arc::clone(&connections);
+            thread::spawn(move || enrich_connections(&connections, &SlowLookup))
+        };
+        thread::sleep(Duration::from_millis(50));
+
+        // Packet processors must still be able to update flows mid-lookup
+        let start = Instant::now();
+        update_connection(
+            &connections,
+            syn_packet(7000, Ipv4Addr::new(10, 0, 4, 1)),
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        update_connection(
+            &connections,
+            syn_packet(7002, Ipv4Addr::new(10, 0, 4, 1)),
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        assert_eq!(enricher.join().unwrap(), 2);
+        let conn = connections
+            .get("TCP:192.168.1.10:7000-TCP:10.0.4.1:80")
+            .unwrap();
+        assert_eq!(conn.pid, Some(42));
+        assert_eq!(conn.packets_received, 2);
+    }
+
+    /// Process lookup backed by a socket table like the platform ones build
+    struct TableLookup(HashMap<ConnectionKey, (u32, String)>);
+
+    impl ProcessLookup for TableLookup {
+        fn get_process_for_connection(&self, conn: &Connection) -> Option<(u32, String)> {
+            lookup_socket(&self.0, conn)
+        }
+    }
+
+    #[test]
+    fn test_enrichment_matches_wildcard_and_mapped_socket_entries() {
+        for (local, remote) in [
+            ("0.0.0.0:7100", "10.0.5.1:80"),
+            ("[::ffff:192.168.1.10]:7100", "[::ffff:10.0.5.1]:80"),
+            ("[::]:7100", "[::]:0"),
+        ] {
+            let connections = DashMap::new();
+            let stats = AppStats::default();
+            let closed = ClosedHistory::default();
+            for _ in 0..3 {
+                update_connection(
+                    &connections,
+                    syn_packet(7100, Ipv4Addr::new(10, 0, 5, 1)),
+                    &stats,
+                    &closed,
+                    100,
+                    &MergeOptions::default(),
+                );
+            }
+            let socket = ConnectionKey {
+                protocol: Protocol::TCP,
+                local_addr: local.parse().unwrap(),
+                remote_addr: remote.parse().unwrap(),
+            }
+            .normalized();
+            let lookup = TableLookup(HashMap::from([(socket, (4242, "nginx".to_string()))]));
+
+            assert_eq!(enrich_connections(&connections, &lookup), 1, "{}", local);
+            assert_eq!(connections.len(), 1, "{}", local);
+            let conn = connections.iter().next().unwrap();
+            assert_eq!(conn.pid, Some(4242));
+            assert_eq!(conn.process_name.as_deref(), Some("nginx"));
+            assert_eq!(conn.packets_received, 3);
+            assert_eq!(conn.bytes_received, 180);
+            // The pcap side's concrete address is kept
+            assert_eq!(conn.local_addr, "192.168.1.10:7100".parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_flow_in_capture_and_socket_table_is_one_row() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let remote = Ipv4Addr::new(10, 0, 6, 1);
+        let mut outgoing = syn_packet(7200, remote);
+        outgoing.is_outgoing = true;
+        update_connection(
+            &connections,
+            outgoing,
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+        update_connection(
+            &connections,
+            syn_packet(7200, remote),
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        let socket = ConnectionKey {
+            protocol: Protocol::TCP,
+            local_addr: "192.168.1.10:7200".parse().unwrap(),
+            remote_addr: "10.0.6.1:80".parse().unwrap(),
+        }
+        .normalized();
+        let lookup = TableLookup(HashMap::from([(socket, (4343, "curl".to_string()))]));
+        assert_eq!(enrich_connections(&connections, &lookup), 1);
+
+        // The socket table adds the owner; the captured counters stay
+        assert_eq!(connections.len(), 1);
+        let conn = connections.iter().next().unwrap().value().clone();
+        assert_eq!(conn.pid, Some(4343));
+        assert_eq!(conn.process_name.as_deref(), Some("curl"));
+        assert_eq!(conn.bytes_sent, 60);
+        assert_eq!(conn.bytes_received, 60);
+
+        // Later lookups find nothing left to fill in
+        assert_eq!(enrich_connections(&connections, &lookup), 0);
+    }
+
+    #[test]
+    fn test_lan_peer_mac_is_filled_in_from_the_neighbor_table() {
+        let neighbors = NeighborTable::default();
+        let pi = [0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56];
+
+        // An ARP reply or Ethernet frame from the peer teaches its MAC
+        let mut seen = syn_packet(7300, Ipv4Addr::new(192, 168, 1, 20));
+        seen.remote_mac = Some(pi);
+        resolve_remote_mac(&neighbors, &mut seen);
+
+        // ...which flows captured without link-layer headers pick up
+        let mut cooked = syn_packet(7301, Ipv4Addr::new(192, 168, 1, 20));
+        resolve_remote_mac(&neighbors, &mut cooked);
+        assert_eq!(cooked.remote_mac, Some(pi));
+        let conn = create_connection_from_packet(&cooked, &MergeOptions::default());
+        assert_eq!(conn.remote_mac, Some(pi));
+
+        let mut internet = syn_packet(7302, Ipv4Addr::new(10, 0, 7, 1));
+        resolve_remote_mac(&neighbors, &mut internet);
+        assert_eq!(internet.remote_mac, None);
+    }
+
+    #[test]
+    fn test_scan_alert_tags_the_flows_that_led_up_to_it() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        let scans = ScanDetector::new(ScanThresholds {
+            ports: 5,
+            ..Default::default()
+        });
+
+        let mut alerts = Vec::new();
+        for port in 0..6u16 {
+            let mut packet = syn_packet(8000 + port, Ipv4Addr::new(10, 0, 8, 1));
+            let (scan, raised) = scans.observe(&packet);
+            packet.scan = scan;
+            update_connection(
+                &connections,
+                packet,
+                &stats,
+                &closed,
+                100,
+                &MergeOptions::default(),
+            );
+            for alert in raised {
+                tag_scan_flows(&connections, &alert);
+                alerts.push(alert);
+            }
+        }
+        // Someone else's connection is left alone
+        update_connection(
+            &connections,
+            syn_packet(8100, Ipv4Addr::new(10, 0, 8, 2)),
+            &stats,
+            &closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].count, 5);
+        let tagged = connections
+            .iter()
+            .filter(|conn| conn.scan == Some(ScanKind::PortScan))
+            .count();
+        assert_eq!(tagged, 6);
+        assert_eq!(connections.len(), 7);
+    }
+
+    #[test]
+    fn test_flows_whose_socket_disappears_are_marked_source_gone() {
+        let connections = DashMap::new();
+        let stats = AppStats::default();
+        let closed = ClosedHistory::default();
+        for port in [7200, 7201] {
+            update_connection(
+                &connections,
+                syn_packet(port, Ipv4Addr::new(10, 0, 6, 1)),
+                &stats,
+                &closed,
+                100,
+                &MergeOptions::default(),
+            );
+        }
+        let socket = ConnectionKey {
+            protocol: Protocol::TCP,
+            local_addr: "192.168.1.10:7200".parse().unwrap(),
+            remote_addr: "10.0.6.1:80".parse().unwrap(),
+        }
+        .normalized();
+        let present = TableLookup(HashMap::from([(socket, (4242, "curl".to_string()))]));
+        let empty = TableLookup(HashMap::new());
+        let mut sockets_seen = HashSet::new();
+
+        // Flows never seen in the socket tables are not flagged
+        assert_eq!(
+            mark_gone_sockets(&connections, &present, &mut sockets_seen),
+            0
+        );
+        assert_eq!(
+            mark_gone_sockets(&connections, &present, &mut sockets_seen),
+            0
+        );
+        assert_eq!(
+            mark_gone_sockets(&connections, &empty, &mut sockets_seen),
+            1
+        );
+        assert_eq!(
+            mark_gone_sockets(&connections, &empty, &mut sockets_seen),
+            0
+        );
+
+        for conn in connections.iter() {
+            let gone = conn.local_addr.port() == 7200;
+            assert_eq!(conn.source_gone, gone);
+            assert_eq!(
+                conn.close_reason() == CloseReason::SourceGone,
+                gone,
+                "{}",
+                conn.local_addr
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_ends_after_count_and_summarises_open_and_closed_flows() {
+        let app = App::new(Config {
+            enable_dpi: false,
+            stop_after_connections: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!app.run_finished());
+
+        update_connection(
+            &app.connections,
+            syn_packet(40000, Ipv4Addr::new(203, 0, 113, 1)),
+            &app.stats,
+            &app.closed,
+            100,
+            &MergeOptions::default(),
+        );
+        assert!(!app.run_finished());
+        update_connection(
+            &app.connections,
+            syn_packet(40001, Ipv4Addr::new(203, 0, 113, 2)),
+            &app.stats,
+            &app.closed,
+            100,
+            &MergeOptions::default(),
+        );
+        assert!(app.run_finished());
+
+        let mut dns = Connection::new(
+            Protocol::UDP,
+            "192.168.1.10:53000".parse().unwrap(),
+            "192.0.2.53:53".parse().unwrap(),
+            ProtocolState::Udp(crate::network::types::UdpState::Active),
+        );
+        dns.bytes_sent = 40;
+        dns.bytes_received = 120;
+        app.closed.record(dns, CloseReason::IdleTimeout);
+
+        let tcp_received: u64 = app.connections.iter().map(|c| c.bytes_received).sum();
+        let summary = app.run_summary();
+        assert_eq!(summary.protocols.len(), 2);
+        assert_eq!(summary.protocols["TCP"].connections, 2);
+        assert_eq!(
+            summary.protocols["UDP"],
+            ProtocolTotals {
+                connections: 1,
+                bytes_sent: 40,
+                bytes_received: 120,
+            }
+        );
+        assert_eq!(summary.total().connections, 3);
+        assert_eq!(summary.total().bytes_received, tcp_received + 120);
+
+        let report = summary.render();
+        assert!(report.contains("Connections: 3\n"));
+        assert!(report.contains(&format!("{:<10}{:>12}{:>16}{:>16}\n", "UDP", 1, 40, 120)));
+        assert!(report.contains("Alerts: 0\n"));
+        assert!(!report.contains("Alert: "));
+
+        // Alerts fired during the run are counted and listed
+        for port in 0..ScanThresholds::default().ports as u16 {
+            app.scans
+                .observe(&syn_packet(9000 + port, Ipv4Addr::new(10, 0, 9, 1)));
+        }
+        let summary = app.run_summary();
+        assert_eq!(summary.alerts_raised, 1);
+        let report = summary.render();
+        assert!(report.contains("Alerts: 1\n"));
+        assert!(report.contains("Alert: port scan from 10.0.9.1: 20 ports in"));
+
+        // A zero duration ends the run as soon as it starts
+        let mut app = App::new(Config {
+            enable_dpi: false,
+            duration: Some(Duration::ZERO),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!app.run_finished());
+        app.started_at = Some(Instant::now());
+        assert!(app.run_finished());
+    }
+
+    #[test]
+    fn test_bpf_filter_needs_a_running_capture() {
+        let app = App::new(Config {
+            enable_dpi: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let err = app.set_bpf_filter("tcp").unwrap_err();
+        assert_eq!(err.to_string(), "Packet capture is not running");
+
+        *app.linktype.write().unwrap() = Some(1);
+        assert!(app.set_bpf_filter("tcp or udp").is_ok());
+        assert!(app.set_bpf_filter("tcp or").is_err());
+        assert_eq!(
+            app.bpf_filter_rx.try_iter().collect::<Vec<_>>(),
+            ["tcp or udp"]
+        );
+    }
+
+    #[test]
+    fn test_connections_export_to_csv() {
+        use crate::network::types::{DpiInfo, HttpInfo, HttpVersion};
+        let app = App::new(Config {
+            enable_dpi: false,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let started = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut web = create_connection_from_packet(
+            &syn_packet(40000, Ipv4Addr::new(203, 0, 113, 1)),
+            &MergeOptions::default(),
+        );
+        web.created_at = started + Duration::from_secs(5);
+        web.last_activity = started + Duration::from_secs(9);
+        web.pid = Some(4242);
+        web.process_name = Some("web, \"beta\"".to_string());
+        web.service_name = Some("http".to_string());
+        web.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Http(HttpInfo {
+                version: HttpVersion::Http11,
+                method: Some("GET".to_string()),
+                host: Some("example.com".to_string()),
+                path: None,
+                status_code: None,
+                user_agent: None,
+                response_time: None,
+            }),
+            first_packet_time: Instant::now(),
+            last_update_time: Instant::now(),
+            pending_request_at: None,
+        });
+        let mut older = create_connection_from_packet(
+            &syn_packet(40001, Ipv4Addr::new(203, 0, 113, 2)),
+            &MergeOptions::default(),
+        );
+        older.created_at = started;
+        older.last_activity = started;
+        for conn in [web, older] {
+            app.connections.insert(conn.key(), conn);
+        }
+
+        let path = std::env::temp_dir().join(format!("rustnet_export_{}.csv", std::process::id()));
+        app.export_connections_to_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[0].split(',').count(), 14);
+        // Oldest first, with unknown values left empty
+        assert!(lines[1].starts_with("TCP,192.168.1.10:40001,203.0.113.2:80,SYN_RECV,,,"));
+        assert!(lines[2].starts_with(
+            "TCP,192.168.1.10:40000,203.0.113.1:80,SYN_RECV,4242,\"web, \"\"beta\"\"\","
+        ));
+        assert!(lines[2].ends_with(",http,HTTP (example.com)"));
+
+        let created_at = lines[1].split(',').nth(10).unwrap();
+        assert_eq!(
+            SystemTime::from(DateTime::parse_from_rfc3339(created_at).unwrap()),
+            started
+        );
+    }
+
+    #[test]
+    fn test_connections_filtered_by_predicate() {
+        let app = App::new(Config {
+            enable_dpi: false,
+            ..Default::default()
+        })
+        .unwrap();
+        for port in [40000, 40001, 40002] {
+            let mut conn = create_connection_from_packet(
+                &syn_packet(port, Ipv4Addr::new(203, 0, 113, 1)),
+                &MergeOptions::default(),
+            );
+            conn.pid = (port != 40001).then_some(4242);
+            app.connections.insert(conn.key(), conn);
+        }
+
+        let mut owned = app.get_connections_filtered(|conn| conn.pid == Some(4242));
+        owned.sort_by_key(|conn| conn.local_addr.port());
+        let ports: Vec<u16> = owned.iter().map(|conn| conn.local_addr.port()).collect();
+        assert_eq!(ports, [40000, 40002]);
+        assert!(app.get_connections_filtered(|_| false).is_empty());
+    }
+
+    #[test]
+    fn test_warm_up_publishes_without_waiting_for_the_refresh() {
+        let app = App::new(Config {
+            enable_dpi: false,
+            refresh_interval: 60_000,
+            ..Default::default()
+        })
+        .unwrap();
+        app.start_snapshot_provider(Arc::clone(&app.connections))
+            .unwrap();
+        update_connection(
+            &app.connections,
+            syn_packet(7300, Ipv4Addr::new(10, 0, 7, 1)),
+            &app.stats,
+            &app.closed,
+            100,
+            &MergeOptions::default(),
+        );
+
+        let start = Instant::now();
+        app.warm_up(Duration::ZERO).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(app.get_snapshot().total_connections, 1);
+        assert!(!app.is_loading());
+        app.stop();
+    }
+
+    #[test]
+    fn test_ui_commands_never_block() {
+        let app = App::new(Config {
+            enable_dpi: false,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Nothing drains the queue since the enrichment thread isn't running
+        let start = Instant::now();
+        for i in 0..1000 {
+            app.request_process_refresh();
+            app.request_process_lookup(format!("TCP:10.0.0.1:{}-TCP:10.0.0.2:443", i));
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(
+            app.enrichment_rx.try_recv().unwrap(),
+            EnrichmentCommand::RefreshNow
+        );
+    }
+}
@@ -13,6 +13,21 @@ pub fn build_cli() -> Command {
                 .help("Network interface to monitor")
                 .required(false),
         )
+        .arg(
+            Arg::new("read-file")
+                .long("read-file")
+                .value_name("FILE")
+                .help("Read packets from a pcap or pcapng file instead of an interface; needs no capture privileges")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .conflicts_with("interface")
+                .required(false),
+        )
+        .arg(
+            Arg::new("no-promisc")
+                .long("no-promisc")
+                .help("Don't put the interface in promiscuous mode; only this host's traffic is seen")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("no-localhost")
                 .long("no-localhost")
@@ -35,12 +50,220 @@ pub fn build_cli() -> Command {
                 .default_value("1000")
                 .required(false),
         )
+        .arg(
+            Arg::new("max-connections")
+                .long("max-connections")
+                .value_name("COUNT")
+                .help("Maximum number of tracked connections before the oldest are evicted")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("50000")
+                .required(false),
+        )
+        .arg(
+            Arg::new("max-idle")
+                .long("max-idle")
+                .value_name("SECONDS")
+                .help("Expire connections idle for longer than this, even if their protocol allows more")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .required(false),
+        )
+        .arg(
+            Arg::new("bpf-filter")
+                .long("bpf-filter")
+                .value_name("FILTER")
+                .help("BPF filter expression for packet capture (e.g. \"tcp port 443\")")
+                .required(false),
+        )
         .arg(
             Arg::new("no-dpi")
                 .long("no-dpi")
                 .help("Disable deep packet inspection")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("count-link-layer")
+                .long("count-link-layer")
+                .help("Include link-layer headers in byte counts (on-the-wire sizes)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("router-mode")
+                .long("router-mode")
+                .value_name("MODE")
+                .help("Treat flows between two other hosts as forwarded: auto (neither address is local), on or off")
+                .value_parser(["auto", "on", "off"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("count-offloaded")
+                .long("count-offloaded")
+                .value_name("MODE")
+                .help("Count TCP packets bigger than the MTU, as captured with segmentation offload, as one packet (frames) or as the MTU-sized segments they stand for (segments)")
+                .value_parser(["frames", "segments"])
+                .default_value("frames"),
+        )
+        .arg(
+            Arg::new("decapsulate-tunnels")
+                .long("decapsulate-tunnels")
+                .help("Show the connections carried inside GRE and VXLAN tunnels instead of the tunnel traffic")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show-hostnames")
+                .long("show-hostnames")
+                .help("Show remote hosts by name, looked up with reverse DNS in the background")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("geoip-db")
+                .long("geoip-db")
+                .value_name("FILE")
+                .help("Locate remote hosts with the MaxMind DB in FILE, e.g. GeoLite2-City.mmdb (default: look in the usual places)")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("coalesce-ports")
+                .long("coalesce-ports")
+                .value_name("PORTS")
+                .help("Show UDP flows from different local ports to these server ports as one row")
+                .value_parser(clap::value_parser!(u16))
+                .value_delimiter(',')
+                .default_value("53,123,443"),
+        )
+        .arg(
+            Arg::new("no-coalesce")
+                .long("no-coalesce")
+                .help("Show every UDP flow as its own row")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore-file")
+                .long("ignore-file")
+                .value_name("FILE")
+                .help("Hide connections matching the domain, net, port or process rules in FILE from the list, totals and alerts")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("record-dir")
+                .long("record-dir")
+                .value_name("DIR")
+                .help("Directory that connections recorded with 'w' are saved to as pcap files [default: .]")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("no-keepalive-detection")
+                .long("no-keepalive-detection")
+                .help("Count TCP keepalive probes towards rates and don't mark keepalive-only connections")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("weak-ciphers")
+                .long("weak-ciphers")
+                .value_name("CODES")
+                .help("Also flag TLS connections negotiating these cipher suites, as hex IDs like 0xc013")
+                .value_parser(|code: &str| {
+                    let digits = code.trim_start_matches("0x").trim_start_matches("0X");
+                    u16::from_str_radix(digits, 16)
+                })
+                .value_delimiter(',')
+                .required(false),
+        )
+        .arg(
+            Arg::new("scan-ports")
+                .long("scan-ports")
+                .value_name("COUNT")
+                .help("Report a port scan when one remote host tries COUNT local ports within the scan window")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("scan-hosts")
+                .long("scan-hosts")
+                .value_name("COUNT")
+                .help("Report a host sweep when this host tries one port on COUNT remote hosts that don't answer within the scan window")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("syn-flood-rate")
+                .long("syn-flood-rate")
+                .value_name("PER_SECOND")
+                .help("Report a SYN flood when one local port receives more SYNs per second than this over the scan window")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("scan-window")
+                .long("scan-window")
+                .value_name("SECONDS")
+                .help("Window the scan and flood thresholds are counted over")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("login-attempts")
+                .long("login-attempts")
+                .value_name("COUNT")
+                .help("Report brute force when one remote host opens COUNT short sessions to one auth port within a minute")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("auth-ports")
+                .long("auth-ports")
+                .value_name("PORTS")
+                .help("Local ports of services that take logins, watched for brute force along with SSH on any port")
+                .value_parser(clap::value_parser!(u16))
+                .value_delimiter(',')
+                .default_value("22,465,587,3389"),
+        )
+        .arg(
+            Arg::new("os-signatures")
+                .long("os-signatures")
+                .value_name("FILE")
+                .help("Guess remote operating systems with the TCP signatures in FILE instead of the built-in table")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("duration")
+                .long("duration")
+                .value_name("SECONDS")
+                .help("Stop after SECONDS and print a summary of the run")
+                .value_parser(clap::value_parser!(u64))
+                .required(false),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .value_name("CONNECTIONS")
+                .help("Stop once CONNECTIONS connections have been seen and print a summary of the run")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .required(false),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .help("Capture without the terminal UI (needs --duration or --count)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary-file")
+                .long("summary-file")
+                .value_name("PATH")
+                .help("Write the end-of-run summary to PATH instead of stdout")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("fail-on-alert")
+                .long("fail-on-alert")
+                .help("Exit with status 1 if a scan or attack alert was raised during the run")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("log-level")
                 .short('l')
@@ -49,4 +272,42 @@ pub fn build_cli() -> Command {
                 .help("Set the log level (if not provided, no logging will be enabled)")
                 .required(false),
         )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Write logs to PATH instead of a timestamped file in logs/ (enables logging)")
+                .required(false),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Read settings from FILE instead of ~/.config/rustnet/config.toml")
+                .value_parser(clap::value_parser!(std::path::PathBuf))
+                .required(false),
+        )
+        .arg(
+            Arg::new("save-config")
+                .long("save-config")
+                .help("Save the interface, localhost filtering, BPF filter, refresh interval, hostname, location and record directory settings in effect to the settings file")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("interfaces")
+                .about("List network interfaces with their addresses and traffic counters")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print JSON instead of a table (one document per refresh with --watch)")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .short('w')
+                        .long("watch")
+                        .help("Refresh every second and show receive and transmit rates")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
 }
@@ -1,187 +1,248 @@
-use anyhow::{Result, anyhow};
-use std::fs;
-use std::path::PathBuf;
-
-/// Application configuration
-#[derive(Debug, Clone)]
-pub struct Config {
-    /// Network interface to monitor
+//! The settings file, `config.toml`
+//!
+//! Every key is optional and overrides the default it is named after;
+//! command-line options are applied on top. Keys and tables this version
+//! doesn't know are logged and ignored so files written by other versions
+//! still load.
+
+use anyhow::{Context, Result, anyhow};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app::Config;
+
+/// UI refresh intervals accepted from the file, in milliseconds
+const REFRESH_INTERVAL_MS: RangeInclusive<u64> = 10..=60_000;
+
+/// The keys of the settings file
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
     pub interface: Option<String>,
-    /// Interface language (ISO code)
-    pub language: String,
-    /// Path to MaxMind GeoIP database
-    pub geoip_db_path: Option<PathBuf>,
-    /// Refresh interval in milliseconds
-    pub refresh_interval: u64,
-    /// Show IP locations (requires MaxMind DB)
-    pub show_locations: bool,
-    /// Filter out localhost (loopback) traffic
-    pub filter_localhost: bool,
-    /// Interval in milliseconds for the packet processing loop's sleep. 0 means minimal sleep for continuous processing.
-    pub packet_processing_interval_ms: u64,
-    /// Custom configuration file path
-    pub config_path: Option<PathBuf>,
+    pub promiscuous: Option<bool>,
+    pub filter_localhost: Option<bool>,
+    pub bpf_filter: Option<String>,
+    pub refresh_interval_ms: Option<u64>,
+    pub show_hostnames: Option<bool>,
+    /// Show where remote hosts are; false skips loading a GeoIP database
+    pub show_locations: Option<bool>,
+    pub geoip_db: Option<PathBuf>,
+    pub record_dir: Option<PathBuf>,
+    /// Cipher suites to flag on top of the built-in weak list, e.g. `[0xc013]`
+    pub weak_ciphers: Option<Vec<u16>>,
+    /// Keep TCP keepalive probes out of rates and dim keepalive-only flows
+    pub keepalive_detection: Option<bool>,
+    /// Longest a flow may sit idle before cleanup, in seconds
+    pub max_idle_secs: Option<u64>,
+    /// Keys from other versions, kept only to warn about them
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            interface: None,
-            language: "en".to_string(),
-            geoip_db_path: None,
-            refresh_interval: 1000,
-            show_locations: true,
-            filter_localhost: true,
-            packet_processing_interval_ms: 0, // Default to continuous processing (minimal sleep)
-            config_path: None,
+impl Settings {
+    /// `$XDG_CONFIG_HOME/rustnet/config.toml` or `~/.config/rustnet/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
+            return Some(PathBuf::from(xdg_config).join("rustnet/config.toml"));
         }
+        std::env::var("HOME")
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .ok()
+            .map(|home| PathBuf::from(home).join(".config/rustnet/config.toml"))
     }
-}
-
-impl Config {
-    /// Load configuration from file
-    pub fn load(path: Option<&str>) -> Result<Self> {
-        let config_path = if let Some(path) = path {
-            PathBuf::from(path)
-        } else {
-            Self::find_config_file()?
-        };
 
-        let mut config = Config::default();
+    /// Read the settings in the TOML file at `path`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("invalid settings in {}", path.display()))
+    }
 
-        if config_path.exists() {
-            config.config_path = Some(config_path.clone());
-
-            // Read config file
-            let content = fs::read_to_string(&config_path)?;
-
-            // Parse YAML
-            for line in content.lines() {
-                let line = line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
-
-                if let Some(pos) = line.find(':') {
-                    let key = line[..pos].trim();
-                    let value = line[pos + 1..].trim();
-
-                    match key {
-                        "interface" => {
-                            config.interface = Some(value.to_string());
-                        }
-                        "language" => {
-                            config.language = value.to_string();
-                        }
-                        "geoip_db_path" => {
-                            config.geoip_db_path = Some(PathBuf::from(value));
-                        }
-                        "refresh_interval" => {
-                            if let Ok(interval) = value.parse::<u64>() {
-                                config.refresh_interval = interval;
-                            }
-                        }
-                        "show_locations" => {
-                            if value == "true" {
-                                config.show_locations = true;
-                            } else if value == "false" {
-                                config.show_locations = false;
-                            }
-                        }
-                        "filter_localhost" => {
-                            if value == "true" {
-                                config.filter_localhost = true;
-                            } else if value == "false" {
-                                config.filter_localhost = false;
-                            }
-                        }
-                        "packet_processing_interval_ms" => {
-                            if let Ok(interval) = value.parse::<u64>() {
-                                config.packet_processing_interval_ms = interval;
-                            }
-                        }
-                        _ => {
-                            // Ignore unknown keys
-                        }
-                    }
-                }
-            }
+    fn parse(content: &str) -> Result<Self> {
+        let settings: Self = toml::from_str(content)?;
+        for key in settings.unknown.keys() {
+            warn!("Ignoring unknown setting {:?}", key);
         }
+        Ok(settings)
+    }
 
-        // Try to find GeoIP database if not specified in config
-        if config.geoip_db_path.is_none() {
-            for path in Self::possible_geoip_paths() {
-                if path.exists() {
-                    config.geoip_db_path = Some(path);
-                    break;
-                }
+    /// Override the defaults in `config` with the keys that are set
+    pub fn apply(self, config: &mut Config) -> Result<()> {
+        if let Some(interval) = self.refresh_interval_ms {
+            if !REFRESH_INTERVAL_MS.contains(&interval) {
+                return Err(anyhow!(
+                    "refresh_interval_ms must be between {} and {}, not {}",
+                    REFRESH_INTERVAL_MS.start(),
+                    REFRESH_INTERVAL_MS.end(),
+                    interval
+                ));
             }
+            config.refresh_interval = interval;
         }
-
-        Ok(config)
-    }
-
-    /// Find configuration file
-    fn find_config_file() -> Result<PathBuf> {
-        // Try XDG config directory first
-        if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
-            let xdg_path = PathBuf::from(xdg_config).join("rustnet/config.yml");
-            if xdg_path.exists() {
-                return Ok(xdg_path);
+        if let Some(interface) = self.interface {
+            if interface.is_empty() {
+                return Err(anyhow!("interface must not be empty"));
             }
+            config.interface = Some(interface);
         }
-
-        // Try ~/.config/rustnet
-        let home = Self::get_home_dir()?;
-        let home_config = home.join(".config/rustnet/config.yml");
-        if home_config.exists() {
-            return Ok(home_config);
+        if self.bpf_filter.is_some() {
+            config.bpf_filter = self.bpf_filter;
         }
-
-        // Try current directory
-        let current_config = PathBuf::from("config.yml");
-        if current_config.exists() {
-            return Ok(current_config);
+        if self.geoip_db.is_some() {
+            config.geoip_db = self.geoip_db;
         }
-
-        // Default to home config path
-        Ok(home_config)
+        if let Some(dir) = self.record_dir {
+            config.record_dir = dir;
+        }
+        if let Some(secs) = self.max_idle_secs {
+            if secs == 0 {
+                return Err(anyhow!("max_idle_secs must be at least 1"));
+            }
+            config.max_idle = Some(Duration::from_secs(secs));
+        }
+        if let Some(codes) = self.weak_ciphers {
+            config.weak_cipher_suites = codes;
+        }
+        config.promiscuous = self.promiscuous.unwrap_or(config.promiscuous);
+        config.filter_localhost = self.filter_localhost.unwrap_or(config.filter_localhost);
+        config.show_hostnames = self.show_hostnames.unwrap_or(config.show_hostnames);
+        config.show_locations = self.show_locations.unwrap_or(config.show_locations);
+        config.keepalive_detection = self
+            .keepalive_detection
+            .unwrap_or(config.keepalive_detection);
+        Ok(())
     }
 
-    /// Get home directory
-    fn get_home_dir() -> Result<PathBuf> {
-        if let Ok(home) = std::env::var("HOME") {
-            return Ok(PathBuf::from(home));
+    /// Write these settings to `path`, creating its directory
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = format!("# RustNet settings\n{}", toml::to_string(self)?);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
         }
+        std::fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
 
-        if let Ok(userprofile) = std::env::var("USERPROFILE") {
-            return Ok(PathBuf::from(userprofile));
+impl From<&Config> for Settings {
+    /// The settings in effect, for `--save-config`
+    fn from(config: &Config) -> Self {
+        Self {
+            interface: config.interface.clone(),
+            promiscuous: Some(config.promiscuous),
+            filter_localhost: Some(config.filter_localhost),
+            bpf_filter: config.bpf_filter.clone(),
+            refresh_interval_ms: Some(config.refresh_interval),
+            show_hostnames: Some(config.show_hostnames),
+            show_locations: Some(config.show_locations),
+            geoip_db: config.geoip_db.clone(),
+            record_dir: Some(config.record_dir.clone()),
+            weak_ciphers: (!config.weak_cipher_suites.is_empty())
+                .then(|| config.weak_cipher_suites.clone()),
+            keepalive_detection: Some(config.keepalive_detection),
+            max_idle_secs: config.max_idle.map(|limit| limit.as_secs()),
+            unknown: BTreeMap::new(),
         }
-
-        Err(anyhow!("Could not determine home directory"))
     }
+}
 
-    /// Get possible GeoIP database paths
-    fn possible_geoip_paths() -> Vec<PathBuf> {
-        let mut paths = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Current directory
-        paths.push(PathBuf::from("GeoLite2-City.mmdb"));
+    fn load(content: &str) -> Result<Config> {
+        let mut config = Config::default();
+        Settings::parse(content)?.apply(&mut config)?;
+        Ok(config)
+    }
 
-        // Try XDG data directory
-        if let Ok(xdg_data) = std::env::var("XDG_DATA_HOME") {
-            paths.push(PathBuf::from(xdg_data).join("rustnet/GeoLite2-City.mmdb"));
-        }
+    #[test]
+    fn test_settings_file_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("rustnet-settings-{}", std::process::id()))
+            .join("config.toml");
+        let config = Config {
+            interface: Some("eth0".to_string()),
+            promiscuous: false,
+            filter_localhost: false,
+            bpf_filter: Some("not host \"10.0.0.1\" \\ x\n\u{7}".to_string()),
+            refresh_interval: 250,
+            show_hostnames: true,
+            show_locations: false,
+            geoip_db: Some(PathBuf::from("/opt/GeoLite2-City.mmdb")),
+            record_dir: PathBuf::from("/var/tmp/captures"),
+            weak_cipher_suites: vec![0xc013, 0xc014],
+            keepalive_detection: false,
+            max_idle: Some(Duration::from_secs(120)),
+            ..Config::default()
+        };
+        Settings::from(&config).save(&path).unwrap();
+        let mut loaded = Config::default();
+        Settings::from_file(&path)
+            .unwrap()
+            .apply(&mut loaded)
+            .unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(loaded.interface, config.interface);
+        assert!(!loaded.promiscuous);
+        assert!(!loaded.filter_localhost);
+        assert_eq!(loaded.bpf_filter, config.bpf_filter);
+        assert_eq!(loaded.refresh_interval, 250);
+        assert!(loaded.show_hostnames);
+        assert!(!loaded.show_locations);
+        assert_eq!(loaded.geoip_db, config.geoip_db);
+        assert_eq!(loaded.record_dir, config.record_dir);
+        assert_eq!(loaded.weak_cipher_suites, config.weak_cipher_suites);
+        assert!(!loaded.keepalive_detection);
+        assert_eq!(loaded.max_idle, config.max_idle);
+
+        // Unset options are left out rather than written empty
+        let defaults = toml::to_string(&Settings::from(&Config::default())).unwrap();
+        assert!(!defaults.contains("interface"));
+        assert_eq!(
+            Settings::parse(&defaults).unwrap(),
+            Settings::from(&Config::default())
+        );
+    }
 
-        // Try home directory
-        if let Ok(home) = Self::get_home_dir() {
-            paths.push(home.join(".local/share/rustnet/GeoLite2-City.mmdb"));
+    #[test]
+    fn test_settings_file_syntax() {
+        // Comments, tables and keys from other versions are skipped
+        let config = load(
+            "# mine\nrefresh_interval_ms = 2_000 # slower\nlanguage = \"en\"\n\n[theme]\ndark = true\n",
+        )
+        .unwrap();
+        assert_eq!(config.refresh_interval, 2000);
+
+        // Cipher suites are written as TOML's hex integers
+        let config = load("weak_ciphers = [0xc013, 0xC014]\n").unwrap();
+        assert_eq!(config.weak_cipher_suites, vec![0xc013, 0xc014]);
+
+        // Keys left out keep their defaults
+        let config = load("show_hostnames = true\n").unwrap();
+        assert!(config.show_hostnames);
+        assert!(config.promiscuous);
+        assert_eq!(config.refresh_interval, Config::default().refresh_interval);
+
+        for bad in [
+            "interface",
+            "interface = eth0",
+            "interface = \"\"",
+            "filter_localhost = \"yes\"",
+            "bpf_filter = \"tcp",
+            "refresh_interval_ms = -1",
+            "refresh_interval_ms = 0",
+            "refresh_interval_ms = 3_600_000",
+            "weak_ciphers = 0xc013",
+            "weak_ciphers = [0x10000]",
+            "max_idle_secs = 0",
+        ] {
+            assert!(load(bad).is_err(), "{}", bad);
         }
-
-        // System paths
-        paths.push(PathBuf::from("/usr/share/GeoIP/GeoLite2-City.mmdb"));
-        paths.push(PathBuf::from("/usr/local/share/GeoIP/GeoLite2-City.mmdb"));
-
-        paths
     }
 }
@@ -1,5 +1,16 @@
 use crate::network::types::{ApplicationProtocol, Connection};
 
+/// Server name a connection was opened for: the SNI of a TLS or QUIC
+/// handshake, or the HTTP Host header
+pub fn server_name(connection: &Connection) -> Option<&str> {
+    match &connection.dpi_info.as_ref()?.application {
+        ApplicationProtocol::Https(info) => info.tls_info.as_ref()?.sni.as_deref(),
+        ApplicationProtocol::Quic(info) => info.tls_info.as_ref()?.sni.as_deref(),
+        ApplicationProtocol::Http(info) => info.host.as_deref(),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FilterCriteria {
     /// Match any field containing this text
@@ -26,6 +37,8 @@ pub enum FilterCriteria {
     Application(String),
     /// Match connection state (e.g., ESTABLISHED, SYN_RECV)
     State(String),
+    /// Match TLS handshake warnings by kind, or any with "weak"
+    TlsWarning(String),
 }
 
 pub struct ConnectionFilter {
@@ -84,6 +97,9 @@ impl ConnectionFilter {
                     "state" => {
                         criteria.push(FilterCriteria::State(value));
                     }
+                    "tls" => {
+                        criteria.push(FilterCriteria::TlsWarning(value));
+                    }
                     _ => {
                         // Unknown keyword, treat as general search
                         criteria.push(FilterCriteria::General(part.to_lowercase()));
@@ -159,6 +175,11 @@ impl ConnectionFilter {
             FilterCriteria::State(state_text) => {
                 connection.state().to_lowercase().contains(state_text)
             }
+            FilterCriteria::TlsWarning(kind_text) => {
+                connection.tls_warnings.iter().any(|warning| {
+                    kind_text == "weak" || warning.keyword().contains(kind_text.as_str())
+                })
+            }
         })
     }
 
@@ -210,31 +231,7 @@ impl ConnectionFilter {
 
     /// Check if SNI matches the search text
     fn matches_sni(&self, connection: &Connection, sni_text: &str) -> bool {
-        if let Some(ref dpi_info) = connection.dpi_info {
-            match &dpi_info.application {
-                ApplicationProtocol::Https(info) => {
-                    if let Some(ref tls_info) = info.tls_info
-                        && let Some(ref sni) = tls_info.sni
-                    {
-                        return sni.to_lowercase().contains(sni_text);
-                    }
-                }
-                ApplicationProtocol::Quic(info) => {
-                    if let Some(ref tls_info) = info.tls_info
-                        && let Some(ref sni) = tls_info.sni
-                    {
-                        return sni.to_lowercase().contains(sni_text);
-                    }
-                }
-                ApplicationProtocol::Http(info) => {
-                    if let Some(ref host) = info.host {
-                        return host.to_lowercase().contains(sni_text);
-                    }
-                }
-                _ => {}
-            }
-        }
-        false
+        server_name(connection).is_some_and(|name| name.to_lowercase().contains(sni_text))
     }
 
     /// Check if application protocol matches the search text
@@ -343,6 +340,13 @@ impl ConnectionFilter {
                     }
                 }
             }
+            ApplicationProtocol::Custom(info) => {
+                if let Some(ref detail) = info.detail
+                    && detail.to_lowercase().contains(text)
+                {
+                    return true;
+                }
+            }
         }
 
         false
@@ -461,7 +465,7 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         let active_filter = ConnectionFilter::parse("state:udp_active");
@@ -524,4 +528,25 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_tls_warning_filter() {
+        use crate::network::types::*;
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), 50000),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 443),
+            ProtocolState::Tcp(TcpState::Established),
+        );
+        assert!(!ConnectionFilter::parse("tls:weak").matches(&conn));
+
+        conn.tls_warnings = vec![TlsWarning::DeprecatedVersion(TlsVersion::Tls10)];
+        assert!(ConnectionFilter::parse("tls:weak").matches(&conn));
+        assert!(ConnectionFilter::parse("tls:deprecated").matches(&conn));
+        assert!(ConnectionFilter::parse("TLS:Deprecated").matches(&conn));
+        assert!(!ConnectionFilter::parse("tls:cipher").matches(&conn));
+        assert!(!ConnectionFilter::parse("tls:nosni").matches(&conn));
+    }
 }
@@ -0,0 +1,424 @@
+use anyhow::{Result, anyhow};
+use log::info;
+use std::net::{IpAddr, SocketAddr};
+use std::process::Command;
+
+use crate::network::types::{Connection, Protocol};
+
+/// Firewall tool used to express a block rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirewallBackend {
+    /// Linux iptables / ip6tables
+    Iptables,
+    /// macOS / BSD packet filter
+    Pf,
+    /// Windows advanced firewall
+    Netsh,
+}
+
+impl FirewallBackend {
+    /// Backend for the platform we are running on
+    pub fn native() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Netsh
+        } else if cfg!(any(target_os = "macos", target_os = "freebsd")) {
+            Self::Pf
+        } else {
+            Self::Iptables
+        }
+    }
+}
+
+/// Whether a generated rule has been executed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleStatus {
+    Pending,
+    Applied,
+    Failed(String),
+}
+
+impl std::fmt::Display for RuleStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleStatus::Pending => write!(f, "pending"),
+            RuleStatus::Applied => write!(f, "applied"),
+            RuleStatus::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+/// A block rule generated for a connection's remote endpoint
+#[derive(Debug, Clone)]
+pub struct FirewallRule {
+    pub protocol: Protocol,
+    pub target: SocketAddr,
+    pub backend: FirewallBackend,
+    pub command: String,
+    pub status: RuleStatus,
+}
+
+impl FirewallRule {
+    /// Build a rule blocking the connection's remote address, in the
+    /// direction the connection was opened
+    pub fn for_connection(conn: &Connection, backend: FirewallBackend) -> Option<Self> {
+        let command = block_command(
+            conn.protocol,
+            conn.remote_addr,
+            conn.local_addr.port(),
+            conn.inbound,
+            backend,
+        )?;
+        Some(Self {
+            protocol: conn.protocol,
+            target: conn.remote_addr,
+            backend,
+            command,
+            status: RuleStatus::Pending,
+        })
+    }
+
+    /// Execute the rule's command through the system shell
+    pub fn apply(&mut self) -> Result<()> {
+        info!("Applying firewall rule: {}", self.command);
+        let output = if self.backend == FirewallBackend::Netsh {
+            Command::new("cmd").args(["/C", &self.command]).output()
+        } else {
+            Command::new("sh").args(["-c", &self.command]).output()
+        };
+
+        let result = match output {
+            Ok(output) if output.status.success() => match self.backend {
+                // Loading the anchor succeeds whether or not pf ever reads it
+                FirewallBackend::Pf => check_pf_anchor(),
+                _ => Ok(()),
+            },
+            Ok(output) => Err(anyhow!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => Err(anyhow!("failed to run command: {}", e)),
+        };
+
+        self.status = match &result {
+            Ok(()) => RuleStatus::Applied,
+            Err(e) => RuleStatus::Failed(e.to_string()),
+        };
+        result
+    }
+}
+
+/// Make sure pf is enabled and its main ruleset evaluates the `rustnet`
+/// anchor, without which the rules loaded into it block nothing
+fn check_pf_anchor() -> Result<()> {
+    let pfctl = |args: &[&str]| -> Result<String> {
+        let output = Command::new("pfctl")
+            .args(args)
+            .output()
+            .map_err(|e| anyhow!("failed to run pfctl: {}", e))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    match pf_anchor_problem(&pfctl(&["-s", "info"])?, &pfctl(&["-s", "rules"])?) {
+        Some(problem) => Err(anyhow!(problem)),
+        None => Ok(()),
+    }
+}
+
+/// Why rules in the `rustnet` anchor would not be evaluated, from the output
+/// of `pfctl -s info` and of `pfctl -s rules` for the main ruleset
+fn pf_anchor_problem(info: &str, main_rules: &str) -> Option<String> {
+    if !info.lines().any(|line| line.starts_with("Status: Enabled")) {
+        return Some("pf is disabled, enable it with pfctl -e".to_string());
+    }
+    let referenced = main_rules.lines().any(|line| {
+        let name = line
+            .trim()
+            .strip_prefix("anchor \"")
+            .and_then(|rest| rest.split('"').next());
+        matches!(name, Some("rustnet" | "rustnet/*" | "*"))
+    });
+    if referenced {
+        None
+    } else {
+        Some(
+            "pf never evaluates the rustnet anchor, add anchor \"rustnet\" to /etc/pf.conf and reload it with pfctl -f /etc/pf.conf"
+                .to_string(),
+        )
+    }
+}
+
+/// Generate the shell command that blocks the connection to or from `remote`.
+/// Outbound connections are blocked by remote port; inbound ones by the
+/// local port they came in on, since the remote port is usually ephemeral.
+/// Returns `None` for protocols that cannot be expressed as an IP rule (ARP).
+pub fn block_command(
+    protocol: Protocol,
+    remote: SocketAddr,
+    local_port: u16,
+    inbound: bool,
+    backend: FirewallBackend,
+) -> Option<String> {
+    let ip = remote.ip();
+    let port = if inbound { local_port } else { remote.port() };
+    let has_port = matches!(protocol, Protocol::TCP | Protocol::UDP | Protocol::SCTP) && port != 0;
+    // Every backend takes other IP protocols by number
+    let number = match protocol {
+        Protocol::Other(number) => number.to_string(),
+        _ => String::new(),
+    };
+
+    let command = match backend {
+        FirewallBackend::Iptables => {
+            let tool = if ip.is_ipv6() {
+                "ip6tables"
+            } else {
+                "iptables"
+            };
+            let proto = match (protocol, ip) {
+                (Protocol::TCP, _) => "tcp",
+                (Protocol::UDP, _) => "udp",
+                (Protocol::SCTP, _) => "sctp",
+                (Protocol::ICMP, IpAddr::V4(_)) => "icmp",
+                (Protocol::ICMP, IpAddr::V6(_)) => "ipv6-icmp",
+                (Protocol::ARP, _) => return None,
+                (Protocol::Other(_), _) => number.as_str(),
+            };
+            let (chain, address) = if inbound {
+                ("INPUT", "-s")
+            } else {
+                ("OUTPUT", "-d")
+            };
+            if has_port {
+                format!(
+                    "{} -A {} {} {} -p {} --dport {} -j DROP",
+                    tool, chain, address, ip, proto, port
+                )
+            } else {
+                format!(
+                    "{} -A {} {} {} -p {} -j DROP",
+                    tool, chain, address, ip, proto
+                )
+            }
+        }
+        FirewallBackend::Pf => {
+            let proto = match (protocol, ip) {
+                (Protocol::TCP, _) => "tcp",
+                (Protocol::UDP, _) => "udp",
+                (Protocol::SCTP, _) => "sctp",
+                (Protocol::ICMP, IpAddr::V4(_)) => "icmp",
+                (Protocol::ICMP, IpAddr::V6(_)) => "icmp6",
+                (Protocol::ARP, _) => return None,
+                (Protocol::Other(_), _) => number.as_str(),
+            };
+            let rule = match (inbound, has_port) {
+                (false, true) => format!(
+                    "block drop out quick proto {} from any to {} port {}",
+                    proto, ip, port
+                ),
+                (false, false) => {
+                    format!("block drop out quick proto {} from any to {}", proto, ip)
+                }
+                (true, true) => format!(
+                    "block drop in quick proto {} from {} to any port {}",
+                    proto, ip, port
+                ),
+                (true, false) => format!("block drop in quick proto {} from {} to any", proto, ip),
+            };
+            // Loading an anchor replaces its rules, so reload the ones already
+            // there along with the new one
+            format!(
+                "(pfctl -a rustnet -s rules 2>/dev/null; echo \"{}\") | pfctl -a rustnet -f -",
+                rule
+            )
+        }
+        FirewallBackend::Netsh => {
+            let proto = match (protocol, ip) {
+                (Protocol::TCP, _) => "TCP",
+                (Protocol::UDP, _) => "UDP",
+                // Ports can only be given for TCP and UDP
+                (Protocol::SCTP, _) => "132",
+                (Protocol::ICMP, IpAddr::V4(_)) => "ICMPv4",
+                (Protocol::ICMP, IpAddr::V6(_)) => "ICMPv6",
+                (Protocol::ARP, _) => return None,
+                (Protocol::Other(_), _) => number.as_str(),
+            };
+            match (inbound, has_port && protocol != Protocol::SCTP) {
+                (false, true) => format!(
+                    "netsh advfirewall firewall add rule name=\"RustNet block {}\" dir=out action=block protocol={} remoteip={} remoteport={}",
+                    remote, proto, ip, port
+                ),
+                (true, true) => format!(
+                    "netsh advfirewall firewall add rule name=\"RustNet block {} to port {}\" dir=in action=block protocol={} remoteip={} localport={}",
+                    ip, port, proto, ip, port
+                ),
+                (inbound, false) => format!(
+                    "netsh advfirewall firewall add rule name=\"RustNet block {}\" dir={} action=block protocol={} remoteip={}",
+                    ip,
+                    if inbound { "in" } else { "out" },
+                    proto,
+                    ip
+                ),
+            }
+        }
+    };
+
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_iptables_rules() {
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5)), 443);
+        assert_eq!(
+            block_command(
+                Protocol::TCP,
+                target,
+                50000,
+                false,
+                FirewallBackend::Iptables
+            )
+            .unwrap(),
+            "iptables -A OUTPUT -d 203.0.113.5 -p tcp --dport 443 -j DROP"
+        );
+
+        let target = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 53);
+        assert_eq!(
+            block_command(
+                Protocol::UDP,
+                target,
+                50000,
+                false,
+                FirewallBackend::Iptables
+            )
+            .unwrap(),
+            "ip6tables -A OUTPUT -d ::1 -p udp --dport 53 -j DROP"
+        );
+    }
+
+    #[test]
+    fn test_pf_and_netsh_rules() {
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7)), 22);
+        assert_eq!(
+            block_command(Protocol::TCP, target, 50000, false, FirewallBackend::Pf).unwrap(),
+            "(pfctl -a rustnet -s rules 2>/dev/null; echo \"block drop out quick proto tcp from any to 198.51.100.7 port 22\") | pfctl -a rustnet -f -"
+        );
+        assert_eq!(
+            block_command(Protocol::TCP, target, 50000, false, FirewallBackend::Netsh).unwrap(),
+            "netsh advfirewall firewall add rule name=\"RustNet block 198.51.100.7:22\" dir=out action=block protocol=TCP remoteip=198.51.100.7 remoteport=22"
+        );
+    }
+
+    #[test]
+    fn test_pf_anchor_check() {
+        let enabled = "Status: Enabled for 0 days 01:02:03           Debug: Urgent\n";
+        let apple =
+            "scrub-anchor \"com.apple/*\" all fragment reassemble\nanchor \"com.apple/*\" all\n";
+
+        let problem = pf_anchor_problem("Status: Disabled           Debug: Urgent\n", apple);
+        assert!(problem.unwrap().contains("pfctl -e"));
+
+        // The stock macOS ruleset only evaluates Apple's anchors
+        let problem = pf_anchor_problem(enabled, apple).unwrap();
+        assert!(problem.contains("anchor \"rustnet\""), "{}", problem);
+
+        let ours = format!("{}anchor \"rustnet\" all\n", apple);
+        assert_eq!(pf_anchor_problem(enabled, &ours), None);
+        assert_eq!(pf_anchor_problem(enabled, "anchor \"*\" all\n"), None);
+        // Another anchor whose name starts the same way doesn't count
+        assert!(pf_anchor_problem(enabled, "anchor \"rustnet-old\" all\n").is_some());
+    }
+
+    #[test]
+    fn test_sctp_rules() {
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7)), 3868);
+        assert_eq!(
+            block_command(
+                Protocol::SCTP,
+                target,
+                50000,
+                false,
+                FirewallBackend::Iptables
+            )
+            .unwrap(),
+            "iptables -A OUTPUT -d 198.51.100.7 -p sctp --dport 3868 -j DROP"
+        );
+        assert_eq!(
+            block_command(Protocol::SCTP, target, 50000, false, FirewallBackend::Pf).unwrap(),
+            "(pfctl -a rustnet -s rules 2>/dev/null; echo \"block drop out quick proto sctp from any to 198.51.100.7 port 3868\") | pfctl -a rustnet -f -"
+        );
+        // netsh can't match SCTP ports, so the whole host is blocked
+        assert_eq!(
+            block_command(Protocol::SCTP, target, 50000, false, FirewallBackend::Netsh).unwrap(),
+            "netsh advfirewall firewall add rule name=\"RustNet block 198.51.100.7\" dir=out action=block protocol=132 remoteip=198.51.100.7"
+        );
+    }
+
+    #[test]
+    fn test_portless_protocols() {
+        let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 0);
+        assert_eq!(
+            block_command(Protocol::ICMP, target, 0, false, FirewallBackend::Iptables).unwrap(),
+            "iptables -A OUTPUT -d 8.8.8.8 -p icmp -j DROP"
+        );
+        assert!(block_command(Protocol::ARP, target, 0, false, FirewallBackend::Pf).is_none());
+        assert_eq!(
+            block_command(
+                Protocol::Other(47),
+                target,
+                0,
+                false,
+                FirewallBackend::Iptables
+            )
+            .unwrap(),
+            "iptables -A OUTPUT -d 8.8.8.8 -p 47 -j DROP"
+        );
+    }
+
+    #[test]
+    fn test_inbound_connections_are_blocked_on_the_way_in() {
+        // A client connecting to our SSH server from its ephemeral port
+        let client = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9)), 51234);
+        assert_eq!(
+            block_command(Protocol::TCP, client, 22, true, FirewallBackend::Iptables).unwrap(),
+            "iptables -A INPUT -s 203.0.113.9 -p tcp --dport 22 -j DROP"
+        );
+        assert_eq!(
+            block_command(Protocol::TCP, client, 22, true, FirewallBackend::Pf).unwrap(),
+            "(pfctl -a rustnet -s rules 2>/dev/null; echo \"block drop in quick proto tcp from 203.0.113.9 to any port 22\") | pfctl -a rustnet -f -"
+        );
+        assert_eq!(
+            block_command(Protocol::TCP, client, 22, true, FirewallBackend::Netsh).unwrap(),
+            "netsh advfirewall firewall add rule name=\"RustNet block 203.0.113.9 to port 22\" dir=in action=block protocol=TCP remoteip=203.0.113.9 localport=22"
+        );
+        assert_eq!(
+            block_command(Protocol::ICMP, client, 0, true, FirewallBackend::Netsh).unwrap(),
+            "netsh advfirewall firewall add rule name=\"RustNet block 203.0.113.9\" dir=in action=block protocol=ICMPv4 remoteip=203.0.113.9"
+        );
+    }
+
+    #[test]
+    fn test_rules_follow_the_connection_direction() {
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            "192.168.1.10:8080".parse().unwrap(),
+            "198.51.100.7:40000".parse().unwrap(),
+            crate::network::types::ProtocolState::Tcp(crate::network::types::TcpState::Established),
+        );
+        conn.inbound = true;
+        let rule = FirewallRule::for_connection(&conn, FirewallBackend::Iptables).unwrap();
+        assert_eq!(
+            rule.command,
+            "iptables -A INPUT -s 198.51.100.7 -p tcp --dport 8080 -j DROP"
+        );
+
+        conn.inbound = false;
+        let rule = FirewallRule::for_connection(&conn, FirewallBackend::Iptables).unwrap();
+        assert_eq!(
+            rule.command,
+            "iptables -A OUTPUT -d 198.51.100.7 -p tcp --dport 40000 -j DROP"
+        );
+    }
+}
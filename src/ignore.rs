@@ -0,0 +1,396 @@
+//! Connections to keep out of the way
+//!
+//! Backup jobs, monitoring agents and the like can drown out everything else.
+//! An ignore file lists them, one rule per line:
+//!
+//! ```text
+//! # Nightly backups, by name and by address
+//! domain:nas.local
+//! net:10.20.0.0/16
+//! port:5938
+//! # Still report scans and password guessing involving the EDR agent
+//! process:falcon-sensor alerts
+//! ```
+//!
+//! Matching connections are hidden from the connection list and left out of
+//! the totals. Scan and brute-force detection skips them too, unless the rule
+//! ends in `alerts`.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::filter::server_name;
+use crate::network::interfaces::LocalSubnet;
+use crate::network::parser::ParsedPacket;
+use crate::network::types::{ApplicationProtocol, Connection};
+
+/// Addresses remembered from DNS answers; the oldest is forgotten first
+const MAX_DNS_NAMES: usize = 10_000;
+
+/// What an ignore rule matches
+#[derive(Debug, Clone)]
+pub enum IgnoreMatch {
+    /// The connection's host name is this domain or one under it
+    Domain(String),
+    /// The remote address is in this network
+    Network(LocalSubnet),
+    /// Either end uses this port
+    Port(u16),
+    /// The owning process has exactly this name
+    Process(String),
+}
+
+/// One line of an ignore file
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    pub matcher: IgnoreMatch,
+    /// Keep raising scan and brute-force alerts for matching connections
+    pub alerts: bool,
+}
+
+impl IgnoreRule {
+    /// Parse `domain:`, `net:`, `port:` or `process:` followed by the value,
+    /// optionally followed by `alerts`
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut fields = line.split_whitespace();
+        let rule = fields.next().ok_or_else(|| anyhow!("empty rule"))?;
+        let alerts = match fields.next() {
+            None => false,
+            Some("alerts") => true,
+            Some(other) => return Err(anyhow!("unexpected '{}' after the rule", other)),
+        };
+        if let Some(extra) = fields.next() {
+            return Err(anyhow!("unexpected '{}' after the rule", extra));
+        }
+
+        let (kind, value) = rule
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected <kind>:<value>, got '{}'", rule))?;
+        let matcher = match kind.to_lowercase().as_str() {
+            "domain" => {
+                let domain = value.trim_start_matches("*.").trim_start_matches('.');
+                if domain.is_empty() {
+                    return Err(anyhow!("empty domain"));
+                }
+                IgnoreMatch::Domain(domain.to_lowercase())
+            }
+            "net" => {
+                let (network, prefix) = value.split_once('/').unwrap_or((value, ""));
+                let network: IpAddr = network
+                    .parse()
+                    .with_context(|| format!("invalid address '{}'", network))?;
+                let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+                let prefix = if prefix.is_empty() {
+                    max_prefix
+                } else {
+                    prefix
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|prefix| *prefix <= max_prefix)
+                        .ok_or_else(|| anyhow!("invalid prefix length '{}'", prefix))?
+                };
+                IgnoreMatch::Network(LocalSubnet { network, prefix })
+            }
+            "port" => IgnoreMatch::Port(
+                value
+                    .parse()
+                    .with_context(|| format!("invalid port '{}'", value))?,
+            ),
+            "process" if !value.is_empty() => IgnoreMatch::Process(value.to_string()),
+            "process" => return Err(anyhow!("empty process name")),
+            _ => return Err(anyhow!("unknown rule kind '{}'", kind)),
+        };
+
+        Ok(Self { matcher, alerts })
+    }
+
+    /// Whether the rule covers `conn`, known by `host` if it has a name
+    fn matches(&self, conn: &Connection, host: Option<&str>) -> bool {
+        match &self.matcher {
+            IgnoreMatch::Domain(domain) => host.is_some_and(|host| is_under(host, domain)),
+            IgnoreMatch::Network(network) => {
+                network.contains(conn.remote_addr.ip())
+                    || (conn.forwarded && network.contains(conn.local_addr.ip()))
+            }
+            IgnoreMatch::Port(port) => {
+                conn.local_addr.port() == *port || conn.remote_addr.port() == *port
+            }
+            IgnoreMatch::Process(name) => conn.process_name.as_deref() == Some(name.as_str()),
+        }
+    }
+
+    /// Whether the rule covers the flow `parsed` belongs to, as far as the
+    /// packet alone tells. Host names need the connection.
+    fn matches_packet(&self, parsed: &ParsedPacket) -> bool {
+        match &self.matcher {
+            IgnoreMatch::Domain(_) => false,
+            IgnoreMatch::Network(network) => {
+                network.contains(parsed.remote_addr.ip())
+                    || (parsed.forwarded && network.contains(parsed.local_addr.ip()))
+            }
+            IgnoreMatch::Port(port) => {
+                parsed.local_addr.port() == *port || parsed.remote_addr.port() == *port
+            }
+            IgnoreMatch::Process(name) => parsed.process_name.as_deref() == Some(name.as_str()),
+        }
+    }
+}
+
+/// `host` is `domain` or a name under it
+fn is_under(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Ordered ignore rules; the first match decides
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreList {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("invalid ignore rules in {}", path.display()))
+    }
+
+    /// Parse one rule per line. Blank lines and `#` comments are skipped.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            rules.push(IgnoreRule::parse(line).with_context(|| format!("line {}", number + 1))?);
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule covering `conn`. Its host name is the server name from
+    /// TLS, QUIC or HTTP, or failing that the name DNS resolved its remote
+    /// address from.
+    pub fn matching(&self, conn: &Connection, dns: &PassiveDns) -> Option<&IgnoreRule> {
+        let host = server_name(conn).or_else(|| dns.name(conn.remote_addr.ip()));
+        self.rules.iter().find(|rule| rule.matches(conn, host))
+    }
+
+    /// Hide or show `conn` and mute its alerts according to the rules
+    pub fn apply(&self, conn: &mut Connection, dns: &PassiveDns) {
+        let rule = self.matching(conn, dns);
+        conn.ignored = rule.is_some();
+        conn.alerts_muted = rule.is_some_and(|rule| !rule.alerts);
+    }
+
+    /// Whether scan detection should skip `parsed`. Rules on host names only
+    /// apply once the connection has been matched by `apply`.
+    pub fn mutes_alerts(&self, parsed: &ParsedPacket) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches_packet(parsed))
+            .is_some_and(|rule| !rule.alerts)
+    }
+}
+
+/// Host names learned from the DNS answers seen on the wire, by address
+#[derive(Debug, Default)]
+pub struct PassiveDns {
+    names: HashMap<IpAddr, (String, Instant)>,
+}
+
+impl PassiveDns {
+    /// Remember the addresses in `conn`'s DNS answer, if it carried one
+    pub fn learn(&mut self, conn: &Connection) {
+        let Some(ApplicationProtocol::Dns(info)) =
+            conn.dpi_info.as_ref().map(|dpi| &dpi.application)
+        else {
+            return;
+        };
+        let Some(name) = info.query_name.as_deref().filter(|_| info.is_response) else {
+            return;
+        };
+        for &ip in &info.response_ips {
+            if self.names.len() >= MAX_DNS_NAMES
+                && !self.names.contains_key(&ip)
+                && let Some(oldest) = self
+                    .names
+                    .iter()
+                    .min_by_key(|(_, (_, learned))| *learned)
+                    .map(|(ip, _)| *ip)
+            {
+                self.names.remove(&oldest);
+            }
+            self.names
+                .insert(ip, (name.to_lowercase(), conn.last_activity_instant));
+        }
+    }
+
+    pub fn name(&self, ip: IpAddr) -> Option<&str> {
+        self.names.get(&ip).map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::types::{
+        DnsInfo, DpiInfo, HttpsInfo, Protocol, ProtocolState, TcpState, TlsInfo, UdpState,
+    };
+
+    fn tcp(remote: &str) -> Connection {
+        Connection::new(
+            Protocol::TCP,
+            "192.168.1.10:50000".parse().unwrap(),
+            remote.parse().unwrap(),
+            ProtocolState::Tcp(TcpState::Established),
+        )
+    }
+
+    fn with_sni(mut conn: Connection, sni: &str) -> Connection {
+        let mut tls_info = TlsInfo::new();
+        tls_info.sni = Some(sni.to_string());
+        conn.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Https(HttpsInfo {
+                tls_info: Some(tls_info),
+                handshake: None,
+            }),
+            first_packet_time: Instant::now(),
+            last_update_time: Instant::now(),
+            pending_request_at: None,
+        });
+        conn
+    }
+
+    /// The answer to a DNS query for `name` resolving to `ips`
+    fn dns_answer(name: &str, ips: &[&str]) -> Connection {
+        let mut conn = Connection::new(
+            Protocol::UDP,
+            "192.168.1.10:40000".parse().unwrap(),
+            "192.168.1.1:53".parse().unwrap(),
+            ProtocolState::Udp(UdpState::Active),
+        );
+        conn.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Dns(DnsInfo {
+                query_name: Some(name.to_string()),
+                query_type: None,
+                response_ips: ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+                is_response: true,
+                rcode: None,
+                truncated: false,
+                ttl: None,
+            }),
+            first_packet_time: Instant::now(),
+            last_update_time: Instant::now(),
+            pending_request_at: None,
+        });
+        conn
+    }
+
+    fn ignored(list: &IgnoreList, conn: &Connection, dns: &PassiveDns) -> bool {
+        list.matching(conn, dns).is_some()
+    }
+
+    #[test]
+    fn test_domain_suffix_matching() {
+        let list = IgnoreList::parse("domain:*.corp.example").unwrap();
+        let dns = PassiveDns::default();
+
+        assert!(ignored(
+            &list,
+            &with_sni(tcp("203.0.113.1:443"), "corp.example"),
+            &dns
+        ));
+        assert!(ignored(
+            &list,
+            &with_sni(tcp("203.0.113.1:443"), "EDR.Corp.Example"),
+            &dns
+        ));
+        // A suffix only counts on a label boundary
+        assert!(!ignored(
+            &list,
+            &with_sni(tcp("203.0.113.1:443"), "notcorp.example"),
+            &dns
+        ));
+        assert!(!ignored(&list, &tcp("203.0.113.1:443"), &dns));
+    }
+
+    #[test]
+    fn test_server_name_takes_precedence_over_dns() {
+        let list = IgnoreList::parse("domain:nas.local").unwrap();
+        let mut dns = PassiveDns::default();
+        dns.learn(&dns_answer("nas.local", &["192.168.1.20", "203.0.113.1"]));
+
+        // Known only by the DNS answer it was resolved from
+        assert!(ignored(&list, &tcp("192.168.1.20:445"), &dns));
+        // The same address serving another site over TLS is not the NAS
+        let cdn = with_sni(tcp("203.0.113.1:443"), "www.example.com");
+        assert!(!ignored(&list, &cdn, &dns));
+    }
+
+    #[test]
+    fn test_network_port_and_process_rules() {
+        let list = IgnoreList::parse(
+            "# backups\n\
+             net:10.20.0.0/16\n\
+             \n\
+             port:5938 alerts\n\
+             process:falcon-sensor\n",
+        )
+        .unwrap();
+        assert_eq!(list.len(), 3);
+        let dns = PassiveDns::default();
+
+        let mut conn = tcp("10.20.3.4:873");
+        list.apply(&mut conn, &dns);
+        assert!(conn.ignored && conn.alerts_muted);
+
+        // Alerts still apply to connections hidden by an `alerts` rule
+        let mut conn = tcp("198.51.100.7:5938");
+        list.apply(&mut conn, &dns);
+        assert!(conn.ignored && !conn.alerts_muted);
+
+        let mut conn = tcp("198.51.100.7:443");
+        conn.process_name = Some("falcon-sensor".to_string());
+        list.apply(&mut conn, &dns);
+        assert!(conn.ignored);
+
+        conn.process_name = Some("falcon-sensor-helper".to_string());
+        list.apply(&mut conn, &dns);
+        assert!(!conn.ignored && !conn.alerts_muted);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        for line in [
+            "nas.local",
+            "domain:",
+            "net:10.0.0.0/33",
+            "net:nas.local",
+            "port:http",
+            "colour:red",
+            "port:22 always",
+        ] {
+            assert!(
+                IgnoreList::parse(line).is_err(),
+                "{} should be rejected",
+                line
+            );
+        }
+    }
+}
@@ -5,5 +5,9 @@
 pub mod app;
 pub mod config;
 pub mod filter;
+pub mod firewall;
+pub mod ignore;
+pub mod logging;
 pub mod network;
+pub mod signatures;
 pub mod ui;
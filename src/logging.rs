@@ -0,0 +1,267 @@
+// logging.rs - Log file setup with per-target filtering and a non-blocking writer
+use anyhow::{Result, anyhow};
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use log::{LevelFilter, Log, Metadata, Record};
+use simplelog::{Config as LogConfig, WriteLogger};
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Lines queued for the writer thread before new ones are dropped
+const LOG_QUEUE_CAPACITY: usize = 8192;
+
+/// How long `flush` waits for the writer thread to catch up
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Log levels per target, in `RUST_LOG` syntax: `info,rustnet::merge=trace`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogDirectives {
+    default: LevelFilter,
+    /// (target prefix, level) pairs
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogDirectives {
+    /// Apply every target at `default`
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Parse `spec` on top of `default`. A bare level replaces the default,
+    /// `target=level` sets the level for a target and everything below it.
+    pub fn parse(spec: &str, default: LevelFilter) -> Result<Self> {
+        let mut directives = Self::new(default);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |level: &str| {
+                level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| anyhow!("Invalid log level in '{}': {}", directive, level))
+            };
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let level = parse_level(level.trim())?;
+                    directives.targets.push((target.trim().to_string(), level));
+                }
+                None => directives.default = parse_level(directive)?,
+            }
+        }
+        Ok(directives)
+    }
+
+    /// Level for `target`, taken from the longest matching target prefix
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level any target is enabled at
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// Wraps a logger and drops records below their target's level
+pub struct TargetFilter<L> {
+    directives: LogDirectives,
+    inner: L,
+}
+
+impl<L: Log> TargetFilter<L> {
+    pub fn new(directives: LogDirectives, inner: L) -> Self {
+        Self { directives, inner }
+    }
+}
+
+impl<L: Log> Log for TargetFilter<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.directives.level_for(metadata.target())
+            && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+enum LogMessage {
+    Line(Vec<u8>),
+    Flush(Sender<()>),
+}
+
+/// Log writer that hands complete lines to a background thread, so a slow
+/// disk never stalls the packet path. Lines are dropped while the queue is full.
+pub struct NonBlockingWriter {
+    line: Vec<u8>,
+    queue: Sender<LogMessage>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl NonBlockingWriter {
+    /// Spawn the writer thread, which buffers output to `writer`
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        let (queue, messages) = channel::bounded(LOG_QUEUE_CAPACITY);
+        thread::spawn(move || write_messages(messages, writer));
+        Self {
+            line: Vec::new(),
+            queue,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Number of lines discarded because the writer thread fell behind
+    pub fn dropped_lines(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped)
+    }
+
+    fn send_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if let Err(TrySendError::Full(_)) = self.queue.try_send(LogMessage::Line(line)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Write for NonBlockingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        if buf.ends_with(b"\n") {
+            self.send_line();
+        }
+        Ok(buf.len())
+    }
+
+    /// Wait (briefly) until everything written so far has reached the file
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.send_line();
+        }
+        let (done_tx, done_rx) = channel::bounded(1);
+        if self
+            .queue
+            .send_timeout(LogMessage::Flush(done_tx), FLUSH_TIMEOUT)
+            .is_ok()
+        {
+            let _ = done_rx.recv_timeout(FLUSH_TIMEOUT);
+        }
+        Ok(())
+    }
+}
+
+/// Writer thread: batch lines into a `BufWriter`, flushing whenever the queue drains
+fn write_messages<W: Write>(messages: Receiver<LogMessage>, writer: W) {
+    let mut writer = BufWriter::new(writer);
+    for message in messages.iter() {
+        match message {
+            LogMessage::Line(line) => {
+                let _ = writer.write_all(&line);
+                if messages.is_empty() {
+                    let _ = writer.flush();
+                }
+            }
+            LogMessage::Flush(done) => {
+                let _ = writer.flush();
+                let _ = done.send(());
+            }
+        }
+    }
+    let _ = writer.flush();
+}
+
+/// Install the global logger, writing through a `NonBlockingWriter`.
+/// Returns the writer's dropped-line counter.
+pub fn init<W: Write + Send + 'static>(
+    directives: LogDirectives,
+    writer: W,
+) -> Result<Arc<AtomicU64>> {
+    let max_level = directives.max_level();
+    let writer = NonBlockingWriter::new(writer);
+    let dropped = writer.dropped_lines();
+    let inner = WriteLogger::new(max_level, LogConfig::default(), writer);
+    log::set_boxed_logger(Box::new(TargetFilter::new(directives, inner)))?;
+    log::set_max_level(max_level);
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_directives_pick_longest_target_prefix() {
+        let directives = LogDirectives::parse(
+            "warn, rustnet::dpi=debug,rustnet::dpi::quic=trace",
+            LevelFilter::Off,
+        )
+        .unwrap();
+
+        assert_eq!(directives.level_for("rustnet::app"), LevelFilter::Warn);
+        assert_eq!(directives.level_for("rustnet::dpi"), LevelFilter::Debug);
+        assert_eq!(
+            directives.level_for("rustnet::dpi::quic"),
+            LevelFilter::Trace
+        );
+        // Prefixes only match whole path segments
+        assert_eq!(directives.level_for("rustnet::dpix"), LevelFilter::Warn);
+        assert_eq!(directives.max_level(), LevelFilter::Trace);
+
+        assert!(LogDirectives::parse("rustnet::merge=loud", LevelFilter::Info).is_err());
+        assert_eq!(
+            LogDirectives::parse("", LevelFilter::Info).unwrap(),
+            LogDirectives::new(LevelFilter::Info)
+        );
+    }
+
+    #[test]
+    fn test_non_blocking_writer_delivers_whole_lines() {
+        let buffer = SharedBuffer::default();
+        let mut writer = NonBlockingWriter::new(buffer.clone());
+
+        write!(writer, "first ").unwrap();
+        writeln!(writer, "line").unwrap();
+        writeln!(writer, "second line").unwrap();
+        write!(writer, "partial").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "first line\nsecond line\npartial"
+        );
+        assert_eq!(writer.dropped_lines().load(Ordering::Relaxed), 0);
+    }
+}
@@ -1,17 +1,26 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use arboard::Clipboard;
-use log::{LevelFilter, debug, error, info};
+use clap::parser::ValueSource;
+use log::{LevelFilter, debug, error, info, warn};
 use ratatui::prelude::CrosstermBackend;
-use simplelog::{Config as LogConfig, WriteLogger};
 use std::fs::{self, File};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+use config::Settings;
+
 mod app;
 mod cli;
+mod config;
 mod filter;
+mod firewall;
+mod ignore;
+mod logging;
 mod network;
+mod signatures;
 mod ui;
 
 fn main() -> Result<()> {
@@ -21,24 +30,65 @@ fn main() -> Result<()> {
 
     // Parse command line arguments
     let matches = cli::build_cli().get_matches();
-    // Set up logging only if log-level was provided
-    if let Some(log_level_str) = matches.get_one::<String>("log-level") {
-        let log_level = log_level_str
-            .parse::<LevelFilter>()
-            .map_err(|_| anyhow::anyhow!("Invalid log level: {}", log_level_str))?;
-        setup_logging(log_level)?;
+    if let Some(matches) = matches.subcommand_matches("interfaces") {
+        return list_interfaces(matches.get_flag("json"), matches.get_flag("watch"));
     }
 
+    // Set up logging only if a log level, log file or RUST_LOG was provided
+    let log_level = matches
+        .get_one::<String>("log-level")
+        .map(|level| {
+            level
+                .parse::<LevelFilter>()
+                .map_err(|_| anyhow::anyhow!("Invalid log level: {}", level))
+        })
+        .transpose()?;
+    let log_file = matches.get_one::<String>("log-file").map(PathBuf::from);
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let dropped_log_lines = if log_level.is_some() || log_file.is_some() || rust_log.is_some() {
+        // RUST_LOG alone only enables the targets it names
+        let default_level = log_level.unwrap_or(if log_file.is_some() {
+            LevelFilter::Info
+        } else {
+            LevelFilter::Off
+        });
+        let directives =
+            logging::LogDirectives::parse(rust_log.as_deref().unwrap_or(""), default_level)?;
+        Some(setup_logging(directives, log_file)?)
+    } else {
+        None
+    };
+
     info!("Starting RustNet Monitor");
 
-    // Build configuration from command line arguments
+    // Settings from the file, if there is one, overridden by the command line
+    let explicit_config = matches.get_one::<PathBuf>("config").cloned();
+    let config_path = explicit_config.clone().or_else(Settings::default_path);
     let mut config = app::Config::default();
+    if let Some(path) = &config_path
+        && (explicit_config.is_some() || path.exists())
+    {
+        info!("Loading settings from {}", path.display());
+        Settings::from_file(path)?
+            .apply(&mut config)
+            .with_context(|| format!("invalid settings in {}", path.display()))?;
+    }
 
     if let Some(interface) = matches.get_one::<String>("interface") {
         config.interface = Some(interface.to_string());
         info!("Using interface: {}", interface);
     }
 
+    if matches.get_flag("no-promisc") {
+        config.promiscuous = false;
+        info!("Promiscuous mode disabled");
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("read-file") {
+        config.read_file = Some(path.clone());
+        info!("Reading packets from {}", path.display());
+    }
+
     if matches.get_flag("no-localhost") {
         config.filter_localhost = true;
         info!("Filtering localhost connections");
@@ -49,32 +99,173 @@ fn main() -> Result<()> {
         info!("Showing localhost connections");
     }
 
-    if let Some(interval) = matches.get_one::<u64>("refresh-interval") {
+    // The option has a default, which mustn't override the settings file
+    if matches.value_source("refresh-interval") == Some(ValueSource::CommandLine)
+        && let Some(interval) = matches.get_one::<u64>("refresh-interval")
+    {
         config.refresh_interval = *interval;
         info!("Using refresh interval: {}ms", interval);
     }
 
+    if let Some(max_connections) = matches.get_one::<usize>("max-connections") {
+        config.max_connections = *max_connections;
+        info!("Tracking at most {} connections", max_connections);
+    }
+
+    if let Some(max_idle) = matches.get_one::<u64>("max-idle") {
+        config.max_idle = Some(Duration::from_secs(*max_idle));
+        info!("Expiring connections idle for {}s", max_idle);
+    }
+
+    if let Some(filter) = matches.get_one::<String>("bpf-filter") {
+        config.bpf_filter = Some(filter.clone());
+        info!("Using BPF filter: {}", filter);
+    }
+
+    // Fail now rather than showing an empty view if the file can't be read
+    if let Some(path) = &config.read_file {
+        network::capture::open_savefile(path, config.bpf_filter.as_deref())?;
+    }
+
     if matches.get_flag("no-dpi") {
         config.enable_dpi = false;
         info!("Deep packet inspection disabled");
     }
 
-    // Set up terminal
-    let backend = CrosstermBackend::new(io::stdout());
-    let mut terminal = ui::setup_terminal(backend)?;
-    info!("Terminal UI initialized");
+    if matches.get_flag("count-link-layer") {
+        config.count_link_layer = true;
+        info!("Counting link-layer headers in byte totals");
+    }
+
+    config.router_mode = match matches.get_one::<String>("router-mode").map(String::as_str) {
+        Some("on") => network::parser::RouterMode::On,
+        Some("off") => network::parser::RouterMode::Off,
+        _ => network::parser::RouterMode::Auto,
+    };
+    info!("Router mode: {:?}", config.router_mode);
+
+    config.offload_counting = match matches
+        .get_one::<String>("count-offloaded")
+        .map(String::as_str)
+    {
+        Some("segments") => network::parser::OffloadCounting::Segments,
+        _ => network::parser::OffloadCounting::Frames,
+    };
+    info!("Offloaded TCP packets: {:?}", config.offload_counting);
+
+    if matches.get_flag("decapsulate-tunnels") {
+        config.decapsulate_tunnels = true;
+        info!("Decapsulating GRE and VXLAN tunnels");
+    }
+
+    if matches.get_flag("show-hostnames") {
+        config.show_hostnames = true;
+        info!("Resolving remote hostnames");
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("geoip-db") {
+        config.geoip_db = Some(path.clone());
+        info!("Using GeoIP database {}", path.display());
+    }
+
+    if matches.get_flag("no-coalesce") {
+        config.coalesce_ports.clear();
+        info!("UDP flow coalescing disabled");
+    } else if let Some(ports) = matches.get_many::<u16>("coalesce-ports") {
+        config.coalesce_ports = ports.copied().collect();
+    }
+    info!("Coalescing UDP flows to ports {:?}", config.coalesce_ports);
+
+    config.os_signatures = matches.get_one::<PathBuf>("os-signatures").cloned();
+    config.ignore_file = matches.get_one::<PathBuf>("ignore-file").cloned();
 
-    // Create and start the application
+    if let Some(dir) = matches.get_one::<PathBuf>("record-dir") {
+        config.record_dir = dir.clone();
+        info!("Saving recorded connections to {}", dir.display());
+    }
+
+    if matches.get_flag("no-keepalive-detection") {
+        config.keepalive_detection = false;
+        info!("Keepalive detection disabled");
+    }
+
+    if let Some(codes) = matches.get_many::<u16>("weak-ciphers") {
+        let codes: Vec<u16> = codes.copied().collect();
+        info!("Also flagging cipher suites {:04x?} as weak", codes);
+        config.weak_cipher_suites = codes;
+    }
+
+    if let Some(ports) = matches.get_one::<usize>("scan-ports") {
+        config.scan_thresholds.ports = *ports;
+    }
+    if let Some(hosts) = matches.get_one::<usize>("scan-hosts") {
+        config.scan_thresholds.hosts = *hosts;
+    }
+    if let Some(rate) = matches.get_one::<u64>("syn-flood-rate") {
+        config.scan_thresholds.syn_rate = *rate as f64;
+    }
+    if let Some(window) = matches.get_one::<u64>("scan-window") {
+        config.scan_thresholds.window = Duration::from_secs(*window);
+    }
+    if let Some(attempts) = matches.get_one::<usize>("login-attempts") {
+        config.scan_thresholds.login_attempts = *attempts;
+    }
+    if let Some(ports) = matches.get_many::<u16>("auth-ports") {
+        config.scan_thresholds.auth_ports = ports.copied().collect();
+    }
+    info!("Scan thresholds: {:?}", config.scan_thresholds);
+
+    if matches.get_flag("save-config") {
+        let path = config_path.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("No home directory to save settings in, use --config")
+        })?;
+        Settings::from(&config).save(path)?;
+        info!("Saved settings to {}", path.display());
+    }
+
+    config.duration = matches
+        .get_one::<u64>("duration")
+        .map(|seconds| Duration::from_secs(*seconds));
+    config.stop_after_connections = matches.get_one::<u64>("count").copied();
+    let headless = matches.get_flag("headless");
+    let summary_file = matches.get_one::<PathBuf>("summary-file").cloned();
+    let fail_on_alert = matches.get_flag("fail-on-alert");
+    if headless && config.duration.is_none() && config.stop_after_connections.is_none() {
+        return Err(anyhow::anyhow!(
+            "--headless needs --duration or --count to know when to stop"
+        ));
+    }
+    // Scripted runs report what they saw when they end
+    let print_summary = headless
+        || summary_file.is_some()
+        || config.duration.is_some()
+        || config.stop_after_connections.is_some();
+
+    // Create and start the application (start blocks briefly to warm up the
+    // connection table, so do it before taking over the terminal)
     let mut app = app::App::new(config)?;
     app.start()?;
     info!("Application started");
 
-    // Run the UI loop
-    let res = run_ui_loop(&mut terminal, &app);
-
-    // Cleanup
-    app.stop();
-    ui::restore_terminal(&mut terminal)?;
+    let res = if headless {
+        info!("Running without the terminal UI");
+        let res = run_headless(&app);
+        app.stop();
+        res
+    } else {
+        // Set up terminal
+        let backend = CrosstermBackend::new(io::stdout());
+        let mut terminal = ui::setup_terminal(backend)?;
+        info!("Terminal UI initialized");
+
+        // Run the UI loop
+        let res = run_ui_loop(&mut terminal, &app);
+
+        // Cleanup
+        app.stop();
+        ui::restore_terminal(&mut terminal)?;
+        res
+    };
 
     // Return any error that occurred
     if let Err(err) = res {
@@ -82,97 +273,126 @@ fn main() -> Result<()> {
         println!("Error: {}", err);
     }
 
+    // Background threads have stopped, so the totals are final
+    let summary = app.run_summary();
+    if print_summary {
+        match &summary_file {
+            Some(path) => fs::write(path, summary.render())?,
+            None => print!("{}", summary.render()),
+        }
+    }
+
     info!("RustNet Monitor shutting down");
+    if let Some(dropped) = dropped_log_lines {
+        let dropped = dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                "{} log lines were dropped because the log writer fell behind",
+                dropped
+            );
+        }
+        log::logger().flush();
+    }
+    if fail_on_alert && summary.alerts_raised > 0 {
+        std::process::exit(1);
+    }
     Ok(())
 }
 
-fn setup_logging(level: LevelFilter) -> Result<()> {
-    // Create logs directory if it doesn't exist
-    let log_dir = Path::new("logs");
-    if !log_dir.exists() {
-        fs::create_dir_all(log_dir)?;
+/// Capture without the UI until `--duration` or `--count` ends the run
+fn run_headless(app: &app::App) -> Result<()> {
+    while !app.run_finished() {
+        std::thread::sleep(Duration::from_millis(100));
     }
-
-    // Create timestamped log file name
-    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
-    let log_file_path = log_dir.join(format!("rustnet_{}.log", timestamp));
-
-    // Initialize the logger
-    WriteLogger::init(level, LogConfig::default(), File::create(log_file_path)?)?;
-
+    info!("Run finished");
     Ok(())
 }
 
-/// Sort connections based on the specified column and direction
-fn sort_connections(
-    connections: &mut [network::types::Connection],
-    sort_column: ui::SortColumn,
-    ascending: bool,
-) {
-    use ui::SortColumn;
-
-    connections.sort_by(|a, b| {
-        let ordering = match sort_column {
-            SortColumn::CreatedAt => a.created_at.cmp(&b.created_at),
-
-            SortColumn::BandwidthDown => {
-                // Compare as f64, handle NaN cases
-                a.current_incoming_rate_bps
-                    .partial_cmp(&b.current_incoming_rate_bps)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            }
+/// `rustnet interfaces`: print the interface list, or keep refreshing it
+fn list_interfaces(json: bool, watch: bool) -> Result<()> {
+    use network::interfaces;
+    use std::io::Write;
+    use std::time::Instant;
 
-            SortColumn::BandwidthUp => {
-                a.current_outgoing_rate_bps
-                    .partial_cmp(&b.current_outgoing_rate_bps)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            }
-
-            SortColumn::Process => {
-                let a_process = a.process_name.as_deref().unwrap_or("");
-                let b_process = b.process_name.as_deref().unwrap_or("");
-                a_process.cmp(b_process)
-            }
-
-            SortColumn::LocalAddress => {
-                a.local_addr.to_string().cmp(&b.local_addr.to_string())
-            }
+    let mut previous = interfaces::list_interfaces()?;
+    if !watch {
+        if json {
+            println!("{}", interfaces::render_json(&previous, None));
+        } else {
+            print!("{}", interfaces::render_table(&previous, None));
+        }
+        return Ok(());
+    }
 
-            SortColumn::RemoteAddress => {
-                a.remote_addr.to_string().cmp(&b.remote_addr.to_string())
-            }
+    let mut last = Instant::now();
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let current = interfaces::list_interfaces()?;
+        let rates = interfaces::rates(&previous, &current, last.elapsed());
+        last = Instant::now();
+
+        let mut stdout = io::stdout().lock();
+        if json {
+            writeln!(
+                stdout,
+                "{}",
+                interfaces::render_json(&current, Some(&rates))
+            )?;
+        } else {
+            // Clear the screen and redraw from the top left
+            write!(
+                stdout,
+                "\x1b[2J\x1b[H{}",
+                interfaces::render_table(&current, Some(&rates))
+            )?;
+        }
+        stdout.flush()?;
+        previous = current;
+    }
+}
 
-            SortColumn::Application => {
-                let a_app = a.dpi_info.as_ref()
-                    .map(|dpi| dpi.application.to_string())
-                    .unwrap_or_default();
-                let b_app = b.dpi_info.as_ref()
-                    .map(|dpi| dpi.application.to_string())
-                    .unwrap_or_default();
-                a_app.cmp(&b_app)
+/// Install the file logger, returning its dropped-line counter
+fn setup_logging(
+    directives: logging::LogDirectives,
+    log_file: Option<PathBuf>,
+) -> Result<Arc<AtomicU64>> {
+    let log_file_path = match log_file {
+        Some(path) => path,
+        None => {
+            // Create logs directory if it doesn't exist
+            let log_dir = Path::new("logs");
+            if !log_dir.exists() {
+                fs::create_dir_all(log_dir)?;
             }
 
-            SortColumn::Service => {
-                let a_service = a.service_name.as_deref().unwrap_or("");
-                let b_service = b.service_name.as_deref().unwrap_or("");
-                a_service.cmp(b_service)
-            }
+            // Create timestamped log file name
+            let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+            log_dir.join(format!("rustnet_{}.log", timestamp))
+        }
+    };
 
-            SortColumn::State => {
-                a.state().cmp(&b.state())
-            }
+    // Initialize the logger
+    logging::init(directives, File::create(log_file_path)?)
+}
 
-            SortColumn::Protocol => {
-                a.protocol.to_string().cmp(&b.protocol.to_string())
+/// Copy text to the system clipboard and report the outcome in the status bar
+fn copy_to_clipboard(ui_state: &mut ui::UIState, text: &str) {
+    let message = match Clipboard::new() {
+        Ok(mut clipboard) => {
+            if let Err(e) = clipboard.set_text(text) {
+                error!("Failed to copy to clipboard: {}", e);
+                format!("Failed to copy: {}", e)
+            } else {
+                info!("Copied {} to clipboard", text);
+                format!("Copied {} to clipboard", text)
             }
-        };
-
-        if ascending {
-            ordering
-        } else {
-            ordering.reverse()
         }
-    });
+        Err(e) => {
+            error!("Failed to access clipboard: {}", e);
+            format!("Clipboard error: {}", e)
+        }
+    };
+    ui_state.clipboard_message = Some((message, std::time::Instant::now()));
 }
 
 fn run_ui_loop<B: ratatui::prelude::Backend>(
@@ -182,29 +402,30 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
     let tick_rate = Duration::from_millis(200);
     let mut last_tick = std::time::Instant::now();
     let mut ui_state = ui::UIState::default();
+    let mut view = ui::ConnectionView::default();
 
     loop {
-        // Get current connections and stats
+        if app.run_finished() {
+            info!("Run finished");
+            break;
+        }
+
+        // Get current connections snapshot
         // IMPORTANT: Fetch connections ONCE per iteration to ensure consistency
         // between display, navigation, and selection operations
-        let mut connections = if ui_state.filter_query.is_empty() && !ui_state.filter_mode {
-            app.get_connections()
-        } else {
-            app.get_filtered_connections(&ui_state.filter_query)
-        };
+        // The view only refilters/resorts when the snapshot, filter or sort changed
+        view.apply_snapshot(&app.get_snapshot(), &ui_state);
+        let snapshot = view.snapshot();
 
-        // Apply sorting (after filtering)
         // This sorted list MUST be used for all operations (display + navigation)
-        sort_connections(&mut connections, ui_state.sort_column, ui_state.sort_ascending);
-
-        let stats = app.get_stats();
+        let connections = view.connections();
 
         // Ensure we have a valid selection (handles connection removals)
         ui_state.ensure_valid_selection(&connections);
 
         // Draw the UI
         terminal.draw(|f| {
-            if let Err(err) = ui::draw(f, app, &ui_state, &connections, &stats) {
+            if let Err(err) = ui::draw(f, app, &ui_state, &connections, snapshot) {
                 error!("UI draw error: {}", err);
             }
         })?;
@@ -226,6 +447,12 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
             ui_state.clipboard_message = None;
         }
 
+        // Show what the background threads reported, e.g. interfaces coming and going
+        let notices = app.take_notices();
+        if !notices.is_empty() {
+            ui_state.clipboard_message = Some((notices.join(" | "), std::time::Instant::now()));
+        }
+
         // Handle input events
         if crossterm::event::poll(timeout)?
             && let crossterm::event::Event::Key(key) = crossterm::event::read()?
@@ -239,7 +466,36 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                 continue;
             }
 
-            if ui_state.filter_mode {
+            if let Some(rule_index) = ui_state.firewall_popup {
+                // Handle input in the firewall rule popup
+                if ui_state.firewall_confirm {
+                    ui_state.firewall_confirm = false;
+                    if key.code == KeyCode::Char('y') {
+                        let message = match app.apply_firewall_rule(rule_index) {
+                            Ok(()) => "Firewall rule applied".to_string(),
+                            Err(e) => {
+                                error!("Failed to apply firewall rule: {}", e);
+                                format!("Failed to apply firewall rule: {}", e)
+                            }
+                        };
+                        ui_state.clipboard_message = Some((message, std::time::Instant::now()));
+                    }
+                } else {
+                    match key.code {
+                        KeyCode::Char('c') => {
+                            if let Some(rule) = app.get_generated_rules().get(rule_index) {
+                                copy_to_clipboard(&mut ui_state, &rule.command);
+                            }
+                        }
+                        KeyCode::Char('x') => {
+                            ui_state.firewall_confirm = true;
+                        }
+                        _ => {
+                            ui_state.firewall_popup = None;
+                        }
+                    }
+                }
+            } else if ui_state.filter_mode {
                 // Handle input in filter mode
                 match key.code {
                     KeyCode::Enter => {
@@ -255,13 +511,13 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                     KeyCode::Backspace => {
                         ui_state.filter_backspace();
                     }
-                    KeyCode::Delete => {
+                    KeyCode::Delete
+                        if ui_state.filter_cursor_position < ui_state.filter_query.len() =>
+                    {
                         // Handle delete key (remove character after cursor)
-                        if ui_state.filter_cursor_position < ui_state.filter_query.len() {
-                            ui_state
-                                .filter_query
-                                .remove(ui_state.filter_cursor_position);
-                        }
+                        ui_state
+                            .filter_query
+                            .remove(ui_state.filter_cursor_position);
                     }
                     KeyCode::Left => {
                         ui_state.filter_cursor_left();
@@ -360,7 +616,7 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                     // Tab navigation
                     (KeyCode::Tab, _) => {
                         ui_state.quit_confirmation = false;
-                        ui_state.selected_tab = (ui_state.selected_tab + 1) % 3;
+                        ui_state.selected_tab = (ui_state.selected_tab + 1) % 9;
                     }
 
                     // Help toggle
@@ -374,6 +630,29 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                         }
                     }
 
+                    // Navigation in the Top tab's host list
+                    (KeyCode::Up, _) | (KeyCode::Char('k'), _) if ui_state.selected_tab == 6 => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.selected_talker = ui_state.selected_talker.saturating_sub(1);
+                    }
+
+                    (KeyCode::Down, _) | (KeyCode::Char('j'), _) if ui_state.selected_tab == 6 => {
+                        ui_state.quit_confirmation = false;
+                        let rows = if ui_state.show_processes {
+                            app.process_bandwidth().len().min(ui::TOP_TALKERS)
+                        } else {
+                            network::talkers::top_talkers(
+                                &snapshot.connections,
+                                ui::TOP_TALKERS,
+                                ui_state.talker_order,
+                            )
+                            .talkers
+                            .len()
+                        };
+                        ui_state.selected_talker =
+                            (ui_state.selected_talker + 1).min(rows.saturating_sub(1));
+                    }
+
                     // Navigation in connection list
                     (KeyCode::Up, _) | (KeyCode::Char('k'), _) => {
                         ui_state.quit_confirmation = false;
@@ -425,14 +704,81 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                         ui_state.move_selection_to_last(&connections);
                     }
 
+                    // Enter on the Top tab filters the connection list to that host or process
+                    (KeyCode::Enter, _) if ui_state.selected_tab == 6 => {
+                        ui_state.quit_confirmation = false;
+                        let query = if ui_state.show_processes {
+                            app.process_bandwidth()
+                                .get(ui_state.selected_talker)
+                                // A filter term can't hold whitespace
+                                .filter(|(process, _, _)| {
+                                    process != network::talkers::UNKNOWN_PROCESS
+                                        && !process.contains(char::is_whitespace)
+                                })
+                                .map(|(process, _, _)| format!("process:{}", process))
+                        } else {
+                            network::talkers::top_talkers(
+                                &snapshot.connections,
+                                ui::TOP_TALKERS,
+                                ui_state.talker_order,
+                            )
+                            .talkers
+                            .get(ui_state.selected_talker)
+                            .map(|talker| talker.filter_query())
+                        };
+                        if let Some(query) = query {
+                            ui_state.set_filter(query);
+                            ui_state.selected_tab = 0;
+                        }
+                    }
+
                     // Enter to view details
                     (KeyCode::Enter, _) => {
                         ui_state.quit_confirmation = false;
                         if ui_state.selected_tab == 0 && !connections.is_empty() {
                             ui_state.selected_tab = 1; // Switch to details view
+
+                            // Resolve the process now rather than on the next enrichment pass
+                            if let Some(conn) = ui_state
+                                .get_selected_index(&connections)
+                                .map(|index| connections[index])
+                                && conn.process_name.is_none()
+                            {
+                                app.request_process_lookup(conn.key());
+                            }
+                        }
+                    }
+
+                    // Expand or collapse a row of coalesced UDP flows
+                    (KeyCode::Char(' '), _) => {
+                        ui_state.quit_confirmation = false;
+                        if ui_state.selected_tab == 0 {
+                            ui_state.toggle_expanded(&connections);
                         }
                     }
 
+                    // Reveal or hide the connections matched by ignore rules
+                    (KeyCode::Char('i'), _) => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.show_ignored = !ui_state.show_ignored;
+                    }
+
+                    // Per-interface counters
+                    (KeyCode::Char('I'), _) => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.selected_tab = 8;
+                    }
+
+                    // Refresh process information now instead of waiting for the next scan
+                    (KeyCode::Char('r'), KeyModifiers::NONE) => {
+                        ui_state.quit_confirmation = false;
+                        app.request_process_refresh();
+                        ui_state.clipboard_message = Some((
+                            "Refreshing process information".to_string(),
+                            std::time::Instant::now(),
+                        ));
+                    }
+
                     // Toggle port number display
                     (KeyCode::Char('p'), _) => {
                         ui_state.quit_confirmation = false;
@@ -447,6 +793,26 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                         );
                     }
 
+                    // Rank the Top tab by rate or by bytes
+                    (KeyCode::Char('s'), KeyModifiers::NONE) if ui_state.selected_tab == 6 => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.talker_order = ui_state.talker_order.toggle();
+                        ui_state.selected_talker = 0;
+                    }
+
+                    // Rank hosts or processes on the Top tab
+                    (KeyCode::Char('b'), _) if ui_state.selected_tab == 6 => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.show_processes = !ui_state.show_processes;
+                        ui_state.selected_talker = 0;
+                    }
+
+                    // Per-connection bandwidth history
+                    (KeyCode::Char('b'), _) => {
+                        ui_state.quit_confirmation = false;
+                        ui_state.selected_tab = 7;
+                    }
+
                     // Cycle sort column with 's'
                     (KeyCode::Char('s'), KeyModifiers::NONE) => {
                         ui_state.quit_confirmation = false;
@@ -475,27 +841,43 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                         if let Some(selected_idx) = ui_state.get_selected_index(&connections)
                             && let Some(conn) = connections.get(selected_idx)
                         {
-                            let remote_addr = conn.remote_addr.to_string();
-                            match Clipboard::new() {
-                                Ok(mut clipboard) => {
-                                    if let Err(e) = clipboard.set_text(&remote_addr) {
-                                        error!("Failed to copy to clipboard: {}", e);
-                                        ui_state.clipboard_message = Some((
-                                            format!("Failed to copy: {}", e),
-                                            std::time::Instant::now(),
-                                        ));
-                                    } else {
-                                        info!("Copied {} to clipboard", remote_addr);
-                                        ui_state.clipboard_message = Some((
-                                            format!("Copied {} to clipboard", remote_addr),
-                                            std::time::Instant::now(),
-                                        ));
-                                    }
+                            copy_to_clipboard(&mut ui_state, &conn.remote_addr.to_string());
+                        }
+                    }
+
+                    // Export the connection table to a CSV file in the working directory
+                    (KeyCode::Char('e'), _) => {
+                        ui_state.quit_confirmation = false;
+                        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+                        let path = PathBuf::from(format!("rustnet_connections_{}.csv", timestamp));
+                        let message = match app.export_connections_to_csv(&path) {
+                            Ok(()) => format!("Exported connections to {}", path.display()),
+                            Err(e) => {
+                                error!("Failed to export connections: {}", e);
+                                format!("Failed to export connections: {}", e)
+                            }
+                        };
+                        ui_state.clipboard_message = Some((message, std::time::Instant::now()));
+                    }
+
+                    // Generate a firewall rule for the selected connection
+                    (KeyCode::Char('f'), _) => {
+                        ui_state.quit_confirmation = false;
+                        if ui_state.selected_tab <= 1
+                            && let Some(selected_idx) = ui_state.get_selected_index(&connections)
+                            && let Some(conn) = connections.get(selected_idx)
+                        {
+                            match app.generate_firewall_rule(conn) {
+                                Some(index) => {
+                                    ui_state.firewall_popup = Some(index);
+                                    ui_state.firewall_confirm = false;
                                 }
-                                Err(e) => {
-                                    error!("Failed to access clipboard: {}", e);
+                                None => {
                                     ui_state.clipboard_message = Some((
-                                        format!("Clipboard error: {}", e),
+                                        format!(
+                                            "Cannot generate a firewall rule for {} traffic",
+                                            conn.protocol
+                                        ),
                                         std::time::Instant::now(),
                                     ));
                                 }
@@ -503,16 +885,36 @@ fn run_ui_loop<B: ratatui::prelude::Backend>(
                         }
                     }
 
+                    // Start or stop saving the selected connection's packets
+                    (KeyCode::Char('w'), _) => {
+                        ui_state.quit_confirmation = false;
+                        if ui_state.selected_tab <= 1
+                            && let Some(selected_idx) = ui_state.get_selected_index(&connections)
+                            && let Some(conn) = connections.get(selected_idx)
+                        {
+                            let message = if let Some(path) = app.stop_recording(conn) {
+                                format!("Saved recording to {}", path.display())
+                            } else {
+                                match app.start_recording(conn) {
+                                    Ok(path) => format!("Recording to {}", path.display()),
+                                    Err(e) => {
+                                        error!("Failed to start recording: {}", e);
+                                        format!("Failed to start recording: {}", e)
+                                    }
+                                }
+                            };
+                            ui_state.clipboard_message = Some((message, std::time::Instant::now()));
+                        }
+                    }
+
                     // Escape to go back or clear filter
                     (KeyCode::Esc, _) => {
                         ui_state.quit_confirmation = false;
                         if !ui_state.filter_query.is_empty() {
                             // Clear filter if one is active
                             ui_state.clear_filter();
-                        } else if ui_state.selected_tab == 1 {
-                            ui_state.selected_tab = 0; // Back to overview
-                        } else if ui_state.selected_tab == 2 {
-                            ui_state.selected_tab = 0; // Back to overview from help
+                        } else if ui_state.selected_tab != 0 {
+                            ui_state.selected_tab = 0; // Back to overview from any other tab
                         }
                     }
 
@@ -1,6 +1,11 @@
 // network/capture.rs - Packet capture setup and utilities
-use anyhow::{Result, anyhow};
-use pcap::{Active, Capture, Device, Error as PcapError};
+use anyhow::{Context, Result, anyhow};
+use pcap::{Activated, Active, Capture, Device, Error as PcapError, Linktype, Offline};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Log target for capture setup and per-packet parsing
+pub(crate) const LOG_TARGET: &str = "rustnet::capture";
 
 /// Packet capture configuration
 #[derive(Debug, Clone)]
@@ -37,6 +42,7 @@ fn find_best_device() -> Result<Device> {
     let devices = Device::list()?;
 
     log::info!(
+        target: LOG_TARGET,
         "Scanning {} devices for best active interface...",
         devices.len()
     );
@@ -53,6 +59,7 @@ fn find_best_device() -> Result<Device> {
         });
 
         log::debug!(
+            target: LOG_TARGET,
             "  Device: {} [up: {}, running: {}, has_ip: {}]",
             d.name,
             d.flags.is_up(),
@@ -115,18 +122,19 @@ fn find_best_device() -> Result<Device> {
     match suitable_device {
         Some(device) => {
             log::info!(
+                target: LOG_TARGET,
                 "Selected active device: {} ({} addresses)",
                 device.name,
                 device.addresses.len()
             );
             for addr in &device.addresses {
-                log::debug!("  Address: {}", addr.addr);
+                log::debug!(target: LOG_TARGET, "  Address: {}", addr.addr);
             }
             Ok(device)
         }
         None => {
-            log::error!("No suitable active network device found!");
-            log::error!("Try specifying an interface manually with -i flag");
+            log::error!(target: LOG_TARGET, "No suitable active network device found!");
+            log::error!(target: LOG_TARGET, "Try specifying an interface manually with -i flag");
             Err(anyhow!(
                 "No active network interface found. Use -i to specify one manually."
             ))
@@ -139,7 +147,7 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
     // Try PKTAP first on macOS for process metadata
     #[cfg(target_os = "macos")]
     {
-        log::info!("Attempting to use PKTAP for process metadata on macOS");
+        log::info!(target: LOG_TARGET, "Attempting to use PKTAP for process metadata on macOS");
 
         match Capture::from_device("pktap") {
             Ok(pktap_builder) => {
@@ -156,11 +164,12 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
                     Ok(mut cap) => {
                         // Try to set direction for better performance (optional)
                         if let Err(e) = cap.direction(pcap::Direction::InOut) {
-                            log::debug!("Could not set PKTAP direction: {}", e);
+                            log::debug!(target: LOG_TARGET, "Could not set PKTAP direction: {}", e);
                         }
 
                         let linktype = cap.get_datalink();
                         log::info!(
+                            target: LOG_TARGET,
                             "✓ PKTAP enabled successfully, linktype: {} ({})",
                             linktype.0,
                             if linktype.0 == 149 {
@@ -172,15 +181,23 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
 
                         // Apply BPF filter if specified
                         if let Some(filter) = &config.filter {
-                            log::info!("Applying BPF filter to PKTAP: {}", filter);
-                            cap.filter(filter, true)?;
+                            log::info!(
+                                target: LOG_TARGET,
+                                "Applying BPF filter to PKTAP: {}", filter
+                            );
+                            cap.filter(filter, true)
+                                .map_err(|e| anyhow!("Invalid BPF filter '{}': {}", filter, e))?;
                         }
 
-                        log::info!("PKTAP capture ready - process metadata will be available");
+                        log::info!(
+                            target: LOG_TARGET,
+                            "PKTAP capture ready - process metadata will be available"
+                        );
                         return Ok((cap, "pktap".to_string(), linktype.0));
                     }
                     Err(e) => {
                         log::warn!(
+                            target: LOG_TARGET,
                             "Failed to open PKTAP capture: {}, falling back to regular capture",
                             e
                         );
@@ -189,6 +206,7 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
             }
             Err(e) => {
                 log::warn!(
+                    target: LOG_TARGET,
                     "Failed to create PKTAP device: {}, falling back to regular capture",
                     e
                 );
@@ -197,10 +215,11 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
     }
 
     // Fallback to regular capture (original code)
-    log::info!("Setting up regular packet capture");
+    log::info!(target: LOG_TARGET, "Setting up regular packet capture");
     let device = find_capture_device(&config.interface)?;
 
     log::info!(
+        target: LOG_TARGET,
         "Setting up capture on device: {} ({})",
         device.name,
         device.desc.as_deref().unwrap_or("no description")
@@ -211,7 +230,10 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
     // Disable promiscuous mode for "any" interface on Linux
     // The "any" device doesn't support promiscuous mode
     let use_promisc = if device_name == "any" {
-        log::info!("Disabling promiscuous mode for 'any' interface (not supported)");
+        log::info!(
+            target: LOG_TARGET,
+            "Disabling promiscuous mode for 'any' interface (not supported)"
+        );
         false
     } else {
         config.promiscuous
@@ -230,8 +252,9 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
 
     // Apply BPF filter if specified
     if let Some(filter) = &config.filter {
-        log::info!("Applying BPF filter: {}", filter);
-        cap.filter(filter, true)?;
+        log::info!(target: LOG_TARGET, "Applying BPF filter: {}", filter);
+        cap.filter(filter, true)
+            .map_err(|e| anyhow!("Invalid BPF filter '{}': {}", filter, e))?;
     }
 
     // Note: We're not setting non-blocking mode as we're using timeout instead
@@ -240,11 +263,36 @@ pub fn setup_packet_capture(config: CaptureConfig) -> Result<(Capture<Active>, S
     Ok((cap, device_name, linktype.0))
 }
 
+/// Open a savefile to read packets from instead of an interface, returning
+/// it with its link type. Needs no capture privileges.
+pub fn open_savefile(path: &Path, filter: Option<&str>) -> Result<(Capture<Offline>, i32)> {
+    let mut cap =
+        Capture::from_file(path).with_context(|| format!("failed to open {}", path.display()))?;
+
+    if let Some(filter) = filter {
+        log::info!(target: LOG_TARGET, "Applying BPF filter: {}", filter);
+        cap.filter(filter, true)
+            .map_err(|e| anyhow!("Invalid BPF filter '{}': {}", filter, e))?;
+    }
+
+    let linktype = cap.get_datalink();
+    Ok((cap, linktype.0))
+}
+
+/// Check that `filter` compiles for captures of `linktype`, without needing
+/// the live capture handle
+pub fn validate_bpf_filter(filter: &str, linktype: i32) -> Result<()> {
+    Capture::dead(Linktype(linktype))?
+        .compile(filter, true)
+        .map_err(|e| anyhow!("Invalid BPF filter '{}': {}", filter, e))?;
+    Ok(())
+}
+
 /// Find a capture device by name or return the default
 fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
     match interface_name {
         Some(name) => {
-            log::info!("Looking for interface: {}", name);
+            log::info!(target: LOG_TARGET, "Looking for interface: {}", name);
 
             // Special handling for 'any' interface
             if name == "any" {
@@ -259,7 +307,10 @@ fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
 
                 #[cfg(target_os = "linux")]
                 {
-                    log::info!("Using 'any' pseudo-interface to capture on all interfaces");
+                    log::info!(
+                        target: LOG_TARGET,
+                        "Using 'any' pseudo-interface to capture on all interfaces"
+                    );
                 }
             }
 
@@ -296,12 +347,13 @@ fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
             ))
         }
         None => {
-            log::info!("No interface specified, using default");
+            log::info!(target: LOG_TARGET, "No interface specified, using default");
 
             // Try to get default device
             match Device::lookup() {
                 Ok(Some(device)) => {
                     log::info!(
+                        target: LOG_TARGET,
                         "Found default device: {} ({})",
                         device.name,
                         device.desc.as_deref().unwrap_or("no description")
@@ -333,10 +385,11 @@ fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
                         && has_valid_ip
                         && !is_problematic
                     {
-                        log::info!("Default device appears active, using it");
+                        log::info!(target: LOG_TARGET, "Default device appears active, using it");
                         Ok(device)
                     } else {
                         log::warn!(
+                            target: LOG_TARGET,
                             "Default device '{}' is not suitable (up: {}, running: {}, has_ip: {}, problematic: {})",
                             device.name,
                             device.flags.is_up(),
@@ -344,14 +397,14 @@ fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
                             has_valid_ip,
                             is_problematic
                         );
-                        log::info!("Looking for a better interface...");
+                        log::info!(target: LOG_TARGET, "Looking for a better interface...");
 
                         // Fall through to the device selection logic below
                         find_best_device()
                     }
                 }
                 Ok(None) => {
-                    log::info!("No default device found");
+                    log::info!(target: LOG_TARGET, "No default device found");
                     find_best_device()
                 }
                 Err(e) => Err(e.into()),
@@ -360,25 +413,135 @@ fn find_capture_device(interface_name: &Option<String>) -> Result<Device> {
     }
 }
 
-/// Simple packet reader that handles timeouts gracefully
-pub struct PacketReader {
-    capture: Capture<Active>,
+/// A captured frame together with the time libpcap recorded it
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    pub data: Vec<u8>,
+    /// Length of the frame on the wire; more than `data.len()` when the
+    /// snaplen cut it short
+    pub wire_len: usize,
+    pub timestamp: SystemTime,
+    /// `timestamp` on the monotonic clock, for ages, idle times and rates
+    pub instant: Instant,
 }
 
-impl PacketReader {
-    pub fn new(capture: Capture<Active>) -> Self {
-        Self { capture }
+/// Places capture timestamps on the monotonic clock. Gaps between recorded
+/// timestamps are kept, but a wall-clock step never moves the result
+/// backwards; the clock re-anchors instead. Live captures are also never
+/// placed after the time they were read, so a forward step or a suspend only
+/// makes a flow look less idle, never more.
+#[derive(Debug, Clone)]
+pub struct CaptureClock {
+    anchor: Option<(SystemTime, Instant)>,
+    last: Option<Instant>,
+    live: bool,
+}
+
+impl CaptureClock {
+    /// Clock for packets read as they arrive
+    pub fn live() -> Self {
+        Self {
+            anchor: None,
+            last: None,
+            live: true,
+        }
+    }
+
+    /// Clock for savefiles, which are read faster than they were recorded
+    pub fn replay() -> Self {
+        Self {
+            live: false,
+            ..Self::live()
+        }
+    }
+
+    /// Monotonic time of a packet captured at `timestamp` and read at `now`
+    pub fn instant_for(&mut self, timestamp: SystemTime, now: Instant) -> Instant {
+        let mapped = self.anchor.and_then(|(anchor_time, anchor_instant)| {
+            let since = timestamp.duration_since(anchor_time).ok()?;
+            anchor_instant.checked_add(since)
+        });
+
+        let instant = match mapped {
+            Some(mapped)
+                if self.last.is_none_or(|last| mapped >= last) && (!self.live || mapped <= now) =>
+            {
+                mapped
+            }
+            // First packet, or the wall clock stepped since the anchor
+            _ => {
+                let instant = match self.last {
+                    Some(last) if self.live => last.max(now),
+                    Some(last) => last,
+                    None => now,
+                };
+                self.anchor = Some((timestamp, instant));
+                instant
+            }
+        };
+        self.last = Some(instant);
+        instant
+    }
+}
+
+/// Convert a pcap header timestamp (seconds + microseconds) to `SystemTime`
+fn capture_time(tv_sec: i64, tv_usec: i64) -> SystemTime {
+    let secs = u64::try_from(tv_sec).unwrap_or(0);
+    let micros = u64::try_from(tv_usec).unwrap_or(0);
+    UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_micros(micros)
+}
+
+/// Simple packet reader that handles timeouts gracefully. Works on live
+/// captures as well as savefiles opened with `Capture::from_file`.
+pub struct PacketReader<T: Activated + ?Sized = Active> {
+    capture: Capture<T>,
+    clock: CaptureClock,
+}
+
+impl<T: Activated + ?Sized> PacketReader<T> {
+    pub fn new(capture: Capture<T>) -> Self {
+        Self {
+            capture,
+            clock: CaptureClock::live(),
+        }
+    }
+
+    /// Reader for a savefile, keeping the recorded gaps between packets
+    pub fn replay(capture: Capture<T>) -> Self {
+        Self {
+            capture,
+            clock: CaptureClock::replay(),
+        }
     }
 
     /// Read next packet, returning None on timeout
-    pub fn next_packet(&mut self) -> Result<Option<Vec<u8>>> {
+    pub fn next_packet(&mut self) -> Result<Option<CapturedPacket>> {
         match self.capture.next_packet() {
-            Ok(packet) => Ok(Some(packet.data.to_vec())),
+            Ok(packet) => {
+                // timeval field widths vary by platform
+                #[allow(clippy::unnecessary_cast)]
+                let timestamp = capture_time(
+                    packet.header.ts.tv_sec as i64,
+                    packet.header.ts.tv_usec as i64,
+                );
+                Ok(Some(CapturedPacket {
+                    data: packet.data.to_vec(),
+                    wire_len: packet.header.len as usize,
+                    timestamp,
+                    instant: self.clock.instant_for(timestamp, Instant::now()),
+                }))
+            }
             Err(PcapError::TimeoutExpired) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
 
+    /// Replace the BPF filter. An empty filter lets every packet through.
+    pub fn set_filter(&mut self, filter: &str) -> Result<()> {
+        self.capture.filter(filter, true)?;
+        Ok(())
+    }
+
     /// Get capture statistics
     pub fn stats(&mut self) -> Result<CaptureStats> {
         let stats = self.capture.stats()?;
@@ -393,16 +556,89 @@ impl PacketReader {
 /// Packet capture statistics
 #[derive(Debug, Clone, Default)]
 pub struct CaptureStats {
+    /// Packets the kernel passed to the capture
     pub received: u32,
+    /// Packets dropped because the capture buffer was full
     pub dropped: u32,
-    #[allow(dead_code)]
-    // TODO: implement interface-specific dropped packets
+    /// Packets the interface or driver dropped, where the platform reports it
     pub if_dropped: u32,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::network::merge::{
+        MergeOptions, create_connection_from_packet, merge_packet_into_connection,
+    };
+    use crate::network::parser::{PacketParser, ParserConfig};
+
+    /// Build an Ethernet/IPv4/TCP frame carrying `payload_len` bytes
+    fn tcp_frame(
+        src: [u8; 4],
+        dst: [u8; 4],
+        sport: u16,
+        dport: u16,
+        payload_len: usize,
+    ) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+
+        let total_len = (20 + 20 + payload_len) as u16;
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+
+        frame.extend_from_slice(&sport.to_be_bytes());
+        frame.extend_from_slice(&dport.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]); // seq + ack
+        frame.extend_from_slice(&[0x50, 0x10, 0xff, 0xff, 0, 0, 0, 0]); // ACK
+        frame.extend(std::iter::repeat_n(0u8, payload_len));
+        frame
+    }
+
+    /// Write a little-endian, microsecond-resolution Ethernet savefile,
+    /// truncating frames to `snaplen` as libpcap would
+    fn write_savefile(path: &Path, snaplen: u32, packets: &[(u32, u32, Vec<u8>)]) {
+        let mut file = 0xa1b2_c3d4u32.to_le_bytes().to_vec();
+        file.extend_from_slice(&2u16.to_le_bytes());
+        file.extend_from_slice(&4u16.to_le_bytes());
+        for field in [0u32, 0, snaplen, 1] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+        for (secs, micros, data) in packets {
+            let captured = &data[..data.len().min(snaplen as usize)];
+            for field in [*secs, *micros, captured.len() as u32, data.len() as u32] {
+                file.extend_from_slice(&field.to_le_bytes());
+            }
+            file.extend_from_slice(captured);
+        }
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_savefiles_open_with_their_filter() {
+        let frame = tcp_frame([198, 51, 100, 7], [192, 0, 2, 10], 443, 50000, 100);
+        let path =
+            std::env::temp_dir().join(format!("rustnet-read-file-{}.pcap", std::process::id()));
+        write_savefile(&path, 65535, &[(1_700_000_000, 0, frame)]);
+
+        let count = |filter| {
+            let (capture, linktype) = open_savefile(&path, filter).unwrap();
+            assert_eq!(linktype, 1);
+            let mut reader = PacketReader::replay(capture);
+            std::iter::from_fn(|| reader.next_packet().ok().flatten()).count()
+        };
+        assert_eq!(count(None), 1);
+        assert_eq!(count(Some("tcp port 443")), 1);
+        assert_eq!(count(Some("udp")), 0);
+
+        let err = open_savefile(&path, Some("tcp port")).unwrap_err();
+        assert!(err.to_string().contains("Invalid BPF filter 'tcp port'"));
+        std::fs::remove_file(&path).ok();
+        assert!(open_savefile(&path, None).is_err());
+    }
 
     #[test]
     fn test_default_config() {
@@ -411,4 +647,168 @@ mod tests {
         assert_eq!(config.snaplen, 1514);
         assert!(config.filter.is_none()); // Default starts without filter
     }
+
+    #[test]
+    fn test_bpf_filters_are_validated() {
+        assert!(validate_bpf_filter("tcp port 443 or icmp", 1).is_ok());
+        // An empty filter matches everything
+        assert!(validate_bpf_filter("", 1).is_ok());
+        let err = validate_bpf_filter("tcp port", 1).unwrap_err();
+        assert!(err.to_string().contains("Invalid BPF filter 'tcp port'"));
+    }
+
+    #[test]
+    fn test_capture_time_conversion() {
+        assert_eq!(
+            capture_time(1_700_000_000, 250_000),
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_250)
+        );
+        assert_eq!(capture_time(-1, -1), UNIX_EPOCH);
+    }
+
+    #[test]
+    fn test_replay_uses_recorded_timestamps() {
+        // Four packets from a documentation-range server, one second apart
+        let frame = tcp_frame([198, 51, 100, 7], [192, 0, 2, 10], 443, 50000, 1000);
+        let start = 1_700_000_000u32;
+        let packets: Vec<_> = (0..4).map(|i| (start + i, 0, frame.clone())).collect();
+
+        let path = std::env::temp_dir().join(format!("rustnet-replay-{}.pcap", std::process::id()));
+        write_savefile(&path, 65535, &packets);
+        let mut reader = PacketReader::replay(Capture::from_file(&path).unwrap());
+        let parser = PacketParser::new().with_linktype(1);
+
+        let mut conn = None;
+        while let Ok(Some(packet)) = reader.next_packet() {
+            let parsed = parser
+                .parse_packet(
+                    &packet.data,
+                    packet.wire_len,
+                    packet.timestamp,
+                    packet.instant,
+                )
+                .unwrap();
+            conn = Some(match conn {
+                None => create_connection_from_packet(&parsed, &MergeOptions::default()),
+                Some(conn) => merge_packet_into_connection(conn, &parsed, &MergeOptions::default()),
+            });
+        }
+        std::fs::remove_file(&path).ok();
+
+        let conn = conn.expect("savefile should produce a connection");
+        let first_seen = UNIX_EPOCH + Duration::from_secs(start as u64);
+        let last_seen = first_seen + Duration::from_secs(3);
+        assert_eq!(conn.packets_received, 4);
+        assert_eq!(conn.created_at, first_seen);
+        assert_eq!(conn.last_activity, last_seen);
+        let last_seen = conn.last_activity_instant;
+        assert_eq!(conn.age_at(last_seen), Duration::from_secs(3));
+        assert_eq!(
+            conn.idle_time_at(last_seen + Duration::from_secs(5)),
+            Duration::from_secs(5)
+        );
+
+        // Three rate samples spanning two recorded seconds; the first one's
+        // delta is skipped, leaving two frames per two seconds. The file is
+        // read far faster than it was recorded, so any decay or compression
+        // would show up here. Only the IP packet is counted, not the Ethernet
+        // header.
+        let expected_rate = (frame.len() - 14) as f64;
+        assert!(
+            (conn.current_incoming_rate_bps - expected_rate).abs() < 1.0,
+            "incoming rate: {}",
+            conn.current_incoming_rate_bps
+        );
+        assert_eq!(conn.current_outgoing_rate_bps, 0.0);
+    }
+
+    #[test]
+    fn test_snaplen_truncated_packets_count_full_ip_length() {
+        // A full-size segment captured with a DPI-friendly snaplen
+        let frame = tcp_frame([198, 51, 100, 7], [192, 0, 2, 10], 443, 50000, 1460);
+        assert_eq!(frame.len(), 1514);
+
+        let path =
+            std::env::temp_dir().join(format!("rustnet-snaplen-{}.pcap", std::process::id()));
+        write_savefile(&path, 96, &[(1_700_000_000, 0, frame)]);
+        let mut reader = PacketReader::new(Capture::from_file(&path).unwrap());
+        let packet = reader.next_packet().unwrap().unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(packet.data.len(), 96);
+        assert_eq!(packet.wire_len, 1514);
+
+        let parser = PacketParser::new().with_linktype(1);
+        let parsed = parser
+            .parse_packet(
+                &packet.data,
+                packet.wire_len,
+                packet.timestamp,
+                packet.instant,
+            )
+            .unwrap();
+        assert_eq!(parsed.packet_len, 1500);
+        let conn = create_connection_from_packet(&parsed, &MergeOptions::default());
+        assert_eq!(conn.bytes_received, 1500);
+
+        let on_the_wire = PacketParser::with_config(ParserConfig {
+            count_link_layer: true,
+            ..Default::default()
+        })
+        .with_linktype(1);
+        let parsed = on_the_wire
+            .parse_packet(
+                &packet.data,
+                packet.wire_len,
+                packet.timestamp,
+                packet.instant,
+            )
+            .unwrap();
+        assert_eq!(parsed.packet_len, 1514);
+    }
+
+    #[test]
+    fn test_capture_clock_ignores_backwards_wall_clock_step() {
+        let mut clock = CaptureClock::live();
+        let wall = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let now = Instant::now();
+        let secs = Duration::from_secs;
+
+        assert_eq!(clock.instant_for(wall, now), now);
+        assert_eq!(
+            clock.instant_for(wall + secs(2), now + secs(2)),
+            now + secs(2)
+        );
+
+        // NTP steps the wall clock back an hour between two packets
+        let stepped = wall - secs(3600);
+        assert_eq!(clock.instant_for(stepped, now + secs(3)), now + secs(3));
+        assert_eq!(
+            clock.instant_for(stepped + secs(1), now + secs(4)),
+            now + secs(4)
+        );
+
+        // Replayed files never run backwards either
+        let mut clock = CaptureClock::replay();
+        assert_eq!(clock.instant_for(wall, now), now);
+        assert_eq!(clock.instant_for(wall + secs(5), now), now + secs(5));
+        assert_eq!(clock.instant_for(stepped, now), now + secs(5));
+        assert_eq!(clock.instant_for(stepped + secs(1), now), now + secs(6));
+    }
+
+    #[test]
+    fn test_live_capture_clock_never_runs_ahead() {
+        let mut clock = CaptureClock::live();
+        let wall = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let now = Instant::now();
+        let secs = Duration::from_secs;
+
+        clock.instant_for(wall, now);
+        // The wall clock jumps a day ahead, e.g. after resuming from suspend
+        let jumped = wall + secs(86_400);
+        assert_eq!(clock.instant_for(jumped, now + secs(1)), now + secs(1));
+        assert_eq!(
+            clock.instant_for(jumped + secs(2), now + secs(3)),
+            now + secs(3)
+        );
+    }
 }
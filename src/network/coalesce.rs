@@ -0,0 +1,226 @@
+// src/network/coalesce.rs - Folding per-exchange UDP flows into one row per server
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::network::types::{Connection, Protocol, ProtocolState, UdpState};
+
+/// UDP server ports whose clients tend to use a new local port for every
+/// exchange: DNS, NTP and QUIC
+pub const DEFAULT_SERVER_PORTS: [u16; 3] = [53, 123, 443];
+
+/// Key of the row a flow is folded into: the flow's own key with the local
+/// port set to 0, which is also what the row's `Connection::key` returns
+pub fn group_key(conn: &Connection) -> String {
+    format!(
+        "{:?}:{}-{:?}:{}",
+        conn.protocol,
+        SocketAddr::new(conn.local_addr.ip(), 0),
+        conn.protocol,
+        conn.remote_addr
+    )
+}
+
+/// Whether `conn` is a client flow to one of `server_ports`
+fn is_coalescible(conn: &Connection, server_ports: &[u16]) -> bool {
+    conn.protocol == Protocol::UDP
+        && !conn.forwarded
+        && server_ports.contains(&conn.remote_addr.port())
+        && !server_ports.contains(&conn.local_addr.port())
+}
+
+/// Fold UDP flows to `server_ports` that differ only in their local port
+/// into one row per server, with `subflow_count` set. A flow with no siblings
+/// is left as it is. The folded flows are returned by `group_key`, oldest
+/// first, so they can still be shown under their row.
+pub fn coalesce_flows(
+    connections: Vec<Connection>,
+    server_ports: &[u16],
+) -> (Vec<Connection>, HashMap<String, Vec<Connection>>) {
+    let mut rows = Vec::with_capacity(connections.len());
+    let mut groups: HashMap<String, Vec<Connection>> = HashMap::new();
+    for conn in connections {
+        if is_coalescible(&conn, server_ports) {
+            groups.entry(group_key(&conn)).or_default().push(conn);
+        } else {
+            rows.push(conn);
+        }
+    }
+
+    let mut subflows = HashMap::new();
+    for (key, mut flows) in groups {
+        if flows.len() == 1 {
+            rows.append(&mut flows);
+            continue;
+        }
+        flows.sort_by_key(|conn| conn.created_instant);
+        rows.push(aggregate(&flows));
+        subflows.insert(key, flows);
+    }
+    (rows, subflows)
+}
+
+/// Row standing for `flows`. Counters and rates are summed; everything else
+/// comes from the most recently active flow.
+fn aggregate(flows: &[Connection]) -> Connection {
+    let newest = flows
+        .iter()
+        .max_by_key(|conn| conn.last_activity_instant)
+        .expect("groups are never empty");
+    let oldest = &flows[0];
+
+    let mut row = newest.clone();
+    row.local_addr = SocketAddr::new(newest.local_addr.ip(), 0);
+    row.subflow_count = flows.len();
+    row.created_at = oldest.created_at;
+    row.created_instant = oldest.created_instant;
+    row.bytes_sent = flows.iter().map(|conn| conn.bytes_sent).sum();
+    row.bytes_received = flows.iter().map(|conn| conn.bytes_received).sum();
+    row.packets_sent = flows.iter().map(|conn| conn.packets_sent).sum();
+    row.packets_received = flows.iter().map(|conn| conn.packets_received).sum();
+    row.current_incoming_rate_bps = flows
+        .iter()
+        .map(|conn| conn.current_incoming_rate_bps)
+        .sum();
+    row.current_outgoing_rate_bps = flows
+        .iter()
+        .map(|conn| conn.current_outgoing_rate_bps)
+        .sum();
+    row.current_rate_bps.incoming_bps = row.current_incoming_rate_bps;
+    row.current_rate_bps.outgoing_bps = row.current_outgoing_rate_bps;
+
+    // Answered queries make the server active even if the latest is pending
+    if flows
+        .iter()
+        .any(|conn| matches!(conn.protocol_state, ProtocolState::Udp(UdpState::Active)))
+    {
+        row.protocol_state = ProtocolState::Udp(UdpState::Active);
+    }
+    if row.pid.is_none()
+        && let Some(owner) = flows.iter().rev().find(|conn| conn.pid.is_some())
+    {
+        row.pid = owner.pid;
+        row.process_name.clone_from(&owner.process_name);
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    fn udp_flow(local_port: u16, remote: &str) -> Connection {
+        Connection::new(
+            Protocol::UDP,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), local_port),
+            remote.parse().unwrap(),
+            ProtocolState::Udp(UdpState::Unconnected),
+        )
+    }
+
+    /// One DNS query and its answer from a fresh local port
+    fn dns_exchange(local_port: u16) -> Connection {
+        let mut conn = udp_flow(local_port, "1.1.1.1:53");
+        conn.protocol_state = ProtocolState::Udp(UdpState::Active);
+        conn.packets_sent = 1;
+        conn.packets_received = 1;
+        conn.bytes_sent = 70;
+        conn.bytes_received = 120;
+        conn.current_incoming_rate_bps = 10.0;
+        conn
+    }
+
+    #[test]
+    fn test_dns_queries_from_ephemeral_ports_collapse_to_one_row() {
+        let start = Instant::now();
+        let mut connections: Vec<Connection> = (0..200)
+            .map(|i| {
+                let mut conn = dns_exchange(40000 + i);
+                conn.created_instant = start + Duration::from_millis(i as u64);
+                conn.last_activity_instant = conn.created_instant;
+                conn
+            })
+            .collect();
+        connections[150].pid = Some(812);
+        connections[150].process_name = Some("systemd-resolve".to_string());
+        connections.push(udp_flow(40500, "9.9.9.9:53"));
+        connections.push(udp_flow(5353, "224.0.0.251:5353"));
+
+        let (rows, subflows) = coalesce_flows(connections, &DEFAULT_SERVER_PORTS);
+
+        assert_eq!(rows.len(), 3);
+        let row = rows
+            .iter()
+            .find(|conn| conn.subflow_count > 0)
+            .expect("the resolver flows should be folded");
+        assert_eq!(row.subflow_count, 200);
+        assert_eq!(row.local_addr, "192.168.1.10:0".parse().unwrap());
+        assert_eq!(row.packets_sent, 200);
+        assert_eq!(row.packets_received, 200);
+        assert_eq!(row.bytes_sent, 200 * 70);
+        assert_eq!(row.bytes_received, 200 * 120);
+        assert_eq!(row.current_incoming_rate_bps, 2000.0);
+        assert_eq!(row.created_instant, start);
+        assert_eq!(
+            row.last_activity_instant,
+            start + Duration::from_millis(199)
+        );
+        assert_eq!(row.pid, Some(812));
+        assert_eq!(row.state(), "UDP_ACTIVE");
+
+        // The individual flows stay available under the row's key
+        let flows = &subflows[&row.key()];
+        assert_eq!(flows.len(), 200);
+        assert_eq!(flows[0].local_addr.port(), 40000);
+        assert_eq!(subflows.len(), 1);
+
+        // A lone query and unrelated ports are left alone
+        assert!(
+            rows.iter()
+                .any(|conn| conn.remote_addr.port() == 53 && conn.local_addr.port() == 40500)
+        );
+    }
+
+    #[test]
+    fn test_only_configured_server_ports_are_coalesced() {
+        let connections = vec![
+            udp_flow(50001, "192.0.2.1:443"),
+            udp_flow(50002, "192.0.2.1:443"),
+            udp_flow(50003, "192.0.2.1:8443"),
+            udp_flow(50004, "192.0.2.1:8443"),
+        ];
+
+        let (rows, _) = coalesce_flows(connections.clone(), &DEFAULT_SERVER_PORTS);
+        assert_eq!(rows.len(), 3);
+
+        let (rows, subflows) = coalesce_flows(connections.clone(), &[443, 8443]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(subflows.len(), 2);
+
+        let (rows, subflows) = coalesce_flows(connections, &[]);
+        assert_eq!(rows.len(), 4);
+        assert!(subflows.is_empty());
+    }
+
+    #[test]
+    fn test_tcp_and_server_side_flows_are_not_coalesced() {
+        let mut tcp = udp_flow(50001, "192.0.2.1:443");
+        tcp.protocol = Protocol::TCP;
+        let connections = vec![
+            tcp.clone(),
+            Connection {
+                local_addr: "192.168.1.10:50002".parse().unwrap(),
+                ..tcp
+            },
+            // Queries to a resolver running on this host
+            udp_flow(53, "192.0.2.7:41000"),
+            udp_flow(53, "192.0.2.7:41001"),
+        ];
+
+        let (rows, subflows) = coalesce_flows(connections, &DEFAULT_SERVER_PORTS);
+        assert_eq!(rows.len(), 4);
+        assert!(subflows.is_empty());
+    }
+}
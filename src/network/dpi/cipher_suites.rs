@@ -4,7 +4,7 @@
 //! The mappings are based on the IANA TLS Cipher Suite Registry and include commonly
 //! used cipher suites from TLS 1.0 through TLS 1.3.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
 
 /// Static mapping of cipher suite codes to their names
@@ -89,7 +89,14 @@ static CIPHER_SUITE_MAP: LazyLock<HashMap<u16, &'static str>> = LazyLock::new(||
     map.insert(0xc076, "TLS_ECDHE_RSA_WITH_CAMELLIA_128_GCM_SHA256");
     map.insert(0xc077, "TLS_ECDHE_RSA_WITH_CAMELLIA_256_GCM_SHA384");
 
+    // Export-grade (40/56-bit keys)
+    map.insert(0x0003, "TLS_RSA_EXPORT_WITH_RC4_40_MD5");
+    map.insert(0x0006, "TLS_RSA_EXPORT_WITH_RC2_CBC_40_MD5");
+    map.insert(0x0008, "TLS_RSA_EXPORT_WITH_DES40_CBC_SHA");
+    map.insert(0x0014, "TLS_DHE_RSA_EXPORT_WITH_DES40_CBC_SHA");
+
     // NULL encryption (for testing/debugging)
+    map.insert(0x0000, "TLS_NULL_WITH_NULL_NULL");
     map.insert(0x0001, "TLS_RSA_WITH_NULL_MD5");
     map.insert(0x0002, "TLS_RSA_WITH_NULL_SHA");
     map.insert(0x003b, "TLS_RSA_WITH_NULL_SHA256");
@@ -97,6 +104,72 @@ static CIPHER_SUITE_MAP: LazyLock<HashMap<u16, &'static str>> = LazyLock::new(||
     map
 });
 
+/// Why a cipher suite is known to be broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherWeakness {
+    /// 40/56-bit export-grade keys
+    Export,
+    /// No encryption at all
+    Null,
+    Rc4,
+    TripleDes,
+    /// Listed with `--weak-ciphers`
+    Configured,
+}
+
+impl std::fmt::Display for CipherWeakness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Export => write!(f, "export-grade"),
+            Self::Null => write!(f, "no encryption"),
+            Self::Rc4 => write!(f, "RC4"),
+            Self::TripleDes => write!(f, "3DES"),
+            Self::Configured => write!(f, "listed as weak"),
+        }
+    }
+}
+
+/// Built-in broken suites from the IANA registry
+static WEAK_CIPHER_SUITES: LazyLock<HashMap<u16, CipherWeakness>> = LazyLock::new(|| {
+    use CipherWeakness::*;
+    let mut map = HashMap::new();
+    for code in [
+        0x0003, 0x0006, 0x0008, 0x000b, 0x000e, 0x0011, 0x0014, 0x0017, 0x0019, 0x0026, 0x0027,
+        0x0028, 0x0029, 0x002a, 0x002b, 0x0060, 0x0061, 0x0062, 0x0063, 0x0064, 0x0065,
+    ] {
+        map.insert(code, Export);
+    }
+    for code in [
+        0x0000, 0x0001, 0x0002, 0x002c, 0x002d, 0x002e, 0x003b, 0x00b0, 0x00b1, 0x00b4, 0x00b5,
+        0x00b8, 0x00b9, 0xc001, 0xc006, 0xc00b, 0xc010, 0xc015, 0xc039, 0xc03a, 0xc03b,
+    ] {
+        map.insert(code, Null);
+    }
+    for code in [
+        0x0004, 0x0005, 0x0018, 0x0020, 0x0024, 0x008a, 0x008e, 0x0092, 0xc002, 0xc007, 0xc00c,
+        0xc011, 0xc016, 0xc033,
+    ] {
+        map.insert(code, Rc4);
+    }
+    for code in [
+        0x000a, 0x000d, 0x0010, 0x0013, 0x0016, 0x001b, 0x001f, 0x0023, 0x008b, 0x008f, 0x0093,
+        0xc003, 0xc008, 0xc00d, 0xc012, 0xc017, 0xc01a, 0xc01b, 0xc01c, 0xc034,
+    ] {
+        map.insert(code, TripleDes);
+    }
+    map
+});
+
+/// Why a cipher suite should not be negotiated, if it shouldn't. `configured`
+/// holds the suites a site policy bans on top of the built-in list.
+pub fn cipher_suite_weakness(code: u16, configured: &HashSet<u16>) -> Option<CipherWeakness> {
+    WEAK_CIPHER_SUITES.get(&code).copied().or_else(|| {
+        configured
+            .contains(&code)
+            .then_some(CipherWeakness::Configured)
+    })
+}
+
 /// Get the human-readable name for a cipher suite code
 ///
 /// # Arguments
@@ -182,6 +255,49 @@ mod tests {
         assert!(!is_secure_cipher_suite(0x000a)); // 3DES
     }
 
+    #[test]
+    fn test_weak_cipher_suites() {
+        let none = HashSet::new();
+        assert_eq!(
+            cipher_suite_weakness(0x0003, &none),
+            Some(CipherWeakness::Export)
+        );
+        assert_eq!(
+            cipher_suite_weakness(0x0002, &none),
+            Some(CipherWeakness::Null)
+        );
+        assert_eq!(
+            cipher_suite_weakness(0xc011, &none),
+            Some(CipherWeakness::Rc4)
+        );
+        assert_eq!(
+            cipher_suite_weakness(0x000a, &none),
+            Some(CipherWeakness::TripleDes)
+        );
+        assert_eq!(cipher_suite_weakness(0x1301, &none), None);
+        assert_eq!(cipher_suite_weakness(0xc02f, &none), None);
+        // Every weak suite is also classed as insecure
+        assert!(
+            WEAK_CIPHER_SUITES
+                .keys()
+                .all(|code| !is_secure_cipher_suite(*code))
+        );
+
+        // CBC suites aren't broken, but a site policy can ban them
+        let banned = HashSet::from([0xc014]);
+        assert_eq!(
+            cipher_suite_weakness(0xc014, &banned),
+            Some(CipherWeakness::Configured)
+        );
+        assert_eq!(cipher_suite_weakness(0xc014, &none), None);
+        assert_eq!(cipher_suite_weakness(0xc013, &banned), None);
+        // The built-in reason wins over a policy listing the same suite
+        assert_eq!(
+            cipher_suite_weakness(0x000a, &HashSet::from([0x000a])),
+            Some(CipherWeakness::TripleDes)
+        );
+    }
+
     #[test]
     fn test_unknown_cipher_suite() {
         assert_eq!(get_cipher_suite_name(0xFFFF), None);
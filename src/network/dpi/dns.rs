@@ -1,117 +1,371 @@
-use crate::network::types::{DnsInfo, DnsQueryType};
+use crate::network::types::{DnsInfo, DnsQueryType, DnsResponseCode};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const DNS_HEADER_LEN: usize = 12;
+
+/// Names longer than this are malformed (RFC 1035, section 2.3.4)
+const MAX_NAME_LEN: usize = 255;
+
+/// Compression pointers followed while reading one name; more means a loop
+const MAX_POINTER_JUMPS: usize = 32;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_OPT: u16 = 41;
 
 pub fn analyze_dns(payload: &[u8]) -> Option<DnsInfo> {
-    if payload.len() < 12 {
+    if payload.len() < DNS_HEADER_LEN {
         return None;
     }
 
+    // DNS header flags
+    let flags = read_u16(payload, 2)?;
     let mut info = DnsInfo {
         query_name: None,
         query_type: None,
         response_ips: Vec::new(),
-        is_response: false,
+        is_response: (flags & 0x8000) != 0, // QR bit
+        rcode: None,
+        truncated: (flags & 0x0200) != 0, // TC bit
+        ttl: None,
     };
 
-    // DNS header flags
-    let flags = u16::from_be_bytes([payload[2], payload[3]]);
-    info.is_response = (flags & 0x8000) != 0; // QR bit
-
-    // Question count
-    let qdcount = u16::from_be_bytes([payload[4], payload[5]]);
-
-    if qdcount > 0 {
-        // Parse first question
-        let mut offset = 12;
-        let mut name = String::new();
-
-        // Parse domain name
-        while offset < payload.len() {
-            let label_len = payload[offset] as usize;
-            if label_len == 0 {
-                offset += 1;
-                break;
-            }
+    // Everything after the header is best effort: a message cut short keeps
+    // whatever was parsed before the cut
+    let mut rcode = flags & 0x000F;
+    let _ = parse_sections(payload, &mut info, &mut rcode);
 
-            if label_len >= 0xC0 {
-                // Compressed name - skip for simplicity
-                offset += 2;
-                break;
-            }
+    if info.is_response {
+        info.rcode = Some(DnsResponseCode::from(rcode));
+    }
 
-            if offset + 1 + label_len > payload.len() {
-                break;
-            }
+    Some(info)
+}
 
+/// Walk the question, answer, authority and additional sections
+fn parse_sections(msg: &[u8], info: &mut DnsInfo, rcode: &mut u16) -> Option<()> {
+    let qdcount = read_u16(msg, 4)?;
+    let ancount = read_u16(msg, 6)? as usize;
+    let record_count = ancount + read_u16(msg, 8)? as usize + read_u16(msg, 10)? as usize;
+
+    let mut offset = DNS_HEADER_LEN;
+    for i in 0..qdcount {
+        if i == 0 {
+            // Only the first question is reported
+            let (name, next) = read_name(msg, offset)?;
             if !name.is_empty() {
-                name.push('.');
+                info.query_name = Some(name);
             }
+            info.query_type = Some(query_type(read_u16(msg, next)?));
+            offset = next + 4; // QTYPE + QCLASS
+        } else {
+            offset = skip_name(msg, offset)? + 4;
+        }
+    }
+
+    for i in 0..record_count {
+        let next = skip_name(msg, offset)?;
+        let rtype = read_u16(msg, next)?;
+        let ttl = u32::from_be_bytes(msg.get(next + 4..next + 8)?.try_into().ok()?);
+        let rdlength = read_u16(msg, next + 8)? as usize;
+        let rdata = msg.get(next + 10..next + 10 + rdlength)?;
+        offset = next + 10 + rdlength;
 
-            if let Ok(label) = std::str::from_utf8(&payload[offset + 1..offset + 1 + label_len]) {
-                name.push_str(label);
+        if i == 0 && ancount > 0 {
+            info.ttl = Some(ttl);
+        }
+
+        match rtype {
+            TYPE_A | TYPE_AAAA if i < ancount => {
+                let ip = match rdata.len() {
+                    4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(rdata).ok()?)),
+                    16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(rdata).ok()?)),
+                    _ => continue,
+                };
+                if !info.response_ips.contains(&ip) {
+                    info.response_ips.push(ip);
+                }
             }
+            // EDNS keeps the upper 8 bits of a 12-bit RCODE in the OPT TTL
+            TYPE_OPT => *rcode |= ((ttl >> 24) as u16) << 4,
+            // Everything else (CNAME, SOA, unknown types, ...) is skipped via RDLENGTH
+            _ => {}
+        }
+    }
+
+    Some(())
+}
+
+fn read_u16(msg: &[u8], offset: usize) -> Option<u16> {
+    let bytes = msg.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
 
-            offset += 1 + label_len;
+/// Read the possibly compressed name at `offset`, returning it and the offset
+/// just past it in the message
+fn read_name(msg: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut wire_len = 0;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *msg.get(offset)? as usize;
+        match len & 0xC0 {
+            0x00 if len == 0 => return Some((name, end.unwrap_or(offset + 1))),
+            0x00 => {
+                let label = msg.get(offset + 1..offset + 1 + len)?;
+                wire_len += 1 + len;
+                if wire_len > MAX_NAME_LEN {
+                    return None;
+                }
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(&String::from_utf8_lossy(label));
+                offset += 1 + len;
+            }
+            0xC0 => {
+                jumps += 1;
+                if jumps > MAX_POINTER_JUMPS {
+                    return None;
+                }
+                let low = *msg.get(offset + 1)? as usize;
+                end.get_or_insert(offset + 2);
+                offset = ((len & 0x3F) << 8) | low;
+            }
+            // 0x40 and 0x80 label types are obsolete or unassigned
+            _ => return None,
         }
+    }
+}
 
-        if !name.is_empty() {
-            info.query_name = Some(name);
+/// Offset just past the name at `offset`, without following pointers
+fn skip_name(msg: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(offset)? as usize;
+        match len & 0xC0 {
+            0x00 if len == 0 => return Some(offset + 1),
+            0x00 => offset += 1 + len,
+            0xC0 => return Some(offset + 2),
+            _ => return None,
         }
+    }
+}
 
-        // Query type
-        if offset + 2 <= payload.len() {
-            let qtype = u16::from_be_bytes([payload[offset], payload[offset + 1]]);
-            info.query_type = Some(match qtype {
-                1 => DnsQueryType::A,
-                2 => DnsQueryType::NS,
-                5 => DnsQueryType::CNAME,
-                6 => DnsQueryType::SOA,
-                12 => DnsQueryType::PTR,
-                13 => DnsQueryType::HINFO,
-                15 => DnsQueryType::MX,
-                16 => DnsQueryType::TXT,
-                17 => DnsQueryType::RP,
-                18 => DnsQueryType::AFSDB,
-                24 => DnsQueryType::SIG,
-                25 => DnsQueryType::KEY,
-                28 => DnsQueryType::AAAA,
-                29 => DnsQueryType::LOC,
-                33 => DnsQueryType::SRV,
-                35 => DnsQueryType::NAPTR,
-                36 => DnsQueryType::KX,
-                37 => DnsQueryType::CERT,
-                39 => DnsQueryType::DNAME,
-                42 => DnsQueryType::APL,
-                43 => DnsQueryType::DS,
-                44 => DnsQueryType::SSHFP,
-                45 => DnsQueryType::IPSECKEY,
-                46 => DnsQueryType::RRSIG,
-                47 => DnsQueryType::NSEC,
-                48 => DnsQueryType::DNSKEY,
-                49 => DnsQueryType::DHCID,
-                50 => DnsQueryType::NSEC3,
-                51 => DnsQueryType::NSEC3PARAM,
-                52 => DnsQueryType::TLSA,
-                53 => DnsQueryType::SMIMEA,
-                55 => DnsQueryType::HIP,
-                59 => DnsQueryType::CDS,
-                60 => DnsQueryType::CDNSKEY,
-                61 => DnsQueryType::OPENPGPKEY,
-                62 => DnsQueryType::CSYNC,
-                63 => DnsQueryType::ZONEMD,
-                64 => DnsQueryType::SVCB,
-                65 => DnsQueryType::HTTPS,
-                108 => DnsQueryType::EUI48,
-                109 => DnsQueryType::EUI64,
-                249 => DnsQueryType::TKEY,
-                250 => DnsQueryType::TSIG,
-                256 => DnsQueryType::URI,
-                257 => DnsQueryType::CAA,
-                32768 => DnsQueryType::TA,
-                32769 => DnsQueryType::DLV,
-                other => DnsQueryType::Other(other),
-            });
+fn query_type(qtype: u16) -> DnsQueryType {
+    match qtype {
+        1 => DnsQueryType::A,
+        2 => DnsQueryType::NS,
+        5 => DnsQueryType::CNAME,
+        6 => DnsQueryType::SOA,
+        12 => DnsQueryType::PTR,
+        13 => DnsQueryType::HINFO,
+        15 => DnsQueryType::MX,
+        16 => DnsQueryType::TXT,
+        17 => DnsQueryType::RP,
+        18 => DnsQueryType::AFSDB,
+        24 => DnsQueryType::SIG,
+        25 => DnsQueryType::KEY,
+        28 => DnsQueryType::AAAA,
+        29 => DnsQueryType::LOC,
+        33 => DnsQueryType::SRV,
+        35 => DnsQueryType::NAPTR,
+        36 => DnsQueryType::KX,
+        37 => DnsQueryType::CERT,
+        39 => DnsQueryType::DNAME,
+        42 => DnsQueryType::APL,
+        43 => DnsQueryType::DS,
+        44 => DnsQueryType::SSHFP,
+        45 => DnsQueryType::IPSECKEY,
+        46 => DnsQueryType::RRSIG,
+        47 => DnsQueryType::NSEC,
+        48 => DnsQueryType::DNSKEY,
+        49 => DnsQueryType::DHCID,
+        50 => DnsQueryType::NSEC3,
+        51 => DnsQueryType::NSEC3PARAM,
+        52 => DnsQueryType::TLSA,
+        53 => DnsQueryType::SMIMEA,
+        55 => DnsQueryType::HIP,
+        59 => DnsQueryType::CDS,
+        60 => DnsQueryType::CDNSKEY,
+        61 => DnsQueryType::OPENPGPKEY,
+        62 => DnsQueryType::CSYNC,
+        63 => DnsQueryType::ZONEMD,
+        64 => DnsQueryType::SVCB,
+        65 => DnsQueryType::HTTPS,
+        108 => DnsQueryType::EUI48,
+        109 => DnsQueryType::EUI64,
+        249 => DnsQueryType::TKEY,
+        250 => DnsQueryType::TSIG,
+        256 => DnsQueryType::URI,
+        257 => DnsQueryType::CAA,
+        32768 => DnsQueryType::TA,
+        32769 => DnsQueryType::DLV,
+        other => DnsQueryType::Other(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Response for www.example.com A as a recursive resolver sends it: the
+    /// answers use compression pointers (including a CNAME target pointing
+    /// into the question) and an EDNS OPT record advertises a 1232-byte payload
+    fn compressed_response() -> Vec<u8> {
+        let parts: [&[u8]; 6] = [
+            b"\x1a\x2b\x81\x80\x00\x01\x00\x02\x00\x00\x00\x01",
+            // Question (offset 12): www.example.com A IN
+            b"\x03www\x07example\x03com\x00\x00\x01\x00\x01",
+            // Answer (offset 33): www.example.com CNAME cdn.example.com
+            b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x06\x03cdn\xc0\x10",
+            // Answer (offset 51): cdn.example.com A 93.184.216.34, named by a
+            // pointer to the CNAME data
+            b"\xc0\x2d\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04",
+            &[93, 184, 216, 34],
+            // OPT pseudo-record
+            b"\x00\x00\x29\x04\xd0\x00\x00\x00\x00\x00\x00",
+        ];
+        parts.concat()
+    }
+
+    #[test]
+    fn test_compressed_response_decodes_exactly() {
+        let msg = compressed_response();
+        let info = analyze_dns(&msg).unwrap();
+
+        assert!(info.is_response);
+        assert_eq!(info.query_name.as_deref(), Some("www.example.com"));
+        assert_eq!(info.query_type, Some(DnsQueryType::A));
+        assert_eq!(info.response_ips, vec![IpAddr::from([93, 184, 216, 34])]);
+        assert_eq!(info.rcode, Some(DnsResponseCode::NoError));
+        assert!(!info.truncated);
+        // From the CNAME, not the A record behind it
+        assert_eq!(info.ttl, Some(3600));
+
+        // The CNAME target is a label followed by a pointer into the question
+        assert_eq!(
+            read_name(&msg, 45),
+            Some(("cdn.example.com".to_string(), 51))
+        );
+        // and the second answer's owner name points at it
+        assert_eq!(
+            read_name(&msg, 51),
+            Some(("cdn.example.com".to_string(), 53))
+        );
+    }
+
+    #[test]
+    fn test_multiple_and_missing_questions() {
+        // Two questions, the second compressed against the first; the AAAA
+        // answer after them must still be found
+        let mut msg = vec![0, 1, 0x81, 0x80, 0, 2, 0, 1, 0, 0, 0, 0];
+        msg.extend_from_slice(b"\x07example\x03com\x00\x00\x1c\x00\x01");
+        msg.extend_from_slice(b"\x04mail\xc0\x0c\x00\x1c\x00\x01");
+        msg.extend_from_slice(&[0xc0, 0x0c, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16]);
+        msg.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let info = analyze_dns(&msg).unwrap();
+        assert_eq!(info.query_name.as_deref(), Some("example.com"));
+        assert_eq!(info.query_type, Some(DnsQueryType::AAAA));
+        assert_eq!(
+            info.response_ips,
+            vec!["2001:db8::1".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(info.ttl, Some(60));
+
+        // No questions at all, e.g. a bare NOTIMP response
+        let info = analyze_dns(&[0, 1, 0x80, 0x04, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(info.query_name, None);
+        assert_eq!(info.query_type, None);
+        assert_eq!(info.rcode, Some(DnsResponseCode::NotImp));
+        assert_eq!(info.ttl, None);
+    }
+
+    #[test]
+    fn test_rcode_truncation_and_extended_rcode() {
+        // NXDOMAIN with TC set
+        let mut msg = vec![0, 1, 0x83, 0x83, 0, 1, 0, 0, 0, 0, 0, 0];
+        msg.extend_from_slice(b"\x07invalid\x00\x00\x01\x00\x01");
+        let info = analyze_dns(&msg).unwrap();
+        assert_eq!(info.rcode, Some(DnsResponseCode::NXDomain));
+        assert!(info.truncated);
+
+        // BADVERS (16): header RCODE 0, upper bits 1 in the OPT record
+        let mut msg = vec![0, 1, 0x81, 0x00, 0, 0, 0, 0, 0, 0, 0, 1];
+        msg.extend_from_slice(&[0, 0, 41, 0x04, 0xd0, 1, 0, 0, 0, 0, 0]);
+        let info = analyze_dns(&msg).unwrap();
+        assert_eq!(info.rcode, Some(DnsResponseCode::BadVers));
+        // The OPT record's TTL field holds flags, not a TTL
+        assert_eq!(info.ttl, None);
+
+        // Queries carry no status
+        let query = [0, 1, 0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(analyze_dns(&query).unwrap().rcode, None);
+    }
+
+    #[test]
+    fn test_compression_loops_are_rejected() {
+        // A pointer to itself, and two pointers to each other
+        let mut msg = vec![0, 1, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        msg.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]);
+        assert_eq!(read_name(&msg, 12), None);
+        assert_eq!(analyze_dns(&msg).unwrap().query_name, None);
+
+        let msg = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xc0, 0x0e, 0xc0, 0x0c];
+        assert_eq!(read_name(&msg, 12), None);
+
+        // A label followed by a pointer back to itself
+        let msg = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, b'a', 0xc0, 0x0c];
+        assert_eq!(read_name(&msg, 12), None);
+    }
+
+    #[test]
+    fn test_truncated_messages_keep_what_was_parsed() {
+        let msg = compressed_response();
+        for len in 0..msg.len() {
+            let info = analyze_dns(&msg[..len]);
+            if len < DNS_HEADER_LEN {
+                assert!(info.is_none());
+                continue;
+            }
+            let info = info.unwrap();
+            assert!(info.is_response);
+            assert!(info.response_ips.len() <= 1);
+            if let Some(name) = info.query_name {
+                assert_eq!(name, "www.example.com");
+            }
         }
     }
 
-    Some(info)
+    #[test]
+    fn test_arbitrary_bytes_never_panic() {
+        // xorshift so the test is deterministic without extra dependencies
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..20_000 {
+            let len = (next() % 300) as usize;
+            let mut msg: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // Small section counts and plenty of pointers make the parser dig deeper
+            if len >= DNS_HEADER_LEN {
+                for count in (4..12).step_by(2) {
+                    msg[count] = 0;
+                    msg[count + 1] = (next() % 4) as u8;
+                }
+            }
+            for byte in msg.iter_mut().skip(DNS_HEADER_LEN) {
+                if next() % 8 == 0 {
+                    *byte |= 0xc0;
+                }
+            }
+            let _ = analyze_dns(&msg);
+        }
+    }
 }
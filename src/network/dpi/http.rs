@@ -13,6 +13,7 @@ pub fn analyze_http(payload: &[u8]) -> Option<HttpInfo> {
         path: None,
         status_code: None,
         user_agent: None,
+        response_time: None,
     };
 
     // Safe string conversion for HTTP parsing
@@ -1,4 +1,5 @@
-use crate::network::types::{HttpsInfo, TlsInfo, TlsVersion};
+use super::LOG_TARGET;
+use crate::network::types::{HttpsInfo, TlsHandshakeReassembler, TlsInfo, TlsVersion};
 use log::debug;
 
 pub fn is_tls_handshake(payload: &[u8]) -> bool {
@@ -15,59 +16,81 @@ pub fn is_tls_handshake(payload: &[u8]) -> bool {
         (payload[2] >= 0x01 && payload[2] <= 0x04) // Minor version 1-4
 }
 
-pub fn analyze_https(payload: &[u8]) -> Option<HttpsInfo> {
+pub fn analyze_https(payload: &[u8], is_outgoing: bool) -> Option<HttpsInfo> {
     // Need at least 5 bytes for the TLS record header
     if payload.len() < 5 {
         return None;
     }
 
-    let mut info = TlsInfo::new();
-
-    // Check content type
     let content_type = payload[0];
-
-    if content_type != 0x16 {
-        // Not a handshake record - still extract version
-        let record_version = version_from_bytes(payload[1], payload[2]);
-        info.version = record_version;
+    if !matches!(content_type, 0x14..=0x17) || payload[1] != 0x03 {
+        // Not a record header, most likely the rest of a record that began in
+        // an earlier segment - it may complete a buffered handshake message
         return Some(HttpsInfo {
-            tls_info: Some(info),
+            tls_info: None,
+            handshake: Some(TlsHandshakeReassembler::segment(is_outgoing, payload)),
         });
     }
 
+    let mut info = TlsInfo::new();
+
     // Record layer version
     let record_version = version_from_bytes(payload[1], payload[2]);
     info.version = record_version;
 
-    // Get record length
-    let record_length = u16::from_be_bytes([payload[3], payload[4]]) as usize;
-
-    // Sanity check
-    if record_length > 16384 + 2048 {
+    if content_type != 0x16 {
+        // Not a handshake record - still extract version
         return Some(HttpsInfo {
             tls_info: Some(info),
+            handshake: None,
         });
     }
 
-    // Calculate available data (handle fragmentation gracefully)
-    let available_data = (payload.len() - 5).min(record_length);
-
-    if available_data < 4 {
+    // The whole handshake message may be here, spread over several records
+    let mut handshake = TlsHandshakeReassembler::segment(is_outgoing, payload);
+    if let Some(message) = handshake.take_message(is_outgoing) {
+        parse_handshake(&message, &mut info, record_version);
         return Some(HttpsInfo {
             tls_info: Some(info),
+            handshake: None,
         });
     }
 
-    // Skip TLS record header (5 bytes)
-    let handshake_data = &payload[5..5 + available_data];
+    // Otherwise get what we can from the first record, and keep the segment
+    // so the message can be completed by the following ones
+    let record_length = u16::from_be_bytes([payload[3], payload[4]]) as usize;
+
+    // Sanity check
+    if record_length <= 16384 + 2048 {
+        // Calculate available data (handle fragmentation gracefully)
+        let available_data = (payload.len() - 5).min(record_length);
+        parse_handshake(&payload[5..5 + available_data], &mut info, record_version);
+    }
+
+    Some(HttpsInfo {
+        tls_info: Some(info),
+        handshake: Some(handshake),
+    })
+}
+
+/// Parse a complete handshake message reassembled from several TCP segments
+pub(crate) fn parse_handshake_message(message: &[u8]) -> TlsInfo {
+    let mut info = TlsInfo::new();
+    parse_handshake(message, &mut info, None);
+    info
+}
+
+/// Parse a handshake message (4-byte header included), which may be cut short
+fn parse_handshake(handshake_data: &[u8], info: &mut TlsInfo, record_version: Option<TlsVersion>) {
+    if handshake_data.len() < 4 {
+        return;
+    }
 
     let handshake_type = handshake_data[0];
 
     // Quick validation
     if !matches!(handshake_type, 0x00..=0x18 | 0xfe) {
-        return Some(HttpsInfo {
-            tls_info: Some(info),
-        });
+        return;
     }
 
     let handshake_length =
@@ -75,18 +98,14 @@ pub fn analyze_https(payload: &[u8]) -> Option<HttpsInfo> {
 
     // Sanity check
     if handshake_length > 16384 {
-        return Some(HttpsInfo {
-            tls_info: Some(info),
-        });
+        return;
     }
 
     // Calculate how much handshake data we actually have
     let handshake_available = (handshake_data.len() - 4).min(handshake_length);
 
     if handshake_available == 0 {
-        return Some(HttpsInfo {
-            tls_info: Some(info),
-        });
+        return;
     }
 
     match handshake_type {
@@ -94,13 +113,13 @@ pub fn analyze_https(payload: &[u8]) -> Option<HttpsInfo> {
             // Client Hello - this is where SNI and ALPN are
             parse_client_hello(
                 &handshake_data[4..4 + handshake_available],
-                &mut info,
+                info,
                 record_version,
             );
         }
         0x02 => {
             // Server Hello
-            parse_server_hello(&handshake_data[4..4 + handshake_available], &mut info);
+            parse_server_hello(&handshake_data[4..4 + handshake_available], info);
         }
         _ => {
             // Other handshake types we don't parse
@@ -108,11 +127,8 @@ pub fn analyze_https(payload: &[u8]) -> Option<HttpsInfo> {
     }
 
     if info.sni.is_some() || !info.alpn.is_empty() {
-        debug!("TLS: Found SNI={:?}, ALPN={:?}", info.sni, info.alpn);
+        debug!(target: LOG_TARGET, "TLS: Found SNI={:?}, ALPN={:?}", info.sni, info.alpn);
     }
-    Some(HttpsInfo {
-        tls_info: Some(info),
-    })
 }
 
 fn version_from_bytes(major: u8, minor: u8) -> Option<TlsVersion> {
@@ -134,6 +150,7 @@ fn parse_client_hello(data: &[u8], info: &mut TlsInfo, record_version: Option<Tl
     // Client version
     let client_version = version_from_bytes(data[0], data[1]);
     info.version = client_version.or(record_version);
+    info.client_max_tls_version = info.version;
 
     // Need at least 34 bytes for version + random
     if data.len() < 34 {
@@ -200,6 +217,7 @@ fn parse_server_hello(data: &[u8], info: &mut TlsInfo) {
     // Server version
     let server_version = version_from_bytes(data[0], data[1]);
     info.version = server_version;
+    info.server_negotiated_version = server_version;
 
     if data.len() < 34 {
         return;
@@ -261,6 +279,11 @@ fn parse_extensions(data: &[u8], info: &mut TlsInfo, is_client_hello: bool) {
         let available_ext_len = data.len().saturating_sub(offset + 4);
         let ext_data_len = ext_len.min(available_ext_len);
 
+        // early_data is empty in a ClientHello
+        if ext_type == 0x002a && is_client_hello {
+            info.has_early_data = true;
+        }
+
         if ext_data_len > 0 {
             let ext_data = &data[offset + 4..offset + 4 + ext_data_len];
 
@@ -285,6 +308,12 @@ fn parse_extensions(data: &[u8], info: &mut TlsInfo, is_client_hello: bool) {
                         parse_supported_versions_resilient(ext_data, is_client_hello)
                     {
                         info.version = Some(version);
+                        if is_client_hello {
+                            info.client_max_tls_version =
+                                info.client_max_tls_version.max(Some(version));
+                        } else {
+                            info.server_negotiated_version = Some(version);
+                        }
                     }
                 }
                 _ => {
@@ -472,4 +501,181 @@ mod tests {
         assert!(!protocols.is_empty());
         assert!(protocols[0].contains("PARTIAL"));
     }
+
+    /// Build a TLS handshake record with the given handshake type and body
+    fn build_handshake_record(handshake_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut handshake = vec![handshake_type, 0x00];
+        handshake.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(body);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    /// ClientHello handshake message (header included) carrying SNI and ALPN
+    fn client_hello_message(sni: &str) -> Vec<u8> {
+        let mut server_name = vec![0x00]; // host_name
+        server_name.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+        server_name.extend_from_slice(sni.as_bytes());
+        let mut sni_ext = vec![0x00, 0x00];
+        sni_ext.extend_from_slice(&(server_name.len() as u16 + 2).to_be_bytes());
+        sni_ext.extend_from_slice(&(server_name.len() as u16).to_be_bytes());
+        sni_ext.extend_from_slice(&server_name);
+        let alpn_ext = [
+            0x00, 0x10, 0x00, 0x05, // ALPN, length 5
+            0x00, 0x03, 0x02, b'h', b'2',
+        ];
+
+        let mut body = vec![0x03, 0x03]; // TLS 1.2
+        body.extend_from_slice(&[0u8; 32]); // Random
+        body.push(0x00); // Session ID length
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // One cipher suite
+        body.extend_from_slice(&[0x01, 0x00]); // Null compression
+        body.extend_from_slice(&((sni_ext.len() + alpn_ext.len()) as u16).to_be_bytes());
+        body.extend_from_slice(&sni_ext);
+        body.extend_from_slice(&alpn_ext);
+
+        let mut message = vec![0x01, 0x00];
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(&body);
+        message
+    }
+
+    /// Wrap each fragment of a handshake message in its own record
+    fn records(fragments: &[&[u8]]) -> Vec<Vec<u8>> {
+        fragments
+            .iter()
+            .map(|fragment| {
+                let mut record = vec![0x16, 0x03, 0x01];
+                record.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+                record.extend_from_slice(fragment);
+                record
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_client_hello_split_across_two_records() {
+        let message = client_hello_message("example.com");
+        // Second record starts inside the cipher suites
+        let segment = records(&[&message[..40], &message[40..]]).concat();
+
+        let https = analyze_https(&segment, true).unwrap();
+        let info = https.tls_info.unwrap();
+        assert_eq!(info.sni.as_deref(), Some("example.com"));
+        assert_eq!(info.alpn, vec!["h2"]);
+        assert!(https.handshake.is_none());
+    }
+
+    #[test]
+    fn test_client_hello_split_mid_extension_across_segments() {
+        let message = client_hello_message("example.com");
+        // The first record ends in the middle of the server name
+        let split = message.windows(4).position(|w| w == b"exam").unwrap() + 4;
+        let records = records(&[&message[..split], &message[split..]]);
+
+        let first = analyze_https(&records[0], true).unwrap();
+        let partial = first.tls_info.unwrap().sni.unwrap();
+        assert!(partial.starts_with("exam") && partial.contains("PARTIAL"));
+        let mut reassembler = first.handshake.unwrap();
+        assert!(reassembler.take_message(true).is_none());
+
+        // The second record arrives split over two more TCP segments, the
+        // last of which has no record header
+        let (head, tail) = records[1].split_at(7);
+        for segment in [head, tail] {
+            let https = analyze_https(segment, true).unwrap();
+            reassembler.append(&https.handshake.unwrap());
+        }
+
+        let complete = reassembler.take_message(true).unwrap();
+        assert_eq!(complete, message);
+        let info = parse_handshake_message(&complete);
+        assert_eq!(info.sni.as_deref(), Some("example.com"));
+        assert_eq!(info.alpn, vec!["h2"]);
+
+        // Nothing more is buffered once the handshake message is done
+        reassembler.append(&TlsHandshakeReassembler::segment(true, &records[0]));
+        assert!(reassembler.take_message(true).is_none());
+    }
+
+    #[test]
+    fn test_handshake_reassembly_is_bounded() {
+        let mut reassembler = TlsHandshakeReassembler::default();
+        // Claims a 1MB ClientHello
+        let header = [0x16, 0x03, 0x01, 0x40, 0x00, 0x01, 0x10, 0x00, 0x00];
+        reassembler.append(&TlsHandshakeReassembler::segment(false, &header));
+        assert!(reassembler.take_message(false).is_none());
+
+        // The direction was given up on, so it doesn't grow
+        let filler = vec![0u8; 1024];
+        for _ in 0..64 {
+            reassembler.append(&TlsHandshakeReassembler::segment(false, &filler));
+        }
+        assert!(reassembler.take_message(false).is_none());
+
+        // Application data never starts a buffer
+        let mut other = TlsHandshakeReassembler::default();
+        other.append(&TlsHandshakeReassembler::segment(
+            true,
+            &[0x17, 0x03, 0x03, 0, 1, 0],
+        ));
+        assert!(other.take_message(true).is_none());
+    }
+
+    #[test]
+    fn test_client_hello_max_version_from_supported_versions() {
+        let mut body = vec![0x03, 0x03]; // Legacy version TLS 1.2
+        body.extend_from_slice(&[0u8; 32]); // Random
+        body.push(0x00); // Session ID length
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // One cipher suite
+        body.extend_from_slice(&[0x01, 0x00]); // Null compression
+        let ext = [
+            0x00, 0x2b, 0x00, 0x05, // supported_versions, length 5
+            0x04, 0x03, 0x04, 0x03, 0x03, // TLS 1.3, TLS 1.2
+        ];
+        body.extend_from_slice(&(ext.len() as u16).to_be_bytes());
+        body.extend_from_slice(&ext);
+
+        let info = analyze_https(&build_handshake_record(0x01, &body), true)
+            .and_then(|https| https.tls_info)
+            .unwrap();
+        assert_eq!(info.client_max_tls_version, Some(TlsVersion::Tls13));
+        assert_eq!(info.server_negotiated_version, None);
+        assert!(!info.has_early_data);
+    }
+
+    #[test]
+    fn test_client_hello_early_data() {
+        let mut message = client_hello_message("example.com");
+        // Append an empty early_data extension to the ClientHello
+        message.extend_from_slice(&[0x00, 0x2a, 0x00, 0x00]);
+        let body_len = message.len() - 4;
+        message[2..4].copy_from_slice(&(body_len as u16).to_be_bytes());
+        let extensions_at = 4 + 2 + 32 + 1 + 4 + 2;
+        let extensions_len = message.len() - extensions_at - 2;
+        message[extensions_at..extensions_at + 2]
+            .copy_from_slice(&(extensions_len as u16).to_be_bytes());
+
+        let info = parse_handshake_message(&message);
+        assert_eq!(info.sni.as_deref(), Some("example.com"));
+        assert!(info.has_early_data);
+    }
+
+    #[test]
+    fn test_server_hello_negotiated_version() {
+        let mut body = vec![0x03, 0x01]; // TLS 1.0
+        body.extend_from_slice(&[0u8; 32]); // Random
+        body.push(0x00); // Session ID length
+        body.extend_from_slice(&[0x00, 0x2f]); // TLS_RSA_WITH_AES_128_CBC_SHA
+        body.push(0x00); // Null compression
+
+        let info = analyze_https(&build_handshake_record(0x02, &body), false)
+            .and_then(|https| https.tls_info)
+            .unwrap();
+        assert_eq!(info.server_negotiated_version, Some(TlsVersion::Tls10));
+        assert!(info.uses_deprecated_version());
+    }
 }
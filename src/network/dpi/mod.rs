@@ -1,14 +1,24 @@
-use crate::network::types::{ApplicationProtocol, QuicInfo};
-use log::{debug, warn};
+use crate::network::types::{ApplicationProtocol, Protocol, QuicInfo};
+use crate::signatures::SignatureRegistry;
+use log::{debug, trace};
 
 mod cipher_suites;
 mod dns;
 mod http;
 mod https;
+mod pool;
 mod quic;
 mod ssh;
+mod stream;
 
-pub use cipher_suites::{format_cipher_suite, is_secure_cipher_suite};
+/// Log target for protocol detection and the DPI worker pool
+pub(crate) const LOG_TARGET: &str = "rustnet::dpi";
+
+pub use cipher_suites::{
+    CipherWeakness, cipher_suite_weakness, format_cipher_suite, is_secure_cipher_suite,
+};
+pub(crate) use https::parse_handshake_message;
+pub use pool::{Classified, DpiJob, DpiWorkerPool};
 
 /// Result of DPI analysis
 #[derive(Debug, Clone)]
@@ -16,12 +26,37 @@ pub struct DpiResult {
     pub application: ApplicationProtocol,
 }
 
+/// Run the built-in detectors for a transport payload, then the custom signatures
+pub fn analyze_payload(
+    protocol: Protocol,
+    payload: &[u8],
+    local_port: u16,
+    remote_port: u16,
+    is_outgoing: bool,
+    signatures: &SignatureRegistry,
+) -> Option<DpiResult> {
+    let builtin = match protocol {
+        Protocol::TCP => analyze_tcp_packet(payload, local_port, remote_port, is_outgoing),
+        Protocol::UDP => analyze_udp_packet(payload, local_port, remote_port, is_outgoing),
+        _ => return None,
+    };
+
+    builtin.or_else(|| {
+        if signatures.is_empty() {
+            return None;
+        }
+        signatures
+            .detect(payload, local_port, remote_port)
+            .map(|application| DpiResult { application })
+    })
+}
+
 /// Analyze a TCP packet payload
 pub fn analyze_tcp_packet(
     payload: &[u8],
     local_port: u16,
     remote_port: u16,
-    _is_outgoing: bool,
+    is_outgoing: bool,
 ) -> Option<DpiResult> {
     if payload.is_empty() {
         return None;
@@ -38,7 +73,7 @@ pub fn analyze_tcp_packet(
 
     // 2. Check for TLS/HTTPS (port 443 or TLS handshake)
     if (local_port == 443 || remote_port == 443 || https::is_tls_handshake(payload))
-        && let Some(tls_result) = https::analyze_https(payload)
+        && let Some(tls_result) = https::analyze_https(payload, is_outgoing)
     {
         return Some(DpiResult {
             application: ApplicationProtocol::Https(tls_result),
@@ -47,7 +82,7 @@ pub fn analyze_tcp_packet(
 
     // 3. Check for SSH (port 22 or SSH banner)
     if (local_port == 22 || remote_port == 22 || ssh::is_likely_ssh(payload))
-        && let Some(ssh_result) = ssh::analyze_ssh(payload, _is_outgoing)
+        && let Some(ssh_result) = ssh::analyze_ssh(payload, is_outgoing)
     {
         return Some(DpiResult {
             application: ApplicationProtocol::Ssh(ssh_result),
@@ -83,12 +118,12 @@ pub fn analyze_udp_packet(
     if (local_port == 443 || remote_port == 443) && quic::is_quic_packet(payload) {
         let quic_info = quic::parse_quic_packet(payload);
         if let Some(quic_info) = quic_info {
-            debug!("QUIC packet detected: {:?}", quic_info);
+            trace!(target: LOG_TARGET, "QUIC packet detected: {:?}", quic_info);
             return Some(DpiResult {
                 application: ApplicationProtocol::Quic(Box::new(quic_info)),
             });
         } else {
-            warn!("Failed to parse QUIC packet");
+            debug!(target: LOG_TARGET, "Failed to parse QUIC packet");
             let empty_quic_info = QuicInfo::new(0);
 
             return Some(DpiResult {
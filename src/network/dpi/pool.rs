@@ -0,0 +1,310 @@
+// network/dpi/pool.rs - Worker pool running DPI off the packet path
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use log::{debug, info};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use super::stream::StreamTable;
+use super::{DpiResult, LOG_TARGET, analyze_payload};
+use crate::network::types::Protocol;
+use crate::signatures::SignatureRegistry;
+
+/// Payload of an unclassified packet waiting for DPI
+#[derive(Debug, Clone)]
+pub struct DpiJob {
+    pub connection_key: String,
+    pub protocol: Protocol,
+    pub local_port: u16,
+    pub remote_port: u16,
+    pub is_outgoing: bool,
+    /// Sequence number of the payload, for TCP
+    pub tcp_seq: Option<u32>,
+    pub payload: Vec<u8>,
+    /// When the packet was captured
+    pub instant: Instant,
+}
+
+/// Detector output to be merged into the connection table
+#[derive(Debug, Clone)]
+pub struct Classified {
+    pub connection_key: String,
+    pub result: DpiResult,
+    /// Capture time of the packet that completed the classification
+    pub instant: Instant,
+}
+
+/// Fixed set of DPI workers. Jobs are routed by connection key so all payloads
+/// of a connection are analysed in order by the same worker, which keeps the
+/// QUIC/TLS reassembly state consistent when results are merged and lets each
+/// worker put split TCP payloads back together.
+pub struct DpiWorkerPool {
+    workers: Vec<Sender<DpiJob>>,
+    results: Receiver<Classified>,
+    dropped: AtomicU64,
+}
+
+impl DpiWorkerPool {
+    /// Spawn `workers` threads, each with a queue of `queue_capacity` jobs
+    pub fn new(workers: usize, queue_capacity: usize, signatures: Arc<SignatureRegistry>) -> Self {
+        let (pool, job_queues, result_tx) = Self::unstarted(workers, queue_capacity);
+
+        for (id, jobs) in job_queues.into_iter().enumerate() {
+            let result_tx = result_tx.clone();
+            let signatures = Arc::clone(&signatures);
+            thread::spawn(move || {
+                info!(target: LOG_TARGET, "DPI worker {} started", id);
+                let mut streams = StreamTable::default();
+                // Exits once the pool (and with it every job sender) is dropped
+                for job in jobs {
+                    if let Some(classified) = run_job(job, &signatures, &mut streams)
+                        && result_tx.send(classified).is_err()
+                    {
+                        break;
+                    }
+                }
+                debug!(target: LOG_TARGET, "DPI worker {} exiting", id);
+            });
+        }
+
+        pool
+    }
+
+    /// Build the pool channels without spawning workers
+    fn unstarted(
+        workers: usize,
+        queue_capacity: usize,
+    ) -> (Self, Vec<Receiver<DpiJob>>, Sender<Classified>) {
+        let (result_tx, results) = channel::unbounded();
+        let (senders, receivers) = (0..workers.max(1))
+            .map(|_| channel::bounded(queue_capacity))
+            .unzip();
+
+        let pool = Self {
+            workers: senders,
+            results,
+            dropped: AtomicU64::new(0),
+        };
+        (pool, receivers, result_tx)
+    }
+
+    /// Queue a job without blocking. Returns false if the worker was busy and
+    /// the job was dropped; the packet itself has already been accounted for.
+    pub fn submit(&self, job: DpiJob) -> bool {
+        let worker = self.route(&job.connection_key);
+        match self.workers[worker].try_send(job) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Results ready to be merged
+    pub fn results(&self) -> &Receiver<Classified> {
+        &self.results
+    }
+
+    /// Number of jobs dropped because a worker queue was full
+    pub fn dropped_jobs(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn route(&self, connection_key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        connection_key.hash(&mut hasher);
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+}
+
+fn run_job(
+    job: DpiJob,
+    signatures: &SignatureRegistry,
+    streams: &mut StreamTable,
+) -> Option<Classified> {
+    let analyze = |payload: &[u8]| {
+        analyze_payload(
+            job.protocol,
+            payload,
+            job.local_port,
+            job.remote_port,
+            job.is_outgoing,
+            signatures,
+        )
+    };
+
+    let result = match job.tcp_seq {
+        Some(seq) => {
+            let key = &job.connection_key;
+            let result = analyze(streams.payload(key, job.is_outgoing, seq, &job.payload)?);
+            streams.record(key, job.is_outgoing, seq, &job.payload, result.is_some());
+            result?
+        }
+        None => analyze(&job.payload)?,
+    };
+    Some(Classified {
+        connection_key: job.connection_key,
+        result,
+        instant: job.instant,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::types::ApplicationProtocol;
+    use std::time::Duration;
+
+    fn http_job(key: &str, path: &str) -> DpiJob {
+        DpiJob {
+            connection_key: key.to_string(),
+            protocol: Protocol::TCP,
+            local_port: 50000,
+            remote_port: 80,
+            is_outgoing: true,
+            tcp_seq: None,
+            payload: format!("GET {} HTTP/1.1\r\nHost: example.com\r\n\r\n", path).into_bytes(),
+            instant: Instant::now(),
+        }
+    }
+
+    fn tcp_segment(remote_port: u16, seq: u32, payload: &[u8]) -> DpiJob {
+        DpiJob {
+            connection_key: "TCP:10.0.0.1:50000-TCP:10.0.0.2:443".to_string(),
+            protocol: Protocol::TCP,
+            local_port: 50000,
+            remote_port,
+            is_outgoing: true,
+            tcp_seq: Some(seq),
+            payload: payload.to_vec(),
+            instant: Instant::now(),
+        }
+    }
+
+    /// TLS record holding a ClientHello with only an SNI extension
+    fn client_hello(sni: &str) -> Vec<u8> {
+        let mut server_name = vec![0x00]; // host_name
+        server_name.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+        server_name.extend_from_slice(sni.as_bytes());
+        let mut extensions = vec![0x00, 0x00]; // server_name
+        extensions.extend_from_slice(&(server_name.len() as u16 + 2).to_be_bytes());
+        extensions.extend_from_slice(&(server_name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&server_name);
+
+        let mut body = vec![0x03, 0x03]; // TLS 1.2
+        body.extend_from_slice(&[0u8; 32]); // Random
+        body.push(0x00); // Session ID length
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // One cipher suite
+        body.extend_from_slice(&[0x01, 0x00]); // Null compression
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(body.len() as u16 + 4).to_be_bytes());
+        record.extend_from_slice(&[0x01, 0x00]);
+        record.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        record.extend_from_slice(&body);
+        record
+    }
+
+    #[test]
+    fn test_client_hello_split_before_record_header_is_reassembled() {
+        let signatures = SignatureRegistry::new();
+        let mut streams = StreamTable::default();
+        let hello = client_hello("example.com");
+
+        // Three bytes can't be told apart from anything else
+        assert!(
+            run_job(
+                tcp_segment(443, 1000, &hello[..3]),
+                &signatures,
+                &mut streams
+            )
+            .is_none()
+        );
+        let classified = run_job(
+            tcp_segment(443, 1003, &hello[3..]),
+            &signatures,
+            &mut streams,
+        )
+        .unwrap();
+        match classified.result.application {
+            ApplicationProtocol::Https(info) => {
+                assert_eq!(info.tls_info.unwrap().sni.as_deref(), Some("example.com"));
+            }
+            other => panic!("unexpected protocol {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_http_request_split_out_of_order_is_reassembled() {
+        let signatures = SignatureRegistry::new();
+        let mut streams = StreamTable::default();
+        let request = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+        // The rest of the request overtakes its first two bytes
+        assert!(run_job(tcp_segment(80, 2, &request[2..]), &signatures, &mut streams).is_none());
+        let classified =
+            run_job(tcp_segment(80, 0, &request[..2]), &signatures, &mut streams).unwrap();
+        match classified.result.application {
+            ApplicationProtocol::Http(info) => {
+                assert_eq!(info.host.as_deref(), Some("example.com"));
+                assert_eq!(info.path.as_deref(), Some("/index.html"));
+            }
+            other => panic!("unexpected protocol {:?}", other),
+        }
+
+        // Identified: later segments are analysed on their own again
+        let response = b"HTTP/1.1 200 OK\r\n\r\n";
+        assert!(run_job(tcp_segment(80, 100, response), &signatures, &mut streams).is_some());
+    }
+
+    #[test]
+    fn test_jobs_for_a_connection_go_to_one_worker() {
+        let (pool, _queues, _results) = DpiWorkerPool::unstarted(4, 16);
+
+        for i in 0..100 {
+            let key = format!("TCP:10.0.0.1:{}-TCP:10.0.0.2:443", 40000 + i);
+            let worker = pool.route(&key);
+            assert!(worker < 4);
+            assert_eq!(pool.route(&key), worker);
+        }
+    }
+
+    #[test]
+    fn test_full_queue_drops_jobs() {
+        let (pool, queues, _results) = DpiWorkerPool::unstarted(1, 2);
+
+        assert!(pool.submit(http_job("a", "/1")));
+        assert!(pool.submit(http_job("a", "/2")));
+        assert!(!pool.submit(http_job("a", "/3")));
+        assert_eq!(pool.dropped_jobs(), 1);
+        assert_eq!(queues[0].len(), 2);
+    }
+
+    #[test]
+    fn test_results_preserve_per_connection_order() {
+        let pool = DpiWorkerPool::new(2, 64, Arc::new(SignatureRegistry::new()));
+        for i in 0..10 {
+            assert!(pool.submit(http_job("conn", &format!("/{}", i))));
+        }
+
+        let paths: Vec<String> = (0..10)
+            .map(|_| {
+                let classified = pool.results().recv_timeout(Duration::from_secs(5)).unwrap();
+                assert_eq!(classified.connection_key, "conn");
+                match classified.result.application {
+                    ApplicationProtocol::Http(info) => info.path.unwrap(),
+                    other => panic!("unexpected protocol {:?}", other),
+                }
+            })
+            .collect();
+
+        let expected: Vec<String> = (0..10).map(|i| format!("/{}", i)).collect();
+        assert_eq!(paths, expected);
+    }
+}
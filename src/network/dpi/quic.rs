@@ -1,9 +1,10 @@
+use super::LOG_TARGET;
 use crate::network::types::{
     CryptoFrameReassembler, QuicConnectionState, QuicInfo, QuicPacketType, TlsInfo, TlsVersion,
 };
 use aes::Aes128;
 use aes::cipher::{BlockEncrypt, KeyInit};
-use log::{debug, warn};
+use log::debug;
 use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey};
 use ring::{aead, hkdf};
 
@@ -19,10 +20,13 @@ const INITIAL_SALT_V2: &[u8] = &[
     0xf9, 0xbd, 0x2e, 0xd9,
 ];
 
+// Longest connection ID QUIC v1 allows (RFC 9000, section 17.2)
+const MAX_CONNECTION_ID_LEN: usize = 20;
+
 /// Main entry point for QUIC packet parsing
 pub fn parse_quic_packet(payload: &[u8]) -> Option<QuicInfo> {
     if payload.is_empty() {
-        debug!("QUIC: Empty payload");
+        debug!(target: LOG_TARGET, "QUIC: Empty payload");
         return None;
     }
 
@@ -30,6 +34,7 @@ pub fn parse_quic_packet(payload: &[u8]) -> Option<QuicInfo> {
     let is_long_header = (first_byte & 0x80) != 0;
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsing packet - first_byte=0x{:02x}, is_long_header={}, payload_len={}",
         first_byte,
         is_long_header,
@@ -69,6 +74,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     // Destination Connection ID
     if offset >= payload.len() {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Payload too short to read DCID length at offset {}",
             offset
         );
@@ -78,12 +84,14 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     offset += 1;
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsing long header packet - version=0x{:08x}, DCID length={}",
         version, dcid_len
     );
 
     if offset + dcid_len > payload.len() {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Payload too short for DCID, need {} bytes, have {}",
             offset + dcid_len,
             payload.len()
@@ -99,6 +107,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     // Source Connection ID
     if offset >= payload.len() {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Payload too short for SCID length at offset {}",
             offset
         );
@@ -109,13 +118,14 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
 
     if offset + scid_len > payload.len() {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Payload too short for SCID, need {} bytes, have {}",
             offset + scid_len,
             payload.len()
         );
         return None;
     }
-    // offset += scid_len; // No longer needed as we don't parse further
+    quic_info.source_connection_id = payload[offset..offset + scid_len].to_vec();
 
     // Set connection state based on packet type
     quic_info.connection_state = match packet_type {
@@ -131,6 +141,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     // This includes looking for unencrypted TLS extensions or other plaintext data
     if let Some(tls_info) = try_parse_unencrypted_crypto_frames(payload) {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Found TLS info in unencrypted packet data: SNI={:?}, ALPN={:?}",
             tls_info.sni, tls_info.alpn
         );
@@ -141,11 +152,14 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     // Focus on Client packets as they contain the SNI information
     match packet_type {
         QuicPacketType::Initial if dcid_len > 0 => {
-            debug!("QUIC: Processing Initial packet with DCID len={}", dcid_len);
+            debug!(
+                target: LOG_TARGET,
+                "QUIC: Processing Initial packet with DCID len={}", dcid_len
+            );
             // Try to decrypt as client packet first (most likely to have SNI)
             if let Some(decrypted_payload) = decrypt_client_initial_packet(payload, &dcid, version)
             {
-                debug!("QUIC: Successfully decrypted Client Initial packet");
+                debug!(target: LOG_TARGET, "QUIC: Successfully decrypted Client Initial packet");
                 // Extract TLS info from decrypted payload using reassembly
                 if let Some(tls_info) =
                     process_crypto_frames_in_packet(&decrypted_payload, &mut quic_info)
@@ -155,6 +169,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
                     if !dcid.is_empty() {
                         quic_info.connection_id_hex = Some(connection_id_to_hex(&dcid));
                         debug!(
+                            target: LOG_TARGET,
                             "QUIC: Marking Client Initial packet with DCID {} for connection tracking",
                             connection_id_to_hex(&dcid)
                         );
@@ -163,7 +178,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
             } else if let Some(decrypted_payload) =
                 decrypt_server_initial_packet(payload, &dcid, version)
             {
-                debug!("QUIC: Successfully decrypted Server Initial packet");
+                debug!(target: LOG_TARGET, "QUIC: Successfully decrypted Server Initial packet");
                 // Server Initial rarely has SNI but may have ALPN or other TLS info
                 if let Some(tls_info) =
                     process_crypto_frames_in_packet(&decrypted_payload, &mut quic_info)
@@ -172,9 +187,11 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
                 }
             } else {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Failed to decrypt Initial packet (tried both client and server keys)"
                 );
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Packet details - DCID={:02x?}, version=0x{:08x}, payload_len={}",
                     dcid,
                     version,
@@ -184,6 +201,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
                 // Try to extract any unencrypted TLS information
                 if let Some(tls_info) = try_parse_unencrypted_crypto_frames(payload) {
                     debug!(
+                        target: LOG_TARGET,
                         "QUIC: Found TLS info in unencrypted parts: SNI={:?}",
                         tls_info.sni
                     );
@@ -192,11 +210,15 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
             }
         }
         QuicPacketType::Handshake if dcid_len > 0 => {
-            debug!("QUIC: Processing Handshake packet - these often contain ClientHello");
+            debug!(
+                target: LOG_TARGET,
+                "QUIC: Processing Handshake packet - these often contain ClientHello"
+            );
             // Handshake packets may also contain TLS ClientHello
             // Modern QUIC often puts the actual TLS handshake in Handshake packets, not Initial
             if let Some(tls_info) = try_parse_unencrypted_crypto_frames(payload) {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Found TLS info in Handshake packet: SNI={:?}",
                     tls_info.sni
                 );
@@ -205,9 +227,11 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
         }
         QuicPacketType::Initial => {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Initial packet has zero-length DCID - this is normal for some QUIC implementations"
             );
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Packet details - version=0x{:08x}, payload_len={}, packet_type={:?}",
                 version,
                 payload.len(),
@@ -218,6 +242,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
             // We should still try to extract what we can from unencrypted parts
             if let Some(tls_info) = try_parse_unencrypted_crypto_frames(payload) {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Extracted TLS info from unencrypted frames: SNI={:?}",
                     tls_info.sni
                 );
@@ -226,6 +251,7 @@ fn parse_long_header_packet(payload: &[u8]) -> Option<QuicInfo> {
         }
         _ => {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Packet type {:?} not processed for TLS extraction",
                 packet_type
             );
@@ -246,13 +272,10 @@ fn parse_short_header_packet(payload: &[u8]) -> Option<QuicInfo> {
     quic_info.packet_type = QuicPacketType::OneRtt;
     quic_info.connection_state = QuicConnectionState::Connected;
 
-    // For short header, connection ID length is not in the packet
-    // We'll use common sizes (8 bytes) as a heuristic
-    let dcid = if payload.len() >= 9 {
-        payload[1..9].to_vec()
-    } else {
-        payload[1..].to_vec()
-    };
+    // For short header, connection ID length is not in the packet. Keep
+    // as many bytes as the longest ID could have; the connection table
+    // matches them against the lengths of IDs seen in long headers.
+    let dcid = payload[1..payload.len().min(1 + MAX_CONNECTION_ID_LEN)].to_vec();
 
     quic_info.connection_id = dcid.clone();
     // Short header packets are data packets - don't use for connection tracking
@@ -276,11 +299,12 @@ fn decrypt_client_initial_packet(packet: &[u8], dcid: &[u8], version: u32) -> Op
     // Derive client initial secret
     let mut client_secret = [0u8; 32];
     if !derive_secret(&initial_secret, b"client in", &mut client_secret) {
-        debug!("QUIC: Failed to derive client initial secret");
+        debug!(target: LOG_TARGET, "QUIC: Failed to derive client initial secret");
         return None;
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Attempting client Initial decryption with DCID len={}",
         dcid.len()
     );
@@ -288,7 +312,7 @@ fn decrypt_client_initial_packet(packet: &[u8], dcid: &[u8], version: u32) -> Op
     // Try to decrypt as a client Initial packet
     let result = try_decrypt_initial_with_secret(packet, &client_secret, version);
     if result.is_none() {
-        debug!("QUIC: Client Initial decryption failed");
+        debug!(target: LOG_TARGET, "QUIC: Client Initial decryption failed");
     }
     result
 }
@@ -308,11 +332,12 @@ fn decrypt_server_initial_packet(packet: &[u8], dcid: &[u8], version: u32) -> Op
     // Derive server initial secret
     let mut server_secret = [0u8; 32];
     if !derive_secret(&initial_secret, b"server in", &mut server_secret) {
-        debug!("QUIC: Failed to derive server initial secret");
+        debug!(target: LOG_TARGET, "QUIC: Failed to derive server initial secret");
         return None;
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Attempting server Initial decryption with DCID len={}",
         dcid.len()
     );
@@ -320,7 +345,7 @@ fn decrypt_server_initial_packet(packet: &[u8], dcid: &[u8], version: u32) -> Op
     // Try to decrypt as a server Initial packet
     let result = try_decrypt_initial_with_secret(packet, &server_secret, version);
     if result.is_none() {
-        debug!("QUIC: Server Initial decryption failed");
+        debug!(target: LOG_TARGET, "QUIC: Server Initial decryption failed");
     }
     result
 }
@@ -336,7 +361,7 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
         || !derive_packet_protection_iv(secret, &mut iv, version)
         || !derive_header_protection_key(secret, &mut hp_key, version)
     {
-        debug!("QUIC: Failed to derive keys from secret");
+        debug!(target: LOG_TARGET, "QUIC: Failed to derive keys from secret");
         return None;
     }
 
@@ -345,14 +370,14 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
 
     // Skip DCID
     if offset >= packet.len() {
-        debug!("QUIC: Packet too short for DCID length field");
+        debug!(target: LOG_TARGET, "QUIC: Packet too short for DCID length field");
         return None;
     }
     let dcid_len = packet[offset] as usize;
     offset += 1 + dcid_len;
 
     if offset >= packet.len() {
-        debug!("QUIC: Packet too short after DCID");
+        debug!(target: LOG_TARGET, "QUIC: Packet too short after DCID");
         return None;
     }
 
@@ -361,21 +386,24 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
     offset += 1 + scid_len;
 
     if offset >= packet.len() {
-        debug!("QUIC: Packet too short after SCID");
+        debug!(target: LOG_TARGET, "QUIC: Packet too short after SCID");
         return None;
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsed connection IDs - DCID len={}, SCID len={}, offset now={}",
         dcid_len, scid_len, offset
     );
 
     // Parse token length (for Initial packets)
     let (token_len, bytes_read) = parse_variable_length_int(&packet[offset..])?;
-    offset += bytes_read + token_len as usize;
+    offset = offset
+        .checked_add(bytes_read)?
+        .checked_add(usize::try_from(token_len).ok()?)?;
 
     // Parse packet length
-    let (packet_payload_length, bytes_read) = parse_variable_length_int(&packet[offset..])?;
+    let (packet_payload_length, bytes_read) = parse_variable_length_int(packet.get(offset..)?)?;
     offset += bytes_read;
 
     // Now offset points to the packet number field
@@ -384,7 +412,7 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
     // Sample is taken 4 bytes after the packet number offset
     let sample_offset = pn_offset + 4;
     if sample_offset + 16 > packet.len() {
-        debug!("QUIC: Not enough data for header protection sample");
+        debug!(target: LOG_TARGET, "QUIC: Not enough data for header protection sample");
         return None;
     }
 
@@ -424,16 +452,19 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
 
     // Decrypt the payload
     let ciphertext_offset = pn_offset + pn_length;
-    let ciphertext_len = packet_payload_length as usize - pn_length;
+    let Some(ciphertext_len) = (packet_payload_length as usize).checked_sub(pn_length) else {
+        debug!(target: LOG_TARGET, "QUIC: Packet length shorter than packet number");
+        return None;
+    };
 
-    if ciphertext_offset + ciphertext_len > packet.len() {
-        debug!("QUIC: Ciphertext extends beyond packet");
+    if ciphertext_len > packet.len() - ciphertext_offset {
+        debug!(target: LOG_TARGET, "QUIC: Ciphertext extends beyond packet");
         return None;
     }
 
     // The ciphertext includes the authentication tag (last 16 bytes)
     if ciphertext_len < 16 {
-        debug!("QUIC: Ciphertext too short for auth tag");
+        debug!(target: LOG_TARGET, "QUIC: Ciphertext too short for auth tag");
         return None;
     }
 
@@ -446,7 +477,7 @@ fn try_decrypt_initial_with_secret(packet: &[u8], secret: &[u8], version: u32) -
             Some(plaintext)
         }
         Err(e) => {
-            debug!("QUIC: AEAD decryption failed: {:?}", e);
+            debug!(target: LOG_TARGET, "QUIC: AEAD decryption failed: {:?}", e);
             None
         }
     }
@@ -477,12 +508,12 @@ pub fn process_crypto_frames_in_packet(
 
             0x01 => {
                 // PING frame
-                debug!("QUIC: Found PING frame");
+                debug!(target: LOG_TARGET, "QUIC: Found PING frame");
             }
 
             0x02 | 0x03 => {
                 // ACK or ACK_ECN frame
-                debug!("QUIC: Found ACK frame");
+                debug!(target: LOG_TARGET, "QUIC: Found ACK frame");
 
                 // Parse and skip ACK frame fields
                 let (_, bytes_read) = parse_variable_length_int(&payload[offset..])?;
@@ -531,7 +562,7 @@ pub fn process_crypto_frames_in_packet(
 
             0x06 => {
                 // CRYPTO frame - this is what we're looking for!
-                debug!("QUIC: Found CRYPTO frame");
+                debug!(target: LOG_TARGET, "QUIC: Found CRYPTO frame");
                 found_crypto_frames = true;
                 quic_info.has_crypto_frame = true;
 
@@ -542,6 +573,7 @@ pub fn process_crypto_frames_in_packet(
                 offset += bytes_read;
 
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: CRYPTO frame - offset={}, length={}",
                     crypto_offset, crypto_length
                 );
@@ -555,7 +587,7 @@ pub fn process_crypto_frames_in_packet(
                     if let Some(reassembler) = &mut quic_info.crypto_reassembler
                         && let Err(e) = reassembler.add_fragment(crypto_offset, crypto_data)
                     {
-                        warn!("QUIC: Failed to add CRYPTO fragment: {}", e);
+                        debug!(target: LOG_TARGET, "QUIC: Failed to add CRYPTO fragment: {}", e);
                     }
                 }
 
@@ -674,6 +706,7 @@ pub fn process_crypto_frames_in_packet(
                 };
 
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Detected CONNECTION_CLOSE frame type 0x{:02x}, error_code: {}, reason: {:?}",
                     frame_type_byte, error_code, reason
                 );
@@ -681,11 +714,12 @@ pub fn process_crypto_frames_in_packet(
 
             0x1e => {
                 // HANDSHAKE_DONE frame
-                debug!("QUIC: Found HANDSHAKE_DONE frame");
+                debug!(target: LOG_TARGET, "QUIC: Found HANDSHAKE_DONE frame");
             }
 
             _ => {
-                warn!(
+                debug!(
+                    target: LOG_TARGET,
                     "QUIC: Unknown frame type 0x{:02x}, stopping",
                     frame_type_byte
                 );
@@ -694,7 +728,7 @@ pub fn process_crypto_frames_in_packet(
         }
 
         if offset > payload.len() {
-            warn!("QUIC: Frame parsing exceeded payload length");
+            debug!(target: LOG_TARGET, "QUIC: Frame parsing exceeded payload length");
             break;
         }
     }
@@ -704,6 +738,7 @@ pub fn process_crypto_frames_in_packet(
         && let Some(tls_info) = try_extract_tls_from_reassembler(reassembler)
     {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Successfully extracted TLS info: SNI={:?}",
             tls_info.sni
         );
@@ -723,9 +758,19 @@ pub fn try_extract_tls_from_reassembler(
         return Some(tls_info.clone());
     }
 
-    // Try to reassemble and parse contiguous data first
-    if let Some(reassembled) = reassembler.get_contiguous_data() {
+    // Try to reassemble and parse contiguous data first. Once the ClientHello's
+    // handshake header is in, nothing past the end of the message is needed.
+    let hello_end = reassembler
+        .get_contiguous_data_up_to(4)
+        .filter(|header| header.len() == 4 && header[0] == 0x01)
+        .map(|header| 4 + u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64);
+    let contiguous = match hello_end {
+        Some(end) => reassembler.get_contiguous_data_up_to(end),
+        None => reassembler.get_contiguous_data(),
+    };
+    if let Some(reassembled) = contiguous {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Attempting to parse {} bytes of contiguous crypto data",
             reassembled.len()
         );
@@ -737,13 +782,17 @@ pub fn try_extract_tls_from_reassembler(
             if let Some(tls_info) = parse_partial_tls_handshake(&reassembled) {
                 // Check if we have the essential info (SNI and ALPN)
                 if tls_info.sni.is_some() || !tls_info.alpn.is_empty() {
-                    debug!("QUIC: Found complete TLS info from contiguous data");
+                    debug!(
+                        target: LOG_TARGET,
+                        "QUIC: Found complete TLS info from contiguous data"
+                    );
                     reassembler.set_complete_tls_info(tls_info.clone());
                     return Some(tls_info);
                 }
             }
         } else {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Only {} contiguous bytes available, waiting for more data before parsing",
                 reassembled.len()
             );
@@ -753,9 +802,13 @@ pub fn try_extract_tls_from_reassembler(
     // If contiguous parsing failed, try parsing individual fragments
     // This can help when we have complete TLS records in separate fragments
     // Only parse fragments that start with proper TLS structures to avoid partial data
-    debug!("QUIC: Trying to parse individual crypto fragments with proper TLS headers");
+    debug!(
+        target: LOG_TARGET,
+        "QUIC: Trying to parse individual crypto fragments with proper TLS headers"
+    );
     for (&offset, fragment_data) in reassembler.get_fragments() {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Trying fragment at offset {} with {} bytes",
             offset,
             fragment_data.len()
@@ -769,6 +822,7 @@ pub fn try_extract_tls_from_reassembler(
             && (tls_info.sni.is_some() || !tls_info.alpn.is_empty())
         {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Found TLS info from individual fragment at offset {}",
                 offset
             );
@@ -783,6 +837,7 @@ pub fn try_extract_tls_from_reassembler(
             && (tls_info.sni.is_some() || !tls_info.alpn.is_empty())
         {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Found TLS info from pattern matching in fragment at offset {}",
                 offset
             );
@@ -790,6 +845,7 @@ pub fn try_extract_tls_from_reassembler(
             return Some(tls_info);
         } else {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Skipping fragment at offset {} - doesn't start with TLS header",
                 offset
             );
@@ -802,6 +858,7 @@ pub fn try_extract_tls_from_reassembler(
 
     if total_fragment_size >= 200 {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Have {} total bytes in fragments, attempting reconstruction",
             total_fragment_size
         );
@@ -810,25 +867,26 @@ pub fn try_extract_tls_from_reassembler(
         if let Some(sni) = try_reconstruct_sni_from_fragments(reassembler) {
             let mut tls_info = TlsInfo::new();
             tls_info.sni = Some(sni);
-            debug!("QUIC: Reconstructed SNI from fragmented data");
+            debug!(target: LOG_TARGET, "QUIC: Reconstructed SNI from fragmented data");
             reassembler.set_complete_tls_info(tls_info.clone());
             return Some(tls_info);
         }
     } else {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Only {} total bytes in fragments, not enough for reliable SNI extraction",
             total_fragment_size
         );
     }
 
-    debug!("QUIC: No TLS info could be extracted from reassembler");
+    debug!(target: LOG_TARGET, "QUIC: No TLS info could be extracted from reassembler");
     None
 }
 
 /// Parse a TLS handshake from reassembled data
 fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     if data.len() < 4 {
-        debug!("QUIC: TLS handshake data too short: {} bytes", data.len());
+        debug!(target: LOG_TARGET, "QUIC: TLS handshake data too short: {} bytes", data.len());
         return None;
     }
 
@@ -836,6 +894,7 @@ fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     let handshake_length = u32::from_be_bytes([0, data[1], data[2], data[3]]) as usize;
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: TLS handshake type=0x{:02x}, declared_length={}, available_data={}",
         handshake_type,
         handshake_length,
@@ -850,6 +909,7 @@ fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     // Sanity check the handshake length
     if handshake_length > 65536 {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Handshake length {} seems too large, skipping",
             handshake_length
         );
@@ -859,16 +919,17 @@ fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     match handshake_type {
         0x01 => {
             // Client Hello
-            debug!("QUIC: Parsing ClientHello with {} bytes", parse_length);
+            debug!(target: LOG_TARGET, "QUIC: Parsing ClientHello with {} bytes", parse_length);
             parse_partial_client_hello(&available_data[..parse_length], &mut info);
         }
         0x02 => {
             // Server Hello
-            debug!("QUIC: Parsing ServerHello with {} bytes", parse_length);
+            debug!(target: LOG_TARGET, "QUIC: Parsing ServerHello with {} bytes", parse_length);
             parse_partial_server_hello(&available_data[..parse_length], &mut info);
         }
         _ => {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Unknown/unsupported handshake type: 0x{:02x}",
                 handshake_type
             );
@@ -877,6 +938,7 @@ fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsed TLS info - SNI={:?}, ALPN={:?}, version={:?}",
         info.sni, info.alpn, info.version
     );
@@ -884,17 +946,18 @@ fn parse_partial_tls_handshake(data: &[u8]) -> Option<TlsInfo> {
     if info.sni.is_some() || !info.alpn.is_empty() || info.version.is_some() {
         Some(info)
     } else {
-        debug!("QUIC: No useful TLS info extracted");
+        debug!(target: LOG_TARGET, "QUIC: No useful TLS info extracted");
         None
     }
 }
 
 /// Parse a partial Client Hello
 fn parse_partial_client_hello(data: &[u8], info: &mut TlsInfo) {
-    debug!("QUIC: Parsing ClientHello with {} bytes", data.len());
+    debug!(target: LOG_TARGET, "QUIC: Parsing ClientHello with {} bytes", data.len());
 
     if data.len() < 34 {
         debug!(
+            target: LOG_TARGET,
             "QUIC: ClientHello too short: {} bytes (need at least 34)",
             data.len()
         );
@@ -904,6 +967,7 @@ fn parse_partial_client_hello(data: &[u8], info: &mut TlsInfo) {
     // Skip version (2) + random (32)
     let mut offset = 34;
     debug!(
+        target: LOG_TARGET,
         "QUIC: ClientHello - skipping version and random, offset now={}",
         offset
     );
@@ -940,6 +1004,7 @@ fn parse_partial_client_hello(data: &[u8], info: &mut TlsInfo) {
     offset += 2;
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: ClientHello extensions - declared_len={}, available_data={}",
         extensions_len,
         data.len() - offset
@@ -947,10 +1012,10 @@ fn parse_partial_client_hello(data: &[u8], info: &mut TlsInfo) {
 
     let available_ext_len = (data.len() - offset).min(extensions_len);
     if available_ext_len > 0 {
-        debug!("QUIC: Parsing {} bytes of extensions", available_ext_len);
+        debug!(target: LOG_TARGET, "QUIC: Parsing {} bytes of extensions", available_ext_len);
         parse_tls_extensions(&data[offset..offset + available_ext_len], info, true);
     } else {
-        debug!("QUIC: No extensions data available");
+        debug!(target: LOG_TARGET, "QUIC: No extensions data available");
     }
 }
 
@@ -1003,6 +1068,7 @@ fn parse_partial_server_hello(data: &[u8], info: &mut TlsInfo) {
 fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
     let mut offset = 0;
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsing {} bytes of TLS extensions (is_client={})",
         data.len(),
         is_client
@@ -1013,12 +1079,14 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
         let ext_len = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
 
         debug!(
+            target: LOG_TARGET,
             "QUIC: Extension type=0x{:04x}, length={}",
             ext_type, ext_len
         );
 
         if offset + 4 + ext_len > data.len() {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Extension data extends beyond available data (need {} bytes, have {})",
                 offset + 4 + ext_len,
                 data.len()
@@ -1028,6 +1096,7 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
             let available_ext_len = (data.len() - offset - 4).min(ext_len);
             if available_ext_len > 0 {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Attempting to parse {} bytes of partial extension data",
                     available_ext_len
                 );
@@ -1038,34 +1107,49 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
                     0x0000 if is_client => {
                         // SNI - try partial parsing
                         debug!(
+                            target: LOG_TARGET,
                             "QUIC: Found partial SNI extension with {} bytes (declared {})",
                             available_ext_len, ext_len
                         );
                         if let Some(sni) = parse_sni_extension(ext_data) {
-                            debug!("QUIC: Successfully parsed SNI from partial data: {}", sni);
+                            debug!(
+                                target: LOG_TARGET,
+                                "QUIC: Successfully parsed SNI from partial data: {}", sni
+                            );
                             info.sni = Some(sni);
                         } else {
-                            debug!("QUIC: Failed to parse partial SNI extension");
+                            debug!(
+                                target: LOG_TARGET,
+                                "QUIC: Failed to parse partial SNI extension"
+                            );
                         }
                     }
                     0x0010 => {
                         // ALPN - try partial parsing
                         debug!(
+                            target: LOG_TARGET,
                             "QUIC: Found partial ALPN extension with {} bytes (declared {})",
                             available_ext_len, ext_len
                         );
                         if let Some(alpn) = parse_alpn_extension(ext_data) {
                             debug!(
+                                target: LOG_TARGET,
                                 "QUIC: Successfully parsed ALPN from partial data: {:?}",
                                 alpn
                             );
                             info.alpn = alpn;
                         } else {
-                            debug!("QUIC: Failed to parse partial ALPN extension");
+                            debug!(
+                                target: LOG_TARGET,
+                                "QUIC: Failed to parse partial ALPN extension"
+                            );
                         }
                     }
                     _ => {
-                        debug!("QUIC: Skipping partial extension type 0x{:04x}", ext_type);
+                        debug!(
+                            target: LOG_TARGET,
+                            "QUIC: Skipping partial extension type 0x{:04x}", ext_type
+                        );
                     }
                 }
             }
@@ -1077,37 +1161,46 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
         match ext_type {
             0x0000 if is_client => {
                 // SNI
-                debug!("QUIC: Found SNI extension with {} bytes", ext_len);
+                debug!(target: LOG_TARGET, "QUIC: Found SNI extension with {} bytes", ext_len);
                 if let Some(sni) = parse_sni_extension(ext_data) {
-                    debug!("QUIC: Successfully parsed SNI: {}", sni);
+                    debug!(target: LOG_TARGET, "QUIC: Successfully parsed SNI: {}", sni);
                     info.sni = Some(sni);
                 } else {
-                    debug!("QUIC: Failed to parse SNI extension");
+                    debug!(target: LOG_TARGET, "QUIC: Failed to parse SNI extension");
                 }
             }
             0x0010 => {
                 // ALPN
-                debug!("QUIC: Found ALPN extension with {} bytes", ext_len);
+                debug!(target: LOG_TARGET, "QUIC: Found ALPN extension with {} bytes", ext_len);
                 if let Some(alpn) = parse_alpn_extension(ext_data) {
-                    debug!("QUIC: Successfully parsed ALPN: {:?}", alpn);
+                    debug!(target: LOG_TARGET, "QUIC: Successfully parsed ALPN: {:?}", alpn);
                     info.alpn = alpn;
                 } else {
-                    debug!("QUIC: Failed to parse ALPN extension");
+                    debug!(target: LOG_TARGET, "QUIC: Failed to parse ALPN extension");
                 }
             }
             0x002b => {
                 // Supported Versions
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Found Supported Versions extension with {} bytes",
                     ext_len
                 );
                 if let Some(version) = parse_supported_versions(ext_data, is_client) {
-                    debug!("QUIC: Successfully parsed version: {:?}", version);
+                    debug!(target: LOG_TARGET, "QUIC: Successfully parsed version: {:?}", version);
                     info.version = Some(version);
                 }
             }
+            0x002a if is_client => {
+                // Early data: the client is sending 0-RTT packets
+                debug!(target: LOG_TARGET, "QUIC: Found early_data extension");
+                info.has_early_data = true;
+            }
             _ => {
-                debug!("QUIC: Skipping unknown extension type 0x{:04x}", ext_type);
+                debug!(
+                    target: LOG_TARGET,
+                    "QUIC: Skipping unknown extension type 0x{:04x}", ext_type
+                );
             }
         }
 
@@ -1115,6 +1208,7 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Finished parsing extensions - found SNI={:?}, ALPN={:?}",
         info.sni, info.alpn
     );
@@ -1123,6 +1217,7 @@ fn parse_tls_extensions(data: &[u8], info: &mut TlsInfo, is_client: bool) {
 /// Parse SNI extension
 fn parse_sni_extension(data: &[u8]) -> Option<String> {
     debug!(
+        target: LOG_TARGET,
         "QUIC: Parsing SNI extension with {} bytes: {:02x?}",
         data.len(),
         &data[..data.len().min(20)]
@@ -1130,6 +1225,7 @@ fn parse_sni_extension(data: &[u8]) -> Option<String> {
 
     if data.len() < 5 {
         debug!(
+            target: LOG_TARGET,
             "QUIC: SNI extension too short: {} bytes (need at least 5)",
             data.len()
         );
@@ -1138,31 +1234,31 @@ fn parse_sni_extension(data: &[u8]) -> Option<String> {
 
     // Parse list length
     let list_len = u16::from_be_bytes([data[0], data[1]]) as usize;
-    debug!("QUIC: SNI list length: {}", list_len);
+    debug!(target: LOG_TARGET, "QUIC: SNI list length: {}", list_len);
 
     // Skip list length (2 bytes) and check type (1 byte)
     if data[2] != 0x00 {
-        debug!("QUIC: SNI type is not hostname (got 0x{:02x})", data[2]);
+        debug!(target: LOG_TARGET, "QUIC: SNI type is not hostname (got 0x{:02x})", data[2]);
         return None;
     }
 
     if data.len() < 5 {
-        debug!("QUIC: Not enough data for SNI name length");
+        debug!(target: LOG_TARGET, "QUIC: Not enough data for SNI name length");
         return None;
     }
 
     let name_len = u16::from_be_bytes([data[3], data[4]]) as usize;
-    debug!("QUIC: SNI name length: {}", name_len);
+    debug!(target: LOG_TARGET, "QUIC: SNI name length: {}", name_len);
 
     // Validate name length is reasonable
     if name_len == 0 || name_len > 253 {
-        debug!("QUIC: Invalid SNI name length {}", name_len);
+        debug!(target: LOG_TARGET, "QUIC: Invalid SNI name length {}", name_len);
         return None;
     }
 
     if 5 + name_len <= data.len() {
         let sni_data = &data[5..5 + name_len];
-        debug!("QUIC: SNI data: {:02x?}", sni_data);
+        debug!(target: LOG_TARGET, "QUIC: SNI data: {:02x?}", sni_data);
 
         match std::str::from_utf8(sni_data) {
             Ok(sni) => {
@@ -1173,20 +1269,24 @@ fn parse_sni_extension(data: &[u8]) -> Option<String> {
                         .chars()
                         .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
                 {
-                    debug!("QUIC: Successfully parsed complete SNI: {}", sni);
+                    debug!(target: LOG_TARGET, "QUIC: Successfully parsed complete SNI: {}", sni);
                     Some(sni.to_string())
                 } else {
-                    debug!("QUIC: SNI doesn't look like a valid hostname: {}", sni);
+                    debug!(
+                        target: LOG_TARGET,
+                        "QUIC: SNI doesn't look like a valid hostname: {}", sni
+                    );
                     None
                 }
             }
             Err(e) => {
-                debug!("QUIC: SNI data is not valid UTF-8: {}", e);
+                debug!(target: LOG_TARGET, "QUIC: SNI data is not valid UTF-8: {}", e);
                 None
             }
         }
     } else {
         debug!(
+            target: LOG_TARGET,
             "QUIC: SNI name extends beyond available data (need {}, have {}) - skipping partial extraction",
             5 + name_len,
             data.len()
@@ -1471,6 +1571,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
     // Look for TLS handshake patterns in the payload
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Searching for unencrypted TLS data in {} byte payload",
         payload.len()
     );
@@ -1489,6 +1590,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
                     u16::from_be_bytes([payload[offset + 3], payload[offset + 4]]) as usize;
 
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Found TLS record at offset {} with length {}",
                     offset, record_length
                 );
@@ -1498,10 +1600,14 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
 
                     // Check if this is a ClientHello (handshake type 0x01)
                     if !handshake_data.is_empty() && handshake_data[0] == 0x01 {
-                        debug!("QUIC: Found potential TLS ClientHello at offset {}", offset);
+                        debug!(
+                            target: LOG_TARGET,
+                            "QUIC: Found potential TLS ClientHello at offset {}", offset
+                        );
 
                         if let Some(tls_info) = parse_partial_tls_handshake(handshake_data) {
                             debug!(
+                                target: LOG_TARGET,
                                 "QUIC: Successfully parsed TLS from unencrypted data - SNI={:?}",
                                 tls_info.sni
                             );
@@ -1528,6 +1634,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
                 && offset + 4 + handshake_length <= payload.len()
             {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Found potential direct handshake at offset {} with length {}",
                     offset, handshake_length
                 );
@@ -1536,6 +1643,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
                     parse_partial_tls_handshake(&payload[offset..offset + 4 + handshake_length])
                 {
                     debug!(
+                        target: LOG_TARGET,
                         "QUIC: Successfully parsed TLS from direct handshake - SNI={:?}",
                         tls_info.sni
                     );
@@ -1567,7 +1675,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
                         && list_len == name_len + 3
                         && let Some(sni) = parse_sni_extension(ext_data)
                     {
-                        debug!("QUIC: Found SNI directly in packet: {}", sni);
+                        debug!(target: LOG_TARGET, "QUIC: Found SNI directly in packet: {}", sni);
                         let mut tls_info = TlsInfo::new();
                         tls_info.sni = Some(sni);
                         return Some(tls_info);
@@ -1582,7 +1690,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
             if ext_len > 2 && offset + 4 + ext_len <= payload.len() {
                 let ext_data = &payload[offset + 4..offset + 4 + ext_len];
                 if let Some(alpn) = parse_alpn_extension(ext_data) {
-                    debug!("QUIC: Found ALPN directly in packet: {:?}", alpn);
+                    debug!(target: LOG_TARGET, "QUIC: Found ALPN directly in packet: {:?}", alpn);
                     let mut tls_info = TlsInfo::new();
                     tls_info.alpn = alpn;
                     return Some(tls_info);
@@ -1593,7 +1701,7 @@ fn try_parse_unencrypted_crypto_frames(payload: &[u8]) -> Option<TlsInfo> {
         offset += 1;
     }
 
-    debug!("QUIC: No unencrypted TLS data found in packet");
+    debug!(target: LOG_TARGET, "QUIC: No unencrypted TLS data found in packet");
     None
 }
 
@@ -1609,7 +1717,7 @@ impl hkdf::KeyType for ArbitraryOutputLen {
 /// Try to reconstruct SNI from fragmented crypto data
 /// This looks for hostname patterns across fragment boundaries
 fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> Option<String> {
-    debug!("QUIC: Attempting SNI reconstruction from fragments");
+    debug!(target: LOG_TARGET, "QUIC: Attempting SNI reconstruction from fragments");
 
     let fragments = reassembler.get_fragments();
     let mut sorted_offsets: Vec<_> = fragments.keys().collect();
@@ -1623,6 +1731,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
         // The SNI extension typically appears after ~70-150 bytes in the ClientHello
         if *offset > 200 {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Skipping fragment at offset {} - too far from ClientHello start",
                 offset
             );
@@ -1631,6 +1740,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
 
         if let Some(data) = fragments.get(offset) {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Scanning fragment at offset {} ({} bytes) for SNI patterns",
                 offset,
                 data.len()
@@ -1665,7 +1775,10 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
                             {
                                 // list_len should equal name_len + 3 (type + name_len)
                                 if let Some(sni) = parse_sni_extension(sni_data) {
-                                    debug!("QUIC: Found complete SNI in fragment: {}", sni);
+                                    debug!(
+                                        target: LOG_TARGET,
+                                        "QUIC: Found complete SNI in fragment: {}", sni
+                                    );
                                     return Some(sni);
                                 }
                             }
@@ -1679,7 +1792,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
     }
 
     // Second try: smart fragment combination - try to fill gaps and maintain order
-    debug!("QUIC: Smart combining fragments for hostname pattern search");
+    debug!(target: LOG_TARGET, "QUIC: Smart combining fragments for hostname pattern search");
 
     // Check if we have fragments that include the ClientHello beginning
     // We need at least one fragment starting at or very close to offset 0
@@ -1687,6 +1800,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
 
     if !has_beginning {
         debug!(
+            target: LOG_TARGET,
             "QUIC: No fragment near offset 0 (first at {:?}) - missing ClientHello beginning, skipping SNI extraction",
             sorted_offsets.first()
         );
@@ -1700,6 +1814,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
     for &offset in &sorted_offsets {
         if let Some(data) = fragments.get(offset) {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Processing fragment at offset {} ({} bytes), expected offset was {}",
                 offset,
                 data.len(),
@@ -1709,19 +1824,26 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
             // If there's a gap, be more careful about continuing
             if *offset > expected_offset {
                 let gap_size = *offset - expected_offset;
-                debug!("QUIC: Gap detected of {} bytes between fragments", gap_size);
+                debug!(
+                    target: LOG_TARGET,
+                    "QUIC: Gap detected of {} bytes between fragments", gap_size
+                );
 
                 // Gaps in the first 100 bytes are critical as they likely contain SNI
                 // The SNI extension typically appears between bytes 70-200 of the ClientHello
                 if expected_offset < 100 && gap_size > 20 {
                     has_significant_gaps = true;
-                    debug!("QUIC: Gap in critical ClientHello region - SNI might be incomplete");
+                    debug!(
+                        target: LOG_TARGET,
+                        "QUIC: Gap in critical ClientHello region - SNI might be incomplete"
+                    );
                 }
 
                 // Large gaps anywhere might indicate missing data
                 if gap_size > 200 {
                     has_significant_gaps = true;
                     debug!(
+                        target: LOG_TARGET,
                         "QUIC: Large gap detected ({} bytes) - data might be incomplete",
                         gap_size
                     );
@@ -1731,7 +1853,10 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
                 if gap_size <= 50 && !all_data.is_empty() {
                     // Add minimal padding to maintain structure
                     all_data.resize(all_data.len() + gap_size as usize, 0);
-                    debug!("QUIC: Added {} bytes of padding for small gap", gap_size);
+                    debug!(
+                        target: LOG_TARGET,
+                        "QUIC: Added {} bytes of padding for small gap", gap_size
+                    );
                 }
             }
 
@@ -1742,6 +1867,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
 
     if all_data.len() < 10 {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Not enough data for SNI reconstruction ({} bytes)",
             all_data.len()
         );
@@ -1749,6 +1875,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Searching for hostname patterns in {} bytes of combined data",
         all_data.len()
     );
@@ -1760,12 +1887,16 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
     // If we have significant gaps (missing fragments), don't trust ANY hostname candidates
     // as they are likely incomplete or corrupted
     if has_significant_gaps {
-        debug!("QUIC: Not returning any hostname candidates due to significant gaps in fragments");
+        debug!(
+            target: LOG_TARGET,
+            "QUIC: Not returning any hostname candidates due to significant gaps in fragments"
+        );
         // We could still look for very long, complete-looking hostnames, but it's safer to wait
         for candidate in &candidates {
             // Only accept very long, complete-looking hostnames when gaps exist
             if candidate.len() >= 15 && candidate.matches('.').count() >= 2 {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Accepting long candidate '{}' despite gaps",
                     candidate
                 );
@@ -1774,6 +1905,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
                 }
             } else {
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Rejecting candidate '{}' due to fragment gaps",
                     candidate
                 );
@@ -1809,7 +1941,7 @@ fn try_reconstruct_sni_from_fragments(reassembler: &CryptoFrameReassembler) -> O
     });
 
     if let Some(candidate) = processed_candidates.first() {
-        debug!("QUIC: Found hostname candidate: {}", candidate);
+        debug!(target: LOG_TARGET, "QUIC: Found hostname candidate: {}", candidate);
         return Some(candidate.clone());
     }
 
@@ -1853,7 +1985,7 @@ fn find_hostname_candidates(data: &[u8]) -> Vec<String> {
                         && !cleaned.ends_with('.')
                         && !cleaned.contains("..")
                     {
-                        debug!("QUIC: Found hostname candidate: {}", cleaned);
+                        debug!(target: LOG_TARGET, "QUIC: Found hostname candidate: {}", cleaned);
                         candidates.push(cleaned.to_string());
 
                         // Also look for sub-patterns within longer strings
@@ -1866,6 +1998,7 @@ fn find_hostname_candidates(data: &[u8]) -> Vec<String> {
                                     let sub_candidate = parts[start_idx..end_idx].join(".");
                                     if sub_candidate != cleaned && sub_candidate.len() >= 4 {
                                         debug!(
+                                            target: LOG_TARGET,
                                             "QUIC: Found sub-hostname candidate: {}",
                                             sub_candidate
                                         );
@@ -1980,6 +2113,7 @@ fn detect_and_mark_truncated_hostname(candidate: &str, full_data: &[u8]) -> Opti
         // Only return if we marked it as truncated and it still looks hostname-like
         if marked && result.split('.').count() >= 2 {
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Detected truncated hostname: {} -> {}",
                 candidate, result
             );
@@ -2053,6 +2187,7 @@ fn is_valid_hostname(hostname: &str) -> bool {
 
     if has_valid_tld && has_alpha && has_reasonable_structure {
         debug!(
+            target: LOG_TARGET,
             "QUIC: Hostname {} looks valid (TLD: {}, Structure: {}, HasAlpha: {})",
             hostname, has_valid_tld, has_reasonable_structure, has_alpha
         );
@@ -2060,6 +2195,7 @@ fn is_valid_hostname(hostname: &str) -> bool {
     }
 
     debug!(
+        target: LOG_TARGET,
         "QUIC: Hostname {} rejected (TLD: {}, Structure: {}, HasAlpha: {})",
         hostname, has_valid_tld, has_reasonable_structure, has_alpha
     );
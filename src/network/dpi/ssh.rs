@@ -1,3 +1,4 @@
+use super::LOG_TARGET;
 use crate::network::types::{SshConnectionState, SshInfo, SshVersion};
 use log::debug;
 
@@ -57,37 +58,49 @@ pub fn analyze_ssh(payload: &[u8], is_outgoing: bool) -> Option<SshInfo> {
                 match msg_type {
                     20 => {
                         info.connection_state = SshConnectionState::KeyExchange;
-                        debug!("SSH: Detected KEXINIT message at offset {}", i);
+                        debug!(target: LOG_TARGET, "SSH: Detected KEXINIT message at offset {}", i);
                         found_packet_state = true;
                         break;
                     }
                     21 => {
                         info.connection_state = SshConnectionState::KeyExchange;
-                        debug!("SSH: Detected NEWKEYS message at offset {}", i);
+                        debug!(target: LOG_TARGET, "SSH: Detected NEWKEYS message at offset {}", i);
                         found_packet_state = true;
                         break;
                     }
                     50 => {
                         info.connection_state = SshConnectionState::Authentication;
-                        debug!("SSH: Detected USERAUTH_REQUEST message at offset {}", i);
+                        debug!(
+                            target: LOG_TARGET,
+                            "SSH: Detected USERAUTH_REQUEST message at offset {}", i
+                        );
                         found_packet_state = true;
                         break;
                     }
                     51 => {
                         info.connection_state = SshConnectionState::Authentication;
-                        debug!("SSH: Detected USERAUTH_FAILURE message at offset {}", i);
+                        debug!(
+                            target: LOG_TARGET,
+                            "SSH: Detected USERAUTH_FAILURE message at offset {}", i
+                        );
                         found_packet_state = true;
                         break;
                     }
                     52 => {
                         info.connection_state = SshConnectionState::Established;
-                        debug!("SSH: Detected USERAUTH_SUCCESS message at offset {}", i);
+                        debug!(
+                            target: LOG_TARGET,
+                            "SSH: Detected USERAUTH_SUCCESS message at offset {}", i
+                        );
                         found_packet_state = true;
                         break;
                     }
                     90..=127 => {
                         info.connection_state = SshConnectionState::Established;
-                        debug!("SSH: Detected connection protocol message at offset {}", i);
+                        debug!(
+                            target: LOG_TARGET,
+                            "SSH: Detected connection protocol message at offset {}", i
+                        );
                         found_packet_state = true;
                         break;
                     }
@@ -116,7 +129,7 @@ pub fn analyze_ssh(payload: &[u8], is_outgoing: bool) -> Option<SshInfo> {
         }
     }
 
-    debug!("SSH analysis result: {:?}", info);
+    debug!(target: LOG_TARGET, "SSH analysis result: {:?}", info);
     Some(info)
 }
 
@@ -0,0 +1,260 @@
+// network/dpi/stream.rs - In-order TCP payload of flows DPI hasn't recognised yet
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Bytes of one direction of a flow buffered while waiting for a protocol match
+pub const STREAM_BUFFER_LIMIT: usize = 16 * 1024;
+
+/// Flows buffered at once by one DPI worker. Beyond this, new unrecognised
+/// flows are only ever analysed a segment at a time.
+const MAX_BUFFERING_FLOWS: usize = 256;
+
+/// Flows whose outcome one worker remembers before starting over
+const MAX_TRACKED_FLOWS: usize = 16 * 1024;
+
+/// The start of one direction of a TCP stream, put back in order by
+/// sequence number
+#[derive(Debug)]
+struct StreamBuffer {
+    /// Sequence number of `data[0]`
+    start_seq: u32,
+    data: Vec<u8>,
+    /// Segments that arrived ahead of a gap, by offset from `start_seq`
+    pending: BTreeMap<usize, Vec<u8>>,
+}
+
+impl StreamBuffer {
+    fn new(seq: u32) -> Self {
+        Self {
+            start_seq: seq,
+            data: Vec::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Add a segment, returning whether the in-order data changed. A segment
+    /// from before the first one seen (the start of a stream whose segments
+    /// were reordered) moves the start of the buffer back. Bytes past the
+    /// limit are dropped, and retransmitted bytes keep their first copy.
+    fn insert(&mut self, seq: u32, payload: &[u8]) -> bool {
+        let offset = i64::from(seq.wrapping_sub(self.start_seq) as i32);
+        if offset < 0 {
+            let shift = offset.unsigned_abs() as usize;
+            if shift >= STREAM_BUFFER_LIMIT {
+                return false;
+            }
+            let mut pending: BTreeMap<usize, Vec<u8>> = std::mem::take(&mut self.pending)
+                .into_iter()
+                .map(|(start, segment)| (start + shift, segment))
+                .collect();
+            if !self.data.is_empty() {
+                pending.insert(shift, std::mem::take(&mut self.data));
+            }
+            self.pending = pending;
+            self.start_seq = seq;
+            return self.insert(seq, payload);
+        }
+
+        let start = offset;
+        let end = (offset + payload.len() as i64).min(STREAM_BUFFER_LIMIT as i64);
+        if start >= end {
+            return false;
+        }
+        let segment = &payload[(start - offset) as usize..(end - offset) as usize];
+        let start = start as usize;
+
+        if start > self.data.len() {
+            self.pending
+                .entry(start)
+                .or_insert_with(|| segment.to_vec());
+            return false;
+        }
+
+        let len_before = self.data.len();
+        self.extend(start, segment);
+        // Segments that were waiting for this one
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > self.data.len() {
+                break;
+            }
+            let (start, segment) = entry.remove_entry();
+            self.extend(start, &segment);
+        }
+        self.data.len() > len_before
+    }
+
+    /// Append the part of `segment`, which begins at offset `start`, that
+    /// lies past the end of `data`
+    fn extend(&mut self, start: usize, segment: &[u8]) {
+        let end = segment.len().min(STREAM_BUFFER_LIMIT - start);
+        if let Some(new) = segment.get(self.data.len() - start..end) {
+            self.data.extend_from_slice(new);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.data.len() >= STREAM_BUFFER_LIMIT
+    }
+}
+
+#[derive(Debug)]
+enum FlowState {
+    /// No segment was recognised on its own yet
+    Buffering {
+        outgoing: Option<StreamBuffer>,
+        incoming: Option<StreamBuffer>,
+    },
+    /// Recognised, or given up on once a buffer filled: segments are
+    /// analysed one at a time again
+    Done,
+}
+
+/// Payload buffers of the TCP flows one DPI worker hasn't recognised.
+///
+/// A protocol header split over several segments (a ClientHello cut before
+/// its record header, an HTTP request line cut mid-method) can't be
+/// recognised from any one of them. Once a flow's first segment comes back
+/// unrecognised, its later segments are analysed together with everything
+/// received before them in the same direction, until a detector matches or
+/// `STREAM_BUFFER_LIMIT` is reached. The buffers are freed at that point.
+#[derive(Debug, Default)]
+pub struct StreamTable {
+    flows: HashMap<String, FlowState>,
+    buffering: usize,
+}
+
+impl StreamTable {
+    /// Bytes to analyse for a TCP segment: the segment itself, or for a
+    /// flow being buffered, its direction's in-order payload so far. None
+    /// when the segment added nothing new to a buffered stream.
+    pub fn payload<'a>(
+        &'a mut self,
+        key: &str,
+        is_outgoing: bool,
+        seq: u32,
+        segment: &'a [u8],
+    ) -> Option<&'a [u8]> {
+        let Some(FlowState::Buffering { outgoing, incoming }) = self.flows.get_mut(key) else {
+            return Some(segment);
+        };
+        let stream = if is_outgoing { outgoing } else { incoming };
+        let buffer = stream.get_or_insert_with(|| StreamBuffer::new(seq));
+        if buffer.insert(seq, segment) {
+            Some(&buffer.data)
+        } else {
+            None
+        }
+    }
+
+    /// Record whether analysing a flow's payload found its protocol
+    pub fn record(&mut self, key: &str, is_outgoing: bool, seq: u32, segment: &[u8], found: bool) {
+        match self.flows.get_mut(key) {
+            Some(FlowState::Done) => {}
+            Some(FlowState::Buffering { outgoing, incoming }) => {
+                let full = [outgoing, incoming]
+                    .into_iter()
+                    .flatten()
+                    .any(|buffer| buffer.is_full());
+                if found || full {
+                    self.flows.insert(key.to_string(), FlowState::Done);
+                    self.buffering -= 1;
+                }
+            }
+            None => {
+                if self.flows.len() >= MAX_TRACKED_FLOWS {
+                    self.clear();
+                }
+                let state = if found || self.buffering >= MAX_BUFFERING_FLOWS {
+                    FlowState::Done
+                } else {
+                    let mut buffer = StreamBuffer::new(seq);
+                    buffer.insert(seq, segment);
+                    self.buffering += 1;
+                    if is_outgoing {
+                        FlowState::Buffering {
+                            outgoing: Some(buffer),
+                            incoming: None,
+                        }
+                    } else {
+                        FlowState::Buffering {
+                            outgoing: None,
+                            incoming: Some(buffer),
+                        }
+                    }
+                };
+                self.flows.insert(key.to_string(), state);
+            }
+        }
+    }
+
+    /// Forget every flow. Flows seen again start over as if new.
+    fn clear(&mut self) {
+        self.flows.clear();
+        self.buffering = 0;
+    }
+
+    /// Bytes buffered across all flows
+    #[cfg(test)]
+    fn buffered_bytes(&self) -> usize {
+        self.flows
+            .values()
+            .map(|state| match state {
+                FlowState::Buffering { outgoing, incoming } => [outgoing, incoming]
+                    .into_iter()
+                    .flatten()
+                    .map(|buffer| buffer.data.len())
+                    .sum(),
+                FlowState::Done => 0,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_are_put_back_in_order() {
+        let stream: Vec<u8> = (0..100).collect();
+        let mut buffer = StreamBuffer::new(u32::MAX - 10);
+        let seq = |offset: u32| (u32::MAX - 10).wrapping_add(offset);
+
+        assert!(buffer.insert(seq(0), &stream[..20]));
+        // Ahead of a gap, then the gap filled by an overlapping retransmission
+        assert!(!buffer.insert(seq(50), &stream[50..]));
+        assert!(!buffer.insert(seq(10), &stream[10..20]));
+        assert!(buffer.insert(seq(15), &stream[15..50]));
+        assert_eq!(buffer.data, stream);
+
+        // The start of the stream arriving after a later segment
+        let mut buffer = StreamBuffer::new(1000);
+        assert!(buffer.insert(1000, &stream[40..60]));
+        assert!(!buffer.insert(1030, &stream[70..]));
+        assert!(buffer.insert(960, &stream[..30]));
+        assert_eq!(buffer.data, &stream[..30]);
+        assert!(buffer.insert(990, &stream[30..70]));
+        assert_eq!(buffer.data, stream);
+    }
+
+    #[test]
+    fn test_buffer_is_bounded_and_freed_once_given_up() {
+        let mut table = StreamTable::default();
+        let segment = [0u8; 1400];
+        table.record("flow", true, 0, &segment, false);
+
+        let mut seq = 0;
+        while table.buffered_bytes() > 0 {
+            seq += 1400;
+            assert!(table.payload("flow", true, seq, &segment).is_some());
+            table.record("flow", true, seq, &segment, false);
+        }
+        assert!(seq as usize + 1400 >= STREAM_BUFFER_LIMIT);
+        assert_eq!(table.buffered_bytes(), 0);
+        // Later segments are looked at on their own
+        assert_eq!(
+            table.payload("flow", true, seq, &segment),
+            Some(&segment[..])
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Passive OS fingerprinting of remote hosts
+//!
+//! TCP stacks differ in the initial TTL, window, window scale and option
+//! order they use when opening a connection. Much like p0f, the first SYN or
+//! SYN-ACK a remote host sends is matched against a table of known stacks.
+//! The built-in table is `assets/os_signatures`; a file in the same format
+//! can replace it.
+
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::Path;
+
+use crate::network::types::OsGuess;
+
+const SIGNATURES_DATA: &str = include_str!("../../assets/os_signatures");
+
+/// Initial TTLs in common use. An observed TTL is rounded up to the next one.
+const INITIAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+
+/// Guesses with a lower confidence are not reported
+const MIN_CONFIDENCE: u8 = 50;
+
+// Share of the confidence each field contributes. A wildcard in the
+// signature earns half its field's share.
+const TTL_WEIGHT: u8 = 16;
+const OPTIONS_WEIGHT: u8 = 40;
+const WINDOW_WEIGHT: u8 = 20;
+const SCALE_WEIGHT: u8 = 14;
+const DF_WEIGHT: u8 = 10;
+
+/// What a SYN or SYN-ACK tells about the stack that sent it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SynFingerprint {
+    /// TTL or hop limit as received
+    pub ttl: u8,
+    /// Don't Fragment bit, `None` for IPv6
+    pub df: Option<bool>,
+    pub window: u16,
+    pub mss: Option<u16>,
+    pub window_scale: Option<u8>,
+    /// Option kinds in the order sent, e.g. `mss,nop,ws,sok,ts`
+    pub options: String,
+}
+
+impl SynFingerprint {
+    /// Read the fingerprint from a TCP header, options included. Options
+    /// cut short by the capture are left out.
+    pub fn from_tcp_header(header: &[u8], ttl: u8, df: Option<bool>) -> Option<Self> {
+        if header.len() < 20 {
+            return None;
+        }
+
+        let window = u16::from_be_bytes([header[14], header[15]]);
+        let mut mss = None;
+        let mut window_scale = None;
+        let mut kinds = Vec::new();
+        let mut options = &header[20..];
+
+        while let Some((&kind, rest)) = options.split_first() {
+            match kind {
+                0 => {
+                    kinds.push("eol".to_string());
+                    break;
+                }
+                1 => {
+                    kinds.push("nop".to_string());
+                    options = rest;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // The length covers the kind and length bytes too
+            let len = rest.first().map_or(0, |&len| len as usize);
+            if len < 2 || len > options.len() {
+                break;
+            }
+            match (kind, &options[2..len]) {
+                (2, &[hi, lo]) => mss = Some(u16::from_be_bytes([hi, lo])),
+                (3, &[shift]) => window_scale = Some(shift),
+                _ => {}
+            }
+            kinds.push(match kind {
+                2 => "mss".to_string(),
+                3 => "ws".to_string(),
+                4 => "sok".to_string(),
+                5 => "sack".to_string(),
+                8 => "ts".to_string(),
+                other => format!("?{}", other),
+            });
+            options = &options[len..];
+        }
+
+        Some(Self {
+            ttl,
+            df,
+            window,
+            mss,
+            window_scale,
+            options: kinds.join(","),
+        })
+    }
+
+    /// The TTL the sender most likely started from
+    pub fn initial_ttl(&self) -> u8 {
+        INITIAL_TTLS
+            .into_iter()
+            .find(|&initial| self.ttl <= initial)
+            .unwrap_or(255)
+    }
+}
+
+/// Receive window expected by a signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WindowMatch {
+    Any,
+    Exact(u16),
+    /// A multiple of the MSS the SYN announces
+    MssMultiple(u16),
+}
+
+/// One known TCP stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OsSignature {
+    os: String,
+    initial_ttl: u8,
+    window: WindowMatch,
+    /// `None` matches any scale
+    window_scale: Option<u8>,
+    /// `None` matches any option layout
+    options: Option<String>,
+    /// `None` matches either value of the DF bit
+    df: Option<bool>,
+}
+
+impl OsSignature {
+    /// Parse a `os | ttl | window | scale | options | df` line
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [os, ttl, window, scale, options, df] = fields[..] else {
+            return Err(anyhow!(
+                "expected 6 `|` separated fields, got {}",
+                fields.len()
+            ));
+        };
+        if os.is_empty() {
+            return Err(anyhow!("missing OS name"));
+        }
+
+        let initial_ttl = ttl
+            .parse()
+            .with_context(|| format!("invalid initial TTL `{}`", ttl))?;
+        let window = match window {
+            "*" => WindowMatch::Any,
+            _ => match window.strip_prefix("mss*") {
+                Some(factor) => WindowMatch::MssMultiple(
+                    factor
+                        .parse()
+                        .with_context(|| format!("invalid window `{}`", window))?,
+                ),
+                None => WindowMatch::Exact(
+                    window
+                        .parse()
+                        .with_context(|| format!("invalid window `{}`", window))?,
+                ),
+            },
+        };
+        let window_scale = match scale {
+            "*" => None,
+            _ => Some(
+                scale
+                    .parse()
+                    .with_context(|| format!("invalid window scale `{}`", scale))?,
+            ),
+        };
+        let options = (options != "*").then(|| options.replace(' ', ""));
+        let df = match df {
+            "df" => Some(true),
+            "-" => Some(false),
+            "*" => None,
+            _ => return Err(anyhow!("invalid df `{}`, expected df, - or *", df)),
+        };
+
+        Ok(Self {
+            os: os.to_string(),
+            initial_ttl,
+            window,
+            window_scale,
+            options,
+            df,
+        })
+    }
+
+    /// Confidence that `syn` came from this stack, or `None` when the
+    /// initial TTL rules it out
+    fn score(&self, syn: &SynFingerprint) -> Option<u8> {
+        if syn.initial_ttl() != self.initial_ttl {
+            return None;
+        }
+
+        let options = match &self.options {
+            None => OPTIONS_WEIGHT / 2,
+            Some(options) if *options == syn.options => OPTIONS_WEIGHT,
+            Some(_) => 0,
+        };
+        let window = match self.window {
+            WindowMatch::Any => WINDOW_WEIGHT / 2,
+            WindowMatch::Exact(window) if window == syn.window => WINDOW_WEIGHT,
+            WindowMatch::MssMultiple(factor)
+                if syn.mss.map(|mss| mss as u32 * factor as u32) == Some(syn.window as u32) =>
+            {
+                WINDOW_WEIGHT
+            }
+            _ => 0,
+        };
+        let scale = match self.window_scale {
+            None => SCALE_WEIGHT / 2,
+            Some(scale) if Some(scale) == syn.window_scale => SCALE_WEIGHT,
+            Some(_) => 0,
+        };
+        let df = match (self.df, syn.df) {
+            (None, _) | (_, None) => DF_WEIGHT / 2,
+            (Some(expected), Some(df)) if expected == df => DF_WEIGHT,
+            _ => 0,
+        };
+
+        Some(TTL_WEIGHT + options + window + scale + df)
+    }
+}
+
+/// Ordered table of known TCP stacks
+#[derive(Debug, Clone, Default)]
+pub struct SignatureTable {
+    signatures: Vec<OsSignature>,
+}
+
+impl SignatureTable {
+    /// The table shipped with RustNet
+    pub fn builtin() -> Self {
+        Self::parse(SIGNATURES_DATA).expect("built-in OS signatures are valid")
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("invalid OS signatures in {}", path.display()))
+    }
+
+    /// Parse one signature per line. Blank lines and `#` comments are skipped.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut signatures = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            signatures
+                .push(OsSignature::parse(line).with_context(|| format!("line {}", number + 1))?);
+        }
+        Ok(Self { signatures })
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Best matching stack for `syn`. On a tie the earlier signature wins.
+    pub fn guess(&self, syn: &SynFingerprint) -> Option<OsGuess> {
+        let mut best: Option<(&OsSignature, u8)> = None;
+        for signature in &self.signatures {
+            if let Some(score) = signature.score(syn)
+                && best.is_none_or(|(_, best_score)| score > best_score)
+            {
+                best = Some((signature, score));
+            }
+        }
+
+        best.filter(|&(_, confidence)| confidence >= MIN_CONFIDENCE)
+            .map(|(signature, confidence)| OsGuess {
+                os: signature.os.clone(),
+                confidence,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// TCP header of a SYN announcing `window` and carrying `options`
+    fn syn_header(window: u16, options: &[u8]) -> Vec<u8> {
+        let mut header = vec![0u8; 20];
+        header[12] = (((20 + options.len()) / 4) as u8) << 4;
+        header[13] = 0x02;
+        header[14..16].copy_from_slice(&window.to_be_bytes());
+        header.extend_from_slice(options);
+        header
+    }
+
+    // Option sets as sent by each stack
+    const LINUX_OPTIONS: [u8; 20] = [
+        2, 4, 0x05, 0xb4, // MSS 1460
+        4, 2, // SACK permitted
+        8, 10, 0, 0, 0, 1, 0, 0, 0, 0, // Timestamps
+        1, // NOP
+        3, 3, 7, // Window scale 7
+    ];
+    const WINDOWS_OPTIONS: [u8; 12] = [
+        2, 4, 0x05, 0xb4, // MSS 1460
+        1,    // NOP
+        3, 3, 8, // Window scale 8
+        1, 1, // NOP, NOP
+        4, 2, // SACK permitted
+    ];
+    const MACOS_OPTIONS: [u8; 24] = [
+        2, 4, 0x05, 0xb4, // MSS 1460
+        1,    // NOP
+        3, 3, 6, // Window scale 6
+        1, 1, // NOP, NOP
+        8, 10, 0, 0, 0, 1, 0, 0, 0, 0, // Timestamps
+        4, 2, // SACK permitted
+        0, 0, // EOL and padding
+    ];
+    const FREEBSD_OPTIONS: [u8; 20] = [
+        2, 4, 0x05, 0xb4, // MSS 1460
+        1,    // NOP
+        3, 3, 6, // Window scale 6
+        4, 2, // SACK permitted
+        8, 10, 0, 0, 0, 1, 0, 0, 0, 0, // Timestamps
+    ];
+
+    fn guess(window: u16, options: &[u8], ttl: u8) -> Option<OsGuess> {
+        let syn =
+            SynFingerprint::from_tcp_header(&syn_header(window, options), ttl, Some(true)).unwrap();
+        SignatureTable::builtin().guess(&syn)
+    }
+
+    #[test]
+    fn test_option_layout_is_read_in_order() {
+        let syn =
+            SynFingerprint::from_tcp_header(&syn_header(64240, &LINUX_OPTIONS), 57, None).unwrap();
+        assert_eq!(syn.options, "mss,sok,ts,nop,ws");
+        assert_eq!(syn.mss, Some(1460));
+        assert_eq!(syn.window_scale, Some(7));
+        assert_eq!(syn.initial_ttl(), 64);
+
+        let syn =
+            SynFingerprint::from_tcp_header(&syn_header(65535, &MACOS_OPTIONS), 64, None).unwrap();
+        assert_eq!(syn.options, "mss,nop,ws,nop,nop,ts,sok,eol");
+
+        // An option running past the header ends the walk instead of panicking
+        let syn = SynFingerprint::from_tcp_header(&syn_header(1024, &[2, 4, 5]), 64, None).unwrap();
+        assert_eq!(syn.options, "");
+        assert_eq!(syn.mss, None);
+    }
+
+    #[test]
+    fn test_common_stacks_are_recognised() {
+        let linux = guess(64240, &LINUX_OPTIONS, 52).unwrap();
+        assert_eq!(linux.os, "Linux");
+        assert_eq!(linux.confidence, 100);
+
+        let windows = guess(64240, &WINDOWS_OPTIONS, 116).unwrap();
+        assert_eq!(windows.os, "Windows 10/11");
+        assert_eq!(windows.confidence, 100);
+
+        let macos = guess(65535, &MACOS_OPTIONS, 60).unwrap();
+        assert_eq!(macos.os, "macOS/iOS");
+        assert_eq!(macos.confidence, 100);
+
+        let freebsd = guess(65535, &FREEBSD_OPTIONS, 63).unwrap();
+        assert_eq!(freebsd.os, "FreeBSD");
+
+        // Same options as Linux, with Android's window
+        let android = guess(65535, &LINUX_OPTIONS, 64).unwrap();
+        assert_eq!(android.os, "Android");
+        assert!(android.confidence < 100);
+    }
+
+    #[test]
+    fn test_unknown_stacks_are_not_guessed() {
+        // A Windows option layout with a Linux TTL matches nothing well
+        assert!(guess(5840, &WINDOWS_OPTIONS, 64).is_none());
+        assert!(guess(64240, &LINUX_OPTIONS, 30).is_none());
+    }
+
+    #[test]
+    fn test_table_can_be_replaced_from_a_file() {
+        let path = std::env::temp_dir().join(format!("rustnet-os-{}", std::process::id()));
+        fs::write(
+            &path,
+            "# Site-specific appliance\nAcmeOS | 255 | 4096 | * | mss | -\n",
+        )
+        .unwrap();
+        let table = SignatureTable::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(table.len(), 1);
+
+        let syn = SynFingerprint::from_tcp_header(
+            &syn_header(4096, &[2, 4, 0x05, 0xb4]),
+            250,
+            Some(false),
+        )
+        .unwrap();
+        assert_eq!(table.guess(&syn).unwrap().os, "AcmeOS");
+
+        assert!(SignatureTable::parse("Linux | 64 | * | * | mss").is_err());
+        assert!(SignatureTable::parse("Linux | 64 | mss*x | * | mss | df").is_err());
+        assert!(SignatureTable::parse("Linux | 64 | * | * | mss | maybe").is_err());
+    }
+}
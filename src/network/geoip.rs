@@ -0,0 +1,243 @@
+//! Locations of remote hosts from a MaxMind DB file
+//!
+//! The GeoLite2 / GeoIP2 City and Country databases are read with the
+//! `maxminddb` crate and only the fields shown in the UI are kept. Builds
+//! without the `geoip` feature never find a database.
+
+#[cfg(feature = "geoip")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(not(feature = "geoip"))]
+use anyhow::anyhow;
+use log::warn;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::network::types::GeoInfo;
+
+/// A MaxMind DB file loaded into memory
+pub struct GeoDatabase {
+    #[cfg(feature = "geoip")]
+    reader: maxminddb::Reader<Vec<u8>>,
+}
+
+impl GeoDatabase {
+    /// Load the database at `path`
+    #[cfg(feature = "geoip")]
+    pub fn open(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(data).with_context(|| format!("{} is not a MaxMind DB", path.display()))
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    pub fn open(path: &Path) -> Result<Self> {
+        Err(anyhow!(
+            "cannot read {}: built without the geoip feature",
+            path.display()
+        ))
+    }
+
+    /// The first database found where GeoLite2 City is usually installed
+    pub fn find_default() -> Option<(PathBuf, Self)> {
+        if !cfg!(feature = "geoip") {
+            return None;
+        }
+        default_paths()
+            .into_iter()
+            .filter(|path| path.is_file())
+            .find_map(|path| match Self::open(&path) {
+                Ok(db) => Some((path, db)),
+                Err(e) => {
+                    warn!("Skipping GeoIP database: {:#}", e);
+                    None
+                }
+            })
+    }
+
+    #[cfg(feature = "geoip")]
+    fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            reader: maxminddb::Reader::from_source(data)?,
+        })
+    }
+
+    /// Where `ip` is, when the database knows
+    #[cfg(feature = "geoip")]
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        // Country databases decode as a City record without city or location
+        let record: maxminddb::geoip2::City = self.reader.lookup(ip).ok()?;
+        geo_info(&record)
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    pub fn lookup(&self, _ip: IpAddr) -> Option<GeoInfo> {
+        None
+    }
+}
+
+/// The fields shown in the UI from a City or Country record
+#[cfg(feature = "geoip")]
+fn geo_info(record: &maxminddb::geoip2::City) -> Option<GeoInfo> {
+    let country_code = record
+        .country
+        .as_ref()
+        .and_then(|country| country.iso_code)
+        .or_else(|| record.registered_country.as_ref()?.iso_code)?
+        .to_string();
+    let city = record
+        .city
+        .as_ref()
+        .and_then(|city| city.names.as_ref()?.get("en"))
+        .map(|name| name.to_string());
+    let location = record.location.as_ref();
+    Some(GeoInfo {
+        country_code,
+        city,
+        latitude: location.and_then(|l| l.latitude).unwrap_or_default(),
+        longitude: location.and_then(|l| l.longitude).unwrap_or_default(),
+    })
+}
+
+/// Where GeoLite2 City is looked for when no database is given
+fn default_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("GeoLite2-City.mmdb")];
+    if let Ok(xdg_data) = std::env::var("XDG_DATA_HOME") {
+        paths.push(PathBuf::from(xdg_data).join("rustnet/GeoLite2-City.mmdb"));
+    }
+    if let Ok(home) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
+        paths.push(PathBuf::from(home).join(".local/share/rustnet/GeoLite2-City.mmdb"));
+    }
+    paths.push(PathBuf::from("/usr/share/GeoIP/GeoLite2-City.mmdb"));
+    paths.push(PathBuf::from("/usr/local/share/GeoIP/GeoLite2-City.mmdb"));
+    paths
+}
+
+#[cfg(all(test, feature = "geoip"))]
+mod tests {
+    use super::*;
+
+    /// Precedes the metadata map at the end of the file
+    const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+
+    /// Zero bytes between the search tree and the data section
+    const DATA_SECTION_SEPARATOR: usize = 16;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = vec![(2 << 5) | s.len() as u8];
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn double(d: f64) -> Vec<u8> {
+        let mut out = vec![(3 << 5) | 8];
+        out.extend_from_slice(&d.to_bits().to_be_bytes());
+        out
+    }
+
+    fn uint16(n: u16) -> Vec<u8> {
+        let mut out = vec![(5 << 5) | 2];
+        out.extend_from_slice(&n.to_be_bytes());
+        out
+    }
+
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![(7 << 5) | entries.len() as u8];
+        for (key, value) in entries {
+            out.extend(string(key));
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// An empty array, which is an extended type
+    fn empty_array() -> Vec<u8> {
+        vec![0, 11 - 7]
+    }
+
+    fn metadata(node_count: u16, ip_version: u16) -> Vec<u8> {
+        let mut out = METADATA_MARKER.to_vec();
+        out.extend(map(&[
+            ("binary_format_major_version", uint16(2)),
+            ("binary_format_minor_version", uint16(0)),
+            ("build_epoch", uint16(0)),
+            ("database_type", string("GeoLite2-City")),
+            ("description", map(&[])),
+            ("ip_version", uint16(ip_version)),
+            ("languages", empty_array()),
+            ("node_count", uint16(node_count)),
+            ("record_size", uint16(24)),
+        ]));
+        out
+    }
+
+    /// An IPv4 database with 24-bit records that knows only 81.0.0.0/8
+    fn city_database() -> Vec<u8> {
+        const NODES: usize = 8;
+        let prefix: u8 = 81;
+
+        // The city name is shared through a pointer, as real databases do
+        let mut data_section = string("Dublin");
+        let record_offset = data_section.len();
+        data_section.extend(map(&[
+            ("city", map(&[("names", map(&[("en", vec![1 << 5, 0])]))])),
+            ("country", map(&[("iso_code", string("IE"))])),
+            (
+                "location",
+                map(&[("latitude", double(53.3)), ("longitude", double(-6.25))]),
+            ),
+        ]));
+
+        let mut tree = Vec::new();
+        for bit in 0..NODES {
+            let matching = if bit + 1 == NODES {
+                NODES + DATA_SECTION_SEPARATOR + record_offset
+            } else {
+                bit + 1
+            };
+            let (left, right) = if prefix & (0x80 >> bit) != 0 {
+                (NODES, matching)
+            } else {
+                (matching, NODES)
+            };
+            tree.extend_from_slice(&(left as u32).to_be_bytes()[1..]);
+            tree.extend_from_slice(&(right as u32).to_be_bytes()[1..]);
+        }
+
+        let mut file = tree;
+        file.extend([0; DATA_SECTION_SEPARATOR]);
+        file.extend(data_section);
+        file.extend(metadata(NODES as u16, 4));
+        file
+    }
+
+    #[test]
+    fn test_lookup() {
+        let db = GeoDatabase::from_bytes(city_database()).unwrap();
+
+        let info = db.lookup("81.2.69.142".parse().unwrap()).unwrap();
+        assert_eq!(info.country_code, "IE");
+        assert_eq!(info.city.as_deref(), Some("Dublin"));
+        assert_eq!(info.latitude, 53.3);
+        assert_eq!(info.longitude, -6.25);
+
+        assert_eq!(db.lookup("80.2.69.142".parse().unwrap()), None);
+        assert_eq!(db.lookup("192.168.1.1".parse().unwrap()), None);
+        // An IPv4-only database knows nothing about IPv6
+        assert_eq!(db.lookup("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(GeoDatabase::from_bytes(b"not a database".to_vec()).is_err());
+
+        let mut file = METADATA_MARKER.to_vec();
+        file.extend(map(&[("record_size", uint16(24))]));
+        assert!(GeoDatabase::from_bytes(file).is_err());
+
+        let path = std::env::temp_dir().join("rustnet-test-not-a-database.mmdb");
+        std::fs::write(&path, b"not a database").unwrap();
+        assert!(GeoDatabase::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
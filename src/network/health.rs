@@ -0,0 +1,179 @@
+// src/network/health.rs - Connection churn and TCP handshake health per snapshot
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Instant;
+
+use crate::network::types::{Connection, Protocol, ProtocolState, TcpState};
+
+/// Ticks of new-connection rate kept for the Statistics panel's sparkline
+pub const RATE_HISTORY_LEN: usize = 60;
+
+/// Connection health at one snapshot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthMetrics {
+    /// Connections first seen since the previous snapshot, per second
+    pub new_per_sec: f64,
+    /// The same rate by process, highest first. Connections whose process
+    /// is not known yet are left out.
+    pub new_per_sec_by_process: Vec<(String, f64)>,
+    /// TCP connections by state name (`ESTABLISHED`, `SYN_SENT`, ...)
+    pub tcp_states: BTreeMap<String, usize>,
+    /// TCP connections in SYN_SENT or SYN_RECV
+    pub half_open: usize,
+    pub established: usize,
+    /// `new_per_sec` of recent snapshots, oldest first
+    pub rate_history: Vec<f64>,
+}
+
+impl HealthMetrics {
+    /// Half-open connections per established one, None while nothing is established
+    pub fn half_open_ratio(&self) -> Option<f64> {
+        (self.established > 0).then(|| self.half_open as f64 / self.established as f64)
+    }
+}
+
+/// Computes `HealthMetrics` from consecutive snapshots
+#[derive(Debug, Default)]
+pub struct HealthMonitor {
+    last_tick: Option<Instant>,
+    rate_history: VecDeque<f64>,
+}
+
+impl HealthMonitor {
+    /// Metrics for the connections in a snapshot taken at `now`. A connection
+    /// counts as new if it was created after the previous call, so the table
+    /// warmed up before the first snapshot isn't mistaken for a burst.
+    pub fn observe<'a>(
+        &mut self,
+        connections: impl IntoIterator<Item = &'a Connection>,
+        now: Instant,
+    ) -> HealthMetrics {
+        let mut metrics = HealthMetrics::default();
+        let mut new_by_process: HashMap<&str, usize> = HashMap::new();
+        let mut new_total = 0;
+
+        for conn in connections {
+            if let Some(last_tick) = self.last_tick
+                && conn.created_instant > last_tick
+            {
+                new_total += 1;
+                if let Some(process) = conn.process_name.as_deref() {
+                    *new_by_process.entry(process).or_default() += 1;
+                }
+            }
+
+            if conn.protocol == Protocol::TCP {
+                *metrics.tcp_states.entry(conn.state()).or_default() += 1;
+                match conn.protocol_state {
+                    ProtocolState::Tcp(TcpState::SynSent | TcpState::SynReceived) => {
+                        metrics.half_open += 1
+                    }
+                    ProtocolState::Tcp(TcpState::Established) => metrics.established += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        let elapsed = self
+            .last_tick
+            .map_or(0.0, |last_tick| (now - last_tick).as_secs_f64());
+        if elapsed > 0.0 {
+            metrics.new_per_sec = new_total as f64 / elapsed;
+            metrics.new_per_sec_by_process = new_by_process
+                .into_iter()
+                .map(|(process, count)| (process.to_string(), count as f64 / elapsed))
+                .collect();
+            metrics
+                .new_per_sec_by_process
+                .sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        }
+        self.last_tick = Some(now);
+
+        if self.rate_history.len() == RATE_HISTORY_LEN {
+            self.rate_history.pop_front();
+        }
+        self.rate_history.push_back(metrics.new_per_sec);
+        metrics.rate_history = self.rate_history.iter().copied().collect();
+
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    fn tcp(port: u16, state: TcpState, process: Option<&str>, created: Instant) -> Connection {
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            SocketAddr::from(([192, 168, 1, 10], port)),
+            "203.0.113.5:443".parse().unwrap(),
+            ProtocolState::Tcp(state),
+        );
+        conn.process_name = process.map(str::to_string);
+        conn.created_instant = created;
+        conn
+    }
+
+    #[test]
+    fn test_new_connection_rate_over_scripted_churn() {
+        let start = Instant::now();
+        let mut monitor = HealthMonitor::default();
+
+        // The table as it stood at startup is not churn
+        let mut table = vec![
+            tcp(40000, TcpState::Established, Some("firefox"), start),
+            tcp(40001, TcpState::Established, Some("ssh"), start),
+        ];
+        let metrics = monitor.observe(&table, start);
+        assert_eq!(metrics.new_per_sec, 0.0);
+        assert_eq!(metrics.established, 2);
+
+        // Two seconds later: six new connections, four from curl, one from an
+        // unknown process, and one old connection has gone
+        let tick = start + Duration::from_secs(2);
+        table.pop();
+        for port in 0..4 {
+            table.push(tcp(
+                41000 + port,
+                TcpState::SynSent,
+                Some("curl"),
+                start + Duration::from_millis(500),
+            ));
+        }
+        table.push(tcp(42000, TcpState::Established, Some("firefox"), tick));
+        table.push(tcp(42001, TcpState::SynReceived, None, tick));
+
+        let metrics = monitor.observe(&table, tick);
+        assert_eq!(metrics.new_per_sec, 3.0);
+        assert_eq!(
+            metrics.new_per_sec_by_process,
+            vec![("curl".to_string(), 2.0), ("firefox".to_string(), 0.5)]
+        );
+        assert_eq!(metrics.half_open, 5);
+        assert_eq!(metrics.established, 2);
+        assert_eq!(metrics.half_open_ratio(), Some(2.5));
+        assert_eq!(metrics.tcp_states["SYN_SENT"], 4);
+        assert_eq!(metrics.tcp_states["SYN_RECV"], 1);
+
+        // A quiet second
+        let metrics = monitor.observe(&table, tick + Duration::from_secs(1));
+        assert_eq!(metrics.new_per_sec, 0.0);
+        assert!(metrics.new_per_sec_by_process.is_empty());
+        assert_eq!(metrics.rate_history, vec![0.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rate_history_is_bounded() {
+        let start = Instant::now();
+        let mut monitor = HealthMonitor::default();
+        let mut metrics = HealthMetrics::default();
+        for second in 0..(RATE_HISTORY_LEN as u64 + 5) {
+            metrics = monitor.observe(&[], start + Duration::from_secs(second));
+        }
+        assert_eq!(metrics.rate_history.len(), RATE_HISTORY_LEN);
+        assert_eq!(metrics.half_open_ratio(), None);
+    }
+}
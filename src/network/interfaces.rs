@@ -0,0 +1,841 @@
+//! Network interface discovery
+//!
+//! Merges what pcap and the OS report about each interface: flags, MTU,
+//! addresses and kernel traffic counters. Backs `rustnet interfaces`, and
+//! gives the packet parser the local addresses it uses to tell which side of
+//! a flow is remote.
+
+use crate::network::mac;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Counters are read at most this often for the Interfaces tab
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Kernel traffic counters of one interface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+/// One interface as listed by `rustnet interfaces`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<&'static str>,
+    pub mtu: Option<u32>,
+    pub mac: Option<String>,
+    /// Addresses with their prefix length, e.g. `192.168.1.10/24`
+    pub addresses: Vec<String>,
+    /// None where the platform has no counters we can read
+    pub counters: Option<InterfaceCounters>,
+    /// Carries the default route
+    pub is_default: bool,
+}
+
+/// Throughput of one interface between two listings
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterfaceRates {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+impl InterfaceRates {
+    /// Throughput between two readings of an interface's counters
+    fn between(before: &InterfaceCounters, now: &InterfaceCounters, seconds: f64) -> Self {
+        Self {
+            rx_bytes_per_sec: now.rx_bytes.saturating_sub(before.rx_bytes) as f64 / seconds,
+            tx_bytes_per_sec: now.tx_bytes.saturating_sub(before.tx_bytes) as f64 / seconds,
+        }
+    }
+}
+
+/// An interface's counters and throughput, as shown on the Interfaces tab
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub counters: InterfaceCounters,
+    /// None until the counters have been read twice
+    pub rates: Option<InterfaceRates>,
+}
+
+/// Reads interface counters at most once a second and works out each
+/// interface's throughput between readings
+#[derive(Debug, Default)]
+pub struct InterfaceStatsTracker {
+    last_read: Option<(Instant, HashMap<String, InterfaceCounters>)>,
+    stats: Vec<InterfaceStats>,
+}
+
+impl InterfaceStatsTracker {
+    /// Every interface by name. `read_counters` is only called once the
+    /// last reading is a second old; until then the stats from it are
+    /// returned again.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        read_counters: impl FnOnce() -> Result<HashMap<String, InterfaceCounters>>,
+    ) -> Result<Vec<InterfaceStats>> {
+        if let Some((read_at, _)) = &self.last_read
+            && now.saturating_duration_since(*read_at) < STATS_INTERVAL
+        {
+            return Ok(self.stats.clone());
+        }
+
+        let counters = read_counters()?;
+        let previous = self.last_read.take();
+        self.stats = counters
+            .iter()
+            .map(|(name, now_counters)| InterfaceStats {
+                name: name.clone(),
+                counters: *now_counters,
+                rates: previous.as_ref().and_then(|(read_at, before)| {
+                    let seconds = now.saturating_duration_since(*read_at).as_secs_f64();
+                    Some(InterfaceRates::between(
+                        before.get(name)?,
+                        now_counters,
+                        seconds,
+                    ))
+                }),
+            })
+            .collect();
+        self.stats.sort_by(|a, b| a.name.cmp(&b.name));
+        self.last_read = Some((now, counters));
+        Ok(self.stats.clone())
+    }
+}
+
+/// An interface appearing or disappearing between two looks at the system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceChange {
+    Added(String),
+    Removed(String),
+}
+
+impl fmt::Display for InterfaceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterfaceChange::Added(name) => write!(f, "Interface {} appeared", name),
+            InterfaceChange::Removed(name) => write!(f, "Interface {} disappeared", name),
+        }
+    }
+}
+
+/// What changed from the interfaces in `previous` to those in `current`,
+/// removals first
+pub fn interface_changes(
+    previous: &BTreeSet<String>,
+    current: &BTreeSet<String>,
+) -> Vec<InterfaceChange> {
+    previous
+        .difference(current)
+        .cloned()
+        .map(InterfaceChange::Removed)
+        .chain(
+            current
+                .difference(previous)
+                .cloned()
+                .map(InterfaceChange::Added),
+        )
+        .collect()
+}
+
+/// A network this host has an address on
+#[derive(Debug, Clone, Copy)]
+pub struct LocalSubnet {
+    pub network: IpAddr,
+    pub prefix: u8,
+}
+
+impl LocalSubnet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix.min(32)))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix.min(128)))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// This host's addresses and the subnets they are on
+pub fn local_addresses() -> (HashSet<IpAddr>, Vec<LocalSubnet>) {
+    let mut local_ips = HashSet::new();
+    let mut local_subnets = Vec::new();
+    for iface in pnet_datalink::interfaces() {
+        for ip_network in iface.ips {
+            local_ips.insert(ip_network.ip());
+            local_subnets.push(LocalSubnet {
+                network: ip_network.ip(),
+                prefix: ip_network.prefix(),
+            });
+        }
+    }
+    (local_ips, local_subnets)
+}
+
+/// Every interface pcap can capture on or the OS has addresses for, by name
+pub fn list_interfaces() -> Result<Vec<InterfaceInfo>> {
+    let mut interfaces: BTreeMap<String, InterfaceInfo> = BTreeMap::new();
+
+    // pcap knows the capture-only devices (`any`, Npcap's loopback adapter)
+    // and has descriptions on Windows, where interface names are GUIDs
+    for device in pcap::Device::list()? {
+        let mut flags = Vec::new();
+        if device.flags.is_up() {
+            flags.push("UP");
+        }
+        if device.flags.is_running() {
+            flags.push("RUNNING");
+        }
+        if device.flags.is_loopback() {
+            flags.push("LOOPBACK");
+        }
+        if device.flags.is_wireless() {
+            flags.push("WIRELESS");
+        }
+        interfaces.insert(
+            device.name.clone(),
+            InterfaceInfo {
+                name: device.name,
+                description: device.desc,
+                flags,
+                addresses: device
+                    .addresses
+                    .iter()
+                    .map(|address| address.addr.to_string())
+                    .collect(),
+                ..Default::default()
+            },
+        );
+    }
+
+    // pnet has the prefix lengths and MAC addresses pcap leaves out
+    for iface in pnet_datalink::interfaces() {
+        let info = interfaces
+            .entry(iface.name.clone())
+            .or_insert_with(|| InterfaceInfo {
+                name: iface.name.clone(),
+                description: Some(iface.description.clone()).filter(|d| !d.is_empty()),
+                ..Default::default()
+            });
+        if info.flags.is_empty() {
+            if iface.is_up() {
+                info.flags.push("UP");
+            }
+            if iface.is_loopback() {
+                info.flags.push("LOOPBACK");
+            }
+        }
+        if iface.is_broadcast() {
+            info.flags.push("BROADCAST");
+        }
+        if iface.is_point_to_point() {
+            info.flags.push("POINTOPOINT");
+        }
+        if iface.is_multicast() {
+            info.flags.push("MULTICAST");
+        }
+        if !iface.ips.is_empty() {
+            info.addresses = iface.ips.iter().map(|ip| ip.to_string()).collect();
+        }
+        info.mac = iface
+            .mac
+            .map(|m| [m.0, m.1, m.2, m.3, m.4, m.5])
+            .filter(|octets| *octets != [0; 6])
+            .map(|octets| mac::format_mac(&octets));
+    }
+
+    let counters = interface_counters().unwrap_or_default();
+    let default_interface = default_route_interface();
+    for info in interfaces.values_mut() {
+        info.counters = counters.get(&info.name).copied();
+        info.mtu = mtu(&info.name);
+        info.is_default = default_interface.as_deref() == Some(info.name.as_str());
+    }
+    Ok(interfaces.into_values().collect())
+}
+
+/// Parse `/proc/net/dev` into counters by interface name
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_proc_net_dev(content: &str) -> HashMap<String, InterfaceCounters> {
+    content
+        .lines()
+        .filter_map(|line| {
+            // Header lines have no colon; old kernels print `eth0:1234` with
+            // no space after it
+            let (name, fields) = line.split_once(':')?;
+            let fields: Vec<u64> = fields
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .ok()?;
+            if fields.len() < 16 {
+                return None;
+            }
+            Some((
+                name.trim().to_string(),
+                InterfaceCounters {
+                    rx_bytes: fields[0],
+                    rx_packets: fields[1],
+                    rx_errors: fields[2],
+                    rx_dropped: fields[3],
+                    tx_bytes: fields[8],
+                    tx_packets: fields[9],
+                    tx_errors: fields[10],
+                    tx_dropped: fields[11],
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Interface of the IPv4 default route in `/proc/net/route`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_proc_net_route(content: &str) -> Option<String> {
+    content.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+        (fields.len() >= 8 && fields[1] == "00000000" && fields[7] == "00000000")
+            .then(|| fields[0].to_string())
+    })
+}
+
+/// Parse the `<Link#n>` rows of macOS `netstat -ibdn`, which hold each
+/// interface's counters. Its other rows list the interface's addresses.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub fn parse_netstat_ib(content: &str) -> HashMap<String, InterfaceCounters> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // Name Mtu Network Address Ipkts Ierrs Ibytes Opkts Oerrs Obytes Coll Drop
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if !fields.get(2)?.starts_with("<Link#") {
+                return None;
+            }
+            // Interfaces without a hardware address leave that column empty
+            let first = if fields.get(3)?.parse::<u64>().is_ok() {
+                3
+            } else {
+                4
+            };
+            let numbers: Vec<u64> = fields
+                .get(first..)?
+                .iter()
+                .map(|field| field.parse())
+                .collect::<Result<_, _>>()
+                .ok()?;
+            if numbers.len() < 6 {
+                return None;
+            }
+            Some((
+                // Interfaces that are down are marked with a `*`
+                fields[0].trim_end_matches('*').to_string(),
+                InterfaceCounters {
+                    rx_packets: numbers[0],
+                    rx_errors: numbers[1],
+                    rx_bytes: numbers[2],
+                    tx_packets: numbers[3],
+                    tx_errors: numbers[4],
+                    tx_bytes: numbers[5],
+                    tx_dropped: numbers.get(7).copied().unwrap_or(0),
+                    rx_dropped: 0,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Kernel traffic counters by interface name
+#[cfg(target_os = "linux")]
+pub fn interface_counters() -> Result<HashMap<String, InterfaceCounters>> {
+    use anyhow::Context;
+    let content =
+        std::fs::read_to_string("/proc/net/dev").context("failed to read /proc/net/dev")?;
+    Ok(parse_proc_net_dev(&content))
+}
+
+/// Kernel traffic counters by interface name
+#[cfg(target_os = "macos")]
+pub fn interface_counters() -> Result<HashMap<String, InterfaceCounters>> {
+    use anyhow::Context;
+    let output = std::process::Command::new("netstat")
+        .args(["-ibdn"])
+        .output()
+        .context("failed to run netstat")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("netstat failed with {}", output.status));
+    }
+    Ok(parse_netstat_ib(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Kernel traffic counters by interface name
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn interface_counters() -> Result<HashMap<String, InterfaceCounters>> {
+    Err(anyhow::anyhow!(
+        "interface counters aren't available on this platform"
+    ))
+}
+
+/// Names of the interfaces the kernel has right now, up or not
+#[cfg(target_os = "linux")]
+pub fn interface_names() -> Result<BTreeSet<String>> {
+    use anyhow::Context;
+    let mut names = BTreeSet::new();
+    for entry in std::fs::read_dir("/sys/class/net").context("failed to read /sys/class/net")? {
+        names.insert(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+/// Names of the interfaces the OS has right now, up or not
+#[cfg(not(target_os = "linux"))]
+pub fn interface_names() -> Result<BTreeSet<String>> {
+    Ok(pnet_datalink::interfaces()
+        .into_iter()
+        .map(|iface| iface.name)
+        .collect())
+}
+
+/// MTU of the interface called `name`, where the OS reports it
+#[cfg(target_os = "linux")]
+pub fn mtu(name: &str) -> Option<u32> {
+    std::fs::read_to_string(format!("/sys/class/net/{}/mtu", name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mtu(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn default_route_interface() -> Option<String> {
+    std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|content| parse_proc_net_route(&content))
+}
+
+/// Without a route table to read, pcap's default device is the best guess
+#[cfg(not(target_os = "linux"))]
+fn default_route_interface() -> Option<String> {
+    pcap::Device::lookup()
+        .ok()
+        .flatten()
+        .map(|device| device.name)
+}
+
+/// Throughput of each interface from two listings `elapsed` apart
+pub fn rates(
+    previous: &[InterfaceInfo],
+    current: &[InterfaceInfo],
+    elapsed: Duration,
+) -> HashMap<String, InterfaceRates> {
+    let seconds = elapsed.as_secs_f64();
+    if seconds <= 0.0 {
+        return HashMap::new();
+    }
+    current
+        .iter()
+        .filter_map(|info| {
+            let now = info.counters?;
+            let before = previous
+                .iter()
+                .find(|old| old.name == info.name)?
+                .counters?;
+            Some((
+                info.name.clone(),
+                InterfaceRates::between(&before, &now, seconds),
+            ))
+        })
+        .collect()
+}
+
+/// Plain-text table, one interface per row with extra addresses on the
+/// lines below it. `*` marks the default-route interface.
+pub fn render_table(
+    interfaces: &[InterfaceInfo],
+    rates: Option<&HashMap<String, InterfaceRates>>,
+) -> String {
+    let name_width = interfaces
+        .iter()
+        .map(|info| info.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let flags_width = interfaces
+        .iter()
+        .map(|info| info.flags.join(",").len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let address_width = interfaces
+        .iter()
+        .flat_map(|info| info.addresses.iter().map(String::len))
+        .max()
+        .unwrap_or(0)
+        .max(9);
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "  {:name_width$}  {:flags_width$}  {:>5}  {:address_width$}  {:>11}  {:>7}  {:>11}  {:>7}",
+        "NAME", "FLAGS", "MTU", "ADDRESSES", "RX", "RX ERR", "TX", "TX ERR"
+    );
+    if rates.is_some() {
+        let _ = write!(out, "  {:>11}  {:>11}", "RX/s", "TX/s");
+    }
+    out.push('\n');
+
+    for info in interfaces {
+        let counter = |field: fn(&InterfaceCounters) -> u64| {
+            info.counters
+                .map(|counters| field(&counters).to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let bytes = |field: fn(&InterfaceCounters) -> u64| {
+            info.counters
+                .map(|counters| crate::ui::format_bytes(field(&counters)))
+                .unwrap_or_else(|| "-".to_string())
+        };
+        let mut addresses = info.addresses.iter();
+        let row = format!(
+            "{} {:name_width$}  {:flags_width$}  {:>5}  {:address_width$}  {:>11}  {:>7}  {:>11}  {:>7}",
+            if info.is_default { '*' } else { ' ' },
+            info.name,
+            info.flags.join(","),
+            info.mtu
+                .map(|mtu| mtu.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            addresses.next().map(String::as_str).unwrap_or("-"),
+            bytes(|c| c.rx_bytes),
+            counter(|c| c.rx_errors),
+            bytes(|c| c.tx_bytes),
+            counter(|c| c.tx_errors),
+        );
+        out.push_str(&row);
+        if let Some(rates) = rates {
+            let rate = rates.get(&info.name).copied().unwrap_or_default();
+            let _ = write!(
+                out,
+                "  {:>11}  {:>11}",
+                crate::ui::format_rate(rate.rx_bytes_per_sec),
+                crate::ui::format_rate(rate.tx_bytes_per_sec)
+            );
+        }
+        out.push('\n');
+        for address in addresses {
+            let _ = writeln!(
+                out,
+                "  {:name_width$}  {:flags_width$}  {:>5}  {}",
+                "", "", "", address
+            );
+        }
+    }
+    out
+}
+
+/// JSON array with one object per interface. Counters are null where the
+/// platform has none; rate fields only appear when `rates` is given.
+pub fn render_json(
+    interfaces: &[InterfaceInfo],
+    rates: Option<&HashMap<String, InterfaceRates>>,
+) -> String {
+    let objects: Vec<String> = interfaces
+        .iter()
+        .map(|info| {
+            let mut fields = vec![
+                format!("\"name\":{}", json_string(&info.name)),
+                format!(
+                    "\"description\":{}",
+                    info.description
+                        .as_deref()
+                        .map_or("null".to_string(), json_string)
+                ),
+                format!(
+                    "\"flags\":[{}]",
+                    info.flags
+                        .iter()
+                        .map(|flag| json_string(flag))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+                format!(
+                    "\"mtu\":{}",
+                    info.mtu.map_or("null".to_string(), |mtu| mtu.to_string())
+                ),
+                format!(
+                    "\"mac\":{}",
+                    info.mac.as_deref().map_or("null".to_string(), json_string)
+                ),
+                format!(
+                    "\"addresses\":[{}]",
+                    info.addresses
+                        .iter()
+                        .map(|address| json_string(address))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+                format!("\"default\":{}", info.is_default),
+                format!(
+                    "\"counters\":{}",
+                    info.counters.map_or("null".to_string(), |c| format!(
+                        "{{\"rx_bytes\":{},\"rx_packets\":{},\"rx_errors\":{},\"rx_dropped\":{},\
+                         \"tx_bytes\":{},\"tx_packets\":{},\"tx_errors\":{},\"tx_dropped\":{}}}",
+                        c.rx_bytes,
+                        c.rx_packets,
+                        c.rx_errors,
+                        c.rx_dropped,
+                        c.tx_bytes,
+                        c.tx_packets,
+                        c.tx_errors,
+                        c.tx_dropped
+                    ))
+                ),
+            ];
+            if let Some(rates) = rates {
+                let rate = rates.get(&info.name).copied().unwrap_or_default();
+                fields.push(format!("\"rx_bytes_per_sec\":{:.0}", rate.rx_bytes_per_sec));
+                fields.push(format!("\"tx_bytes_per_sec\":{:.0}", rate.tx_bytes_per_sec));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    const PROC_NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  123456     789    0    0    0     0          0         0   123456     789    0    0    0     0       0          0
+  eth0: 98765432  65432    3    7    0     0          0       120 12345678  43210    1    2    0     0       0          0
+wlan0:1000 10 0 0 0 0 0 0 2000 20 0 0 0 0 0 0
+";
+
+    #[test]
+    fn test_parse_proc_net_dev() {
+        let counters = parse_proc_net_dev(PROC_NET_DEV);
+        assert_eq!(counters.len(), 3);
+        assert_eq!(
+            counters["eth0"],
+            InterfaceCounters {
+                rx_bytes: 98765432,
+                rx_packets: 65432,
+                rx_errors: 3,
+                rx_dropped: 7,
+                tx_bytes: 12345678,
+                tx_packets: 43210,
+                tx_errors: 1,
+                tx_dropped: 2,
+            }
+        );
+        assert_eq!(counters["lo"].tx_packets, 789);
+        assert_eq!(counters["wlan0"].tx_bytes, 2000);
+
+        // Truncated rows are skipped rather than misread
+        assert!(parse_proc_net_dev("  eth0: 1 2 3\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_netstat_ib() {
+        let netstat = "\
+Name       Mtu   Network       Address            Ipkts Ierrs     Ibytes    Opkts Oerrs     Obytes  Coll Drop
+lo0        16384 <Link#1>                        123456     0   98765432   123456     0   98765432     0    0
+lo0        16384 127           127.0.0.1         123456     -   98765432   123456     -   98765432     -    -
+en0        1500  <Link#6>    a4:83:e7:12:34:56  5432100     3 6543210987  2345678     1  345678901     0   12
+en0        1500  192.168.1     192.168.1.20      5432100     -          -  2345678     -          -     -    -
+en5*       1500  <Link#9>    a4:83:e7:65:43:21        0     0          0        0     0          0     0    0
+";
+        let counters = parse_netstat_ib(netstat);
+        assert_eq!(counters.len(), 3);
+        assert_eq!(
+            counters["en0"],
+            InterfaceCounters {
+                rx_bytes: 6543210987,
+                rx_packets: 5432100,
+                rx_errors: 3,
+                rx_dropped: 0,
+                tx_bytes: 345678901,
+                tx_packets: 2345678,
+                tx_errors: 1,
+                tx_dropped: 12,
+            }
+        );
+        assert_eq!(counters["lo0"].rx_bytes, 98765432);
+        assert!(counters.contains_key("en5"));
+    }
+
+    #[test]
+    fn test_stats_tracker_rates() {
+        let reading = |rx_bytes, tx_bytes| {
+            move || {
+                let counters = InterfaceCounters {
+                    rx_bytes,
+                    tx_bytes,
+                    ..Default::default()
+                };
+                Ok(HashMap::from([("eth0".to_string(), counters)]))
+            }
+        };
+        let mut tracker = InterfaceStatsTracker::default();
+        let start = Instant::now();
+
+        let first = tracker.update(start, reading(1000, 500)).unwrap();
+        assert_eq!(first[0].name, "eth0");
+        assert_eq!(first[0].rates, None);
+
+        // Read again only once a second has passed
+        let early = tracker.update(start + Duration::from_millis(200), || {
+            Err(anyhow!("read too soon"))
+        });
+        assert_eq!(early.unwrap(), first);
+
+        let later = tracker
+            .update(start + Duration::from_secs(2), reading(5000, 2500))
+            .unwrap();
+        assert_eq!(later[0].counters.rx_bytes, 5000);
+        assert_eq!(
+            later[0].rates,
+            Some(InterfaceRates {
+                rx_bytes_per_sec: 2000.0,
+                tx_bytes_per_sec: 1000.0,
+            })
+        );
+
+        let failed = tracker.update(start + Duration::from_secs(4), || {
+            Err(anyhow!("netstat failed"))
+        });
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn test_interface_changes() {
+        let names = |list: &[&str]| -> BTreeSet<String> {
+            list.iter().map(|name| name.to_string()).collect()
+        };
+        let before = names(&["eth0", "lo", "wlan0"]);
+        let after = names(&["docker0", "eth0", "lo", "veth1a2b"]);
+
+        assert_eq!(
+            interface_changes(&before, &after),
+            vec![
+                InterfaceChange::Removed("wlan0".to_string()),
+                InterfaceChange::Added("docker0".to_string()),
+                InterfaceChange::Added("veth1a2b".to_string()),
+            ]
+        );
+        assert!(interface_changes(&after, &after).is_empty());
+        assert_eq!(
+            InterfaceChange::Removed("wlan0".to_string()).to_string(),
+            "Interface wlan0 disappeared"
+        );
+    }
+
+    #[test]
+    fn test_parse_proc_net_route() {
+        let route = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+docker0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+        assert_eq!(parse_proc_net_route(route), Some("eth0".to_string()));
+        assert_eq!(parse_proc_net_route(route.lines().next().unwrap()), None);
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let interfaces = vec![
+            InterfaceInfo {
+                name: "eth0".to_string(),
+                flags: vec!["UP", "RUNNING"],
+                mtu: Some(1500),
+                mac: Some("b8:27:eb:12:34:56".to_string()),
+                addresses: vec!["192.168.1.10/24".to_string()],
+                counters: Some(InterfaceCounters {
+                    rx_bytes: 2048,
+                    tx_bytes: 1024,
+                    ..Default::default()
+                }),
+                is_default: true,
+                ..Default::default()
+            },
+            InterfaceInfo {
+                name: "any".to_string(),
+                description: Some("Pseudo-device \"any\"".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            render_json(&interfaces, None),
+            "[{\"name\":\"eth0\",\"description\":null,\"flags\":[\"UP\",\"RUNNING\"],\
+             \"mtu\":1500,\"mac\":\"b8:27:eb:12:34:56\",\"addresses\":[\"192.168.1.10/24\"],\
+             \"default\":true,\"counters\":{\"rx_bytes\":2048,\"rx_packets\":0,\"rx_errors\":0,\
+             \"rx_dropped\":0,\"tx_bytes\":1024,\"tx_packets\":0,\"tx_errors\":0,\"tx_dropped\":0}},\
+             {\"name\":\"any\",\"description\":\"Pseudo-device \\\"any\\\"\",\"flags\":[],\
+             \"mtu\":null,\"mac\":null,\"addresses\":[],\"default\":false,\"counters\":null}]"
+        );
+
+        let mut later = interfaces.clone();
+        later[0].counters = Some(InterfaceCounters {
+            rx_bytes: 4096,
+            tx_bytes: 1536,
+            ..Default::default()
+        });
+        let rates = rates(&interfaces, &later, Duration::from_secs(2));
+        assert_eq!(rates.len(), 1);
+        let json = render_json(&later, Some(&rates));
+        assert!(json.contains("\"counters\":null,\"rx_bytes_per_sec\":0,\"tx_bytes_per_sec\":0}"));
+        assert!(json.contains("\"rx_bytes_per_sec\":1024,\"tx_bytes_per_sec\":256}"));
+    }
+}
@@ -0,0 +1,144 @@
+//! MAC addresses of hosts on the local network
+//!
+//! Vendors come from a compiled-in copy of the IEEE OUI registry
+//! (`assets/oui`). The neighbor table remembers the MAC seen for each LAN
+//! address in Ethernet headers and ARP, so flows captured without
+//! link-layer headers still show which device they belong to.
+
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::LazyLock;
+
+pub type MacAddr = [u8; 6];
+
+const OUI_DATA: &str = include_str!("../../assets/oui");
+
+/// Hosts kept in a `NeighborTable`. A LAN segment rarely comes close; the
+/// cap keeps spoofed ARP floods from growing it without bound.
+const MAX_NEIGHBORS: usize = 4096;
+
+/// Vendor names by 24-bit OUI
+static VENDORS: LazyLock<HashMap<u32, &'static str>> = LazyLock::new(|| {
+    OUI_DATA
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (prefix, vendor) = line.split_once(' ')?;
+            Some((u32::from_str_radix(prefix, 16).ok()?, vendor.trim()))
+        })
+        .collect()
+});
+
+/// Organisation the IEEE assigned the address's prefix to
+pub fn vendor(mac: &MacAddr) -> Option<&'static str> {
+    VENDORS
+        .get(&u32::from_be_bytes([0, mac[0], mac[1], mac[2]]))
+        .copied()
+}
+
+/// Whether the address was set locally, as for VMs, containers and
+/// randomized Wi-Fi addresses, rather than assigned by the IEEE
+pub fn is_locally_administered(mac: &MacAddr) -> bool {
+    mac[0] & 0x02 != 0
+}
+
+/// Whether the address can belong to a single host: not all zeros, and
+/// neither broadcast nor multicast
+pub fn is_unicast(mac: &MacAddr) -> bool {
+    mac[0] & 0x01 == 0 && *mac != [0; 6]
+}
+
+pub fn format_mac(mac: &MacAddr) -> String {
+    mac.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// The address and its vendor, e.g. `b8:27:eb:12:34:56 (Raspberry Pi Foundation)`
+pub fn describe(mac: &MacAddr) -> String {
+    let address = format_mac(mac);
+    match vendor(mac) {
+        Some(vendor) => format!("{} ({})", address, vendor),
+        None if is_locally_administered(mac) => format!("{} (locally administered)", address),
+        None => address,
+    }
+}
+
+/// MAC addresses of LAN hosts by IP, shared by the packet processors
+#[derive(Debug, Default)]
+pub struct NeighborTable {
+    macs: DashMap<IpAddr, MacAddr>,
+}
+
+impl NeighborTable {
+    /// Remember that `ip` was last seen at `mac`
+    pub fn learn(&self, ip: IpAddr, mac: MacAddr) {
+        // ARP probes come from 0.0.0.0
+        if ip.is_unspecified() || !is_unicast(&mac) {
+            return;
+        }
+        // Most packets confirm what is already known, which only needs a read lock
+        if self.macs.get(&ip).is_some_and(|known| *known == mac) {
+            return;
+        }
+        if self.macs.len() >= MAX_NEIGHBORS && !self.macs.contains_key(&ip) {
+            return;
+        }
+        self.macs.insert(ip, mac);
+    }
+
+    pub fn get(&self, ip: &IpAddr) -> Option<MacAddr> {
+        self.macs.get(ip).map(|mac| *mac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PI: MacAddr = [0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56];
+
+    #[test]
+    fn test_vendor_lookup() {
+        assert_eq!(vendor(&PI), Some("Raspberry Pi Foundation"));
+        assert_eq!(describe(&PI), "b8:27:eb:12:34:56 (Raspberry Pi Foundation)");
+        assert_eq!(
+            vendor(&[0x00, 0x50, 0x56, 0x00, 0x00, 0x01]),
+            Some("VMware, Inc.")
+        );
+
+        // Docker bridge addresses are not in the registry
+        let container = [0x02, 0x42, 0xac, 0x11, 0x00, 0x02];
+        assert_eq!(vendor(&container), None);
+        assert_eq!(
+            describe(&container),
+            "02:42:ac:11:00:02 (locally administered)"
+        );
+    }
+
+    #[test]
+    fn test_every_oui_entry_parses() {
+        let entries = OUI_DATA
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .count();
+        assert_eq!(VENDORS.len(), entries);
+    }
+
+    #[test]
+    fn test_neighbor_table_ignores_non_host_addresses() {
+        let neighbors = NeighborTable::default();
+        let ip: IpAddr = "192.168.1.20".parse().unwrap();
+
+        neighbors.learn(ip, [0xff; 6]);
+        neighbors.learn(ip, [0; 6]);
+        neighbors.learn("0.0.0.0".parse().unwrap(), PI);
+        assert_eq!(neighbors.get(&ip), None);
+        assert!(neighbors.macs.is_empty());
+
+        neighbors.learn(ip, PI);
+        assert_eq!(neighbors.get(&ip), Some(PI));
+    }
+}
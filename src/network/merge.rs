@@ -1,48 +1,119 @@
 // src/network/merge.rs - Connection merging and update utilities
 
-use log::{debug, info, warn};
-use std::time::{Instant, SystemTime};
+use log::{Level, debug, log_enabled, trace, warn};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
 
-use crate::network::dpi::DpiResult;
+use crate::network::dpi::{self, DpiResult};
 use crate::network::parser::{ParsedPacket, TcpFlags};
 use crate::network::types::{
     ApplicationProtocol, Connection, DnsInfo, DpiInfo, HttpInfo, HttpsInfo, ProtocolState,
-    QuicConnectionState, QuicInfo, SshInfo, TcpState,
+    QuicConnectionState, QuicInfo, SshInfo, TcpState, TlsHandshakeReassembler, TlsInfo, UdpState,
 };
 
+/// Log target for everything emitted while folding packets into connections
+const LOG_TARGET: &str = "rustnet::merge";
+
+/// Gaps between keepalive probes; stacks are commonly set to probe every
+/// minute or so
+const KEEPALIVE_INTERVAL: RangeInclusive<Duration> =
+    Duration::from_secs(40)..=Duration::from_secs(80);
+
+/// How soon after a probe the peer's ACK counts as its reply
+const KEEPALIVE_REPLY: Duration = Duration::from_secs(2);
+
+/// Probes in a row, with no payload in between, before a flow counts as
+/// held open by keepalives alone
+const KEEPALIVE_MIN_PROBES: u32 = 3;
+
+/// How packets and DPI results are folded into connections, from the app's
+/// `Config`
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// Cipher suites flagged as weak on top of the built-in list
+    pub weak_cipher_suites: HashSet<u16>,
+    /// Spot keepalive probes. When off they count towards rates like any
+    /// other packet and no flow is marked keepalive-only.
+    pub keepalive_detection: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            weak_cipher_suites: HashSet::new(),
+            keepalive_detection: true,
+        }
+    }
+}
+
 /// Update TCP connection state based on observed flags and current state
-/// This implements the TCP state machine according to RFC 793
+/// This implements the TCP state machine according to RFC 9293 (section 3.3.2),
+/// from the point of view of the local endpoint
 fn update_tcp_state(current_state: TcpState, flags: &TcpFlags, is_outgoing: bool) -> TcpState {
-    debug!(
+    trace!(
+        target: LOG_TARGET,
         "Updating TCP state: current_state={:?}, flags={:?}, is_outgoing={}",
         current_state, flags, is_outgoing
     );
 
-    match (current_state, flags.syn, flags.ack, flags.fin, flags.rst) {
-        // Connection establishment - three-way handshake
-        (TcpState::Unknown, true, false, false, false) if !is_outgoing => TcpState::SynReceived,
-        (TcpState::Unknown, true, false, false, false) if is_outgoing => TcpState::SynSent,
-        (TcpState::Listen, true, false, false, false) if !is_outgoing => TcpState::SynReceived,
-        (TcpState::Listen, true, false, false, false) if is_outgoing => TcpState::SynSent,
-        (TcpState::SynSent, true, true, false, false) if !is_outgoing => TcpState::Established,
-        (TcpState::SynReceived, false, true, false, false) if is_outgoing => TcpState::Established,
-
-        // This might happen if we start parsing connections after the SYN-ACK
-        (TcpState::Unknown, false, true, false, false) => TcpState::Established,
-        (TcpState::Unknown, false, true, true, false) => TcpState::Established,
-
-        // Connection termination - normal close
-        (TcpState::Established, false, _, true, false) if is_outgoing => TcpState::FinWait1,
-        (TcpState::Established, false, _, true, false) if !is_outgoing => TcpState::CloseWait,
-        (TcpState::FinWait1, false, true, false, false) if !is_outgoing => TcpState::FinWait2,
-        (TcpState::FinWait1, false, _, true, false) if !is_outgoing => TcpState::Closing,
-        (TcpState::FinWait2, false, _, true, false) if !is_outgoing => TcpState::TimeWait,
-        (TcpState::CloseWait, false, _, true, false) if is_outgoing => TcpState::LastAck,
-        (TcpState::LastAck, false, true, false, false) if !is_outgoing => TcpState::Closed,
-        (TcpState::Closing, false, true, false, false) if !is_outgoing => TcpState::TimeWait,
-
-        // Connection reset
-        (_, _, _, _, true) => TcpState::Closed,
+    // A reset ends the connection from any state, including when it is the
+    // first packet we see
+    if flags.rst {
+        return TcpState::Reset;
+    }
+
+    let incoming = !is_outgoing;
+    match (current_state, flags.syn, flags.ack, flags.fin) {
+        // Connection establishment - three-way handshake. A fresh SYN after a
+        // finished connection means the 4-tuple is being reused.
+        (
+            TcpState::Unknown
+            | TcpState::Listen
+            | TcpState::TimeWait
+            | TcpState::Closed
+            | TcpState::Reset,
+            true,
+            false,
+            false,
+        ) => {
+            if is_outgoing {
+                TcpState::SynSent
+            } else {
+                TcpState::SynReceived
+            }
+        }
+        (TcpState::SynSent, true, true, false) if incoming => TcpState::Established,
+        // Simultaneous open: the peer's SYN crossed ours
+        (TcpState::SynSent, true, false, false) if incoming => TcpState::SynReceived,
+        (TcpState::SynReceived, true, true, false) if incoming => TcpState::Established,
+        (TcpState::SynReceived, false, true, false) => TcpState::Established,
+
+        // We started parsing after the SYN
+        (TcpState::Unknown, true, true, false) if is_outgoing => TcpState::SynReceived,
+        (TcpState::Unknown, true, true, false) if incoming => TcpState::Established,
+        (TcpState::Unknown, false, true, false) => TcpState::Established,
+
+        // Connection termination. Almost every FIN carries ACK, so the ACK bit
+        // only matters where it tells us our own FIN was acknowledged. A FIN seen
+        // before any handshake means we joined the connection mid-stream.
+        (TcpState::Unknown | TcpState::SynReceived | TcpState::Established, false, _, true) => {
+            if is_outgoing {
+                TcpState::FinWait1
+            } else {
+                TcpState::CloseWait
+            }
+        }
+        (TcpState::FinWait1, false, true, false) if incoming => TcpState::FinWait2,
+        // Without sequence numbers we take a FIN|ACK to acknowledge our FIN too
+        (TcpState::FinWait1, false, true, true) if incoming => TcpState::TimeWait,
+        // Simultaneous close: the peer's FIN crossed ours
+        (TcpState::FinWait1, false, false, true) if incoming => TcpState::Closing,
+        (TcpState::FinWait2, false, _, true) if incoming => TcpState::TimeWait,
+        (TcpState::Closing, false, true, _) if incoming => TcpState::TimeWait,
+        (TcpState::CloseWait, false, _, true) if is_outgoing => TcpState::LastAck,
+        (TcpState::LastAck, false, true, _) if incoming => TcpState::Closed,
 
         // Keep current state if no state transition
         _ => current_state,
@@ -53,60 +124,101 @@ fn update_tcp_state(current_state: TcpState, flags: &TcpFlags, is_outgoing: bool
 pub fn merge_packet_into_connection(
     mut conn: Connection,
     parsed: &ParsedPacket,
-    now: SystemTime,
+    options: &MergeOptions,
 ) -> Connection {
-    // Update timing
-    conn.last_activity = now;
+    let previous_activity = conn.last_activity_instant;
+
+    // Update timing from the capture time; processor threads may hand us
+    // packets slightly out of order, so never move backwards
+    conn.last_activity = conn.last_activity.max(parsed.timestamp);
+    conn.last_activity_instant = conn.last_activity_instant.max(parsed.instant);
 
     // Update packet counts and bytes
     if parsed.is_outgoing {
-        conn.packets_sent += 1;
+        conn.packets_sent += parsed.segments as u64;
         conn.bytes_sent += parsed.packet_len as u64;
     } else {
-        conn.packets_received += 1;
+        conn.packets_received += parsed.segments as u64;
         conn.bytes_received += parsed.packet_len as u64;
     }
 
+    // Before the retransmit check moves the highest sequence number sent
+    track_rtt(&mut conn, parsed);
+
+    // Keepalives count as packets and bytes but not towards the rate
+    let keepalive =
+        options.keepalive_detection && track_keepalive(&mut conn, parsed, previous_activity);
+    if keepalive {
+        conn.rate_tracker
+            .initialize_with_counts(conn.bytes_sent, conn.bytes_received);
+    } else {
+        // Rather than count probes, which resend a byte on purpose
+        track_retransmits(&mut conn, parsed);
+    }
+
     // Update protocol state (from packet flags/state)
-    if parsed.tcp_flags.is_some() {
+    if let Some(tcp_flags) = &parsed.tcp_flags {
         let current_tcp_state = match conn.protocol_state {
             ProtocolState::Tcp(state) => state,
             _ => {
-                warn!("Merging TCP packet into non-TCP connection, resetting to Unknown state");
+                debug!(
+                    target: LOG_TARGET,
+                    "Merging TCP packet into non-TCP connection, resetting to Unknown state"
+                );
                 TcpState::Unknown
             }
         };
 
-        let new_tcp_state = update_tcp_state(
-            current_tcp_state,
-            &parsed.tcp_flags.unwrap(),
-            parsed.is_outgoing,
-        );
+        let new_tcp_state = update_tcp_state(current_tcp_state, tcp_flags, parsed.is_outgoing);
 
         if current_tcp_state != new_tcp_state {
             debug!(
+                target: LOG_TARGET,
                 "TCP state transition: {:?} -> {:?}",
                 current_tcp_state, new_tcp_state
             );
         }
 
         conn.protocol_state = ProtocolState::Tcp(new_tcp_state);
-    } else {
-        // If no TCP flags, keep existing state or use the one from packet
-        match (&conn.protocol_state, &parsed.protocol_state) {
-            (ProtocolState::Tcp(_), _) => {
-                // Keep existing TCP state if we have it
-            }
-            _ => {
-                // Use the state from the packet for non-TCP protocols
-                conn.protocol_state = parsed.protocol_state;
-            }
+    } else if let ProtocolState::Udp(UdpState::Unconnected) = conn.protocol_state {
+        // A UDP flow becomes a conversation once both sides have sent something
+        if conn.packets_sent > 0 && conn.packets_received > 0 {
+            conn.protocol_state = ProtocolState::Udp(UdpState::Active);
         }
+    } else if !matches!(
+        conn.protocol_state,
+        ProtocolState::Tcp(_) | ProtocolState::Udp(_)
+    ) {
+        // Only ICMP and ARP take their state from the packet; TCP and UDP
+        // connections work theirs out from the traffic
+        conn.protocol_state = parsed.protocol_state;
     }
 
     // Update DPI info if available
     if let Some(dpi_result) = &parsed.dpi_result {
-        merge_dpi_info(&mut conn, dpi_result);
+        merge_dpi_result(&mut conn, dpi_result, parsed.instant, options);
+    }
+
+    // Keep the guess from the first SYN or SYN-ACK the peer sent
+    if conn.remote_os_guess.is_none() {
+        conn.remote_os_guess.clone_from(&parsed.os_guess);
+    }
+
+    // A LAN address can move to another device, so the latest MAC wins
+    if parsed.remote_mac.is_some() {
+        conn.remote_mac = parsed.remote_mac;
+    }
+    if parsed.vlan_id.is_some() {
+        conn.vlan_id = parsed.vlan_id;
+    }
+    if parsed.tunnel.is_some() {
+        conn.tunnel = parsed.tunnel;
+    }
+    record_sctp_addresses(&mut conn, parsed);
+
+    // Once tagged, a flow stays tagged after the scan ends
+    if parsed.scan.is_some() {
+        conn.scan = parsed.scan;
     }
 
     // Update PKTAP process metadata if available
@@ -116,7 +228,8 @@ pub fn merge_packet_into_connection(
             None => {
                 // First time setting process name - this becomes immutable
                 conn.process_name = Some(new_process_name.clone());
-                info!(
+                debug!(
+                    target: LOG_TARGET,
                     "🔒 Set IMMUTABLE process name for connection {} from PKTAP: '{}' (len:{})",
                     conn.key(),
                     new_process_name,
@@ -127,26 +240,33 @@ pub fn merge_packet_into_connection(
                 // Process name is already set - it's now IMMUTABLE
                 // Log the attempt but NEVER change it
                 if existing_name != new_process_name {
-                    warn!(
-                        "🚫 IMMUTABILITY VIOLATION: Attempt to change process name for {} from '{}' to '{}' - REJECTED",
-                        conn.key(),
-                        existing_name,
-                        new_process_name
-                    );
-                    debug!(
-                        "🔒 Existing: '{}' (len:{}, bytes:{:?})",
-                        existing_name,
-                        existing_name.len(),
-                        existing_name.as_bytes()
-                    );
-                    debug!(
-                        "🚫 Rejected: '{}' (len:{}, bytes:{:?})",
-                        new_process_name,
-                        new_process_name.len(),
-                        new_process_name.as_bytes()
-                    );
+                    // Only pay for the key and byte dumps when someone is listening
+                    if log_enabled!(target: LOG_TARGET, Level::Debug) {
+                        debug!(
+                            target: LOG_TARGET,
+                            "🚫 IMMUTABILITY VIOLATION: Attempt to change process name for {} from '{}' to '{}' - REJECTED",
+                            conn.key(),
+                            existing_name,
+                            new_process_name
+                        );
+                        trace!(
+                            target: LOG_TARGET,
+                            "🔒 Existing: '{}' (len:{}, bytes:{:?})",
+                            existing_name,
+                            existing_name.len(),
+                            existing_name.as_bytes()
+                        );
+                        trace!(
+                            target: LOG_TARGET,
+                            "🚫 Rejected: '{}' (len:{}, bytes:{:?})",
+                            new_process_name,
+                            new_process_name.len(),
+                            new_process_name.as_bytes()
+                        );
+                    }
                 } else {
-                    debug!(
+                    trace!(
+                        target: LOG_TARGET,
                         "✅ Process name confirmed unchanged for {}: '{}'",
                         conn.key(),
                         existing_name
@@ -162,14 +282,16 @@ pub fn merge_packet_into_connection(
             None => {
                 // First time setting PID - this becomes immutable
                 conn.pid = Some(new_pid);
-                info!(
+                debug!(
+                    target: LOG_TARGET,
                     "🔒 Set IMMUTABLE process ID for connection {} from PKTAP: {}",
                     conn.key(),
                     new_pid
                 );
             }
             Some(existing_pid) if existing_pid != new_pid => {
-                warn!(
+                debug!(
+                    target: LOG_TARGET,
                     "🚫 IMMUTABILITY VIOLATION: Attempt to change PID for {} from {} to {} - REJECTED",
                     conn.key(),
                     existing_pid,
@@ -178,7 +300,8 @@ pub fn merge_packet_into_connection(
                 // NEVER update - PID is immutable once set
             }
             Some(existing_pid) => {
-                debug!(
+                trace!(
+                    target: LOG_TARGET,
                     "✅ Process ID confirmed unchanged for {}: {}",
                     conn.key(),
                     existing_pid
@@ -188,61 +311,232 @@ pub fn merge_packet_into_connection(
     }
 
     // Update rate calculations
-    update_connection_rates(&mut conn);
+    update_connection_rates(&mut conn, parsed.instant);
 
     conn
 }
 
+/// Whether `parsed` is a keepalive probe on an established TCP connection
+/// or the peer's reply to one, updating the connection's keepalive streak.
+/// A probe is a bare ACK with at most one byte of payload, sent after the
+/// flow has been quiet for a keepalive interval. Any payload ends the streak.
+fn track_keepalive(
+    conn: &mut Connection,
+    parsed: &ParsedPacket,
+    previous_activity: Instant,
+) -> bool {
+    let Some(flags) = &parsed.tcp_flags else {
+        return false;
+    };
+    if !matches!(
+        conn.protocol_state,
+        ProtocolState::Tcp(TcpState::Established)
+    ) {
+        return false;
+    }
+
+    // Some stacks resend the last byte already acknowledged as the probe
+    let bare_ack = flags.ack && !flags.syn && !flags.fin && !flags.rst;
+    if !bare_ack || parsed.payload_len > 1 {
+        conn.keepalive_probes = 0;
+        conn.keepalive_only = false;
+        return false;
+    }
+
+    let gap = parsed.instant.saturating_duration_since(previous_activity);
+    if KEEPALIVE_INTERVAL.contains(&gap) {
+        conn.keepalive_probes += 1;
+        conn.last_keepalive_probe = Some(parsed.instant);
+        if !conn.keepalive_only && conn.keepalive_probes >= KEEPALIVE_MIN_PROBES {
+            conn.keepalive_only = true;
+            debug!(
+                target: LOG_TARGET,
+                "Connection {} is held open by keepalives only",
+                conn.key()
+            );
+        }
+        true
+    } else {
+        conn.last_keepalive_probe == Some(previous_activity) && gap <= KEEPALIVE_REPLY
+    }
+}
+
+/// Count a TCP segment whose sequence space ends at or before the highest
+/// end seen in its direction as a retransmission, or move that end forward.
+/// Only segments taking up sequence space count: bare ACKs repeat the
+/// sequence number as a matter of course.
+fn track_retransmits(conn: &mut Connection, parsed: &ParsedPacket) {
+    let (Some(flags), Some(seq)) = (&parsed.tcp_flags, parsed.tcp_seq) else {
+        return;
+    };
+    let len = parsed.payload_len as u32 + u32::from(flags.syn) + u32::from(flags.fin);
+    if len == 0 || flags.rst {
+        return;
+    }
+
+    let end = seq.wrapping_add(len);
+    let highest = if parsed.is_outgoing {
+        &mut conn.seq_end_sent
+    } else {
+        &mut conn.seq_end_received
+    };
+    match highest {
+        Some(seen) if end.wrapping_sub(*seen) as i32 <= 0 => conn.retransmit_count += 1,
+        _ => *highest = Some(end),
+    }
+}
+
+/// Fold round-trip samples into the connection's RTT estimate, smoothed as
+/// in RFC 6298. Flows using TCP timestamps are timed by them, others by
+/// their sequence and acknowledgment numbers. Must run before
+/// `track_retransmits` sees the packet.
+fn track_rtt(conn: &mut Connection, parsed: &ParsedPacket) {
+    match parsed.tcp_timestamps {
+        Some(timestamps) => track_rtt_by_timestamps(conn, parsed, timestamps),
+        None => track_rtt_by_ack(conn, parsed),
+    }
+}
+
+fn add_rtt_sample(conn: &mut Connection, sample: Duration) {
+    conn.rtt_estimate = Some(match conn.rtt_estimate {
+        Some(srtt) => (srtt * 7 + sample) / 8,
+        None => sample,
+    });
+}
+
+/// Time an outgoing segment's TCP timestamp until the peer echoes it. Only
+/// one segment is timed at a time.
+fn track_rtt_by_timestamps(
+    conn: &mut Connection,
+    parsed: &ParsedPacket,
+    (tsval, tsecr): (u32, u32),
+) {
+    if parsed.is_outgoing {
+        if conn.rtt_probe.is_none() {
+            conn.rtt_probe = Some((tsval, parsed.instant));
+        }
+        return;
+    }
+
+    let Some((probe, sent)) = conn.rtt_probe else {
+        return;
+    };
+    if tsecr == probe {
+        add_rtt_sample(conn, parsed.instant.saturating_duration_since(sent));
+        conn.rtt_probe = None;
+    } else if tsecr.wrapping_sub(probe) as i32 > 0 {
+        // The echo of a later segment: the timed one's was missed
+        conn.rtt_probe = None;
+    }
+}
+
+/// Time an outgoing SYN or data segment until an ACK covers it, starting
+/// with the handshake. Only new segments are timed, and a segment sent again
+/// is no longer timed (Karn's algorithm), as its ACK could be for either copy.
+fn track_rtt_by_ack(conn: &mut Connection, parsed: &ParsedPacket) {
+    let (Some(flags), Some(seq)) = (&parsed.tcp_flags, parsed.tcp_seq) else {
+        return;
+    };
+    if parsed.is_outgoing {
+        let len = parsed.payload_len as u32 + u32::from(flags.syn) + u32::from(flags.fin);
+        if len == 0 || flags.rst {
+            return;
+        }
+        let end = seq.wrapping_add(len);
+        let is_new = conn
+            .seq_end_sent
+            .is_none_or(|seen| end.wrapping_sub(seen) as i32 > 0);
+        match conn.rtt_ack_probe {
+            None if is_new => conn.rtt_ack_probe = Some((end, parsed.instant)),
+            Some((probe_end, _)) if !is_new && probe_end.wrapping_sub(end) as i32 >= 0 => {
+                conn.rtt_ack_probe = None;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let (Some((probe_end, sent)), Some(ack)) = (conn.rtt_ack_probe, parsed.tcp_ack) else {
+        return;
+    };
+    if ack.wrapping_sub(probe_end) as i32 >= 0 {
+        add_rtt_sample(conn, parsed.instant.saturating_duration_since(sent));
+        conn.rtt_ack_probe = None;
+    }
+}
+
 /// Create a new connection from a parsed packet
-pub fn create_connection_from_packet(parsed: &ParsedPacket, now: SystemTime) -> Connection {
+/// Whether the remote host opened the flow `parsed` is the first packet of.
+/// A SYN says so directly; otherwise the side with the lower port is taken
+/// to be the server.
+fn opened_by_remote(parsed: &ParsedPacket) -> bool {
+    if let Some(flags) = &parsed.tcp_flags
+        && flags.syn
+        && !flags.ack
+    {
+        return !parsed.is_outgoing;
+    }
+    match parsed.local_addr.port().cmp(&parsed.remote_addr.port()) {
+        std::cmp::Ordering::Less => true,
+        std::cmp::Ordering::Greater => false,
+        std::cmp::Ordering::Equal => !parsed.is_outgoing,
+    }
+}
+
+pub fn create_connection_from_packet(parsed: &ParsedPacket, options: &MergeOptions) -> Connection {
     let mut conn = Connection::new(
         parsed.protocol,
         parsed.local_addr,
         parsed.remote_addr,
         parsed.protocol_state,
     );
+    conn.forwarded = parsed.forwarded;
+    conn.inbound = opened_by_remote(parsed);
+
+    // Set initial TCP state based on flags; other protocols keep the packet's state
+    if let Some(tcp_flags) = &parsed.tcp_flags {
+        conn.protocol_state = ProtocolState::Tcp(update_tcp_state(
+            TcpState::Unknown,
+            tcp_flags,
+            parsed.is_outgoing,
+        ));
 
-    // Set initial TCP state based on flags if TCP
-    if parsed.tcp_flags.is_some() {
-        if let Some(tcp_flags) = &parsed.tcp_flags {
-            conn.protocol_state = ProtocolState::Tcp(update_tcp_state(
-                TcpState::Unknown,
-                tcp_flags,
-                parsed.is_outgoing,
-            ));
-
-            debug!(
-                "Created new {} connection: {:?} -> {:?}, state: {:?}",
-                parsed.protocol, parsed.local_addr, parsed.remote_addr, conn.protocol_state
-            );
-        }
-    } else {
-        // For non-TCP protocols, use the provided state directly
-        conn.protocol_state = parsed.protocol_state;
+        debug!(
+            target: LOG_TARGET,
+            "Created new {} connection: {:?} -> {:?}, state: {:?}",
+            parsed.protocol, parsed.local_addr, parsed.remote_addr, conn.protocol_state
+        );
     }
 
     // Set initial stats based on packet direction
     if parsed.is_outgoing {
-        conn.packets_sent = 1;
+        conn.packets_sent = parsed.segments as u64;
         conn.bytes_sent = parsed.packet_len as u64;
         conn.packets_received = 0;
         conn.bytes_received = 0;
     } else {
         conn.packets_sent = 0;
         conn.bytes_sent = 0;
-        conn.packets_received = 1;
+        conn.packets_received = parsed.segments as u64;
         conn.bytes_received = parsed.packet_len as u64;
     }
 
+    track_rtt(&mut conn, parsed);
+    track_retransmits(&mut conn, parsed);
+
     // Apply DPI results if any
     if let Some(dpi_result) = &parsed.dpi_result {
         conn.dpi_info = Some(DpiInfo {
             application: dpi_result.application.clone(),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
+        track_http_response_time(&mut conn, dpi_result, parsed.instant);
+        update_tls_warnings(&mut conn, options);
 
         debug!(
+            target: LOG_TARGET,
             "New connection with DPI: {} - {}",
             conn.key(),
             dpi_result.application
@@ -253,6 +547,7 @@ pub fn create_connection_from_packet(parsed: &ParsedPacket, now: SystemTime) ->
     if let Some(process_name) = &parsed.process_name {
         conn.process_name = Some(process_name.clone());
         debug!(
+            target: LOG_TARGET,
             "✓ New connection {} with process name: {}",
             conn.key(),
             process_name
@@ -261,14 +556,24 @@ pub fn create_connection_from_packet(parsed: &ParsedPacket, now: SystemTime) ->
     if let Some(process_id) = parsed.process_id {
         conn.pid = Some(process_id);
         debug!(
+            target: LOG_TARGET,
             "✓ New connection {} with process ID: {}",
             conn.key(),
             process_id
         );
     }
 
-    conn.created_at = now;
-    conn.last_activity = now;
+    conn.remote_os_guess.clone_from(&parsed.os_guess);
+    conn.remote_mac = parsed.remote_mac;
+    conn.vlan_id = parsed.vlan_id;
+    conn.tunnel = parsed.tunnel;
+    conn.scan = parsed.scan;
+    record_sctp_addresses(&mut conn, parsed);
+
+    conn.created_at = parsed.timestamp;
+    conn.last_activity = parsed.timestamp;
+    conn.created_instant = parsed.instant;
+    conn.last_activity_instant = parsed.instant;
 
     // Initialize the rate tracker with the initial byte counts
     // This prevents incorrect delta calculation on the first update
@@ -278,6 +583,82 @@ pub fn create_connection_from_packet(parsed: &ParsedPacket, now: SystemTime) ->
     conn
 }
 
+/// Keep the other addresses the local end of an SCTP association listed in
+/// its INIT or INIT ACK. For forwarded flows that end is endpoint A.
+fn record_sctp_addresses(conn: &mut Connection, parsed: &ParsedPacket) {
+    if !parsed.is_outgoing || parsed.sctp_addresses.is_empty() {
+        return;
+    }
+    let port = parsed.local_addr.port();
+    conn.alternate_local_addrs = parsed
+        .sctp_addresses
+        .iter()
+        .filter(|&&ip| ip != parsed.local_addr.ip())
+        .map(|&ip| SocketAddr::new(ip, port))
+        .collect();
+}
+
+/// Merge a DPI result into a connection, e.g. one produced by the DPI worker
+/// pool. `at` is when the packet it came from was captured.
+pub fn merge_dpi_result(
+    conn: &mut Connection,
+    dpi_result: &DpiResult,
+    at: Instant,
+    options: &MergeOptions,
+) {
+    merge_dpi_info(conn, dpi_result);
+    track_http_response_time(conn, dpi_result, at);
+    update_tls_downgrade_status(conn);
+    update_tls_warnings(conn, options);
+}
+
+/// Re-evaluate the TLS handshake, logging each warning the first time it appears
+fn update_tls_warnings(conn: &mut Connection, options: &MergeOptions) {
+    let warnings = conn.evaluate_tls_warnings(&options.weak_cipher_suites);
+    for warning in &warnings {
+        if !conn.tls_warnings.contains(warning) {
+            warn!(target: LOG_TARGET, "{} on {}", warning, conn.key());
+        }
+    }
+    conn.tls_warnings = warnings;
+}
+
+/// Flag connections whose TLS handshake looks like a version downgrade
+fn update_tls_downgrade_status(conn: &mut Connection) {
+    let possible_downgrade = conn
+        .tls_info()
+        .is_some_and(|tls| tls.is_possible_downgrade());
+    if possible_downgrade && !conn.possible_downgrade {
+        warn!(
+            target: LOG_TARGET,
+            "Possible TLS downgrade on {}: client offered {:?}, server negotiated {:?}",
+            conn.key(),
+            conn.tls_info().and_then(|tls| tls.client_max_tls_version),
+            conn.tls_info()
+                .and_then(|tls| tls.server_negotiated_version)
+        );
+    }
+    conn.possible_downgrade = possible_downgrade;
+}
+
+/// Time HTTP exchanges: a request starts the clock and the next response on
+/// the connection stops it. Pipelined requests are timed from the first.
+fn track_http_response_time(conn: &mut Connection, dpi_result: &DpiResult, at: Instant) {
+    let (Some(dpi_info), ApplicationProtocol::Http(new_info)) =
+        (&mut conn.dpi_info, &dpi_result.application)
+    else {
+        return;
+    };
+    if new_info.method.is_some() {
+        dpi_info.pending_request_at.get_or_insert(at);
+    } else if new_info.status_code.is_some()
+        && let Some(requested_at) = dpi_info.pending_request_at.take()
+        && let ApplicationProtocol::Http(info) = &mut dpi_info.application
+    {
+        info.response_time = Some(at.saturating_duration_since(requested_at));
+    }
+}
+
 /// Merge DPI information into an existing connection
 fn merge_dpi_info(conn: &mut Connection, dpi_result: &DpiResult) {
     match &mut conn.dpi_info {
@@ -287,9 +668,11 @@ fn merge_dpi_info(conn: &mut Connection, dpi_result: &DpiResult) {
                 application: dpi_result.application.clone(),
                 first_packet_time: Instant::now(),
                 last_update_time: Instant::now(),
+                pending_request_at: None,
             });
 
             debug!(
+                target: LOG_TARGET,
                 "Added DPI info to connection: {} - {}",
                 conn.key(),
                 dpi_result.application
@@ -326,6 +709,13 @@ fn merge_dpi_info(conn: &mut Connection, dpi_result: &DpiResult) {
                     merge_ssh_info(old_info, new_info);
                 }
 
+                // Custom signature detections - keep the latest detail
+                (ApplicationProtocol::Custom(old_info), ApplicationProtocol::Custom(new_info))
+                    if old_info.name == new_info.name && new_info.detail.is_some() =>
+                {
+                    old_info.detail = new_info.detail.clone();
+                }
+
                 _ => {
                     // Keep existing protocol
                 }
@@ -381,7 +771,58 @@ fn merge_https_info(old_info: &mut HttpsInfo, new_info: &HttpsInfo) {
         if old_tls.cipher_suite.is_none() && new_tls.cipher_suite.is_some() {
             old_tls.cipher_suite = new_tls.cipher_suite;
         }
+        if old_tls.client_max_tls_version.is_none() && new_tls.client_max_tls_version.is_some() {
+            old_tls.client_max_tls_version = new_tls.client_max_tls_version;
+        }
+        if new_tls.server_negotiated_version.is_some() {
+            // The ServerHello decides the version actually in use
+            old_tls.server_negotiated_version = new_tls.server_negotiated_version;
+            old_tls.version = new_tls.server_negotiated_version;
+        }
+        old_tls.has_early_data |= new_tls.has_early_data;
+    }
+
+    // Continue handshake messages that span several segments
+    if let Some(segment) = &new_info.handshake {
+        let handshake = old_info
+            .handshake
+            .get_or_insert_with(TlsHandshakeReassembler::default);
+        handshake.append(segment);
+        for is_outgoing in [true, false] {
+            if let Some(message) = handshake.take_message(is_outgoing) {
+                let complete = dpi::parse_handshake_message(&message);
+                apply_complete_handshake(&mut old_info.tls_info, complete);
+            }
+        }
+    }
+}
+
+/// Take the fields of a fully reassembled ClientHello/ServerHello over
+/// whatever was read from its first, partial segment
+fn apply_complete_handshake(tls_info: &mut Option<TlsInfo>, complete: TlsInfo) {
+    let Some(tls) = tls_info else {
+        *tls_info = Some(complete);
+        return;
+    };
+    if complete.sni.is_some() {
+        tls.sni = complete.sni;
+    }
+    if !complete.alpn.is_empty() {
+        tls.alpn = complete.alpn;
+    }
+    if complete.cipher_suite.is_some() {
+        tls.cipher_suite = complete.cipher_suite;
+    }
+    if complete.client_max_tls_version.is_some() {
+        tls.client_max_tls_version = complete.client_max_tls_version;
     }
+    if complete.server_negotiated_version.is_some() {
+        tls.server_negotiated_version = complete.server_negotiated_version;
+        tls.version = complete.server_negotiated_version;
+    } else if tls.version.is_none() {
+        tls.version = complete.version;
+    }
+    tls.has_early_data |= complete.has_early_data;
 }
 
 /// Merge QUIC information with reassembly support
@@ -390,6 +831,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
     match (&old_info.connection_state, &new_info.connection_state) {
         (old_state, _new_state) if !matches!(old_state, _new_state) => {
             debug!(
+                target: LOG_TARGET,
                 "QUIC connection state changed: {:?} -> {:?}",
                 old_state, _new_state
             );
@@ -419,6 +861,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
             // First time seeing crypto frames, initialize the connection-level reassembler
             old_info.crypto_reassembler = Some(new_reassembler.clone());
             debug!(
+                target: LOG_TARGET,
                 "QUIC: Initialized crypto reassembler for connection with Connection ID: {:?}",
                 old_info.connection_id_hex
             );
@@ -428,14 +871,15 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
             for (&offset, data) in new_reassembler.get_fragments() {
                 match old_reassembler.add_fragment(offset, data.clone()) {
                     Ok(_) => {
-                        debug!(
+                        trace!(
+                            target: LOG_TARGET,
                             "QUIC: Merged CRYPTO fragment at offset {} for connection {}",
                             offset,
                             old_info.connection_id_hex.as_deref().unwrap_or("unknown")
                         );
                     }
                     Err(e) => {
-                        warn!("QUIC: Failed to merge CRYPTO fragment: {}", e);
+                        debug!(target: LOG_TARGET, "QUIC: Failed to merge CRYPTO fragment: {}", e);
                     }
                 }
             }
@@ -444,6 +888,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
             if let Some(tls_info) = new_reassembler.get_cached_tls_info() {
                 old_info.tls_info = Some(tls_info.clone());
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Updated TLS info from reassembler - SNI: {:?}, ALPN: {:?}",
                     tls_info.sni, tls_info.alpn
                 );
@@ -455,7 +900,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
     match (&old_info.tls_info, &new_info.tls_info) {
         (None, Some(new_tls)) => {
             old_info.tls_info = Some(new_tls.clone());
-            debug!("QUIC: Added TLS info - SNI: {:?}", new_tls.sni);
+            debug!(target: LOG_TARGET, "QUIC: Added TLS info - SNI: {:?}", new_tls.sni);
         }
         (Some(old_tls), Some(new_tls)) => {
             // Merge TLS info - prefer more complete info
@@ -482,9 +927,14 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
                 updated = true;
             }
 
+            if !old_tls.has_early_data && new_tls.has_early_data {
+                merged_tls.has_early_data = true;
+                updated = true;
+            }
+
             if updated {
                 old_info.tls_info = Some(merged_tls);
-                debug!("QUIC: Merged TLS info");
+                debug!(target: LOG_TARGET, "QUIC: Merged TLS info");
             }
         }
         _ => {}
@@ -504,12 +954,16 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
         old_info.connection_state = match new_close.frame_type {
             0x1c if new_close.error_code == 0 => {
                 // NO_ERROR transport close - enter draining state
-                debug!("QUIC: Connection entering draining state (NO_ERROR transport close)");
+                debug!(
+                    target: LOG_TARGET,
+                    "QUIC: Connection entering draining state (NO_ERROR transport close)"
+                );
                 QuicConnectionState::Draining
             }
             0x1c => {
                 // Transport error - connection is closed
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Connection closed due to transport error: {}",
                     new_close.error_code
                 );
@@ -518,6 +972,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
             0x1d => {
                 // Application close - connection is closed
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Connection closed by application: {}",
                     new_close.error_code
                 );
@@ -526,6 +981,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
             _ => {
                 // Unknown close type - assume closed
                 debug!(
+                    target: LOG_TARGET,
                     "QUIC: Connection closed (unknown frame type: 0x{:02x})",
                     new_close.frame_type
                 );
@@ -534,6 +990,7 @@ fn merge_quic_info(old_info: &mut QuicInfo, new_info: &QuicInfo) {
         };
 
         debug!(
+            target: LOG_TARGET,
             "QUIC: Updated connection state to {:?} due to CONNECTION_CLOSE frame",
             old_info.connection_state
         );
@@ -564,9 +1021,12 @@ fn merge_dns_info(old_info: &mut DnsInfo, new_info: &DnsInfo) {
         }
     }
 
-    // Update response flag
+    // Update response flag; the latest response decides the status
     if new_info.is_response {
         old_info.is_response = true;
+        old_info.rcode = new_info.rcode;
+        old_info.truncated = new_info.truncated;
+        old_info.ttl = new_info.ttl;
     }
 }
 
@@ -636,16 +1096,17 @@ fn merge_ssh_info(old_info: &mut SshInfo, new_info: &SshInfo) {
 }
 
 /// Update connection rate calculations using sliding window
-fn update_connection_rates(conn: &mut Connection) {
+fn update_connection_rates(conn: &mut Connection, now: Instant) {
     // Use the new rate tracker with sliding window calculation
-    conn.update_rates();
+    conn.update_rates_at(now);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::network::types::{Protocol, ProtocolState, TcpState};
+    use crate::network::types::{Protocol, ProtocolState, TcpState, TlsVersion};
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::time::{Duration, SystemTime};
 
     fn create_test_connection() -> Connection {
         Connection::new(
@@ -671,11 +1132,26 @@ mod tests {
                 psh: false,
                 urg: false,
             }),
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
             is_outgoing,
+            forwarded: false,
             packet_len: 100,
+            payload_len: 0,
+            timestamp: SystemTime::now(),
+            instant: Instant::now(),
             dpi_result: None,
+            dpi_payload: None,
             process_name: None,
             process_id: None,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
         }
     }
 
@@ -684,17 +1160,352 @@ mod tests {
         let mut conn = create_test_connection();
         let packet = create_test_packet(true, false);
 
-        conn = merge_packet_into_connection(conn, &packet, SystemTime::now());
+        conn = merge_packet_into_connection(conn, &packet, &MergeOptions::default());
 
         assert_eq!(conn.packets_sent, 1);
         assert_eq!(conn.bytes_sent, 100);
         assert_eq!(conn.packets_received, 0);
     }
 
+    #[test]
+    fn test_connection_direction() {
+        // A SYN says who opened the connection
+        let mut syn = create_test_packet(false, false);
+        syn.tcp_flags = Some(tcp_flags("S"));
+        assert!(create_connection_from_packet(&syn, &MergeOptions::default()).inbound);
+        syn.is_outgoing = true;
+        assert!(!create_connection_from_packet(&syn, &MergeOptions::default()).inbound);
+
+        // Without one, the side with the service port is the server
+        let reply = create_test_packet(false, false);
+        assert!(!create_connection_from_packet(&reply, &MergeOptions::default()).inbound);
+        let mut request = create_test_packet(false, false);
+        request.local_addr.set_port(22);
+        request.remote_addr.set_port(51234);
+        assert!(create_connection_from_packet(&request, &MergeOptions::default()).inbound);
+    }
+
+    #[test]
+    fn test_merge_keeps_computed_tcp_state() {
+        // The parser reports every TCP packet as Unknown; only the flags may
+        // move the connection's state
+        let packets = [
+            ("S", true, TcpState::SynSent),
+            ("SA", false, TcpState::Established),
+            ("A", true, TcpState::Established),
+            ("PA", true, TcpState::Established),
+            ("PA", false, TcpState::Established),
+            ("FA", true, TcpState::FinWait1),
+        ];
+
+        let mut conn: Option<Connection> = None;
+        for (flags, is_outgoing, expected) in packets {
+            let mut packet = create_test_packet(is_outgoing, false);
+            packet.tcp_flags = Some(tcp_flags(flags));
+            let merged = match conn {
+                None => create_connection_from_packet(&packet, &MergeOptions::default()),
+                Some(conn) => merge_packet_into_connection(conn, &packet, &MergeOptions::default()),
+            };
+            assert!(
+                matches!(merged.protocol_state, ProtocolState::Tcp(state) if state == expected),
+                "after {}: {:?}",
+                flags,
+                merged.protocol_state
+            );
+            conn = Some(merged);
+        }
+    }
+
+    #[test]
+    fn test_udp_flow_is_active_once_both_sides_send() {
+        let mut packet = create_test_packet(true, false);
+        packet.protocol = Protocol::UDP;
+        packet.tcp_flags = None;
+        packet.protocol_state = ProtocolState::Udp(UdpState::Unconnected);
+
+        let mut conn = create_connection_from_packet(&packet, &MergeOptions::default());
+        conn = merge_packet_into_connection(conn, &packet, &MergeOptions::default());
+        assert!(matches!(
+            conn.protocol_state,
+            ProtocolState::Udp(UdpState::Unconnected)
+        ));
+        assert_eq!(conn.state(), "UDP_UNCONN");
+
+        packet.is_outgoing = false;
+        conn = merge_packet_into_connection(conn, &packet, &MergeOptions::default());
+        assert_eq!(conn.state(), "UDP_ACTIVE");
+
+        // Every packet is parsed as unconnected; that mustn't undo it
+        packet.is_outgoing = true;
+        conn = merge_packet_into_connection(conn, &packet, &MergeOptions::default());
+        assert_eq!(conn.state(), "UDP_ACTIVE");
+    }
+
+    #[test]
+    fn test_icmp_echo_flow_counts_both_directions() {
+        let mut packet = create_test_packet(true, false);
+        packet.protocol = Protocol::ICMP;
+        packet.tcp_flags = None;
+        // Echo flows are keyed by identifier, carried in the port fields
+        packet.local_addr.set_port(0x1234);
+        packet.remote_addr.set_port(0x1234);
+        packet.protocol_state = ProtocolState::Icmp {
+            icmp_type: 8,
+            icmp_code: 0,
+        };
+        packet.packet_len = 98;
+
+        let mut conn = create_connection_from_packet(&packet, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REQUEST");
+
+        packet.is_outgoing = false;
+        packet.protocol_state = ProtocolState::Icmp {
+            icmp_type: 0,
+            icmp_code: 0,
+        };
+        conn = merge_packet_into_connection(conn, &packet, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REPLY");
+        assert_eq!((conn.packets_sent, conn.packets_received), (1, 1));
+        assert_eq!((conn.bytes_sent, conn.bytes_received), (98, 98));
+    }
+
+    #[test]
+    fn test_rtt_is_estimated_from_echoed_timestamps() {
+        let start = Instant::now();
+        let segment = |is_outgoing: bool, timestamps: (u32, u32), after_ms: u64| {
+            let mut packet = create_test_packet(is_outgoing, false);
+            packet.tcp_timestamps = Some(timestamps);
+            packet.instant = start + Duration::from_millis(after_ms);
+            packet
+        };
+
+        let mut conn =
+            create_connection_from_packet(&segment(true, (100, 0), 0), &MergeOptions::default());
+        // A second segment with the same TSval isn't timed separately
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, (100, 0), 5),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(false, (9000, 100), 20),
+            &MergeOptions::default(),
+        );
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_millis(20)));
+
+        // Later samples are smoothed in
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, (130, 9000), 30),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(false, (9040, 130), 70),
+            &MergeOptions::default(),
+        );
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_micros(22_500)));
+
+        // An echo of a later segment drops the one being timed
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, (150, 9040), 80),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(false, (9100, 160), 200),
+            &MergeOptions::default(),
+        );
+        assert!(conn.rtt_probe.is_none());
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_micros(22_500)));
+    }
+
+    #[test]
+    fn test_rtt_is_estimated_from_acks_without_timestamps() {
+        let start = Instant::now();
+        let segment =
+            |is_outgoing: bool, seq: u32, ack: Option<u32>, payload_len: usize, after_ms: u64| {
+                let mut packet = create_test_packet(is_outgoing, false);
+                packet.tcp_seq = Some(seq);
+                packet.tcp_ack = ack;
+                packet.payload_len = payload_len;
+                packet.instant = start + Duration::from_millis(after_ms);
+                packet
+            };
+
+        // Our SYN until the SYN-ACK acknowledging it
+        let mut syn = segment(true, 1000, None, 0, 0);
+        syn.tcp_flags.as_mut().unwrap().syn = true;
+        let mut syn_ack = segment(false, 5000, Some(1001), 0, 30);
+        syn_ack.tcp_flags.as_mut().unwrap().syn = true;
+        let mut conn = create_connection_from_packet(&syn, &MergeOptions::default());
+        conn = merge_packet_into_connection(conn, &syn_ack, &MergeOptions::default());
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_millis(30)));
+
+        // A data segment until an ACK covers it, with one more sent behind it
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, 1001, Some(5001), 100, 40),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, 1101, Some(5001), 100, 45),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(false, 5001, Some(1201), 0, 60),
+            &MergeOptions::default(),
+        );
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_micros(28_750)));
+
+        // A segment sent again is no longer timed
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, 1201, Some(5001), 100, 70),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, 1201, Some(5001), 100, 300),
+            &MergeOptions::default(),
+        );
+        assert!(conn.rtt_ack_probe.is_none());
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(false, 5001, Some(1301), 0, 320),
+            &MergeOptions::default(),
+        );
+        assert_eq!(conn.rtt_estimate, Some(Duration::from_micros(28_750)));
+    }
+
+    #[test]
+    fn test_retransmissions_are_counted_per_direction() {
+        let segment = |is_outgoing: bool, seq: u32, payload_len: usize| {
+            let mut packet = create_test_packet(is_outgoing, false);
+            packet.tcp_seq = Some(seq);
+            packet.payload_len = payload_len;
+            packet
+        };
+
+        // Sequence numbers wrap, and each side's are tracked separately
+        let mut conn = create_connection_from_packet(
+            &segment(true, u32::MAX - 99, 100),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(conn, &segment(true, 0, 100), &MergeOptions::default());
+        conn =
+            merge_packet_into_connection(conn, &segment(false, 0, 100), &MergeOptions::default());
+        assert_eq!(conn.retransmit_count, 0);
+
+        // Segments resent in full or in part
+        conn = merge_packet_into_connection(
+            conn,
+            &segment(true, u32::MAX - 99, 100),
+            &MergeOptions::default(),
+        );
+        conn = merge_packet_into_connection(conn, &segment(true, 50, 50), &MergeOptions::default());
+        conn =
+            merge_packet_into_connection(conn, &segment(false, 0, 100), &MergeOptions::default());
+        assert_eq!(conn.retransmit_count, 3);
+
+        // Bare ACKs repeat the sequence number without resending anything
+        conn = merge_packet_into_connection(conn, &segment(true, 100, 0), &MergeOptions::default());
+        conn = merge_packet_into_connection(conn, &segment(true, 100, 0), &MergeOptions::default());
+        conn =
+            merge_packet_into_connection(conn, &segment(true, 100, 10), &MergeOptions::default());
+        assert_eq!(conn.retransmit_count, 3);
+    }
+
+    #[test]
+    fn test_merge_reassembles_client_hello_across_segments() {
+        let mut body = vec![0x03, 0x03]; // TLS 1.2
+        body.extend_from_slice(&[0u8; 32]); // Random
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]); // No session ID
+        body.extend_from_slice(&[0x00, 0x14, 0x00, 0x00, 0x00, 0x10]); // Extensions, SNI
+        body.extend_from_slice(&[0x00, 0x0e, 0x00, 0x00, 0x0b]); // host_name, length 11
+        body.extend_from_slice(b"example.com");
+        let mut message = vec![0x01, 0x00];
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(&body);
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        record.extend_from_slice(&message);
+
+        // The TCP segment boundary falls inside the server name
+        let (first, second) = record.split_at(record.len() - 6);
+        let mut conn = create_test_connection();
+        for segment in [first, second] {
+            let result = dpi::analyze_tcp_packet(segment, 12345, 443, true).unwrap();
+            merge_dpi_info(&mut conn, &result);
+        }
+
+        let tls = conn.tls_info().unwrap();
+        assert_eq!(tls.sni.as_deref(), Some("example.com"));
+        assert_eq!(tls.client_max_tls_version, Some(TlsVersion::Tls12));
+    }
+
+    #[test]
+    fn test_http_response_time() {
+        let request = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let request = dpi::analyze_tcp_packet(request, 12345, 80, true).unwrap();
+        let response = dpi::analyze_tcp_packet(response, 12345, 80, false).unwrap();
+        let response_time = |conn: &Connection| match &conn.dpi_info.as_ref()?.application {
+            ApplicationProtocol::Http(info) => info.response_time,
+            _ => None,
+        };
+
+        let start = Instant::now();
+        let mut conn = create_test_connection();
+        merge_dpi_result(&mut conn, &request, start, &MergeOptions::default());
+        assert_eq!(response_time(&conn), None);
+        merge_dpi_result(
+            &mut conn,
+            &response,
+            start + Duration::from_millis(120),
+            &MergeOptions::default(),
+        );
+        assert_eq!(response_time(&conn), Some(Duration::from_millis(120)));
+
+        // A response nothing asked for leaves the last timing alone
+        merge_dpi_result(
+            &mut conn,
+            &response,
+            start + Duration::from_secs(5),
+            &MergeOptions::default(),
+        );
+        assert_eq!(response_time(&conn), Some(Duration::from_millis(120)));
+
+        // Pipelined requests are timed from the first
+        merge_dpi_result(
+            &mut conn,
+            &request,
+            start + Duration::from_secs(10),
+            &MergeOptions::default(),
+        );
+        merge_dpi_result(
+            &mut conn,
+            &request,
+            start + Duration::from_secs(11),
+            &MergeOptions::default(),
+        );
+        merge_dpi_result(
+            &mut conn,
+            &response,
+            start + Duration::from_millis(12_500),
+            &MergeOptions::default(),
+        );
+        assert_eq!(response_time(&conn), Some(Duration::from_millis(2500)));
+    }
+
     #[test]
     fn test_create_connection_from_packet() {
         let packet = create_test_packet(false, false);
-        let conn = create_connection_from_packet(&packet, SystemTime::now());
+        let conn = create_connection_from_packet(&packet, &MergeOptions::default());
 
         assert_eq!(conn.packets_received, 1);
         assert_eq!(conn.bytes_received, 100);
@@ -705,7 +1516,7 @@ mod tests {
     fn test_new_connection_rate_tracker_initialization() {
         // Test that the rate tracker is properly initialized for new connections
         let packet = create_test_packet(true, false);
-        let conn = create_connection_from_packet(&packet, SystemTime::now());
+        let conn = create_connection_from_packet(&packet, &MergeOptions::default());
 
         // The connection should have initial bytes
         assert_eq!(conn.bytes_sent, 100);
@@ -713,7 +1524,8 @@ mod tests {
 
         // Now simulate merging another packet
         let packet2 = create_test_packet(true, false);
-        let mut updated_conn = merge_packet_into_connection(conn, &packet2, SystemTime::now());
+        let mut updated_conn =
+            merge_packet_into_connection(conn, &packet2, &MergeOptions::default());
 
         // Bytes should have increased
         assert_eq!(updated_conn.bytes_sent, 200);
@@ -728,54 +1540,183 @@ mod tests {
         assert!(updated_conn.current_outgoing_rate_bps >= 0.0);
     }
 
+    /// An ACK on the test connection carrying `payload_len` bytes, captured at `at`
+    fn ack_packet(is_outgoing: bool, payload_len: usize, at: Instant) -> ParsedPacket {
+        let mut packet = create_test_packet(is_outgoing, false);
+        packet.tcp_flags = Some(tcp_flags("A"));
+        packet.packet_len = 52 + payload_len;
+        packet.payload_len = payload_len;
+        packet.instant = at;
+        packet
+    }
+
+    /// A request and its answer every 45 seconds, each `payload_len` bytes
+    fn exchange_every_45s(payload_len: usize, rounds: u64, options: &MergeOptions) -> Connection {
+        let start = Instant::now();
+        let mut conn = create_test_connection();
+        conn.last_activity_instant = start;
+        for round in 1..=rounds {
+            let at = start + Duration::from_secs(45 * round);
+            conn = merge_packet_into_connection(conn, &ack_packet(true, payload_len, at), options);
+            let reply = ack_packet(false, payload_len, at + Duration::from_millis(200));
+            conn = merge_packet_into_connection(conn, &reply, options);
+        }
+        conn
+    }
+
     #[test]
-    fn test_tcp_state_transitions() {
-        // Test SYN -> SYN_SENT
-        let flags = TcpFlags {
-            syn: true,
-            ack: false,
-            fin: false,
-            rst: false,
-            psh: false,
-            urg: false,
-        };
-        let new_state = update_tcp_state(TcpState::Unknown, &flags, true);
-        assert_eq!(new_state, TcpState::SynSent);
-
-        // Test SYN-ACK -> ESTABLISHED
-        let flags = TcpFlags {
-            syn: true,
-            ack: true,
-            fin: false,
-            rst: false,
-            psh: false,
-            urg: false,
-        };
-        let new_state = update_tcp_state(TcpState::SynSent, &flags, false);
-        assert_eq!(new_state, TcpState::Established);
-
-        // Test FIN -> FIN_WAIT_1
-        let flags = TcpFlags {
-            syn: false,
-            ack: false,
-            fin: true,
-            rst: false,
-            psh: false,
-            urg: false,
-        };
-        let new_state = update_tcp_state(TcpState::Established, &flags, true);
-        assert_eq!(new_state, TcpState::FinWait1);
-
-        // Test RST -> CLOSED
-        let flags = TcpFlags {
-            syn: false,
-            ack: false,
-            fin: false,
-            rst: true,
-            psh: false,
-            urg: false,
+    fn test_keepalive_only_connection() {
+        let options = MergeOptions::default();
+        let conn = exchange_every_45s(0, 2, &options);
+        assert!(!conn.keepalive_only);
+
+        let conn = exchange_every_45s(0, 3, &options);
+        assert!(conn.keepalive_only);
+        assert_eq!(conn.keepalive_probes, 3);
+        // Probes and replies are counted but don't make the flow look busy
+        assert_eq!(conn.packets_sent, 3);
+        assert_eq!(conn.packets_received, 3);
+        assert_eq!(conn.bytes_received, 156);
+        assert_eq!(conn.current_incoming_rate_bps, 0.0);
+
+        // Data ends it
+        let at = conn.last_activity_instant + Duration::from_secs(1);
+        let conn = merge_packet_into_connection(conn, &ack_packet(true, 200, at), &options);
+        assert!(!conn.keepalive_only);
+        assert_eq!(conn.keepalive_probes, 0);
+    }
+
+    #[test]
+    fn test_keepalive_detection_can_be_turned_off() {
+        let options = MergeOptions {
+            keepalive_detection: false,
+            ..MergeOptions::default()
         };
-        let new_state = update_tcp_state(TcpState::Established, &flags, true);
-        assert_eq!(new_state, TcpState::Closed);
+        let conn = exchange_every_45s(0, 3, &options);
+        assert!(!conn.keepalive_only);
+        assert_eq!(conn.keepalive_probes, 0);
+        assert!(conn.current_incoming_rate_bps > 0.0);
+    }
+
+    #[test]
+    fn test_low_rate_flow_with_payload_is_not_keepalive() {
+        // Something like an MQTT ping: tiny, on the same cadence, but data
+        let conn = exchange_every_45s(2, 5, &MergeOptions::default());
+        assert!(!conn.keepalive_only);
+        assert_eq!(conn.keepalive_probes, 0);
+        assert_eq!(conn.packets_received, 5);
+        assert!(conn.current_incoming_rate_bps > 0.0);
+    }
+
+    /// Flags from a string such as "SA" (SYN|ACK), "FA" (FIN|ACK) or "R"
+    fn tcp_flags(flags: &str) -> TcpFlags {
+        TcpFlags {
+            fin: flags.contains('F'),
+            syn: flags.contains('S'),
+            rst: flags.contains('R'),
+            psh: flags.contains('P'),
+            ack: flags.contains('A'),
+            urg: flags.contains('U'),
+        }
+    }
+
+    #[test]
+    fn test_tcp_state_transitions() {
+        use TcpState::*;
+        const OUT: bool = true;
+        const IN: bool = false;
+
+        // (from, flags, is_outgoing, to)
+        let transitions = [
+            // Active open
+            (Unknown, "S", OUT, SynSent),
+            (SynSent, "SA", IN, Established),
+            // Passive open; the handshake completes on the peer's ACK
+            (Unknown, "S", IN, SynReceived),
+            (Listen, "S", IN, SynReceived),
+            (SynReceived, "SA", OUT, SynReceived),
+            (SynReceived, "A", IN, Established),
+            // Simultaneous open
+            (SynSent, "S", IN, SynReceived),
+            (SynReceived, "SA", IN, Established),
+            // Joined after the SYN
+            (Unknown, "SA", IN, Established),
+            (Unknown, "SA", OUT, SynReceived),
+            (Unknown, "A", OUT, Established),
+            (Unknown, "PA", IN, Established),
+            // Active close, with and without ACK on the FIN
+            (Established, "F", OUT, FinWait1),
+            (Established, "FA", OUT, FinWait1),
+            (FinWait1, "A", IN, FinWait2),
+            (FinWait2, "FA", IN, TimeWait),
+            (FinWait2, "F", IN, TimeWait),
+            (FinWait1, "FA", IN, TimeWait),
+            // Simultaneous close
+            (FinWait1, "F", IN, Closing),
+            (Closing, "A", IN, TimeWait),
+            (TimeWait, "A", IN, TimeWait),
+            // Passive close
+            (Established, "FA", IN, CloseWait),
+            (Established, "F", IN, CloseWait),
+            (CloseWait, "A", OUT, CloseWait),
+            (CloseWait, "FA", OUT, LastAck),
+            (LastAck, "A", IN, Closed),
+            // First packet seen is a FIN
+            (Unknown, "FA", OUT, FinWait1),
+            (Unknown, "FA", IN, CloseWait),
+            // Resets, including as the first packet seen
+            (Established, "R", IN, Reset),
+            (Established, "RA", OUT, Reset),
+            (SynSent, "RA", IN, Reset),
+            (FinWait1, "R", IN, Reset),
+            (Unknown, "R", IN, Reset),
+            // Data and retransmissions don't move the state
+            (Established, "PA", IN, Established),
+            (Established, "A", OUT, Established),
+            (FinWait1, "FA", OUT, FinWait1),
+            (LastAck, "FA", OUT, LastAck),
+            (Closed, "A", IN, Closed),
+            (Reset, "A", IN, Reset),
+            // The 4-tuple is reused for a new connection
+            (TimeWait, "S", OUT, SynSent),
+            (Closed, "S", IN, SynReceived),
+            (Reset, "S", OUT, SynSent),
+        ];
+
+        for (from, flags, is_outgoing, to) in transitions {
+            assert_eq!(
+                update_tcp_state(from, &tcp_flags(flags), is_outgoing),
+                to,
+                "{:?} + {} ({})",
+                from,
+                flags,
+                if is_outgoing { "out" } else { "in" }
+            );
+        }
+    }
+
+    #[test]
+    fn test_tcp_close_sequences() {
+        use TcpState::*;
+
+        // Packets as flags followed by '>' for outgoing or '<' for incoming
+        let sequences = [
+            // Graceful close where the peer piggybacks its FIN on the ACK
+            ("S> SA< A> FA> FA< A>", TimeWait),
+            // Both sides send FIN|ACK before seeing the other's
+            ("S> SA< A> FA> FA< A> A<", TimeWait),
+            // Server side of a connection closed by the client
+            ("S< SA> A< FA< A> FA> A<", Closed),
+            // Refused connection
+            ("S> RA<", Reset),
+        ];
+
+        for (packets, expected) in sequences {
+            let state = packets.split_whitespace().fold(Unknown, |state, packet| {
+                let (flags, direction) = packet.split_at(packet.len() - 1);
+                update_tcp_state(state, &tcp_flags(flags), direction == ">")
+            });
+            assert_eq!(state, expected, "{}", packets);
+        }
     }
 }
@@ -1,9 +1,19 @@
 pub mod capture;
+pub mod coalesce;
 pub mod dpi;
+pub mod fingerprint;
+pub mod geoip;
+pub mod health;
+pub mod interfaces;
+pub mod mac;
 pub mod merge;
 pub mod parser;
 #[cfg(target_os = "macos")]
 pub mod pktap;
 pub mod platform;
+pub mod recorder;
+pub mod resolver;
+pub mod scan;
 pub mod services;
+pub mod talkers;
 pub mod types;
@@ -1,20 +1,61 @@
 // network/parser.rs - Updated with DPI integration and PKTAP support
+use crate::network::capture::LOG_TARGET;
 use crate::network::dpi::{self, DpiResult};
+use crate::network::fingerprint::{SignatureTable, SynFingerprint};
+use crate::network::interfaces::{self, LocalSubnet};
+use crate::network::mac::{self, MacAddr};
 #[cfg(target_os = "macos")]
 use crate::network::pktap;
+use crate::network::scan::ScanKind;
 use crate::network::types::*;
+use crate::signatures::SignatureRegistry;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// Whether a flow, by connection key, still has payloads worth inspecting
+pub type DpiGate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
 
 /// Common parameters for transport layer parsing
 struct TransportParams {
     src_ip: IpAddr,
     dst_ip: IpAddr,
     is_outgoing: bool,
+    forwarded: bool,
     packet_len: usize,
+    ttl: u8,          // TTL or hop limit, for OS fingerprinting
+    df: Option<bool>, // IPv4 Don't Fragment bit
     process_name: Option<String>,
     process_id: Option<u32>,
 }
 
+impl TransportParams {
+    /// The packet's (local, remote) endpoints and whether it goes from local
+    /// to remote. Forwarded flows have no local end, so they're keyed with the
+    /// lower endpoint first and "outgoing" means lower to higher.
+    fn endpoints(&self, src_port: u16, dst_port: u16) -> (SocketAddr, SocketAddr, bool) {
+        let src = SocketAddr::new(self.src_ip, src_port);
+        let dst = SocketAddr::new(self.dst_ip, dst_port);
+        let is_outgoing = if self.forwarded {
+            src <= dst
+        } else {
+            self.is_outgoing
+        };
+        if is_outgoing {
+            (src, dst, true)
+        } else {
+            (dst, src, false)
+        }
+    }
+}
+
+/// Bytes of payload handed to the DPI worker pool. Large enough for a full
+/// QUIC Initial packet, which has to be decrypted as a whole.
+const DPI_PAYLOAD_PREFIX: usize = 4096;
+
+/// MTU assumed when the capture interface's is unknown, as for savefiles
+pub const DEFAULT_MTU: usize = 1500;
+
 // Define TCP flags as bit masks
 const TCP_FIN: u8 = 0x01;
 const TCP_SYN: u8 = 0x02;
@@ -34,6 +75,143 @@ pub struct TcpFlags {
     pub urg: bool,
 }
 
+/// Length of an Ethernet/IPv4 ARP message, excluding the frame padding
+const ARP_MESSAGE_LEN: usize = 28;
+
+/// Tunnels followed inside one another. A packet nested deeper is tracked as
+/// the tunnel traffic it appears to be.
+const MAX_TUNNEL_DEPTH: usize = 4;
+
+/// UDP port of VXLAN (RFC 7348)
+const VXLAN_PORT: u16 = 4789;
+
+/// MPLS labels walked looking for the bottom of the stack before the frame
+/// is taken to be garbage
+const MAX_MPLS_LABELS: usize = 8;
+
+/// IPv6 extension headers walked in front of the upper-layer header. RFC 8504
+/// leaves hosts free to drop packets with longer chains, and no real traffic
+/// comes close.
+const MAX_IPV6_EXTENSION_HEADERS: usize = 8;
+
+/// Address family in a BSD loopback header (DLT_NULL, DLT_LOOP)
+const AF_INET: u32 = 2;
+/// AF_INET6 differs between BSDs: NetBSD/OpenBSD, FreeBSD, macOS
+const AF_INET6: [u32; 3] = [24, 28, 30];
+
+/// Address family from a BSD loopback header. DLT_NULL stores it in the
+/// capturing host's byte order and DLT_LOOP in network order; families are
+/// small, so a value with the high bytes set was read in the wrong order.
+fn loopback_family(header: [u8; 4]) -> u32 {
+    let family = u32::from_le_bytes(header);
+    if family > 0xFFFF {
+        family.swap_bytes()
+    } else {
+        family
+    }
+}
+
+/// Bytes of an IPv4 packet, from the total-length field. That field is 0 on
+/// packets captured before TCP segmentation offload, in which case count what
+/// was captured plus what the snaplen cut off.
+fn ipv4_packet_len(ip_data: &[u8], truncated: usize) -> usize {
+    match u16::from_be_bytes([ip_data[2], ip_data[3]]) as usize {
+        total_len if total_len >= 20 => total_len,
+        _ => ip_data.len() + truncated,
+    }
+}
+
+/// Bytes of an IPv6 packet, from the payload-length field. A zero payload
+/// length (jumbograms, segmentation offload) falls back to the wire length.
+fn ipv6_packet_len(ip_data: &[u8], truncated: usize) -> usize {
+    match u16::from_be_bytes([ip_data[4], ip_data[5]]) as usize {
+        0 => ip_data.len() + truncated,
+        payload_len => 40 + payload_len,
+    }
+}
+
+/// Segments a TCP super-packet of `packet_len` IP bytes, `payload_len` of
+/// them payload, is split into when every segment repeats its headers
+fn offloaded_segments(packet_len: usize, payload_len: usize, mtu: usize) -> u32 {
+    // Guards against lengths that don't add up, as in mangled packets
+    let headers = packet_len.saturating_sub(payload_len).min(mtu / 2);
+    let mss = (mtu - headers).max(1);
+    (packet_len - headers).div_ceil(mss) as u32
+}
+
+/// What an MPLS label stack (RFC 3032) carries: everything after the entry
+/// with the bottom-of-stack bit
+fn mpls_payload(data: &[u8]) -> Option<&[u8]> {
+    for label in 0..MAX_MPLS_LABELS {
+        let entry = data.get(label * 4..label * 4 + 4)?;
+        if entry[2] & 0x01 != 0 {
+            return data.get(label * 4 + 4..);
+        }
+    }
+    None
+}
+
+/// Whether a UDP datagram is VXLAN: sent to the VXLAN port with the flag
+/// marking its network identifier as valid
+fn is_vxlan(udp_data: &[u8]) -> bool {
+    udp_data.get(2..4) == Some(&VXLAN_PORT.to_be_bytes()[..])
+        && udp_data.get(8).is_some_and(|flags| flags & 0x08 != 0)
+}
+
+/// Whether a GRE header is one `parse_gre` can follow: version 0, without
+/// the deprecated routing field. Enhanced GRE as used by PPTP carries PPP.
+fn is_plain_gre(gre_data: &[u8]) -> bool {
+    gre_data.get(..2).is_some_and(|flags| {
+        let flags = u16::from_be_bytes([flags[0], flags[1]]);
+        flags & 0x4000 == 0 && flags & 0x0007 == 0
+    })
+}
+
+/// Addresses listed in the IPv4 and IPv6 address parameters of an SCTP INIT
+/// or INIT ACK, which is alone in its packet when `chunks` starts with one
+fn sctp_init_addresses(chunks: &[u8]) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    if !matches!(chunks.first(), Some(1 | 2)) {
+        return addresses;
+    }
+    let Some(len) = chunks.get(2..4) else {
+        return addresses;
+    };
+    // A snapshot length can cut the chunk short
+    let chunk_len = usize::from(u16::from_be_bytes([len[0], len[1]])).min(chunks.len());
+
+    // Parameters follow the chunk header and 16 bytes of fixed fields
+    let mut params = chunks.get(20..chunk_len).unwrap_or_default();
+    while let Some(header) = params.get(..4) {
+        let param_type = u16::from_be_bytes([header[0], header[1]]);
+        let param_len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        if param_len < 4 {
+            break;
+        }
+        let value = params.get(4..param_len).unwrap_or_default();
+        match param_type {
+            5 => addresses.extend(<[u8; 4]>::try_from(value).ok().map(IpAddr::from)),
+            6 => addresses.extend(<[u8; 16]>::try_from(value).ok().map(IpAddr::from)),
+            _ => {}
+        }
+        // Each parameter is padded to a multiple of 4 bytes
+        params = params
+            .get(param_len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    addresses
+}
+
+/// Identifier of an ICMP echo request or reply. It pairs replies with their
+/// requests, so it stands in for the port and keeps concurrent pings to one
+/// host apart. Other messages are keyed by host pair alone.
+fn echo_identifier(icmp_data: &[u8], is_echo: bool) -> u16 {
+    match icmp_data.get(4..6) {
+        Some(id) if is_echo => u16::from_be_bytes([id[0], id[1]]),
+        _ => 0,
+    }
+}
+
 fn parse_tcp_flags(flags: u8) -> TcpFlags {
     TcpFlags {
         fin: (flags & TCP_FIN) != 0,
@@ -45,6 +223,35 @@ fn parse_tcp_flags(flags: u8) -> TcpFlags {
     }
 }
 
+/// TSval and TSecr from the timestamps option (kind 8) among a TCP header's
+/// options. Options cut short by the capture are left out.
+fn tcp_timestamps(mut options: &[u8]) -> Option<(u32, u32)> {
+    while let Some((&kind, rest)) = options.split_first() {
+        match kind {
+            0 => return None,
+            1 => {
+                options = rest;
+                continue;
+            }
+            _ => {}
+        }
+
+        // The length covers the kind and length bytes too
+        let len = *rest.first()? as usize;
+        if len < 2 || len > options.len() {
+            return None;
+        }
+        if let (8, &[a, b, c, d, e, f, g, h]) = (kind, &options[2..len]) {
+            return Some((
+                u32::from_be_bytes([a, b, c, d]),
+                u32::from_be_bytes([e, f, g, h]),
+            ));
+        }
+        options = &options[len..];
+    }
+    None
+}
+
 /// Result of parsing a packet
 #[derive(Debug)]
 pub struct ParsedPacket {
@@ -53,19 +260,81 @@ pub struct ParsedPacket {
     pub local_addr: SocketAddr,
     pub remote_addr: SocketAddr,
     pub tcp_flags: Option<TcpFlags>,
+    pub tcp_seq: Option<u32>, // TCP sequence number, for reassembling payloads
+    pub tcp_ack: Option<u32>, // TCP acknowledgment number, when the ACK flag is set
+    /// TSval and TSecr of the TCP timestamps option, for estimating RTT
+    pub tcp_timestamps: Option<(u32, u32)>,
     pub protocol_state: ProtocolState,
     pub is_outgoing: bool,
-    pub packet_len: usize,
+    pub forwarded: bool,       // Between two other hosts, keyed canonically
+    pub packet_len: usize,     // IP length, or whole frame with count_link_layer
+    pub payload_len: usize,    // TCP or UDP payload bytes captured
+    pub timestamp: SystemTime, // Capture time recorded by libpcap
+    pub instant: Instant,      // Capture time on the monotonic clock
     pub dpi_result: Option<DpiResult>, // DPI results if available
-    pub process_name: Option<String>,  // Process name from PKTAP metadata
-    pub process_id: Option<u32>,       // Process ID from PKTAP metadata
+    pub dpi_payload: Option<Vec<u8>>, // Payload prefix left for the DPI worker pool
+    pub process_name: Option<String>, // Process name from PKTAP metadata
+    pub process_id: Option<u32>, // Process ID from PKTAP metadata
+    pub os_guess: Option<OsGuess>, // Remote OS from an inbound SYN or SYN-ACK
+    pub remote_mac: Option<MacAddr>, // Peer's MAC when it's on a local subnet
+    pub vlan_id: Option<u16>,  // Innermost 802.1Q tag of the frame, if it had one
+    pub tunnel: Option<Tunnel>, // Outermost GRE or VXLAN tunnel it was carried in
+    pub sctp_addresses: Vec<IpAddr>, // Addresses an SCTP INIT or INIT ACK lists for its sender
+    pub segments: u32,         // Packets it counts as: more than 1 for offloaded super-packets
+    pub scan: Option<ScanKind>, // Set by the scan detector once the source is over a threshold
+}
+
+/// How TCP packets bigger than the interface MTU are counted. Capturing on
+/// the sending host, segmentation offload (TSO/GSO) hands libpcap one packet
+/// of up to 64 KB that the NIC splits up afterwards; on the receiving host
+/// GRO merges segments into one before libpcap sees them. Bytes come from
+/// the IP length either way, only the packet count differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OffloadCounting {
+    /// One packet per frame, as libpcap captured it
+    #[default]
+    Frames,
+    /// The MTU-sized segments the frame was sent or received as
+    Segments,
+}
+
+/// How to treat packets that neither come from nor go to this host, as seen
+/// when running on a router or a mirror port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RouterMode {
+    /// Forwarded when neither address belongs to a local interface
+    #[default]
+    Auto,
+    /// Every flow is forwarded traffic
+    On,
+    /// Attribute everything to this host, treating unknown sources as incoming
+    Off,
 }
 
 #[derive(Clone)]
 pub struct ParserConfig {
     pub enable_dpi: bool,
-    #[allow(dead_code)]
     pub dpi_packet_limit: usize, // Only inspect first N packets per connection
+    /// Consulted before a payload is inspected or copied for the worker
+    /// pool; None inspects every payload
+    pub wants_dpi: Option<DpiGate>,
+    /// User-defined detectors consulted after the built-in DPI
+    pub signatures: Arc<SignatureRegistry>,
+    /// Copy payloads into `dpi_payload` for the DPI worker pool instead of
+    /// analysing them inline
+    pub defer_dpi: bool,
+    /// Count whole frames as seen on the wire (link-layer headers included)
+    /// instead of the IP packet length
+    pub count_link_layer: bool,
+    pub router_mode: RouterMode,
+    /// Largest IP packet the capture interface carries in one frame
+    pub mtu: usize,
+    pub offload_counting: OffloadCounting,
+    /// Known TCP stacks for guessing the OS of remote hosts
+    pub os_signatures: Arc<SignatureTable>,
+    /// Track the connections inside GRE and VXLAN tunnels instead of the
+    /// tunnel traffic itself
+    pub decapsulate_tunnels: bool,
 }
 
 impl Default for ParserConfig {
@@ -73,6 +342,15 @@ impl Default for ParserConfig {
         Self {
             enable_dpi: true,
             dpi_packet_limit: 10, // Only inspect first 10 packets
+            wants_dpi: None,
+            signatures: Arc::new(SignatureRegistry::new()),
+            defer_dpi: false,
+            count_link_layer: false,
+            router_mode: RouterMode::default(),
+            mtu: DEFAULT_MTU,
+            offload_counting: OffloadCounting::default(),
+            os_signatures: Arc::new(SignatureTable::builtin()),
+            decapsulate_tunnels: false,
         }
     }
 }
@@ -80,6 +358,7 @@ impl Default for ParserConfig {
 /// Packet parser - stateless, thread-safe
 pub struct PacketParser {
     local_ips: std::collections::HashSet<IpAddr>,
+    local_subnets: Vec<LocalSubnet>,
     config: ParserConfig,
     linktype: Option<i32>, // DLT linktype - 149 means PKTAP on macOS
 }
@@ -93,82 +372,134 @@ impl Default for PacketParser {
 impl PacketParser {
     #[allow(dead_code)]
     pub fn new() -> Self {
-        let mut local_ips = std::collections::HashSet::new();
-        for iface in pnet_datalink::interfaces() {
-            for ip_network in iface.ips {
-                local_ips.insert(ip_network.ip());
-            }
-        }
-        Self {
-            local_ips,
-            config: ParserConfig::default(),
-            linktype: None,
-        }
+        Self::with_config(ParserConfig::default())
     }
 
     pub fn with_config(config: ParserConfig) -> Self {
-        let mut local_ips = std::collections::HashSet::new();
-        for iface in pnet_datalink::interfaces() {
-            for ip_network in iface.ips {
-                local_ips.insert(ip_network.ip());
-            }
-        }
+        let (local_ips, local_subnets) = interfaces::local_addresses();
         Self {
             local_ips,
+            local_subnets,
             config,
             linktype: None,
         }
     }
 
+    /// Read this host's addresses again, as DHCP leases and VPNs change them
+    pub fn refresh_local_addresses(&mut self) {
+        (self.local_ips, self.local_subnets) = interfaces::local_addresses();
+    }
+
     /// Set the linktype for this parser (needed for PKTAP detection)
     pub fn with_linktype(mut self, linktype: i32) -> Self {
         self.linktype = Some(linktype);
         self
     }
 
-    /// Parse a raw packet
-    pub fn parse_packet(&self, data: &[u8]) -> Option<ParsedPacket> {
+    /// Whether a packet is outgoing, and whether it's forwarded between two
+    /// other hosts rather than sent or received by this one
+    fn direction(&self, src_ip: IpAddr, dst_ip: IpAddr) -> (bool, bool) {
+        let is_outgoing = self.local_ips.contains(&src_ip);
+        let forwarded = match self.config.router_mode {
+            // Multicast, such as IPv6 Neighbor Discovery, is delivered here too
+            RouterMode::Auto => {
+                !is_outgoing && !self.local_ips.contains(&dst_ip) && !dst_ip.is_multicast()
+            }
+            RouterMode::On => true,
+            RouterMode::Off => false,
+        };
+        (is_outgoing, forwarded)
+    }
+
+    /// Parse a raw packet captured at `timestamp`, which `instant` places on
+    /// the monotonic clock. `wire_len` is the frame's length on the wire,
+    /// which is more than `data.len()` when the snaplen cut it short.
+    pub fn parse_packet(
+        &self,
+        data: &[u8],
+        wire_len: usize,
+        timestamp: SystemTime,
+        instant: Instant,
+    ) -> Option<ParsedPacket> {
+        let truncated = wire_len.saturating_sub(data.len());
+        let mut parsed = self.parse_frame(data, truncated)?;
+        parsed.timestamp = timestamp;
+        parsed.instant = instant;
+        if self.config.offload_counting == OffloadCounting::Segments
+            && parsed.protocol == Protocol::TCP
+            && parsed.packet_len > self.config.mtu
+        {
+            // The snaplen only ever cuts payload off
+            let payload_len = parsed.payload_len + truncated;
+            parsed.segments = offloaded_segments(parsed.packet_len, payload_len, self.config.mtu);
+        }
+        // The outer headers of a tunnel belong to it, not to the flow inside
+        if self.config.count_link_layer && parsed.tunnel.is_none() {
+            parsed.packet_len = data.len() + truncated;
+        }
+        Some(parsed)
+    }
+
+    /// Parse a frame according to the capture linktype. `truncated` is the
+    /// number of bytes the snaplen cut off the end. The returned packet's
+    /// timestamps are placeholders that `parse_packet` overwrites.
+    fn parse_frame(&self, data: &[u8], truncated: usize) -> Option<ParsedPacket> {
         // Check if this is PKTAP data
         #[cfg(target_os = "macos")]
         if let Some(linktype) = self.linktype
             && pktap::is_pktap_linktype(linktype)
         {
-            return self.parse_pktap_packet(data);
+            return self.parse_pktap_packet(data, truncated);
         }
 
-        // Check if this is Linux Cooked Capture (used by "any" interface on Linux)
+        // Check if this is Linux Cooked Capture (used by "any" interface on
+        // Linux), a BSD loopback device (lo0 on macOS and the BSDs) or a
+        // tunnel carrying bare IP packets
         if let Some(linktype) = self.linktype {
             match linktype {
                 113 => {
-                    log::debug!("Parsing as Linux SLL (linktype 113)");
-                    return self.parse_linux_sll_packet(data);
+                    log::trace!(target: LOG_TARGET, "Parsing as Linux SLL (linktype 113)");
+                    return self.parse_linux_sll_packet(data, truncated);
                 }
                 276 => {
-                    log::debug!("Parsing as Linux SLL2 (linktype 276)");
-                    return self.parse_linux_sll2_packet(data);
+                    log::trace!(target: LOG_TARGET, "Parsing as Linux SLL2 (linktype 276)");
+                    return self.parse_linux_sll2_packet(data, truncated);
+                }
+                0 | 108 => {
+                    log::trace!(
+                        target: LOG_TARGET,
+                        "Parsing as BSD loopback (linktype {})", linktype
+                    );
+                    return self.parse_loopback_packet(data, truncated, None, None);
+                }
+                // PPPoE session frames without their Ethernet header (DLT_PPP_ETHER)
+                51 => {
+                    log::trace!(target: LOG_TARGET, "Parsing as PPPoE (linktype 51)");
+                    return self.parse_pppoe_session(data, truncated, None, None, 0);
+                }
+                // PPP, with or without the HDLC address and control bytes
+                9 | 50 => {
+                    log::trace!(target: LOG_TARGET, "Parsing as PPP (linktype {})", linktype);
+                    let data = data.strip_prefix(&[0xff, 0x03]).unwrap_or(data);
+                    return self.parse_ppp_frame(data, truncated, None, None, 0);
+                }
+                // DLT_RAW (14 on OpenBSD), LINKTYPE_RAW, LINKTYPE_IPV4/IPV6:
+                // tun devices such as wg0 and tun0 have no link-layer header
+                12 | 14 | 101 | 228 | 229 => {
+                    log::trace!(target: LOG_TARGET, "Parsing as raw IP (linktype {})", linktype);
+                    return self.parse_raw_ip_packet(data, truncated, None, None, 0);
                 }
                 _ => {
-                    log::debug!("Using regular Ethernet parsing (linktype {})", linktype);
+                    log::trace!(
+                        target: LOG_TARGET,
+                        "Using regular Ethernet parsing (linktype {})", linktype
+                    );
                 }
             }
         }
 
         // Regular Ethernet parsing
-        if data.len() < 14 {
-            return None;
-        }
-
-        let ethertype = u16::from_be_bytes([data[12], data[13]]);
-
-        match ethertype {
-            0x0800 => self.parse_ipv4_packet_inner(data, None, None),
-            0x86dd => self.parse_ipv6_packet_inner(data, None, None),
-            0x0806 => self.parse_arp_packet_inner(data, None, None),
-            _ => {
-                log::debug!("Unknown ethertype: 0x{:04x}", ethertype);
-                None
-            }
-        }
+        self.parse_ethernet_packet(data, truncated, None, None, 0)
     }
 
     /// Parse Linux Cooked Capture v1 packet (DLT_LINUX_SLL)
@@ -178,7 +509,7 @@ impl PacketParser {
     /// - Link-layer address length (2 bytes)
     /// - Link-layer address (8 bytes)
     /// - Protocol type (2 bytes) - ethertype
-    fn parse_linux_sll_packet(&self, data: &[u8]) -> Option<ParsedPacket> {
+    fn parse_linux_sll_packet(&self, data: &[u8], truncated: usize) -> Option<ParsedPacket> {
         if data.len() < 16 {
             return None;
         }
@@ -189,14 +520,14 @@ impl PacketParser {
         match protocol {
             0x0800 => {
                 // IPv4 - payload starts at byte 16
-                let ip_data = &data[16..];
-                self.parse_raw_ipv4_packet(ip_data, None, None)
+                self.parse_raw_ipv4_packet(data.get(16..)?, truncated, None, None, 0)
             }
             0x86dd => {
                 // IPv6 - payload starts at byte 16
-                let ip_data = &data[16..];
-                self.parse_raw_ipv6_packet(ip_data, None, None)
+                self.parse_raw_ipv6_packet(data.get(16..)?, truncated, None, None, 0)
             }
+            0x0806 => self.parse_arp_packet_inner(data.get(16..)?, None, None),
+            0x8864 => self.parse_pppoe_session(data.get(16..)?, truncated, None, None, 0),
             _ => None,
         }
     }
@@ -210,7 +541,7 @@ impl PacketParser {
     /// - Packet type (1 byte)
     /// - Link-layer address length (1 byte)
     /// - Link-layer address (8 bytes)
-    fn parse_linux_sll2_packet(&self, data: &[u8]) -> Option<ParsedPacket> {
+    fn parse_linux_sll2_packet(&self, data: &[u8], truncated: usize) -> Option<ParsedPacket> {
         if data.len() < 20 {
             return None;
         }
@@ -221,24 +552,92 @@ impl PacketParser {
         match protocol {
             0x0800 => {
                 // IPv4 - payload starts at byte 20
-                let ip_data = &data[20..];
-                self.parse_raw_ipv4_packet(ip_data, None, None)
+                self.parse_raw_ipv4_packet(data.get(20..)?, truncated, None, None, 0)
             }
             0x86dd => {
                 // IPv6 - payload starts at byte 20
-                let ip_data = &data[20..];
-                self.parse_raw_ipv6_packet(ip_data, None, None)
+                self.parse_raw_ipv6_packet(data.get(20..)?, truncated, None, None, 0)
+            }
+            0x0806 => self.parse_arp_packet_inner(data.get(20..)?, None, None),
+            0x8864 => self.parse_pppoe_session(data.get(20..)?, truncated, None, None, 0),
+            _ => None,
+        }
+    }
+
+    /// Parse a PPPoE session-stage frame (RFC 2516): version and type 1,
+    /// code 0, session ID and length, then the PPP protocol field. Discovery
+    /// frames (ethertype 0x8863) carry no traffic and are skipped.
+    fn parse_pppoe_session(
+        &self,
+        data: &[u8],
+        truncated: usize,
+        process_name: Option<String>,
+        process_id: Option<u32>,
+        depth: usize,
+    ) -> Option<ParsedPacket> {
+        let header = data.get(..6)?;
+        if header[0] != 0x11 || header[1] != 0 {
+            return None;
+        }
+        self.parse_ppp_frame(&data[6..], truncated, process_name, process_id, depth)
+    }
+
+    /// Parse a PPP frame starting at its protocol field. Only IPv4 (0x0021)
+    /// and IPv6 (0x0057) carry connections; link control and the other
+    /// network protocols are skipped.
+    fn parse_ppp_frame(
+        &self,
+        data: &[u8],
+        truncated: usize,
+        process_name: Option<String>,
+        process_id: Option<u32>,
+        depth: usize,
+    ) -> Option<ParsedPacket> {
+        let protocol = u16::from_be_bytes(data.get(..2)?.try_into().ok()?);
+        let payload = &data[2..];
+        match protocol {
+            0x0021 => {
+                self.parse_raw_ipv4_packet(payload, truncated, process_name, process_id, depth)
+            }
+            0x0057 => {
+                self.parse_raw_ipv6_packet(payload, truncated, process_name, process_id, depth)
             }
             _ => None,
         }
     }
 
+    /// Parse BSD loopback packet (DLT_NULL or DLT_LOOP)
+    /// Header format (4 bytes):
+    /// - Address family - host byte order for DLT_NULL, network order for DLT_LOOP
+    fn parse_loopback_packet(
+        &self,
+        data: &[u8],
+        truncated: usize,
+        process_name: Option<String>,
+        process_id: Option<u32>,
+    ) -> Option<ParsedPacket> {
+        let header: [u8; 4] = data.get(..4)?.try_into().ok()?;
+        let payload = &data[4..];
+
+        match loopback_family(header) {
+            AF_INET => self.parse_raw_ipv4_packet(payload, truncated, process_name, process_id, 0),
+            family if AF_INET6.contains(&family) => {
+                self.parse_raw_ipv6_packet(payload, truncated, process_name, process_id, 0)
+            }
+            family => {
+                log::trace!(target: LOG_TARGET, "Unknown loopback family: {}", family);
+                None
+            }
+        }
+    }
+
     #[cfg(target_os = "macos")]
-    fn parse_pktap_packet(&self, data: &[u8]) -> Option<ParsedPacket> {
+    fn parse_pktap_packet(&self, data: &[u8], truncated: usize) -> Option<ParsedPacket> {
         let (pktap_header, payload) = pktap::parse_pktap_packet(data)?;
         let (process_name, process_id) = pktap_header.get_process_info();
 
-        log::debug!(
+        log::trace!(
+            target: LOG_TARGET,
             "PKTAP packet: interface={}, process={:?}, pid={:?}, payload_len={}",
             pktap_header.get_interface(),
             process_name,
@@ -250,199 +649,117 @@ impl PacketParser {
         match pktap_header.inner_dlt() {
             1 => {
                 // DLT_EN10MB - Ethernet frame
-                if payload.len() < 14 {
-                    return None;
-                }
-                let ethertype = u16::from_be_bytes([payload[12], payload[13]]);
-                match ethertype {
-                    0x0800 => self.parse_ipv4_packet_inner(payload, process_name, process_id),
-                    0x86dd => self.parse_ipv6_packet_inner(payload, process_name, process_id),
-                    0x0806 => self.parse_arp_packet_inner(payload, process_name, process_id),
-                    _ => None,
-                }
+                self.parse_ethernet_packet(payload, truncated, process_name, process_id, 0)
+            }
+            0 => {
+                // DLT_NULL - lo0 traffic
+                self.parse_loopback_packet(payload, truncated, process_name, process_id)
             }
             12 => {
                 // DLT_RAW - Raw IP packet
-                if payload.is_empty() {
-                    return None;
-                }
-                let version = payload[0] >> 4;
-                match version {
-                    4 => self.parse_raw_ipv4_packet(payload, process_name, process_id),
-                    6 => self.parse_raw_ipv6_packet(payload, process_name, process_id),
-                    _ => None,
-                }
+                self.parse_raw_ip_packet(payload, truncated, process_name, process_id, 0)
             }
             _ => {
-                log::debug!("Unsupported PKTAP inner DLT: {}", pktap_header.inner_dlt());
+                log::debug!(
+                    target: LOG_TARGET,
+                    "Unsupported PKTAP inner DLT: {}", pktap_header.inner_dlt()
+                );
                 None
             }
         }
     }
 
-    fn parse_ipv4_packet_inner(
+    /// Parse an Ethernet frame. 802.1Q and 802.1ad (QinQ) tags between the
+    /// MAC addresses and the ethertype are skipped, and the innermost VLAN
+    /// ID is kept on the packet.
+    fn parse_ethernet_packet(
         &self,
         data: &[u8],
+        truncated: usize,
         process_name: Option<String>,
         process_id: Option<u32>,
+        depth: usize,
     ) -> Option<ParsedPacket> {
-        let ip_data = &data[14..];
-        if ip_data.len() < 20 {
-            return None;
-        }
-
-        let version = ip_data[0] >> 4;
-        if version != 4 {
-            return None;
-        }
-
-        let protocol_num = ip_data[9];
-        let src_ip = IpAddr::V4(Ipv4Addr::new(
-            ip_data[12],
-            ip_data[13],
-            ip_data[14],
-            ip_data[15],
-        ));
-        let dst_ip = IpAddr::V4(Ipv4Addr::new(
-            ip_data[16],
-            ip_data[17],
-            ip_data[18],
-            ip_data[19],
-        ));
-
-        let ihl = ip_data[0] & 0x0F;
-        let ip_header_len = (ihl as usize) * 4;
-
-        if ip_data.len() < ip_header_len {
-            return None;
+        let mut offset = 12;
+        let mut vlan_id = None;
+        let mut ethertype = u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+        while matches!(ethertype, 0x8100 | 0x88a8) {
+            let tag = data.get(offset + 2..offset + 6)?;
+            vlan_id = Some(u16::from_be_bytes([tag[0], tag[1]]) & 0x0fff);
+            ethertype = u16::from_be_bytes([tag[2], tag[3]]);
+            offset += 4;
         }
+        let payload = &data[offset + 2..];
 
-        let transport_data = &ip_data[ip_header_len..];
-        let is_outgoing = self.local_ips.contains(&src_ip);
-
-        match protocol_num {
-            1 => self.parse_icmp(
-                transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
+        let mut parsed = match ethertype {
+            0x0800 => {
+                let mut parsed = self.parse_raw_ipv4_packet(
+                    payload,
+                    truncated,
                     process_name,
                     process_id,
-                },
-            ),
-            6 => self.parse_tcp(
-                transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
+                    depth,
+                )?;
+                parsed.remote_mac = self.lan_peer_mac(data, &parsed);
+                parsed
+            }
+            0x86dd => {
+                let mut parsed = self.parse_raw_ipv6_packet(
+                    payload,
+                    truncated,
                     process_name,
                     process_id,
-                },
-            ),
-            17 => self.parse_udp(
-                transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
+                    depth,
+                )?;
+                parsed.remote_mac = self.lan_peer_mac(data, &parsed);
+                parsed
+            }
+            // Unicast and multicast MPLS. The label stack doesn't say what's
+            // under it, so the IP version is read from the packet itself.
+            0x8847 | 0x8848 => {
+                let mut parsed = self.parse_raw_ip_packet(
+                    mpls_payload(payload)?,
+                    truncated,
                     process_name,
                     process_id,
-                },
-            ),
-            _ => None,
-        }
+                    depth,
+                )?;
+                parsed.remote_mac = self.lan_peer_mac(data, &parsed);
+                parsed
+            }
+            0x8864 => {
+                let mut parsed =
+                    self.parse_pppoe_session(payload, truncated, process_name, process_id, depth)?;
+                parsed.remote_mac = self.lan_peer_mac(data, &parsed);
+                parsed
+            }
+            0x0806 => self.parse_arp_packet_inner(payload, process_name, process_id)?,
+            _ => {
+                log::trace!(target: LOG_TARGET, "Unknown ethertype: 0x{:04x}", ethertype);
+                return None;
+            }
+        };
+        // A frame from inside a tunnel keeps its own tag
+        parsed.vlan_id = parsed.vlan_id.or(vlan_id);
+        Some(parsed)
     }
 
-    fn parse_ipv6_packet_inner(
-        &self,
-        data: &[u8],
-        process_name: Option<String>,
-        process_id: Option<u32>,
-    ) -> Option<ParsedPacket> {
-        let ip_data = &data[14..];
-        if ip_data.len() < 40 {
-            return None;
-        }
-
-        let version = ip_data[0] >> 4;
-        if version != 6 {
+    /// MAC of the remote end of an Ethernet frame. Only peers on one of this
+    /// host's subnets count; for anything else the frame carries the
+    /// router's address.
+    fn lan_peer_mac(&self, frame: &[u8], parsed: &ParsedPacket) -> Option<MacAddr> {
+        let remote_ip = parsed.remote_addr.ip();
+        if parsed.forwarded
+            || !self
+                .local_subnets
+                .iter()
+                .any(|subnet| subnet.contains(remote_ip))
+        {
             return None;
         }
-
-        let next_header = ip_data[6];
-
-        // Extract IPv6 addresses
-        let src_ip = IpAddr::V6(Ipv6Addr::new(
-            u16::from_be_bytes([ip_data[8], ip_data[9]]),
-            u16::from_be_bytes([ip_data[10], ip_data[11]]),
-            u16::from_be_bytes([ip_data[12], ip_data[13]]),
-            u16::from_be_bytes([ip_data[14], ip_data[15]]),
-            u16::from_be_bytes([ip_data[16], ip_data[17]]),
-            u16::from_be_bytes([ip_data[18], ip_data[19]]),
-            u16::from_be_bytes([ip_data[20], ip_data[21]]),
-            u16::from_be_bytes([ip_data[22], ip_data[23]]),
-        ));
-
-        let dst_ip = IpAddr::V6(Ipv6Addr::new(
-            u16::from_be_bytes([ip_data[24], ip_data[25]]),
-            u16::from_be_bytes([ip_data[26], ip_data[27]]),
-            u16::from_be_bytes([ip_data[28], ip_data[29]]),
-            u16::from_be_bytes([ip_data[30], ip_data[31]]),
-            u16::from_be_bytes([ip_data[32], ip_data[33]]),
-            u16::from_be_bytes([ip_data[34], ip_data[35]]),
-            u16::from_be_bytes([ip_data[36], ip_data[37]]),
-            u16::from_be_bytes([ip_data[38], ip_data[39]]),
-        ));
-
-        let transport_data = &ip_data[40..];
-        let is_outgoing = self.local_ips.contains(&src_ip);
-
-        // Handle extension headers if needed
-        let (final_next_header, transport_offset) =
-            self.parse_ipv6_extension_headers(next_header, transport_data);
-        let final_transport_data = &transport_data[transport_offset..];
-
-        match final_next_header {
-            58 => self.parse_icmpv6(
-                final_transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
-                    process_name,
-                    process_id,
-                },
-            ),
-            6 => self.parse_tcp(
-                final_transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
-                    process_name,
-                    process_id,
-                },
-            ),
-            17 => self.parse_udp(
-                final_transport_data,
-                TransportParams {
-                    src_ip,
-                    dst_ip,
-                    is_outgoing,
-                    packet_len: data.len(),
-                    process_name,
-                    process_id,
-                },
-            ),
-            _ => None,
-        }
+        let range = if parsed.is_outgoing { 0..6 } else { 6..12 };
+        let mac: MacAddr = frame.get(range)?.try_into().ok()?;
+        mac::is_unicast(&mac).then_some(mac)
     }
 
     fn parse_tcp(&self, transport_data: &[u8], params: TransportParams) -> Option<ParsedPacket> {
@@ -450,57 +767,124 @@ impl PacketParser {
             return None;
         }
 
+        // The data offset can't point inside the fixed header
+        let tcp_header_len = ((transport_data[12] >> 4) as usize) * 4;
+        if tcp_header_len < 20 {
+            return None;
+        }
+
         let src_port = u16::from_be_bytes([transport_data[0], transport_data[1]]);
         let dst_port = u16::from_be_bytes([transport_data[2], transport_data[3]]);
+        let seq = u32::from_be_bytes([
+            transport_data[4],
+            transport_data[5],
+            transport_data[6],
+            transport_data[7],
+        ]);
+        let ack = u32::from_be_bytes([
+            transport_data[8],
+            transport_data[9],
+            transport_data[10],
+            transport_data[11],
+        ]);
         let flags = transport_data[13];
 
         let tcp_flags = parse_tcp_flags(flags);
 
-        let (local_addr, remote_addr) = if params.is_outgoing {
-            (
-                SocketAddr::new(params.src_ip, src_port),
-                SocketAddr::new(params.dst_ip, dst_port),
-            )
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(src_port, dst_port);
+
+        // An inbound SYN or SYN-ACK shows how the remote host's stack opens connections
+        let os_guess = if tcp_flags.syn && !is_outgoing && !params.forwarded {
+            let header = &transport_data[..tcp_header_len.min(transport_data.len())];
+            SynFingerprint::from_tcp_header(header, params.ttl, params.df)
+                .and_then(|syn| self.config.os_signatures.guess(&syn))
         } else {
-            (
-                SocketAddr::new(params.dst_ip, dst_port),
-                SocketAddr::new(params.src_ip, src_port),
-            )
+            None
         };
 
+        let header_end = tcp_header_len.min(transport_data.len());
+        let tcp_timestamps = tcp_timestamps(&transport_data[20..header_end]);
+
+        // Options running past the captured bytes leave no payload to look at
+        let payload = transport_data.get(tcp_header_len..).unwrap_or_default();
+
+        let connection_key = format!("TCP:{}-TCP:{}", local_addr, remote_addr);
+
         // Perform DPI if enabled and there's payload
-        let dpi_result = if self.config.enable_dpi {
-            let tcp_header_len = ((transport_data[12] >> 4) as usize) * 4;
-            if transport_data.len() > tcp_header_len {
-                let payload = &transport_data[tcp_header_len..];
-                dpi::analyze_tcp_packet(
-                    payload,
-                    local_addr.port(),
-                    remote_addr.port(),
-                    params.is_outgoing,
-                )
-            } else {
-                None
-            }
+        let (dpi_result, dpi_payload) = if self.config.enable_dpi && !payload.is_empty() {
+            self.inspect_payload(
+                Protocol::TCP,
+                &connection_key,
+                payload,
+                local_addr,
+                remote_addr,
+                is_outgoing,
+            )
         } else {
-            None
+            (None, None)
         };
 
         Some(ParsedPacket {
-            connection_key: format!("TCP:{}-TCP:{}", local_addr, remote_addr),
+            connection_key,
             protocol: Protocol::TCP,
             local_addr,
             remote_addr,
             tcp_flags: Some(tcp_flags),
+            tcp_seq: Some(seq),
+            tcp_ack: tcp_flags.ack.then_some(ack),
+            tcp_timestamps,
             protocol_state: ProtocolState::Tcp(TcpState::Unknown),
-            is_outgoing: params.is_outgoing,
+            is_outgoing,
+            forwarded: params.forwarded,
             packet_len: params.packet_len,
+            payload_len: payload.len(),
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
             dpi_result,
+            dpi_payload,
             process_name: params.process_name,
             process_id: params.process_id,
+            os_guess,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
         })
     }
 
+    /// Run DPI inline, or copy the payload prefix for the DPI worker pool,
+    /// unless the flow has been inspected enough already
+    fn inspect_payload(
+        &self,
+        protocol: Protocol,
+        connection_key: &str,
+        payload: &[u8],
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        is_outgoing: bool,
+    ) -> (Option<DpiResult>, Option<Vec<u8>>) {
+        if let Some(wants_dpi) = &self.config.wants_dpi
+            && !wants_dpi(connection_key)
+        {
+            return (None, None);
+        }
+        if self.config.defer_dpi {
+            let len = payload.len().min(DPI_PAYLOAD_PREFIX);
+            return (None, Some(payload[..len].to_vec()));
+        }
+        let dpi_result = dpi::analyze_payload(
+            protocol,
+            payload,
+            local_addr.port(),
+            remote_addr.port(),
+            is_outgoing,
+            &self.config.signatures,
+        );
+        (dpi_result, None)
+    }
+
     fn parse_udp(&self, transport_data: &[u8], params: TransportParams) -> Option<ParsedPacket> {
         if transport_data.len() < 8 {
             return None;
@@ -509,43 +893,60 @@ impl PacketParser {
         let src_port = u16::from_be_bytes([transport_data[0], transport_data[1]]);
         let dst_port = u16::from_be_bytes([transport_data[2], transport_data[3]]);
 
-        let (local_addr, remote_addr) = if params.is_outgoing {
-            (
-                SocketAddr::new(params.src_ip, src_port),
-                SocketAddr::new(params.dst_ip, dst_port),
-            )
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(src_port, dst_port);
+
+        // The length field covers header and payload. Trust it only when it
+        // fits in what was captured.
+        let udp_len = u16::from_be_bytes([transport_data[4], transport_data[5]]) as usize;
+        let payload = if (8..=transport_data.len()).contains(&udp_len) {
+            &transport_data[8..udp_len]
         } else {
-            (
-                SocketAddr::new(params.dst_ip, dst_port),
-                SocketAddr::new(params.src_ip, src_port),
-            )
+            &transport_data[8..]
         };
 
+        let connection_key = format!("UDP:{}-UDP:{}", local_addr, remote_addr);
+
         // Perform DPI if enabled and there's payload
-        let dpi_result = if self.config.enable_dpi && transport_data.len() > 8 {
-            let payload = &transport_data[8..];
-            dpi::analyze_udp_packet(
+        let (dpi_result, dpi_payload) = if self.config.enable_dpi && !payload.is_empty() {
+            self.inspect_payload(
+                Protocol::UDP,
+                &connection_key,
                 payload,
-                local_addr.port(),
-                remote_addr.port(),
-                params.is_outgoing,
+                local_addr,
+                remote_addr,
+                is_outgoing,
             )
         } else {
-            None
+            (None, None)
         };
 
         Some(ParsedPacket {
-            connection_key: format!("UDP:{}-UDP:{}", local_addr, remote_addr),
+            connection_key,
             protocol: Protocol::UDP,
             local_addr,
             remote_addr,
             tcp_flags: None,
-            protocol_state: ProtocolState::Udp,
-            is_outgoing: params.is_outgoing,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
+            protocol_state: ProtocolState::Udp(UdpState::Unconnected),
+            is_outgoing,
+            forwarded: params.forwarded,
             packet_len: params.packet_len,
+            payload_len: payload.len(),
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
             dpi_result,
+            dpi_payload,
             process_name: params.process_name,
             process_id: params.process_id,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
         })
     }
 
@@ -561,17 +962,8 @@ impl PacketParser {
             0
         };
 
-        let (local_addr, remote_addr) = if params.is_outgoing {
-            (
-                SocketAddr::new(params.src_ip, 0),
-                SocketAddr::new(params.dst_ip, 0),
-            )
-        } else {
-            (
-                SocketAddr::new(params.dst_ip, 0),
-                SocketAddr::new(params.src_ip, 0),
-            )
-        };
+        let echo_id = echo_identifier(transport_data, matches!(icmp_type, 0 | 8));
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(echo_id, echo_id);
 
         Some(ParsedPacket {
             connection_key: format!("ICMP:{}-ICMP:{}", local_addr, remote_addr),
@@ -579,15 +971,111 @@ impl PacketParser {
             local_addr,
             remote_addr,
             tcp_flags: None,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
             protocol_state: ProtocolState::Icmp {
                 icmp_type,
                 icmp_code,
             },
-            is_outgoing: params.is_outgoing,
+            is_outgoing,
+            forwarded: params.forwarded,
+            packet_len: params.packet_len,
+            payload_len: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
+            dpi_result: None,
+            dpi_payload: None,
+            process_name: params.process_name,
+            process_id: params.process_id,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
+        })
+    }
+
+    /// A packet of an IP protocol that isn't decoded, like ESP. It has no
+    /// ports, so each pair of hosts is one flow per protocol.
+    fn parse_other_ip(&self, protocol_num: u8, params: TransportParams) -> Option<ParsedPacket> {
+        let protocol = Protocol::Other(protocol_num);
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(0, 0);
+
+        Some(ParsedPacket {
+            connection_key: format!(
+                "{:?}:{}-{:?}:{}",
+                protocol, local_addr, protocol, remote_addr
+            ),
+            protocol,
+            local_addr,
+            remote_addr,
+            tcp_flags: None,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
+            protocol_state: ProtocolState::Other,
+            is_outgoing,
+            forwarded: params.forwarded,
+            packet_len: params.packet_len,
+            payload_len: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
+            dpi_result: None,
+            dpi_payload: None,
+            process_name: params.process_name,
+            process_id: params.process_id,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
+        })
+    }
+
+    /// An SCTP packet, keyed by the ports of its common header like UDP. The
+    /// chunks aren't followed, apart from the addresses a multi-homed sender
+    /// lists in its INIT or INIT ACK.
+    fn parse_sctp(&self, transport_data: &[u8], params: TransportParams) -> Option<ParsedPacket> {
+        if transport_data.len() < 12 {
+            return None;
+        }
+
+        let src_port = u16::from_be_bytes([transport_data[0], transport_data[1]]);
+        let dst_port = u16::from_be_bytes([transport_data[2], transport_data[3]]);
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(src_port, dst_port);
+
+        Some(ParsedPacket {
+            connection_key: format!("SCTP:{}-SCTP:{}", local_addr, remote_addr),
+            protocol: Protocol::SCTP,
+            local_addr,
+            remote_addr,
+            tcp_flags: None,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
+            protocol_state: ProtocolState::Other,
+            is_outgoing,
+            forwarded: params.forwarded,
             packet_len: params.packet_len,
+            payload_len: transport_data.len() - 12,
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
             dpi_result: None,
+            dpi_payload: None,
             process_name: params.process_name,
             process_id: params.process_id,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: sctp_init_addresses(&transport_data[12..]),
+            segments: 1,
+            scan: None,
         })
     }
 
@@ -603,17 +1091,8 @@ impl PacketParser {
             0
         };
 
-        let (local_addr, remote_addr) = if params.is_outgoing {
-            (
-                SocketAddr::new(params.src_ip, 0),
-                SocketAddr::new(params.dst_ip, 0),
-            )
-        } else {
-            (
-                SocketAddr::new(params.dst_ip, 0),
-                SocketAddr::new(params.src_ip, 0),
-            )
-        };
+        let echo_id = echo_identifier(transport_data, matches!(icmp_type, 128 | 129));
+        let (local_addr, remote_addr, is_outgoing) = params.endpoints(echo_id, echo_id);
 
         Some(ParsedPacket {
             connection_key: format!("ICMP:{}-ICMP:{}", local_addr, remote_addr),
@@ -621,26 +1100,40 @@ impl PacketParser {
             local_addr,
             remote_addr,
             tcp_flags: None,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
             protocol_state: ProtocolState::Icmp {
                 icmp_type,
                 icmp_code,
             },
-            is_outgoing: params.is_outgoing,
+            is_outgoing,
+            forwarded: params.forwarded,
             packet_len: params.packet_len,
+            payload_len: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
             dpi_result: None, // No DPI for ICMPv6
+            dpi_payload: None,
             process_name: params.process_name,
             process_id: params.process_id,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
         })
     }
 
     fn parse_arp_packet_inner(
         &self,
-        data: &[u8],
+        arp_data: &[u8],
         process_name: Option<String>,
         process_id: Option<u32>,
     ) -> Option<ParsedPacket> {
-        let arp_data = &data[14..];
-        if arp_data.len() < 28 {
+        if arp_data.len() < ARP_MESSAGE_LEN {
             return None;
         }
 
@@ -662,6 +1155,13 @@ impl PacketParser {
         };
 
         let is_outgoing = self.local_ips.contains(&sender_ip);
+        // Requests leave the target's MAC zeroed
+        let remote_mac: MacAddr = if is_outgoing {
+            arp_data[18..24].try_into().ok()?
+        } else {
+            arp_data[8..14].try_into().ok()?
+        };
+        let remote_mac = mac::is_unicast(&remote_mac).then_some(remote_mac);
         let (local_addr, remote_addr) = if is_outgoing {
             (SocketAddr::new(sender_ip, 0), SocketAddr::new(target_ip, 0))
         } else {
@@ -674,21 +1174,54 @@ impl PacketParser {
             local_addr,
             remote_addr,
             tcp_flags: None,
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
             protocol_state: ProtocolState::Arp { operation },
             is_outgoing,
-            packet_len: data.len(),
+            forwarded: false,
+            packet_len: ARP_MESSAGE_LEN,
+            payload_len: 0,
+            timestamp: SystemTime::UNIX_EPOCH,
+            instant: Instant::now(),
             dpi_result: None,
+            dpi_payload: None,
             process_name,
             process_id,
+            os_guess: None,
+            remote_mac,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
         })
     }
 
     // Raw IP packet parsing for PKTAP DLT_RAW and Linux Cooked Capture
+    /// Parse an IP packet with no link-layer header, by its version nibble
+    fn parse_raw_ip_packet(
+        &self,
+        data: &[u8],
+        truncated: usize,
+        process_name: Option<String>,
+        process_id: Option<u32>,
+        depth: usize,
+    ) -> Option<ParsedPacket> {
+        match data.first()? >> 4 {
+            4 => self.parse_raw_ipv4_packet(data, truncated, process_name, process_id, depth),
+            6 => self.parse_raw_ipv6_packet(data, truncated, process_name, process_id, depth),
+            _ => None,
+        }
+    }
+
     fn parse_raw_ipv4_packet(
         &self,
         data: &[u8],
+        truncated: usize,
         process_name: Option<String>,
         process_id: Option<u32>,
+        depth: usize,
     ) -> Option<ParsedPacket> {
         if data.len() < 20 {
             return None;
@@ -703,15 +1236,26 @@ impl PacketParser {
         let src_ip = IpAddr::V4(Ipv4Addr::new(data[12], data[13], data[14], data[15]));
         let dst_ip = IpAddr::V4(Ipv4Addr::new(data[16], data[17], data[18], data[19]));
 
+        // A header shorter than 20 bytes is malformed, and one whose options
+        // run past the captured bytes can't be parsed
         let ihl = data[0] & 0x0F;
         let ip_header_len = (ihl as usize) * 4;
-
-        if data.len() < ip_header_len {
+        if ihl < 5 || data.len() < ip_header_len {
             return None;
         }
 
-        let transport_data = &data[ip_header_len..];
-        let is_outgoing = self.local_ips.contains(&src_ip);
+        let packet_len = ipv4_packet_len(data, truncated);
+        // Leave out the link-layer padding of short packets
+        let total_len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let data = if (ip_header_len..data.len()).contains(&total_len) {
+            &data[..total_len]
+        } else {
+            data
+        };
+        let transport_data = data.get(ip_header_len..)?;
+        let (is_outgoing, forwarded) = self.direction(src_ip, dst_ip);
+        let ttl = data[8];
+        let df = Some(data[6] & 0x40 != 0);
 
         match protocol_num {
             1 => self.parse_icmp(
@@ -720,7 +1264,10 @@ impl PacketParser {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
@@ -731,31 +1278,72 @@ impl PacketParser {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
             ),
+            17 if self.follows_tunnel(depth) && is_vxlan(transport_data) => {
+                self.parse_vxlan(transport_data, truncated, (src_ip, dst_ip), depth)
+            }
             17 => self.parse_udp(
                 transport_data,
                 TransportParams {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
+                    process_name,
+                    process_id,
+                },
+            ),
+            47 if self.follows_tunnel(depth) && is_plain_gre(transport_data) => {
+                self.parse_gre(transport_data, truncated, (src_ip, dst_ip), depth)
+            }
+            132 => self.parse_sctp(
+                transport_data,
+                TransportParams {
+                    src_ip,
+                    dst_ip,
+                    is_outgoing,
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
+                    process_name,
+                    process_id,
+                },
+            ),
+            other => self.parse_other_ip(
+                other,
+                TransportParams {
+                    src_ip,
+                    dst_ip,
+                    is_outgoing,
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
             ),
-            _ => None,
         }
     }
 
     fn parse_raw_ipv6_packet(
         &self,
         data: &[u8],
+        truncated: usize,
         process_name: Option<String>,
         process_id: Option<u32>,
+        depth: usize,
     ) -> Option<ParsedPacket> {
         if data.len() < 40 {
             return None;
@@ -791,13 +1379,24 @@ impl PacketParser {
             u16::from_be_bytes([data[38], data[39]]),
         ));
 
+        let packet_len = ipv6_packet_len(data, truncated);
+        // Leave out the link-layer padding of short packets
+        let payload_len = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let data = if payload_len > 0 && 40 + payload_len < data.len() {
+            &data[..40 + payload_len]
+        } else {
+            data
+        };
         let transport_data = &data[40..];
-        let is_outgoing = self.local_ips.contains(&src_ip);
+        let (is_outgoing, forwarded) = self.direction(src_ip, dst_ip);
+        let ttl = data[7];
+        let df = None;
 
-        // Handle extension headers if needed
+        // Handle extension headers if needed. Their declared lengths can run
+        // past the captured bytes.
         let (final_next_header, transport_offset) =
-            self.parse_ipv6_extension_headers(next_header, transport_data);
-        let final_transport_data = &transport_data[transport_offset..];
+            self.parse_ipv6_extension_headers(next_header, transport_data)?;
+        let final_transport_data = transport_data.get(transport_offset..)?;
 
         match final_next_header {
             58 => self.parse_icmpv6(
@@ -806,7 +1405,10 @@ impl PacketParser {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
@@ -817,72 +1419,1398 @@ impl PacketParser {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
             ),
+            17 if self.follows_tunnel(depth) && is_vxlan(final_transport_data) => {
+                self.parse_vxlan(final_transport_data, truncated, (src_ip, dst_ip), depth)
+            }
             17 => self.parse_udp(
                 final_transport_data,
                 TransportParams {
                     src_ip,
                     dst_ip,
                     is_outgoing,
-                    packet_len: data.len(),
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
+                    process_name,
+                    process_id,
+                },
+            ),
+            47 if self.follows_tunnel(depth) && is_plain_gre(final_transport_data) => {
+                self.parse_gre(final_transport_data, truncated, (src_ip, dst_ip), depth)
+            }
+            132 => self.parse_sctp(
+                final_transport_data,
+                TransportParams {
+                    src_ip,
+                    dst_ip,
+                    is_outgoing,
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
+                    process_name,
+                    process_id,
+                },
+            ),
+            // Extension headers cut off by the snapshot length, or nothing
+            // after them
+            0 | 43 | 44 | 51 | 59 | 60 => None,
+            other => self.parse_other_ip(
+                other,
+                TransportParams {
+                    src_ip,
+                    dst_ip,
+                    is_outgoing,
+                    forwarded,
+                    packet_len,
+                    ttl,
+                    df,
                     process_name,
                     process_id,
                 },
             ),
-            _ => None,
         }
     }
 
-    fn parse_ipv6_extension_headers(&self, mut next_header: u8, data: &[u8]) -> (u8, usize) {
+    /// Whether to look inside a tunnel found `depth` tunnels deep
+    fn follows_tunnel(&self, depth: usize) -> bool {
+        self.config.decapsulate_tunnels && depth < MAX_TUNNEL_DEPTH
+    }
+
+    /// Parse the packet a GRE header (RFC 2784, with the RFC 2890 key and
+    /// sequence number) carries, recording the tunnel on it. The header has
+    /// been checked with `is_plain_gre`.
+    fn parse_gre(
+        &self,
+        gre_data: &[u8],
+        truncated: usize,
+        outer: (IpAddr, IpAddr),
+        depth: usize,
+    ) -> Option<ParsedPacket> {
+        let header = gre_data.get(..4)?;
+        let flags = u16::from_be_bytes([header[0], header[1]]);
+        // Checksum, key and sequence number each add four bytes when present
+        let optional_fields = [0x8000, 0x2000, 0x1000]
+            .into_iter()
+            .filter(|bit| flags & bit != 0)
+            .count();
+        let inner = gre_data.get(4 + optional_fields * 4..)?;
+
+        // The process PKTAP names owns the tunnel, not the flows inside it
+        let mut parsed = match u16::from_be_bytes([header[2], header[3]]) {
+            0x0800 | 0x86dd => self.parse_raw_ip_packet(inner, truncated, None, None, depth + 1),
+            // Transparent Ethernet bridging
+            0x6558 => self.parse_ethernet_packet(inner, truncated, None, None, depth + 1),
+            _ => None,
+        }?;
+        parsed.tunnel = Some(Tunnel {
+            kind: TunnelKind::Gre,
+            outer,
+        });
+        Some(parsed)
+    }
+
+    /// Parse the Ethernet frame in a VXLAN datagram (RFC 7348), recording the
+    /// tunnel and the frame's VNI on it
+    fn parse_vxlan(
+        &self,
+        udp_data: &[u8],
+        truncated: usize,
+        outer: (IpAddr, IpAddr),
+        depth: usize,
+    ) -> Option<ParsedPacket> {
+        let header = udp_data.get(8..16)?;
+        let vni = u32::from_be_bytes([0, header[4], header[5], header[6]]);
+        let frame = &udp_data[16..];
+
+        let mut parsed = self.parse_ethernet_packet(frame, truncated, None, None, depth + 1)?;
+        parsed.tunnel = Some(Tunnel {
+            kind: TunnelKind::Vxlan { vni },
+            outer,
+        });
+        Some(parsed)
+    }
+
+    /// Walk the extension headers in front of an IPv6 packet's upper-layer
+    /// header, returning its protocol number and offset. A chain cut short by
+    /// the capture stops where the captured bytes end. None for a fragment
+    /// other than the first, which carries no transport header to read, and
+    /// for chains longer than `MAX_IPV6_EXTENSION_HEADERS`.
+    fn parse_ipv6_extension_headers(
+        &self,
+        mut next_header: u8,
+        data: &[u8],
+    ) -> Option<(u8, usize)> {
         let mut offset = 0;
 
         const HOP_BY_HOP: u8 = 0;
         const ROUTING: u8 = 43;
         const FRAGMENT: u8 = 44;
-        const ENCAPSULATING_SECURITY: u8 = 50;
         const AUTHENTICATION: u8 = 51;
         const DESTINATION_OPTIONS: u8 = 60;
 
-        loop {
+        // One pass per extension header, plus one for the header after them
+        for _ in 0..=MAX_IPV6_EXTENSION_HEADERS {
+            if offset >= data.len() {
+                return Some((next_header, offset));
+            }
             match next_header {
                 HOP_BY_HOP | ROUTING | DESTINATION_OPTIONS => {
                     if data.len() < offset + 2 {
-                        return (next_header, offset);
+                        return Some((next_header, offset));
                     }
                     next_header = data[offset];
-                    let header_len = ((data[offset + 1] as usize) + 1) * 8;
-                    offset += header_len;
+                    offset += ((data[offset + 1] as usize) + 1) * 8;
                 }
                 FRAGMENT => {
                     if data.len() < offset + 8 {
-                        return (next_header, offset);
+                        return Some((next_header, offset));
+                    }
+                    let fragment_offset =
+                        u16::from_be_bytes([data[offset + 2], data[offset + 3]]) >> 3;
+                    if fragment_offset != 0 {
+                        return None;
                     }
                     next_header = data[offset];
                     offset += 8;
                 }
                 AUTHENTICATION => {
                     if data.len() < offset + 2 {
-                        return (next_header, offset);
+                        return Some((next_header, offset));
                     }
                     next_header = data[offset];
-                    let header_len = ((data[offset + 1] as usize) + 2) * 4;
-                    offset += header_len;
-                }
-                ENCAPSULATING_SECURITY => {
-                    return (next_header, offset);
-                }
-                _ => {
-                    return (next_header, offset);
+                    offset += ((data[offset + 1] as usize) + 2) * 4;
                 }
+                // ESP hides whatever follows it, and anything else is the
+                // upper-layer header
+                _ => return Some((next_header, offset)),
             }
+        }
+        None
+    }
+}
 
-            if offset >= data.len() {
-                return (next_header, offset);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::merge::{
+        MergeOptions, create_connection_from_packet, merge_packet_into_connection,
+    };
+
+    const ROUTER: [u8; 4] = [192, 0, 2, 1];
+    const LAN_HOST: [u8; 4] = [10, 0, 0, 5];
+    const WAN_HOST: [u8; 4] = [198, 51, 100, 7];
+
+    /// Parser for a host whose only address is `ROUTER`
+    fn router_parser(router_mode: RouterMode) -> PacketParser {
+        PacketParser {
+            local_ips: [IpAddr::from(ROUTER)].into_iter().collect(),
+            local_subnets: vec![LocalSubnet {
+                network: IpAddr::from(ROUTER),
+                prefix: 24,
+            }],
+            config: ParserConfig {
+                enable_dpi: false,
+                router_mode,
+                ..Default::default()
+            },
+            linktype: Some(1),
+        }
+    }
+
+    /// Ethernet/IPv4/UDP frame with `payload_len` bytes of payload
+    fn udp_frame(
+        src: [u8; 4],
+        sport: u16,
+        dst: [u8; 4],
+        dport: u16,
+        payload_len: usize,
+    ) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((20 + 8 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&sport.to_be_bytes());
+        frame.extend_from_slice(&dport.to_be_bytes());
+        frame.extend_from_slice(&((8 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend(std::iter::repeat_n(0u8, payload_len));
+        frame
+    }
+
+    /// Ethernet/IPv4 TCP SYN to port 443 with the given TTL, window and options
+    fn syn_frame(src: [u8; 4], dst: [u8; 4], ttl: u8, window: u16, options: &[u8]) -> Vec<u8> {
+        let tcp_len = 20 + options.len();
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((20 + tcp_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, ttl, 6, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&443u16.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(&[((tcp_len / 4) as u8) << 4, 0x02]);
+        frame.extend_from_slice(&window.to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(options);
+        frame
+    }
+
+    fn parse(parser: &PacketParser, frame: &[u8]) -> ParsedPacket {
+        parser
+            .parse_packet(frame, frame.len(), SystemTime::now(), Instant::now())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_direction_follows_interface_addresses() {
+        // A host on a private LAN and a VPN at the same time
+        let wifi = [192, 168, 1, 10];
+        let vpn = [10, 8, 0, 2];
+        let lan_peer = [192, 168, 1, 20];
+        let parser = PacketParser {
+            local_ips: [IpAddr::from(wifi), IpAddr::from(vpn)]
+                .into_iter()
+                .collect(),
+            local_subnets: Vec::new(),
+            ..router_parser(RouterMode::Auto)
+        };
+
+        // Both ends are private, but only one is this host
+        let outbound = parse(&parser, &udp_frame(wifi, 40000, lan_peer, 8009, 10));
+        let inbound = parse(&parser, &udp_frame(lan_peer, 8009, wifi, 40000, 10));
+        assert!(outbound.is_outgoing && !inbound.is_outgoing);
+        assert!(!outbound.forwarded && !inbound.forwarded);
+        assert_eq!(inbound.connection_key, outbound.connection_key);
+        assert_eq!(inbound.local_addr, "192.168.1.10:40000".parse().unwrap());
+
+        // The second interface's address is this host's too
+        let tunnelled = parse(&parser, &udp_frame(vpn, 41000, WAN_HOST, 53, 10));
+        assert!(tunnelled.is_outgoing && !tunnelled.forwarded);
+        assert_eq!(tunnelled.local_addr, "10.8.0.2:41000".parse().unwrap());
+
+        // Two other private hosts are someone else's flow
+        let between = parse(&parser, &udp_frame(LAN_HOST, 5000, lan_peer, 6000, 10));
+        assert!(between.forwarded);
+    }
+
+    #[test]
+    fn test_local_flows_keep_local_and_remote_sides() {
+        let parser = router_parser(RouterMode::Auto);
+
+        let outbound = parse(&parser, &udp_frame(ROUTER, 5353, WAN_HOST, 53, 10));
+        assert!(outbound.is_outgoing && !outbound.forwarded);
+        assert_eq!(outbound.local_addr, "192.0.2.1:5353".parse().unwrap());
+
+        let inbound = parse(&parser, &udp_frame(WAN_HOST, 53, ROUTER, 5353, 10));
+        assert!(!inbound.is_outgoing && !inbound.forwarded);
+        assert_eq!(inbound.connection_key, outbound.connection_key);
+    }
+
+    #[test]
+    fn test_forwarded_flow_counts_each_direction() {
+        let parser = router_parser(RouterMode::Auto);
+
+        // LAN host to WAN host and back, through the router
+        let a_to_b = parse(&parser, &udp_frame(LAN_HOST, 40000, WAN_HOST, 53, 10));
+        let b_to_a = parse(&parser, &udp_frame(WAN_HOST, 53, LAN_HOST, 40000, 100));
+        assert!(a_to_b.forwarded && b_to_a.forwarded);
+        assert_eq!(a_to_b.connection_key, b_to_a.connection_key);
+        assert_eq!(a_to_b.local_addr, "10.0.0.5:40000".parse().unwrap());
+        assert_eq!(a_to_b.remote_addr, "198.51.100.7:53".parse().unwrap());
+        assert!(a_to_b.is_outgoing && !b_to_a.is_outgoing);
+
+        // The key doesn't depend on which side spoke first
+        let reply_first = parse(&parser, &udp_frame(WAN_HOST, 53, LAN_HOST, 40000, 100));
+        assert_eq!(reply_first.local_addr, a_to_b.local_addr);
+
+        let conn = create_connection_from_packet(&a_to_b, &MergeOptions::default());
+        let conn = merge_packet_into_connection(conn, &b_to_a, &MergeOptions::default());
+        assert!(conn.forwarded);
+        assert_eq!((conn.packets_sent, conn.bytes_sent), (1, 38));
+        assert_eq!((conn.packets_received, conn.bytes_received), (1, 128));
+    }
+
+    #[test]
+    fn test_offloaded_super_packets() {
+        // 28,960 payload bytes sent as one TSO packet: 20 full-sized segments
+        let mut frame = syn_frame(ROUTER, WAN_HOST, 64, 502, &[]);
+        frame[16..18].copy_from_slice(&29000u16.to_be_bytes());
+        frame[47] = TCP_ACK | TCP_PSH;
+        frame.extend(std::iter::repeat_n(0u8, 28960));
+
+        // Bytes come from the IP length, without the Ethernet header
+        let packet = parse(&router_parser(RouterMode::Auto), &frame);
+        assert_eq!((packet.packet_len, packet.payload_len), (29000, 28960));
+        assert_eq!(packet.segments, 1);
+
+        let mut segmenting = router_parser(RouterMode::Auto);
+        segmenting.config.offload_counting = OffloadCounting::Segments;
+        let packet = parse(&segmenting, &frame);
+        assert_eq!((packet.packet_len, packet.segments), (29000, 20));
+        let conn = create_connection_from_packet(&packet, &MergeOptions::default());
+        assert_eq!((conn.packets_sent, conn.bytes_sent), (20, 29000));
+
+        // Only what the MTU couldn't carry in one packet is split
+        let small = parse(&segmenting, &syn_frame(ROUTER, WAN_HOST, 64, 502, &[]));
+        assert_eq!(small.segments, 1);
+        segmenting.config.mtu = 9000;
+        assert_eq!(parse(&segmenting, &frame).segments, 4);
+
+        // A super-packet cut short by the snaplen is still counted in full
+        let captured = &frame[..14 + 40 + 100];
+        let packet = segmenting
+            .parse_packet(captured, frame.len(), SystemTime::now(), Instant::now())
+            .unwrap();
+        assert_eq!((packet.packet_len, packet.segments), (29000, 4));
+    }
+
+    #[test]
+    fn test_inbound_syn_guesses_the_remote_os() {
+        let parser = router_parser(RouterMode::Auto);
+        // MSS 1460, NOP, window scale 8, NOP, NOP, SACK permitted
+        let windows = [2, 4, 0x05, 0xb4, 1, 3, 3, 8, 1, 1, 4, 2];
+
+        let frame = syn_frame(WAN_HOST, ROUTER, 113, 64240, &windows);
+        let inbound = parse(&parser, &frame);
+        let guess = inbound.os_guess.clone().unwrap();
+        assert_eq!(guess.os, "Windows 10/11");
+        assert_eq!(guess.to_string(), "Windows 10/11 (100%)");
+
+        // Only the first guess sticks
+        let mut conn = create_connection_from_packet(&inbound, &MergeOptions::default());
+        let mut retransmit = parse(&parser, &frame);
+        retransmit.os_guess = None;
+        conn = merge_packet_into_connection(conn, &retransmit, &MergeOptions::default());
+        assert_eq!(conn.remote_os_guess, Some(guess));
+
+        // This host's own SYNs say nothing about the peer
+        let outbound = parse(&parser, &syn_frame(ROUTER, WAN_HOST, 128, 64240, &windows));
+        assert!(outbound.os_guess.is_none());
+    }
+
+    #[test]
+    fn test_tcp_timestamps_option() {
+        let parser = router_parser(RouterMode::Auto);
+        // MSS 1460, SACK permitted, timestamps 100/7, NOP, window scale 7
+        let options = [
+            2, 4, 0x05, 0xb4, 4, 2, 8, 10, 0, 0, 0, 100, 0, 0, 0, 7, 1, 3, 3, 7,
+        ];
+        let syn = parse(&parser, &syn_frame(ROUTER, WAN_HOST, 64, 64240, &options));
+        assert_eq!(syn.tcp_timestamps, Some((100, 7)));
+
+        let without = parse(
+            &parser,
+            &syn_frame(ROUTER, WAN_HOST, 64, 64240, &options[..4]),
+        );
+        assert_eq!(without.tcp_timestamps, None);
+        // Cut short by the capture
+        assert_eq!(tcp_timestamps(&options[6..12]), None);
+    }
+
+    #[test]
+    fn test_cooked_capture_frames() {
+        let ip_packet = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[])[14..].to_vec();
+
+        // SLL: packet type, ARPHRD, address length, address, protocol
+        let mut sll = vec![0, 0, 0, 1, 0, 6];
+        sll.extend_from_slice(&[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc, 0, 0]);
+        sll.extend_from_slice(&0x0800u16.to_be_bytes());
+        sll.extend_from_slice(&ip_packet);
+
+        // SLL2: protocol, reserved, interface index, ARPHRD, packet type,
+        // address length, address
+        let mut sll2 = 0x0800u16.to_be_bytes().to_vec();
+        sll2.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 1, 0, 6]);
+        sll2.extend_from_slice(&[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc, 0, 0]);
+        sll2.extend_from_slice(&ip_packet);
+
+        for (linktype, frame) in [(113, sll), (276, sll2)] {
+            let mut parser = router_parser(RouterMode::Auto);
+            parser.linktype = Some(linktype);
+            let syn = parse(&parser, &frame);
+            assert_eq!(syn.protocol, Protocol::TCP);
+            assert!(!syn.is_outgoing);
+            assert_eq!(syn.local_addr, "192.0.2.1:443".parse().unwrap());
+            assert_eq!(syn.remote_addr, "198.51.100.7:40000".parse().unwrap());
+
+            let conn = create_connection_from_packet(&syn, &MergeOptions::default());
+            assert_eq!(conn.state(), "SYN_RECV");
+            assert_eq!(conn.bytes_received, ip_packet.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_pppoe_session_frames() {
+        let plain = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[]);
+        let ip_packet = &plain[14..];
+        let expected = parse(&router_parser(RouterMode::Auto), &plain);
+
+        // PPPoE session 0x1234 carrying IPv4
+        let mut pppoe = vec![0x11, 0x00, 0x12, 0x34];
+        pppoe.extend_from_slice(&((2 + ip_packet.len()) as u16).to_be_bytes());
+        pppoe.extend_from_slice(&0x0021u16.to_be_bytes());
+        pppoe.extend_from_slice(ip_packet);
+
+        let ethernet = |ethertype: u16, pppoe: &[u8]| {
+            let mut frame = plain[..12].to_vec();
+            frame.extend_from_slice(&ethertype.to_be_bytes());
+            frame.extend_from_slice(pppoe);
+            frame
+        };
+        let mut sll = vec![0, 0, 0, 1, 0, 6];
+        sll.extend_from_slice(&[0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc, 0, 0]);
+        sll.extend_from_slice(&0x8864u16.to_be_bytes());
+        sll.extend_from_slice(&pppoe);
+        // PPP in HDLC-like framing, as on a BSD ppp interface
+        let mut ppp = vec![0xff, 0x03];
+        ppp.extend_from_slice(&pppoe[6..]);
+
+        for (linktype, frame) in [
+            (1, ethernet(0x8864, &pppoe)),
+            (113, sll),
+            (51, pppoe.clone()),
+            (9, ppp),
+        ] {
+            let mut parser = router_parser(RouterMode::Auto);
+            parser.linktype = Some(linktype);
+            let packet = parse(&parser, &frame);
+            assert_eq!(packet.connection_key, expected.connection_key);
+            assert_eq!(packet.packet_len, expected.packet_len);
+            assert_eq!(
+                create_connection_from_packet(&packet, &MergeOptions::default()).state(),
+                "SYN_RECV"
+            );
+        }
+
+        // Discovery and link control frames carry no connection
+        let parser = router_parser(RouterMode::Auto);
+        let mut lcp = pppoe.clone();
+        lcp[6..8].copy_from_slice(&0xc021u16.to_be_bytes());
+        for frame in [ethernet(0x8863, &pppoe), ethernet(0x8864, &lcp)] {
+            assert!(
+                parser
+                    .parse_packet(&frame, frame.len(), SystemTime::now(), Instant::now())
+                    .is_none()
+            );
+        }
+    }
+
+    /// `router_parser` in auto mode, looking inside tunnels
+    fn tunnel_parser() -> PacketParser {
+        PacketParser {
+            config: ParserConfig {
+                decapsulate_tunnels: true,
+                ..router_parser(RouterMode::Auto).config
+            },
+            ..router_parser(RouterMode::Auto)
+        }
+    }
+
+    #[test]
+    fn test_gre_tunnels_are_decapsulated() {
+        const TUNNEL_PEER: [u8; 4] = [203, 0, 113, 9];
+        let plain = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[]);
+        // Ethernet/IPv4/GRE from the tunnel peer carrying `inner`
+        let gre = |flags: u16, protocol: u16, inner: &[u8]| {
+            let optional = [0x8000u16, 0x2000, 0x1000]
+                .into_iter()
+                .filter(|bit| flags & bit != 0)
+                .count();
+            let gre_len = 4 + optional * 4 + inner.len();
+            let mut frame = vec![0u8; 12];
+            frame.extend_from_slice(&0x0800u16.to_be_bytes());
+            frame.extend_from_slice(&[0x45, 0]);
+            frame.extend_from_slice(&((20 + gre_len) as u16).to_be_bytes());
+            frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 47, 0, 0]);
+            frame.extend_from_slice(&TUNNEL_PEER);
+            frame.extend_from_slice(&ROUTER);
+            frame.extend_from_slice(&flags.to_be_bytes());
+            frame.extend_from_slice(&protocol.to_be_bytes());
+            frame.extend(std::iter::repeat_n(0xaau8, optional * 4));
+            frame.extend_from_slice(inner);
+            frame
+        };
+        let routed = gre(0x2000, 0x0800, &plain[14..]);
+        let bridged = gre(0xb000, 0x6558, &plain);
+
+        // A flow between the tunnel ends unless asked to look inside
+        let parser = router_parser(RouterMode::Auto);
+        let outer = parse(&parser, &routed);
+        assert_eq!(outer.protocol, Protocol::Other(47));
+        assert!(outer.tunnel.is_none());
+
+        let parser = tunnel_parser();
+        let expected = parse(&parser, &plain);
+        let tunnel = Some(Tunnel {
+            kind: TunnelKind::Gre,
+            outer: (IpAddr::from(TUNNEL_PEER), IpAddr::from(ROUTER)),
+        });
+        for frame in [routed, bridged] {
+            let packet = parse(&parser, &frame);
+            assert_eq!(packet.tunnel, tunnel);
+            assert_eq!(packet.connection_key, expected.connection_key);
+            assert!(!packet.is_outgoing);
+
+            let conn = create_connection_from_packet(&packet, &MergeOptions::default());
+            assert_eq!(conn.tunnel, tunnel);
+            assert_eq!(conn.state(), "SYN_RECV");
+        }
+
+        // Routing present, or the enhanced GRE of PPTP, stay outer flows
+        for flags in [0x4000, 0x0001] {
+            let frame = gre(flags, 0x0800, &plain[14..]);
+            let packet = parse(&parser, &frame);
+            assert_eq!(packet.protocol, Protocol::Other(47));
+            assert_eq!(packet.remote_addr, "203.0.113.9:0".parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_vxlan_tunnels_are_decapsulated() {
+        const TUNNEL_PEER: [u8; 4] = [203, 0, 113, 9];
+        let plain = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[]);
+        // Ethernet/IPv4/UDP/VXLAN from the tunnel peer on VNI 5001
+        let vxlan = |inner: &[u8]| {
+            let mut frame = udp_frame(TUNNEL_PEER, 51000, ROUTER, VXLAN_PORT, 8 + inner.len());
+            frame.truncate(frame.len() - 8 - inner.len());
+            frame.extend_from_slice(&[0x08, 0, 0, 0, 0x00, 0x13, 0x89, 0]);
+            frame.extend_from_slice(inner);
+            frame
+        };
+        let encapsulated = vxlan(&plain);
+
+        // The tunnel itself unless asked to look inside
+        let packet = parse(&router_parser(RouterMode::Auto), &encapsulated);
+        assert_eq!(packet.remote_addr, "203.0.113.9:51000".parse().unwrap());
+        assert_eq!(packet.tunnel, None);
+
+        let parser = tunnel_parser();
+        let expected = parse(&parser, &plain);
+        let tunnel = Some(Tunnel {
+            kind: TunnelKind::Vxlan { vni: 5001 },
+            outer: (IpAddr::from(TUNNEL_PEER), IpAddr::from(ROUTER)),
+        });
+        let packet = parse(&parser, &encapsulated);
+        assert_eq!(packet.tunnel, tunnel);
+        assert_eq!(packet.connection_key, expected.connection_key);
+        // Only the inner packet counts, even when counting link-layer headers
+        assert_eq!(packet.packet_len, expected.packet_len);
+        let counting = PacketParser {
+            config: ParserConfig {
+                count_link_layer: true,
+                ..tunnel_parser().config
+            },
+            ..tunnel_parser()
+        };
+        assert_eq!(
+            parse(&counting, &encapsulated).packet_len,
+            expected.packet_len
+        );
+
+        let conn = create_connection_from_packet(&packet, &MergeOptions::default());
+        assert_eq!(conn.tunnel, tunnel);
+        assert_eq!(conn.state(), "SYN_RECV");
+
+        // Tunnels nested past the limit are left as tunnel traffic, tagged
+        // with the outermost one
+        let mut nested = plain;
+        for _ in 0..MAX_TUNNEL_DEPTH {
+            nested = vxlan(&nested);
+        }
+        assert_eq!(
+            parse(&parser, &nested).connection_key,
+            expected.connection_key
+        );
+        let packet = parse(&parser, &vxlan(&nested));
+        assert_eq!(packet.remote_addr, "203.0.113.9:51000".parse().unwrap());
+        assert_eq!(packet.tunnel, tunnel);
+    }
+
+    #[test]
+    fn test_mpls_label_stacks_are_skipped() {
+        let parser = router_parser(RouterMode::Auto);
+        let plain = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[]);
+        let expected = parse(&parser, &plain);
+        // Label entries with TTL 64, the last one marked bottom of stack
+        let labelled = |labels: &[u32]| {
+            let mut frame = plain[..12].to_vec();
+            frame.extend_from_slice(&0x8847u16.to_be_bytes());
+            for (i, label) in labels.iter().enumerate() {
+                let bottom = u32::from(i == labels.len() - 1);
+                frame.extend_from_slice(&((label << 12) | (bottom << 8) | 64).to_be_bytes());
+            }
+            frame.extend_from_slice(&plain[14..]);
+            frame
+        };
+
+        for frame in [labelled(&[16]), labelled(&[16, 1024])] {
+            let packet = parse(&parser, &frame);
+            assert_eq!(packet.connection_key, expected.connection_key);
+            assert_eq!(packet.packet_len, expected.packet_len);
+            assert_eq!(
+                create_connection_from_packet(&packet, &MergeOptions::default()).state(),
+                "SYN_RECV"
+            );
+        }
+
+        // Bottom of stack past the limit
+        let endless = labelled(&[16; MAX_MPLS_LABELS + 1]);
+        assert!(
+            parser
+                .parse_packet(&endless, endless.len(), SystemTime::now(), Instant::now())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_vlan_tags_are_skipped() {
+        let parser = router_parser(RouterMode::Auto);
+        let plain = syn_frame(WAN_HOST, ROUTER, 64, 64240, &[]);
+        let tagged = |tags: &[(u16, u16)]| {
+            let mut frame = plain[..12].to_vec();
+            for &(tpid, tci) in tags {
+                frame.extend_from_slice(&tpid.to_be_bytes());
+                frame.extend_from_slice(&tci.to_be_bytes());
+            }
+            frame.extend_from_slice(&plain[12..]);
+            frame
+        };
+
+        let expected = parse(&parser, &plain);
+        assert_eq!(expected.vlan_id, None);
+
+        // Priority 5 on VLAN 100, then an 802.1ad outer tag around it
+        let dot1q = tagged(&[(0x8100, 0xa064)]);
+        let qinq = tagged(&[(0x88a8, 0x0007), (0x8100, 0x0064)]);
+        for frame in [dot1q, qinq] {
+            let packet = parse(&parser, &frame);
+            assert_eq!(packet.vlan_id, Some(100));
+            assert_eq!(packet.connection_key, expected.connection_key);
+            assert_eq!(packet.packet_len, expected.packet_len);
+
+            let conn = create_connection_from_packet(&packet, &MergeOptions::default());
+            assert_eq!(conn.vlan_id, Some(100));
+            assert_eq!(conn.state(), "SYN_RECV");
+        }
+
+        // A tag cut off by the snaplen
+        let truncated = &tagged(&[(0x8100, 100)])[..16];
+        assert!(
+            parser
+                .parse_packet(truncated, 64, SystemTime::now(), Instant::now())
+                .is_none()
+        );
+    }
+
+    /// Ethernet ARP message with `opcode` 1 (request) or 2 (reply)
+    fn arp_frame(opcode: u16, sender: ([u8; 6], [u8; 4]), target: ([u8; 6], [u8; 4])) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0806u16.to_be_bytes());
+        frame.extend_from_slice(&[0, 1, 0x08, 0, 6, 4]);
+        frame.extend_from_slice(&opcode.to_be_bytes());
+        frame.extend_from_slice(&sender.0);
+        frame.extend_from_slice(&sender.1);
+        frame.extend_from_slice(&target.0);
+        frame.extend_from_slice(&target.1);
+        frame
+    }
+
+    #[test]
+    fn test_arp_exchanges_are_tracked_per_ip_pair() {
+        const LAN_PEER: [u8; 4] = [192, 0, 2, 50];
+        const OTHER_PEER: [u8; 4] = [192, 0, 2, 51];
+        let parser = router_parser(RouterMode::Auto);
+        let pi = [0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56];
+        let own = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc];
+
+        // A peer asks for this host's MAC and gets an answer
+        let request = parse(&parser, &arp_frame(1, (pi, LAN_PEER), ([0; 6], ROUTER)));
+        let reply = parse(&parser, &arp_frame(2, (own, ROUTER), (pi, LAN_PEER)));
+        assert_eq!(request.protocol, Protocol::ARP);
+        assert!(!request.is_outgoing && reply.is_outgoing);
+        assert_eq!(request.connection_key, reply.connection_key);
+        assert_eq!(request.local_addr, "192.0.2.1:0".parse().unwrap());
+        assert_eq!(request.remote_addr, "192.0.2.50:0".parse().unwrap());
+
+        let mut conn = create_connection_from_packet(&request, &MergeOptions::default());
+        assert_eq!(conn.state(), "ARP_REQUEST");
+        conn = merge_packet_into_connection(conn, &reply, &MergeOptions::default());
+        assert_eq!(conn.state(), "ARP_REPLY");
+        assert_eq!((conn.packets_sent, conn.packets_received), (1, 1));
+        assert_eq!(conn.remote_mac, Some(pi));
+
+        // A gratuitous ARP announces the sender's own address
+        let gratuitous = parse(&parser, &arp_frame(2, (pi, OTHER_PEER), (pi, OTHER_PEER)));
+        assert_eq!(gratuitous.local_addr, gratuitous.remote_addr);
+        assert_ne!(gratuitous.connection_key, request.connection_key);
+
+        // The `any` device delivers ARP in cooked framing
+        let mut sll = vec![0, 0, 0, 1, 0, 6];
+        sll.extend_from_slice(&pi);
+        sll.extend_from_slice(&[0, 0]);
+        sll.extend_from_slice(&arp_frame(1, (pi, LAN_PEER), ([0; 6], ROUTER))[12..]);
+        let cooked = PacketParser {
+            linktype: Some(113),
+            ..router_parser(RouterMode::Auto)
+        };
+        assert_eq!(parse(&cooked, &sll).connection_key, request.connection_key);
+
+        // Only Ethernet/IPv4 ARP is understood
+        let mut other_hardware = arp_frame(1, (pi, LAN_PEER), ([0; 6], ROUTER));
+        other_hardware[15] = 6;
+        assert!(
+            parser
+                .parse_packet(
+                    &other_hardware,
+                    other_hardware.len(),
+                    SystemTime::now(),
+                    Instant::now()
+                )
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_mac_is_kept_only_for_peers_on_a_local_subnet() {
+        const LAN_PEER: [u8; 4] = [192, 0, 2, 50];
+        let parser = router_parser(RouterMode::Auto);
+        let pi = [0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56];
+        let gateway = [0x00, 0x00, 0x0c, 0x01, 0x02, 0x03];
+        let own = [0x00, 0x1b, 0x21, 0xaa, 0xbb, 0xcc];
+        let with_macs = |mut frame: Vec<u8>, dst: [u8; 6], src: [u8; 6]| {
+            frame[..6].copy_from_slice(&dst);
+            frame[6..12].copy_from_slice(&src);
+            frame
+        };
+
+        // A host on this host's /24, in both directions
+        let inbound = udp_frame(LAN_PEER, 5000, ROUTER, 53, 10);
+        assert_eq!(
+            parse(&parser, &with_macs(inbound, own, pi)).remote_mac,
+            Some(pi)
+        );
+        let outbound = udp_frame(ROUTER, 53, LAN_PEER, 5000, 10);
+        assert_eq!(
+            parse(&parser, &with_macs(outbound, pi, own)).remote_mac,
+            Some(pi)
+        );
+
+        // Off-subnet traffic carries the gateway's MAC, not the peer's
+        let internet = udp_frame(WAN_HOST, 53, ROUTER, 5353, 10);
+        assert_eq!(
+            parse(&parser, &with_macs(internet, own, gateway)).remote_mac,
+            None
+        );
+
+        // Broadcasts don't name a host
+        let broadcast = udp_frame(ROUTER, 68, LAN_PEER, 67, 10);
+        assert_eq!(
+            parse(&parser, &with_macs(broadcast, [0xff; 6], own)).remote_mac,
+            None
+        );
+
+        // ARP replies name the sender
+        let mut arp = vec![0u8; 12];
+        arp.extend_from_slice(&0x0806u16.to_be_bytes());
+        arp.extend_from_slice(&[0, 1, 0x08, 0, 6, 4, 0, 2]);
+        arp.extend_from_slice(&pi);
+        arp.extend_from_slice(&LAN_PEER);
+        arp.extend_from_slice(&own);
+        arp.extend_from_slice(&ROUTER);
+        assert_eq!(
+            parse(&parser, &with_macs(arp, own, pi)).remote_mac,
+            Some(pi)
+        );
+
+        let subnet = LocalSubnet {
+            network: "2001:db8:1::10".parse().unwrap(),
+            prefix: 64,
+        };
+        assert!(subnet.contains("2001:db8:1::99".parse().unwrap()));
+        assert!(!subnet.contains("2001:db8:2::99".parse().unwrap()));
+        assert!(!subnet.contains(IpAddr::from(LAN_PEER)));
+    }
+
+    #[test]
+    fn test_malformed_headers_are_rejected_without_panicking() {
+        // Deferred DPI hands back the payload the parser settled on
+        let parser = PacketParser {
+            config: ParserConfig {
+                enable_dpi: true,
+                defer_dpi: true,
+                ..router_parser(RouterMode::Off).config
+            },
+            ..router_parser(RouterMode::Off)
+        };
+        let try_parse = |frame: &[u8]| {
+            parser.parse_packet(frame, frame.len(), SystemTime::now(), Instant::now())
+        };
+        let frame = udp_frame(ROUTER, 5353, WAN_HOST, 53, 10);
+
+        // IHL below 5, and options running past the end of the packet
+        for ihl in [0x40, 0x44, 0x4f] {
+            let mut bad = frame.clone();
+            bad[14] = ihl;
+            assert!(try_parse(&bad).is_none());
+        }
+
+        // Every truncation of the frame
+        for len in 0..frame.len() {
+            try_parse(&frame[..len]);
+        }
+
+        // Empty UDP payloads don't go to DPI
+        let empty = parse(&parser, &udp_frame(ROUTER, 5353, WAN_HOST, 53, 0));
+        assert!(empty.dpi_payload.is_none());
+
+        // Ethernet padding isn't payload, and a bogus UDP length is ignored
+        let mut padded = udp_frame(ROUTER, 5353, WAN_HOST, 53, 4);
+        padded.extend_from_slice(&[0xaa; 20]);
+        assert_eq!(parse(&parser, &padded).dpi_payload.unwrap().len(), 4);
+        let mut bogus = frame.clone();
+        bogus[38..40].copy_from_slice(&0xffffu16.to_be_bytes());
+        assert_eq!(parse(&parser, &bogus).dpi_payload.unwrap().len(), 10);
+
+        // Nothing is copied for a flow that has had enough DPI
+        let inspected = PacketParser {
+            config: ParserConfig {
+                wants_dpi: Some(Arc::new(|key: &str| !key.starts_with("UDP:"))),
+                ..parser.config.clone()
+            },
+            ..router_parser(RouterMode::Off)
+        };
+        assert!(parse(&inspected, &frame).dpi_payload.is_none());
+
+        // A TCP data offset inside the fixed header
+        let mut tcp = frame.clone();
+        tcp[23] = 6;
+        tcp.extend_from_slice(&[0; 12]);
+        tcp[16..18].copy_from_slice(&40u16.to_be_bytes());
+        tcp[34 + 12] = 0x40;
+        assert!(try_parse(&tcp).is_none());
+
+        // IPv6 extension header longer than the packet
+        let mut ipv6 = vec![0u8; 12];
+        ipv6.extend_from_slice(&0x86ddu16.to_be_bytes());
+        ipv6.extend_from_slice(&[0x60, 0, 0, 0, 0, 16, 0, 64]);
+        ipv6.extend_from_slice(&[0; 32]);
+        ipv6.extend_from_slice(&[17, 0xff, 0, 0, 0, 0, 0, 0]);
+        ipv6.extend_from_slice(&[0; 8]);
+        assert!(try_parse(&ipv6).is_none());
+    }
+
+    #[test]
+    fn test_router_mode_can_be_forced() {
+        let transit = udp_frame(LAN_HOST, 40000, WAN_HOST, 53, 10);
+        let local = udp_frame(ROUTER, 5353, WAN_HOST, 53, 10);
+
+        let off = router_parser(RouterMode::Off);
+        let parsed = parse(&off, &transit);
+        assert!(!parsed.forwarded && !parsed.is_outgoing);
+        assert_eq!(parsed.local_addr, "198.51.100.7:53".parse().unwrap());
+
+        let on = router_parser(RouterMode::On);
+        assert!(parse(&on, &local).forwarded);
+        assert!(parse(&on, &transit).forwarded);
+    }
+
+    /// Ethernet/IPv4 ICMP message of `icmp_type` whose identifier field is `id`
+    fn icmp_frame(src: [u8; 4], dst: [u8; 4], icmp_type: u8, id: u16) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0, 0, 28, 0, 0, 0x40, 0, 64, 1, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&[icmp_type, 0, 0, 0]);
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(&1u16.to_be_bytes()); // Sequence number
+        frame
+    }
+
+    #[test]
+    fn test_echo_requests_and_replies_pair_by_identifier() {
+        let parser = router_parser(RouterMode::Auto);
+
+        let request = parse(&parser, &icmp_frame(ROUTER, WAN_HOST, 8, 0x1234));
+        let reply = parse(&parser, &icmp_frame(WAN_HOST, ROUTER, 0, 0x1234));
+        assert_eq!(request.protocol, Protocol::ICMP);
+        assert!(request.is_outgoing && !reply.is_outgoing);
+        assert_eq!(request.connection_key, reply.connection_key);
+        assert_eq!(request.local_addr, "192.0.2.1:4660".parse().unwrap());
+
+        // A second ping to the same host is its own flow
+        let other = parse(&parser, &icmp_frame(ROUTER, WAN_HOST, 8, 0x4321));
+        assert_ne!(other.connection_key, request.connection_key);
+
+        let mut conn = create_connection_from_packet(&request, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REQUEST");
+        conn = merge_packet_into_connection(conn, &reply, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REPLY");
+        assert_eq!((conn.packets_sent, conn.packets_received), (1, 1));
+
+        // Errors carry no identifier and are keyed by host pair
+        let unreachable = parse(&parser, &icmp_frame(WAN_HOST, ROUTER, 3, 0x1234));
+        assert_eq!(unreachable.local_addr.port(), 0);
+        assert_eq!(
+            create_connection_from_packet(&unreachable, &MergeOptions::default()).state(),
+            "DEST_UNREACH"
+        );
+    }
+
+    #[test]
+    fn test_other_ip_protocols_are_flows_between_hosts() {
+        let parser = router_parser(RouterMode::Auto);
+        // ESP in place of the ICMP header
+        let esp = |src, dst| {
+            let mut frame = icmp_frame(src, dst, 0, 0);
+            frame[23] = 50;
+            frame
+        };
+        let outbound = parse(&parser, &esp(ROUTER, WAN_HOST));
+        let inbound = parse(&parser, &esp(WAN_HOST, ROUTER));
+        assert_eq!(outbound.protocol, Protocol::Other(50));
+        assert_eq!(outbound.protocol.to_string(), "IP50");
+        assert_eq!(outbound.connection_key, inbound.connection_key);
+        assert!(outbound.is_outgoing && !inbound.is_outgoing);
+        assert_eq!(outbound.remote_addr, "198.51.100.7:0".parse().unwrap());
+
+        let conn = create_connection_from_packet(&outbound, &MergeOptions::default());
+        assert_eq!(conn.key(), outbound.connection_key);
+        assert_eq!(conn.state(), "ACTIVE");
+
+        // ESP behind IPv6 extension headers
+        let local: Ipv6Addr = "2001:db8:1::10".parse().unwrap();
+        let server: Ipv6Addr = "2001:db8:2::1".parse().unwrap();
+        let mut esp = tcp6_frame(server, local, 0, false);
+        esp[62] = 50;
+        let parsed = parse(&parser, &esp);
+        assert_eq!(parsed.protocol, Protocol::Other(50));
+        assert_eq!(parsed.remote_addr, "[2001:db8:2::1]:0".parse().unwrap());
+
+        // SCTP has ports where TCP has them
+        esp[62] = 132;
+        let parsed = parse(&parser, &esp);
+        assert_eq!(parsed.protocol, Protocol::SCTP);
+        assert_eq!(parsed.remote_addr, "[2001:db8:2::1]:443".parse().unwrap());
+        assert_eq!(parsed.local_addr, "[2001:db8:1::10]:50000".parse().unwrap());
+    }
+
+    /// Ethernet/IPv4 SCTP packet from port 36412 to 38412 carrying `chunk`
+    fn sctp_frame(
+        src: [u8; 4],
+        dst: [u8; 4],
+        src_port: u16,
+        dst_port: u16,
+        chunk: &[u8],
+    ) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((20 + 12 + chunk.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 132, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&src_port.to_be_bytes());
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]); // Verification tag and checksum
+        frame.extend_from_slice(chunk);
+        frame
+    }
+
+    #[test]
+    fn test_sctp_associations_and_their_addresses() {
+        let parser = router_parser(RouterMode::Auto);
+
+        // INIT listing this host's second address and an IPv6 one
+        let mut init = vec![1, 0, 0, 0];
+        init.extend_from_slice(&[0; 16]); // Tag, window, streams and TSN
+        init.extend_from_slice(&[0, 5, 0, 8, 192, 0, 2, 1]);
+        init.extend_from_slice(&[0, 5, 0, 8, 203, 0, 113, 9]);
+        init.extend_from_slice(&[0, 6, 0, 20]);
+        init.extend_from_slice(&"2001:db8::9".parse::<Ipv6Addr>().unwrap().octets());
+        init.extend_from_slice(&[0, 0xc, 0, 6, 0, 5, 0, 0]); // Supported address types, padded
+        let init_len = init.len() as u16;
+        init[2..4].copy_from_slice(&init_len.to_be_bytes());
+
+        let outbound = parse(&parser, &sctp_frame(ROUTER, WAN_HOST, 36412, 38412, &init));
+        assert_eq!(outbound.protocol, Protocol::SCTP);
+        assert_eq!(outbound.protocol.to_string(), "SCTP");
+        assert_eq!(outbound.local_addr, "192.0.2.1:36412".parse().unwrap());
+        assert_eq!(outbound.remote_addr, "198.51.100.7:38412".parse().unwrap());
+        assert_eq!(outbound.sctp_addresses.len(), 3);
+
+        let conn = create_connection_from_packet(&outbound, &MergeOptions::default());
+        assert_eq!(conn.key(), outbound.connection_key);
+        assert_eq!(
+            conn.alternate_local_addrs,
+            [
+                "203.0.113.9:36412".parse::<SocketAddr>().unwrap(),
+                "[2001:db8::9]:36412".parse().unwrap(),
+            ]
+        );
+
+        // The peer's DATA lands on the same association and keeps the addresses
+        let data = [0, 3, 0, 16, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        let inbound = parse(&parser, &sctp_frame(WAN_HOST, ROUTER, 38412, 36412, &data));
+        assert_eq!(inbound.connection_key, outbound.connection_key);
+        assert!(!inbound.is_outgoing && inbound.sctp_addresses.is_empty());
+        let conn = merge_packet_into_connection(conn, &inbound, &MergeOptions::default());
+        assert_eq!(conn.alternate_local_addrs.len(), 2);
+
+        // A chunk cut short, and a parameter claiming no length
+        assert_eq!(sctp_init_addresses(&init[..32]), [IpAddr::from(ROUTER)]);
+        let mut init_ack = [0u8; 28];
+        init_ack[0] = 2;
+        init_ack[3] = 28;
+        init_ack[21] = 5;
+        assert!(sctp_init_addresses(&init_ack).is_empty());
+    }
+
+    /// Ethernet/IPv6 TCP ACK from `src` port 443 to `dst` port 50000 carrying
+    /// `payload_len` bytes, behind hop-by-hop options and a routing header.
+    /// A jumbogram leaves the IPv6 payload length at zero.
+    fn tcp6_frame(src: Ipv6Addr, dst: Ipv6Addr, payload_len: usize, jumbogram: bool) -> Vec<u8> {
+        let ip_payload_len = if jumbogram {
+            0
+        } else {
+            8 + 8 + 20 + payload_len
+        };
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&(ip_payload_len as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 64]); // Hop-by-hop options next
+        frame.extend_from_slice(&src.octets());
+        frame.extend_from_slice(&dst.octets());
+        frame.extend_from_slice(&[43, 0, 1, 4, 0, 0, 0, 0]); // PadN, routing header next
+        frame.extend_from_slice(&[6, 0, 0, 0, 0, 0, 0, 0]); // TCP next
+        frame.extend_from_slice(&443u16.to_be_bytes());
+        frame.extend_from_slice(&50000u16.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(&[0x50, 0x10, 0xff, 0xff, 0, 0, 0, 0]);
+        frame.extend(std::iter::repeat_n(0xabu8, payload_len));
+        frame
+    }
+
+    #[test]
+    fn test_ipv6_tcp_behind_extension_headers() {
+        let local: Ipv6Addr = "2001:db8:1::10".parse().unwrap();
+        let server: Ipv6Addr = "2001:db8:2::1".parse().unwrap();
+        let parser = PacketParser {
+            local_ips: [IpAddr::V6(local)].into_iter().collect(),
+            local_subnets: Vec::new(),
+            ..router_parser(RouterMode::Auto)
+        };
+
+        let mut frame = tcp6_frame(server, local, 10, false);
+        frame.extend_from_slice(&[0; 4]); // Ethernet padding
+        let parsed = parse(&parser, &frame);
+        assert_eq!(parsed.protocol, Protocol::TCP);
+        assert!(!parsed.is_outgoing && !parsed.forwarded);
+        assert_eq!(parsed.local_addr, "[2001:db8:1::10]:50000".parse().unwrap());
+        assert_eq!(parsed.remote_addr, "[2001:db8:2::1]:443".parse().unwrap());
+        assert!(parsed.tcp_flags.as_ref().is_some_and(|flags| flags.ack));
+        assert_eq!((parsed.packet_len, parsed.payload_len), (86, 10));
+
+        // A jumbogram's length comes from the frame instead
+        let jumbo = parse(&parser, &tcp6_frame(server, local, 10, true));
+        assert_eq!(jumbo.connection_key, parsed.connection_key);
+        assert_eq!((jumbo.packet_len, jumbo.payload_len), (86, 10));
+    }
+
+    #[test]
+    fn test_ipv6_fragments() {
+        let local: Ipv6Addr = "2001:db8:1::10".parse().unwrap();
+        let server: Ipv6Addr = "2001:db8:2::1".parse().unwrap();
+        let parser = PacketParser {
+            local_ips: [IpAddr::V6(local)].into_iter().collect(),
+            local_subnets: Vec::new(),
+            ..router_parser(RouterMode::Auto)
+        };
+        // A fragment header in place of the routing header
+        let fragment = |offset: u16| {
+            let mut frame = tcp6_frame(server, local, 10, false);
+            frame[62..70].copy_from_slice(&[6, 0, 0, 0, 0, 0, 0, 1]);
+            frame[64..66].copy_from_slice(&((offset << 3) | 1).to_be_bytes());
+            frame[54] = 44;
+            frame
+        };
+
+        let first = parse(&parser, &fragment(0));
+        assert_eq!(first.remote_addr, "[2001:db8:2::1]:443".parse().unwrap());
+
+        // Later fragments carry no TCP header, so their bytes aren't ports
+        let later = fragment(185);
+        assert!(
+            parser
+                .parse_packet(&later, later.len(), SystemTime::now(), Instant::now())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ipv6_extension_chains_never_panic_or_spin() {
+        let local: Ipv6Addr = "2001:db8:1::10".parse().unwrap();
+        let server: Ipv6Addr = "2001:db8:2::1".parse().unwrap();
+        let parser = router_parser(RouterMode::Auto);
+        // xorshift so the test is deterministic without extra dependencies
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        const EXTENSION_HEADERS: [u8; 6] = [0, 43, 44, 50, 51, 60];
+
+        for _ in 0..20_000 {
+            let mut frame = tcp6_frame(server, local, 0, next() % 2 == 0);
+            frame.truncate(54);
+            // A chain of extension headers with small, arbitrary lengths,
+            // sometimes pointing back at itself without end
+            let headers = (next() % 16) as usize;
+            frame[20] = EXTENSION_HEADERS[(next() % 6) as usize];
+            for i in 0..headers {
+                let next_header = if i + 1 == headers && next() % 2 == 0 {
+                    [6, 17, 58][(next() % 3) as usize]
+                } else {
+                    EXTENSION_HEADERS[(next() % 6) as usize]
+                };
+                let len = (next() % 3) as usize;
+                frame.extend_from_slice(&[next_header, len as u8]);
+                frame.extend((0..6 + len * 8).map(|_| next() as u8));
             }
+            frame.extend((0..next() % 40).map(|_| next() as u8));
+            let cut = (next() as usize) % (frame.len() + 1);
+            frame.truncate(cut.max(54));
+            let _ = parser.parse_packet(&frame, frame.len(), SystemTime::now(), Instant::now());
+        }
+
+        // Hop-by-hop options naming themselves as the next header, in a
+        // jumbogram so that all of them count
+        let mut frame = tcp6_frame(server, local, 0, true);
+        frame.truncate(54);
+        for _ in 0..1000 {
+            frame.extend_from_slice(&[0, 0, 1, 4, 0, 0, 0, 0]);
+        }
+        assert!(
+            parser
+                .parse_packet(&frame, frame.len(), SystemTime::now(), Instant::now())
+                .is_none()
+        );
+    }
+
+    /// Ethernet/IPv6/UDP frame with `payload_len` bytes of payload
+    fn udp6_frame(
+        src: Ipv6Addr,
+        sport: u16,
+        dst: Ipv6Addr,
+        dport: u16,
+        payload_len: usize,
+    ) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&((8 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[17, 64]);
+        frame.extend_from_slice(&src.octets());
+        frame.extend_from_slice(&dst.octets());
+        frame.extend_from_slice(&sport.to_be_bytes());
+        frame.extend_from_slice(&dport.to_be_bytes());
+        frame.extend_from_slice(&((8 + payload_len) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend(std::iter::repeat_n(0u8, payload_len));
+        frame
+    }
+
+    #[test]
+    fn test_ipv6_flow_counts_each_direction() {
+        // A v6-only host: its link-local and global addresses are both its own
+        let link_local: Ipv6Addr = "fe80::10".parse().unwrap();
+        let global: Ipv6Addr = "2001:db8:1::10".parse().unwrap();
+        let resolver: Ipv6Addr = "2001:db8:2::53".parse().unwrap();
+        let router: Ipv6Addr = "fe80::1".parse().unwrap();
+        let parser = PacketParser {
+            local_ips: [IpAddr::V6(link_local), IpAddr::V6(global)]
+                .into_iter()
+                .collect(),
+            local_subnets: Vec::new(),
+            ..router_parser(RouterMode::Auto)
+        };
+
+        let query = parse(&parser, &udp6_frame(global, 40000, resolver, 53, 30));
+        let answer = parse(&parser, &udp6_frame(resolver, 53, global, 40000, 120));
+        assert!(query.is_outgoing && !answer.is_outgoing);
+        assert!(!query.forwarded && !answer.forwarded);
+        assert_eq!(query.connection_key, answer.connection_key);
+
+        let conn = create_connection_from_packet(&query, &MergeOptions::default());
+        let conn = merge_packet_into_connection(conn, &answer, &MergeOptions::default());
+        assert_eq!((conn.packets_sent, conn.bytes_sent), (1, 78));
+        assert_eq!((conn.packets_received, conn.bytes_received), (1, 168));
+
+        // Link-local traffic to the router is this host's too
+        let solicit = parse(&parser, &udp6_frame(link_local, 546, router, 547, 40));
+        assert!(solicit.is_outgoing && !solicit.forwarded);
+        assert_eq!(solicit.local_addr, "[fe80::10]:546".parse().unwrap());
+    }
+
+    /// Ethernet/IPv6 frame carrying an ICMPv6 message of `icmp_type` with
+    /// `body` after the checksum
+    fn icmp6_frame(src: Ipv6Addr, dst: Ipv6Addr, icmp_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&((4 + body.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[58, 255]);
+        frame.extend_from_slice(&src.octets());
+        frame.extend_from_slice(&dst.octets());
+        frame.extend_from_slice(&[icmp_type, 0, 0, 0]);
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn test_icmpv6_echo_and_neighbor_discovery() {
+        let local: Ipv6Addr = "fe80::10".parse().unwrap();
+        let router: Ipv6Addr = "fe80::1".parse().unwrap();
+        let server: Ipv6Addr = "2001:db8:2::1".parse().unwrap();
+        let parser = PacketParser {
+            local_ips: [IpAddr::V6(local)].into_iter().collect(),
+            local_subnets: Vec::new(),
+            ..router_parser(RouterMode::Auto)
+        };
+
+        // Identifier 0x1234, sequence 1
+        let echo = [0x12, 0x34, 0, 1];
+        let request = parse(&parser, &icmp6_frame(local, server, 128, &echo));
+        let reply = parse(&parser, &icmp6_frame(server, local, 129, &echo));
+        assert_eq!(request.protocol, Protocol::ICMP);
+        assert!(request.is_outgoing && !reply.is_outgoing);
+        assert_eq!(request.connection_key, reply.connection_key);
+        assert_eq!(request.local_addr, "[fe80::10]:4660".parse().unwrap());
+        let conn = create_connection_from_packet(&request, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REQUEST");
+        let conn = merge_packet_into_connection(conn, &reply, &MergeOptions::default());
+        assert_eq!(conn.state(), "ECHO_REPLY");
+
+        // Neighbor solicitation for the router, to its solicited-node group
+        let mut solicit = vec![0; 4];
+        solicit.extend_from_slice(&router.octets());
+        let group: Ipv6Addr = "ff02::1:ff00:1".parse().unwrap();
+        let solicit = parse(&parser, &icmp6_frame(local, group, 135, &solicit));
+        assert!(solicit.is_outgoing);
+        assert_eq!(solicit.local_addr.port(), 0);
+        assert_eq!(
+            create_connection_from_packet(&solicit, &MergeOptions::default()).state(),
+            "NEIGHBOR_SOLICIT"
+        );
+
+        // A router advertisement to all nodes reaches this host
+        let all_nodes: Ipv6Addr = "ff02::1".parse().unwrap();
+        let advert = parse(&parser, &icmp6_frame(router, all_nodes, 134, &[0; 12]));
+        assert!(!advert.is_outgoing && !advert.forwarded);
+        assert_eq!(
+            create_connection_from_packet(&advert, &MergeOptions::default()).state(),
+            "ROUTER_ADVERT"
+        );
+    }
+
+    #[test]
+    fn test_bsd_loopback_frames() {
+        let v4 = IpAddr::from([127, 0, 0, 1]);
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let request = udp_frame([127, 0, 0, 1], 52000, [127, 0, 0, 1], 3000, 10);
+        let request6 = udp6_frame(Ipv6Addr::LOCALHOST, 52000, Ipv6Addr::LOCALHOST, 3000, 10);
+
+        // lo0 on a little-endian Mac, DLT_NULL captured on a big-endian host,
+        // and DLT_LOOP, which is always big-endian
+        let cases = [
+            (0, 2u32.to_le_bytes(), &request, v4),
+            (0, 2u32.to_be_bytes(), &request, v4),
+            (108, 2u32.to_be_bytes(), &request, v4),
+            (0, 30u32.to_le_bytes(), &request6, v6),
+            (0, 28u32.to_be_bytes(), &request6, v6),
+            (108, 24u32.to_be_bytes(), &request6, v6),
+        ];
+        for (linktype, family, frame, ip) in cases {
+            let parser = PacketParser {
+                local_ips: [v4, v6].into_iter().collect(),
+                local_subnets: Vec::new(),
+                linktype: Some(linktype),
+                ..router_parser(RouterMode::Auto)
+            };
+            let mut loopback = family.to_vec();
+            loopback.extend_from_slice(&frame[14..]);
+
+            let packet = parse(&parser, &loopback);
+            assert_eq!(packet.protocol, Protocol::UDP);
+            assert!(packet.is_outgoing && !packet.forwarded);
+            assert_eq!(packet.local_addr, SocketAddr::new(ip, 52000));
+            assert_eq!(packet.remote_addr, SocketAddr::new(ip, 3000));
+            assert_eq!(packet.packet_len, frame.len() - 14);
+        }
+
+        // Neither IPv4 nor IPv6
+        let parser = PacketParser {
+            linktype: Some(0),
+            ..router_parser(RouterMode::Auto)
+        };
+        let mut other = 7u32.to_le_bytes().to_vec();
+        other.extend_from_slice(&request[14..]);
+        assert!(
+            parser
+                .parse_packet(&other, other.len(), SystemTime::now(), Instant::now())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_raw_ip_frames_from_tunnel_interfaces() {
+        // A WireGuard peer address on wg0, talking to a host behind the tunnel
+        let tunnel_v4 = IpAddr::from([10, 8, 0, 2]);
+        let tunnel_v6: Ipv6Addr = "fd00:8::2".parse().unwrap();
+        let remote_v6: Ipv6Addr = "fd00:8::1".parse().unwrap();
+        let dns = udp_frame([10, 8, 0, 2], 40000, [10, 8, 0, 1], 53, 30);
+        let dns6 = udp6_frame(tunnel_v6, 40000, remote_v6, 53, 30);
+
+        for linktype in [12, 101] {
+            let parser = PacketParser {
+                local_ips: [tunnel_v4, IpAddr::V6(tunnel_v6)].into_iter().collect(),
+                local_subnets: Vec::new(),
+                linktype: Some(linktype),
+                ..router_parser(RouterMode::Auto)
+            };
+
+            let query = parse(&parser, &dns[14..]);
+            assert!(query.is_outgoing && !query.forwarded);
+            let conn = create_connection_from_packet(&query, &MergeOptions::default());
+            assert_eq!(conn.protocol, Protocol::UDP);
+            assert_eq!(conn.local_addr, "10.8.0.2:40000".parse().unwrap());
+            assert_eq!(conn.remote_addr, "10.8.0.1:53".parse().unwrap());
+            assert_eq!((conn.packets_sent, conn.bytes_sent), (1, 58));
+
+            let query6 = parse(&parser, &dns6[14..]);
+            assert!(query6.is_outgoing);
+            assert_eq!(
+                query6.remote_addr,
+                SocketAddr::new(IpAddr::V6(remote_v6), 53)
+            );
         }
     }
 }
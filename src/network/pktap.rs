@@ -1,6 +1,7 @@
 // PKTAP (Packet Tap) support for macOS
 // Provides process identification for network packets
-use log::{debug, warn};
+use crate::network::capture::LOG_TARGET;
+use log::{debug, trace};
 use std::mem;
 
 /// PKTAP header structure as defined by Apple
@@ -33,7 +34,7 @@ impl PktapHeader {
     pub fn from_bytes(data: &[u8]) -> Option<Self> {
         // Check minimum size
         if data.len() < mem::size_of::<PktapHeader>() {
-            debug!("Packet too small for PKTAP header: {} bytes", data.len());
+            trace!(target: LOG_TARGET, "Packet too small for PKTAP header: {} bytes", data.len());
             return None;
         }
 
@@ -43,7 +44,8 @@ impl PktapHeader {
 
         // Sanity check the length field
         if length < 108 || length as usize > data.len() {
-            debug!(
+            trace!(
+                target: LOG_TARGET,
                 "Invalid PKTAP header length: {} (packet size: {})",
                 length,
                 data.len()
@@ -51,10 +53,11 @@ impl PktapHeader {
             return None;
         }
 
-        // Parse the full header
+        // Parse the full header. Packet buffers have no particular alignment,
+        // so the header has to be read unaligned.
         unsafe {
             let header_ptr = data.as_ptr() as *const PktapHeader;
-            let mut header = (*header_ptr).clone();
+            let mut header = std::ptr::read_unaligned(header_ptr);
 
             // Convert from network byte order if needed
             header.pth_length = u32::from_le_bytes(header.pth_length.to_le_bytes());
@@ -90,7 +93,8 @@ impl PktapHeader {
             process_name
         };
 
-        debug!(
+        trace!(
+            target: LOG_TARGET,
             "PKTAP process info: name={:?}, pid={:?}",
             final_process_name, pid
         );
@@ -157,13 +161,15 @@ fn extract_process_name_from_bytes(bytes: &[u8; 20]) -> Option<String> {
         .join(" "); // Join with single spaces
 
     if normalized.is_empty() || !normalized.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
-        debug!(
+        trace!(
+            target: LOG_TARGET,
             "🚫 Rejected PKTAP process name: raw='{:?}', normalized='{}'",
             raw_str, normalized
         );
         None
     } else {
-        debug!(
+        trace!(
+            target: LOG_TARGET,
             "✅ Extracted PKTAP process name: raw='{:?}' -> normalized='{}'",
             raw_str, normalized
         );
@@ -185,13 +191,14 @@ pub fn parse_pktap_packet(data: &[u8]) -> Option<(PktapHeader, &[u8])> {
     let header = PktapHeader::from_bytes(data)?;
 
     if !header.is_valid() {
-        warn!("Invalid PKTAP header detected");
+        debug!(target: LOG_TARGET, "Invalid PKTAP header detected");
         return None;
     }
 
     let payload_offset = header.payload_offset();
     if data.len() <= payload_offset {
-        warn!(
+        debug!(
+            target: LOG_TARGET,
             "PKTAP header claims payload at offset {} but packet is only {} bytes",
             payload_offset,
             data.len()
@@ -200,7 +207,8 @@ pub fn parse_pktap_packet(data: &[u8]) -> Option<(PktapHeader, &[u8])> {
     }
 
     let payload = &data[payload_offset..];
-    debug!(
+    trace!(
+        target: LOG_TARGET,
         "PKTAP: header_len={}, inner_dlt={}, payload_len={}",
         header.pth_length,
         header.pth_dlt,
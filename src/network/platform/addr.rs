@@ -0,0 +1,176 @@
+// network/platform/addr.rs - Socket addresses as printed by lsof, ss and netstat
+use crate::network::services::ServiceLookup;
+use crate::network::types::Protocol;
+use anyhow::{Result, anyhow};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::OnceLock;
+
+/// Address parsed from tool output, with the zone ID (`%eth0`) split off so
+/// the address still matches what we see on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedAddr {
+    pub addr: SocketAddr,
+    pub zone: Option<String>,
+}
+
+impl ParsedAddr {
+    /// The address with a v4-mapped IPv6 address (`::ffff:10.0.0.1`) turned
+    /// back into the IPv4 address that actually goes over the wire
+    pub fn unmapped(&self) -> SocketAddr {
+        unmap(self.addr)
+    }
+}
+
+/// Turn a v4-mapped IPv6 address (`::ffff:10.0.0.1`), as reported for the
+/// IPv4 traffic of dual-stack sockets, back into the plain IPv4 address
+pub fn unmap(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(IpAddr::V4(v4), v6.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
+fn services() -> &'static ServiceLookup {
+    static SERVICES: OnceLock<ServiceLookup> = OnceLock::new();
+    SERVICES.get_or_init(|| ServiceLookup::from_embedded().unwrap_or_default())
+}
+
+/// Parse `host:port` in any of the forms the socket tools print:
+/// `10.0.0.1:443`, `[::1]:443`, `[fe80::1%eth0]:546`, `fe80::1%eth0:546`,
+/// `::ffff:10.0.0.1:8080`, `*:443`, `0.0.0.0:*` and `127.0.0.1:http`.
+/// A `*` address is the IPv4 unspecified address and a `*` port is 0; service
+/// names are resolved for `protocol`.
+pub fn parse_addr(input: &str, protocol: Protocol) -> Result<ParsedAddr> {
+    let input = input.trim();
+
+    // Split on the last colon outside brackets
+    let (host, port) = if let Some(rest) = input.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .ok_or_else(|| anyhow!("Unclosed '[' in address '{}'", input))?;
+        let port = after
+            .strip_prefix(':')
+            .ok_or_else(|| anyhow!("Missing port in address '{}'", input))?;
+        (host, port)
+    } else {
+        input
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("Missing port in address '{}'", input))?
+    };
+
+    let (host, zone) = match host.split_once('%') {
+        Some((_, "")) => return Err(anyhow!("Empty zone ID in address '{}'", input)),
+        Some((host, zone)) => (host, Some(zone.to_string())),
+        None => (host, None),
+    };
+
+    let ip = match host {
+        "*" => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        _ => host
+            .parse::<IpAddr>()
+            .map_err(|_| anyhow!("Invalid IP address '{}' in '{}'", host, input))?,
+    };
+    if zone.is_some() && !ip.is_ipv6() {
+        return Err(anyhow!("Zone ID on a non-IPv6 address '{}'", input));
+    }
+
+    let port = match port {
+        "*" => 0,
+        _ if port.bytes().all(|b| b.is_ascii_digit()) => port
+            .parse::<u16>()
+            .map_err(|_| anyhow!("Invalid port '{}' in '{}'", port, input))?,
+        _ => services()
+            .port(port, protocol)
+            .ok_or_else(|| anyhow!("Unknown {} service '{}' in '{}'", protocol, port, input))?,
+    };
+
+    Ok(ParsedAddr {
+        addr: SocketAddr::new(ip, port),
+        zone,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> ParsedAddr {
+        parse_addr(input, Protocol::TCP).unwrap()
+    }
+
+    #[test]
+    fn test_parse_addr_formats() {
+        let cases = [
+            ("10.0.0.1:443", "10.0.0.1:443", None),
+            ("[::1]:443", "[::1]:443", None),
+            (":::22", "[::]:22", None),
+            ("[fe80::1%eth0]:546", "[fe80::1]:546", Some("eth0")),
+            ("fe80::1%2:546", "[fe80::1]:546", Some("2")),
+            ("*:443", "0.0.0.0:443", None),
+            ("0.0.0.0:*", "0.0.0.0:0", None),
+            ("[::]:*", "[::]:0", None),
+            ("*:*", "0.0.0.0:0", None),
+            ("::ffff:10.0.0.1:8080", "[::ffff:10.0.0.1]:8080", None),
+            ("[::ffff:10.0.0.1]:8080", "[::ffff:10.0.0.1]:8080", None),
+            ("127.0.0.1:http", "127.0.0.1:80", None),
+            (" 127.0.0.1:ssh ", "127.0.0.1:22", None),
+        ];
+
+        for (input, addr, zone) in cases {
+            let parsed = parse(input);
+            assert_eq!(parsed.addr, addr.parse().unwrap(), "{}", input);
+            assert_eq!(parsed.zone.as_deref(), zone, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unmapped_only_changes_v4_mapped_addresses() {
+        assert_eq!(
+            parse("::ffff:10.0.0.1:8080").unmapped(),
+            "10.0.0.1:8080".parse().unwrap()
+        );
+        assert_eq!(parse("[::1]:443").unmapped(), "[::1]:443".parse().unwrap());
+        assert_eq!(
+            parse("10.0.0.1:443").unmapped(),
+            "10.0.0.1:443".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_service_names_depend_on_protocol() {
+        let ntp = parse_addr("0.0.0.0:ntp", Protocol::UDP).unwrap();
+        assert_eq!(ntp.addr.port(), 123);
+        assert!(parse_addr("0.0.0.0:no-such-service", Protocol::TCP).is_err());
+    }
+
+    #[test]
+    fn test_parse_addr_rejects_garbage() {
+        for input in [
+            "",
+            ":",
+            "10.0.0.1",
+            "10.0.0.1:",
+            "10.0.0.1:65536",
+            "10.0.0.1:-1",
+            "10.0.0.256:80",
+            "host:80",
+            "[::1]",
+            "[::1]443",
+            "[::1:443",
+            "[fe80::1%]:546",
+            "10.0.0.1%eth0:80",
+            "*",
+            "[]:80",
+            "->",
+        ] {
+            assert!(
+                parse_addr(input, Protocol::TCP).is_err(),
+                "accepted '{}'",
+                input
+            );
+        }
+    }
+}
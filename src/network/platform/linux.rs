@@ -1,30 +1,35 @@
 // network/platform/linux.rs - Linux process lookup
-use super::{ConnectionKey, ProcessLookup};
+use super::socket_cache::{DEFAULT_REFRESH_INTERVAL, SocketTableCache};
+use super::{ConnectionKey, ProcessLookup, ProcessUsage, lookup_socket};
 use crate::network::types::{Connection, Protocol};
 use anyhow::Result;
 use std::collections::HashMap;
 use std::fs;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
-use std::sync::RwLock;
+use std::path::Path;
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
-pub struct LinuxProcessLookup {
-    // Cache: ConnectionKey -> (pid, process_name)
-    cache: RwLock<ProcessCache>,
-}
+/// CPU samples of processes that haven't been asked about for this long are dropped
+const CPU_SAMPLE_MAX_AGE: Duration = Duration::from_secs(60);
 
-struct ProcessCache {
-    lookup: HashMap<ConnectionKey, (u32, String)>,
-    last_refresh: Instant,
+pub struct LinuxProcessLookup {
+    // Cache: ConnectionKey -> (pid, process_name), rebuilt at most once per interval
+    cache: SocketTableCache<HashMap<ConnectionKey, (u32, String)>>,
+    // pid -> (utime + stime in clock ticks, when they were read)
+    cpu_samples: Mutex<HashMap<u32, (u64, Instant)>>,
 }
 
 impl LinuxProcessLookup {
     pub fn new() -> Result<Self> {
+        Self::with_refresh_interval(DEFAULT_REFRESH_INTERVAL)
+    }
+
+    /// Create a lookup that rescans /proc at most once per `interval`
+    pub fn with_refresh_interval(interval: Duration) -> Result<Self> {
         Ok(Self {
-            cache: RwLock::new(ProcessCache {
-                lookup: HashMap::new(),
-                last_refresh: Instant::now() - Duration::from_secs(3600),
-            }),
+            cache: SocketTableCache::new(interval, Self::build_process_map),
+            cpu_samples: Mutex::new(HashMap::new()),
         })
     }
 
@@ -115,7 +120,17 @@ impl LinuxProcessLookup {
             Ok(c) => c,
             Err(_) => return Ok(()), // File might not exist
         };
+        Self::map_table(&content, protocol, inode_map, result);
+        Ok(())
+    }
 
+    /// Map the sockets of one /proc/net table to the processes owning their inodes
+    fn map_table(
+        content: &str,
+        protocol: Protocol,
+        inode_map: &HashMap<u64, (u32, String)>,
+        result: &mut HashMap<ConnectionKey, (u32, String)>,
+    ) {
         for (i, line) in content.lines().enumerate() {
             if i == 0 {
                 continue; // Skip header
@@ -141,16 +156,17 @@ impl LinuxProcessLookup {
             if let Ok(inode) = parts[9].parse::<u64>()
                 && let Some((pid, name)) = inode_map.get(&inode)
             {
+                // Dual-stack sockets in the tcp6/udp6 tables list their IPv4
+                // peers as v4-mapped addresses
                 let key = ConnectionKey {
                     protocol,
                     local_addr,
                     remote_addr,
-                };
+                }
+                .normalized();
                 result.insert(key, (*pid, name.clone()));
             }
         }
-
-        Ok(())
     }
 
     fn parse_hex_address(hex_addr: &str) -> Option<SocketAddr> {
@@ -182,6 +198,39 @@ impl LinuxProcessLookup {
         }
     }
 
+    /// Resident memory in bytes from the `VmRSS` line of /proc/{pid}/status
+    fn parse_vm_rss(status: &str) -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kib * 1024)
+    }
+
+    /// utime + stime in clock ticks from /proc/{pid}/stat. The command name
+    /// may contain spaces and parentheses, so fields are counted after its last ')'.
+    fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+        let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+        // utime and stime are fields 14 and 15, and the state (field 3) comes first here
+        let utime: u64 = fields.get(11)?.parse().ok()?;
+        let stime: u64 = fields.get(12)?.parse().ok()?;
+        Some(utime + stime)
+    }
+
+    /// Percent of one core used by a process whose CPU time went from
+    /// `previous` to `ticks` over `elapsed`
+    fn cpu_percent(
+        previous: u64,
+        ticks: u64,
+        elapsed: Duration,
+        ticks_per_second: u64,
+    ) -> Option<f32> {
+        if elapsed.is_zero() || ticks_per_second == 0 {
+            return None;
+        }
+        // A reused PID can start over from fewer ticks
+        let cpu_seconds = ticks.saturating_sub(previous) as f64 / ticks_per_second as f64;
+        Some((cpu_seconds / elapsed.as_secs_f64() * 100.0) as f32)
+    }
+
     fn extract_socket_inode(link: &str) -> Option<u64> {
         if link.starts_with("socket:[") && link.ends_with(']') {
             let inode_str = &link[8..link.len() - 1];
@@ -194,34 +243,137 @@ impl LinuxProcessLookup {
 
 impl ProcessLookup for LinuxProcessLookup {
     fn get_process_for_connection(&self, conn: &Connection) -> Option<(u32, String)> {
-        let key = ConnectionKey::from_connection(conn);
+        // A miss doesn't trigger a rescan - the table is only rebuilt once it is stale
+        let table = self.cache.get().ok()?;
+        lookup_socket(&table, conn)
+    }
 
-        // Try cache first
-        {
-            let cache = self.cache.read().unwrap();
-            if cache.last_refresh.elapsed() < Duration::from_secs(2)
-                && let Some(process_info) = cache.lookup.get(&key)
-            {
-                return Some(process_info.clone());
-            }
-        }
+    fn refresh(&self) -> Result<()> {
+        self.cache.force_refresh()?;
+        Ok(())
+    }
 
-        // Cache is stale or miss, refresh
-        if self.refresh().is_ok() {
-            let cache = self.cache.read().unwrap();
-            cache.lookup.get(&key).cloned()
-        } else {
+    fn process_usage(&self, pid: u32) -> Option<ProcessUsage> {
+        let proc_dir = Path::new("/proc").join(pid.to_string());
+        let mut samples = self.cpu_samples.lock().unwrap();
+        let now = Instant::now();
+        samples.retain(|_, (_, read_at)| now.duration_since(*read_at) < CPU_SAMPLE_MAX_AGE);
+
+        let Some(ticks) = fs::read_to_string(proc_dir.join("stat"))
+            .ok()
+            .and_then(|stat| Self::parse_cpu_ticks(&stat))
+        else {
+            // The process has exited
+            samples.remove(&pid);
+            return None;
+        };
+        let memory_usage = fs::read_to_string(proc_dir.join("status"))
+            .ok()
+            .and_then(|status| Self::parse_vm_rss(&status));
+
+        let cpu_usage = samples
+            .insert(pid, (ticks, now))
+            .and_then(|(previous, read_at)| {
+                Self::cpu_percent(
+                    previous,
+                    ticks,
+                    now.duration_since(read_at),
+                    procfs::ticks_per_second(),
+                )
+            });
+        Some(ProcessUsage {
+            cpu_usage,
+            memory_usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proc_net_tables_map_sockets_to_processes() {
+        let tcp = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   998        0 4101 1 0000000000000000 100 0 0 10 0
+   1: 0A01A8C0:D431 0D2ED8AC:01BB 01 00000000:00000000 02:000009E4 00000000  1000        0 4102 2 0000000000000000 20 4 30 10 -1
+";
+        // A dual-stack socket talking to an IPv4 peer, and a plain IPv6 one
+        let tcp6 = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0000000000000000FFFF00000A01A8C0:1F90 0000000000000000FFFF00006400A8C0:C350 01 00000000:00000000 00:00000000 00000000  1000        0 4103 1 0000000000000000 20 4 0 10 -1
+   1: B80D01200000000000000000E8030000:D432 B80D01200000000000000000010000A0:01BB 01 00000000:00000000 00:00000000 00000000  1000        0 4104 1 0000000000000000 20 4 0 10 -1
+";
+        let inode_map: HashMap<u64, (u32, String)> = [
+            (4102, (311, "firefox".to_string())),
+            (4103, (42, "server".to_string())),
+            (4104, (311, "firefox".to_string())),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut result = HashMap::new();
+        LinuxProcessLookup::map_table(tcp, Protocol::TCP, &inode_map, &mut result);
+        LinuxProcessLookup::map_table(tcp6, Protocol::TCP, &inode_map, &mut result);
+
+        let key = |local: &str, remote: &str| ConnectionKey {
+            protocol: Protocol::TCP,
+            local_addr: local.parse().unwrap(),
+            remote_addr: remote.parse().unwrap(),
+        };
+        // The listener's inode belongs to no known process
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result[&key("192.168.1.10:54321", "172.216.46.13:443")],
+            (311, "firefox".to_string())
+        );
+        assert_eq!(
+            result[&key("192.168.1.10:8080", "192.168.0.100:50000")],
+            (42, "server".to_string())
+        );
+        assert_eq!(
+            result[&key("[2001:db8::3e8]:54322", "[2001:db8::a000:1]:443")],
+            (311, "firefox".to_string())
+        );
+        assert_eq!(
+            LinuxProcessLookup::extract_socket_inode("socket:[4102]"),
+            Some(4102)
+        );
+        assert_eq!(
+            LinuxProcessLookup::extract_socket_inode("pipe:[4102]"),
             None
-        }
+        );
     }
 
-    fn refresh(&self) -> Result<()> {
-        let process_map = Self::build_process_map()?;
+    #[test]
+    fn test_proc_pid_files_give_cpu_and_memory_use() {
+        let status = "Name:\tfirefox\nVmPeak:\t  912340 kB\nVmRSS:\t  204800 kB\nThreads:\t81\n";
+        assert_eq!(
+            LinuxProcessLookup::parse_vm_rss(status),
+            Some(204800 * 1024)
+        );
+        // Kernel threads have no VmRSS line
+        assert_eq!(LinuxProcessLookup::parse_vm_rss("Name:\tkthreadd\n"), None);
 
-        let mut cache = self.cache.write().unwrap();
-        cache.lookup = process_map;
-        cache.last_refresh = Instant::now();
+        // A command name with spaces and a parenthesis in it
+        let stat = "311 (Web Content (1)) S 1 311 311 0 -1 4194560 52132 0 0 0 1500 250 0 0 20 0 81 0 1234 912340 51200";
+        assert_eq!(LinuxProcessLookup::parse_cpu_ticks(stat), Some(1750));
+        assert_eq!(
+            LinuxProcessLookup::parse_cpu_ticks("311 (firefox) S 1"),
+            None
+        );
 
-        Ok(())
+        // Half a second of CPU time over two seconds
+        let cpu = LinuxProcessLookup::cpu_percent(1700, 1750, Duration::from_secs(2), 100);
+        assert_eq!(cpu, Some(25.0));
+        assert_eq!(
+            LinuxProcessLookup::cpu_percent(1750, 1700, Duration::from_secs(2), 100),
+            Some(0.0)
+        );
+        assert_eq!(
+            LinuxProcessLookup::cpu_percent(1700, 1750, Duration::ZERO, 100),
+            None
+        );
     }
 }
@@ -1,6 +1,6 @@
 //! Enhanced Linux process lookup combining eBPF and procfs approaches
 
-use super::{ConnectionKey, ProcessLookup};
+use super::{ConnectionKey, ProcessLookup, ProcessUsage};
 
 use super::linux::LinuxProcessLookup;
 use crate::network::types::{Connection, Protocol};
@@ -291,6 +291,10 @@ mod ebpf_enhanced {
             }
         }
 
+        fn process_usage(&self, pid: u32) -> Option<ProcessUsage> {
+            self.procfs_lookup.process_usage(pid)
+        }
+
         fn refresh(&self) -> Result<()> {
             // Refresh the procfs lookup
             self.procfs_lookup.refresh()?;
@@ -505,6 +509,10 @@ mod procfs_only {
             }
         }
 
+        fn process_usage(&self, pid: u32) -> Option<ProcessUsage> {
+            self.procfs_lookup.process_usage(pid)
+        }
+
         fn refresh(&self) -> Result<()> {
             // Refresh the procfs lookup
             self.procfs_lookup.refresh()?;
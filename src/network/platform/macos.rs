@@ -1,4 +1,5 @@
-use super::{ConnectionKey, ProcessLookup};
+use super::addr::parse_addr;
+use super::{ConnectionKey, ProcessLookup, lookup_socket};
 use crate::network::types::{Connection, Protocol};
 use anyhow::Result;
 use log::{debug, error, info, warn};
@@ -158,7 +159,7 @@ impl ProcessLookup for MacOSProcessLookup {
     fn get_process_for_connection(&self, conn: &Connection) -> Option<(u32, String)> {
         let key = ConnectionKey::from_connection(conn);
         let cache = self.cache.read().unwrap();
-        let result = cache.get(&key).cloned();
+        let result = lookup_socket(&cache, conn);
 
         if result.is_some() {
             debug!("Found process info for connection {:?}: {:?}", key, result);
@@ -212,11 +213,12 @@ fn parse_lsof_connection_with_hint(
             "    Parsing arrow connection: '{}' -> '{}'",
             parts[0], parts[1]
         );
-        let local = parse_socket_addr(parts[0])?;
-        let remote = parse_socket_addr(parts[1])?;
 
         // Use hint if available, otherwise assume TCP for established connections
         let protocol = protocol_hint.unwrap_or(Protocol::TCP);
+        let local = parse_socket_addr(parts[0], protocol)?;
+        let remote = parse_socket_addr(parts[1], protocol)?;
+
         debug!(
             "    Success: {:?} {}:{} -> {}:{}",
             protocol,
@@ -229,7 +231,10 @@ fn parse_lsof_connection_with_hint(
     } else if name.contains(":") {
         // UDP or listening socket
         debug!("    Parsing single address: '{}'", name);
-        let local = parse_socket_addr(name)?;
+
+        // Use hint if available, otherwise assume UDP for single address
+        let protocol = protocol_hint.unwrap_or(Protocol::UDP);
+        let local = parse_socket_addr(name, protocol)?;
 
         // For UDP or listening, we create a dummy remote address
         let remote = match local {
@@ -237,8 +242,6 @@ fn parse_lsof_connection_with_hint(
             SocketAddr::V6(_) => "[::]:0".parse().ok()?,
         };
 
-        // Use hint if available, otherwise assume UDP for single address
-        let protocol = protocol_hint.unwrap_or(Protocol::UDP);
         debug!(
             "    Success: {:?} {}:{} (listening/UDP)",
             protocol,
@@ -252,25 +255,22 @@ fn parse_lsof_connection_with_hint(
     }
 }
 
-fn parse_socket_addr(addr_str: &str) -> Option<SocketAddr> {
+fn parse_socket_addr(addr_str: &str, protocol: Protocol) -> Option<SocketAddr> {
     debug!("      Parsing socket address: '{}'", addr_str);
 
-    // Handle IPv6 addresses in brackets
-    if addr_str.starts_with('[') {
-        let result = addr_str.parse().ok();
-        debug!("      IPv6 parse result: {:?}", result);
-        result
-    } else if addr_str.starts_with('*') {
-        // Listening on all interfaces
-        let port_str = addr_str.strip_prefix("*:")?;
-        let port = port_str.parse().ok()?;
-        let result = Some(SocketAddr::new("0.0.0.0".parse().ok()?, port));
-        debug!("      Wildcard parse result: {:?}", result);
-        result
-    } else {
-        let result = addr_str.parse().ok();
-        debug!("      Regular parse result: {:?}", result);
-        result
+    match parse_addr(addr_str, protocol) {
+        Ok(parsed) => {
+            debug!(
+                "      Parse result: {:?} (zone: {:?})",
+                parsed.addr, parsed.zone
+            );
+            // Dual-stack sockets report v4-mapped addresses; the packets are IPv4
+            Some(parsed.unmapped())
+        }
+        Err(e) => {
+            debug!("      Parse failed: {}", e);
+            None
+        }
     }
 }
 
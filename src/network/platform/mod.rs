@@ -1,7 +1,8 @@
 // network/platform/mod.rs - Platform process lookup
 use crate::network::types::{Connection, Protocol};
 use anyhow::Result;
-use std::net::SocketAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 // Platform-specific modules
 #[cfg(target_os = "linux")]
@@ -15,6 +16,11 @@ mod macos;
 #[cfg(target_os = "windows")]
 mod windows;
 
+// Only lsof output is parsed into addresses so far
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod addr;
+mod socket_cache;
+
 // Re-export the appropriate implementation
 #[cfg(target_os = "linux")]
 pub use linux::LinuxProcessLookup;
@@ -35,6 +41,21 @@ pub trait ProcessLookup: Send + Sync {
     fn refresh(&self) -> Result<()> {
         Ok(()) // Default no-op
     }
+
+    /// Sample the CPU and memory use of `pid`, or `None` where that isn't
+    /// supported or the process is gone
+    fn process_usage(&self, _pid: u32) -> Option<ProcessUsage> {
+        None
+    }
+}
+
+/// Resource use of a process that owns connections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessUsage {
+    /// Percent of one core used since the previous sample, `None` on the first
+    pub cpu_usage: Option<f32>,
+    /// Resident memory in bytes
+    pub memory_usage: Option<u64>,
 }
 
 /// No-op process lookup for when PKTAP is providing process metadata
@@ -124,4 +145,155 @@ impl ConnectionKey {
             remote_addr: conn.remote_addr,
         }
     }
+
+    /// The key with v4-mapped IPv6 addresses unmapped, so a dual-stack
+    /// socket's entry matches the IPv4 packets it exchanges
+    pub fn normalized(self) -> Self {
+        Self {
+            protocol: self.protocol,
+            local_addr: addr::unmap(self.local_addr),
+            remote_addr: addr::unmap(self.remote_addr),
+        }
+    }
+
+    /// Keys of the sockets that could own this flow, most specific first: the
+    /// connected socket itself, one bound to the wildcard address, then
+    /// unconnected ones (listeners and plain UDP sockets). IPv4 flows can also
+    /// belong to dual-stack sockets bound to `[::]`.
+    fn candidates(&self) -> Vec<Self> {
+        let wildcards: &[IpAddr] = match self.local_addr {
+            SocketAddr::V4(_) => &[
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            ],
+            SocketAddr::V6(_) => &[IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+        };
+        let port = self.local_addr.port();
+        let locals: Vec<SocketAddr> = std::iter::once(self.local_addr)
+            .chain(wildcards.iter().map(|&ip| SocketAddr::new(ip, port)))
+            .collect();
+        let remotes = std::iter::once(self.remote_addr)
+            .chain(wildcards.iter().map(|&ip| SocketAddr::new(ip, 0)));
+
+        remotes
+            .flat_map(|remote_addr| {
+                locals.iter().map(move |&local_addr| Self {
+                    protocol: self.protocol,
+                    local_addr,
+                    remote_addr,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Find the owner of `conn` in a socket table with normalized keys. The
+/// socket tools report wildcard, unconnected and v4-mapped forms of the
+/// concrete addresses seen on the wire; those entries only match when no
+/// exact one does.
+pub fn lookup_socket<V: Clone>(table: &HashMap<ConnectionKey, V>, conn: &Connection) -> Option<V> {
+    ConnectionKey::from_connection(conn)
+        .normalized()
+        .candidates()
+        .iter()
+        .find_map(|key| table.get(key))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::types::{ProtocolState, TcpState, UdpState};
+
+    fn entry(protocol: Protocol, local: &str, remote: &str) -> ConnectionKey {
+        ConnectionKey {
+            protocol,
+            local_addr: local.parse().unwrap(),
+            remote_addr: remote.parse().unwrap(),
+        }
+        .normalized()
+    }
+
+    fn tcp_flow(local: &str, remote: &str) -> Connection {
+        Connection::new(
+            Protocol::TCP,
+            local.parse().unwrap(),
+            remote.parse().unwrap(),
+            ProtocolState::Tcp(TcpState::Established),
+        )
+    }
+
+    #[test]
+    fn test_lookup_matches_reported_forms_of_the_flow() {
+        let flow = tcp_flow("192.0.2.10:8080", "198.51.100.7:55555");
+        for (local, remote) in [
+            ("192.0.2.10:8080", "198.51.100.7:55555"),
+            ("[::ffff:192.0.2.10]:8080", "[::ffff:198.51.100.7]:55555"),
+            ("0.0.0.0:8080", "198.51.100.7:55555"),
+            ("0.0.0.0:8080", "0.0.0.0:0"),
+            ("[::]:8080", "[::]:0"),
+        ] {
+            let table = HashMap::from([(entry(Protocol::TCP, local, remote), 42)]);
+            assert_eq!(
+                lookup_socket(&table, &flow),
+                Some(42),
+                "{} {}",
+                local,
+                remote
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_prefers_the_most_specific_socket() {
+        let table = HashMap::from([
+            (entry(Protocol::UDP, "0.0.0.0:443", "0.0.0.0:0"), 1),
+            (entry(Protocol::UDP, "192.0.2.10:443", "0.0.0.0:0"), 2),
+            (
+                entry(Protocol::UDP, "192.0.2.10:443", "198.51.100.7:50000"),
+                3,
+            ),
+        ]);
+        let flow = |remote: &str| {
+            Connection::new(
+                Protocol::UDP,
+                "192.0.2.10:443".parse().unwrap(),
+                remote.parse().unwrap(),
+                ProtocolState::Udp(UdpState::Active),
+            )
+        };
+        assert_eq!(lookup_socket(&table, &flow("198.51.100.7:50000")), Some(3));
+        assert_eq!(lookup_socket(&table, &flow("198.51.100.8:50000")), Some(2));
+
+        let other_address = Connection::new(
+            Protocol::UDP,
+            "192.0.2.11:443".parse().unwrap(),
+            "198.51.100.8:50000".parse().unwrap(),
+            ProtocolState::Udp(UdpState::Active),
+        );
+        assert_eq!(lookup_socket(&table, &other_address), Some(1));
+    }
+
+    #[test]
+    fn test_lookup_does_not_cross_protocols_or_address_families() {
+        let table = HashMap::from([
+            (entry(Protocol::UDP, "0.0.0.0:8080", "0.0.0.0:0"), 1),
+            (entry(Protocol::TCP, "0.0.0.0:8080", "0.0.0.0:0"), 2),
+        ]);
+        assert_eq!(
+            lookup_socket(
+                &table,
+                &tcp_flow("[2001:db8::1]:8080", "[2001:db8::2]:55555")
+            ),
+            None
+        );
+        assert_eq!(
+            lookup_socket(&table, &tcp_flow("192.0.2.10:8081", "198.51.100.7:55555")),
+            None
+        );
+        assert_eq!(
+            lookup_socket(&table, &tcp_flow("192.0.2.10:8080", "198.51.100.7:55555")),
+            Some(2)
+        );
+    }
 }
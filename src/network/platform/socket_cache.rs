@@ -0,0 +1,140 @@
+// network/platform/socket_cache.rs - Throttled cache for socket table collectors
+use anyhow::Result;
+use log::debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Default minimum time between two socket table collections
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+type Collector<T> = Box<dyn Fn() -> Result<T> + Send + Sync>;
+
+/// Runs an expensive socket table collector (procfs scan, lsof, ...) at most once
+/// per interval and serves the last parsed result in between.
+pub struct SocketTableCache<T> {
+    collector: Collector<T>,
+    interval: Duration,
+    table: RwLock<Option<(Arc<T>, Instant)>>,
+    /// Serialises collections so concurrent callers don't all run the collector
+    collecting: Mutex<()>,
+    collections: AtomicU64,
+}
+
+impl<T> SocketTableCache<T> {
+    pub fn new(
+        interval: Duration,
+        collector: impl Fn() -> Result<T> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            collector: Box::new(collector),
+            interval,
+            table: RwLock::new(None),
+            collecting: Mutex::new(()),
+            collections: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the cached table, collecting a fresh one only if it is older than the interval
+    pub fn get(&self) -> Result<Arc<T>> {
+        if let Some(table) = self.fresh_table() {
+            return Ok(table);
+        }
+
+        let _guard = self.collecting.lock().unwrap();
+        // Another caller may have refreshed while we waited for the lock
+        if let Some(table) = self.fresh_table() {
+            return Ok(table);
+        }
+        self.collect()
+    }
+
+    /// Collect a fresh table regardless of the cache age
+    pub fn force_refresh(&self) -> Result<Arc<T>> {
+        let _guard = self.collecting.lock().unwrap();
+        self.collect()
+    }
+
+    /// Number of times the collector has run
+    pub fn collection_count(&self) -> u64 {
+        self.collections.load(Ordering::Relaxed)
+    }
+
+    fn fresh_table(&self) -> Option<Arc<T>> {
+        match &*self.table.read().unwrap() {
+            Some((table, collected_at)) if collected_at.elapsed() < self.interval => {
+                Some(Arc::clone(table))
+            }
+            _ => None,
+        }
+    }
+
+    fn collect(&self) -> Result<Arc<T>> {
+        let start = Instant::now();
+        let table = Arc::new((self.collector)()?);
+        self.collections.fetch_add(1, Ordering::Relaxed);
+        debug!(
+            "Socket table collected in {:?} (collection #{})",
+            start.elapsed(),
+            self.collection_count()
+        );
+
+        *self.table.write().unwrap() = Some((Arc::clone(&table), Instant::now()));
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting_cache(interval: Duration) -> (SocketTableCache<u64>, Arc<AtomicU64>) {
+        let runs = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&runs);
+        let cache = SocketTableCache::new(interval, move || {
+            Ok(counter.fetch_add(1, Ordering::Relaxed) + 1)
+        });
+        (cache, runs)
+    }
+
+    #[test]
+    fn test_rapid_calls_collect_once() {
+        let (cache, runs) = counting_cache(Duration::from_secs(60));
+
+        for _ in 0..100 {
+            assert_eq!(*cache.get().unwrap(), 1);
+        }
+
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.collection_count(), 1);
+    }
+
+    #[test]
+    fn test_force_refresh_bypasses_interval() {
+        let (cache, runs) = counting_cache(Duration::from_secs(60));
+
+        cache.get().unwrap();
+        assert_eq!(*cache.force_refresh().unwrap(), 2);
+        assert_eq!(*cache.get().unwrap(), 2);
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_stale_table_is_recollected() {
+        let (cache, runs) = counting_cache(Duration::ZERO);
+
+        cache.get().unwrap();
+        cache.get().unwrap();
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_collector_error_is_not_cached() {
+        let cache: SocketTableCache<u64> =
+            SocketTableCache::new(Duration::from_secs(60), || Err(anyhow::anyhow!("boom")));
+
+        assert!(cache.get().is_err());
+        assert!(cache.get().is_err());
+        assert_eq!(cache.collection_count(), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{ConnectionKey, ProcessLookup};
+use super::{ConnectionKey, ProcessLookup, lookup_socket};
 use crate::network::types::Connection;
 use anyhow::Result;
 use std::collections::HashMap;
@@ -43,8 +43,7 @@ impl WindowsProcessLookup {
 
 impl ProcessLookup for WindowsProcessLookup {
     fn get_process_for_connection(&self, conn: &Connection) -> Option<(u32, String)> {
-        let key = ConnectionKey::from_connection(conn);
-        self.cache.read().unwrap().get(&key).cloned()
+        lookup_socket(&self.cache.read().unwrap(), conn)
     }
 
     fn refresh(&self) -> Result<()> {
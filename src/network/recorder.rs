@@ -0,0 +1,264 @@
+// src/network/recorder.rs - Save the packets of chosen connections to pcap files
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+use crate::network::capture::CapturedPacket;
+
+/// Size at which a recording is closed, header included
+pub const MAX_RECORDING_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Snapshot length written to the file header; frames are saved as captured
+const SAVEFILE_SNAPLEN: u32 = 65535;
+
+/// A pcap file receiving one connection's packets
+struct Recording {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    bytes: u64,
+}
+
+impl Recording {
+    fn create(path: PathBuf, linktype: i32) -> Result<Self> {
+        let file =
+            File::create(&path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        // Microsecond-resolution savefile in the host's byte order
+        writer.write_all(&0xa1b2_c3d4u32.to_ne_bytes())?;
+        writer.write_all(&2u16.to_ne_bytes())?;
+        writer.write_all(&4u16.to_ne_bytes())?;
+        for field in [0, 0, SAVEFILE_SNAPLEN, savefile_linktype(linktype)] {
+            writer.write_all(&field.to_ne_bytes())?;
+        }
+        Ok(Self {
+            path,
+            writer,
+            bytes: 24,
+        })
+    }
+
+    fn write(&mut self, packet: &CapturedPacket) -> std::io::Result<()> {
+        let since_epoch = packet
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let header = [
+            since_epoch.as_secs() as u32,
+            since_epoch.subsec_micros(),
+            packet.data.len() as u32,
+            packet.wire_len as u32,
+        ];
+        for field in header {
+            self.writer.write_all(&field.to_ne_bytes())?;
+        }
+        self.writer.write_all(&packet.data)?;
+        self.bytes += 16 + packet.data.len() as u64;
+        Ok(())
+    }
+
+    /// Flush what's buffered, returning where the file is
+    fn close(mut self) -> PathBuf {
+        if let Err(e) = self.writer.flush() {
+            warn!("Failed to finish {}: {}", self.path.display(), e);
+        }
+        self.path
+    }
+}
+
+/// Savefile link type for a capture's data link type. They only differ
+/// where DLT values vary between platforms, which for this tool is raw IP.
+fn savefile_linktype(datalink: i32) -> u32 {
+    match datalink {
+        12 | 14 => 101, // DLT_RAW
+        _ => datalink as u32,
+    }
+}
+
+/// Connections whose packets are being saved, each to its own pcap file in
+/// `dir`.
+///
+/// A recording ends when the user stops it, when its connection leaves the
+/// table, or once its file reaches `MAX_RECORDING_BYTES`.
+pub struct FlowRecorder {
+    dir: PathBuf,
+    recordings: Mutex<HashMap<String, Recording>>,
+    /// Number of recordings, so packets skip the lock when there are none
+    active: AtomicUsize,
+}
+
+impl FlowRecorder {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            recordings: Mutex::new(HashMap::new()),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Start saving the packets of the connection with `key`, returning the
+    /// file they go to. Packets are framed for `linktype`, the capture's
+    /// data link type.
+    pub fn start(&self, key: &str, linktype: i32) -> Result<PathBuf> {
+        let mut recordings = self.recordings.lock().unwrap();
+        if let Some(recording) = recordings.get(key) {
+            return Ok(recording.path.clone());
+        }
+
+        let name: String = key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+        let path = self
+            .dir
+            .join(format!("rustnet_{}_{}.pcap", name, timestamp));
+        let recording = Recording::create(path.clone(), linktype)?;
+        info!("Recording {} to {}", key, path.display());
+        recordings.insert(key.to_string(), recording);
+        self.active.store(recordings.len(), Ordering::Relaxed);
+        Ok(path)
+    }
+
+    /// Stop saving the connection's packets, returning the finished file
+    pub fn stop(&self, key: &str) -> Option<PathBuf> {
+        let mut recordings = self.recordings.lock().unwrap();
+        let recording = recordings.remove(key)?;
+        self.active.store(recordings.len(), Ordering::Relaxed);
+        let path = recording.close();
+        info!("Stopped recording {} to {}", key, path.display());
+        Some(path)
+    }
+
+    pub fn is_recording(&self, key: &str) -> bool {
+        self.active.load(Ordering::Relaxed) > 0 && self.recordings.lock().unwrap().contains_key(key)
+    }
+
+    /// Save `packet` if its connection is being recorded
+    pub fn write(&self, key: &str, packet: &CapturedPacket) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut recordings = self.recordings.lock().unwrap();
+        let Some(recording) = recordings.get_mut(key) else {
+            return;
+        };
+
+        let written = if recording.bytes + 16 + packet.data.len() as u64 > MAX_RECORDING_BYTES {
+            warn!(
+                "Recording of {} reached {} bytes, closing it",
+                key, MAX_RECORDING_BYTES
+            );
+            false
+        } else if let Err(e) = recording.write(packet) {
+            warn!("Failed to write to {}: {}", recording.path.display(), e);
+            false
+        } else {
+            true
+        };
+        if !written {
+            if let Some(recording) = recordings.remove(key) {
+                recording.close();
+            }
+            self.active.store(recordings.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// Close the recordings of connections `keep` returns false for
+    pub fn retain(&self, mut keep: impl FnMut(&str) -> bool) {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut recordings = self.recordings.lock().unwrap();
+        let ended: Vec<String> = recordings
+            .keys()
+            .filter(|key| !keep(key))
+            .cloned()
+            .collect();
+        for key in ended {
+            if let Some(recording) = recordings.remove(&key) {
+                let path = recording.close();
+                info!("Connection {} ended, closed {}", key, path.display());
+            }
+        }
+        self.active.store(recordings.len(), Ordering::Relaxed);
+    }
+
+    /// Close every recording
+    pub fn stop_all(&self) {
+        self.retain(|_| false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcap::Capture;
+    use std::time::{Duration, Instant};
+
+    fn packet(data: Vec<u8>, secs: u64) -> CapturedPacket {
+        CapturedPacket {
+            wire_len: data.len(),
+            data,
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            instant: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_only_recorded_connections_are_saved() {
+        let dir = std::env::temp_dir().join(format!("rustnet-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let recorder = FlowRecorder::new(dir.clone());
+
+        let path = recorder.start("TCP:flow", 1).unwrap();
+        assert!(recorder.is_recording("TCP:flow"));
+        recorder.write("TCP:flow", &packet(vec![0xaa; 60], 1_700_000_000));
+        recorder.write("TCP:other", &packet(vec![0xbb; 60], 1_700_000_001));
+        recorder.write("TCP:flow", &packet(vec![0xcc; 70], 1_700_000_002));
+        assert_eq!(recorder.stop("TCP:flow"), Some(path.clone()));
+        assert!(!recorder.is_recording("TCP:flow"));
+        // Packets after the recording stopped are left out
+        recorder.write("TCP:flow", &packet(vec![0xdd; 60], 1_700_000_003));
+
+        let mut capture = Capture::from_file(&path).unwrap();
+        let mut saved = Vec::new();
+        while let Ok(packet) = capture.next_packet() {
+            saved.push((packet.header.ts.tv_sec as u64, packet.data.to_vec()));
+        }
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(
+            saved,
+            [
+                (1_700_000_000, vec![0xaa; 60]),
+                (1_700_000_002, vec![0xcc; 70])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recordings_close_when_the_connection_ends() {
+        let dir = std::env::temp_dir().join(format!("rustnet-record-end-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let recorder = FlowRecorder::new(dir.clone());
+
+        recorder.start("UDP:kept", 1).unwrap();
+        let ended = recorder.start("UDP:gone", 1).unwrap();
+        recorder.write("UDP:gone", &packet(vec![0; 42], 1_700_000_000));
+        recorder.retain(|key| key == "UDP:kept");
+        assert!(recorder.is_recording("UDP:kept"));
+        assert!(!recorder.is_recording("UDP:gone"));
+        // Closed files are complete
+        assert_eq!(std::fs::metadata(&ended).unwrap().len(), 24 + 16 + 42);
+
+        recorder.stop_all();
+        assert!(!recorder.is_recording("UDP:kept"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,120 @@
+// src/network/resolver.rs - Reverse DNS lookups of remote hosts, off the snapshot thread
+
+use crossbeam::channel::{self, Receiver, Sender};
+use log::debug;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::thread;
+
+/// Lookups in flight at once. Each blocks its thread until the resolver
+/// answers or times out.
+const RESOLVER_THREADS: usize = 4;
+
+/// Addresses remembered before the cache starts over
+const MAX_CACHED_ADDRESSES: usize = 16 * 1024;
+
+/// Host names of remote addresses, looked up in the background.
+///
+/// `hostname` never blocks: an address seen for the first time is queued for
+/// a reverse lookup on one of the resolver threads, and its name shows up once
+/// `collect_results` has picked up the answer. Addresses without a PTR record
+/// are remembered too, so they're only looked up once.
+pub struct HostnameResolver {
+    /// Names by address. None while the lookup is pending or when it found
+    /// no name.
+    cache: Mutex<HashMap<IpAddr, Option<String>>>,
+    request_tx: Sender<IpAddr>,
+    result_rx: Receiver<(IpAddr, Option<String>)>,
+}
+
+impl HostnameResolver {
+    /// Resolver asking the system resolver. Its threads exit once it's dropped.
+    pub fn new() -> Self {
+        Self::with_lookup(|ip| {
+            // getnameinfo hands back the address itself when there's no name
+            dns_lookup::lookup_addr(&ip)
+                .ok()
+                .filter(|name| name.parse::<IpAddr>().is_err())
+        })
+    }
+
+    fn with_lookup(lookup: fn(IpAddr) -> Option<String>) -> Self {
+        let (request_tx, request_rx) = channel::unbounded::<IpAddr>();
+        let (result_tx, result_rx) = channel::unbounded();
+        for _ in 0..RESOLVER_THREADS {
+            let request_rx = request_rx.clone();
+            let result_tx = result_tx.clone();
+            thread::spawn(move || {
+                for ip in request_rx {
+                    let name = lookup(ip);
+                    debug!("Reverse lookup of {}: {:?}", ip, name);
+                    if result_tx.send((ip, name)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Self {
+            cache: Mutex::new(HashMap::new()),
+            request_tx,
+            result_rx,
+        }
+    }
+
+    /// Name of `ip` if a lookup already found one. Otherwise starts a lookup
+    /// unless one was done before.
+    pub fn hostname(&self, ip: IpAddr) -> Option<String> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(name) = cache.get(&ip) {
+            return name.clone();
+        }
+        if cache.len() >= MAX_CACHED_ADDRESSES {
+            cache.clear();
+        }
+        cache.insert(ip, None);
+        let _ = self.request_tx.send(ip);
+        None
+    }
+
+    /// Move the names of finished lookups into the cache
+    pub fn collect_results(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        for (ip, name) in self.result_rx.try_iter() {
+            cache.insert(ip, name);
+        }
+    }
+}
+
+impl Default for HostnameResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_names_arrive_without_blocking() {
+        let resolver = HostnameResolver::with_lookup(|ip| match ip {
+            IpAddr::V4(v4) if v4.octets()[3] == 1 => Some("one.example".to_string()),
+            _ => None,
+        });
+        let named: IpAddr = "192.0.2.1".parse().unwrap();
+        let unnamed: IpAddr = "192.0.2.2".parse().unwrap();
+
+        assert_eq!(resolver.hostname(named), None);
+        assert_eq!(resolver.hostname(unnamed), None);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while resolver.hostname(named).is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+            resolver.collect_results();
+        }
+        assert_eq!(resolver.hostname(named).as_deref(), Some("one.example"));
+        assert_eq!(resolver.hostname(unnamed), None);
+    }
+}
@@ -0,0 +1,840 @@
+// src/network/scan.rs - Port scan, host sweep, SYN flood and brute-force detection
+
+use log::warn;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::network::parser::ParsedPacket;
+use crate::network::types::{ApplicationProtocol, Connection, Protocol, ProtocolState, TcpState};
+
+/// Sources tracked per detector. A scan from spoofed addresses would
+/// otherwise grow the tables without bound; new sources are ignored until
+/// old ones age out.
+const MAX_SOURCES: usize = 4096;
+
+/// Entries remembered per source within the window
+const MAX_EVENTS_PER_SOURCE: usize = 4096;
+
+/// SYNs with the same port or host closer together than this share an
+/// entry, so a flood is counted without keeping every packet
+const BUCKET: Duration = Duration::from_millis(100);
+
+/// Alerts kept for the UI
+const MAX_ALERTS: usize = 100;
+
+/// Sessions to an auth-bearing service that end sooner than this count as
+/// login attempts
+const SHORT_SESSION: Duration = Duration::from_secs(5);
+
+/// Window login attempts are counted over
+const BRUTE_FORCE_WINDOW: Duration = Duration::from_secs(60);
+
+/// What a burst of connections looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanKind {
+    /// One remote host probing many of our ports
+    PortScan,
+    /// This host probing one port on many remote hosts that don't answer
+    HostSweep,
+    /// Handshakes opened against one of our ports faster than a server
+    /// normally sees
+    SynFlood,
+    /// One remote host logging in to one of our services over and over
+    BruteForce,
+}
+
+impl ScanKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::PortScan => "port scan",
+            Self::HostSweep => "host sweep",
+            Self::SynFlood => "SYN flood",
+            Self::BruteForce => "brute force",
+        }
+    }
+}
+
+/// When a burst of connections counts as a scan or an attack
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanThresholds {
+    /// Distinct local ports one remote host may try within the window
+    pub ports: usize,
+    /// Distinct unanswering hosts this host may try on one port within the window
+    pub hosts: usize,
+    /// Inbound SYNs per second one local port may receive
+    pub syn_rate: f64,
+    pub window: Duration,
+    /// Short sessions one remote host may open to one auth port per minute
+    pub login_attempts: usize,
+    /// Local ports of services that take logins (SSH-classified flows are
+    /// watched on any port)
+    pub auth_ports: Vec<u16>,
+}
+
+impl Default for ScanThresholds {
+    fn default() -> Self {
+        Self {
+            ports: 20,
+            hosts: 20,
+            syn_rate: 100.0,
+            window: Duration::from_secs(10),
+            login_attempts: 10,
+            // SSH, SMTP submission and RDP
+            auth_ports: vec![22, 465, 587, 3389],
+        }
+    }
+}
+
+/// A threshold crossing, with the evidence for it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanAlert {
+    pub kind: ScanKind,
+    /// The scanning host: the remote end for port scans, floods and brute
+    /// force, the local end for host sweeps
+    pub source: IpAddr,
+    /// The swept remote port, or the flooded or attacked local port
+    pub port: Option<u16>,
+    /// Distinct ports or hosts tried, SYNs received, or short sessions
+    /// opened within the window
+    pub count: usize,
+    pub window: Duration,
+    pub time: SystemTime,
+}
+
+impl ScanAlert {
+    pub fn describe(&self) -> String {
+        let window = self.window.as_secs();
+        match self.kind {
+            ScanKind::PortScan => format!(
+                "port scan from {}: {} ports in {}s",
+                self.source, self.count, window
+            ),
+            ScanKind::HostSweep => format!(
+                "host sweep from {} on port {}: {} unanswered hosts in {}s",
+                self.source,
+                self.port.unwrap_or_default(),
+                self.count,
+                window
+            ),
+            ScanKind::SynFlood => format!(
+                "SYN flood on port {}: {} SYNs in {}s",
+                self.port.unwrap_or_default(),
+                self.count,
+                window
+            ),
+            ScanKind::BruteForce => format!(
+                "brute force from {} on port {}: {} short sessions in {}s",
+                self.source,
+                self.port.unwrap_or_default(),
+                self.count,
+                window
+            ),
+        }
+    }
+
+    /// Whether `conn` is one of the flows the alert is about. For sweeps and
+    /// floods only the half-open ones are, as the port also carries the
+    /// host's ordinary traffic.
+    pub fn matches(&self, conn: &Connection) -> bool {
+        if conn.protocol != Protocol::TCP {
+            return false;
+        }
+        match self.kind {
+            ScanKind::PortScan => conn.remote_addr.ip() == self.source,
+            ScanKind::HostSweep => {
+                conn.local_addr.ip() == self.source
+                    && Some(conn.remote_addr.port()) == self.port
+                    && matches!(conn.protocol_state, ProtocolState::Tcp(TcpState::SynSent))
+            }
+            ScanKind::SynFlood => {
+                Some(conn.local_addr.port()) == self.port
+                    && matches!(
+                        conn.protocol_state,
+                        ProtocolState::Tcp(TcpState::SynReceived)
+                    )
+            }
+            ScanKind::BruteForce => {
+                conn.remote_addr.ip() == self.source && Some(conn.local_addr.port()) == self.port
+            }
+        }
+    }
+}
+
+/// SYNs seen from one source within the window
+#[derive(Debug)]
+struct Window<T> {
+    /// When each entry started, what it was for, and how many SYNs it holds
+    events: VecDeque<(Instant, T, usize)>,
+    /// Entries per value, so distinct ports or hosts are counted without a
+    /// pass over the window
+    values: HashMap<T, usize>,
+    /// SYNs across all entries
+    syns: usize,
+    /// The newest SYN ever pushed, which orders the source for eviction
+    last: Instant,
+    /// Set once the source crossed the threshold, so it alerts only once
+    /// until it goes quiet for a whole window
+    alerted: bool,
+}
+
+impl<T: Eq + Hash + Copy> Window<T> {
+    fn new(last: Instant) -> Self {
+        Self {
+            events: VecDeque::new(),
+            values: HashMap::new(),
+            syns: 0,
+            last,
+            alerted: false,
+        }
+    }
+
+    fn push(&mut self, now: Instant, value: T) {
+        self.syns += 1;
+        if let Some((at, last, count)) = self.events.back_mut()
+            && *last == value
+            && now
+                .checked_duration_since(*at)
+                .is_some_and(|since| since < BUCKET)
+        {
+            *count += 1;
+            return;
+        }
+        if self.events.len() == MAX_EVENTS_PER_SOURCE
+            && let Some(event) = self.events.pop_front()
+        {
+            self.forget(event);
+        }
+        // Closes are counted in table order rather than by when the flows
+        // ended, so keep the entries sorted for expire()
+        let at = self.events.partition_point(|(at, _, _)| *at <= now);
+        self.events.insert(at, (now, value, 1));
+        *self.values.entry(value).or_default() += 1;
+    }
+
+    fn expire(&mut self, cutoff: Instant) {
+        while self.events.front().is_some_and(|(at, _, _)| *at < cutoff) {
+            if let Some(event) = self.events.pop_front() {
+                self.forget(event);
+            }
+        }
+    }
+
+    /// Drop every entry for `value`
+    fn remove(&mut self, value: T) {
+        if self.values.remove(&value).is_none() {
+            return;
+        }
+        let syns = &mut self.syns;
+        self.events.retain(|(_, other, count)| {
+            let keep = *other != value;
+            if !keep {
+                *syns -= count;
+            }
+            keep
+        });
+    }
+
+    /// Take a dropped entry out of the running counts
+    fn forget(&mut self, (_, value, count): (Instant, T, usize)) {
+        self.syns -= count;
+        if let Some(entries) = self.values.get_mut(&value) {
+            *entries -= 1;
+            if *entries == 0 {
+                self.values.remove(&value);
+            }
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.syns
+    }
+
+    fn distinct(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// Windows by source, bounded by `MAX_SOURCES`
+#[derive(Debug)]
+struct Windows<K, T> {
+    sources: HashMap<K, Window<T>>,
+    /// Sources by their newest SYN, quietest first
+    by_last: BTreeSet<(Instant, K)>,
+}
+
+impl<K: Ord + Hash + Copy, T: Eq + Hash + Copy> Windows<K, T> {
+    fn new() -> Self {
+        Self {
+            sources: HashMap::new(),
+            by_last: BTreeSet::new(),
+        }
+    }
+
+    /// The source's window for a SYN at `now`, with events older than
+    /// `cutoff` dropped, or None when the table is full of other sources
+    /// that are still active
+    fn get(&mut self, key: K, now: Instant, cutoff: Instant) -> Option<&mut Window<T>> {
+        if self.sources.len() >= MAX_SOURCES && !self.sources.contains_key(&key) {
+            // Make room by evicting the quietest source, once all it sent
+            // has aged out
+            let &(last, quietest) = self.by_last.first()?;
+            if last >= cutoff {
+                return None;
+            }
+            self.by_last.remove(&(last, quietest));
+            self.sources.remove(&quietest);
+        }
+        let window = match self.sources.entry(key) {
+            Entry::Occupied(entry) => {
+                let window = entry.into_mut();
+                if now > window.last {
+                    self.by_last.remove(&(window.last, key));
+                    self.by_last.insert((now, key));
+                    window.last = now;
+                }
+                window
+            }
+            Entry::Vacant(entry) => {
+                self.by_last.insert((now, key));
+                entry.insert(Window::new(now))
+            }
+        };
+        window.expire(cutoff);
+        if window.events.is_empty() {
+            window.alerted = false;
+        }
+        Some(window)
+    }
+}
+
+#[derive(Debug)]
+struct DetectorState {
+    /// Local ports tried by each remote host
+    port_scans: Windows<IpAddr, u16>,
+    /// Unanswered remote hosts tried by each local address, per remote port
+    host_sweeps: Windows<(IpAddr, u16), IpAddr>,
+    /// Inbound SYNs per local port
+    syn_floods: Windows<u16, ()>,
+    /// Short sessions per remote host and local auth port
+    logins: Windows<(IpAddr, u16), ()>,
+    alerts: VecDeque<ScanAlert>,
+    /// Every alert raised, including those that fell out of `alerts`
+    raised: usize,
+}
+
+impl DetectorState {
+    fn raise(&mut self, alert: ScanAlert) {
+        warn!("Possible {}", alert.describe());
+        if self.alerts.len() == MAX_ALERTS {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(alert);
+        self.raised += 1;
+    }
+}
+
+/// Sliding-window counters shared by the packet processors and the cleanup
+/// thread
+#[derive(Debug)]
+pub struct ScanDetector {
+    thresholds: ScanThresholds,
+    state: Mutex<DetectorState>,
+}
+
+impl ScanDetector {
+    pub fn new(thresholds: ScanThresholds) -> Self {
+        Self {
+            thresholds,
+            state: Mutex::new(DetectorState {
+                port_scans: Windows::new(),
+                host_sweeps: Windows::new(),
+                syn_floods: Windows::new(),
+                logins: Windows::new(),
+                alerts: VecDeque::new(),
+                raised: 0,
+            }),
+        }
+    }
+
+    /// Count a packet towards the windows. Returns the kind of scan the
+    /// packet is part of once its source is over a threshold, and the alerts
+    /// for the thresholds this packet crossed.
+    pub fn observe(&self, parsed: &ParsedPacket) -> (Option<ScanKind>, Vec<ScanAlert>) {
+        // Only handshakes count, and forwarded flows have no local end to probe
+        let Some(flags) = parsed.tcp_flags else {
+            return (None, Vec::new());
+        };
+        if !flags.syn || parsed.forwarded {
+            return (None, Vec::new());
+        }
+
+        let thresholds = &self.thresholds;
+        let now = parsed.instant;
+        let cutoff = now.checked_sub(thresholds.window).unwrap_or(now);
+        let local_ip = parsed.local_addr.ip();
+        let local_port = parsed.local_addr.port();
+        let remote_ip = parsed.remote_addr.ip();
+        let remote_port = parsed.remote_addr.port();
+
+        let mut state = self.state.lock().unwrap();
+        let mut tag = None;
+        let mut alerts = Vec::new();
+
+        if flags.ack {
+            // A SYN-ACK means the host we tried is there. Browsers and other
+            // clients reach hosts that answer, so only silent hosts count
+            // towards a sweep.
+            if !parsed.is_outgoing
+                && let Some(window) = state.host_sweeps.sources.get_mut(&(local_ip, remote_port))
+            {
+                window.remove(remote_ip);
+            }
+            return (None, Vec::new());
+        }
+
+        if parsed.is_outgoing {
+            if let Some(window) = state.host_sweeps.get((local_ip, remote_port), now, cutoff) {
+                window.push(now, remote_ip);
+                let hosts = window.distinct();
+                if hosts >= thresholds.hosts {
+                    tag = Some(ScanKind::HostSweep);
+                    if !window.alerted {
+                        window.alerted = true;
+                        alerts.push(self.alert(
+                            ScanKind::HostSweep,
+                            local_ip,
+                            Some(remote_port),
+                            hosts,
+                            parsed,
+                        ));
+                    }
+                }
+            }
+        } else {
+            if let Some(window) = state.port_scans.get(remote_ip, now, cutoff) {
+                window.push(now, local_port);
+                let ports = window.distinct();
+                if ports >= thresholds.ports {
+                    tag = Some(ScanKind::PortScan);
+                    if !window.alerted {
+                        window.alerted = true;
+                        alerts.push(self.alert(ScanKind::PortScan, remote_ip, None, ports, parsed));
+                    }
+                }
+            }
+
+            if let Some(window) = state.syn_floods.get(local_port, now, cutoff) {
+                window.push(now, ());
+                let syns = window.total();
+                if syns as f64 >= thresholds.syn_rate * thresholds.window.as_secs_f64() {
+                    // A port scan hits each port only a few times, so a flood
+                    // wins the tag when both apply
+                    tag = Some(ScanKind::SynFlood);
+                    if !window.alerted {
+                        window.alerted = true;
+                        alerts.push(self.alert(
+                            ScanKind::SynFlood,
+                            remote_ip,
+                            Some(local_port),
+                            syns,
+                            parsed,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for alert in &alerts {
+            state.raise(alert.clone());
+        }
+
+        (tag, alerts)
+    }
+
+    /// Count a connection removed from the table towards the brute-force
+    /// window of its source, returning the alert if it crossed the threshold
+    pub fn observe_close(&self, conn: &Connection) -> Option<ScanAlert> {
+        let port = self.login_port(conn)?;
+        // Closes are reported after the flow's timeout, so its last packet
+        // is when it really ended
+        let ended = conn.last_activity_instant;
+        let cutoff = ended.checked_sub(BRUTE_FORCE_WINDOW).unwrap_or(ended);
+        let source = conn.remote_addr.ip();
+
+        let mut state = self.state.lock().unwrap();
+        let window = state.logins.get((source, port), ended, cutoff)?;
+        window.push(ended, ());
+        let attempts = window.total();
+        if attempts < self.thresholds.login_attempts || window.alerted {
+            return None;
+        }
+        window.alerted = true;
+        let alert = ScanAlert {
+            kind: ScanKind::BruteForce,
+            source,
+            port: Some(port),
+            count: attempts,
+            window: BRUTE_FORCE_WINDOW,
+            time: conn.last_activity,
+        };
+        state.raise(alert.clone());
+        Some(alert)
+    }
+
+    /// The local port of a closed flow that looks like one login attempt:
+    /// a session to an auth port that got past the handshake, with data
+    /// both ways, and ended within `SHORT_SESSION`
+    fn login_port(&self, conn: &Connection) -> Option<u16> {
+        if conn.protocol != Protocol::TCP || conn.forwarded {
+            return None;
+        }
+        let port = conn.local_addr.port();
+        let is_ssh = matches!(
+            conn.dpi_info.as_ref().map(|dpi| &dpi.application),
+            Some(ApplicationProtocol::Ssh(_))
+        );
+        // An SSH flow is to our listener when ours is the lower port
+        let listener = self.thresholds.auth_ports.contains(&port)
+            || (is_ssh && port < conn.remote_addr.port());
+        // Connecting and closing without a word has the server send three
+        // packets (SYN-ACK, FIN and an ACK); a login exchanges more
+        let exchanged_data = conn.packets_sent >= 4 && conn.packets_received >= 4;
+        let lasted = conn
+            .last_activity_instant
+            .saturating_duration_since(conn.created_instant);
+        (listener && exchanged_data && lasted <= SHORT_SESSION).then_some(port)
+    }
+
+    fn alert(
+        &self,
+        kind: ScanKind,
+        source: IpAddr,
+        port: Option<u16>,
+        count: usize,
+        parsed: &ParsedPacket,
+    ) -> ScanAlert {
+        ScanAlert {
+            kind,
+            source,
+            port,
+            count,
+            window: self.thresholds.window,
+            time: parsed.timestamp,
+        }
+    }
+
+    /// Alerts raised so far, newest last
+    pub fn alerts(&self) -> Vec<ScanAlert> {
+        self.state.lock().unwrap().alerts.iter().cloned().collect()
+    }
+
+    /// How many alerts have been raised since the detector was created
+    pub fn alerts_raised(&self) -> usize {
+        self.state.lock().unwrap().raised
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::parser::TcpFlags;
+    use std::net::SocketAddr;
+
+    const LOCAL: [u8; 4] = [192, 168, 1, 10];
+
+    fn packet(
+        local: SocketAddr,
+        remote: SocketAddr,
+        is_outgoing: bool,
+        ack: bool,
+        instant: Instant,
+    ) -> ParsedPacket {
+        ParsedPacket {
+            connection_key: format!("TCP:{}-TCP:{}", local, remote),
+            protocol: Protocol::TCP,
+            local_addr: local,
+            remote_addr: remote,
+            tcp_flags: Some(TcpFlags {
+                syn: true,
+                ack,
+                fin: false,
+                rst: false,
+                psh: false,
+                urg: false,
+            }),
+            tcp_seq: None,
+            tcp_ack: None,
+            tcp_timestamps: None,
+            protocol_state: ProtocolState::Tcp(TcpState::Unknown),
+            is_outgoing,
+            forwarded: false,
+            packet_len: 60,
+            payload_len: 0,
+            timestamp: SystemTime::now(),
+            instant,
+            dpi_result: None,
+            dpi_payload: None,
+            process_name: None,
+            process_id: None,
+            os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            sctp_addresses: Vec::new(),
+            segments: 1,
+            scan: None,
+        }
+    }
+
+    fn inbound_syn(remote: [u8; 4], local_port: u16, instant: Instant) -> ParsedPacket {
+        packet(
+            SocketAddr::from((LOCAL, local_port)),
+            SocketAddr::from((remote, 51000)),
+            false,
+            false,
+            instant,
+        )
+    }
+
+    fn outbound_syn(remote: [u8; 4], remote_port: u16, instant: Instant) -> ParsedPacket {
+        packet(
+            SocketAddr::from((LOCAL, 40000)),
+            SocketAddr::from((remote, remote_port)),
+            true,
+            false,
+            instant,
+        )
+    }
+
+    fn syn_ack(remote: [u8; 4], remote_port: u16, instant: Instant) -> ParsedPacket {
+        packet(
+            SocketAddr::from((LOCAL, 40000)),
+            SocketAddr::from((remote, remote_port)),
+            false,
+            true,
+            instant,
+        )
+    }
+
+    #[test]
+    fn test_scans_alert_once_with_evidence() {
+        let detector = ScanDetector::new(ScanThresholds::default());
+        let start = Instant::now();
+        let scanner = [203, 0, 113, 66];
+
+        // nmap-style: one SYN to each of 30 ports, 50ms apart
+        let mut alerts = Vec::new();
+        let mut tags = Vec::new();
+        for (i, port) in (1..=30).enumerate() {
+            let at = start + Duration::from_millis(50 * i as u64);
+            let (tag, raised) = detector.observe(&inbound_syn(scanner, port, at));
+            tags.push(tag);
+            alerts.extend(raised);
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, ScanKind::PortScan);
+        assert_eq!(alerts[0].source, IpAddr::from(scanner));
+        assert_eq!(alerts[0].count, 20);
+        assert!(tags[..19].iter().all(Option::is_none));
+        assert!(
+            tags[19..]
+                .iter()
+                .all(|tag| *tag == Some(ScanKind::PortScan))
+        );
+
+        // A worm trying port 445 on a /24 where nobody answers
+        for host in 1..=25 {
+            let at = start + Duration::from_millis(20 * host as u64);
+            detector.observe(&outbound_syn([10, 9, 8, host], 445, at));
+        }
+
+        // A flood of SYNs at the web server from random sources
+        for i in 0..1500u32 {
+            let at = start + Duration::from_micros(5000 * i as u64);
+            let [_, _, c, d] = i.to_be_bytes();
+            detector.observe(&inbound_syn([198, 51, c, d], 443, at));
+        }
+
+        let kinds: Vec<(ScanKind, Option<u16>)> = detector
+            .alerts()
+            .iter()
+            .map(|alert| (alert.kind, alert.port))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ScanKind::PortScan, None),
+                (ScanKind::HostSweep, Some(445)),
+                (ScanKind::SynFlood, Some(443)),
+            ]
+        );
+        assert_eq!(
+            detector.alerts()[1].describe(),
+            "host sweep from 192.168.1.10 on port 445: 20 unanswered hosts in 10s"
+        );
+    }
+
+    #[test]
+    fn test_quiet_sources_make_room_for_new_ones() {
+        let mut windows: Windows<u32, u16> = Windows::new();
+        let start = Instant::now();
+        for source in 0..MAX_SOURCES as u32 {
+            let at = start + Duration::from_millis(source as u64);
+            windows.get(source, at, start).unwrap().push(at, 80);
+        }
+
+        // Source 0 is heard from again, leaving source 1 the quietest
+        let at = start + Duration::from_secs(1);
+        let window = windows.get(0, at, start).unwrap();
+        window.push(at, 443);
+        assert_eq!((window.distinct(), window.total()), (2, 2));
+        // Nobody has gone quiet for a whole window yet
+        assert!(windows.get(u32::MAX, at, start).is_none());
+
+        let cutoff = start + Duration::from_millis(2);
+        assert!(windows.get(u32::MAX, at, cutoff).is_some());
+        assert!(!windows.sources.contains_key(&1));
+        assert_eq!(windows.sources.len(), MAX_SOURCES);
+        assert!(windows.get(u32::MAX - 1, at, cutoff).is_none());
+
+        // Counts follow the entries that age out
+        let window = windows.get(0, at, cutoff).unwrap();
+        assert_eq!((window.distinct(), window.total()), (1, 1));
+    }
+
+    #[test]
+    fn test_late_entries_expire_in_time_order() {
+        let start = Instant::now();
+        let mut window = Window::new(start);
+        for secs in [50, 10, 30] {
+            window.push(start + Duration::from_secs(secs), ());
+        }
+        window.expire(start + Duration::from_secs(20));
+        assert_eq!(window.total(), 2);
+        window.expire(start + Duration::from_secs(40));
+        assert_eq!(window.total(), 1);
+    }
+
+    #[test]
+    fn test_browser_traffic_does_not_alert() {
+        let detector = ScanDetector::new(ScanThresholds::default());
+        let start = Instant::now();
+
+        // A page load: many connections to one host, then 40 CDN and ad
+        // hosts on 443, each answering within a few milliseconds
+        for i in 0..30 {
+            let at = start + Duration::from_millis(10 * i);
+            detector.observe(&outbound_syn([203, 0, 113, 5], 443, at));
+            detector.observe(&syn_ack(
+                [203, 0, 113, 5],
+                443,
+                at + Duration::from_millis(3),
+            ));
+        }
+        for host in 1..=40 {
+            let at = start + Duration::from_millis(5 * host as u64);
+            let (tag, _) = detector.observe(&outbound_syn([198, 51, 100, host], 443, at));
+            assert_eq!(tag, None);
+            detector.observe(&syn_ack(
+                [198, 51, 100, host],
+                443,
+                at + Duration::from_millis(2),
+            ));
+        }
+
+        // A busy server: many clients, a few SYNs each
+        for client in 1..=200u8 {
+            let at = start + Duration::from_millis(20 * client as u64);
+            detector.observe(&inbound_syn([100, 64, 0, client], 443, at));
+            detector.observe(&inbound_syn([100, 64, 0, client], 80, at));
+        }
+
+        // A slow scanner that stays under the threshold for any one window
+        for port in 1..=30u16 {
+            let at = start + Duration::from_secs(port as u64);
+            detector.observe(&inbound_syn([203, 0, 113, 77], port, at));
+        }
+
+        assert!(detector.alerts().is_empty());
+    }
+
+    fn ssh_session(remote: [u8; 4], start: Instant, lasted: Duration, packets: u64) -> Connection {
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            SocketAddr::from((LOCAL, 22)),
+            SocketAddr::from((remote, 50022)),
+            ProtocolState::Tcp(TcpState::Closed),
+        );
+        conn.packets_sent = packets;
+        conn.packets_received = packets;
+        conn.created_instant = start;
+        conn.last_activity_instant = start + lasted;
+        conn
+    }
+
+    #[test]
+    fn test_repeated_short_logins_alert_as_brute_force() {
+        let detector = ScanDetector::new(ScanThresholds::default());
+        let start = Instant::now();
+        let attacker = [198, 51, 100, 23];
+
+        // A password guesser: a new session every 4 seconds, each rejected
+        // after a couple of seconds
+        let mut alerts = Vec::new();
+        for attempt in 0..15 {
+            let opened = start + Duration::from_secs(4 * attempt);
+            let session = ssh_session(attacker, opened, Duration::from_secs(2), 12);
+            alerts.extend(detector.observe_close(&session));
+        }
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, ScanKind::BruteForce);
+        assert_eq!(alerts[0].port, Some(22));
+        assert_eq!(alerts[0].count, 10);
+        assert_eq!(
+            alerts[0].describe(),
+            "brute force from 198.51.100.23 on port 22: 10 short sessions in 60s"
+        );
+
+        // The attacker's other connections are the ones tagged
+        let live = ssh_session(attacker, start, Duration::from_secs(1), 2);
+        assert!(alerts[0].matches(&live));
+        let admin = ssh_session([192, 168, 1, 50], start, Duration::from_secs(1), 2);
+        assert!(!alerts[0].matches(&admin));
+    }
+
+    #[test]
+    fn test_normal_ssh_use_does_not_alert() {
+        let detector = ScanDetector::new(ScanThresholds::default());
+        let start = Instant::now();
+        let admin = [192, 168, 1, 50];
+
+        // A long interactive session, and a few quick commands or git fetches
+        let long = ssh_session(admin, start, Duration::from_secs(3600), 5000);
+        assert_eq!(detector.observe_close(&long), None);
+        for i in 0..5 {
+            let opened = start + Duration::from_secs(10 * i);
+            let session = ssh_session(admin, opened, Duration::from_secs(1), 20);
+            assert_eq!(detector.observe_close(&session), None);
+        }
+
+        // A scanner connecting and hanging up without a word is not logging in
+        for i in 0..30 {
+            let opened = start + Duration::from_secs(i);
+            let probe = ssh_session([203, 0, 113, 66], opened, Duration::from_millis(50), 3);
+            assert_eq!(detector.observe_close(&probe), None);
+        }
+
+        // Nor is a short-lived flow to a port nobody logs in on
+        let mut web = ssh_session([203, 0, 113, 9], start, Duration::from_secs(1), 20);
+        web.local_addr.set_port(8080);
+        for _ in 0..20 {
+            assert_eq!(detector.observe_close(&web), None);
+        }
+
+        assert!(detector.alerts().is_empty());
+    }
+}
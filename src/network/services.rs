@@ -55,6 +55,7 @@ impl ServiceLookup {
             let protocol = match port_parts[1].to_lowercase().as_str() {
                 "tcp" => Protocol::TCP,
                 "udp" => Protocol::UDP,
+                "sctp" => Protocol::SCTP,
                 _ => continue,
             };
 
@@ -124,6 +125,15 @@ impl ServiceLookup {
     pub fn lookup(&self, port: u16, protocol: Protocol) -> Option<&str> {
         self.services.get(&(port, protocol)).map(|s| s.as_str())
     }
+
+    /// Look up the port of a service by name and protocol
+    pub fn port(&self, name: &str, protocol: Protocol) -> Option<u16> {
+        self.services
+            .iter()
+            .filter(|((_, proto), service)| *proto == protocol && service.as_str() == name)
+            .map(|((port, _), _)| *port)
+            .min()
+    }
 }
 
 impl Default for ServiceLookup {
@@ -145,4 +155,13 @@ mod tests {
         assert_eq!(lookup.lookup(22, Protocol::TCP), Some("ssh"));
         assert_eq!(lookup.lookup(53, Protocol::UDP), Some("dns"));
     }
+
+    #[test]
+    fn test_port_by_name() {
+        let lookup = ServiceLookup::with_defaults();
+
+        assert_eq!(lookup.port("https", Protocol::TCP), Some(443));
+        assert_eq!(lookup.port("ntp", Protocol::UDP), Some(123));
+        assert_eq!(lookup.port("ntp", Protocol::TCP), None);
+    }
 }
@@ -0,0 +1,284 @@
+// src/network/talkers.rs - Traffic totals per remote host and per process for the Top tab
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::network::types::{ApplicationProtocol, Connection};
+
+/// Which total the Top tab ranks hosts by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TalkerOrder {
+    /// Current receive + transmit rate
+    #[default]
+    Rate,
+    /// Bytes moved since the flows were first seen
+    Bytes,
+}
+
+impl TalkerOrder {
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Rate => Self::Bytes,
+            Self::Bytes => Self::Rate,
+        }
+    }
+}
+
+/// Traffic of all flows to one remote host
+#[derive(Debug, Clone, PartialEq)]
+pub struct Talker {
+    /// TLS/QUIC server name or HTTP host if any flow carried one, otherwise
+    /// the remote IP
+    pub host: String,
+    pub connections: usize,
+    pub rate_bps: f64,
+    pub bytes: u64,
+}
+
+impl Talker {
+    /// Filter query that narrows the connection list to this host
+    pub fn filter_query(&self) -> String {
+        if self.host.parse::<IpAddr>().is_ok() {
+            format!("dst:{}", self.host)
+        } else {
+            format!("sni:{}", self.host)
+        }
+    }
+}
+
+/// Talkers ranked by one total, with the totals over every host so each
+/// entry's share can be shown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopTalkers {
+    pub talkers: Vec<Talker>,
+    pub total_rate_bps: f64,
+    pub total_bytes: u64,
+}
+
+/// Name a flow's remote end is grouped under: the server name from TLS or
+/// QUIC, then the HTTP Host header, then the remote IP
+pub fn host_key(conn: &Connection) -> String {
+    let name = conn
+        .dpi_info
+        .as_ref()
+        .and_then(|dpi| match &dpi.application {
+            ApplicationProtocol::Https(info) => {
+                info.tls_info.as_ref().and_then(|tls| tls.sni.clone())
+            }
+            ApplicationProtocol::Quic(info) => {
+                info.tls_info.as_ref().and_then(|tls| tls.sni.clone())
+            }
+            ApplicationProtocol::Http(info) => info.host.clone(),
+            _ => None,
+        });
+    name.map(|name| name.to_lowercase())
+        .unwrap_or_else(|| conn.remote_addr.ip().to_string())
+}
+
+/// Sum the traffic of `connections` per remote host
+pub fn aggregate(connections: &[Connection]) -> Vec<Talker> {
+    let mut hosts: HashMap<String, Talker> = HashMap::new();
+    // Flows between two other hosts have no remote end of ours, and ignored
+    // flows stay out of the totals
+    for conn in connections
+        .iter()
+        .filter(|conn| !conn.forwarded && !conn.ignored)
+    {
+        let host = host_key(conn);
+        let talker = hosts.entry(host.clone()).or_insert_with(|| Talker {
+            host,
+            connections: 0,
+            rate_bps: 0.0,
+            bytes: 0,
+        });
+        talker.connections += conn.subflow_count.max(1);
+        talker.rate_bps += conn.current_incoming_rate_bps + conn.current_outgoing_rate_bps;
+        talker.bytes += conn.bytes_sent + conn.bytes_received;
+    }
+    hosts.into_values().collect()
+}
+
+/// The `n` hosts with the most traffic by `order`. Ties are broken by host
+/// name so the list doesn't shuffle between frames.
+pub fn top_talkers(connections: &[Connection], n: usize, order: TalkerOrder) -> TopTalkers {
+    let mut talkers = aggregate(connections);
+    let total_rate_bps = talkers.iter().map(|talker| talker.rate_bps).sum();
+    let total_bytes = talkers.iter().map(|talker| talker.bytes).sum();
+
+    talkers.sort_by(|a, b| {
+        let ordering = match order {
+            TalkerOrder::Rate => b.rate_bps.total_cmp(&a.rate_bps),
+            TalkerOrder::Bytes => b.bytes.cmp(&a.bytes),
+        };
+        ordering.then_with(|| a.host.cmp(&b.host))
+    });
+    talkers.truncate(n);
+
+    TopTalkers {
+        talkers,
+        total_rate_bps,
+        total_bytes,
+    }
+}
+
+/// Name flows without a known process are grouped under
+pub const UNKNOWN_PROCESS: &str = "unknown";
+
+/// Bytes sent and received per local process, as (process, sent, received),
+/// most traffic first. Ties are broken by process name.
+pub fn process_bandwidth(connections: &[Connection]) -> Vec<(String, u64, u64)> {
+    let mut processes: HashMap<&str, (u64, u64)> = HashMap::new();
+    for conn in connections
+        .iter()
+        .filter(|conn| !conn.forwarded && !conn.ignored)
+    {
+        let name = conn.process_name.as_deref().unwrap_or(UNKNOWN_PROCESS);
+        let (sent, received) = processes.entry(name).or_default();
+        *sent += conn.bytes_sent;
+        *received += conn.bytes_received;
+    }
+
+    let mut processes: Vec<(String, u64, u64)> = processes
+        .into_iter()
+        .map(|(name, (sent, received))| (name.to_string(), sent, received))
+        .collect();
+    processes.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then_with(|| a.0.cmp(&b.0)));
+    processes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::types::{
+        DpiInfo, HttpInfo, HttpVersion, HttpsInfo, Protocol, ProtocolState, TcpState, TlsInfo,
+    };
+    use std::net::SocketAddr;
+
+    fn flow(remote: &str, bytes: u64, rate_bps: f64) -> Connection {
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            "192.168.1.10:50000".parse().unwrap(),
+            remote.parse::<SocketAddr>().unwrap(),
+            ProtocolState::Tcp(TcpState::Established),
+        );
+        conn.bytes_received = bytes;
+        conn.current_incoming_rate_bps = rate_bps;
+        conn
+    }
+
+    fn with_sni(mut conn: Connection, sni: &str) -> Connection {
+        let mut tls_info = TlsInfo::new();
+        tls_info.sni = Some(sni.to_string());
+        conn.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Https(HttpsInfo {
+                tls_info: Some(tls_info),
+                handshake: None,
+            }),
+            first_packet_time: std::time::Instant::now(),
+            last_update_time: std::time::Instant::now(),
+            pending_request_at: None,
+        });
+        conn
+    }
+
+    fn with_http_host(mut conn: Connection, host: &str) -> Connection {
+        conn.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Http(HttpInfo {
+                version: HttpVersion::Http11,
+                method: Some("GET".to_string()),
+                host: Some(host.to_string()),
+                path: None,
+                status_code: None,
+                user_agent: None,
+                response_time: None,
+            }),
+            first_packet_time: std::time::Instant::now(),
+            last_update_time: std::time::Instant::now(),
+            pending_request_at: None,
+        });
+        conn
+    }
+
+    #[test]
+    fn test_flows_are_grouped_by_name_before_address() {
+        let connections = vec![
+            // Two CDN addresses serving the same site
+            with_sni(flow("203.0.113.1:443", 1000, 10.0), "Example.com"),
+            with_sni(flow("203.0.113.2:443", 3000, 30.0), "example.com"),
+            with_http_host(flow("198.51.100.9:80", 500, 0.0), "plain.example"),
+            flow("198.51.100.9:22", 200, 5.0),
+            flow("198.51.100.9:23", 100, 0.0),
+        ];
+
+        let mut talkers = aggregate(&connections);
+        talkers.sort_by(|a, b| a.host.cmp(&b.host));
+        let summary: Vec<(&str, usize, u64)> = talkers
+            .iter()
+            .map(|talker| (talker.host.as_str(), talker.connections, talker.bytes))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("198.51.100.9", 2, 300),
+                ("example.com", 2, 4000),
+                ("plain.example", 1, 500),
+            ]
+        );
+        assert_eq!(talkers[1].rate_bps, 40.0);
+        assert_eq!(talkers[0].filter_query(), "dst:198.51.100.9");
+        assert_eq!(talkers[1].filter_query(), "sni:example.com");
+    }
+
+    #[test]
+    fn test_top_n_by_rate_and_by_bytes() {
+        let connections = vec![
+            flow("203.0.113.1:443", 9000, 1.0),
+            flow("203.0.113.2:443", 100, 800.0),
+            flow("203.0.113.3:443", 5000, 200.0),
+            flow("203.0.113.4:443", 5000, 0.0),
+        ];
+
+        let by_rate = top_talkers(&connections, 2, TalkerOrder::Rate);
+        let hosts: Vec<&str> = by_rate.talkers.iter().map(|t| t.host.as_str()).collect();
+        assert_eq!(hosts, vec!["203.0.113.2", "203.0.113.3"]);
+        // Totals cover every host, not just the ones shown
+        assert_eq!(by_rate.total_rate_bps, 1001.0);
+        assert_eq!(by_rate.total_bytes, 19100);
+
+        let by_bytes = top_talkers(&connections, 3, TalkerOrder::Bytes);
+        let hosts: Vec<&str> = by_bytes.talkers.iter().map(|t| t.host.as_str()).collect();
+        // Equal totals fall back to name order
+        assert_eq!(hosts, vec!["203.0.113.1", "203.0.113.3", "203.0.113.4"]);
+
+        assert!(top_talkers(&[], 5, TalkerOrder::Rate).talkers.is_empty());
+    }
+
+    #[test]
+    fn test_bandwidth_is_summed_per_process() {
+        let mut connections = vec![
+            flow("203.0.113.1:443", 1000, 0.0),
+            flow("203.0.113.2:443", 3000, 0.0),
+            flow("203.0.113.3:443", 500, 0.0),
+            flow("203.0.113.4:443", 4000, 0.0),
+        ];
+        connections[0].process_name = Some("firefox".to_string());
+        connections[0].bytes_sent = 200;
+        connections[1].process_name = Some("firefox".to_string());
+        connections[2].process_name = Some("curl".to_string());
+        connections[2].bytes_sent = 4500;
+        // Ignored flows stay out of the totals
+        connections[3].process_name = Some("curl".to_string());
+        connections[3].ignored = true;
+        connections.push(flow("203.0.113.5:443", 700, 0.0));
+
+        assert_eq!(
+            process_bandwidth(&connections),
+            vec![
+                ("curl".to_string(), 4500, 500),
+                ("firefox".to_string(), 200, 4000),
+                (UNKNOWN_PROCESS.to_string(), 0, 700),
+            ]
+        );
+        assert!(process_bandwidth(&[]).is_empty());
+    }
+}
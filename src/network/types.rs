@@ -1,8 +1,41 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fmt;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant, SystemTime};
 
+use crate::network::scan::ScanKind;
+
+/// Upper bound on CRYPTO frame bytes buffered across all QUIC reassemblers
+pub const QUIC_REASSEMBLY_BUDGET: usize = 16 * 1024 * 1024;
+
+/// Bytes currently buffered by all `CryptoFrameReassembler`s
+static QUIC_REASSEMBLY_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Fragments rejected because the global reassembly budget was exhausted
+static QUIC_REASSEMBLY_REJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes currently buffered for QUIC CRYPTO frame reassembly
+pub fn quic_reassembly_bytes() -> usize {
+    QUIC_REASSEMBLY_BYTES.load(Ordering::Relaxed)
+}
+
+/// Number of CRYPTO fragments rejected by the global reassembly budget
+pub fn quic_reassembly_rejected() -> u64 {
+    QUIC_REASSEMBLY_REJECTED.load(Ordering::Relaxed)
+}
+
+/// Add `len` to `counter` unless that would take it past `budget`
+fn reserve_bytes(counter: &AtomicUsize, budget: usize, len: usize) -> bool {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+            let new_total = total + len;
+            (new_total <= budget).then_some(new_total)
+        })
+        .is_ok()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(clippy::upper_case_acronyms)] // Protocol names are standardized
 pub enum Protocol {
@@ -10,6 +43,9 @@ pub enum Protocol {
     UDP,
     ICMP,
     ARP,
+    SCTP,
+    /// Any other IP protocol, by its number (GRE is 47, ESP 50)
+    Other(u8),
 }
 
 impl std::fmt::Display for Protocol {
@@ -19,6 +55,8 @@ impl std::fmt::Display for Protocol {
             Protocol::UDP => write!(f, "UDP"),
             Protocol::ICMP => write!(f, "ICMP"),
             Protocol::ARP => write!(f, "ARP"),
+            Protocol::SCTP => write!(f, "SCTP"),
+            Protocol::Other(number) => write!(f, "IP{}", number),
         }
     }
 }
@@ -34,16 +72,10 @@ impl std::fmt::Display for ApplicationProtocol {
                 }
             }
             ApplicationProtocol::Https(info) => {
-                if info.tls_info.is_none() {
-                    write!(f, "HTTPS")
-                } else {
-                    let info = info.tls_info.as_ref().unwrap();
-                    // If SNI is available, include it in the display
-                    if let Some(sni) = &info.sni {
-                        write!(f, "HTTPS ({})", sni)
-                    } else {
-                        write!(f, "HTTPS")
-                    }
+                // If SNI is available, include it in the display
+                match info.tls_info.as_ref().and_then(|tls| tls.sni.as_ref()) {
+                    Some(sni) => write!(f, "HTTPS ({})", sni),
+                    None => write!(f, "HTTPS"),
                 }
             }
             ApplicationProtocol::Dns(info) => {
@@ -77,6 +109,10 @@ impl std::fmt::Display for ApplicationProtocol {
                     write!(f, "QUIC")
                 }
             }
+            ApplicationProtocol::Custom(info) => match &info.detail {
+                Some(detail) => write!(f, "{} ({})", info.name, detail),
+                None => write!(f, "{}", info.name),
+            },
         }
     }
 }
@@ -99,13 +135,86 @@ pub enum TcpState {
     TimeWait,
     Closing,
     Closed,
+    /// Torn down by a RST rather than a graceful close
+    Reset,
     Unknown,
 }
 
+/// Why a connection left the connection table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// Closed gracefully with FINs
+    FinClosed,
+    /// Torn down by a RST
+    Reset,
+    /// No packets for longer than its timeout
+    IdleTimeout,
+    /// Dropped to make room in a full table
+    Evicted,
+    /// Its socket disappeared from the system socket table with no close
+    /// seen on the wire
+    SourceGone,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseReason::FinClosed => write!(f, "FIN"),
+            CloseReason::Reset => write!(f, "RESET"),
+            CloseReason::IdleTimeout => write!(f, "IDLE"),
+            CloseReason::Evicted => write!(f, "EVICTED"),
+            CloseReason::SourceGone => write!(f, "SOCKET_GONE"),
+        }
+    }
+}
+
+/// Operating system guessed from a remote host's SYN or SYN-ACK
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsGuess {
+    pub os: String,
+    /// How much of the matching signature agreed with the packet, 0-100
+    pub confidence: u8,
+}
+
+impl fmt::Display for OsGuess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}%)", self.os, self.confidence)
+    }
+}
+
+/// Where a remote host is, from a GeoIP database
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoInfo {
+    /// ISO 3166-1 alpha-2 code, e.g. `IE`
+    pub country_code: String,
+    pub city: Option<String>,
+    /// Zero when the database has no coordinates, as country databases don't
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl fmt::Display for GeoInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.city {
+            Some(city) => write!(f, "{}, {}", city, self.country_code),
+            None => write!(f, "{}", self.country_code),
+        }
+    }
+}
+
+/// UDP has no handshake, so a flow's state comes from the traffic seen on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpState {
+    /// Packets seen in one direction only, like an unanswered query or a scan
+    Unconnected,
+    /// Packets seen in both directions
+    Active,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ProtocolState {
     Tcp(TcpState),
-    Udp,
+    Udp(UdpState),
     Icmp {
         icmp_type: u8,
         #[allow(dead_code)]
@@ -114,6 +223,8 @@ pub enum ProtocolState {
     Arp {
         operation: ArpOperation,
     },
+    /// Protocols that aren't decoded past the IP header
+    Other,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -153,6 +264,15 @@ pub enum ApplicationProtocol {
     Dns(DnsInfo),
     Ssh(SshInfo),
     Quic(Box<QuicInfo>),
+    /// Protocol recognised by a custom signature
+    Custom(CustomProtocolInfo),
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomProtocolInfo {
+    /// Protocol name given by the signature
+    pub name: String,
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -163,6 +283,8 @@ pub struct HttpInfo {
     pub path: Option<String>,
     pub status_code: Option<u16>,
     pub user_agent: Option<String>,
+    /// Time from the latest request to the response that followed it
+    pub response_time: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -175,6 +297,8 @@ pub enum HttpVersion {
 #[derive(Debug, Clone)]
 pub struct HttpsInfo {
     pub tls_info: Option<TlsInfo>,
+    /// TLS bytes of a ClientHello/ServerHello that didn't fit in one segment
+    pub handshake: Option<TlsHandshakeReassembler>,
 }
 
 #[derive(Debug, Clone)]
@@ -183,6 +307,13 @@ pub struct TlsInfo {
     pub sni: Option<String>,
     pub alpn: Vec<String>,
     pub cipher_suite: Option<u16>,
+    /// Highest version offered in the ClientHello (legacy version or supported_versions)
+    pub client_max_tls_version: Option<TlsVersion>,
+    /// Version selected by the server in the ServerHello
+    pub server_negotiated_version: Option<TlsVersion>,
+    /// The ClientHello offered 0-RTT early data, which an attacker who
+    /// captures it can replay
+    pub has_early_data: bool,
 }
 
 impl Default for TlsInfo {
@@ -198,9 +329,27 @@ impl TlsInfo {
             sni: None,
             alpn: Vec::new(),
             cipher_suite: None,
+            client_max_tls_version: None,
+            server_negotiated_version: None,
+            has_early_data: false,
+        }
+    }
+
+    /// Check whether the server negotiated a version more than one step below
+    /// what the client offered, which suggests a downgrading middlebox
+    pub fn is_possible_downgrade(&self) -> bool {
+        match (self.client_max_tls_version, self.server_negotiated_version) {
+            (Some(client), Some(server)) => (client as u8).saturating_sub(server as u8) > 1,
+            _ => false,
         }
     }
 
+    /// Check whether the negotiated version is deprecated (RFC 8996)
+    pub fn uses_deprecated_version(&self) -> bool {
+        self.server_negotiated_version
+            .is_some_and(|version| version.is_deprecated())
+    }
+
     /// Format the cipher suite with name and hex code
     pub fn format_cipher_suite(&self) -> Option<String> {
         self.cipher_suite
@@ -212,9 +361,181 @@ impl TlsInfo {
         self.cipher_suite
             .map(crate::network::dpi::is_secure_cipher_suite)
     }
+
+    /// Weaknesses of the handshake. `client_is_local` says whether this host
+    /// sent the ClientHello, as only our own clients are expected to send SNI;
+    /// `weak_cipher_suites` are flagged on top of the built-in list.
+    pub fn warnings(
+        &self,
+        client_is_local: bool,
+        weak_cipher_suites: &HashSet<u16>,
+    ) -> Vec<TlsWarning> {
+        let mut warnings = Vec::new();
+        if let Some(version) = self.server_negotiated_version
+            && version.is_deprecated()
+        {
+            warnings.push(TlsWarning::DeprecatedVersion(version));
+        }
+        if let Some(code) = self.cipher_suite
+            && let Some(weakness) =
+                crate::network::dpi::cipher_suite_weakness(code, weak_cipher_suites)
+        {
+            warnings.push(TlsWarning::WeakCipher(code, weakness));
+        }
+        // Only a parsed ClientHello sets the client's version
+        if client_is_local && self.client_max_tls_version.is_some() && self.sni.is_none() {
+            warnings.push(TlsWarning::MissingSni);
+        }
+        warnings
+    }
 }
 
+/// A TLS handshake weakness worth a compliance review
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsWarning {
+    /// Negotiated version below TLS 1.2 (RFC 8996)
+    DeprecatedVersion(TlsVersion),
+    /// Negotiated cipher suite is broken or banned by the `weak_ciphers` setting
+    WeakCipher(u16, crate::network::dpi::CipherWeakness),
+    /// ClientHello from this host without a server name
+    MissingSni,
+}
+
+impl TlsWarning {
+    /// Name used by the `tls:` filter keyword
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::DeprecatedVersion(_) => "deprecated",
+            Self::WeakCipher(..) => "cipher",
+            Self::MissingSni => "nosni",
+        }
+    }
+}
+
+impl fmt::Display for TlsWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeprecatedVersion(version) => {
+                write!(f, "Deprecated TLS version {} (RFC 8996)", version)
+            }
+            Self::WeakCipher(code, weakness) => write!(
+                f,
+                "Weak cipher suite {} ({})",
+                crate::network::dpi::format_cipher_suite(*code),
+                weakness
+            ),
+            Self::MissingSni => write!(f, "Outbound HTTPS without SNI"),
+        }
+    }
+}
+
+/// Most TLS bytes buffered per direction while waiting for the first
+/// handshake message to complete
+pub const TLS_HANDSHAKE_BUFFER_LIMIT: usize = 32 * 1024;
+
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// Buffers the TLS stream of each direction until its first handshake message
+/// (ClientHello or ServerHello) is complete, which may take several records
+/// and several TCP segments.
+/// This is part of the HttpsInfo data model, even though it's used by DPI
+#[derive(Debug, Clone, Default)]
+pub struct TlsHandshakeReassembler {
+    outgoing: TlsHandshakeStream,
+    incoming: TlsHandshakeStream,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TlsHandshakeStream {
+    data: Vec<u8>,
+    /// The first handshake message was taken, or the stream was given up on
+    done: bool,
+}
+
+impl TlsHandshakeStream {
+    fn finish(&mut self) {
+        self.data = Vec::new();
+        self.done = true;
+    }
+}
+
+impl TlsHandshakeReassembler {
+    /// Hold a single segment's payload, to be appended to a connection's
+    /// reassembler when the DPI result is merged
+    pub fn segment(is_outgoing: bool, payload: &[u8]) -> Self {
+        let mut reassembler = Self::default();
+        reassembler.stream_mut(is_outgoing).data = payload.to_vec();
+        reassembler
+    }
+
+    /// Append the segments held by `other`. A direction only starts buffering
+    /// at a handshake record, and stops once it's done or over the limit.
+    pub fn append(&mut self, other: &TlsHandshakeReassembler) {
+        for (is_outgoing, bytes) in [(true, &other.outgoing.data), (false, &other.incoming.data)] {
+            let stream = self.stream_mut(is_outgoing);
+            if stream.done
+                || bytes.is_empty()
+                || (stream.data.is_empty() && bytes[0] != TLS_HANDSHAKE_RECORD)
+            {
+                continue;
+            }
+            if stream.data.len() + bytes.len() > TLS_HANDSHAKE_BUFFER_LIMIT {
+                stream.finish();
+                continue;
+            }
+            stream.data.extend_from_slice(bytes);
+        }
+    }
+
+    /// Take the first handshake message of a direction (4-byte header
+    /// included) once all of it has arrived, concatenating the payloads of
+    /// successive handshake records. The direction stops buffering afterwards.
+    pub fn take_message(&mut self, is_outgoing: bool) -> Option<Vec<u8>> {
+        let stream = self.stream_mut(is_outgoing);
+        let mut message = Vec::new();
+        let mut offset = 0;
+
+        while offset + 5 <= stream.data.len() {
+            let header = &stream.data[offset..offset + 5];
+            let record_length = u16::from_be_bytes([header[3], header[4]]) as usize;
+            if header[0] != TLS_HANDSHAKE_RECORD || header[1] != 0x03 || record_length > 16384 {
+                // Not (or no longer) a handshake we can follow
+                stream.finish();
+                return None;
+            }
+
+            let body_start = offset + 5;
+            let body_end = stream.data.len().min(body_start + record_length);
+            message.extend_from_slice(&stream.data[body_start..body_end]);
+
+            if message.len() >= 4 {
+                let message_length =
+                    4 + u32::from_be_bytes([0, message[1], message[2], message[3]]) as usize;
+                if message.len() >= message_length {
+                    message.truncate(message_length);
+                    stream.finish();
+                    return Some(message);
+                }
+                if message_length > TLS_HANDSHAKE_BUFFER_LIMIT {
+                    stream.finish();
+                    return None;
+                }
+            }
+            offset = body_start + record_length;
+        }
+        None
+    }
+
+    fn stream_mut(&mut self, is_outgoing: bool) -> &mut TlsHandshakeStream {
+        if is_outgoing {
+            &mut self.outgoing
+        } else {
+            &mut self.incoming
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TlsVersion {
     #[allow(dead_code)]
     Ssl3,
@@ -224,6 +545,16 @@ pub enum TlsVersion {
     Tls13,
 }
 
+impl TlsVersion {
+    /// SSL 3.0, TLS 1.0 and TLS 1.1 are deprecated by RFC 8996
+    pub fn is_deprecated(&self) -> bool {
+        matches!(
+            self,
+            TlsVersion::Ssl3 | TlsVersion::Tls10 | TlsVersion::Tls11
+        )
+    }
+}
+
 impl fmt::Display for TlsVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -240,9 +571,54 @@ impl fmt::Display for TlsVersion {
 pub struct DnsInfo {
     pub query_name: Option<String>,
     pub query_type: Option<DnsQueryType>,
-    #[allow(dead_code)]
     pub response_ips: Vec<std::net::IpAddr>,
     pub is_response: bool,
+    pub rcode: Option<DnsResponseCode>, // Status of a response
+    pub truncated: bool,                // TC bit: the client should retry over TCP
+    pub ttl: Option<u32>,               // Of the first answer record, in seconds
+}
+
+/// DNS response status, including the EDNS extended RCODE bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsResponseCode {
+    NoError,    // 0
+    FormErr,    // 1
+    ServFail,   // 2
+    NXDomain,   // 3
+    NotImp,     // 4
+    Refused,    // 5
+    BadVers,    // 16, EDNS only
+    Other(u16), // For any other code
+}
+
+impl From<u16> for DnsResponseCode {
+    fn from(rcode: u16) -> Self {
+        match rcode {
+            0 => DnsResponseCode::NoError,
+            1 => DnsResponseCode::FormErr,
+            2 => DnsResponseCode::ServFail,
+            3 => DnsResponseCode::NXDomain,
+            4 => DnsResponseCode::NotImp,
+            5 => DnsResponseCode::Refused,
+            16 => DnsResponseCode::BadVers,
+            other => DnsResponseCode::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for DnsResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsResponseCode::NoError => write!(f, "NOERROR"),
+            DnsResponseCode::FormErr => write!(f, "FORMERR"),
+            DnsResponseCode::ServFail => write!(f, "SERVFAIL"),
+            DnsResponseCode::NXDomain => write!(f, "NXDOMAIN"),
+            DnsResponseCode::NotImp => write!(f, "NOTIMP"),
+            DnsResponseCode::Refused => write!(f, "REFUSED"),
+            DnsResponseCode::BadVers => write!(f, "BADVERS"),
+            DnsResponseCode::Other(code) => write!(f, "RCODE{}", code),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -314,6 +690,9 @@ pub struct QuicInfo {
     pub packet_type: QuicPacketType,
     pub connection_id: Vec<u8>,
     pub connection_id_hex: Option<String>,
+    /// Source connection ID of a long-header packet: the ID its sender wants
+    /// to be addressed by
+    pub source_connection_id: Vec<u8>,
     pub connection_state: QuicConnectionState,
     pub tls_info: Option<TlsInfo>, // Extracted TLS handshake info
     pub has_crypto_frame: bool,    // Whether packet contains CRYPTO frame
@@ -329,6 +708,7 @@ impl QuicInfo {
             connection_id_hex: None,
             packet_type: QuicPacketType::Unknown,
             connection_id: Vec::new(),
+            source_connection_id: Vec::new(),
             connection_state: QuicConnectionState::Unknown,
             tls_info: None,
             has_crypto_frame: false,
@@ -343,6 +723,28 @@ impl QuicInfo {
             self.crypto_reassembler = Some(CryptoFrameReassembler::new());
         }
     }
+
+    /// Free reassembly memory that is no longer useful: the reassembler is dropped
+    /// once it produced TLS info, and a stale one loses its buffered fragments.
+    /// Returns the number of bytes released.
+    pub fn reclaim_reassembler(&mut self, stale_after: Duration) -> usize {
+        let Some(reassembler) = &mut self.crypto_reassembler else {
+            return 0;
+        };
+
+        if let Some(tls_info) = reassembler.get_cached_tls_info() {
+            if self.tls_info.is_none() {
+                self.tls_info = Some(tls_info.clone());
+            }
+            let freed = reassembler.buffered_bytes();
+            self.crypto_reassembler = None;
+            freed
+        } else if reassembler.is_stale(stale_after) {
+            reassembler.clear_fragments()
+        } else {
+            0
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -407,12 +809,36 @@ fn quic_version_to_string(version: u32) -> Option<String> {
     }
 }
 
+/// CRYPTO frame fragments, shared by a reassembler and its clones. Their bytes
+/// are charged against `QUIC_REASSEMBLY_BUDGET` once, as they are added, and
+/// given back when the last holder lets go.
+#[derive(Debug, Default)]
+struct BufferedFragments {
+    /// Fragments indexed by offset - using BTreeMap for ordered iteration
+    map: BTreeMap<u64, Vec<u8>>,
+
+    /// Bytes held in `map`
+    size: usize,
+}
+
+impl Drop for BufferedFragments {
+    fn drop(&mut self) {
+        QUIC_REASSEMBLY_BYTES.fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
 /// Tracks CRYPTO frame fragments for reassembly
 /// This is part of the QuicInfo data model, even though it's used by DPI
+///
+/// Buffered bytes are also charged against `QUIC_REASSEMBLY_BUDGET`, which is
+/// shared by every reassembler so a burst of half-open QUIC connections can't
+/// pin unbounded memory. Clones share the fragments rather than copying them,
+/// so snapshots of a connection cost the budget nothing.
 #[derive(Debug, Clone)]
 pub struct CryptoFrameReassembler {
-    /// Fragments indexed by offset - using BTreeMap for ordered iteration
-    fragments: BTreeMap<u64, Vec<u8>>,
+    /// Buffered fragments, copied only when a reassembler adds to fragments
+    /// a clone still holds
+    fragments: Arc<BufferedFragments>,
 
     /// Highest contiguous byte we've reassembled from offset 0
     contiguous_offset: u64,
@@ -426,9 +852,6 @@ pub struct CryptoFrameReassembler {
     /// Maximum total size we'll buffer (prevent memory exhaustion)
     max_buffer_size: usize,
 
-    /// Current total buffered size
-    current_buffer_size: usize,
-
     /// Timestamp of last update (for cleanup of stale fragments)
     last_update: Instant,
 }
@@ -442,47 +865,90 @@ impl Default for CryptoFrameReassembler {
 impl CryptoFrameReassembler {
     pub fn new() -> Self {
         Self {
-            fragments: BTreeMap::new(),
+            fragments: Arc::default(),
             contiguous_offset: 0,
             has_complete_tls_info: false,
             cached_tls_info: None,
             max_buffer_size: 64 * 1024, // 64KB max buffer
-            current_buffer_size: 0,
             last_update: Instant::now(),
         }
     }
 
-    /// Add a new CRYPTO frame fragment
+    /// Add a new CRYPTO frame fragment. Retransmissions may cover bytes we
+    /// already hold with shifted offsets; those bytes are kept as they are
+    /// (first write wins) and only the new ranges of `data` are stored.
     pub fn add_fragment(&mut self, offset: u64, data: Vec<u8>) -> Result<(), &'static str> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let data_end = offset + data.len() as u64;
+
+        // Collect the ranges not yet covered. Stored fragments never overlap, so
+        // only the one starting at or before `offset` can reach into the start.
+        let mut new_ranges = Vec::new();
+        let mut cursor = offset;
+        let preceding = self.fragments.map.range(..=offset).next_back();
+        for (&frag_offset, frag_data) in preceding
+            .into_iter()
+            .chain(self.fragments.map.range(offset + 1..data_end))
+        {
+            let frag_end = frag_offset + frag_data.len() as u64;
+            if frag_offset > cursor {
+                new_ranges.push((cursor, frag_offset));
+            }
+            cursor = cursor.max(frag_end);
+        }
+        if cursor < data_end {
+            new_ranges.push((cursor, data_end));
+        }
+
+        let new_bytes: usize = new_ranges
+            .iter()
+            .map(|(start, end)| (end - start) as usize)
+            .sum();
+        if new_bytes == 0 {
+            // Exact or contained duplicate
+            return Ok(());
+        }
+
         // Check if this would exceed our buffer limit
-        if self.current_buffer_size + data.len() > self.max_buffer_size {
+        if self.fragments.size + new_bytes > self.max_buffer_size {
             return Err("Fragment would exceed maximum buffer size");
         }
 
         self.last_update = Instant::now();
 
-        // Check for overlapping fragments
-        let data_end = offset + data.len() as u64;
-
-        // Handle overlaps by keeping the existing data (first write wins)
-        for (&frag_offset, frag_data) in &self.fragments {
-            let frag_end = frag_offset + frag_data.len() as u64;
-
-            // Check for exact duplicate
-            if offset == frag_offset && data_end == frag_end {
-                return Ok(());
-            }
+        // Reserve space in the global budget. Fragments a clone still holds
+        // are copied before adding to them, and the copy is charged in full.
+        let shared = Arc::get_mut(&mut self.fragments).is_none();
+        let charge = if shared {
+            self.fragments.size + new_bytes
+        } else {
+            new_bytes
+        };
+        if !reserve_bytes(&QUIC_REASSEMBLY_BYTES, QUIC_REASSEMBLY_BUDGET, charge) {
+            QUIC_REASSEMBLY_REJECTED.fetch_add(1, Ordering::Relaxed);
+            return Err("Fragment would exceed global reassembly budget");
+        }
+        if shared {
+            self.fragments = Arc::new(BufferedFragments {
+                map: self.fragments.map.clone(),
+                size: self.fragments.size,
+            });
+        }
+        let fragments = Arc::get_mut(&mut self.fragments).expect("fragments are unshared");
 
-            // Check for overlap
-            if offset < frag_end && data_end > frag_offset {
-                return Ok(());
+        // Add the fragment, or the pieces of it we didn't have
+        fragments.size += new_bytes;
+        if new_bytes == data.len() {
+            fragments.map.insert(offset, data);
+        } else {
+            for (start, end) in new_ranges {
+                let piece = &data[(start - offset) as usize..(end - offset) as usize];
+                fragments.map.insert(start, piece.to_vec());
             }
         }
 
-        // Add the fragment
-        self.current_buffer_size += data.len();
-        self.fragments.insert(offset, data);
-
         // Try to advance contiguous offset
         self.update_contiguous_offset();
 
@@ -493,7 +959,7 @@ impl CryptoFrameReassembler {
     fn update_contiguous_offset(&mut self) {
         let mut current = self.contiguous_offset;
 
-        for (&offset, data) in &self.fragments {
+        for (&offset, data) in &self.fragments.map {
             if offset <= current {
                 let fragment_end = offset + data.len() as u64;
                 if fragment_end > current {
@@ -509,32 +975,26 @@ impl CryptoFrameReassembler {
 
     /// Get all contiguous data from offset 0
     pub fn get_contiguous_data(&self) -> Option<Vec<u8>> {
-        if self.contiguous_offset == 0 {
+        self.get_contiguous_data_up_to(self.contiguous_offset)
+    }
+
+    /// Contiguous data from offset 0 up to `end`, or up to the first gap if
+    /// that comes sooner. DPI can look at the start of the stream this way
+    /// while later fragments are still missing.
+    pub fn get_contiguous_data_up_to(&self, end: u64) -> Option<Vec<u8>> {
+        let end = end.min(self.contiguous_offset);
+        if end == 0 {
             return None;
         }
 
-        let mut result = Vec::with_capacity(self.contiguous_offset as usize);
-        let mut current_offset = 0u64;
-
-        for (&offset, data) in &self.fragments {
-            if offset <= current_offset {
-                let skip = (current_offset - offset) as usize;
-                if skip < data.len() {
-                    result.extend_from_slice(&data[skip..]);
-                    current_offset = offset + data.len() as u64;
-                }
-            }
-
-            if current_offset >= self.contiguous_offset {
-                break;
-            }
-        }
-
-        if result.is_empty() {
-            None
-        } else {
-            Some(result)
+        // Stored fragments never overlap and there is no gap before `end`,
+        // so they tile the range in offset order
+        let mut result = Vec::with_capacity(end as usize);
+        for (&offset, data) in self.fragments.map.range(..end) {
+            let take = ((end - offset) as usize).min(data.len());
+            result.extend_from_slice(&data[..take]);
         }
+        Some(result)
     }
 
     /// Mark as having complete TLS info
@@ -552,10 +1012,28 @@ impl CryptoFrameReassembler {
         }
     }
 
+    /// Bytes currently buffered in fragments
+    pub fn buffered_bytes(&self) -> usize {
+        self.fragments.size
+    }
+
+    /// Whether no fragment arrived within `timeout`
+    pub fn is_stale(&self, timeout: Duration) -> bool {
+        self.last_update.elapsed() > timeout
+    }
+
+    /// Drop all buffered fragments, returning the bytes released. Cached TLS info is kept.
+    pub fn clear_fragments(&mut self) -> usize {
+        let freed = self.fragments.size;
+        self.fragments = Arc::default();
+        self.contiguous_offset = 0;
+        freed
+    }
+
     /// Get a reference to the fragments for merging purposes
     /// Returns an immutable reference to the internal fragments map
     pub fn get_fragments(&self) -> &BTreeMap<u64, Vec<u8>> {
-        &self.fragments
+        &self.fragments.map
     }
 }
 
@@ -566,6 +1044,8 @@ pub struct DpiInfo {
     pub first_packet_time: Instant,
     #[allow(dead_code)]
     pub last_update_time: Instant,
+    /// When the HTTP request still waiting for a response was seen
+    pub pending_request_at: Option<Instant>,
 }
 
 #[derive(Debug, Clone)]
@@ -596,11 +1076,17 @@ struct RateSample {
     delta_received: u64,
 }
 
+/// Seconds of per-second byte totals kept for `RateTracker::throughput_at`
+pub const THROUGHPUT_HISTORY_SECS: usize = 60;
+
 #[derive(Debug, Clone)]
 pub struct RateTracker {
     samples: VecDeque<RateSample>,
+    // Bytes moved per second, oldest first, for averages over longer windows
+    // than `window_duration`
+    history: VecDeque<RateSample>,
     window_duration: Duration,
-    last_update: Instant,
+    last_update: Option<Instant>,
     max_samples: usize,
     // Keep track of last byte counts for delta calculation
     last_bytes_sent: u64,
@@ -615,8 +1101,10 @@ impl RateTracker {
     pub fn with_window_duration(window_duration: Duration) -> Self {
         Self {
             samples: VecDeque::new(),
+            history: VecDeque::with_capacity(THROUGHPUT_HISTORY_SECS),
             window_duration,
-            last_update: Instant::now(),
+            // Advanced to the newest sample's time, which may be a mapped capture time
+            last_update: None,
             max_samples: 100, // Limit memory usage
             last_bytes_sent: 0,
             last_bytes_received: 0,
@@ -630,10 +1118,9 @@ impl RateTracker {
         self.last_bytes_received = bytes_received;
     }
 
-    /// Update the rate tracker with new byte counts
-    pub fn update(&mut self, bytes_sent: u64, bytes_received: u64) {
-        let now = Instant::now();
-
+    /// Update the rate tracker with byte counts observed at `now`, e.g. the
+    /// capture time of the packet that produced them
+    pub fn update_at(&mut self, bytes_sent: u64, bytes_received: u64, now: Instant) {
         // Calculate deltas since last update
         let delta_sent = bytes_sent.saturating_sub(self.last_bytes_sent);
         let delta_received = bytes_received.saturating_sub(self.last_bytes_received);
@@ -645,10 +1132,13 @@ impl RateTracker {
             delta_received,
         });
 
+        self.record_history(delta_sent, delta_received, now);
+
         // Update last values for next delta calculation
         self.last_bytes_sent = bytes_sent;
         self.last_bytes_received = bytes_received;
-        self.last_update = now;
+        // Packets from different processor threads can arrive slightly out of order
+        self.last_update = self.last_update.max(Some(now));
 
         // Remove samples outside the window
         self.prune_old_samples();
@@ -659,9 +1149,74 @@ impl RateTracker {
         }
     }
 
+    /// Add a delta to the current one-second bucket, starting a new bucket
+    /// once a second has passed since the current one began
+    fn record_history(&mut self, delta_sent: u64, delta_received: u64, now: Instant) {
+        if let Some(bucket) = self.history.back_mut()
+            && now.saturating_duration_since(bucket.timestamp) < Duration::from_secs(1)
+        {
+            bucket.delta_sent += delta_sent;
+            bucket.delta_received += delta_received;
+            return;
+        }
+
+        if self.history.len() == THROUGHPUT_HISTORY_SECS {
+            self.history.pop_front();
+        }
+        self.history.push_back(RateSample {
+            timestamp: now,
+            delta_sent,
+            delta_received,
+        });
+    }
+
+    /// Average (outgoing, incoming) bytes per second over the `window`
+    /// ending at `now`, at one-second granularity. Windows longer than the
+    /// kept history are cut to it.
+    pub fn throughput_at(&self, now: Instant, window: Duration) -> (f64, f64) {
+        let window = window.min(Duration::from_secs(THROUGHPUT_HISTORY_SECS as u64));
+        if window.is_zero() {
+            return (0.0, 0.0);
+        }
+
+        let cutoff = now.checked_sub(window);
+        let (sent, received) = self
+            .history
+            .iter()
+            .filter(|bucket| cutoff.is_none_or(|cutoff| bucket.timestamp >= cutoff))
+            .fold((0, 0), |(sent, received), bucket| {
+                (sent + bucket.delta_sent, received + bucket.delta_received)
+            });
+        let secs = window.as_secs_f64();
+        (sent as f64 / secs, received as f64 / secs)
+    }
+
+    /// (outgoing, incoming) bytes moved in each of the last
+    /// `THROUGHPUT_HISTORY_SECS` seconds before `now`, oldest first. Seconds
+    /// without traffic are zero.
+    pub fn rate_history_at(&self, now: Instant) -> Vec<(f64, f64)> {
+        let mut history = vec![(0.0, 0.0); THROUGHPUT_HISTORY_SECS];
+        for bucket in &self.history {
+            let age = now.saturating_duration_since(bucket.timestamp).as_secs() as usize;
+            if let Some(slot) = THROUGHPUT_HISTORY_SECS
+                .checked_sub(age + 1)
+                .and_then(|index| history.get_mut(index))
+            {
+                slot.0 += bucket.delta_sent as f64;
+                slot.1 += bucket.delta_received as f64;
+            }
+        }
+        history
+    }
+
     /// Remove samples older than the window duration
     fn prune_old_samples(&mut self) {
-        let cutoff_time = self.last_update - self.window_duration;
+        let Some(cutoff_time) = self
+            .last_update
+            .and_then(|last_update| last_update.checked_sub(self.window_duration))
+        else {
+            return;
+        };
 
         while let Some(oldest) = self.samples.front() {
             if oldest.timestamp < cutoff_time {
@@ -674,16 +1229,26 @@ impl RateTracker {
 
     /// Get the current incoming rate in bytes per second
     pub fn get_incoming_rate_bps(&self) -> f64 {
-        self.calculate_rate_from_deltas(|sample| sample.delta_received)
+        self.incoming_rate_bps_at(Instant::now())
     }
 
     /// Get the current outgoing rate in bytes per second
     pub fn get_outgoing_rate_bps(&self) -> f64 {
-        self.calculate_rate_from_deltas(|sample| sample.delta_sent)
+        self.outgoing_rate_bps_at(Instant::now())
+    }
+
+    /// Incoming rate in bytes per second as of `now`
+    pub fn incoming_rate_bps_at(&self, now: Instant) -> f64 {
+        self.calculate_rate_from_deltas(now, |sample| sample.delta_received)
+    }
+
+    /// Outgoing rate in bytes per second as of `now`
+    pub fn outgoing_rate_bps_at(&self, now: Instant) -> f64 {
+        self.calculate_rate_from_deltas(now, |sample| sample.delta_sent)
     }
 
     /// Calculate rate using delta values for accurate sliding window calculation
-    fn calculate_rate_from_deltas<F>(&self, delta_getter: F) -> f64
+    fn calculate_rate_from_deltas<F>(&self, now: Instant, delta_getter: F) -> f64
     where
         F: Fn(&RateSample) -> u64,
     {
@@ -702,7 +1267,7 @@ impl RateTracker {
         // Calculate the time span of our samples
         let time_span = newest
             .timestamp
-            .duration_since(oldest.timestamp)
+            .saturating_duration_since(oldest.timestamp)
             .as_secs_f64();
 
         // Need at least 100ms of data to avoid division by very small numbers
@@ -722,8 +1287,7 @@ impl RateTracker {
         let base_rate = total_bytes as f64 / time_span;
 
         // Apply time-based decay more gently, similar to iftop's approach
-        let now = Instant::now();
-        let time_since_last_sample = now.duration_since(newest.timestamp).as_secs_f64();
+        let time_since_last_sample = now.saturating_duration_since(newest.timestamp).as_secs_f64();
 
         // More gentle decay - start decay after 3 seconds, fully decay by 10 seconds
         if time_since_last_sample > 10.0 {
@@ -756,12 +1320,53 @@ impl Default for RateTracker {
     }
 }
 
+/// Encapsulation a flow was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelKind {
+    Gre,
+    Vxlan {
+        /// Network identifier of the VXLAN segment
+        vni: u32,
+    },
+}
+
+/// The outermost tunnel a flow's packets were decapsulated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tunnel {
+    pub kind: TunnelKind,
+    /// Source and destination addresses of the outer packet
+    pub outer: (IpAddr, IpAddr),
+}
+
+impl fmt::Display for Tunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TunnelKind::Gre => write!(f, "GRE")?,
+            TunnelKind::Vxlan { vni } => write!(f, "VXLAN {}", vni)?,
+        }
+        write!(f, " {} ↔ {}", self.outer.0, self.outer.1)
+    }
+}
+
+/// How long a flow is kept after its socket disappeared from the system
+/// socket table, for packets still in flight
+pub const SOURCE_GONE_GRACE: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone)]
 pub struct Connection {
     // Core identification
     pub protocol: Protocol,
     pub local_addr: SocketAddr,
     pub remote_addr: SocketAddr,
+    /// Traffic between two other hosts. There's no local side: `local_addr`
+    /// is endpoint A, `remote_addr` endpoint B, and "sent" means A to B.
+    pub forwarded: bool,
+    /// Opened by the remote host, going by the first SYN or, without one,
+    /// by which side has the lower (service) port
+    pub inbound: bool,
+    /// Number of per-port UDP flows folded into this row by the snapshot,
+    /// 0 for a single flow. A folded row's local port is 0.
+    pub subflow_count: usize,
 
     // Protocol state
     pub protocol_state: ProtocolState,
@@ -769,6 +1374,13 @@ pub struct Connection {
     // Process information
     pub pid: Option<u32>,
     pub process_name: Option<String>,
+    /// The owning process's CPU use in percent of one core, and its resident
+    /// memory in bytes, as last sampled
+    pub cpu_usage: Option<f32>,
+    pub memory_usage: Option<u64>,
+    /// Other addresses of this host's end of a multi-homed SCTP association,
+    /// as offered in its INIT or INIT ACK
+    pub alternate_local_addrs: Vec<SocketAddr>,
 
     // Traffic statistics
     pub bytes_sent: u64,
@@ -776,9 +1388,12 @@ pub struct Connection {
     pub packets_sent: u64,
     pub packets_received: u64,
 
-    // Timing
+    // Timing: wall-clock times are for display and export only, every
+    // duration is measured on the monotonic clock
     pub created_at: SystemTime,
     pub last_activity: SystemTime,
+    pub created_instant: Instant,
+    pub last_activity_instant: Instant,
 
     // Service identification
     pub service_name: Option<String>,
@@ -791,13 +1406,69 @@ pub struct Connection {
     #[allow(dead_code)]
     // Legacy rate info - kept for backward compatibility during transition
     pub current_rate_bps: RateInfo,
-    #[allow(dead_code)]
-    // TODO: implement RTT estimation
+    /// Smoothed round-trip time, from TCP timestamps echoed by the peer or,
+    /// without them, from how long segments wait for their ACK
     pub rtt_estimate: Option<Duration>,
+    /// TSval of an outgoing segment waiting to be echoed, and when it was sent
+    pub rtt_probe: Option<(u32, Instant)>,
+    /// End sequence number of an outgoing segment waiting to be acknowledged,
+    /// and when it was sent
+    pub rtt_ack_probe: Option<(u32, Instant)>,
+    /// TCP segments resending sequence space already seen in their direction
+    pub retransmit_count: u64,
+    /// End of the highest sequence space seen going out and coming in
+    pub seq_end_sent: Option<u32>,
+    pub seq_end_received: Option<u32>,
 
     // Backward compatibility fields - updated by rate_tracker
     pub current_incoming_rate_bps: f64,
     pub current_outgoing_rate_bps: f64,
+
+    // Security indicators
+    pub possible_downgrade: bool,
+
+    /// The socket owning this flow was in the system socket table and has
+    /// since disappeared from it
+    pub source_gone: bool,
+
+    /// Peer operating system guessed from its first SYN or SYN-ACK
+    pub remote_os_guess: Option<OsGuess>,
+
+    /// Peer's MAC address, known when it is on the local network
+    pub remote_mac: Option<[u8; 6]>,
+
+    /// 802.1Q VLAN the flow's frames were tagged with
+    pub vlan_id: Option<u16>,
+
+    /// Tunnel the flow was carried in, when tunnels are decapsulated
+    pub tunnel: Option<Tunnel>,
+
+    /// Remote host's name from a reverse lookup, when hostnames are shown
+    pub hostname: Option<String>,
+
+    /// Remote host's location, when a GeoIP database is loaded
+    pub geo_info: Option<GeoInfo>,
+
+    /// The flow's packets are being saved to a pcap file
+    pub recording: bool,
+
+    /// Scan or flood the flow was part of, once its source crossed a threshold
+    pub scan: Option<ScanKind>,
+
+    /// Weaknesses of the TLS handshake, set when DPI results are merged
+    pub tls_warnings: Vec<TlsWarning>,
+
+    /// The flow has carried nothing but keepalive probes for a while: idle,
+    /// but held open
+    pub keepalive_only: bool,
+    /// Keepalive probes since the last packet with payload
+    pub keepalive_probes: u32,
+    pub last_keepalive_probe: Option<Instant>,
+
+    /// Matched an ignore rule: hidden from the connection list and totals
+    pub ignored: bool,
+    /// Scan and brute-force detection skip this flow
+    pub alerts_muted: bool,
 }
 
 impl Connection {
@@ -809,29 +1480,85 @@ impl Connection {
         state: ProtocolState,
     ) -> Self {
         let now = SystemTime::now();
+        let now_instant = Instant::now();
         Self {
             protocol,
             local_addr,
             remote_addr,
+            forwarded: false,
+            inbound: false,
+            subflow_count: 0,
             protocol_state: state,
             pid: None,
             process_name: None,
+            cpu_usage: None,
+            memory_usage: None,
+            alternate_local_addrs: Vec::new(),
             bytes_sent: 0,
             bytes_received: 0,
             packets_sent: 0,
             packets_received: 0,
             created_at: now,
             last_activity: now,
+            created_instant: now_instant,
+            last_activity_instant: now_instant,
             service_name: None,
             dpi_info: None,
             rate_tracker: RateTracker::new(),
             current_rate_bps: RateInfo::default(),
             rtt_estimate: None,
+            rtt_probe: None,
+            rtt_ack_probe: None,
+            retransmit_count: 0,
+            seq_end_sent: None,
+            seq_end_received: None,
             current_incoming_rate_bps: 0.0,
             current_outgoing_rate_bps: 0.0,
+            possible_downgrade: false,
+            source_gone: false,
+            remote_os_guess: None,
+            remote_mac: None,
+            vlan_id: None,
+            tunnel: None,
+            hostname: None,
+            geo_info: None,
+            recording: false,
+            scan: None,
+            tls_warnings: Vec::new(),
+            keepalive_only: false,
+            keepalive_probes: 0,
+            last_keepalive_probe: None,
+            ignored: false,
+            alerts_muted: false,
+        }
+    }
+
+    /// Get the TLS handshake info for HTTPS or QUIC connections
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        match &self.dpi_info.as_ref()?.application {
+            ApplicationProtocol::Https(info) => info.tls_info.as_ref(),
+            ApplicationProtocol::Quic(info) => info.tls_info.as_ref(),
+            _ => None,
         }
     }
 
+    /// Check whether the server negotiated a TLS version deprecated by RFC 8996
+    pub fn uses_deprecated_tls(&self) -> bool {
+        self.tls_info()
+            .is_some_and(|tls| tls.uses_deprecated_version())
+    }
+
+    /// Weaknesses of this flow's TLS handshake. DPI doesn't record which side
+    /// sent the ClientHello, so this host counts as the client when the
+    /// remote end is on the HTTPS port and the local end isn't.
+    pub fn evaluate_tls_warnings(&self, weak_cipher_suites: &HashSet<u16>) -> Vec<TlsWarning> {
+        let client_is_local =
+            !self.forwarded && self.remote_addr.port() == 443 && self.local_addr.port() != 443;
+        self.tls_info()
+            .map(|tls| tls.warnings(client_is_local, weak_cipher_suites))
+            .unwrap_or_default()
+    }
+
     /// Generate a unique key for this connection
     pub fn key(&self) -> String {
         format!(
@@ -842,19 +1569,30 @@ impl Connection {
 
     /// Check if connection is active (had activity in the last minute)
     pub fn is_active(&self) -> bool {
-        self.last_activity.elapsed().unwrap_or_default() < Duration::from_secs(300)
+        self.idle_time() < Duration::from_secs(300)
     }
 
     /// Get the age of the connection
     #[allow(dead_code)]
     pub fn age(&self) -> Duration {
-        self.created_at.elapsed().unwrap_or_default()
+        self.age_at(Instant::now())
+    }
+
+    /// Age of the connection as of `now`, which may be a mapped capture time.
+    /// Zero rather than negative if `now` is earlier.
+    pub fn age_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.created_instant)
     }
 
     /// Get time since last activity
     #[allow(dead_code)]
     pub fn idle_time(&self) -> Duration {
-        self.last_activity.elapsed().unwrap_or_default()
+        self.idle_time_at(Instant::now())
+    }
+
+    /// Time since last activity as of `now`
+    pub fn idle_time_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_activity_instant)
     }
 
     /// Get display state with enhanced UDP/QUIC visibility
@@ -873,12 +1611,13 @@ impl Connection {
                     TcpState::LastAck => "LAST_ACK",
                     TcpState::Closing => "CLOSING",
                     TcpState::Closed => "CLOSED",
+                    TcpState::Reset => "RESET",
                     TcpState::Listen => "LISTEN",
                     TcpState::Unknown => "TCP_UNKNOWN",
                 }
                 .to_string()
             }
-            ProtocolState::Udp => {
+            ProtocolState::Udp(udp_state) => {
                 // Check if it's a DPI-identified protocol
                 if let Some(dpi_info) = &self.dpi_info {
                     match &dpi_info.application {
@@ -914,7 +1653,13 @@ impl Connection {
                         ApplicationProtocol::Http(_) => "HTTP_UDP".to_string(),
                         ApplicationProtocol::Https(_) => "HTTPS_UDP".to_string(),
                         ApplicationProtocol::Ssh(_) => "SSH_UDP".to_string(),
+                        ApplicationProtocol::Custom(info) => {
+                            format!("{}_UDP", info.name.to_uppercase())
+                        }
                     }
+                } else if *udp_state == UdpState::Unconnected {
+                    // Nothing has come back (or nothing has been sent) yet
+                    "UDP_UNCONN".to_string()
                 } else {
                     // Regular UDP without DPI classification
                     // Check activity level to provide more meaningful states
@@ -928,6 +1673,21 @@ impl Connection {
                     }
                 }
             }
+            // ICMPv6 numbers its types differently from ICMPv4
+            ProtocolState::Icmp { icmp_type, .. } if self.remote_addr.is_ipv6() => {
+                match icmp_type {
+                    128 => "ECHO_REQUEST".to_string(),
+                    129 => "ECHO_REPLY".to_string(),
+                    1 => "DEST_UNREACH".to_string(),
+                    2 => "PACKET_TOO_BIG".to_string(),
+                    3 => "TIME_EXCEEDED".to_string(),
+                    133 => "ROUTER_SOLICIT".to_string(),
+                    134 => "ROUTER_ADVERT".to_string(),
+                    135 => "NEIGHBOR_SOLICIT".to_string(),
+                    136 => "NEIGHBOR_ADVERT".to_string(),
+                    _ => "ICMP_OTHER".to_string(),
+                }
+            }
             ProtocolState::Icmp { icmp_type, .. } => match icmp_type {
                 8 => "ECHO_REQUEST".to_string(),
                 0 => "ECHO_REPLY".to_string(),
@@ -939,21 +1699,33 @@ impl Connection {
                 ArpOperation::Request => "ARP_REQUEST".to_string(),
                 ArpOperation::Reply => "ARP_REPLY".to_string(),
             },
+            ProtocolState::Other => {
+                if self.idle_time() > Duration::from_secs(30) {
+                    "IDLE".to_string()
+                } else {
+                    "ACTIVE".to_string()
+                }
+            }
         }
     }
 
     /// Update transfer rates using sliding window calculation
+    #[allow(dead_code)]
     pub fn update_rates(&mut self) {
+        self.update_rates_at(Instant::now());
+    }
+
+    /// Update transfer rates with the byte counts observed at `now`
+    pub fn update_rates_at(&mut self, now: Instant) {
         // Update the rate tracker with current byte counts
         self.rate_tracker
-            .update(self.bytes_sent, self.bytes_received);
+            .update_at(self.bytes_sent, self.bytes_received, now);
 
         // Update backward compatibility fields with smoothed rates
-        self.current_incoming_rate_bps = self.rate_tracker.get_incoming_rate_bps();
-        self.current_outgoing_rate_bps = self.rate_tracker.get_outgoing_rate_bps();
+        self.current_incoming_rate_bps = self.rate_tracker.incoming_rate_bps_at(now);
+        self.current_outgoing_rate_bps = self.rate_tracker.outgoing_rate_bps_at(now);
 
         // Also update the legacy RateInfo struct for any code that still uses it
-        let now = Instant::now();
         self.current_rate_bps = RateInfo {
             incoming_bps: self.current_incoming_rate_bps,
             outgoing_bps: self.current_outgoing_rate_bps,
@@ -961,6 +1733,18 @@ impl Connection {
         };
     }
 
+    /// Average (outgoing, incoming) bytes per second over the trailing
+    /// `window`, e.g. 1, 5 or 30 seconds
+    pub fn throughput_window(&self, window: Duration) -> (f64, f64) {
+        self.rate_tracker.throughput_at(Instant::now(), window)
+    }
+
+    /// (outgoing, incoming) bytes per second over the last minute, oldest
+    /// first
+    pub fn rate_history(&self) -> Vec<(f64, f64)> {
+        self.rate_tracker.rate_history_at(Instant::now())
+    }
+
     /// Refresh rates without adding new data - useful for idle connections
     /// This ensures rates decay to zero when no traffic is flowing
     pub fn refresh_rates(&mut self) {
@@ -978,11 +1762,18 @@ impl Connection {
         };
     }
 
-    /// Get dynamic timeout for this connection based on protocol and state
-    pub fn get_timeout(&self) -> Duration {
+    /// Get dynamic timeout for this connection based on protocol and state,
+    /// capped at `max_idle`. Protocols that already expire sooner, like plain
+    /// UDP and DNS, keep their shorter timeouts.
+    pub fn get_timeout(&self, max_idle: Option<Duration>) -> Duration {
+        let timeout = self.protocol_timeout();
+        max_idle.map_or(timeout, |limit| timeout.min(limit))
+    }
+
+    fn protocol_timeout(&self) -> Duration {
         match &self.protocol_state {
             ProtocolState::Tcp(tcp_state) => self.get_tcp_timeout(tcp_state),
-            ProtocolState::Udp => {
+            ProtocolState::Udp(_) => {
                 if let Some(dpi_info) = &self.dpi_info {
                     match &dpi_info.application {
                         ApplicationProtocol::Quic(quic) => self.get_quic_timeout(quic),
@@ -991,6 +1782,7 @@ impl Connection {
                         ApplicationProtocol::Http(_) => Duration::from_secs(600), // 10 minutes (was 3 min)
                         ApplicationProtocol::Https(_) => Duration::from_secs(600), // 10 minutes (was 3 min)
                         ApplicationProtocol::Ssh(_) => Duration::from_secs(1800), // SSH can be very long-lived (30 min)
+                        ApplicationProtocol::Custom(_) => Duration::from_secs(60),
                     }
                 } else {
                     // Regular UDP without DPI classification
@@ -999,6 +1791,7 @@ impl Connection {
             }
             ProtocolState::Icmp { .. } => Duration::from_secs(10),
             ProtocolState::Arp { .. } => Duration::from_secs(30),
+            ProtocolState::Other => Duration::from_secs(60),
         }
     }
 
@@ -1028,7 +1821,7 @@ impl Connection {
                 }
             }
             TcpState::TimeWait => Duration::from_secs(30), // Standard TCP TIME_WAIT
-            TcpState::Closed => Duration::from_secs(5),    // Quick cleanup for closed
+            TcpState::Closed | TcpState::Reset => Duration::from_secs(5), // Quick cleanup for closed
             TcpState::FinWait1 | TcpState::FinWait2 => Duration::from_secs(60), // Allow for proper close sequence
             TcpState::CloseWait | TcpState::LastAck => Duration::from_secs(60),
             TcpState::SynSent | TcpState::SynReceived => Duration::from_secs(60), // Connection establishment
@@ -1072,10 +1865,34 @@ impl Connection {
         }
     }
 
-    /// Check if this connection should be cleaned up based on its timeout
-    pub fn should_cleanup(&self, now: SystemTime) -> bool {
-        let timeout = self.get_timeout();
-        now.duration_since(self.last_activity).unwrap_or_default() > timeout
+    /// Check if this connection should be cleaned up based on its timeout.
+    /// A flow whose socket is gone only waits for stragglers to pass.
+    pub fn should_cleanup(&self, now: Instant, max_idle: Option<Duration>) -> bool {
+        let timeout = if self.close_reason() == CloseReason::SourceGone {
+            SOURCE_GONE_GRACE.min(self.get_timeout(max_idle))
+        } else {
+            self.get_timeout(max_idle)
+        };
+        self.idle_time_at(now) > timeout
+    }
+
+    /// Why the connection would be closed if it were cleaned up now. The
+    /// packets seen take precedence over the socket table.
+    pub fn close_reason(&self) -> CloseReason {
+        match self.protocol_state {
+            ProtocolState::Tcp(TcpState::Reset) => CloseReason::Reset,
+            ProtocolState::Tcp(
+                TcpState::FinWait1
+                | TcpState::FinWait2
+                | TcpState::CloseWait
+                | TcpState::LastAck
+                | TcpState::Closing
+                | TcpState::TimeWait
+                | TcpState::Closed,
+            ) => CloseReason::FinClosed,
+            _ if self.source_gone => CloseReason::SourceGone,
+            _ => CloseReason::IdleTimeout,
+        }
     }
 
     /// Get the staleness level as a percentage (0.0 to 1.0+)
@@ -1084,8 +1901,8 @@ impl Connection {
     /// - 0.75 = at warning threshold
     /// - 1.0 = will be cleaned up
     /// - >1.0 = should have been cleaned up already
-    pub fn staleness_ratio(&self) -> f32 {
-        let timeout = self.get_timeout();
+    pub fn staleness_ratio(&self, max_idle: Option<Duration>) -> f32 {
+        let timeout = self.get_timeout(max_idle);
         let idle = self.idle_time();
 
         idle.as_secs_f32() / timeout.as_secs_f32()
@@ -1122,7 +1939,7 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // First update establishes baseline
-        tracker.update(1000, 500);
+        tracker.update_at(1000, 500, Instant::now());
         assert_eq!(tracker.get_incoming_rate_bps(), 0.0);
         assert_eq!(tracker.get_outgoing_rate_bps(), 0.0);
         // Test single update - need at least 2 samples for rate
@@ -1133,11 +1950,11 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // Add initial sample
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
         thread::sleep(Duration::from_millis(200)); // Wait 200ms
 
         // Add second sample - 1000 bytes sent, 500 received over ~0.2 seconds
-        tracker.update(1000, 500);
+        tracker.update_at(1000, 500, Instant::now());
 
         let outgoing_rate = tracker.get_outgoing_rate_bps();
         let incoming_rate = tracker.get_incoming_rate_bps();
@@ -1160,12 +1977,12 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // Simulate steady transfer over time
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
 
         // Add samples every 100ms
         for i in 1..=5 {
             thread::sleep(Duration::from_millis(100));
-            tracker.update(i * 1000, i * 500); // 1000 bytes/100ms = 10KB/s, 500 bytes/100ms = 5KB/s
+            tracker.update_at(i * 1000, i * 500, Instant::now()); // 1000 bytes/100ms = 10KB/s, 500 bytes/100ms = 5KB/s
         }
 
         let outgoing_rate = tracker.get_outgoing_rate_bps();
@@ -1184,19 +2001,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_throughput_over_trailing_windows() {
+        let start = Instant::now();
+        let mut tracker = RateTracker::new();
+
+        // 10 KB/s out and 1 KB/s in for 20 seconds, then 40 s at 100 KB/s in,
+        // with a packet every half second
+        let (mut sent, mut received) = (0, 0);
+        for tick in 1..=120u64 {
+            if tick <= 40 {
+                sent += 5_000;
+                received += 500;
+            } else {
+                received += 50_000;
+            }
+            tracker.update_at(sent, received, start + Duration::from_millis(tick * 500));
+        }
+        let now = start + Duration::from_secs(60);
+
+        assert_eq!(
+            tracker.throughput_at(now, Duration::from_secs(5)),
+            (0.0, 100_000.0)
+        );
+        assert_eq!(
+            tracker.throughput_at(now, Duration::from_secs(30)),
+            (0.0, 100_000.0)
+        );
+        // Windows reaching back past the history see only what it holds
+        let (out_avg, in_avg) = tracker.throughput_at(now, Duration::from_secs(600));
+        assert!(out_avg > 0.0 && out_avg < 10_000.0, "{out_avg}");
+        assert!(in_avg > 60_000.0 && in_avg < 70_000.0, "{in_avg}");
+        assert_eq!(tracker.history.len(), THROUGHPUT_HISTORY_SECS);
+
+        // Nothing moved in the last second after a quiet spell
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            tracker.throughput_at(later, Duration::from_secs(1)),
+            (0.0, 0.0)
+        );
+        assert_eq!(tracker.throughput_at(later, Duration::ZERO), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rate_history_has_one_sample_per_second() {
+        let mut tracker = RateTracker::new();
+        let start = Instant::now();
+        tracker.update_at(1_000, 0, start);
+        tracker.update_at(1_500, 200, start + Duration::from_millis(400));
+        // Two quiet seconds, then incoming traffic
+        tracker.update_at(1_500, 5_200, start + Duration::from_millis(3_100));
+
+        let history = tracker.rate_history_at(start + Duration::from_millis(3_500));
+        assert_eq!(history.len(), THROUGHPUT_HISTORY_SECS);
+        assert_eq!(
+            history[THROUGHPUT_HISTORY_SECS - 4..],
+            [(1_500.0, 200.0), (0.0, 0.0), (0.0, 0.0), (0.0, 5_000.0)]
+        );
+        assert!(
+            history[..THROUGHPUT_HISTORY_SECS - 4]
+                .iter()
+                .all(|&rate| rate == (0.0, 0.0))
+        );
+
+        // A minute later the samples have scrolled out
+        let later = tracker.rate_history_at(start + Duration::from_secs(70));
+        assert!(later.iter().all(|&rate| rate == (0.0, 0.0)));
+    }
+
     #[test]
     fn test_rate_tracker_window_pruning() {
         let window_duration = Duration::from_millis(300);
         let mut tracker = RateTracker::with_window_duration(window_duration);
 
         // Add samples that will be pruned
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
         thread::sleep(Duration::from_millis(100));
-        tracker.update(1000, 500);
+        tracker.update_at(1000, 500, Instant::now());
 
         // Wait for samples to age out of the window
         thread::sleep(Duration::from_millis(400));
-        tracker.update(2000, 1000);
+        tracker.update_at(2000, 1000, Instant::now());
 
         // Should only consider recent samples (rate based on last sample interval)
         let rate = tracker.get_outgoing_rate_bps();
@@ -1235,7 +2120,7 @@ mod tests {
         // Add more samples than the max limit
         for i in 0..150 {
             // More than max_samples (100)
-            tracker.update(i * 100, i * 50);
+            tracker.update_at(i * 100, i * 50, Instant::now());
             thread::sleep(Duration::from_millis(1)); // Small delay to ensure different timestamps
         }
 
@@ -1254,18 +2139,18 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // Initial state
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
         thread::sleep(Duration::from_millis(100));
 
         // Burst of traffic
-        tracker.update(10000, 5000);
+        tracker.update_at(10000, 5000, Instant::now());
         thread::sleep(Duration::from_millis(100));
 
         // No more traffic (same byte counts)
-        tracker.update(10000, 5000);
+        tracker.update_at(10000, 5000, Instant::now());
         thread::sleep(Duration::from_millis(100));
 
-        tracker.update(10000, 5000);
+        tracker.update_at(10000, 5000, Instant::now());
 
         // Rate should be averaged over the entire window, so lower than instantaneous burst
         let outgoing_rate = tracker.get_outgoing_rate_bps();
@@ -1291,8 +2176,8 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // Add two samples with identical or very close timestamps
-        tracker.update(0, 0);
-        tracker.update(1000, 500); // Immediately after, should be < 100ms apart
+        tracker.update_at(0, 0, Instant::now());
+        tracker.update_at(1000, 500, Instant::now()); // Immediately after, should be < 100ms apart
 
         // Should return 0 to avoid division by very small numbers
         assert_eq!(tracker.get_outgoing_rate_bps(), 0.0);
@@ -1306,13 +2191,13 @@ mod tests {
 
         // Simulate a connection that has been running for a while
         // with cumulative byte counts
-        tracker.update(1_000_000, 500_000); // 1MB sent, 500KB received total
+        tracker.update_at(1_000_000, 500_000, Instant::now()); // 1MB sent, 500KB received total
         thread::sleep(Duration::from_millis(100));
 
-        tracker.update(1_100_000, 550_000); // 100KB more sent, 50KB more received
+        tracker.update_at(1_100_000, 550_000, Instant::now()); // 100KB more sent, 50KB more received
         thread::sleep(Duration::from_millis(100));
 
-        tracker.update(1_200_000, 600_000); // 100KB more sent, 50KB more received
+        tracker.update_at(1_200_000, 600_000, Instant::now()); // 100KB more sent, 50KB more received
 
         // The rate should be based on the deltas, not the cumulative values
         // We sent 200KB in ~200ms = ~1MB/s, received 100KB in ~200ms = ~500KB/s
@@ -1341,20 +2226,20 @@ mod tests {
         let mut tracker = RateTracker::with_window_duration(window_duration);
 
         // Add initial samples
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
         thread::sleep(Duration::from_millis(100));
-        tracker.update(100_000, 50_000); // 100KB sent, 50KB received
+        tracker.update_at(100_000, 50_000, Instant::now()); // 100KB sent, 50KB received
 
         thread::sleep(Duration::from_millis(100));
-        tracker.update(200_000, 100_000); // Another 100KB sent, 50KB received
+        tracker.update_at(200_000, 100_000, Instant::now()); // Another 100KB sent, 50KB received
 
         // Wait for window to slide past first samples
         thread::sleep(Duration::from_millis(600));
 
         // Add new samples with same rate
-        tracker.update(300_000, 150_000); // Another 100KB sent, 50KB received
+        tracker.update_at(300_000, 150_000, Instant::now()); // Another 100KB sent, 50KB received
         thread::sleep(Duration::from_millis(100));
-        tracker.update(400_000, 200_000); // Another 100KB sent, 50KB received
+        tracker.update_at(400_000, 200_000, Instant::now()); // Another 100KB sent, 50KB received
 
         // Rate should still be consistent despite window sliding
         let outgoing_rate = tracker.get_outgoing_rate_bps();
@@ -1379,9 +2264,9 @@ mod tests {
         let mut tracker = RateTracker::new();
 
         // Simulate active traffic
-        tracker.update(0, 0);
+        tracker.update_at(0, 0, Instant::now());
         thread::sleep(Duration::from_millis(100));
-        tracker.update(100_000, 50_000); // 100KB sent, 50KB received
+        tracker.update_at(100_000, 50_000, Instant::now()); // 100KB sent, 50KB received
 
         // Should have non-zero rate immediately after traffic
         let initial_out = tracker.get_outgoing_rate_bps();
@@ -1509,7 +2394,7 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 443),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         // Test QUIC with different states
@@ -1520,6 +2405,7 @@ mod tests {
             application: ApplicationProtocol::Quic(Box::new(quic_info.clone())),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         };
         conn.dpi_info = Some(dpi_info);
 
@@ -1532,6 +2418,7 @@ mod tests {
             application: ApplicationProtocol::Quic(Box::new(quic_connected)),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
         assert_eq!(conn.state(), "QUIC_CONNECTED");
 
@@ -1542,6 +2429,7 @@ mod tests {
             application: ApplicationProtocol::Quic(Box::new(quic_draining)),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
         assert_eq!(conn.state(), "QUIC_DRAINING");
     }
@@ -1552,7 +2440,7 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         // Test DNS query
@@ -1561,12 +2449,16 @@ mod tests {
             query_type: Some(DnsQueryType::A),
             response_ips: vec![],
             is_response: false,
+            rcode: None,
+            truncated: false,
+            ttl: None,
         };
 
         conn.dpi_info = Some(DpiInfo {
             application: ApplicationProtocol::Dns(dns_query),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
         assert_eq!(conn.state(), "DNS_QUERY");
 
@@ -1576,12 +2468,16 @@ mod tests {
             query_type: Some(DnsQueryType::A),
             response_ips: vec!["93.184.216.34".parse().unwrap()],
             is_response: true,
+            rcode: Some(DnsResponseCode::NoError),
+            truncated: false,
+            ttl: None,
         };
 
         conn.dpi_info = Some(DpiInfo {
             application: ApplicationProtocol::Dns(dns_response),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
         assert_eq!(conn.state(), "DNS_RESPONSE");
     }
@@ -1592,18 +2488,23 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 8080),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         // No DPI info - should show activity-based state
         assert_eq!(conn.state(), "UDP_ACTIVE"); // Fresh connection
 
         // Simulate aging the connection
-        conn.last_activity = SystemTime::now() - Duration::from_secs(45);
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(45);
         assert_eq!(conn.state(), "UDP_IDLE"); // Idle but not stale
 
-        conn.last_activity = SystemTime::now() - Duration::from_secs(90);
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(90);
         assert_eq!(conn.state(), "UDP_STALE"); // Stale connection
+
+        // One-way flows are unconnected however recent they are
+        conn.protocol_state = ProtocolState::Udp(UdpState::Unconnected);
+        conn.last_activity_instant = Instant::now();
+        assert_eq!(conn.state(), "UDP_UNCONN");
     }
 
     #[test]
@@ -1612,19 +2513,19 @@ mod tests {
 
         // Test established connection timeout (updated from 300s to 600s)
         conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
-        assert_eq!(conn.get_timeout(), Duration::from_secs(600)); // Active established (was 300)
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(600)); // Active established (was 300)
 
         // Test idle established connection (updated from 180s to 300s)
-        conn.last_activity = SystemTime::now() - Duration::from_secs(120);
-        assert_eq!(conn.get_timeout(), Duration::from_secs(300)); // Idle established (was 180)
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(120);
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(300)); // Idle established (was 180)
 
         // Test TIME_WAIT
         conn.protocol_state = ProtocolState::Tcp(TcpState::TimeWait);
-        assert_eq!(conn.get_timeout(), Duration::from_secs(30));
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(30));
 
         // Test closed connections
         conn.protocol_state = ProtocolState::Tcp(TcpState::Closed);
-        assert_eq!(conn.get_timeout(), Duration::from_secs(5));
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(5));
     }
 
     #[test]
@@ -1633,7 +2534,7 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 443),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         // Test QUIC with CONNECTION_CLOSE frame
@@ -1649,9 +2550,10 @@ mod tests {
             application: ApplicationProtocol::Quic(Box::new(quic_info)),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
 
-        assert_eq!(conn.get_timeout(), Duration::from_secs(10)); // Draining period
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(10)); // Draining period
 
         // Test application close
         let mut quic_app_close = QuicInfo::new(0x00000001);
@@ -1666,9 +2568,10 @@ mod tests {
             application: ApplicationProtocol::Quic(Box::new(quic_app_close)),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
 
-        assert_eq!(conn.get_timeout(), Duration::from_secs(1)); // Immediate cleanup
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(1)); // Immediate cleanup
     }
 
     #[test]
@@ -1677,7 +2580,7 @@ mod tests {
             Protocol::UDP,
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
-            ProtocolState::Udp,
+            ProtocolState::Udp(UdpState::Active),
         );
 
         let dns_info = DnsInfo {
@@ -1685,38 +2588,131 @@ mod tests {
             query_type: Some(DnsQueryType::A),
             response_ips: vec![],
             is_response: false,
+            rcode: None,
+            truncated: false,
+            ttl: None,
         };
 
         conn.dpi_info = Some(DpiInfo {
             application: ApplicationProtocol::Dns(dns_info),
             first_packet_time: Instant::now(),
             last_update_time: Instant::now(),
+            pending_request_at: None,
         });
 
-        assert_eq!(conn.get_timeout(), Duration::from_secs(30)); // Short timeout for DNS
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(30)); // Short timeout for DNS
     }
 
     #[test]
     fn test_should_cleanup() {
         let mut conn = create_test_connection();
-        let now = SystemTime::now();
+        let now = Instant::now();
 
         // Fresh connection should not be cleaned up
-        assert!(!conn.should_cleanup(now));
+        assert!(!conn.should_cleanup(now, None));
 
         // Test TCP closed connection cleanup
         conn.protocol_state = ProtocolState::Tcp(TcpState::Closed);
-        conn.last_activity = now - Duration::from_secs(10); // Beyond 5s timeout for closed
-        assert!(conn.should_cleanup(now));
+        conn.last_activity_instant = now - Duration::from_secs(10); // Beyond 5s timeout for closed
+        assert!(conn.should_cleanup(now, None));
 
         // Test established connection within timeout (updated timeout from 300s to 600s)
         conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
-        conn.last_activity = now - Duration::from_secs(100); // Within 600s timeout
-        assert!(!conn.should_cleanup(now));
+        conn.last_activity_instant = now - Duration::from_secs(100); // Within 600s timeout
+        assert!(!conn.should_cleanup(now, None));
 
         // Test established connection beyond timeout (updated timeout to 600s)
-        conn.last_activity = now - Duration::from_secs(700); // Beyond 600s timeout
-        assert!(conn.should_cleanup(now));
+        conn.last_activity_instant = now - Duration::from_secs(700); // Beyond 600s timeout
+        assert!(conn.should_cleanup(now, None));
+    }
+
+    #[test]
+    fn test_max_idle_caps_longer_timeouts() {
+        let now = Instant::now();
+        let max_idle = Some(Duration::from_secs(120));
+
+        // An established TCP flow goes at the cap instead of after 600s
+        let mut tcp = create_test_connection();
+        tcp.protocol_state = ProtocolState::Tcp(TcpState::Established);
+        tcp.last_activity_instant = now - Duration::from_secs(150);
+        assert_eq!(tcp.get_timeout(max_idle), Duration::from_secs(120));
+        assert!(tcp.should_cleanup(now, max_idle));
+        assert!(!tcp.should_cleanup(now, None));
+
+        // Plain UDP and DNS already expire sooner and keep their timeouts
+        let mut udp = Connection::new(
+            Protocol::UDP,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12345),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53),
+            ProtocolState::Udp(UdpState::Active),
+        );
+        assert_eq!(udp.get_timeout(max_idle), Duration::from_secs(60));
+        udp.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Dns(DnsInfo {
+                query_name: Some("example.com".to_string()),
+                query_type: Some(DnsQueryType::A),
+                response_ips: vec![],
+                is_response: false,
+                rcode: None,
+                truncated: false,
+                ttl: None,
+            }),
+            first_packet_time: now,
+            last_update_time: now,
+            pending_request_at: None,
+        });
+        assert_eq!(udp.get_timeout(max_idle), Duration::from_secs(30));
+        udp.last_activity_instant = now - Duration::from_secs(45);
+        assert!(udp.should_cleanup(now, max_idle));
+    }
+
+    #[test]
+    fn test_close_reason() {
+        let mut conn = create_test_connection();
+        let now = Instant::now();
+        assert_eq!(conn.close_reason(), CloseReason::IdleTimeout);
+
+        conn.protocol_state = ProtocolState::Tcp(TcpState::TimeWait);
+        assert_eq!(conn.close_reason(), CloseReason::FinClosed);
+        conn.protocol_state = ProtocolState::Tcp(TcpState::Reset);
+        assert_eq!(conn.close_reason(), CloseReason::Reset);
+
+        // The packets seen win over the socket table
+        conn.source_gone = true;
+        assert_eq!(conn.close_reason(), CloseReason::Reset);
+
+        // A gone socket only waits out the grace period
+        conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
+        assert_eq!(conn.close_reason(), CloseReason::SourceGone);
+        assert_eq!(conn.close_reason().to_string(), "SOCKET_GONE");
+        conn.last_activity_instant = now - Duration::from_secs(5);
+        assert!(!conn.should_cleanup(now, None));
+        conn.last_activity_instant = now - SOURCE_GONE_GRACE - Duration::from_secs(1);
+        assert!(conn.should_cleanup(now, None));
+    }
+
+    #[test]
+    fn test_wall_clock_steps_do_not_affect_timing() {
+        let mut conn = create_test_connection();
+        conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
+
+        // The wall clock was stepped back an hour after the last packet
+        conn.last_activity = SystemTime::now() + Duration::from_secs(3600);
+        assert!(conn.is_active());
+        assert!(conn.idle_time() < Duration::from_secs(1));
+
+        // ...or forward a day
+        conn.last_activity = SystemTime::now() - Duration::from_secs(86_400);
+        conn.created_at = conn.last_activity;
+        assert!(conn.is_active());
+        assert!(conn.age() < Duration::from_secs(1));
+        assert!(!conn.should_cleanup(Instant::now(), None));
+        assert_eq!(conn.state(), "ESTABLISHED");
+
+        // A monotonic time before the last packet reads as zero, not a panic
+        let earlier = conn.last_activity_instant - Duration::from_secs(5);
+        assert_eq!(conn.idle_time_at(earlier), Duration::ZERO);
+        assert_eq!(conn.age_at(earlier), Duration::ZERO);
     }
 
     #[test]
@@ -1725,12 +2721,12 @@ mod tests {
         conn.protocol_state = ProtocolState::Tcp(TcpState::Established);
 
         // Fresh connection - staleness ratio near 0
-        let ratio = conn.staleness_ratio();
+        let ratio = conn.staleness_ratio(None);
         assert!(ratio < 0.05, "Fresh connection should have low staleness ratio");
 
         // At 50% of timeout (300s total for idle, 150s elapsed)
-        conn.last_activity = SystemTime::now() - Duration::from_secs(150);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(150);
+        let ratio = conn.staleness_ratio(None);
         assert!(
             (ratio - 0.5).abs() < 0.1,
             "Staleness ratio should be around 0.5, got {}",
@@ -1738,8 +2734,8 @@ mod tests {
         );
 
         // At 75% of timeout (warning threshold) - 225s
-        conn.last_activity = SystemTime::now() - Duration::from_secs(225);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(225);
+        let ratio = conn.staleness_ratio(None);
         assert!(
             ratio >= 0.75,
             "Staleness ratio should be >= 0.75 at warning threshold, got {}",
@@ -1747,8 +2743,8 @@ mod tests {
         );
 
         // At 90% of timeout (critical threshold) - 270s
-        conn.last_activity = SystemTime::now() - Duration::from_secs(270);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(270);
+        let ratio = conn.staleness_ratio(None);
         assert!(
             ratio >= 0.90,
             "Staleness ratio should be >= 0.90 at critical threshold, got {}",
@@ -1756,8 +2752,8 @@ mod tests {
         );
 
         // Beyond timeout - 350s (beyond 300s timeout)
-        conn.last_activity = SystemTime::now() - Duration::from_secs(350);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(350);
+        let ratio = conn.staleness_ratio(None);
         assert!(
             ratio > 1.0,
             "Staleness ratio should exceed 1.0 beyond timeout, got {}",
@@ -1772,16 +2768,16 @@ mod tests {
         conn.protocol_state = ProtocolState::Tcp(TcpState::TimeWait);
 
         // At 75% of 30s = 22.5s
-        conn.last_activity = SystemTime::now() - Duration::from_secs(23);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(23);
+        let ratio = conn.staleness_ratio(None);
         assert!(ratio >= 0.75, "TIME_WAIT connection should be stale at 23s, ratio: {}", ratio);
 
         // Test CLOSED (5s timeout)
         conn.protocol_state = ProtocolState::Tcp(TcpState::Closed);
 
         // At 75% of 5s = 3.75s
-        conn.last_activity = SystemTime::now() - Duration::from_secs(4);
-        let ratio = conn.staleness_ratio();
+        conn.last_activity_instant = Instant::now() - Duration::from_secs(4);
+        let ratio = conn.staleness_ratio(None);
         assert!(ratio >= 0.75, "CLOSED connection should be stale at 4s, ratio: {}", ratio);
     }
 
@@ -1799,7 +2795,24 @@ mod tests {
         );
 
         assert_eq!(conn.state(), "ECHO_REQUEST");
-        assert_eq!(conn.get_timeout(), Duration::from_secs(10));
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(10));
+
+        // ICMPv6 types are told apart by address family
+        let mut icmpv6 = Connection::new(
+            Protocol::ICMP,
+            "[fe80::10]:0".parse().unwrap(),
+            "[fe80::1]:0".parse().unwrap(),
+            ProtocolState::Icmp {
+                icmp_type: 3,
+                icmp_code: 0,
+            },
+        );
+        assert_eq!(icmpv6.state(), "TIME_EXCEEDED");
+        icmpv6.protocol_state = ProtocolState::Icmp {
+            icmp_type: 134,
+            icmp_code: 0,
+        };
+        assert_eq!(icmpv6.state(), "ROUTER_ADVERT");
 
         // Test ARP states
         conn.protocol = Protocol::ARP;
@@ -1807,6 +2820,299 @@ mod tests {
             operation: ArpOperation::Request,
         };
         assert_eq!(conn.state(), "ARP_REQUEST");
-        assert_eq!(conn.get_timeout(), Duration::from_secs(30));
+        assert_eq!(conn.get_timeout(None), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_tls_downgrade_detection() {
+        let mut tls = TlsInfo::new();
+        tls.client_max_tls_version = Some(TlsVersion::Tls13);
+
+        // TLS 1.3 -> TLS 1.2 is a normal fallback, not a downgrade
+        tls.server_negotiated_version = Some(TlsVersion::Tls12);
+        assert!(!tls.is_possible_downgrade());
+        assert!(!tls.uses_deprecated_version());
+
+        // TLS 1.3 -> TLS 1.1 skips more than one version
+        tls.server_negotiated_version = Some(TlsVersion::Tls11);
+        assert!(tls.is_possible_downgrade());
+        assert!(tls.uses_deprecated_version());
+
+        // Missing either side never flags a downgrade
+        tls.client_max_tls_version = None;
+        assert!(!tls.is_possible_downgrade());
+    }
+
+    #[test]
+    fn test_connection_deprecated_tls() {
+        let mut conn = create_test_connection();
+        assert!(conn.tls_info().is_none());
+        assert!(!conn.uses_deprecated_tls());
+
+        let mut tls = TlsInfo::new();
+        tls.server_negotiated_version = Some(TlsVersion::Tls10);
+        conn.dpi_info = Some(DpiInfo {
+            application: ApplicationProtocol::Https(HttpsInfo {
+                tls_info: Some(tls),
+                handshake: None,
+            }),
+            first_packet_time: Instant::now(),
+            last_update_time: Instant::now(),
+            pending_request_at: None,
+        });
+        assert!(conn.uses_deprecated_tls());
+        assert!(TlsVersion::Tls13 > TlsVersion::Tls12);
+    }
+
+    /// A completed modern handshake with a server name
+    fn good_tls() -> TlsInfo {
+        let mut tls = TlsInfo::new();
+        tls.sni = Some("example.com".to_string());
+        tls.client_max_tls_version = Some(TlsVersion::Tls13);
+        tls.server_negotiated_version = Some(TlsVersion::Tls13);
+        tls.cipher_suite = Some(0x1301);
+        tls
+    }
+
+    #[test]
+    fn test_tls_warnings() {
+        use crate::network::dpi::CipherWeakness;
+        let none = HashSet::new();
+
+        assert!(good_tls().warnings(true, &none).is_empty());
+
+        let mut tls = good_tls();
+        tls.server_negotiated_version = Some(TlsVersion::Tls11);
+        assert_eq!(
+            tls.warnings(true, &none),
+            vec![TlsWarning::DeprecatedVersion(TlsVersion::Tls11)]
+        );
+        tls.server_negotiated_version = Some(TlsVersion::Tls12);
+        assert!(tls.warnings(true, &none).is_empty());
+
+        for (code, weakness) in [
+            (0x0003, CipherWeakness::Export),
+            (0x0001, CipherWeakness::Null),
+            (0x0005, CipherWeakness::Rc4),
+            (0xc012, CipherWeakness::TripleDes),
+        ] {
+            let mut tls = good_tls();
+            tls.cipher_suite = Some(code);
+            assert_eq!(
+                tls.warnings(true, &none),
+                vec![TlsWarning::WeakCipher(code, weakness)]
+            );
+        }
+
+        // CBC suites are only flagged when a site policy bans them
+        let mut tls = good_tls();
+        tls.cipher_suite = Some(0xc014);
+        assert!(tls.warnings(true, &none).is_empty());
+        assert_eq!(
+            tls.warnings(true, &HashSet::from([0xc014])),
+            vec![TlsWarning::WeakCipher(0xc014, CipherWeakness::Configured)]
+        );
+
+        let mut tls = good_tls();
+        tls.sni = None;
+        assert_eq!(tls.warnings(true, &none), vec![TlsWarning::MissingSni]);
+        // Clients connecting to us are not our concern
+        assert!(tls.warnings(false, &none).is_empty());
+        // Nor is a flow whose ClientHello was never seen
+        tls.client_max_tls_version = None;
+        assert!(tls.warnings(true, &none).is_empty());
+    }
+
+    #[test]
+    fn test_missing_sni_only_on_outbound_https() {
+        let mut tls = good_tls();
+        tls.sni = None;
+        let https = |local: &str, remote: &str| {
+            let mut conn = Connection::new(
+                Protocol::TCP,
+                local.parse().unwrap(),
+                remote.parse().unwrap(),
+                ProtocolState::Tcp(TcpState::Established),
+            );
+            conn.dpi_info = Some(DpiInfo {
+                application: ApplicationProtocol::Https(HttpsInfo {
+                    tls_info: Some(tls.clone()),
+                    handshake: None,
+                }),
+                first_packet_time: Instant::now(),
+                last_update_time: Instant::now(),
+                pending_request_at: None,
+            });
+            conn
+        };
+
+        let outbound = https("192.168.1.10:50000", "203.0.113.5:443");
+        assert_eq!(
+            outbound.evaluate_tls_warnings(&HashSet::new()),
+            vec![TlsWarning::MissingSni]
+        );
+        let inbound = https("192.168.1.10:443", "203.0.113.5:50000");
+        assert!(inbound.evaluate_tls_warnings(&HashSet::new()).is_empty());
+        assert!(
+            create_test_connection()
+                .evaluate_tls_warnings(&HashSet::new())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_reassembly_budget_is_never_exceeded() {
+        // Many half-open handshakes each buffering 60KB against a 1MB budget
+        let counter = AtomicUsize::new(0);
+        let budget = 1024 * 1024;
+        let accepted = (0..1000)
+            .filter(|_| reserve_bytes(&counter, budget, 60 * 1024))
+            .count();
+
+        assert_eq!(accepted, budget / (60 * 1024));
+        assert!(counter.load(Ordering::Relaxed) <= budget);
+        assert!(!reserve_bytes(&counter, budget, 60 * 1024));
+        assert!(reserve_bytes(&counter, budget, 1024));
+    }
+
+    /// Reassembler holding `ranges` of a byte stream where byte `i` is `i as u8`
+    fn reassembler_with(ranges: &[(u64, u64)]) -> CryptoFrameReassembler {
+        let mut reassembler = CryptoFrameReassembler::new();
+        for &(start, end) in ranges {
+            let data = (start..end).map(|i| i as u8).collect();
+            reassembler.add_fragment(start, data).unwrap();
+        }
+        reassembler
+    }
+
+    fn stream(len: u64) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn test_overlapping_crypto_fragments() {
+        // (fragments in arrival order, contiguous bytes, buffered bytes)
+        type OverlapCase = (&'static [(u64, u64)], u64, usize);
+        let cases: [OverlapCase; 5] = [
+            // Retransmission extending what we have
+            (&[(0, 1000), (0, 1200)], 1200, 1200),
+            // Overlapping prefix
+            (&[(500, 1200), (0, 800)], 1200, 1200),
+            // Overlapping suffix
+            (&[(0, 800), (500, 1200)], 1200, 1200),
+            // Fully containing an existing fragment
+            (&[(400, 600), (0, 1000)], 1000, 1000),
+            // Gap later filled by a retransmission spanning both sides
+            (&[(0, 500), (800, 1200), (300, 1000)], 1200, 1200),
+        ];
+
+        for (ranges, contiguous, buffered) in cases {
+            let reassembler = reassembler_with(ranges);
+            assert_eq!(
+                reassembler.get_contiguous_data(),
+                Some(stream(contiguous)),
+                "{:?}",
+                ranges
+            );
+            assert_eq!(reassembler.buffered_bytes(), buffered, "{:?}", ranges);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_crypto_fragment_keeps_first_write() {
+        let mut reassembler = reassembler_with(&[(0, 100)]);
+        reassembler.add_fragment(50, vec![0xff; 100]).unwrap();
+        // Duplicates and fragments inside stored data add nothing
+        reassembler.add_fragment(0, stream(100)).unwrap();
+        reassembler.add_fragment(10, vec![0xee; 20]).unwrap();
+
+        let mut expected = stream(100);
+        expected.extend_from_slice(&[0xff; 50]);
+        assert_eq!(reassembler.get_contiguous_data(), Some(expected));
+        assert_eq!(reassembler.buffered_bytes(), 150);
+    }
+
+    #[test]
+    fn test_out_of_order_crypto_fragments_are_readable_as_they_arrive() {
+        let mut reassembler = reassembler_with(&[(600, 900), (200, 400)]);
+        assert_eq!(reassembler.get_contiguous_data_up_to(100), None);
+
+        reassembler.add_fragment(0, stream(300)).unwrap();
+        assert_eq!(reassembler.get_contiguous_data_up_to(4), Some(stream(4)));
+        assert_eq!(
+            reassembler.get_contiguous_data_up_to(250),
+            Some(stream(250))
+        );
+        // Only up to the gap at 400
+        assert_eq!(
+            reassembler.get_contiguous_data_up_to(700),
+            Some(stream(400))
+        );
+
+        reassembler
+            .add_fragment(350, stream(650)[350..].to_vec())
+            .unwrap();
+        assert_eq!(reassembler.get_contiguous_data(), Some(stream(900)));
+        assert_eq!(
+            reassembler.get_contiguous_data_up_to(650),
+            Some(stream(650))
+        );
+        assert_eq!(reassembler.buffered_bytes(), 900);
+    }
+
+    #[test]
+    fn test_reassembler_clones_share_fragments_until_written() {
+        let mut reassembler = reassembler_with(&[(0, 500)]);
+        let snapshot = reassembler.clone();
+        assert!(Arc::ptr_eq(&reassembler.fragments, &snapshot.fragments));
+
+        reassembler
+            .add_fragment(500, stream(800)[500..].to_vec())
+            .unwrap();
+        assert!(!Arc::ptr_eq(&reassembler.fragments, &snapshot.fragments));
+        assert_eq!(reassembler.get_contiguous_data(), Some(stream(800)));
+        assert_eq!(snapshot.get_contiguous_data(), Some(stream(500)));
+        assert_eq!(snapshot.buffered_bytes(), 500);
+
+        // Once the clone is gone the fragments are written in place
+        drop(snapshot);
+        let fragments = Arc::as_ptr(&reassembler.fragments);
+        reassembler.add_fragment(800, vec![0; 100]).unwrap();
+        assert_eq!(Arc::as_ptr(&reassembler.fragments), fragments);
+    }
+
+    #[test]
+    fn test_reclaim_stale_reassembler() {
+        let mut quic = QuicInfo::new(1);
+        quic.ensure_reassembler();
+        let reassembler = quic.crypto_reassembler.as_mut().unwrap();
+        reassembler.add_fragment(100, vec![0u8; 1000]).unwrap();
+        reassembler.add_fragment(2000, vec![0u8; 500]).unwrap();
+        assert_eq!(reassembler.buffered_bytes(), 1500);
+
+        assert_eq!(quic.reclaim_reassembler(Duration::from_secs(60)), 0);
+        assert_eq!(quic.reclaim_reassembler(Duration::ZERO), 1500);
+
+        let reassembler = quic.crypto_reassembler.as_ref().unwrap();
+        assert_eq!(reassembler.buffered_bytes(), 0);
+        assert!(reassembler.get_fragments().is_empty());
+    }
+
+    #[test]
+    fn test_reclaim_completed_reassembler_keeps_tls_info() {
+        let mut quic = QuicInfo::new(1);
+        quic.ensure_reassembler();
+        let reassembler = quic.crypto_reassembler.as_mut().unwrap();
+        reassembler.add_fragment(0, vec![0u8; 256]).unwrap();
+        let mut tls_info = TlsInfo::new();
+        tls_info.sni = Some("example.com".to_string());
+        reassembler.set_complete_tls_info(tls_info);
+
+        assert_eq!(quic.reclaim_reassembler(Duration::from_secs(60)), 256);
+        assert!(quic.crypto_reassembler.is_none());
+        assert_eq!(
+            quic.tls_info.and_then(|tls| tls.sni).as_deref(),
+            Some("example.com")
+        );
     }
 }
@@ -0,0 +1,291 @@
+//! Custom protocol signatures
+//!
+//! Signatures are checked after the built-in detectors and let sites recognise
+//! their own protocols without patching the DPI pipeline. They are small files
+//! dropped into `~/.config/rustnet/signatures/` (see `signatures/example/`);
+//! library users can also register a [`Detector`] of their own.
+
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::network::types::{ApplicationProtocol, CustomProtocolInfo};
+
+/// A user-defined protocol detector
+pub trait Detector: Send + Sync {
+    /// Name shown in logs and in the connection details
+    fn name(&self) -> &str;
+
+    /// Inspect a payload seen on `port` (the service side of the flow)
+    fn detect(&self, payload: &[u8], port: u16) -> Option<ApplicationProtocol>;
+}
+
+/// Ordered set of detectors consulted by the DPI pipeline
+#[derive(Default)]
+pub struct SignatureRegistry {
+    detectors: Vec<Box<dyn Detector>>,
+}
+
+impl SignatureRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, detector: Box<dyn Detector>) {
+        info!("Registered custom signature: {}", detector.name());
+        self.detectors.push(detector);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.detectors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.detectors.len()
+    }
+
+    /// Load every `*.yml` signature file in the default signature directory.
+    /// A missing directory simply yields an empty registry.
+    pub fn load_default() -> Self {
+        let mut registry = Self::new();
+        if let Some(dir) = default_signature_dir()
+            && dir.is_dir()
+            && let Err(e) = registry.load_dir(&dir)
+        {
+            warn!("Failed to load signatures from {}: {}", dir.display(), e);
+        }
+        registry
+    }
+
+    /// Load signatures from `dir`. Invalid files are skipped with a warning.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "yml"))
+            .collect();
+        // Deterministic order so the first-match rule is predictable
+        paths.sort();
+
+        for path in paths {
+            match Signature::from_file(&path) {
+                Ok(signature) => self.register(Box::new(signature)),
+                Err(e) => warn!("Skipping signature {}: {}", path.display(), e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run detectors in registration order and return the first match.
+    /// The lower port is treated as the service port.
+    pub fn detect(
+        &self,
+        payload: &[u8],
+        local_port: u16,
+        remote_port: u16,
+    ) -> Option<ApplicationProtocol> {
+        if payload.is_empty() {
+            return None;
+        }
+        let port = local_port.min(remote_port);
+        self.detectors
+            .iter()
+            .find_map(|detector| detector.detect(payload, port))
+    }
+}
+
+/// Signature directory: `$XDG_CONFIG_HOME/rustnet/signatures` or `~/.config/rustnet/signatures`
+pub fn default_signature_dir() -> Option<PathBuf> {
+    if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(xdg_config).join("rustnet/signatures"));
+    }
+    std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()
+        .map(|home| PathBuf::from(home).join(".config/rustnet/signatures"))
+}
+
+/// A fixed byte signature, optionally restricted to ports
+#[derive(Debug, Clone)]
+pub struct Signature {
+    name: String,
+    ports: Vec<u16>,
+    offset: usize,
+    signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Parse a signature definition made of `key: value` lines:
+    /// `name`, `ports` (comma separated), `offset`, and either `prefix` (text)
+    /// or `prefix_hex`.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut name = None;
+        let mut ports = Vec::new();
+        let mut offset = 0;
+        let mut signature = None;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(anyhow!("expected `key: value`, got `{}`", line));
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "ports" => {
+                    for port in value.split(',') {
+                        ports.push(
+                            port.trim()
+                                .parse()
+                                .with_context(|| format!("invalid port `{}`", port.trim()))?,
+                        );
+                    }
+                }
+                "offset" => {
+                    offset = value
+                        .parse()
+                        .with_context(|| format!("invalid offset `{}`", value))?
+                }
+                "prefix" => signature = Some(unquote(value).as_bytes().to_vec()),
+                "prefix_hex" => signature = Some(parse_hex(value)?),
+                other => warn!("Unknown signature key: {}", other),
+            }
+        }
+
+        let name = name.ok_or_else(|| anyhow!("missing `name`"))?;
+        let signature = signature
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| anyhow!("missing `prefix` or `prefix_hex`"))?;
+
+        Ok(Self {
+            name,
+            ports,
+            offset,
+            signature,
+        })
+    }
+}
+
+impl Detector for Signature {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, payload: &[u8], port: u16) -> Option<ApplicationProtocol> {
+        if !self.ports.is_empty() && !self.ports.contains(&port) {
+            return None;
+        }
+        let end = self.offset.checked_add(self.signature.len())?;
+        if payload.get(self.offset..end)? != self.signature.as_slice() {
+            return None;
+        }
+        Some(ApplicationProtocol::Custom(CustomProtocolInfo {
+            name: self.name.clone(),
+            detail: None,
+        }))
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = value.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(anyhow!("odd number of hex digits"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let hex = std::str::from_utf8(pair)
+                .ok()
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .ok_or_else(|| anyhow!("invalid hex digits in `{}`", value))?;
+            Ok(u8::from_str_radix(hex, 16)?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACME: &str =
+        "# Acme internal RPC\nname: ACME-RPC\nports: 7400, 7401\nprefix: \"ACME/\"\n";
+
+    fn detected_name(app: Option<ApplicationProtocol>) -> Option<String> {
+        match app {
+            Some(ApplicationProtocol::Custom(info)) => Some(info.name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_signature_matches_prefix_and_port() {
+        let signature = Signature::parse(ACME).unwrap();
+
+        assert_eq!(
+            detected_name(signature.detect(b"ACME/1.0 HELLO", 7400)).as_deref(),
+            Some("ACME-RPC")
+        );
+        assert!(signature.detect(b"ACME/1.0 HELLO", 8080).is_none());
+        assert!(signature.detect(b"ACM", 7400).is_none());
+    }
+
+    #[test]
+    fn test_hex_signature_with_offset() {
+        let signature = Signature::parse("name: Beacon\noffset: 2\nprefix_hex: ca fe\n").unwrap();
+
+        assert!(signature.detect(&[0x00, 0x01, 0xca, 0xfe], 9999).is_some());
+        assert!(signature.detect(&[0xca, 0xfe], 9999).is_none());
+        assert!(Signature::parse("name: Bad\nprefix_hex: abc\n").is_err());
+        assert!(Signature::parse("prefix: x\n").is_err());
+    }
+
+    #[test]
+    fn test_registry_uses_service_port_and_first_match() {
+        let mut registry = SignatureRegistry::new();
+        registry.register(Box::new(Signature::parse(ACME).unwrap()));
+        registry.register(Box::new(
+            Signature::parse("name: Catch-all\nprefix: ACME\n").unwrap(),
+        ));
+
+        assert_eq!(
+            detected_name(registry.detect(b"ACME/1.0", 51234, 7400)).as_deref(),
+            Some("ACME-RPC")
+        );
+        assert_eq!(
+            detected_name(registry.detect(b"ACME/1.0", 51234, 443)).as_deref(),
+            Some("Catch-all")
+        );
+        assert!(registry.detect(b"GET /", 51234, 7400).is_none());
+    }
+
+    #[test]
+    fn test_load_dir_skips_invalid_files() {
+        let dir = std::env::temp_dir().join(format!("rustnet-signatures-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("acme.yml"), ACME).unwrap();
+        fs::write(dir.join("broken.yml"), "name: Broken\n").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let mut registry = SignatureRegistry::new();
+        registry.load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(registry.len(), 1);
+    }
+}
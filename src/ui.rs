@@ -7,17 +7,30 @@ use ratatui::{
     widgets::{Block, Borders, Cell, Paragraph, Row, Table, Tabs, Wrap},
 };
 
-use crate::app::{App, AppStats};
-use crate::network::types::{Connection, Protocol};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::app::{App, ConnectionSnapshot};
+use crate::filter::ConnectionFilter;
+use crate::network::health::HealthMetrics;
+use crate::network::interfaces::InterfaceRates;
+use crate::network::talkers::{self, TalkerOrder};
+use crate::network::types::{
+    CloseReason, Connection, DnsResponseCode, Protocol, THROUGHPUT_HISTORY_SECS,
+};
 
 pub type Terminal<B> = RatatuiTerminal<B>;
 
 /// Sort column options for the connections table
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SortColumn {
+    #[default]
     CreatedAt,        // Default: creation time (oldest first)
     BandwidthDown,
     BandwidthUp,
+    Rtt,
     Process,
     LocalAddress,
     RemoteAddress,
@@ -25,12 +38,9 @@ pub enum SortColumn {
     Service,
     State,
     Protocol,
-}
-
-impl Default for SortColumn {
-    fn default() -> Self {
-        Self::CreatedAt
-    }
+    LastActivity,
+    BytesReceived,
+    BytesSent,
 }
 
 impl SortColumn {
@@ -45,8 +55,12 @@ impl SortColumn {
             Self::Service => Self::Application,          // Column 6: Application / Host
             Self::Application => Self::BandwidthDown,    // Column 7: Down/Up (Down first)
             Self::BandwidthDown => Self::BandwidthUp,    // Column 7: Down/Up (Up second)
-            Self::BandwidthUp => Self::Process,          // Column 8: Process
-            Self::Process => Self::CreatedAt,            // Back to default
+            Self::BandwidthUp => Self::Rtt,              // Column 8: RTT
+            Self::Rtt => Self::Process,                  // Column 9: Process
+            Self::Process => Self::LastActivity,         // Not shown as columns:
+            Self::LastActivity => Self::BytesReceived,   // most recent packet,
+            Self::BytesReceived => Self::BytesSent,      // then total bytes
+            Self::BytesSent => Self::CreatedAt,          // Back to default
         }
     }
 
@@ -56,6 +70,11 @@ impl SortColumn {
             // Descending by default - show biggest/most active first
             Self::BandwidthDown => false,
             Self::BandwidthUp => false,
+            Self::Rtt => false, // Slowest first
+            // Most recently active and heaviest flows first
+            Self::LastActivity => false,
+            Self::BytesReceived => false,
+            Self::BytesSent => false,
 
             // Ascending by default - alphabetical or chronological
             Self::Process => true,
@@ -75,6 +94,7 @@ impl SortColumn {
             Self::CreatedAt => "Time",
             Self::BandwidthDown => "Bandwidth ↓",
             Self::BandwidthUp => "Bandwidth ↑",
+            Self::Rtt => "RTT",
             Self::Process => "Process",
             Self::LocalAddress => "Local Addr",
             Self::RemoteAddress => "Remote Addr",
@@ -82,6 +102,9 @@ impl SortColumn {
             Self::Service => "Service",
             Self::State => "State",
             Self::Protocol => "Protocol",
+            Self::LastActivity => "Last Activity",
+            Self::BytesReceived => "Bytes Received",
+            Self::BytesSent => "Bytes Sent",
         }
     }
 }
@@ -112,6 +135,226 @@ pub fn restore_terminal<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>
     Ok(())
 }
 
+/// Compare two connections by the given column and direction. Ties are broken by
+/// creation time so the order doesn't depend on the order of the input.
+pub fn compare_connections(
+    a: &Connection,
+    b: &Connection,
+    sort_column: SortColumn,
+    ascending: bool,
+) -> Ordering {
+    let ordering = match sort_column {
+        SortColumn::CreatedAt => a.created_at.cmp(&b.created_at),
+
+        SortColumn::BandwidthDown => {
+            // Compare as f64, handle NaN cases
+            a.current_incoming_rate_bps
+                .partial_cmp(&b.current_incoming_rate_bps)
+                .unwrap_or(Ordering::Equal)
+        }
+
+        SortColumn::BandwidthUp => a
+            .current_outgoing_rate_bps
+            .partial_cmp(&b.current_outgoing_rate_bps)
+            .unwrap_or(Ordering::Equal),
+
+        // Connections without an estimate sort as the fastest
+        SortColumn::Rtt => a.rtt_estimate.cmp(&b.rtt_estimate),
+
+        SortColumn::Process => {
+            let a_process = a.process_name.as_deref().unwrap_or("");
+            let b_process = b.process_name.as_deref().unwrap_or("");
+            a_process.cmp(b_process)
+        }
+
+        SortColumn::LocalAddress => a.local_addr.to_string().cmp(&b.local_addr.to_string()),
+
+        SortColumn::RemoteAddress => a.remote_addr.to_string().cmp(&b.remote_addr.to_string()),
+
+        SortColumn::Application => {
+            let a_app = a
+                .dpi_info
+                .as_ref()
+                .map(|dpi| dpi.application.to_string())
+                .unwrap_or_default();
+            let b_app = b
+                .dpi_info
+                .as_ref()
+                .map(|dpi| dpi.application.to_string())
+                .unwrap_or_default();
+            a_app.cmp(&b_app)
+        }
+
+        SortColumn::Service => {
+            let a_service = a.service_name.as_deref().unwrap_or("");
+            let b_service = b.service_name.as_deref().unwrap_or("");
+            a_service.cmp(b_service)
+        }
+
+        SortColumn::State => a.state().cmp(&b.state()),
+
+        SortColumn::Protocol => a.protocol.to_string().cmp(&b.protocol.to_string()),
+
+        SortColumn::LastActivity => a.last_activity_instant.cmp(&b.last_activity_instant),
+
+        SortColumn::BytesReceived => a.bytes_received.cmp(&b.bytes_received),
+
+        SortColumn::BytesSent => a.bytes_sent.cmp(&b.bytes_sent),
+    };
+
+    let ordering = if ascending {
+        ordering
+    } else {
+        ordering.reverse()
+    };
+    ordering.then_with(|| a.created_at.cmp(&b.created_at))
+}
+
+/// How `ConnectionView::apply_snapshot` updated the view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewUpdate {
+    /// Same snapshot, filter and sort as last time
+    Unchanged,
+    /// New snapshot, previous order still sorted
+    Reused,
+    /// New snapshot, order had to be re-sorted
+    Resorted,
+    /// Filter or sort changed, view built from scratch
+    Rebuilt,
+}
+
+/// Filtered and sorted view of the latest snapshot, kept between frames.
+///
+/// The UI loop redraws several times per snapshot, so the view is only
+/// recomputed when the snapshot, filter or sort changes. For a new snapshot
+/// the previous order is carried over by connection key, new connections are
+/// appended and the list is only re-sorted if that order is no longer sorted.
+#[derive(Default)]
+pub struct ConnectionView {
+    snapshot: Arc<ConnectionSnapshot>,
+    filter_query: String,
+    sort_column: SortColumn,
+    sort_ascending: bool,
+    /// Keys of coalesced rows whose flows are listed under them
+    expanded_rows: HashSet<String>,
+    show_ignored: bool,
+    /// Indices into `snapshot.connections`, in display order
+    order: Vec<usize>,
+}
+
+impl ConnectionView {
+    /// Bring the view up to date with a snapshot and the UI's filter and sort
+    pub fn apply_snapshot(
+        &mut self,
+        snapshot: &Arc<ConnectionSnapshot>,
+        ui_state: &UIState,
+    ) -> ViewUpdate {
+        let criteria_changed = self.filter_query != ui_state.filter_query
+            || self.sort_column != ui_state.sort_column
+            || self.sort_ascending != ui_state.sort_ascending
+            || self.expanded_rows != ui_state.expanded_rows
+            || self.show_ignored != ui_state.show_ignored;
+        let snapshot_changed = !Arc::ptr_eq(&self.snapshot, snapshot);
+
+        if !criteria_changed && !snapshot_changed {
+            return ViewUpdate::Unchanged;
+        }
+
+        self.filter_query.clone_from(&ui_state.filter_query);
+        self.sort_column = ui_state.sort_column;
+        self.sort_ascending = ui_state.sort_ascending;
+        self.expanded_rows.clone_from(&ui_state.expanded_rows);
+        self.show_ignored = ui_state.show_ignored;
+
+        let filter = (!self.filter_query.trim().is_empty())
+            .then(|| ConnectionFilter::parse(&self.filter_query));
+        let visible = snapshot
+            .connections
+            .iter()
+            .enumerate()
+            .filter(|(_, conn)| self.show_ignored || !conn.ignored)
+            .filter(|(_, conn)| filter.as_ref().is_none_or(|f| f.matches(conn)));
+
+        let update = if criteria_changed {
+            self.order = visible.map(|(index, _)| index).collect();
+            self.sort(snapshot);
+            ViewUpdate::Rebuilt
+        } else {
+            let mut new_indices: HashMap<String, usize> =
+                visible.map(|(index, conn)| (conn.key(), index)).collect();
+
+            // Keep the previous order for connections that are still visible...
+            let mut order = Vec::with_capacity(new_indices.len());
+            for &index in &self.order {
+                if let Some(new_index) = new_indices.remove(&self.snapshot.connections[index].key())
+                {
+                    order.push(new_index);
+                }
+            }
+            // ...and append genuinely new ones in snapshot (creation) order
+            let mut added: Vec<usize> = new_indices.into_values().collect();
+            added.sort_unstable();
+            order.extend(added);
+            self.order = order;
+
+            if self.is_sorted(snapshot) {
+                ViewUpdate::Reused
+            } else {
+                self.sort(snapshot);
+                ViewUpdate::Resorted
+            }
+        };
+
+        self.snapshot = Arc::clone(snapshot);
+        update
+    }
+
+    /// Connections in display order, borrowed from the snapshot. The flows
+    /// of an expanded row follow it, oldest first.
+    pub fn connections(&self) -> Vec<&Connection> {
+        let mut connections = Vec::with_capacity(self.order.len());
+        for &index in &self.order {
+            let conn = &self.snapshot.connections[index];
+            connections.push(conn);
+            if conn.subflow_count > 0
+                && self.expanded_rows.contains(&conn.key())
+                && let Some(flows) = self.snapshot.subflows.get(&conn.key())
+            {
+                connections.extend(flows);
+            }
+        }
+        connections
+    }
+
+    /// The snapshot the view was built from
+    pub fn snapshot(&self) -> &ConnectionSnapshot {
+        &self.snapshot
+    }
+
+    fn is_sorted(&self, snapshot: &ConnectionSnapshot) -> bool {
+        self.order.is_sorted_by(|&a, &b| {
+            compare_connections(
+                &snapshot.connections[a],
+                &snapshot.connections[b],
+                self.sort_column,
+                self.sort_ascending,
+            ) != Ordering::Greater
+        })
+    }
+
+    fn sort(&mut self, snapshot: &ConnectionSnapshot) {
+        let (column, ascending) = (self.sort_column, self.sort_ascending);
+        self.order.sort_by(|&a, &b| {
+            compare_connections(
+                &snapshot.connections[a],
+                &snapshot.connections[b],
+                column,
+                ascending,
+            )
+        });
+    }
+}
+
 /// UI state for managing the interface
 pub struct UIState {
     pub selected_tab: usize,
@@ -125,6 +368,20 @@ pub struct UIState {
     pub show_port_numbers: bool,
     pub sort_column: SortColumn,
     pub sort_ascending: bool,
+    /// Index of the generated firewall rule shown in the popup, if open
+    pub firewall_popup: Option<usize>,
+    /// Waiting for the user to confirm executing the popup's rule
+    pub firewall_confirm: bool,
+    /// Coalesced rows expanded to list their flows
+    pub expanded_rows: HashSet<String>,
+    /// Total the Top tab ranks remote hosts by
+    pub talker_order: TalkerOrder,
+    /// Row selected on the Top tab
+    pub selected_talker: usize,
+    /// Rank local processes on the Top tab instead of remote hosts
+    pub show_processes: bool,
+    /// List connections matched by an ignore rule
+    pub show_ignored: bool,
 }
 
 impl Default for UIState {
@@ -141,13 +398,20 @@ impl Default for UIState {
             show_port_numbers: false,
             sort_column: SortColumn::default(),
             sort_ascending: true, // Default to ascending
+            firewall_popup: None,
+            firewall_confirm: false,
+            expanded_rows: HashSet::new(),
+            talker_order: TalkerOrder::default(),
+            selected_talker: 0,
+            show_processes: false,
+            show_ignored: false,
         }
     }
 }
 
 impl UIState {
     /// Get the current selected connection index, if any
-    pub fn get_selected_index(&self, connections: &[Connection]) -> Option<usize> {
+    pub fn get_selected_index(&self, connections: &[&Connection]) -> Option<usize> {
         if let Some(ref selected_key) = self.selected_connection_key {
             connections
                 .iter()
@@ -160,14 +424,14 @@ impl UIState {
     }
 
     /// Set the selected connection to the one at the given index
-    pub fn set_selected_by_index(&mut self, connections: &[Connection], index: usize) {
+    pub fn set_selected_by_index(&mut self, connections: &[&Connection], index: usize) {
         if let Some(conn) = connections.get(index) {
             self.selected_connection_key = Some(conn.key());
         }
     }
 
     /// Move selection up by one position
-    pub fn move_selection_up(&mut self, connections: &[Connection]) {
+    pub fn move_selection_up(&mut self, connections: &[&Connection]) {
         if connections.is_empty() {
             log::debug!("move_selection_up: connections list is empty");
             return;
@@ -205,7 +469,7 @@ impl UIState {
     }
 
     /// Move selection down by one position
-    pub fn move_selection_down(&mut self, connections: &[Connection]) {
+    pub fn move_selection_down(&mut self, connections: &[&Connection]) {
         if connections.is_empty() {
             log::debug!("move_selection_down: connections list is empty");
             return;
@@ -242,7 +506,7 @@ impl UIState {
     }
 
     /// Move selection up by one page
-    pub fn move_selection_page_up(&mut self, connections: &[Connection], page_size: usize) {
+    pub fn move_selection_page_up(&mut self, connections: &[&Connection], page_size: usize) {
         if connections.is_empty() {
             return;
         }
@@ -256,7 +520,7 @@ impl UIState {
     }
 
     /// Move selection down by one page
-    pub fn move_selection_page_down(&mut self, connections: &[Connection], page_size: usize) {
+    pub fn move_selection_page_down(&mut self, connections: &[&Connection], page_size: usize) {
         if connections.is_empty() {
             return;
         }
@@ -271,7 +535,7 @@ impl UIState {
     }
 
     /// Move selection to the first connection (vim-style 'g')
-    pub fn move_selection_to_first(&mut self, connections: &[Connection]) {
+    pub fn move_selection_to_first(&mut self, connections: &[&Connection]) {
         if connections.is_empty() {
             return;
         }
@@ -279,7 +543,7 @@ impl UIState {
     }
 
     /// Move selection to the last connection (vim-style 'G')
-    pub fn move_selection_to_last(&mut self, connections: &[Connection]) {
+    pub fn move_selection_to_last(&mut self, connections: &[&Connection]) {
         if connections.is_empty() {
             return;
         }
@@ -287,7 +551,7 @@ impl UIState {
     }
 
     /// Ensure we have a valid selection when connections list changes
-    pub fn ensure_valid_selection(&mut self, connections: &[Connection]) {
+    pub fn ensure_valid_selection(&mut self, connections: &[&Connection]) {
         if connections.is_empty() {
             log::debug!("ensure_valid_selection: connections list is empty, clearing selection");
             self.selected_connection_key = None;
@@ -308,6 +572,24 @@ impl UIState {
         }
     }
 
+    /// Expand or collapse the selected row if it coalesces several flows.
+    /// Returns whether there was such a row.
+    pub fn toggle_expanded(&mut self, connections: &[&Connection]) -> bool {
+        let Some(conn) = self
+            .get_selected_index(connections)
+            .and_then(|index| connections.get(index))
+            .filter(|conn| conn.subflow_count > 0)
+        else {
+            return false;
+        };
+
+        let key = conn.key();
+        if !self.expanded_rows.remove(&key) {
+            self.expanded_rows.insert(key);
+        }
+        true
+    }
+
     /// Enter filter mode
     pub fn enter_filter_mode(&mut self) {
         self.filter_mode = true;
@@ -320,6 +602,12 @@ impl UIState {
         self.filter_cursor_position = 0;
     }
 
+    /// Replace the filter with `query` without entering filter mode
+    pub fn set_filter(&mut self, query: String) {
+        self.filter_query = query;
+        self.exit_filter_mode();
+    }
+
     /// Clear filter and exit filter mode
     pub fn clear_filter(&mut self) {
         self.filter_query.clear();
@@ -372,8 +660,8 @@ pub fn draw(
     f: &mut Frame,
     app: &App,
     ui_state: &UIState,
-    connections: &[Connection],
-    stats: &AppStats,
+    connections: &[&Connection],
+    snapshot: &ConnectionSnapshot,
 ) -> Result<()> {
     // If still loading, show loading screen
     if app.is_loading() {
@@ -412,17 +700,39 @@ pub fn draw(
     };
 
     match ui_state.selected_tab {
-        0 => draw_overview(f, ui_state, connections, stats, app, content_area)?,
+        0 => draw_overview(f, ui_state, connections, snapshot, app, content_area)?,
         1 => draw_connection_details(f, ui_state, connections, content_area)?,
         2 => draw_help(f, content_area)?,
+        3 => draw_firewall_rules(f, app, content_area)?,
+        4 => draw_heatmap(f, app, content_area)?,
+        5 => draw_closed_connections(f, app, content_area)?,
+        6 => draw_top_talkers(f, ui_state, snapshot, content_area)?,
+        7 => draw_bandwidth(f, ui_state, connections, content_area)?,
+        8 => draw_interfaces(f, app, content_area)?,
         _ => {}
     }
 
+    if let Some(index) = ui_state.firewall_popup {
+        draw_firewall_popup(f, app, ui_state, index, content_area);
+    }
+
     if let Some(filter_area) = filter_area {
         draw_filter_input(f, ui_state, filter_area);
     }
 
-    draw_status_bar(f, ui_state, connections.len(), status_area);
+    let ignored_count = snapshot
+        .connections
+        .iter()
+        .filter(|conn| conn.ignored)
+        .count();
+    draw_status_bar(
+        f,
+        ui_state,
+        snapshot,
+        connections.len(),
+        ignored_count,
+        status_area,
+    );
 
     Ok(())
 }
@@ -433,6 +743,12 @@ fn draw_tabs(f: &mut Frame, ui_state: &UIState, area: Rect) {
         Span::styled("Overview", Style::default().fg(Color::Green)),
         Span::styled("Details", Style::default().fg(Color::Green)),
         Span::styled("Help", Style::default().fg(Color::Green)),
+        Span::styled("Rules", Style::default().fg(Color::Green)),
+        Span::styled("Heatmap", Style::default().fg(Color::Green)),
+        Span::styled("Closed", Style::default().fg(Color::Green)),
+        Span::styled("Top", Style::default().fg(Color::Green)),
+        Span::styled("Bandwidth", Style::default().fg(Color::Green)),
+        Span::styled("Interfaces", Style::default().fg(Color::Green)),
     ];
 
     let tabs = Tabs::new(titles.into_iter().map(Line::from).collect::<Vec<_>>())
@@ -456,8 +772,8 @@ fn draw_tabs(f: &mut Frame, ui_state: &UIState, area: Rect) {
 fn draw_overview(
     f: &mut Frame,
     ui_state: &UIState,
-    connections: &[Connection],
-    stats: &AppStats,
+    connections: &[&Connection],
+    snapshot: &ConnectionSnapshot,
     app: &App,
     area: Rect,
 ) -> Result<()> {
@@ -466,8 +782,15 @@ fn draw_overview(
         .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
         .split(area);
 
-    draw_connections_list(f, ui_state, connections, chunks[0]);
-    draw_stats_panel(f, connections, stats, app, chunks[1])?;
+    draw_connections_list(
+        f,
+        ui_state,
+        connections,
+        snapshot,
+        app.max_idle(),
+        chunks[0],
+    );
+    draw_stats_panel(f, connections, snapshot, app, chunks[1])?;
 
     Ok(())
 }
@@ -476,7 +799,9 @@ fn draw_overview(
 fn draw_connections_list(
     f: &mut Frame,
     ui_state: &UIState,
-    connections: &[Connection],
+    connections: &[&Connection],
+    snapshot: &ConnectionSnapshot,
+    max_idle: Option<Duration>,
     area: Rect,
 ) {
     let widths = [
@@ -487,6 +812,7 @@ fn draw_connections_list(
         Constraint::Length(10), // Service (7 + arrow = 9, need at least 10 for padding)
         Constraint::Length(24), // DPI/Application (18 + arrow = 20, fits in 24)
         Constraint::Length(12), // Bandwidth (7 + arrow = 9, fits in 12)
+        Constraint::Length(8),  // RTT (3 + arrow = 5, values up to "999ms")
         Constraint::Min(20),    // Process (flexible remaining space)
     ];
 
@@ -523,6 +849,7 @@ fn draw_connections_list(
         add_sort_indicator("Service", &[SortColumn::Service]),
         add_sort_indicator("Application / Host", &[SortColumn::Application]),
         bandwidth_label,  // Use custom bandwidth label instead of generic indicator
+        add_sort_indicator("RTT", &[SortColumn::Rtt]),
         add_sort_indicator("Process", &[SortColumn::Process]),
     ];
 
@@ -540,7 +867,8 @@ fn draw_connections_list(
                 5 => ui_state.sort_column == SortColumn::Application,
                 6 => ui_state.sort_column == SortColumn::BandwidthDown
                      || ui_state.sort_column == SortColumn::BandwidthUp,
-                7 => ui_state.sort_column == SortColumn::Process,
+                7 => ui_state.sort_column == SortColumn::Rtt,
+                8 => ui_state.sort_column == SortColumn::Process,
                 _ => false,
             } && ui_state.sort_column != SortColumn::CreatedAt;
 
@@ -594,7 +922,11 @@ fn draw_connections_list(
             }
 
             // Process names are now pre-normalized at the source (PKTAP/lsof), so we can use them directly
-            let process_str = conn.process_name.clone().unwrap_or_else(|| "-".to_string());
+            let process_str = if conn.forwarded {
+                "(forwarded)".to_string()
+            } else {
+                conn.process_name.clone().unwrap_or_else(|| "-".to_string())
+            };
 
             let process_display = if conn.pid.is_some() {
                 // Ensure exactly one space between process name and PID: "PROCESS_NAME (PID)"
@@ -634,6 +966,13 @@ fn draw_connections_list(
 
             // DPI/Application protocol display (enhanced for hostnames)
             let dpi_display = match &conn.dpi_info {
+                Some(dpi)
+                    if conn.possible_downgrade
+                        || conn.uses_deprecated_tls()
+                        || !conn.tls_warnings.is_empty() =>
+                {
+                    format!("⚠ {}", dpi.application)
+                }
                 Some(dpi) => dpi.application.to_string(),
                 None => "-".to_string(),
             };
@@ -641,32 +980,89 @@ fn draw_connections_list(
             // Compact bandwidth display to fit in 14 chars
             let incoming_rate = format_rate_compact(conn.current_incoming_rate_bps);
             let outgoing_rate = format_rate_compact(conn.current_outgoing_rate_bps);
-            let bandwidth_display = format!("{}↓/{}↑", incoming_rate, outgoing_rate);
+            let bandwidth_display = if conn.forwarded {
+                // A→B and B→A, matching the order of the address columns
+                format!("{}→/{}←", outgoing_rate, incoming_rate)
+            } else {
+                format!("{}↓/{}↑", incoming_rate, outgoing_rate)
+            };
 
             // Determine row color based on staleness
             // - Normal (white/default): fresh connections (< 75% of timeout)
             // - Yellow: approaching timeout (75-90% of timeout)
             // - Red: very close to timeout (> 90% of timeout)
-            let staleness = conn.staleness_ratio();
+            // - Dark gray: idle, with only keepalive probes going back and forth
+            let staleness = conn.staleness_ratio(max_idle);
             let row_style = if staleness >= 0.90 {
                 // Critical: > 90% of timeout - will be cleaned up very soon
                 Style::default().fg(Color::Red)
             } else if staleness >= 0.75 {
                 // Warning: 75-90% of timeout - approaching cleanup
                 Style::default().fg(Color::Yellow)
+            } else if conn.keepalive_only {
+                // Idle but held open: only keepalive probes keep it fresh
+                Style::default().fg(Color::DarkGray)
             } else {
                 // Normal: < 75% of timeout
                 Style::default()
             };
 
+            // Coalesced rows show their flow count, and their flows are listed
+            // under them by local port when expanded
+            let (protocol_display, local_display, state_display) = if conn.subflow_count > 0 {
+                let marker = if ui_state.expanded_rows.contains(&conn.key()) {
+                    "▾"
+                } else {
+                    "▸"
+                };
+                let local = conn.local_addr.to_string();
+                (
+                    format!("{}{}", marker, conn.protocol),
+                    format!("{}*", local.strip_suffix('0').unwrap_or(&local)),
+                    format!("{} ×{}", conn.state(), conn.subflow_count),
+                )
+            } else if snapshot.is_subflow(conn) {
+                (
+                    String::new(),
+                    format!(" └ :{}", conn.local_addr.port()),
+                    conn.state(),
+                )
+            } else {
+                (
+                    conn.protocol.to_string(),
+                    conn.local_addr.to_string(),
+                    conn.state(),
+                )
+            };
+
+            // Resolved names replace the address, keeping the port
+            let remote_display = match &conn.hostname {
+                Some(hostname) => format!("{}:{}", hostname, conn.remote_addr.port()),
+                None => conn.remote_addr.to_string(),
+            };
+            // Flows from a detected scan or flood are marked in the remote column
+            let remote_cell = match conn.scan {
+                Some(_) => Cell::from(format!("⚑ {}", remote_display))
+                    .style(Style::default().fg(Color::Magenta)),
+                None => Cell::from(remote_display),
+            };
+
+            // Flows being saved to a pcap file are marked in the protocol column
+            let protocol_cell = if conn.recording {
+                Cell::from(format!("●{}", protocol_display)).style(Style::default().fg(Color::Red))
+            } else {
+                Cell::from(protocol_display)
+            };
+
             let cells = [
-                Cell::from(conn.protocol.to_string()),
-                Cell::from(conn.local_addr.to_string()),
-                Cell::from(conn.remote_addr.to_string()),
-                Cell::from(conn.state()),
+                protocol_cell,
+                Cell::from(local_display),
+                remote_cell,
+                Cell::from(state_display),
                 Cell::from(service_display),
                 Cell::from(dpi_display),
                 Cell::from(bandwidth_display),
+                Cell::from(format_rtt_compact(conn.rtt_estimate)),
                 Cell::from(process_display),
             ];
             Row::new(cells).style(row_style)
@@ -707,15 +1103,15 @@ fn draw_connections_list(
 /// Draw stats panel
 fn draw_stats_panel(
     f: &mut Frame,
-    connections: &[Connection],
-    stats: &AppStats,
+    connections: &[&Connection],
+    snapshot: &ConnectionSnapshot,
     app: &App,
     area: Rect,
 ) -> Result<()> {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(10), // Connection stats (increased for interface line)
+            Constraint::Length(19), // Connection stats (interface line, health, scan/eviction/DPI warnings)
             Constraint::Min(0),     // Traffic stats
         ])
         .split(area);
@@ -734,51 +1130,111 @@ fn draw_stats_panel(
         .get_current_interface()
         .unwrap_or_else(|| "Unknown".to_string());
 
-    let conn_stats_text: Vec<Line> = vec![
+    let mut conn_stats_text: Vec<Line> = vec![
         Line::from(format!("Interface: {}", interface_name)),
         Line::from(""),
         Line::from(format!("TCP Connections: {}", tcp_count)),
         Line::from(format!("UDP Connections: {}", udp_count)),
-        Line::from(format!("Total Connections: {}", connections.len())),
+        Line::from(format!(
+            "Total Connections: {} ({} tracked)",
+            connections.len(),
+            snapshot.total_connections
+        )),
+        Line::from(format!(
+            "Expired: {}  Evicted: {}",
+            snapshot.connections_expired, snapshot.connections_evicted
+        )),
         Line::from(""),
+        Line::from(format!("Packets Processed: {}", snapshot.packets_processed)),
         Line::from(format!(
-            "Packets Processed: {}",
-            stats
-                .packets_processed
-                .load(std::sync::atomic::Ordering::Relaxed)
+            "Packets Dropped: {} ({}/s)",
+            snapshot.packets_dropped, snapshot.drops_per_sec
         )),
         Line::from(format!(
-            "Packets Dropped: {}",
-            stats
-                .packets_dropped
-                .load(std::sync::atomic::Ordering::Relaxed)
+            "TCP Retransmits: {:.2}%",
+            snapshot.packet_loss_rate() * 100.0
         )),
+        Line::from(""),
     ];
+    conn_stats_text.extend(health_lines(&snapshot.health));
+    if let Some(last) = snapshot.scan_alerts.last() {
+        let style = Style::default().fg(Color::Magenta);
+        let raised_at: chrono::DateTime<chrono::Local> = last.time.into();
+        conn_stats_text.push(Line::from(Span::styled(
+            format!("⚑ Scan Alerts: {}", snapshot.scan_alerts.len()),
+            style,
+        )));
+        conn_stats_text.push(Line::from(Span::styled(
+            format!("  {} {}", raised_at.format("%H:%M:%S"), last.describe()),
+            style,
+        )));
+    }
+    if snapshot.connections_evicted > 0 {
+        conn_stats_text.push(Line::from(Span::styled(
+            format!(
+                "⚠ Table full - {} oldest flows dropped",
+                snapshot.connections_evicted
+            ),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    if snapshot.queue_dropped > 0 {
+        conn_stats_text.push(Line::from(Span::styled(
+            format!(
+                "⚠ Processing behind - {} packets dropped",
+                snapshot.queue_dropped
+            ),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    if snapshot.dpi_jobs_dropped > 0 {
+        conn_stats_text.push(Line::from(Span::styled(
+            format!("DPI Jobs Dropped: {}", snapshot.dpi_jobs_dropped),
+            Style::default().fg(Color::Yellow),
+        )));
+    }
 
     let conn_stats = Paragraph::new(conn_stats_text)
         .block(Block::default().borders(Borders::ALL).title("Statistics"))
         .style(Style::default());
     f.render_widget(conn_stats, chunks[0]);
 
-    // Traffic statistics
+    // Traffic statistics; ignored connections don't count even when shown
     let total_incoming: f64 = connections
         .iter()
+        .filter(|c| !c.ignored)
         .map(|c| c.current_incoming_rate_bps)
         .sum();
     let total_outgoing: f64 = connections
         .iter()
+        .filter(|c| !c.ignored)
         .map(|c| c.current_outgoing_rate_bps)
         .sum();
 
-    let traffic_stats_text: Vec<Line> = vec![
+    let mut traffic_stats_text: Vec<Line> = vec![
         Line::from(format!("Total Incoming: {}", format_rate(total_incoming))),
         Line::from(format!("Total Outgoing: {}", format_rate(total_outgoing))),
         Line::from(""),
         Line::from(format!(
             "Last Update: {:?} ago",
-            stats.last_update.read().unwrap().elapsed()
+            snapshot.taken_at.elapsed()
         )),
     ];
+    if snapshot.quic_reassembly_bytes > 0 || snapshot.quic_reassembly_rejected > 0 {
+        let style = if snapshot.quic_reassembly_rejected > 0 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        traffic_stats_text.push(Line::from(Span::styled(
+            format!(
+                "QUIC Reassembly: {} ({} rejected)",
+                format_bytes(snapshot.quic_reassembly_bytes as u64),
+                snapshot.quic_reassembly_rejected
+            ),
+            style,
+        )));
+    }
 
     let traffic_stats = Paragraph::new(traffic_stats_text)
         .block(Block::default().borders(Borders::ALL).title("Traffic"))
@@ -788,11 +1244,80 @@ fn draw_stats_panel(
     Ok(())
 }
 
+/// Half-open TCP connections per established one above which the count is highlighted
+const HALF_OPEN_WARNING_RATIO: f64 = 0.5;
+
+/// Half-open connections below this are never highlighted; a few are normal
+const HALF_OPEN_WARNING_MIN: usize = 10;
+
+/// One character per value, scaled to the largest
+fn sparkline(values: &[f64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|&value| {
+            if max > 0.0 {
+                BARS[((value / max) * (BARS.len() - 1) as f64).round() as usize]
+            } else {
+                BARS[0]
+            }
+        })
+        .collect()
+}
+
+/// Statistics panel lines for connection churn and TCP handshake health
+fn health_lines(health: &HealthMetrics) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(format!(
+        "New Conns/s: {:.1} {}",
+        health.new_per_sec,
+        sparkline(&health.rate_history[health.rate_history.len().saturating_sub(20)..])
+    ))];
+    lines.push(Line::from(match health.new_per_sec_by_process.first() {
+        Some((process, rate)) => format!("  Most From: {} ({:.1}/s)", process, rate),
+        None => "  Most From: -".to_string(),
+    }));
+
+    let half_open_style = if health.half_open >= HALF_OPEN_WARNING_MIN
+        && health
+            .half_open_ratio()
+            .is_none_or(|ratio| ratio > HALF_OPEN_WARNING_RATIO)
+    {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    lines.push(Line::from(Span::styled(
+        format!(
+            "Half-open: {} ({} established)",
+            health.half_open, health.established
+        ),
+        half_open_style,
+    )));
+
+    let mut states: Vec<(&String, &usize)> = health.tcp_states.iter().collect();
+    states.sort_by(|a, b| b.1.cmp(a.1));
+    let states: Vec<String> = states
+        .iter()
+        .take(3)
+        .map(|(state, count)| format!("{} {}", state, count))
+        .collect();
+    lines.push(Line::from(format!(
+        "TCP: {}",
+        if states.is_empty() {
+            "-".to_string()
+        } else {
+            states.join(", ")
+        }
+    )));
+    lines
+}
+
 /// Draw connection details view
 fn draw_connection_details(
     f: &mut Frame,
     ui_state: &UIState,
-    connections: &[Connection],
+    connections: &[&Connection],
     area: Rect,
 ) -> Result<()> {
     if connections.is_empty() {
@@ -816,6 +1341,18 @@ fn draw_connection_details(
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
         .split(area);
 
+    // Forwarded flows have no local side, so show them as endpoints A and B
+    let (local_label, remote_label) = if conn.forwarded {
+        ("Endpoint A: ", "Endpoint B: ")
+    } else {
+        ("Local Address: ", "Remote Address: ")
+    };
+    let (sent, received, rate_in, rate_out) = if conn.forwarded {
+        ("A→B", "B→A", "B→A", "A→B")
+    } else {
+        ("Sent", "Received", "In", "Out")
+    };
+
     // Connection details
     let mut details_text: Vec<Line> = vec![
         Line::from(vec![
@@ -823,12 +1360,15 @@ fn draw_connection_details(
             Span::raw(conn.protocol.to_string()),
         ]),
         Line::from(vec![
-            Span::styled("Local Address: ", Style::default().fg(Color::Yellow)),
+            Span::styled(local_label, Style::default().fg(Color::Yellow)),
             Span::raw(conn.local_addr.to_string()),
         ]),
         Line::from(vec![
-            Span::styled("Remote Address: ", Style::default().fg(Color::Yellow)),
-            Span::raw(conn.remote_addr.to_string()),
+            Span::styled(remote_label, Style::default().fg(Color::Yellow)),
+            Span::raw(match &conn.hostname {
+                Some(hostname) => format!("{} ({})", conn.remote_addr, hostname),
+                None => conn.remote_addr.to_string(),
+            }),
         ]),
         Line::from(vec![
             Span::styled("State: ", Style::default().fg(Color::Yellow)),
@@ -836,7 +1376,11 @@ fn draw_connection_details(
         ]),
         Line::from(vec![
             Span::styled("Process: ", Style::default().fg(Color::Yellow)),
-            Span::raw(conn.process_name.clone().unwrap_or_else(|| "-".to_string())),
+            Span::raw(if conn.forwarded {
+                "(forwarded between other hosts)".to_string()
+            } else {
+                conn.process_name.clone().unwrap_or_else(|| "-".to_string())
+            }),
         ]),
         Line::from(vec![
             Span::styled("PID: ", Style::default().fg(Color::Yellow)),
@@ -852,6 +1396,124 @@ fn draw_connection_details(
         ]),
     ];
 
+    if !conn.alternate_local_addrs.is_empty() {
+        let addrs: Vec<String> = conn
+            .alternate_local_addrs
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        details_text.push(Line::from(vec![
+            Span::styled("SCTP Multi-homing: ", Style::default().fg(Color::Yellow)),
+            Span::raw(addrs.join(", ")),
+        ]));
+    }
+    if let Some(usage) = process_usage_text(conn) {
+        details_text.push(Line::from(vec![
+            Span::styled("Process Usage: ", Style::default().fg(Color::Yellow)),
+            Span::raw(usage),
+        ]));
+    }
+
+    if let Some(mac) = &conn.remote_mac {
+        details_text.push(Line::from(vec![
+            Span::styled("Remote MAC: ", Style::default().fg(Color::Yellow)),
+            Span::raw(crate::network::mac::describe(mac)),
+        ]));
+    }
+
+    if let Some(vlan_id) = conn.vlan_id {
+        details_text.push(Line::from(vec![
+            Span::styled("VLAN: ", Style::default().fg(Color::Yellow)),
+            Span::raw(vlan_id.to_string()),
+        ]));
+    }
+
+    if let Some(tunnel) = conn.tunnel {
+        details_text.push(Line::from(vec![
+            Span::styled("Tunnel: ", Style::default().fg(Color::Yellow)),
+            Span::raw(tunnel.to_string()),
+        ]));
+    }
+
+    if conn.recording {
+        details_text.push(Line::from(vec![
+            Span::styled("Recording: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                "saving packets to a pcap file (w to stop)",
+                Style::default().fg(Color::Red),
+            ),
+        ]));
+    }
+
+    if let Some(rtt) = conn.rtt_estimate {
+        details_text.push(Line::from(vec![
+            Span::styled("RTT: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{:.1} ms", rtt.as_secs_f64() * 1000.0)),
+        ]));
+    }
+
+    if conn.retransmit_count > 0 {
+        details_text.push(Line::from(vec![
+            Span::styled("Retransmits: ", Style::default().fg(Color::Yellow)),
+            Span::raw(conn.retransmit_count.to_string()),
+        ]));
+    }
+
+    if let Some(guess) = &conn.remote_os_guess {
+        details_text.push(Line::from(vec![
+            Span::styled("Remote OS: ", Style::default().fg(Color::Yellow)),
+            Span::raw(guess.to_string()),
+        ]));
+    }
+
+    if let Some(geo) = &conn.geo_info {
+        details_text.push(Line::from(vec![
+            Span::styled("Location: ", Style::default().fg(Color::Yellow)),
+            Span::raw(geo.to_string()),
+        ]));
+    }
+
+    if let Some(scan) = conn.scan {
+        details_text.push(Line::from(vec![
+            Span::styled("Scan: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("⚑ part of a {}", scan.label()),
+                Style::default().fg(Color::Magenta),
+            ),
+        ]));
+    }
+
+    if conn.ignored {
+        details_text.push(Line::from(vec![
+            Span::styled("Ignored: ", Style::default().fg(Color::Yellow)),
+            Span::raw(if conn.alerts_muted {
+                "matched an ignore rule, not counted in totals or alerts"
+            } else {
+                "matched an ignore rule, not counted in totals"
+            }),
+        ]));
+    }
+
+    if conn.keepalive_only {
+        details_text.push(Line::from(vec![
+            Span::styled("Keepalive: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("idle, held open by {} probes", conn.keepalive_probes),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+
+    if conn.subflow_count > 0 {
+        details_text.push(Line::from(vec![
+            Span::styled("Flows: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!(
+                "{} from different local ports (Space to list them)",
+                conn.subflow_count
+            )),
+        ]));
+    }
+
     // Add DPI information
     match &conn.dpi_info {
         Some(dpi) => {
@@ -881,6 +1543,15 @@ fn draw_connection_details(
                             Span::raw(status.to_string()),
                         ]));
                     }
+                    if let Some(response_time) = info.response_time {
+                        details_text.push(Line::from(vec![
+                            Span::styled(
+                                "  HTTP Response Time: ",
+                                Style::default().fg(Color::Cyan),
+                            ),
+                            Span::raw(format!("{:.1} ms", response_time.as_secs_f64() * 1000.0)),
+                        ]));
+                    }
                 }
                 crate::network::types::ApplicationProtocol::Https(info) => {
                     if let Some(tls_info) = &info.tls_info {
@@ -897,11 +1568,36 @@ fn draw_connection_details(
                             ]));
                         }
                         if let Some(version) = &tls_info.version {
+                            let version_style = if version.is_deprecated() {
+                                Style::default().fg(Color::Red)
+                            } else {
+                                Style::default()
+                            };
                             details_text.push(Line::from(vec![
                                 Span::styled("  TLS Version: ", Style::default().fg(Color::Cyan)),
-                                Span::raw(version.to_string()),
+                                Span::styled(version.to_string(), version_style),
                             ]));
                         }
+                        for warning in &conn.tls_warnings {
+                            details_text.push(Line::from(vec![Span::styled(
+                                format!("  ⚠ {}", warning),
+                                Style::default().fg(Color::Red),
+                            )]));
+                        }
+                        if conn.possible_downgrade
+                            && let (Some(client), Some(server)) = (
+                                tls_info.client_max_tls_version,
+                                tls_info.server_negotiated_version,
+                            )
+                        {
+                            details_text.push(Line::from(vec![Span::styled(
+                                format!(
+                                    "  ⚠ Possible TLS downgrade: client offered {}, server chose {}",
+                                    client, server
+                                ),
+                                Style::default().fg(Color::Red),
+                            )]));
+                        }
                         if let Some(formatted_cipher) = tls_info.format_cipher_suite() {
                             let cipher_color = if tls_info.is_cipher_suite_secure().unwrap_or(false)
                             {
@@ -914,9 +1610,22 @@ fn draw_connection_details(
                                 Span::styled(formatted_cipher, Style::default().fg(cipher_color)),
                             ]));
                         }
+                        if tls_info.has_early_data {
+                            details_text.push(early_data_line());
+                        }
                     }
                 }
                 crate::network::types::ApplicationProtocol::Dns(info) => {
+                    if let Some(query_name) = &info.query_name {
+                        let query = match info.ttl {
+                            Some(ttl) => format!("{} (TTL: {}s)", query_name, ttl),
+                            None => query_name.clone(),
+                        };
+                        details_text.push(Line::from(vec![
+                            Span::styled("  DNS Query: ", Style::default().fg(Color::Cyan)),
+                            Span::raw(query),
+                        ]));
+                    }
                     if let Some(query_type) = &info.query_type {
                         details_text.push(Line::from(vec![
                             Span::styled("  DNS Type: ", Style::default().fg(Color::Cyan)),
@@ -929,6 +1638,22 @@ fn draw_connection_details(
                             Span::raw(format!("{:?}", info.response_ips)),
                         ]));
                     }
+                    if let Some(rcode) = &info.rcode {
+                        let status = if info.truncated {
+                            format!("{} (truncated, retry over TCP)", rcode)
+                        } else {
+                            rcode.to_string()
+                        };
+                        let status_color = if *rcode == DnsResponseCode::NoError {
+                            Color::Green
+                        } else {
+                            Color::Yellow
+                        };
+                        details_text.push(Line::from(vec![
+                            Span::styled("  DNS Status: ", Style::default().fg(Color::Cyan)),
+                            Span::styled(status, Style::default().fg(status_color)),
+                        ]));
+                    }
                 }
                 crate::network::types::ApplicationProtocol::Quic(info) => {
                     if let Some(tls_info) = &info.tls_info {
@@ -942,6 +1667,15 @@ fn draw_connection_details(
                             Span::styled("  QUIC ALPN: ", Style::default().fg(Color::Cyan)),
                             Span::raw(alpn),
                         ]));
+                        if tls_info.has_early_data {
+                            details_text.push(early_data_line());
+                        }
+                        for warning in &conn.tls_warnings {
+                            details_text.push(Line::from(vec![Span::styled(
+                                format!("  ⚠ {}", warning),
+                                Style::default().fg(Color::Red),
+                            )]));
+                        }
                     }
                     if let Some(version) = info.version_string.as_ref() {
                         details_text.push(Line::from(vec![
@@ -1003,6 +1737,12 @@ fn draw_connection_details(
                         ]));
                     }
                 }
+                crate::network::types::ApplicationProtocol::Custom(_) => {
+                    details_text.push(Line::from(vec![
+                        Span::styled("  Detected By: ", Style::default().fg(Color::Cyan)),
+                        Span::raw("custom signature"),
+                    ]));
+                }
             }
         }
         None => {
@@ -1025,31 +1765,61 @@ fn draw_connection_details(
     f.render_widget(details, chunks[0]);
 
     // Traffic details
+    let (average_out, average_in) = conn.throughput_window(Duration::from_secs(30));
     let traffic_text: Vec<Line> = vec![
         Line::from(vec![
-            Span::styled("Bytes Sent: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Bytes {}: ", sent),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(format_bytes(conn.bytes_sent)),
         ]),
         Line::from(vec![
-            Span::styled("Bytes Received: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Bytes {}: ", received),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(format_bytes(conn.bytes_received)),
         ]),
         Line::from(vec![
-            Span::styled("Packets Sent: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Packets {}: ", sent),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(conn.packets_sent.to_string()),
         ]),
         Line::from(vec![
-            Span::styled("Packets Received: ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Packets {}: ", received),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(conn.packets_received.to_string()),
         ]),
         Line::from(vec![
-            Span::styled("Current Rate (In): ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Current Rate ({}): ", rate_in),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(format_rate(conn.current_incoming_rate_bps)),
         ]),
         Line::from(vec![
-            Span::styled("Current Rate (Out): ", Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("Current Rate ({}): ", rate_out),
+                Style::default().fg(Color::Yellow),
+            ),
             Span::raw(format_rate(conn.current_outgoing_rate_bps)),
         ]),
+        Line::from(vec![
+            Span::styled(
+                format!("30s Average ({}/{}): ", rate_in, rate_out),
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::raw(format!(
+                "{} / {}",
+                format_rate(average_in),
+                format_rate(average_out)
+            )),
+        ]),
     ];
 
     let traffic = Paragraph::new(traffic_text)
@@ -1066,6 +1836,27 @@ fn draw_connection_details(
     Ok(())
 }
 
+/// Details line for a TLS or QUIC handshake that offered 0-RTT early data
+fn early_data_line() -> Line<'static> {
+    Line::from(vec![
+        Span::styled("  0-RTT: ", Style::default().fg(Color::Cyan)),
+        Span::styled(
+            "early data offered (replayable)",
+            Style::default().fg(Color::Yellow),
+        ),
+    ])
+}
+
+/// CPU and memory use of a connection's process, e.g. "12.5% CPU, 200.00 MB"
+fn process_usage_text(conn: &Connection) -> Option<String> {
+    let cpu = conn.cpu_usage.map(|cpu| format!("{:.1}% CPU", cpu));
+    let memory = conn.memory_usage.map(format_bytes);
+    match (cpu, memory) {
+        (Some(cpu), Some(memory)) => Some(format!("{}, {}", cpu, memory)),
+        (cpu, memory) => cpu.or(memory),
+    }
+}
+
 /// Draw help screen
 fn draw_help(f: &mut Frame, area: Rect) -> Result<()> {
     let help_text: Vec<Line> = vec![
@@ -1103,17 +1894,41 @@ fn draw_help(f: &mut Frame, area: Rect) -> Result<()> {
             Span::styled("Page Up/Down ", Style::default().fg(Color::Yellow)),
             Span::raw("Navigate connections by page"),
         ]),
+        Line::from(vec![
+            Span::styled("b ", Style::default().fg(Color::Yellow)),
+            Span::raw(
+                "Show per-connection bandwidth history; on Top, switch between remote hosts and bytes per process",
+            ),
+        ]),
         Line::from(vec![
             Span::styled("c ", Style::default().fg(Color::Yellow)),
             Span::raw("Copy remote address to clipboard"),
         ]),
+        Line::from(vec![
+            Span::styled("e ", Style::default().fg(Color::Yellow)),
+            Span::raw("Export all connections to a CSV file"),
+        ]),
+        Line::from(vec![
+            Span::styled("f ", Style::default().fg(Color::Yellow)),
+            Span::raw("Generate a firewall rule blocking the remote endpoint"),
+        ]),
+        Line::from(vec![
+            Span::styled("w ", Style::default().fg(Color::Yellow)),
+            Span::raw("Start or stop saving the selected connection's packets to a pcap file"),
+        ]),
         Line::from(vec![
             Span::styled("p ", Style::default().fg(Color::Yellow)),
             Span::raw("Toggle between service names and port numbers"),
         ]),
+        Line::from(vec![
+            Span::styled("r ", Style::default().fg(Color::Yellow)),
+            Span::raw("Refresh process information now"),
+        ]),
         Line::from(vec![
             Span::styled("s ", Style::default().fg(Color::Yellow)),
-            Span::raw("Cycle through sort columns (Bandwidth, Process, etc.)"),
+            Span::raw(
+                "Cycle through sort columns (Bandwidth, Process, etc.); on Top, rank by rate or bytes",
+            ),
         ]),
         Line::from(vec![
             Span::styled("S ", Style::default().fg(Color::Yellow)),
@@ -1121,7 +1936,19 @@ fn draw_help(f: &mut Frame, area: Rect) -> Result<()> {
         ]),
         Line::from(vec![
             Span::styled("Enter ", Style::default().fg(Color::Yellow)),
-            Span::raw("View connection details"),
+            Span::raw("View connection details; on Top, filter connections to the selected row"),
+        ]),
+        Line::from(vec![
+            Span::styled("Space ", Style::default().fg(Color::Yellow)),
+            Span::raw("Expand or collapse a row of coalesced UDP flows"),
+        ]),
+        Line::from(vec![
+            Span::styled("i ", Style::default().fg(Color::Yellow)),
+            Span::raw("Show or hide connections matched by the ignore file"),
+        ]),
+        Line::from(vec![
+            Span::styled("I ", Style::default().fg(Color::Yellow)),
+            Span::raw("Show each interface's traffic, error and drop counters"),
         ]),
         Line::from(vec![
             Span::styled("Esc ", Style::default().fg(Color::Yellow)),
@@ -1155,6 +1982,23 @@ fn draw_help(f: &mut Frame, area: Rect) -> Result<()> {
             Span::raw("Critical - will be removed soon (> 90% of timeout)"),
         ]),
         Line::from(""),
+        Line::from(vec![Span::styled(
+            "Capture Options:",
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )]),
+        Line::from(vec![
+            Span::styled("  --no-promisc ", Style::default().fg(Color::Green)),
+            Span::raw(
+                "Leave promiscuous mode off; other hosts' flows only appear if this host forwards them",
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("  --router-mode ", Style::default().fg(Color::Green)),
+            Span::raw("Whether flows between two other hosts count as forwarded (auto, on, off)"),
+        ]),
+        Line::from(""),
         Line::from(vec![Span::styled(
             "Filter Examples:",
             Style::default()
@@ -1199,19 +2043,627 @@ fn draw_help(f: &mut Frame, area: Rect) -> Result<()> {
     Ok(())
 }
 
-/// Draw filter input area
-fn draw_filter_input(f: &mut Frame, ui_state: &UIState, area: Rect) {
-    let title = if ui_state.filter_mode {
-        "Filter (↑↓/jk to navigate, Enter to confirm, Esc to cancel)"
-    } else {
-        "Active Filter (Press Esc to clear)"
-    };
+/// Draw the list of generated firewall rules
+fn draw_firewall_rules(f: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let rules = app.get_generated_rules();
+
+    if rules.is_empty() {
+        let text =
+            Paragraph::new("No firewall rules generated yet. Select a connection and press 'f'.")
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Firewall Rules"),
+                )
+                .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(text, area);
+        return Ok(());
+    }
 
-    let input_text = if ui_state.filter_mode {
-        // Show cursor when in filter mode
-        let mut display_query = ui_state.filter_query.clone();
-        if ui_state.filter_cursor_position <= display_query.len() {
-            display_query.insert(ui_state.filter_cursor_position, '|');
+    let rows: Vec<Row> = rules
+        .iter()
+        .map(|rule| {
+            let status_style = match rule.status {
+                crate::firewall::RuleStatus::Pending => Style::default().fg(Color::Yellow),
+                crate::firewall::RuleStatus::Applied => Style::default().fg(Color::Green),
+                crate::firewall::RuleStatus::Failed(_) => Style::default().fg(Color::Red),
+            };
+            Row::new([
+                Cell::from(rule.status.to_string()).style(status_style),
+                Cell::from(format!("{} {}", rule.protocol, rule.target)),
+                Cell::from(rule.command.clone()),
+            ])
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(12),
+            Constraint::Length(28),
+            Constraint::Min(20),
+        ],
+    )
+    .header(
+        Row::new(["Status", "Target", "Command"])
+            .style(Style::default().fg(Color::Yellow))
+            .bottom_margin(1),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Firewall Rules"),
+    );
+
+    f.render_widget(table, area);
+
+    Ok(())
+}
+
+/// Draw the most recently closed connections and why each one went away
+fn draw_closed_connections(f: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let closed = app.closed_connections();
+
+    if closed.is_empty() {
+        let text = Paragraph::new("No connections have closed yet.")
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Closed Connections"),
+            )
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(text, area);
+        return Ok(());
+    }
+
+    let rows: Vec<Row> = closed
+        .iter()
+        .map(|entry| {
+            let conn = &entry.connection;
+            let reason_style = match entry.reason {
+                CloseReason::FinClosed => Style::default().fg(Color::Green),
+                CloseReason::Reset => Style::default().fg(Color::Red),
+                CloseReason::IdleTimeout => Style::default().fg(Color::DarkGray),
+                CloseReason::Evicted => Style::default().fg(Color::Magenta),
+                CloseReason::SourceGone => Style::default().fg(Color::Yellow),
+            };
+            let closed_at: chrono::DateTime<chrono::Local> = entry.closed_at.into();
+            Row::new([
+                Cell::from(closed_at.format("%H:%M:%S").to_string()),
+                Cell::from(conn.protocol.to_string()),
+                Cell::from(conn.local_addr.to_string()),
+                Cell::from(conn.remote_addr.to_string()),
+                Cell::from(conn.state()),
+                Cell::from(entry.reason.to_string()).style(reason_style),
+                Cell::from(conn.process_name.clone().unwrap_or_else(|| "-".to_string())),
+                Cell::from(format_bytes(conn.bytes_sent + conn.bytes_received)),
+            ])
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(9),
+            Constraint::Length(6),
+            Constraint::Length(24),
+            Constraint::Length(24),
+            Constraint::Length(14),
+            Constraint::Length(12),
+            Constraint::Min(12),
+            Constraint::Length(10),
+        ],
+    )
+    .header(
+        Row::new([
+            "Closed",
+            "Pro",
+            "Local",
+            "Remote",
+            "Last State",
+            "Reason",
+            "Process",
+            "Bytes",
+        ])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1),
+    )
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Closed Connections ({})", closed.len())),
+    );
+
+    f.render_widget(table, area);
+
+    Ok(())
+}
+
+/// Draw the kernel's traffic counters for each interface
+fn draw_interfaces(f: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    let block = Block::default().borders(Borders::ALL).title("Interfaces");
+    let message = match app.interface_stats() {
+        Ok(stats) if !stats.is_empty() => {
+            let capturing = app.get_current_interface();
+            let count_style = |count: u64| {
+                if count > 0 {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                }
+            };
+            let rows: Vec<Row> = stats
+                .iter()
+                .map(|iface| {
+                    let c = &iface.counters;
+                    let rate = |bytes_per_sec: fn(&InterfaceRates) -> f64| {
+                        iface
+                            .rates
+                            .as_ref()
+                            .map_or_else(|| "-".to_string(), |r| format_rate(bytes_per_sec(r)))
+                    };
+                    let drops = c.rx_dropped + c.tx_dropped;
+                    let row = Row::new([
+                        Cell::from(iface.name.clone()),
+                        Cell::from(rate(|r| r.rx_bytes_per_sec)),
+                        Cell::from(rate(|r| r.tx_bytes_per_sec)),
+                        Cell::from(format_bytes(c.rx_bytes)),
+                        Cell::from(format_bytes(c.tx_bytes)),
+                        Cell::from(c.rx_packets.to_string()),
+                        Cell::from(c.tx_packets.to_string()),
+                        Cell::from(format!("{}/{}", c.rx_errors, c.tx_errors))
+                            .style(count_style(c.rx_errors + c.tx_errors)),
+                        Cell::from(drops.to_string()).style(count_style(drops)),
+                    ]);
+                    if capturing.as_deref() == Some(iface.name.as_str()) {
+                        row.style(Style::default().add_modifier(Modifier::BOLD))
+                    } else {
+                        row
+                    }
+                })
+                .collect();
+
+            let table = Table::new(
+                rows,
+                [
+                    Constraint::Min(12),
+                    Constraint::Length(12),
+                    Constraint::Length(12),
+                    Constraint::Length(10),
+                    Constraint::Length(10),
+                    Constraint::Length(12),
+                    Constraint::Length(12),
+                    Constraint::Length(10),
+                    Constraint::Length(8),
+                ],
+            )
+            .header(
+                Row::new([
+                    "Interface",
+                    "RX Rate",
+                    "TX Rate",
+                    "RX Bytes",
+                    "TX Bytes",
+                    "RX Packets",
+                    "TX Packets",
+                    "Errors",
+                    "Drops",
+                ])
+                .style(Style::default().fg(Color::Yellow))
+                .bottom_margin(1),
+            )
+            .block(block);
+            f.render_widget(table, area);
+            return Ok(());
+        }
+        Ok(_) => "No interfaces found.".to_string(),
+        Err(e) => format!("No interface counters: {:#}", e),
+    };
+
+    let text = Paragraph::new(message)
+        .block(block)
+        .alignment(ratatui::layout::Alignment::Center);
+    f.render_widget(text, area);
+    Ok(())
+}
+
+/// Remote hosts or processes listed on the Top tab
+pub const TOP_TALKERS: usize = 25;
+
+/// Bar of `width` cells filled in proportion to `value / total`, then the percentage
+fn share_bar(value: f64, total: f64, width: usize) -> String {
+    let fraction = if total > 0.0 {
+        (value / total).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let filled = (fraction * width as f64).round() as usize;
+    format!(
+        "{}{} {:>3.0}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        fraction * 100.0
+    )
+}
+
+/// Draw each connection's bytes per second over the last minute
+fn draw_bandwidth(
+    f: &mut Frame,
+    ui_state: &UIState,
+    connections: &[&Connection],
+    area: Rect,
+) -> Result<()> {
+    let title = "Bandwidth (bytes per second, last 60 s)";
+
+    if connections.is_empty() {
+        let text = Paragraph::new("No connections yet.")
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(text, area);
+        return Ok(());
+    }
+
+    let rows: Vec<Row> = connections
+        .iter()
+        .map(|conn| {
+            let totals: Vec<f64> = conn
+                .rate_history()
+                .into_iter()
+                .map(|(sent, received)| sent + received)
+                .collect();
+            let peak = totals.iter().copied().fold(0.0, f64::max);
+            let remote = match &conn.hostname {
+                Some(hostname) => format!("{}:{}", hostname, conn.remote_addr.port()),
+                None => conn.remote_addr.to_string(),
+            };
+            Row::new([
+                Cell::from(conn.protocol.to_string()),
+                Cell::from(remote),
+                Cell::from(conn.process_name.clone().unwrap_or_else(|| "-".to_string())),
+                Cell::from(format_rate(
+                    conn.current_incoming_rate_bps + conn.current_outgoing_rate_bps,
+                )),
+                Cell::from(format_rate(peak)),
+                Cell::from(sparkline(&totals)).style(Style::default().fg(Color::Cyan)),
+            ])
+        })
+        .collect();
+
+    let mut state = ratatui::widgets::TableState::default();
+    state.select(ui_state.get_selected_index(connections));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(6),
+            Constraint::Min(24),
+            Constraint::Length(16),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(THROUGHPUT_HISTORY_SECS as u16),
+        ],
+    )
+    .header(
+        Row::new(["Proto", "Remote", "Process", "Rate", "Peak", "History"])
+            .style(Style::default().fg(Color::Yellow))
+            .bottom_margin(1),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .highlight_symbol("> ");
+
+    f.render_stateful_widget(table, area, &mut state);
+
+    Ok(())
+}
+
+/// Draw the remote hosts with the most traffic
+fn draw_top_talkers(
+    f: &mut Frame,
+    ui_state: &UIState,
+    snapshot: &ConnectionSnapshot,
+    area: Rect,
+) -> Result<()> {
+    if ui_state.show_processes {
+        return draw_process_bandwidth(f, ui_state, snapshot, area);
+    }
+
+    let top = talkers::top_talkers(&snapshot.connections, TOP_TALKERS, ui_state.talker_order);
+    let title = match ui_state.talker_order {
+        TalkerOrder::Rate => "Top Remote Hosts by Rate (s: by bytes, b: processes, Enter: filter)",
+        TalkerOrder::Bytes => "Top Remote Hosts by Bytes (s: by rate, b: processes, Enter: filter)",
+    };
+
+    if top.talkers.is_empty() {
+        let text = Paragraph::new("No remote hosts yet.")
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(text, area);
+        return Ok(());
+    }
+
+    let rows: Vec<Row> = top
+        .talkers
+        .iter()
+        .enumerate()
+        .map(|(rank, talker)| {
+            Row::new([
+                Cell::from(format!("{}", rank + 1)),
+                Cell::from(talker.host.clone()),
+                Cell::from(talker.connections.to_string()),
+                Cell::from(format_rate(talker.rate_bps)),
+                Cell::from(share_bar(talker.rate_bps, top.total_rate_bps, 20))
+                    .style(Style::default().fg(Color::Cyan)),
+                Cell::from(format_bytes(talker.bytes)),
+                Cell::from(share_bar(talker.bytes as f64, top.total_bytes as f64, 20))
+                    .style(Style::default().fg(Color::Green)),
+            ])
+        })
+        .collect();
+
+    let mut state = ratatui::widgets::TableState::default();
+    state.select(Some(ui_state.selected_talker.min(top.talkers.len() - 1)));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Min(24),
+            Constraint::Length(6),
+            Constraint::Length(12),
+            Constraint::Length(26),
+            Constraint::Length(10),
+            Constraint::Length(26),
+        ],
+    )
+    .header(
+        Row::new([
+            "#",
+            "Host",
+            "Flows",
+            "Rate",
+            "Share of Rate",
+            "Bytes",
+            "Share of Bytes",
+        ])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .highlight_symbol("> ");
+
+    f.render_stateful_widget(table, area, &mut state);
+
+    Ok(())
+}
+
+/// Draw the local processes that moved the most bytes
+fn draw_process_bandwidth(
+    f: &mut Frame,
+    ui_state: &UIState,
+    snapshot: &ConnectionSnapshot,
+    area: Rect,
+) -> Result<()> {
+    let processes = talkers::process_bandwidth(&snapshot.connections);
+    let title = "Top Processes by Bytes (b: remote hosts, Enter: filter)";
+
+    if processes.is_empty() {
+        let text = Paragraph::new("No processes yet.")
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(text, area);
+        return Ok(());
+    }
+
+    let total_bytes: u64 = processes
+        .iter()
+        .map(|(_, sent, received)| sent + received)
+        .sum();
+    let rows: Vec<Row> = processes
+        .iter()
+        .take(TOP_TALKERS)
+        .enumerate()
+        .map(|(rank, (process, sent, received))| {
+            Row::new([
+                Cell::from(format!("{}", rank + 1)),
+                Cell::from(process.clone()),
+                Cell::from(format_bytes(*sent)),
+                Cell::from(format_bytes(*received)),
+                Cell::from(format_bytes(sent + received)),
+                Cell::from(share_bar((sent + received) as f64, total_bytes as f64, 30))
+                    .style(Style::default().fg(Color::Green)),
+            ])
+        })
+        .collect();
+
+    let mut state = ratatui::widgets::TableState::default();
+    state.select(Some(ui_state.selected_talker.min(rows.len() - 1)));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Min(24),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(36),
+        ],
+    )
+    .header(
+        Row::new([
+            "#",
+            "Process",
+            "Sent",
+            "Received",
+            "Total",
+            "Share of Bytes",
+        ])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .highlight_symbol("> ");
+
+    f.render_stateful_widget(table, area, &mut state);
+
+    Ok(())
+}
+
+/// Heatmap color for a bucket: background for zero, green shades, white at the maximum
+fn heatmap_color(bytes: u64, max: u64) -> Option<Color> {
+    const GREENS: [Color; 4] = [
+        Color::Rgb(0, 68, 27),
+        Color::Rgb(0, 109, 44),
+        Color::Rgb(35, 139, 69),
+        Color::Rgb(116, 196, 118),
+    ];
+
+    if bytes == 0 || max == 0 {
+        None
+    } else if bytes >= max {
+        Some(Color::White)
+    } else {
+        let level = (bytes as f64 / max as f64 * GREENS.len() as f64) as usize;
+        Some(GREENS[level.min(GREENS.len() - 1)])
+    }
+}
+
+/// Draw the weekly traffic heatmap (day-of-week x hour-of-day)
+fn draw_heatmap(f: &mut Frame, app: &App, area: Rect) -> Result<()> {
+    const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+    let buckets = app.heatmap();
+    let max = buckets.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut lines = Vec::with_capacity(DAYS.len() + 4);
+
+    // Hour axis, labelled every third hour to keep cells two characters wide
+    let mut hour_axis = String::from("     ");
+    for hour in 0..24 {
+        if hour % 3 == 0 {
+            hour_axis.push_str(&format!("{:02}", hour));
+        } else {
+            hour_axis.push_str("  ");
+        }
+        hour_axis.push(' ');
+    }
+    lines.push(Line::from(Span::styled(
+        hour_axis,
+        Style::default().fg(Color::Yellow),
+    )));
+
+    for (day, hours) in DAYS.iter().zip(buckets.iter()) {
+        let mut spans = vec![Span::styled(
+            format!("{}  ", day),
+            Style::default().fg(Color::Yellow),
+        )];
+        for &bytes in hours {
+            let cell = match heatmap_color(bytes, max) {
+                Some(color) => Span::styled("██", Style::default().fg(color)),
+                None => Span::styled("··", Style::default().fg(Color::DarkGray)),
+            };
+            spans.push(cell);
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::from(""));
+    let mut legend = vec![
+        Span::raw("Less "),
+        Span::styled("··", Style::default().fg(Color::DarkGray)),
+    ];
+    for fraction in [0.1, 0.35, 0.6, 0.85, 1.0] {
+        let color = heatmap_color((fraction * 100.0) as u64, 100).unwrap_or(Color::Reset);
+        legend.push(Span::raw(" "));
+        legend.push(Span::styled("██", Style::default().fg(color)));
+    }
+    legend.push(Span::raw(format!(
+        " More   (max {} per hour)",
+        format_bytes(max)
+    )));
+    lines.push(Line::from(legend));
+
+    let heatmap = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Traffic Heatmap (bytes per hour)"),
+    );
+    f.render_widget(heatmap, area);
+
+    Ok(())
+}
+
+/// Draw the popup for a freshly generated firewall rule
+fn draw_firewall_popup(f: &mut Frame, app: &App, ui_state: &UIState, index: usize, area: Rect) {
+    let Some(rule) = app.get_generated_rules().into_iter().nth(index) else {
+        return;
+    };
+
+    let prompt = if ui_state.firewall_confirm {
+        Line::from(Span::styled(
+            "Execute this command now? [y] confirm  [any other key] cancel",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ))
+    } else {
+        Line::from(Span::styled(
+            "[c] copy to clipboard  [x] execute  [Esc] close",
+            Style::default().fg(Color::Yellow),
+        ))
+    };
+
+    let text = vec![
+        Line::from(vec![
+            Span::styled("Block: ", Style::default().fg(Color::Cyan)),
+            Span::raw(format!("{} {}", rule.protocol, rule.target)),
+        ]),
+        Line::from(vec![
+            Span::styled("Status: ", Style::default().fg(Color::Cyan)),
+            Span::raw(rule.status.to_string()),
+        ]),
+        Line::from(""),
+        Line::from(rule.command),
+        Line::from(""),
+        prompt,
+    ];
+
+    let width = area.width.saturating_sub(4).min(100);
+    let height = 10.min(area.height);
+    let popup_area = Rect {
+        x: area.x + (area.width.saturating_sub(width)) / 2,
+        y: area.y + (area.height.saturating_sub(height)) / 2,
+        width,
+        height,
+    };
+
+    let popup = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Firewall Rule"),
+        )
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+/// Draw filter input area
+fn draw_filter_input(f: &mut Frame, ui_state: &UIState, area: Rect) {
+    let title = if ui_state.filter_mode {
+        "Filter (↑↓/jk to navigate, Enter to confirm, Esc to cancel)"
+    } else {
+        "Active Filter (Press Esc to clear)"
+    };
+
+    let input_text = if ui_state.filter_mode {
+        // Show cursor when in filter mode
+        let mut display_query = ui_state.filter_query.clone();
+        if ui_state.filter_cursor_position <= display_query.len() {
+            display_query.insert(ui_state.filter_cursor_position, '|');
         }
         display_query
     } else {
@@ -1232,9 +2684,16 @@ fn draw_filter_input(f: &mut Frame, ui_state: &UIState, area: Rect) {
     f.render_widget(filter_input, area);
 }
 
-/// Draw status bar
-fn draw_status_bar(f: &mut Frame, ui_state: &UIState, connection_count: usize, area: Rect) {
-    let status = if ui_state.quit_confirmation {
+/// Status bar text for `connection_count` listed connections, with
+/// `ignored_count` of the snapshot's connections matched by ignore rules
+fn status_text(ui_state: &UIState, connection_count: usize, ignored_count: usize) -> String {
+    let ignored = match (ignored_count, ui_state.show_ignored) {
+        (0, _) => String::new(),
+        (count, false) => format!("| {} ignored hidden ('i' to show) ", count),
+        (count, true) => format!("| {} ignored shown ('i' to hide) ", count),
+    };
+
+    if ui_state.quit_confirmation {
         " Press 'q' again to quit or any other key to cancel ".to_string()
     } else if let Some((ref msg, ref time)) = ui_state.clipboard_message {
         // Show clipboard message for 3 seconds
@@ -1242,21 +2701,58 @@ fn draw_status_bar(f: &mut Frame, ui_state: &UIState, connection_count: usize, a
             format!(" {} ", msg)
         } else {
             format!(
-                " Press 'h' for help | 'c' to copy address | Connections: {} ",
-                connection_count
+                " Press 'h' for help | 'c' to copy address | Connections: {} {}",
+                connection_count, ignored
             )
         }
     } else if !ui_state.filter_query.is_empty() {
         format!(
-            " Press 'h' for help | '/' to filter | Showing {} filtered connections (Esc to clear filter) ",
-            connection_count
+            " Press 'h' for help | '/' to filter | Showing {} filtered connections (Esc to clear filter) {}",
+            connection_count, ignored
         )
     } else {
         format!(
-            " Press 'h' for help | '/' to filter & navigate | 'c' to copy address | Connections: {} ",
-            connection_count
+            " Press 'h' for help | '/' to filter & navigate | 'c' to copy address | Connections: {} {}",
+            connection_count, ignored
         )
-    };
+    }
+}
+
+/// Kernel capture counters for the status bar. Drops mean byte counts are
+/// short, so any there have been are highlighted.
+fn capture_stats_span(snapshot: &ConnectionSnapshot) -> Span<'static> {
+    let dropped = snapshot.packets_dropped + snapshot.interface_dropped;
+    let text = format!(
+        "| Captured: {} | Dropped: {} ({}/s) ",
+        snapshot.capture_received, dropped, snapshot.drops_per_sec
+    );
+    if dropped > 0 {
+        Span::styled(
+            text,
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::raw(text)
+    }
+}
+
+/// Draw status bar
+fn draw_status_bar(
+    f: &mut Frame,
+    ui_state: &UIState,
+    snapshot: &ConnectionSnapshot,
+    connection_count: usize,
+    ignored_count: usize,
+    area: Rect,
+) {
+    let mut status = vec![Span::raw(status_text(
+        ui_state,
+        connection_count,
+        ignored_count,
+    ))];
 
     let style = if ui_state.quit_confirmation {
         Style::default().fg(Color::Black).bg(Color::Yellow)
@@ -1272,10 +2768,11 @@ fn draw_status_bar(f: &mut Frame, ui_state: &UIState, connection_count: usize, a
     {
         Style::default().fg(Color::Black).bg(Color::Green)
     } else {
+        status.push(capture_stats_span(snapshot));
         Style::default().fg(Color::White).bg(Color::Blue)
     };
 
-    let status_bar = Paragraph::new(status)
+    let status_bar = Paragraph::new(Line::from(status))
         .style(style)
         .alignment(ratatui::layout::Alignment::Left);
 
@@ -1318,7 +2815,7 @@ fn draw_loading_screen(f: &mut Frame) {
 }
 
 /// Format rate to human readable form
-fn format_rate(bytes_per_second: f64) -> String {
+pub(crate) fn format_rate(bytes_per_second: f64) -> String {
     const KB_PER_SEC: f64 = 1024.0;
     const MB_PER_SEC: f64 = KB_PER_SEC * 1024.0;
     const GB_PER_SEC: f64 = MB_PER_SEC * 1024.0;
@@ -1355,8 +2852,23 @@ fn format_rate_compact(bytes_per_second: f64) -> String {
     }
 }
 
+/// Round-trip time in at most 5 characters, or "-" without an estimate
+fn format_rtt_compact(rtt: Option<Duration>) -> String {
+    let Some(rtt) = rtt else {
+        return "-".to_string();
+    };
+    let ms = rtt.as_secs_f64() * 1000.0;
+    if ms < 10.0 {
+        format!("{:.1}ms", ms)
+    } else if ms < 999.5 {
+        format!("{:.0}ms", ms)
+    } else {
+        format!("{:.1}s", ms / 1000.0)
+    }
+}
+
 /// Format bytes to human readable form
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -1376,6 +2888,62 @@ fn format_bytes(bytes: u64) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sparkline_scales_to_the_peak() {
+        assert_eq!(sparkline(&[0.0, 1.0, 4.0, 8.0, 0.5]), "▁▂▅█▁");
+        assert_eq!(sparkline(&[0.0; 3]), "▁▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+
+    #[test]
+    fn test_capture_drops_are_highlighted() {
+        let mut snapshot = ConnectionSnapshot {
+            capture_received: 1000,
+            ..Default::default()
+        };
+        assert_eq!(capture_stats_span(&snapshot).style, Style::default());
+
+        snapshot.packets_dropped = 5;
+        snapshot.interface_dropped = 2;
+        snapshot.drops_per_sec = 3;
+        let span = capture_stats_span(&snapshot);
+        assert_eq!(span.content, "| Captured: 1000 | Dropped: 7 (3/s) ");
+        assert_eq!(span.style.bg, Some(Color::Red));
+    }
+
+    #[test]
+    fn test_process_usage_text() {
+        use crate::network::types::{Protocol, ProtocolState, TcpState};
+
+        let mut conn = Connection::new(
+            Protocol::TCP,
+            "192.168.1.10:54321".parse().unwrap(),
+            "192.0.2.1:443".parse().unwrap(),
+            ProtocolState::Tcp(TcpState::Established),
+        );
+        assert_eq!(process_usage_text(&conn), None);
+
+        // The first sample has memory but no CPU use yet
+        conn.memory_usage = Some(200 * 1024 * 1024);
+        assert_eq!(process_usage_text(&conn).unwrap(), "200.00 MB");
+        conn.cpu_usage = Some(12.54);
+        assert_eq!(process_usage_text(&conn).unwrap(), "12.5% CPU, 200.00 MB");
+    }
+
+    #[test]
+    fn test_rtt_column() {
+        assert_eq!(format_rtt_compact(None), "-");
+        assert_eq!(
+            format_rtt_compact(Some(Duration::from_micros(2_340))),
+            "2.3ms"
+        );
+        assert_eq!(format_rtt_compact(Some(Duration::from_millis(87))), "87ms");
+        assert_eq!(
+            format_rtt_compact(Some(Duration::from_millis(1_500))),
+            "1.5s"
+        );
+    }
+
     #[test]
     fn test_port_toggle_default_state() {
         let ui_state = UIState::default();
@@ -1409,17 +2977,25 @@ mod tests {
         assert_eq!(Service.next(), Application);
         assert_eq!(Application.next(), BandwidthDown);
         assert_eq!(BandwidthDown.next(), BandwidthUp);
-        assert_eq!(BandwidthUp.next(), Process);
-        assert_eq!(Process.next(), CreatedAt); // Cycles back
+        assert_eq!(BandwidthUp.next(), Rtt);
+        assert_eq!(Rtt.next(), Process);
+        assert_eq!(Process.next(), LastActivity);
+        assert_eq!(LastActivity.next(), BytesReceived);
+        assert_eq!(BytesReceived.next(), BytesSent);
+        assert_eq!(BytesSent.next(), CreatedAt); // Cycles back
     }
 
     #[test]
     fn test_sort_column_default_directions() {
         use SortColumn::*;
 
-        // Bandwidth should default to descending (false)
+        // Bandwidth, RTT, activity and byte totals should default to descending (false)
         assert!(!BandwidthDown.default_direction());
         assert!(!BandwidthUp.default_direction());
+        assert!(!Rtt.default_direction());
+        assert!(!LastActivity.default_direction());
+        assert!(!BytesReceived.default_direction());
+        assert!(!BytesSent.default_direction());
 
         // Everything else should default to ascending (true)
         assert!(Process.default_direction());
@@ -1492,6 +3068,7 @@ mod tests {
         assert_eq!(CreatedAt.display_name(), "Time");
         assert_eq!(BandwidthDown.display_name(), "Bandwidth ↓");
         assert_eq!(BandwidthUp.display_name(), "Bandwidth ↑");
+        assert_eq!(Rtt.display_name(), "RTT");
         assert_eq!(Process.display_name(), "Process");
         assert_eq!(LocalAddress.display_name(), "Local Addr");
         assert_eq!(RemoteAddress.display_name(), "Remote Addr");
@@ -1499,6 +3076,9 @@ mod tests {
         assert_eq!(Service.display_name(), "Service");
         assert_eq!(State.display_name(), "State");
         assert_eq!(Protocol.display_name(), "Protocol");
+        assert_eq!(LastActivity.display_name(), "Last Activity");
+        assert_eq!(BytesReceived.display_name(), "Bytes Received");
+        assert_eq!(BytesSent.display_name(), "Bytes Sent");
     }
 
     #[test]
@@ -1577,6 +3157,13 @@ mod tests {
         connections[1].process_name = Some("alpha".to_string());
         connections[2].process_name = Some("beta".to_string());
 
+        // The UI borrows from a shared snapshot rather than owning the connections
+        let snapshot = ConnectionSnapshot {
+            connections,
+            ..Default::default()
+        };
+        let mut connections: Vec<&Connection> = snapshot.connections.iter().collect();
+
         // Create UI state
         let mut ui_state = UIState::default();
 
@@ -1609,4 +3196,217 @@ mod tests {
         assert_eq!(ui_state.get_selected_index(&connections), Some(0), "Should move to index 0");
         assert_eq!(ui_state.selected_connection_key, Some(connections[0].key()));
     }
+
+    #[test]
+    fn test_heatmap_color_gradient() {
+        assert_eq!(heatmap_color(0, 1000), None);
+        assert_eq!(heatmap_color(1000, 1000), Some(Color::White));
+        assert_eq!(heatmap_color(10, 1000), Some(Color::Rgb(0, 68, 27)));
+        assert_eq!(heatmap_color(900, 1000), Some(Color::Rgb(116, 196, 118)));
+    }
+
+    #[test]
+    fn test_selection_survives_new_snapshot() {
+        use crate::network::types::{Protocol, ProtocolState, TcpState};
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+        use std::sync::Arc;
+
+        let make_conn = |port: u16| {
+            Connection::new(
+                Protocol::TCP,
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), port),
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 443),
+                ProtocolState::Tcp(TcpState::Established),
+            )
+        };
+
+        let first = Arc::new(ConnectionSnapshot {
+            connections: vec![make_conn(1000), make_conn(1001), make_conn(1002)],
+            ..Default::default()
+        });
+        let mut ui_state = UIState::default();
+        let mut view = ConnectionView::default();
+        view.apply_snapshot(&first, &ui_state);
+        ui_state.set_selected_by_index(&view.connections(), 1);
+
+        // A newer snapshot drops the first connection; the old Arc is still readable
+        let second = Arc::new(ConnectionSnapshot {
+            connections: vec![make_conn(1001), make_conn(1002)],
+            ..Default::default()
+        });
+        let held = Arc::clone(&first);
+        view.apply_snapshot(&second, &ui_state);
+        assert_eq!(ui_state.get_selected_index(&view.connections()), Some(0));
+        assert_eq!(held.connections.len(), 3);
+
+        // Filtering borrows matching entries from the same snapshot
+        ui_state.filter_query = "sport:1002".to_string();
+        view.apply_snapshot(&second, &ui_state);
+        let filtered = view.connections();
+        assert_eq!(filtered.len(), 1);
+        assert!(std::ptr::eq(filtered[0], &second.connections[1]));
+    }
+
+    fn view_test_snapshot(rates: &[(u16, f64)]) -> Arc<ConnectionSnapshot> {
+        use crate::network::types::{ProtocolState, TcpState};
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let connections = rates
+            .iter()
+            .map(|&(port, rate)| {
+                let mut conn = Connection::new(
+                    Protocol::TCP,
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), port),
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 443),
+                    ProtocolState::Tcp(TcpState::Established),
+                );
+                // Creation time follows the port so ties break deterministically
+                conn.created_at = UNIX_EPOCH + Duration::from_secs(port as u64);
+                conn.last_activity = SystemTime::now();
+                conn.current_incoming_rate_bps = rate;
+                conn
+            })
+            .collect();
+        Arc::new(ConnectionSnapshot {
+            connections,
+            ..Default::default()
+        })
+    }
+
+    fn view_ports(view: &ConnectionView) -> Vec<u16> {
+        view.connections()
+            .iter()
+            .map(|conn| conn.local_addr.port())
+            .collect()
+    }
+
+    #[test]
+    fn test_view_incremental_update_matches_rebuild() {
+        let ui_state = UIState {
+            sort_column: SortColumn::BandwidthDown,
+            sort_ascending: false,
+            ..Default::default()
+        };
+
+        let mut view = ConnectionView::default();
+        let first = view_test_snapshot(&[(1000, 10.0), (1001, 30.0), (1002, 20.0)]);
+        assert_eq!(view.apply_snapshot(&first, &ui_state), ViewUpdate::Rebuilt);
+        assert_eq!(
+            view.apply_snapshot(&first, &ui_state),
+            ViewUpdate::Unchanged
+        );
+        assert_eq!(view_ports(&view), vec![1001, 1002, 1000]);
+
+        // 1000 disappears, 1003 is new and 1002 overtakes 1001
+        let second = view_test_snapshot(&[(1001, 5.0), (1002, 20.0), (1003, 0.0)]);
+        assert_eq!(
+            view.apply_snapshot(&second, &ui_state),
+            ViewUpdate::Resorted
+        );
+
+        let mut rebuilt = ConnectionView::default();
+        assert_eq!(
+            rebuilt.apply_snapshot(&second, &ui_state),
+            ViewUpdate::Rebuilt
+        );
+        assert_eq!(view_ports(&view), view_ports(&rebuilt));
+        assert_eq!(view_ports(&view), vec![1002, 1001, 1003]);
+    }
+
+    #[test]
+    fn test_view_skips_sort_for_unchanged_order() {
+        let mut ui_state = UIState {
+            sort_column: SortColumn::BandwidthDown,
+            sort_ascending: false,
+            ..Default::default()
+        };
+        let mut view = ConnectionView::default();
+        view.apply_snapshot(&view_test_snapshot(&[(1000, 1.0), (1001, 2.0)]), &ui_state);
+
+        // Same flows with new rates that keep their relative order
+        let next = view_test_snapshot(&[(1000, 3.0), (1001, 4.0)]);
+        assert_eq!(view.apply_snapshot(&next, &ui_state), ViewUpdate::Reused);
+        assert_eq!(view_ports(&view), vec![1001, 1000]);
+
+        // Changing the filter rebuilds against the same snapshot
+        ui_state.filter_query = "sport:1000".to_string();
+        assert_eq!(view.apply_snapshot(&next, &ui_state), ViewUpdate::Rebuilt);
+        assert_eq!(view_ports(&view), vec![1000]);
+    }
+
+    #[test]
+    fn test_ignored_connections_are_hidden_until_revealed() {
+        let mut ui_state = UIState {
+            sort_column: SortColumn::BandwidthDown,
+            ..Default::default()
+        };
+        let mut snapshot =
+            Arc::into_inner(view_test_snapshot(&[(1000, 1.0), (1001, 2.0), (1002, 3.0)])).unwrap();
+        snapshot.connections[1].ignored = true;
+        let snapshot = Arc::new(snapshot);
+
+        let mut view = ConnectionView::default();
+        view.apply_snapshot(&snapshot, &ui_state);
+        assert_eq!(view_ports(&view), vec![1000, 1002]);
+        assert_eq!(
+            status_text(&ui_state, 2, 1),
+            " Press 'h' for help | '/' to filter & navigate | 'c' to copy address | Connections: 2 | 1 ignored hidden ('i' to show) "
+        );
+
+        ui_state.show_ignored = true;
+        assert_eq!(
+            view.apply_snapshot(&snapshot, &ui_state),
+            ViewUpdate::Rebuilt
+        );
+        assert_eq!(view_ports(&view), vec![1000, 1001, 1002]);
+        assert!(status_text(&ui_state, 3, 1).ends_with("| 1 ignored shown ('i' to hide) "));
+
+        // Without ignore rules the status bar doesn't mention them
+        assert!(!status_text(&ui_state, 3, 0).contains("ignored"));
+    }
+
+    #[test]
+    fn test_expanded_rows_list_their_flows() {
+        use crate::network::coalesce::{DEFAULT_SERVER_PORTS, coalesce_flows};
+        use crate::network::types::{ProtocolState, UdpState};
+
+        let flows = [50001, 50002]
+            .into_iter()
+            .map(|port| {
+                Connection::new(
+                    Protocol::UDP,
+                    format!("192.168.1.10:{}", port).parse().unwrap(),
+                    "1.1.1.1:53".parse().unwrap(),
+                    ProtocolState::Udp(UdpState::Active),
+                )
+            })
+            .collect();
+        let (connections, subflows) = coalesce_flows(flows, &DEFAULT_SERVER_PORTS);
+        let snapshot = Arc::new(ConnectionSnapshot {
+            connections,
+            subflows,
+            ..Default::default()
+        });
+
+        let mut ui_state = UIState::default();
+        let mut view = ConnectionView::default();
+        view.apply_snapshot(&snapshot, &ui_state);
+        assert_eq!(view_ports(&view), vec![0]);
+
+        assert!(ui_state.toggle_expanded(&view.connections()));
+        view.apply_snapshot(&snapshot, &ui_state);
+        assert_eq!(view_ports(&view), vec![0, 50001, 50002]);
+        let rows = view.connections();
+        assert!(!snapshot.is_subflow(rows[0]));
+        assert!(rows[1..].iter().all(|conn| snapshot.is_subflow(conn)));
+
+        // Flows themselves don't expand; collapsing goes through the row
+        ui_state.set_selected_by_index(&rows, 1);
+        assert!(!ui_state.toggle_expanded(&rows));
+        ui_state.set_selected_by_index(&rows, 0);
+        assert!(ui_state.toggle_expanded(&rows));
+        view.apply_snapshot(&snapshot, &ui_state);
+        assert_eq!(view_ports(&view), vec![0]);
+    }
 }
@@ -0,0 +1,148 @@
+//! The packet path must stay quiet at info level and above
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rustnet_monitor::network::merge::{
+    MergeOptions, create_connection_from_packet, merge_packet_into_connection,
+};
+use rustnet_monitor::network::parser::PacketParser;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// Records (level, target) of every log call
+struct CountingLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CountingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.target().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CountingLogger = CountingLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+const CLIENT: [u8; 4] = [192, 0, 2, 10];
+const SERVER: [u8; 4] = [198, 51, 100, 7];
+
+/// Build an Ethernet/IPv4 frame around a transport header and payload
+fn ipv4_frame(src: [u8; 4], dst: [u8; 4], protocol: u8, transport: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; 12];
+    frame.extend_from_slice(&0x0800u16.to_be_bytes());
+    frame.extend_from_slice(&[0x45, 0]);
+    frame.extend_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(transport);
+    frame
+}
+
+fn tcp_frame(outgoing: bool, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let (src, dst, sport, dport) = if outgoing {
+        (CLIENT, SERVER, 50000u16, 80u16)
+    } else {
+        (SERVER, CLIENT, 80, 50000)
+    };
+    let mut tcp = Vec::new();
+    tcp.extend_from_slice(&sport.to_be_bytes());
+    tcp.extend_from_slice(&dport.to_be_bytes());
+    tcp.extend_from_slice(&[0; 8]);
+    tcp.extend_from_slice(&[0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    tcp.extend_from_slice(payload);
+    ipv4_frame(src, dst, 6, &tcp)
+}
+
+fn dns_query_frame() -> Vec<u8> {
+    let mut dns = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in ["example", "com"] {
+        dns.push(label.len() as u8);
+        dns.extend_from_slice(label.as_bytes());
+    }
+    dns.extend_from_slice(&[0, 0, 1, 0, 1]);
+
+    let mut udp = Vec::new();
+    udp.extend_from_slice(&53000u16.to_be_bytes());
+    udp.extend_from_slice(&53u16.to_be_bytes());
+    udp.extend_from_slice(&((8 + dns.len()) as u16).to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(&dns);
+    ipv4_frame(CLIENT, SERVER, 17, &udp)
+}
+
+#[test]
+fn test_packet_path_logs_nothing_above_debug() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+    const FIN: u8 = 0x01;
+    const RST: u8 = 0x04;
+    let frames = [
+        tcp_frame(true, SYN, b""),
+        tcp_frame(false, SYN | ACK, b""),
+        tcp_frame(true, ACK, b""),
+        tcp_frame(true, ACK, b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+        tcp_frame(false, ACK, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        tcp_frame(true, FIN | ACK, b""),
+        tcp_frame(false, RST, b""),
+    ];
+
+    let parser = PacketParser::new().with_linktype(1);
+    let mut tcp_conn = None;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut parsed = parser
+            .parse_packet(frame, frame.len(), SystemTime::now(), Instant::now())
+            .unwrap();
+        // Conflicting process metadata used to be logged as a warning per packet
+        parsed.process_name = Some(format!("proc-{}", i));
+        parsed.process_id = Some(1000 + i as u32);
+        tcp_conn = Some(match tcp_conn {
+            None => create_connection_from_packet(&parsed, &MergeOptions::default()),
+            Some(conn) => merge_packet_into_connection(conn, &parsed, &MergeOptions::default()),
+        });
+    }
+
+    let dns_frame = dns_query_frame();
+    let parsed = parser
+        .parse_packet(
+            &dns_frame,
+            dns_frame.len(),
+            SystemTime::now(),
+            Instant::now(),
+        )
+        .unwrap();
+    let dns_conn = create_connection_from_packet(&parsed, &MergeOptions::default());
+    merge_packet_into_connection(dns_conn, &parsed, &MergeOptions::default());
+
+    let records = LOGGER.records.lock().unwrap();
+    let noisy: Vec<_> = records
+        .iter()
+        .filter(|(level, _)| *level < Level::Debug)
+        .collect();
+    assert!(
+        noisy.is_empty(),
+        "packet path logged above debug: {:?}",
+        noisy
+    );
+    assert!(
+        records.iter().all(
+            |(_, target)| ["rustnet::capture", "rustnet::merge", "rustnet::dpi"]
+                .contains(&target.as_str())
+        ),
+        "packet path logged outside its targets: {:?}",
+        records
+    );
+    assert!(records.iter().any(|(_, target)| target == "rustnet::merge"));
+}
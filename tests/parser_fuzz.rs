@@ -0,0 +1,318 @@
+//! Malformed packets must never panic the capture path. Valid frames of each
+//! kind are mutated at random and fed through parsing, DPI and merging, for a
+//! bounded number of rounds so the test can run in CI. Set
+//! `RUSTNET_FUZZ_ITERATIONS` for a longer run.
+
+use rustnet_monitor::network::dpi::analyze_payload;
+use rustnet_monitor::network::merge::{
+    MergeOptions, create_connection_from_packet, merge_packet_into_connection,
+};
+use rustnet_monitor::network::parser::PacketParser;
+use rustnet_monitor::network::types::{Connection, Protocol};
+use rustnet_monitor::signatures::SignatureRegistry;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
+
+const CLIENT: [u8; 4] = [192, 0, 2, 10];
+const SERVER: [u8; 4] = [198, 51, 100, 7];
+const CLIENT6: [u8; 16] = [
+    0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x0a,
+];
+const SERVER6: [u8; 16] = [
+    0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x07,
+];
+
+fn iterations() -> usize {
+    std::env::var("RUSTNET_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(20_000)
+}
+
+/// xorshift so the test is deterministic without extra dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; 12];
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn ipv4(protocol: u8, transport: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x45, 0];
+    packet.extend_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+    packet.extend_from_slice(&CLIENT);
+    packet.extend_from_slice(&SERVER);
+    packet.extend_from_slice(transport);
+    packet
+}
+
+/// IPv6 packet with a hop-by-hop options header in front of the transport
+fn ipv6(protocol: u8, transport: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x60, 0, 0, 0];
+    packet.extend_from_slice(&((8 + transport.len()) as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 64]);
+    packet.extend_from_slice(&CLIENT6);
+    packet.extend_from_slice(&SERVER6);
+    packet.extend_from_slice(&[protocol, 0, 1, 4, 0, 0, 0, 0]);
+    packet.extend_from_slice(transport);
+    packet
+}
+
+fn tcp(dport: u16, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend_from_slice(&50000u16.to_be_bytes());
+    segment.extend_from_slice(&dport.to_be_bytes());
+    segment.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0]);
+    segment.extend_from_slice(&[0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+    segment.extend_from_slice(payload);
+    segment
+}
+
+fn udp(dport: u16, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    datagram.extend_from_slice(&50000u16.to_be_bytes());
+    datagram.extend_from_slice(&dport.to_be_bytes());
+    datagram.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+fn tls_client_hello(sni: &str) -> Vec<u8> {
+    let mut sni_ext = Vec::new();
+    sni_ext.extend_from_slice(&((sni.len() + 3) as u16).to_be_bytes());
+    sni_ext.push(0);
+    sni_ext.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+    sni_ext.extend_from_slice(sni.as_bytes());
+    let mut extensions = vec![0, 0];
+    extensions.extend_from_slice(&(sni_ext.len() as u16).to_be_bytes());
+    extensions.extend_from_slice(&sni_ext);
+    extensions.extend_from_slice(&[0, 0x10, 0, 5, 0, 3, 2, b'h', b'2']);
+
+    let mut hello = vec![0x03, 0x03];
+    hello.extend_from_slice(&[0x11; 32]);
+    hello.extend_from_slice(&[0, 0, 2, 0x13, 0x01, 1, 0]);
+    hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01, 0];
+    handshake.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+    handshake.extend_from_slice(&hello);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+fn dns_query() -> Vec<u8> {
+    let mut dns = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in ["example", "com"] {
+        dns.push(label.len() as u8);
+        dns.extend_from_slice(label.as_bytes());
+    }
+    dns.extend_from_slice(&[0, 0, 1, 0, 1]);
+    dns
+}
+
+/// Long-header QUIC Initial: version 1, 8-byte DCID, no token
+fn quic_initial(rng: &mut Rng) -> Vec<u8> {
+    let mut packet = vec![0xc3, 0, 0, 0, 1, 8];
+    packet.extend_from_slice(&rng.bytes(8));
+    packet.extend_from_slice(&[0, 0, 0x44, 0x00]);
+    packet.extend_from_slice(&rng.bytes(1024));
+    packet
+}
+
+fn arp_request() -> Vec<u8> {
+    let mut arp = vec![0, 1, 0x08, 0, 6, 4, 0, 1];
+    arp.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+    arp.extend_from_slice(&CLIENT);
+    arp.extend_from_slice(&[0; 6]);
+    arp.extend_from_slice(&SERVER);
+    arp
+}
+
+/// Well-formed Ethernet frames covering every parser and DPI path
+fn seed_frames(rng: &mut Rng) -> Vec<Vec<u8>> {
+    let http = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    vec![
+        ethernet(0x0800, &ipv4(6, &tcp(80, http))),
+        ethernet(
+            0x0800,
+            &ipv4(6, &tcp(443, &tls_client_hello("example.com"))),
+        ),
+        ethernet(0x0800, &ipv4(6, &tcp(22, b"SSH-2.0-OpenSSH_9.6\r\n"))),
+        ethernet(0x0800, &ipv4(17, &udp(53, &dns_query()))),
+        ethernet(0x0800, &ipv4(17, &udp(443, &quic_initial(rng)))),
+        ethernet(0x0800, &ipv4(17, &udp(9999, b""))),
+        ethernet(0x0800, &ipv4(1, &[8, 0, 0, 0, 0, 1, 0, 1])),
+        ethernet(
+            0x86dd,
+            &ipv6(6, &tcp(443, &tls_client_hello("example.org"))),
+        ),
+        ethernet(0x86dd, &ipv6(17, &udp(53, &dns_query()))),
+        ethernet(0x86dd, &ipv6(58, &[128, 0, 0, 0, 0, 1, 0, 1])),
+        ethernet(0x0806, &arp_request()),
+    ]
+}
+
+/// The same frame as captured on a Linux "any" device
+fn cooked(frame: &[u8], linktype: i32) -> Vec<u8> {
+    let ethertype = [frame[12], frame[13]];
+    let mut header = match linktype {
+        113 => {
+            let mut header = vec![0u8; 14];
+            header.extend_from_slice(&ethertype);
+            header
+        }
+        276 => {
+            let mut header = ethertype.to_vec();
+            header.extend_from_slice(&[0u8; 18]);
+            header
+        }
+        _ => return frame.to_vec(),
+    };
+    header.extend_from_slice(&frame[14..]);
+    header
+}
+
+fn mutate(rng: &mut Rng, frame: &mut Vec<u8>) {
+    for _ in 0..1 + rng.below(4) {
+        if frame.is_empty() {
+            let len = 1 + rng.below(64);
+            frame.extend(rng.bytes(len));
+            continue;
+        }
+        let at = rng.below(frame.len());
+        match rng.below(6) {
+            0 => frame[at] = rng.next() as u8,
+            // Header lengths and length fields are the interesting bytes
+            1 => frame[at] = [0x00, 0x01, 0x05, 0x0f, 0x40, 0x7f, 0x80, 0xff][rng.below(8)],
+            2 => frame.truncate(at),
+            3 => {
+                let len = 1 + rng.below(32);
+                let tail = frame.split_off(at);
+                frame.extend(rng.bytes(len));
+                frame.extend(tail);
+            }
+            4 => {
+                let end = (at + 1 + rng.below(16)).min(frame.len());
+                frame.drain(at..end);
+            }
+            _ => frame.extend(std::iter::repeat_n(0u8, rng.below(64))),
+        }
+    }
+}
+
+/// Parse `frame`, then run its connection through merging the way the
+/// capture loop does
+fn feed(parser: &PacketParser, connections: &mut HashMap<String, Connection>, frame: &[u8]) {
+    let Some(parsed) = parser.parse_packet(frame, frame.len(), SystemTime::now(), Instant::now())
+    else {
+        return;
+    };
+    let conn = match connections.remove(&parsed.connection_key) {
+        Some(conn) => merge_packet_into_connection(conn, &parsed, &MergeOptions::default()),
+        None => create_connection_from_packet(&parsed, &MergeOptions::default()),
+    };
+    connections.insert(parsed.connection_key.clone(), conn);
+}
+
+#[test]
+fn test_mutated_frames_never_panic() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let seeds = seed_frames(&mut rng);
+
+    for linktype in [1, 113, 276] {
+        let parser = PacketParser::new().with_linktype(linktype);
+        // Cooked captures only carry IP
+        let seeds: Vec<Vec<u8>> = seeds
+            .iter()
+            .filter(|frame| linktype == 1 || frame[12..14] != [0x08, 0x06])
+            .map(|frame| cooked(frame, linktype))
+            .collect();
+        let mut connections = HashMap::new();
+
+        // Every seed parses as it is
+        for (i, seed) in seeds.iter().enumerate() {
+            assert!(
+                parser
+                    .parse_packet(seed, seed.len(), SystemTime::now(), Instant::now())
+                    .is_some(),
+                "seed {} for linktype {} doesn't parse",
+                i,
+                linktype
+            );
+        }
+
+        for round in 0..iterations() {
+            let mut frame = if round % 10 == 0 {
+                let len = rng.below(256);
+                rng.bytes(len)
+            } else {
+                seeds[rng.below(seeds.len())].clone()
+            };
+            mutate(&mut rng, &mut frame);
+            feed(&parser, &mut connections, &frame);
+
+            // Keep the table small enough that merging stays the hot path
+            if connections.len() > 256 {
+                connections.clear();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_arbitrary_payloads_never_panic_dpi() {
+    let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+    let signatures = SignatureRegistry::new();
+    let prefixes: [&[u8]; 5] = [
+        b"",
+        b"GET ",
+        &[0x16, 0x03, 0x01],
+        b"SSH-2.0-",
+        &[0xc3, 0, 0, 0, 1],
+    ];
+
+    for _ in 0..iterations() {
+        let mut payload = prefixes[rng.below(prefixes.len())].to_vec();
+        let len = rng.below(512);
+        payload.extend(rng.bytes(len));
+        let protocol = if rng.below(2) == 0 {
+            Protocol::TCP
+        } else {
+            Protocol::UDP
+        };
+        let port = [22, 53, 80, 443, 5353, 50000][rng.below(6)];
+        analyze_payload(
+            protocol,
+            &payload,
+            50000,
+            port,
+            rng.below(2) == 0,
+            &signatures,
+        );
+    }
+}